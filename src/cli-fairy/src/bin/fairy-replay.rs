@@ -0,0 +1,49 @@
+use clap::Parser;
+use cli_fairy::replay::{replay_workload, Pacing, ReplayOptions};
+use common::physical::config::ClientConfig;
+use common::workload::read_workload;
+use std::path::PathBuf;
+
+/// Replays a workload captured with the server's `--capture_file` option.
+#[derive(Parser, Debug)]
+struct ReplayArgs {
+    /// Server IP address
+    #[clap(short = 'o', long = "host", default_value = "127.0.0.1")]
+    host: String,
+    /// Server port number
+    #[clap(short = 'p', long = "port", default_value = "3333")]
+    port: String,
+    /// Capture file to replay
+    #[clap(short = 'w', long = "workload")]
+    workload: PathBuf,
+    /// Keep the original spacing between statements instead of replaying as fast as possible
+    #[clap(long = "original-pacing")]
+    original_pacing: bool,
+    /// Skip statements that change the server or database state
+    #[clap(long = "skip-mutations")]
+    skip_mutations: bool,
+}
+
+fn main() {
+    let args = ReplayArgs::parse();
+    let workload = read_workload(&args.workload).unwrap();
+    let config = ClientConfig {
+        host: args.host,
+        port: args.port,
+        script: String::new(),
+        ..Default::default()
+    };
+    let options = ReplayOptions {
+        pacing: if args.original_pacing {
+            Pacing::Original
+        } else {
+            Pacing::AsFastAsPossible
+        },
+        include_mutations: !args.skip_mutations,
+    };
+    let report = replay_workload(&config, &workload, &options).unwrap();
+    println!("{}", report);
+    if !report.mismatches().is_empty() {
+        std::process::exit(1);
+    }
+}
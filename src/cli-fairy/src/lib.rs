@@ -7,17 +7,48 @@ use rustyline::history::FileHistory;
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Editor};
 
-use std::io::{Read, Write};
+use std::cell::RefCell;
+use std::io::{IsTerminal, Read, Write};
 use std::net::TcpStream;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub mod render;
+pub mod replay;
+pub mod script;
+pub mod wait;
 
 pub use common::commands::Response;
-use common::commands::{self, CommandWithArgs};
+use common::commands::{self, Command, CommandWithArgs, ErrorCode, SystemCommand};
 use common::physical::config::ClientConfig;
+pub use common::query::query_result::Notice;
+use render::{
+    parse_pset, render_error_at, render_notices, ChunkPrinter, OutputFormat, PrintSettings,
+};
+use script::{
+    response_error, retain_rows, split_statements, ScriptMode, ScriptOptions, ScriptOutcome,
+    ScriptSummary, StatementError,
+};
+use wait::{FrameReader, Wait, POLL_INTERVAL};
 
 #[allow(dead_code)]
 pub struct Client {
     config: ClientConfig,
     stream: TcpStream,
+    /// Database the connection is using, as far as the client has seen.
+    database: Option<String>,
+    /// Output options set with `\pset`, shared with the printer of scripts.
+    print: Rc<RefCell<PrintSettings>>,
+    /// Whether the user waits at the prompt, so that waits show a spinner and
+    /// Ctrl-C gives up on them.
+    interactive: bool,
+    /// Set on Ctrl-C.
+    interrupt: Arc<AtomicBool>,
+    /// The response being read.
+    frame: FrameReader,
+    /// Responses the server still owes, including those given up on.
+    pending: usize,
 }
 
 pub fn connect_to_kill_server(config: &ClientConfig) -> Result<(), FairyError> {
@@ -30,12 +61,68 @@ pub fn connect_to_kill_server(config: &ClientConfig) -> Result<(), FairyError> {
 
 impl Client {
     pub fn new(config: ClientConfig) -> Self {
+        Self::connect(config).unwrap()
+    }
+
+    /// Connects to the server, returning an error instead of panicking if it is unreachable.
+    pub fn connect(config: ClientConfig) -> Result<Self, FairyError> {
         let _ = env_logger::builder().try_init();
         let mut bind_addr = config.host.clone();
         bind_addr.push(':');
         bind_addr.push_str(&config.port);
-        let stream = TcpStream::connect(bind_addr).unwrap();
-        Client { config, stream }
+        let stream = TcpStream::connect(bind_addr)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let database = config.database.clone();
+        let mut client = Client {
+            config,
+            stream,
+            database: None,
+            print: Rc::default(),
+            interactive: false,
+            interrupt: Arc::default(),
+            frame: FrameReader::default(),
+            pending: 0,
+        };
+        if !database.is_empty() {
+            client.use_database(&database)?;
+        }
+        Ok(client)
+    }
+
+    /// Switches the connection to the given database. The connection keeps
+    /// using the current database if this fails.
+    pub fn use_database(&mut self, name: &str) -> Result<(), FairyError> {
+        let response = self.handle_command(format!("\\c {}", name), false)?;
+        match response_error(&response) {
+            Some(message) => Err(c_err(&format!(
+                "Could not connect to database {}: {}",
+                name, message
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// The database the connection is using, if any.
+    pub fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+
+    /// The output options set with `\pset`.
+    pub fn print_settings(&self) -> PrintSettings {
+        self.print.borrow().clone()
+    }
+
+    /// The CLI prompt, which names the current database.
+    pub fn prompt(&self) -> String {
+        match &self.database {
+            Some(name) => format!("[fairydb:{}]>>", name),
+            None => "[fairydb]>>".to_string(),
+        }
+    }
+
+    /// Sends a single command (SQL or a `\` command) and waits for the response.
+    pub fn execute(&mut self, command: &str) -> Result<Response, FairyError> {
+        self.handle_command(command.to_string(), false)
     }
 
     pub fn run_cli(&mut self) {
@@ -49,9 +136,37 @@ impl Client {
         } else {
             info!("Running script: {}", self.config.script);
             let file = std::fs::File::open(&self.config.script).unwrap();
-            let _ = self.send_requests_from_buffer(file);
+            let mode = if self.config.continue_on_error {
+                ScriptMode::ContinueOnError
+            } else {
+                ScriptMode::StopOnError
+            };
+            // Responses are printed as they arrive so that large results are
+            // not held for the whole script.
+            let print = Rc::clone(&self.print);
+            let res = self.run_script_with(file, mode, |_, outcome| match outcome {
+                Ok(Response::QueryResult(result @ QueryResult::Select { .. })) => {
+                    print_select(&print.borrow(), &result)
+                }
+                Ok(response) => info!("Received response: {:?}", response),
+                Err(error) => error!("Script failed at {}", error),
+            });
+            match res {
+                Ok(summary) => info!(
+                    "Script done: {} succeeded, {} failed, {} skipped, {} rows",
+                    summary.succeeded, summary.failed, summary.skipped, summary.rows
+                ),
+                Err(e) => error!("Error running script: {}", e),
+            }
         }
 
+        // The prompt reads Ctrl-C as a key, the handler only sees it while a
+        // statement runs.
+        let interrupt = Arc::clone(&self.interrupt);
+        if let Err(e) = ctrlc::set_handler(move || interrupt.store(true, Ordering::SeqCst)) {
+            error!("Failed to handle Ctrl-C: {}", e);
+        }
+        self.interactive = true;
         self.process_cli_loop(&mut rl);
 
         if rl.save_history("history.txt").is_err() {
@@ -60,14 +175,12 @@ impl Client {
     }
 
     fn process_cli_loop(&mut self, rl: &mut Editor<(), FileHistory>) {
-        let prompt: &str = "[fairydb]>>";
-
-        while let Some(line) = self.read_cli_line(rl, prompt) {
+        while let Some(line) = self.read_cli_line(rl, &self.prompt()) {
             if !line.is_empty() {
                 let _ = rl.add_history_entry(&line);
-                match self.handle_command(line) {
+                match self.handle_command(line.clone(), true) {
                     Ok(response) => {
-                        if !self.handle_response(response) {
+                        if !self.handle_response(&line, response) {
                             info!("Server shutdown");
                             break;
                         }
@@ -100,32 +213,101 @@ impl Client {
         }
     }
 
-    pub fn send_requests_from_buffer<T: Read>(
+    /// Runs the statements of a script one at a time and hands the outcome of
+    /// each to `on_outcome` together with the statement's ordinal, without
+    /// retaining it. A statement fails if it cannot be sent or the server answers
+    /// with an error. Failures are reported with the position of the statement;
+    /// whether the rest of the script still runs depends on `mode`. Only failing
+    /// to read the script is an error.
+    pub fn run_script_with<T, F>(
         &mut self,
         mut buffer: T,
-    ) -> Result<Vec<Response>, FairyError> {
+        mode: ScriptMode,
+        mut on_outcome: F,
+    ) -> Result<ScriptSummary, FairyError>
+    where
+        T: Read,
+        F: FnMut(usize, Result<Response, StatementError>),
+    {
         let mut content = String::new();
-        match buffer.read_to_string(&mut content) {
-            Ok(_) => {
-                let commands = self.split_into_commands(&content);
-                let mut responses = Vec::new();
-                for command in commands {
-                    responses.push(self.handle_command(command)?);
-                }
-                Ok(responses)
+        if let Err(e) = buffer.read_to_string(&mut content) {
+            error!("Failed to read buffer: {:?}", e);
+            return Err(c_err("Failed to read buffer"));
+        }
+        let statements = split_statements(&content);
+        let mut summary = ScriptSummary {
+            statements: statements.len(),
+            ..Default::default()
+        };
+        for statement in statements {
+            let (ordinal, line) = (statement.ordinal, statement.line);
+            let outcome = match self.handle_command(statement.text.clone(), false) {
+                // The error is shown under the statement so that it is clear
+                // which part of a long script it is about.
+                Ok(Response::QueryErrorAt {
+                    message, position, ..
+                }) => Err(render_error_at(&statement.text, &message, &position, false)),
+                Ok(response) => match response_error(&response) {
+                    Some(message) => Err(message),
+                    None => Ok(response),
+                },
+                Err(e) => Err(e.to_string()),
             }
-            Err(e) => {
-                error!("Failed to read buffer: {:?}", e);
-                Err(c_err("Failed to read buffer"))
+            .map_err(|message| StatementError {
+                ordinal,
+                line,
+                message,
+            });
+            summary.record(&outcome);
+            let failed = outcome.is_err();
+            on_outcome(ordinal, outcome);
+            if failed && mode == ScriptMode::StopOnError {
+                break;
             }
         }
+        summary.skipped = summary.statements - summary.succeeded - summary.failed;
+        Ok(summary)
     }
 
-    fn handle_command(&mut self, command: String) -> Result<Response, FairyError> {
+    /// Runs a script like `run_script_with` and retains the outcome of every
+    /// statement, keeping at most `options.max_retained_rows` result rows.
+    pub fn send_requests_from_buffer<T: Read>(
+        &mut self,
+        buffer: T,
+        options: ScriptOptions,
+    ) -> Result<ScriptOutcome, FairyError> {
+        let mut budget = options.max_retained_rows.unwrap_or(usize::MAX);
+        let mut outcomes = Vec::new();
+        let mut summary = self.run_script_with(buffer, options.mode, |_, outcome| {
+            outcomes.push(outcome.map(|response| retain_rows(response, &mut budget)));
+        })?;
+        summary.truncated = outcomes
+            .iter()
+            .filter(|o| o.as_ref().is_ok_and(|r| r.truncated))
+            .count();
+        Ok(ScriptOutcome { outcomes, summary })
+    }
+
+    /// Runs a command. With `print_chunks`, the rows of a result the server
+    /// sends in chunks are printed as they arrive, see `receive_response`.
+    fn handle_command(
+        &mut self,
+        command: String,
+        print_chunks: bool,
+    ) -> Result<Response, FairyError> {
+        // \pset only changes how this client prints, so it is not sent.
+        if let Some((option, value)) = parse_pset(&command) {
+            return Ok(match self.print.borrow_mut().set(option, value) {
+                Ok(message) => Response::SystemMsg(message),
+                Err(e) => Response::SystemErr(ErrorCode::System, e.to_string()),
+            });
+        }
         match commands::parse_command(command.clone()) {
             Some(request) => {
                 debug!("Request to send {:?}", request);
-                self.send_and_wait(&request)
+                let response = self.send_and_wait(&request, print_chunks)?;
+                self.track_database(&request, &response);
+                Ok(response)
             }
             None => {
                 info!("Invalid request: {}", command);
@@ -134,48 +316,41 @@ impl Client {
         }
     }
 
-    fn split_into_commands(&self, buffer_content: &str) -> Vec<String> {
-        buffer_content
-            .split(';')
-            .map(str::trim)
-            .filter(|cmd| !cmd.is_empty())
-            .map(|cmd| cmd.replace('\n', " "))
-            .collect()
+    /// Updates the current database after a request that changes it succeeded.
+    fn track_database(&mut self, request: &CommandWithArgs, response: &Response) {
+        if response_error(response).is_some() {
+            return;
+        }
+        match request.command {
+            Command::System(SystemCommand::Connect) => {
+                self.database = request.args.first().cloned();
+            }
+            Command::System(SystemCommand::CloseConnection | SystemCommand::Reset) => {
+                self.database = None;
+            }
+            _ => {}
+        }
     }
 
     /// Sends a request to the server and waits for a response.
-    fn send_and_wait(&mut self, request: &CommandWithArgs) -> Result<Response, FairyError> {
+    fn send_and_wait(
+        &mut self,
+        request: &CommandWithArgs,
+        print_chunks: bool,
+    ) -> Result<Response, FairyError> {
+        self.interrupt.store(false, Ordering::SeqCst);
         if !self.send_request(request) {
             return Err(c_err("Failed to send request"));
         }
 
-        let response_data = self.receive_response()?;
-
-        if response_data.is_empty() {
-            info!("Received empty response. Check server logs.");
-            return Ok(Response::QuietOk);
-        }
-
-        let response: Response = match serde_cbor::from_slice(&response_data) {
-            Ok(resp) => {
-                match &resp {
-                    // pretty print table results
-                    Response::QueryResult(query_result) => {
-                        info!("Received response:\n{}", query_result);
-                        resp
-                    }
-                    _ => {
-                        info!("Received response: {:?}", &resp);
-                        resp
-                    }
-                }
-            }
-            Err(e) => {
-                error!("Failed to deserialize response: {:?}", e);
-                return Err(c_err("Failed to deserialize response"));
+        let response = self.receive_response(print_chunks)?;
+        match &response {
+            // pretty print table results
+            Response::QueryResult(query_result) => {
+                info!("Received response:\n{}", query_result)
             }
-        };
-
+            _ => info!("Received response: {:?}", &response),
+        }
         Ok(response)
     }
 
@@ -188,7 +363,13 @@ impl Client {
             }
         };
 
-        if let Err(e) = self.stream.write_all(&serialized_request) {
+        // The server reads the length of the request first.
+        let length = (serialized_request.len() as u64).to_be_bytes();
+        let sent = self
+            .stream
+            .write_all(&length)
+            .and_then(|_| self.stream.write_all(&serialized_request));
+        if let Err(e) = sent {
             error!("Error sending data: {:?}", e);
             false
         } else {
@@ -196,35 +377,69 @@ impl Client {
         }
     }
 
-    fn receive_response(&mut self) -> Result<Vec<u8>, FairyError> {
-        // Read the length of the response first
-        // TODO xtx magic number again
-        let mut length_bytes = [0u8; 8];
-        self.stream.read_exact(&mut length_bytes)?;
-        let response_length = u64::from_be_bytes(length_bytes) as usize;
-
-        // Now read the response data
-        let mut response_data = Vec::with_capacity(response_length);
-        while response_data.len() < response_length {
-            // TODO xtx magic number need to make a config file
-            let mut buffer =
-                vec![0; std::cmp::min(1024 * 32, response_length - response_data.len())];
-            let size = self.stream.read(&mut buffer)?;
-            if size == 0 {
-                break; // End of stream
+    /// Waits for the response to the request just sent. Interactive waits can
+    /// be given up on with Ctrl-C and all of them time out after
+    /// `response_timeout_ms`. The statements keep running unless cancelled
+    /// from another client, so the responses given up on are skipped once they
+    /// arrive.
+    ///
+    /// A select result the server sends in chunks is collected into one
+    /// result, or with `print_chunks` printed chunk by chunk as it arrives and
+    /// answered with `ResultEnd`.
+    fn receive_response(&mut self, print_chunks: bool) -> Result<Response, FairyError> {
+        let mut wait = Wait::new(
+            self.config.response_timeout_ms,
+            &self.interrupt,
+            self.interactive,
+        );
+        // The server answers in order, so the responses given up on come first.
+        self.pending += 1;
+        // The result being sent in chunks, unless it is one given up on.
+        let mut chunks = None;
+        loop {
+            let frame = self.frame.read(&mut self.stream, &mut wait)?;
+            let response = match decode_response(&frame)? {
+                Response::ResultHeader(header) => {
+                    if self.pending == 1 {
+                        chunks = Some(if print_chunks {
+                            Chunks::Print(ChunkPrinter::new(&self.print.borrow(), header))
+                        } else {
+                            Chunks::Collect(header)
+                        });
+                    }
+                    continue;
+                }
+                Response::ResultRows(rows) => {
+                    match &mut chunks {
+                        Some(Chunks::Print(printer)) => {
+                            print!("{}", printer.render(rows));
+                            let _ = std::io::stdout().flush();
+                        }
+                        Some(Chunks::Collect(result)) => result.extend_rows(rows),
+                        None => {}
+                    }
+                    continue;
+                }
+                Response::ResultEnd => match chunks.take() {
+                    Some(Chunks::Print(mut printer)) => {
+                        print!("{}", printer.finish());
+                        print_notices(&self.print.borrow(), printer.header().notices());
+                        Response::ResultEnd
+                    }
+                    Some(Chunks::Collect(result)) => Response::QueryResult(result),
+                    None => Response::ResultEnd,
+                },
+                response => response,
+            };
+            self.pending -= 1;
+            if self.pending == 0 {
+                return Ok(response);
             }
-            response_data.extend_from_slice(&buffer[..size]);
-        }
-
-        if response_data.len() != response_length {
-            return Err(c_err("Incomplete response received"));
         }
-
-        Ok(response_data)
     }
 
     // Returns true if the server should continue running.
-    fn handle_response(&self, response: Response) -> bool {
+    fn handle_response(&self, line: &str, response: Response) -> bool {
         match response {
             Response::Shutdown(from_client) => {
                 if from_client {
@@ -248,25 +463,50 @@ impl Client {
                 info!("Received OK");
                 true
             }
+            Response::Ack => {
+                debug!("Received Ack");
+                true
+            }
             Response::SystemMsg(msg) => {
                 info!("Received SystemMsg: {}", msg);
                 true
             }
-            Response::SystemErr(msg) => {
-                error!("Received SystemErr: {}", msg);
+            Response::SystemErr(code, msg) => {
+                error!("Received SystemErr ({:?}): {}", code, msg);
                 true
             }
             Response::QueryResult(result) => self.process_query_result(result),
-            Response::QueryExecutionError(msg) => {
-                error!("Received QueryExecutionError: {}", msg);
+            Response::QueryExecutionError(code, msg) => {
+                error!("Received QueryExecutionError ({:?}): {}", code, msg);
+                true
+            }
+            Response::QueryErrorAt {
+                code,
+                message,
+                position,
+            } => {
+                debug!("Received QueryErrorAt ({:?}): {}", code, message);
+                let sql = commands::parse_command(line.to_string())
+                    .map(|request| request.args.join(" "))
+                    .unwrap_or_default();
+                let color = std::io::stderr().is_terminal();
+                eprintln!("{}", render_error_at(&sql, &message, &position, color));
                 true
             }
             Response::QuietOk => {
                 debug!("Received quiet OK");
                 true
             }
-            Response::QuietErr => {
-                debug!("Received quiet Err");
+            Response::QuietErr { code, message } => {
+                debug!("Received quiet Err ({:?}): {}", code, message);
+                true
+            }
+            Response::ResultEnd => {
+                debug!("Printed a result sent in chunks");
+                true
+            }
+            Response::ResultHeader(_) | Response::ResultRows(_) => {
+                error!("Received a chunk outside of a result");
                 true
             }
         }
@@ -277,18 +517,269 @@ impl Client {
             QueryResult::MessageOnly(message) => {
                 info!("Received Query Result: {}", message);
             }
-            QueryResult::Select { .. } => {
-                // pretty‐print the table using the Display impl
-                println!("{}", result);
-            }
+            QueryResult::Select { .. } => print_select(&self.print.borrow(), &result),
             QueryResult::Insert {
                 inserted,
                 table_name,
+                notice,
             } => {
                 let message = format!("Inserted {} rows to table: {}", inserted, table_name);
                 info!("Received Query Result: {}", message);
+                if let Some(notice) = notice {
+                    info!("{}", notice);
+                }
             }
         }
         true
     }
 }
+
+/// Prints the rows of a select result followed by its notices. The notices of
+/// CSV and JSON output go to stderr, so that the rows can be piped on as they
+/// are.
+fn print_select(print: &PrintSettings, result: &QueryResult) {
+    println!("{}", print.render(result));
+    print_notices(print, result.notices());
+}
+
+/// Prints the notices of a select result after its rows.
+fn print_notices(print: &PrintSettings, notices: &[Notice]) {
+    if print.format == OutputFormat::Table {
+        if let Some(notes) = render_notices(notices, std::io::stdout().is_terminal()) {
+            println!("{}", notes);
+        }
+    } else if let Some(notes) = render_notices(notices, std::io::stderr().is_terminal()) {
+        eprintln!("{}", notes);
+    }
+}
+
+/// Decodes a frame of a response.
+fn decode_response(frame: &[u8]) -> Result<Response, FairyError> {
+    // The server always sends a serialized response, so an empty frame means
+    // it failed after writing the length prefix.
+    if frame.is_empty() {
+        error!("Received empty response frame. Check server logs.");
+        return Err(FairyError::IOError(
+            "Protocol error: received empty response frame".to_string(),
+        ));
+    }
+    serde_cbor::from_slice(frame).map_err(|e| {
+        error!("Failed to deserialize response: {:?}", e);
+        c_err("Failed to deserialize response")
+    })
+}
+
+/// What the client does with the rows of a result the server sends in chunks.
+enum Chunks {
+    /// Collects them into the result, which holds the rows received so far.
+    Collect(QueryResult),
+    Print(ChunkPrinter),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::commands::ErrorCode;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Wraps a payload in a length-prefixed frame as sent by the server.
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = (payload.len() as u64).to_be_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Reads a length-prefixed request as sent by the client.
+    fn read_request(stream: &mut TcpStream) {
+        let mut length = [0u8; 8];
+        stream.read_exact(&mut length).unwrap();
+        let mut request = vec![0; u64::from_be_bytes(length) as usize];
+        stream.read_exact(&mut request).unwrap();
+    }
+
+    /// Starts a fake server that answers a single request with the given frame.
+    fn fake_server(frame: Vec<u8>) -> (ClientConfig, thread::JoinHandle<()>) {
+        fake_server_with(move |mut stream| {
+            read_request(&mut stream);
+            stream.write_all(&frame).unwrap();
+        })
+    }
+
+    /// Starts a fake server that serves a single connection with `serve`.
+    fn fake_server_with<F>(serve: F) -> (ClientConfig, thread::JoinHandle<()>)
+    where
+        F: FnOnce(TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || serve(listener.accept().unwrap().0));
+        let config = ClientConfig {
+            host: "127.0.0.1".to_string(),
+            port: port.to_string(),
+            script: String::new(),
+            ..Default::default()
+        };
+        (config, handle)
+    }
+
+    #[test]
+    fn test_empty_frame_is_protocol_error() {
+        let (config, handle) = fake_server(frame(&[]));
+        let mut client = Client::new(config);
+        let res = client.handle_command("\\dt".to_string(), false);
+        assert!(matches!(res, Err(FairyError::IOError(msg)) if msg.contains("Protocol error")));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_quiet_err_carries_context() {
+        let response = Response::QuietErr {
+            code: ErrorCode::QueryExecution,
+            message: "Table foo does not exist".to_string(),
+        };
+        let (config, handle) = fake_server(frame(&serde_cbor::to_vec(&response).unwrap()));
+        let mut client = Client::new(config);
+        let res = client.handle_command("SELECT * FROM foo".to_string(), false);
+        assert_eq!(res.unwrap(), response);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_notices_survive_the_frame() {
+        let notices = [Notice::CacheHit { hash: u64::MAX }, Notice::StaleStats];
+        let result =
+            QueryResult::new_select_result(&Default::default(), vec![], None).with_notices(notices);
+        let response = Response::QueryResult(result);
+        let (config, handle) = fake_server(frame(&serde_cbor::to_vec(&response).unwrap()));
+        let mut client = Client::new(config);
+        let res = client.execute("SELECT * FROM foo").unwrap();
+        assert_eq!(res.notices(), notices);
+        handle.join().unwrap();
+    }
+
+    /// A fake server whose answer to the first request only comes after the
+    /// client sent a second one, followed by the answer to the second.
+    fn late_server<F>(after_first: F) -> (ClientConfig, thread::JoinHandle<()>)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        fake_server_with(move |mut stream| {
+            read_request(&mut stream);
+            after_first();
+            read_request(&mut stream);
+            for message in ["first", "second"] {
+                let response = Response::SystemMsg(message.to_string());
+                stream
+                    .write_all(&frame(&serde_cbor::to_vec(&response).unwrap()))
+                    .unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn test_timeout_skips_late_response() {
+        let (config, handle) = late_server(|| {});
+        let mut client = Client::new(ClientConfig {
+            response_timeout_ms: 200,
+            ..config
+        });
+        let err = client.execute("\\dt").unwrap_err();
+        assert!(
+            err.to_string().contains("No response within 200 ms"),
+            "{}",
+            err
+        );
+        let response = client.execute("\\dt").unwrap();
+        assert_eq!(response, Response::SystemMsg("second".to_string()));
+        handle.join().unwrap();
+    }
+
+    /// Frames of a select result of `rows` rows sent in chunks of two.
+    fn chunk_frames(rows: i64) -> Vec<u8> {
+        let schema = common::TableSchema::from_vecs(vec!["a"], vec![common::DataType::BigInt]);
+        let header = QueryResult::new_select_result(&schema, vec![], None)
+            .with_notices([Notice::StaleStats]);
+        let tuples: Vec<common::Tuple> = (0..rows)
+            .map(|i| common::Tuple::new(vec![common::Field::BigInt(i)]))
+            .collect();
+        let mut responses = vec![Response::ResultHeader(header)];
+        responses.extend(tuples.chunks(2).map(|c| Response::ResultRows(c.to_vec())));
+        responses.push(Response::ResultEnd);
+        responses
+            .iter()
+            .flat_map(|r| frame(&serde_cbor::to_vec(r).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_are_collected() {
+        let (config, handle) = fake_server(chunk_frames(5));
+        let mut client = Client::new(config);
+        let response = client.execute("SELECT * FROM foo").unwrap();
+        let rows: Vec<common::Field> = match &response {
+            Response::QueryResult(result) => result
+                .get_tuples()
+                .unwrap()
+                .iter()
+                .map(|t| t.field_vals[0].clone())
+                .collect(),
+            r => panic!("Expected a select result, got {:?}", r),
+        };
+        assert_eq!(rows, (0..5).map(common::Field::BigInt).collect::<Vec<_>>());
+        assert_eq!(response.notices(), [Notice::StaleStats]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_timeout_skips_late_chunks() {
+        let (config, handle) = fake_server_with(|mut stream| {
+            read_request(&mut stream);
+            read_request(&mut stream);
+            let second = Response::SystemMsg("second".to_string());
+            let mut frames = chunk_frames(5);
+            frames.extend(frame(&serde_cbor::to_vec(&second).unwrap()));
+            stream.write_all(&frames).unwrap();
+        });
+        let mut client = Client::new(ClientConfig {
+            response_timeout_ms: 200,
+            ..config
+        });
+        assert!(client.execute("SELECT * FROM foo").is_err());
+        let response = client.execute("\\dt").unwrap();
+        assert_eq!(response, Response::SystemMsg("second".to_string()));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_ctrl_c_gives_up_on_wait() {
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let pressed = Arc::clone(&interrupt);
+        let (config, handle) = late_server(move || pressed.store(true, Ordering::SeqCst));
+        let mut client = Client::new(config);
+        client.interactive = true;
+        client.interrupt = interrupt;
+        let err = client.execute("\\dt").unwrap_err();
+        assert!(err.to_string().contains("Stopped waiting"), "{}", err);
+        let response = client.execute("\\dt").unwrap();
+        assert_eq!(response, Response::SystemMsg("second".to_string()));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_scripts_wait_through_ctrl_c() {
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let pressed = Arc::clone(&interrupt);
+        let response = Response::SystemMsg("done".to_string());
+        let payload = serde_cbor::to_vec(&response).unwrap();
+        let (config, handle) = fake_server_with(move |mut stream| {
+            read_request(&mut stream);
+            pressed.store(true, Ordering::SeqCst);
+            stream.write_all(&frame(&payload)).unwrap();
+        });
+        let mut client = Client::new(config);
+        client.interrupt = interrupt;
+        assert_eq!(client.execute("\\dt").unwrap(), response);
+        handle.join().unwrap();
+    }
+}
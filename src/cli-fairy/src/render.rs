@@ -0,0 +1,383 @@
+use common::datatypes::DisplayFormat;
+use common::error::{c_err, SourcePosition};
+use common::query::query_result::{csv_line, Notice};
+use common::{FairyError, QueryResult, Tuple};
+use std::borrow::Cow;
+
+const RED_BOLD: &str = "\x1b[1;31m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders an error reported at `position` of `sql` the way psql does: the
+/// message, then the offending line of the statement with a caret under the
+/// character the error points at. `color` highlights the message and caret
+/// with ANSI escapes, for terminals.
+pub fn render_error_at(sql: &str, message: &str, position: &SourcePosition, color: bool) -> String {
+    let text = sql.split('\n').nth(position.line - 1).unwrap_or("");
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    let prefix = format!("LINE {}: ", position.line);
+    // Tabs are kept in the padding so that the caret lines up with the text
+    // whatever the tab width of the terminal.
+    let padding: String = " ".repeat(prefix.len())
+        + &text
+            .chars()
+            .take(position.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+    let (start, end) = if color { (RED_BOLD, RESET) } else { ("", "") };
+    format!(
+        "{}{}{}\n{}{}\n{}{}^{}",
+        start, message, end, prefix, text, padding, start, end
+    )
+}
+
+/// Renders the notices of a result as footnotes, one per line, or returns None
+/// if there are none. `color` dims them with ANSI escapes, for terminals.
+pub fn render_notices(notices: &[Notice], color: bool) -> Option<String> {
+    if notices.is_empty() {
+        return None;
+    }
+    let (start, end) = if color { (DIM, RESET) } else { ("", "") };
+    let lines: Vec<String> = notices
+        .iter()
+        .map(|notice| format!("{}* {}{}", start, notice, end))
+        .collect();
+    Some(lines.join("\n"))
+}
+
+/// How the client prints select results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns under a header.
+    #[default]
+    Table,
+    /// A header line and one comma separated line per row.
+    Csv,
+    /// A JSON array holding one object per row.
+    Json,
+}
+
+/// Output options set with `\pset`. An option the client set wins over the
+/// format of the server session; the options it did not set follow the
+/// session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrintSettings {
+    pub null_text: Option<String>,
+    pub date_format: Option<String>,
+    pub decimal_separator: Option<char>,
+    pub format: OutputFormat,
+}
+
+impl PrintSettings {
+    /// The format fields are printed in, given the format of the session.
+    pub fn display_format(&self, session: &DisplayFormat) -> DisplayFormat {
+        let mut format = session.clone();
+        if let Some(null_text) = &self.null_text {
+            format.null_text = null_text.clone().into();
+        }
+        if let Some(date_format) = &self.date_format {
+            format.date_format = date_format.clone().into();
+        }
+        if let Some(separator) = self.decimal_separator {
+            format.decimal_separator = separator;
+        }
+        format
+    }
+
+    /// Renders a query result for printing.
+    pub fn render(&self, result: &QueryResult) -> String {
+        let format = self.display_format(result.get_format());
+        match self.format {
+            OutputFormat::Table => result.render_table(&format),
+            OutputFormat::Csv => result.render_csv(&format).trim_end().to_string(),
+            OutputFormat::Json => result.render_json(&format),
+        }
+    }
+
+    /// Applies `\pset OPTION [VALUE]` and describes the new value. Without a
+    /// value the option follows the session again, or the format goes back to
+    /// a table. A value may be quoted with single quotes, e.g. to set an empty
+    /// NULL text.
+    pub fn set(&mut self, option: &str, value: Option<&str>) -> Result<String, FairyError> {
+        let value = value.map(|v| {
+            v.strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .unwrap_or(v)
+        });
+        match option.to_ascii_lowercase().as_str() {
+            "null" => self.null_text = value.map(str::to_string),
+            "date_format" => {
+                if let Some(value) = value {
+                    DisplayFormat::validate_date_format(value)?;
+                }
+                self.date_format = value.map(str::to_string);
+            }
+            "decimal_separator" => {
+                self.decimal_separator = match value {
+                    None => None,
+                    Some(v) if v.chars().count() == 1 => v.chars().next(),
+                    Some(_) => return Err(c_err("decimal_separator must be a single character")),
+                }
+            }
+            "format" => {
+                self.format = match value.map(str::to_ascii_lowercase).as_deref() {
+                    None | Some("table") => OutputFormat::Table,
+                    Some("csv") => OutputFormat::Csv,
+                    Some("json") => OutputFormat::Json,
+                    Some(other) => {
+                        return Err(c_err(&format!(
+                            "Invalid output format {}, expected table, csv or json",
+                            other
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(c_err(&format!(
+                "Unknown \\pset option {}, expected null, date_format, decimal_separator or format",
+                option
+            )))
+            }
+        }
+        Ok(match value {
+            Some(value) => format!("{} is \"{}\"", option, value),
+            None if option.eq_ignore_ascii_case("format") => "format is table".to_string(),
+            None => format!("{} follows the session", option),
+        })
+    }
+}
+
+/// Splits a `\pset` command line into its option and value, or returns None
+/// for any other line.
+pub fn parse_pset(line: &str) -> Option<(&str, Option<&str>)> {
+    let rest = line.trim().strip_prefix("\\pset")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim().trim_end_matches(';').trim_end();
+    Some(match rest.split_once(char::is_whitespace) {
+        Some((option, value)) => (option, Some(value.trim())),
+        None => (rest, None),
+    })
+}
+
+/// Renders a select result the server sends in chunks as the chunks arrive,
+/// so that its rows are printed without holding them all. The columns of a
+/// table are as wide as the header and the rows of each chunk, so they may
+/// shift from one chunk to the next.
+pub struct ChunkPrinter {
+    output: OutputFormat,
+    format: DisplayFormat,
+    /// The result without its rows, as in the header of the stream.
+    header: QueryResult,
+    chunks: usize,
+}
+
+impl ChunkPrinter {
+    pub fn new(settings: &PrintSettings, header: QueryResult) -> Self {
+        ChunkPrinter {
+            output: settings.format,
+            format: settings.display_format(header.get_format()),
+            header,
+            chunks: 0,
+        }
+    }
+
+    /// Renders the rows of the next chunk, after the column names for the
+    /// first one.
+    pub fn render(&mut self, rows: Vec<Tuple>) -> String {
+        let schema = self.header.get_schema().cloned().unwrap_or_default();
+        let chunk = QueryResult::new_select_result(&schema, rows, None);
+        let first = self.chunks == 0;
+        self.chunks += 1;
+        match self.output {
+            OutputFormat::Table => chunk.render_table_rows(&self.format, first),
+            OutputFormat::Csv => {
+                let mut csv = String::new();
+                if first {
+                    let names = schema.attributes.iter().map(|a| Cow::from(a.name.as_str()));
+                    csv.push_str(&csv_line(names));
+                    csv.push('\n');
+                }
+                for tuple in chunk.get_tuples().into_iter().flatten() {
+                    csv.push_str(&tuple.to_csv_with(&self.format));
+                    csv.push('\n');
+                }
+                csv
+            }
+            OutputFormat::Json => {
+                let objects: Vec<String> = chunk
+                    .json_rows(&self.format)
+                    .iter()
+                    .map(|row| row.to_string())
+                    .collect();
+                match (first, objects.is_empty()) {
+                    (true, _) => format!("[{}", objects.join(",")),
+                    (false, true) => String::new(),
+                    (false, false) => format!(",{}", objects.join(",")),
+                }
+            }
+        }
+    }
+
+    /// Renders what follows the last chunk: the footer of a table or the end
+    /// of a JSON array, so that the whole output reads as `PrintSettings::render`
+    /// followed by a newline.
+    pub fn finish(&mut self) -> String {
+        let mut end = if self.chunks == 0 {
+            self.render(Vec::new())
+        } else {
+            String::new()
+        };
+        match self.output {
+            OutputFormat::Table => {
+                if let Some(footer) = self.header.get_footer() {
+                    end.push_str(footer);
+                    end.push('\n');
+                }
+                end.push('\n');
+            }
+            OutputFormat::Csv => {}
+            OutputFormat::Json => end.push_str("]\n"),
+        }
+        end
+    }
+
+    /// The result without its rows, for its notices.
+    pub fn header(&self) -> &QueryResult {
+        &self.header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{DataType, Field, TableSchema};
+
+    /// Column of the caret in the last line of a rendered error.
+    fn caret_column(rendered: &str) -> usize {
+        rendered.lines().last().unwrap().find('^').unwrap()
+    }
+
+    #[test]
+    fn test_caret_under_position() {
+        let sql = "SELECT a,\n  bogus FROM t";
+        let position = SourcePosition::from_offset(sql, sql.find("bogus").unwrap());
+        let rendered = render_error_at(sql, "Column bogus does not exist", &position, false);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "Column bogus does not exist");
+        assert_eq!(lines[1], "LINE 2:   bogus FROM t");
+        assert_eq!(caret_column(&rendered), "LINE 2:   ".len());
+    }
+
+    #[test]
+    fn test_caret_after_tabs_and_past_end() {
+        let sql = "SELECT\t*\tFROM";
+        let position = SourcePosition::from_offset(sql, sql.len());
+        let rendered = render_error_at(sql, "Expected identifier", &position, false);
+        assert_eq!(
+            rendered.lines().last().unwrap(),
+            format!("{}\t \t    ^", " ".repeat("LINE 1: ".len() + 6))
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        let position = SourcePosition::from_offset("SELEC 1", 0);
+        let rendered = render_error_at("SELEC 1", "bad", &position, true);
+        assert!(rendered.starts_with(RED_BOLD));
+        assert!(rendered.ends_with(&format!("{}^{}", RED_BOLD, RESET)));
+    }
+
+    #[test]
+    fn test_render_notices() {
+        assert_eq!(render_notices(&[], true), None);
+        let notices = [
+            Notice::Truncated { rows: 5 },
+            Notice::CacheHit { hash: 0xab },
+        ];
+        assert_eq!(
+            render_notices(&notices, false).unwrap(),
+            "* Result truncated to 5 rows by max_result_rows\n\
+             * Cached result of plan 00000000000000ab"
+        );
+        let dimmed = render_notices(&notices[..1], true).unwrap();
+        assert!(dimmed.starts_with(DIM) && dimmed.ends_with(RESET));
+    }
+
+    #[test]
+    fn test_parse_pset() {
+        assert_eq!(
+            parse_pset("\\pset null '(none)';"),
+            Some(("null", Some("'(none)'")))
+        );
+        assert_eq!(
+            parse_pset("\\pset date_format %d %b %Y"),
+            Some(("date_format", Some("%d %b %Y")))
+        );
+        assert_eq!(parse_pset("\\pset format"), Some(("format", None)));
+        assert_eq!(parse_pset("\\psetx"), None);
+        assert_eq!(parse_pset("SELECT 1"), None);
+    }
+
+    #[test]
+    fn test_client_settings_win_over_session() {
+        let session = DisplayFormat {
+            null_text: "-".into(),
+            date_format: "%d.%m.%Y".into(),
+            decimal_separator: ',',
+        };
+        let mut print = PrintSettings::default();
+        assert_eq!(print.display_format(&session), session);
+
+        print.set("null", Some("''")).unwrap();
+        print.set("date_format", Some("%Y/%m/%d")).unwrap();
+        let format = print.display_format(&session);
+        assert_eq!(format.null_text, "");
+        assert_eq!(format.date_format, "%Y/%m/%d");
+        assert_eq!(format.decimal_separator, ',');
+
+        // Without a value the option follows the session again.
+        print.set("date_format", None).unwrap();
+        assert_eq!(print.display_format(&session).date_format, "%d.%m.%Y");
+
+        assert!(print.set("date_format", Some("%Q")).is_err());
+        assert!(print.set("decimal_separator", Some("::")).is_err());
+        assert!(print.set("format", Some("xml")).is_err());
+        assert!(print.set("border", Some("2")).is_err());
+        print.set("format", Some("CSV")).unwrap();
+        assert_eq!(print.format, OutputFormat::Csv);
+    }
+
+    #[test]
+    fn test_chunks_print_as_the_whole_result() {
+        let schema = TableSchema::from_vecs(vec!["n", "s"], vec![DataType::Int, DataType::String]);
+        let rows: Vec<Tuple> = (10..15)
+            .map(|n| Tuple::new(vec![Field::Int(n), Field::String(format!("v{}", n))]))
+            .collect();
+        let result = QueryResult::new_select_result(&schema, rows.clone(), None)
+            .with_footer("footer".to_string());
+        let header =
+            QueryResult::new_select_result(&schema, vec![], None).with_footer("footer".to_string());
+        for format in [OutputFormat::Table, OutputFormat::Csv, OutputFormat::Json] {
+            let print = PrintSettings {
+                format,
+                ..Default::default()
+            };
+            let mut printer = ChunkPrinter::new(&print, header.clone());
+            let mut streamed: String = rows.chunks(2).map(|c| printer.render(c.to_vec())).collect();
+            streamed.push_str(&printer.finish());
+            assert_eq!(
+                streamed,
+                format!("{}\n", print.render(&result)),
+                "{:?}",
+                format
+            );
+
+            let mut empty = ChunkPrinter::new(&print, header.clone());
+            let empty_result = QueryResult::new_select_result(&schema, vec![], None)
+                .with_footer("footer".to_string());
+            assert_eq!(empty.finish(), format!("{}\n", print.render(&empty_result)));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::Client;
+use common::physical::config::ClientConfig;
+use common::workload::{is_mutating, result_count, CapturedStatement};
+use common::FairyError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How statements are spaced out during a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// Issue every statement at the same offset from the start as in the capture.
+    Original,
+    /// Issue every statement as soon as the previous one of its session finished.
+    AsFastAsPossible,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub pacing: Pacing,
+    /// Replay statements that change the server or database state.
+    /// Disable this to replay against a server that is already loaded.
+    pub include_mutations: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            pacing: Pacing::AsFastAsPossible,
+            include_mutations: true,
+        }
+    }
+}
+
+/// Outcome of one replayed statement next to its recorded values.
+#[derive(Debug, Clone)]
+pub struct ReplayedStatement {
+    pub recorded: CapturedStatement,
+    pub latency_us: u64,
+    pub ok: bool,
+    pub result_count: Option<usize>,
+}
+
+impl ReplayedStatement {
+    /// Replayed latency minus recorded latency. Negative if the replay was faster.
+    pub fn latency_delta_us(&self) -> i64 {
+        self.latency_us as i64 - self.recorded.latency_us as i64
+    }
+
+    /// Returns true if the statement failed or succeeded differently than recorded,
+    /// or returned a different number of rows.
+    pub fn is_mismatch(&self) -> bool {
+        self.ok != self.recorded.ok || self.result_count != self.recorded.result_count
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Replayed statements, grouped by session in capture order.
+    pub statements: Vec<ReplayedStatement>,
+}
+
+impl ReplayReport {
+    pub fn mismatches(&self) -> Vec<&ReplayedStatement> {
+        self.statements.iter().filter(|s| s.is_mismatch()).collect()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in &self.statements {
+            writeln!(
+                f,
+                "[session {}] {:>+10}us {}{}",
+                s.recorded.session_id,
+                s.latency_delta_us(),
+                if s.is_mismatch() { "MISMATCH " } else { "" },
+                s.recorded.statement
+            )?;
+        }
+        write!(
+            f,
+            "{} statements replayed, {} mismatches",
+            self.statements.len(),
+            self.mismatches().len()
+        )
+    }
+}
+
+/// Replays a captured workload against the server in `config`.
+///
+/// Every captured session is replayed on its own connection and thread, issuing its
+/// statements in the recorded order. Statements of different sessions are only
+/// ordered by their pacing, so mutations shared across sessions should be replayed
+/// with `Pacing::Original`.
+pub fn replay_workload(
+    config: &ClientConfig,
+    workload: &[CapturedStatement],
+    options: &ReplayOptions,
+) -> Result<ReplayReport, FairyError> {
+    let mut sessions: BTreeMap<u64, Vec<CapturedStatement>> = BTreeMap::new();
+    for statement in workload {
+        if options.include_mutations || !is_mutating(&statement.statement) {
+            sessions
+                .entry(statement.session_id)
+                .or_default()
+                .push(statement.clone());
+        }
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = sessions
+        .into_values()
+        .map(|statements| {
+            let config = config.clone();
+            let pacing = options.pacing;
+            thread::spawn(move || replay_session(config, statements, pacing, start))
+        })
+        .collect();
+
+    let mut report = ReplayReport::default();
+    for handle in handles {
+        let statements = handle
+            .join()
+            .map_err(|_| FairyError::ExecutionError("Replay session panicked".to_string()))??;
+        report.statements.extend(statements);
+    }
+    Ok(report)
+}
+
+fn replay_session(
+    config: ClientConfig,
+    statements: Vec<CapturedStatement>,
+    pacing: Pacing,
+    start: Instant,
+) -> Result<Vec<ReplayedStatement>, FairyError> {
+    let mut client = Client::connect(config)?;
+    let mut replayed = Vec::with_capacity(statements.len());
+    for recorded in statements {
+        if pacing == Pacing::Original {
+            let due = start + Duration::from_micros(recorded.offset_us);
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+        let started = Instant::now();
+        let response = client.execute(&recorded.statement)?;
+        replayed.push(ReplayedStatement {
+            latency_us: started.elapsed().as_micros() as u64,
+            ok: response.is_ok(),
+            result_count: result_count(&response),
+            recorded,
+        });
+    }
+    Ok(replayed)
+}
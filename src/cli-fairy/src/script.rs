@@ -0,0 +1,288 @@
+use common::commands::Response;
+use common::QueryResult;
+use std::fmt;
+
+/// A statement of a script, with its position for error reporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptStatement {
+    /// 1-based position of the statement in the script.
+    pub ordinal: usize,
+    /// 1-based line on which the statement starts.
+    pub line: usize,
+    pub text: String,
+}
+
+/// What to do when a statement of a script fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScriptMode {
+    /// Skip the rest of the script.
+    #[default]
+    StopOnError,
+    /// Run the rest of the script and report every failure.
+    ContinueOnError,
+}
+
+/// Options of `Client::send_requests_from_buffer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScriptOptions {
+    pub mode: ScriptMode,
+    /// Most result rows kept across all responses of the script. Rows past the
+    /// cap are dropped from the retained responses, which are then flagged as
+    /// truncated. None keeps every row.
+    pub max_retained_rows: Option<usize>,
+}
+
+/// A failed statement of a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementError {
+    pub ordinal: usize,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "statement {} (line {}): {}",
+            self.ordinal, self.line, self.message
+        )
+    }
+}
+
+/// Counts of the outcomes of the statements of a script.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScriptSummary {
+    /// Statements in the script.
+    pub statements: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Statements not sent because an earlier one failed.
+    pub skipped: usize,
+    /// Rows returned by all queries, whether or not they were retained.
+    pub rows: usize,
+    /// Responses whose rows were cut by `ScriptOptions::max_retained_rows`.
+    pub truncated: usize,
+}
+
+impl ScriptSummary {
+    /// Records the outcome of a statement.
+    pub fn record(&mut self, outcome: &Result<Response, StatementError>) {
+        match outcome {
+            Ok(response) => {
+                self.succeeded += 1;
+                self.rows += response_rows(response);
+            }
+            Err(_) => self.failed += 1,
+        }
+    }
+}
+
+/// Response retained for a successful statement.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RetainedResponse {
+    pub response: Response,
+    /// True if rows of the response were dropped to respect
+    /// `ScriptOptions::max_retained_rows`.
+    pub truncated: bool,
+}
+
+/// Outcome of every statement of a script that was sent, in script order.
+/// Statements skipped after a failure in `ScriptMode::StopOnError` have no
+/// outcome.
+#[derive(Debug, Default)]
+pub struct ScriptOutcome {
+    pub outcomes: Vec<Result<RetainedResponse, StatementError>>,
+    pub summary: ScriptSummary,
+}
+
+impl ScriptOutcome {
+    pub fn is_ok(&self) -> bool {
+        self.outcomes.iter().all(Result::is_ok)
+    }
+
+    /// Responses of the statements that succeeded.
+    pub fn responses(&self) -> impl Iterator<Item = &Response> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| o.as_ref().ok())
+            .map(|r| &r.response)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &StatementError> {
+        self.outcomes.iter().filter_map(|o| o.as_ref().err())
+    }
+}
+
+/// Number of rows carried by a response.
+pub fn response_rows(response: &Response) -> usize {
+    match response {
+        Response::QueryResult(result) => result.get_tuples().map_or(0, Vec::len),
+        _ => 0,
+    }
+}
+
+/// Keeps at most `*budget` rows of a query response and takes the rows kept
+/// from the budget. Returns the response and whether rows were dropped.
+pub fn retain_rows(mut response: Response, budget: &mut usize) -> RetainedResponse {
+    let mut truncated = false;
+    if let Response::QueryResult(QueryResult::Select { result, .. }) = &mut response {
+        if result.len() > *budget {
+            result.truncate(*budget);
+            result.shrink_to_fit();
+            truncated = true;
+        }
+        *budget -= result.len();
+    }
+    RetainedResponse {
+        response,
+        truncated,
+    }
+}
+
+/// Splits a script into statements separated by `;`. Semicolons inside single or
+/// double quoted strings do not separate statements; a doubled quote inside a
+/// string is an escaped quote. A `--` outside a string starts a comment that runs
+/// to the end of the line and is left out of the statement. Newlines within a
+/// statement become spaces.
+pub fn split_statements(script: &str) -> Vec<ScriptStatement> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut line = 1;
+    let mut quote: Option<char> = None;
+
+    let mut finish = |current: &mut String, start_line: usize| {
+        let text = current.trim();
+        if !text.is_empty() {
+            statements.push(ScriptStatement {
+                ordinal: statements.len() + 1,
+                line: start_line,
+                text: text.replace('\n', " "),
+            });
+        }
+        current.clear();
+    };
+
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, ';') => {
+                finish(&mut current, start_line);
+                continue;
+            }
+            (None, '-') if chars.peek() == Some(&'-') => {
+                // Skip the comment but keep its newline, which counts the line.
+                while chars.next_if(|&next| next != '\n').is_some() {}
+                continue;
+            }
+            // A doubled quote closes and reopens the string, which is what
+            // escaping it means.
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+        if current.trim().is_empty() && !c.is_whitespace() {
+            start_line = line;
+        }
+        current.push(c);
+        if c == '\n' {
+            line += 1;
+        }
+    }
+    finish(&mut current, start_line);
+    statements
+}
+
+/// Returns the error message carried by a response, if it reports a failure.
+pub fn response_error(response: &Response) -> Option<String> {
+    match response {
+        Response::QueryExecutionError(_, msg) | Response::SystemErr(_, msg) => Some(msg.clone()),
+        Response::QueryErrorAt {
+            message, position, ..
+        } => Some(format!(
+            "{} at line {}, column {}",
+            message, position.line, position.column
+        )),
+        Response::QuietErr { message, .. } => Some(message.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(script: &str) -> Vec<String> {
+        split_statements(script)
+            .into_iter()
+            .map(|s| s.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_split_quoted_semicolons() {
+        assert_eq!(
+            texts("INSERT INTO t VALUES ('a;b'); SELECT \"x;y\" FROM t;"),
+            vec!["INSERT INTO t VALUES ('a;b')", "SELECT \"x;y\" FROM t"]
+        );
+        // Escaped quotes do not end the string.
+        assert_eq!(
+            texts("INSERT INTO t VALUES ('it''s;fine');\\dt"),
+            vec!["INSERT INTO t VALUES ('it''s;fine')", "\\dt"]
+        );
+        // A double quote inside a single quoted string is just a character.
+        assert_eq!(
+            texts("SELECT '\";' FROM t; SELECT 1"),
+            vec!["SELECT '\";' FROM t", "SELECT 1"]
+        );
+        assert!(texts(" ;\n; ").is_empty());
+    }
+
+    #[test]
+    fn test_split_comments() {
+        assert_eq!(
+            texts("-- don't stop here;\nSELECT 1; -- or 'here\nSELECT '--not a comment' FROM t;"),
+            vec!["SELECT 1", "SELECT '--not a comment' FROM t"]
+        );
+        let statements = split_statements("-- header\n\nSELECT a -- the key\nFROM t;");
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].line, 3);
+        assert_eq!(statements[0].text, "SELECT a  FROM t");
+    }
+
+    #[test]
+    fn test_split_positions() {
+        let script = "CREATE TABLE t (a INT);\n\n\nINSERT INTO t\nVALUES (1);\n  SELECT * FROM t";
+        let statements = split_statements(script);
+        assert_eq!(
+            statements,
+            vec![
+                ScriptStatement {
+                    ordinal: 1,
+                    line: 1,
+                    text: "CREATE TABLE t (a INT)".to_string(),
+                },
+                ScriptStatement {
+                    ordinal: 2,
+                    line: 4,
+                    text: "INSERT INTO t VALUES (1)".to_string(),
+                },
+                ScriptStatement {
+                    ordinal: 3,
+                    line: 6,
+                    text: "SELECT * FROM t".to_string(),
+                },
+            ]
+        );
+        let error = StatementError {
+            ordinal: 314,
+            line: 1022,
+            message: "Table foo does not exist".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "statement 314 (line 1022): Table foo does not exist"
+        );
+    }
+}
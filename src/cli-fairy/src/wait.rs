@@ -0,0 +1,136 @@
+//! Waiting for responses without blocking on the socket, so that the wait can
+//! time out, be given up on with Ctrl-C and show that it is still going.
+
+use common::error::c_err;
+use common::FairyError;
+use std::io::{ErrorKind, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long a read of the socket blocks before the wait checks its deadline
+/// and Ctrl-C again.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a wait lasts before the spinner shows.
+const SPINNER_DELAY: Duration = Duration::from_secs(1);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+/// Largest read of a response at once.
+const READ_CHUNK: usize = 32 * 1024;
+
+/// A wait for the responses to a request.
+pub struct Wait<'a> {
+    started: Instant,
+    /// How long the wait may last, 0 for no limit.
+    timeout_ms: u64,
+    /// Set on Ctrl-C, if the wait may be given up on.
+    interrupt: Option<&'a AtomicBool>,
+    /// Whether the spinner shows on stderr.
+    spinner: bool,
+    /// Last spinner frame drawn.
+    drawn: Option<u128>,
+}
+
+impl<'a> Wait<'a> {
+    /// Starts a wait. Interactive waits show the spinner and end on Ctrl-C.
+    pub fn new(timeout_ms: u64, interrupt: &'a AtomicBool, interactive: bool) -> Self {
+        Wait {
+            started: Instant::now(),
+            timeout_ms,
+            interrupt: interactive.then_some(interrupt),
+            spinner: interactive && std::io::stderr().is_terminal(),
+            drawn: None,
+        }
+    }
+
+    /// Called while no data arrives. Fails once the wait timed out or was given
+    /// up on, and otherwise keeps the spinner going.
+    fn tick(&mut self) -> Result<(), FairyError> {
+        let elapsed = self.started.elapsed();
+        if self.interrupt.is_some_and(|i| i.load(Ordering::SeqCst)) {
+            return Err(c_err(
+                "Stopped waiting for the response, which will be discarded when it arrives. \
+                 \\cancel from another client stops the statement",
+            ));
+        }
+        if self.timeout_ms > 0 && elapsed >= Duration::from_millis(self.timeout_ms) {
+            return Err(c_err(&format!(
+                "No response within {} ms, it will be discarded when it arrives",
+                self.timeout_ms
+            )));
+        }
+        let frame = elapsed.as_millis() / POLL_INTERVAL.as_millis();
+        if self.spinner && elapsed >= SPINNER_DELAY && self.drawn != Some(frame) {
+            let mut stderr = std::io::stderr();
+            let _ = write!(
+                stderr,
+                "\r{} {:.1}s",
+                SPINNER[frame as usize % SPINNER.len()],
+                elapsed.as_secs_f64()
+            );
+            let _ = stderr.flush();
+            self.drawn = Some(frame);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Wait<'_> {
+    fn drop(&mut self) {
+        if self.drawn.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// A length-prefixed frame being read. It is kept across waits, so that a
+/// response given up on part way through can still be skipped.
+#[derive(Default)]
+pub struct FrameReader {
+    bytes: Vec<u8>,
+    /// Length of the payload, once the prefix was read.
+    length: Option<usize>,
+}
+
+impl FrameReader {
+    /// Reads from `stream`, which must have a read timeout, until a whole frame
+    /// arrived and returns its payload.
+    pub fn read<R: Read>(
+        &mut self,
+        stream: &mut R,
+        wait: &mut Wait,
+    ) -> Result<Vec<u8>, FairyError> {
+        loop {
+            let wanted = match self.length {
+                None if self.bytes.len() == 8 => {
+                    let mut prefix = [0u8; 8];
+                    prefix.copy_from_slice(&self.bytes);
+                    self.length = Some(u64::from_be_bytes(prefix) as usize);
+                    self.bytes.clear();
+                    continue;
+                }
+                None => 8,
+                Some(length) if self.bytes.len() == length => {
+                    self.length = None;
+                    return Ok(std::mem::take(&mut self.bytes));
+                }
+                Some(length) => length,
+            };
+            let start = self.bytes.len();
+            self.bytes.resize(wanted.min(start + READ_CHUNK), 0);
+            let read = stream.read(&mut self.bytes[start..]);
+            self.bytes.truncate(start + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => return Err(c_err("Incomplete response received")),
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                    ) =>
+                {
+                    wait.tick()?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
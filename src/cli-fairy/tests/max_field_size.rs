@@ -0,0 +1,95 @@
+//! Values larger than max_field_size are refused with an error, and the
+//! connection stays usable.
+
+use cli_fairy::{Client, Response};
+use common::commands::ErrorCode;
+use common::physical::config::ServerConfig;
+use common::MAX_TUPLE_SIZE;
+use test_support::TestServer;
+
+/// max_field_size of the servers of the tests, in bytes.
+const LIMIT: usize = 64;
+
+fn start(max_field_size: usize) -> TestServer {
+    TestServer::with_config(ServerConfig {
+        max_field_size,
+        ..ServerConfig::default()
+    })
+}
+
+/// Connects to the database `db` of the server, with an empty table `t`.
+fn connect(server: &TestServer) -> Client {
+    let mut client = server.connect().unwrap();
+    for sql in [
+        "\\r db",
+        "\\c db",
+        "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(200))",
+    ] {
+        assert!(client.execute(sql).unwrap().is_ok(), "{}", sql);
+    }
+    client
+}
+
+#[test]
+fn test_literal_over_the_limit() {
+    let server = start(LIMIT);
+    let mut client = connect(&server);
+    let sql = format!("INSERT INTO t VALUES (1, '{}')", "x".repeat(LIMIT));
+    assert!(client.execute(&sql).unwrap().is_ok());
+
+    let sql = format!("INSERT INTO t\nVALUES (2, '{}')", "x".repeat(LIMIT + 1));
+    match client.execute(&sql).unwrap() {
+        Response::QueryErrorAt {
+            code,
+            message,
+            position,
+        } => {
+            assert_eq!(code, ErrorCode::FieldTooLarge);
+            assert_eq!(
+                message,
+                format!(
+                    "Value of {} bytes exceeds max_field_size of {} bytes",
+                    LIMIT + 1,
+                    LIMIT
+                )
+            );
+            assert_eq!((position.line, position.column), (2, 12));
+        }
+        r => panic!("Expected error at the literal, got {:?}", r),
+    }
+    assert!(client.execute("SELECT * FROM t").unwrap().is_ok());
+}
+
+#[test]
+fn test_request_over_the_limit() {
+    let server = start(LIMIT);
+    let mut client = connect(&server);
+    // Far larger than the limit and the room left for the rest of a request.
+    let sql = format!("SELECT * FROM t WHERE b = '{}'", "x".repeat(2 << 20));
+    match client.execute(&sql).unwrap() {
+        Response::QueryExecutionError(code, message) => {
+            assert_eq!(code, ErrorCode::FieldTooLarge);
+            assert!(message.contains("exceeds max_field_size"), "{}", message);
+        }
+        r => panic!("Expected error, got {:?}", r),
+    }
+    assert!(client.execute("SELECT * FROM t").unwrap().is_ok());
+}
+
+#[test]
+fn test_large_value_with_raised_limit() {
+    let server = start(128 << 20);
+    let mut client = connect(&server);
+    let sql = format!("SELECT * FROM t WHERE b = '{}'", "x".repeat(64 << 20));
+    assert!(client.execute(&sql).unwrap().is_ok());
+
+    // Without overflow pages, a row must fit in a page to be stored.
+    let sql = format!("INSERT INTO t VALUES (1, '{}')", "x".repeat(MAX_TUPLE_SIZE));
+    match client.execute(&sql).unwrap() {
+        Response::QueryExecutionError(_, message) => {
+            assert!(message.contains("exceeds the page limit"), "{}", message)
+        }
+        r => panic!("Expected error, got {:?}", r),
+    }
+    assert!(client.execute("SELECT * FROM t").unwrap().is_ok());
+}
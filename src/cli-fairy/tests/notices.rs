@@ -0,0 +1,157 @@
+//! Reads the notices of results through the client.
+
+use cli_fairy::{Client, Notice, Response};
+use common::{Field, QueryResult};
+use test_support::TestServer;
+
+/// Rows of the table `t` when connecting.
+const ROWS: usize = 100;
+
+/// Connects to the database `db` of the server, with `ROWS` rows in the table
+/// `t`.
+fn connect(server: &TestServer) -> Client {
+    let mut client = server.connect().unwrap();
+    let values: Vec<String> = (0..ROWS)
+        .map(|i| format!("({}, {}, {})", i, i % 10, i % 5))
+        .collect();
+    for sql in [
+        "\\r db",
+        "\\c db",
+        "CREATE TABLE t (a INT PRIMARY KEY, b INT, c INT)",
+    ] {
+        assert!(client.execute(sql).unwrap().is_ok(), "{}", sql);
+    }
+    insert(&mut client, &values);
+    client
+}
+
+/// Inserts rows into `t`. Requests are read in a single buffer of 1 KiB.
+fn insert(client: &mut Client, values: &[String]) {
+    for chunk in values.chunks(50) {
+        let sql = format!("INSERT INTO t VALUES {}", chunk.join(", "));
+        assert!(client.execute(&sql).unwrap().is_ok());
+    }
+}
+
+fn rows(response: &Response) -> usize {
+    match response {
+        Response::QueryResult(result @ QueryResult::Select { .. }) => {
+            result.get_tuples().unwrap().len()
+        }
+        response => panic!("Expected select result, got {:?}", response),
+    }
+}
+
+#[test]
+fn test_truncated_result() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let response = client.execute("SELECT * FROM t").unwrap();
+    assert_eq!(rows(&response), ROWS);
+    assert!(response.notices().is_empty());
+
+    assert!(client.execute("SET max_result_rows = 7").unwrap().is_ok());
+    let response = client.execute("SELECT * FROM t").unwrap();
+    assert_eq!(rows(&response), 7);
+    assert_eq!(response.notices(), [Notice::Truncated { rows: 7 }]);
+    // A result within the limit is whole.
+    let response = client.execute("SELECT * FROM t WHERE a < 3").unwrap();
+    assert_eq!(rows(&response), 3);
+    assert!(response.notices().is_empty());
+
+    assert!(!client.execute("SET max_result_rows = -1").unwrap().is_ok());
+    assert!(client.execute("SET max_result_rows = 0").unwrap().is_ok());
+    assert_eq!(rows(&client.execute("SELECT * FROM t").unwrap()), ROWS);
+}
+
+#[test]
+fn test_cache_hit() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let sql = "SELECT a FROM t";
+    let response = client.execute(&format!("\\register q {}", sql)).unwrap();
+    assert!(response.notices().is_empty());
+
+    let response = client.execute(sql).unwrap();
+    assert_eq!(rows(&response), ROWS);
+    let cache = client.execute("SELECT * FROM system.plan_cache").unwrap();
+    let Response::QueryResult(cache) = cache else {
+        panic!("Expected query result, got {:?}", cache);
+    };
+    let Some(Field::String(hash)) = cache.get_tuples().unwrap()[0].get_field(0) else {
+        panic!("Expected plan hash, got {:?}", cache);
+    };
+    let hash = u64::from_str_radix(hash, 16).unwrap();
+    assert_eq!(response.notices(), [Notice::CacheHit { hash }]);
+}
+
+#[test]
+fn test_stale_stats_on_last_cursor_batch() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    assert!(client.execute("ANALYZE t (b, c)").unwrap().is_ok());
+    let response = client.execute("SELECT * FROM t").unwrap();
+    assert!(response.notices().is_empty());
+
+    // The table grows by half after the group was analyzed.
+    let values: Vec<String> = (ROWS..ROWS + ROWS / 2)
+        .map(|i| format!("({}, 0, 0)", i))
+        .collect();
+    insert(&mut client, &values);
+    let response = client.execute("SELECT * FROM t").unwrap();
+    assert_eq!(response.notices(), [Notice::StaleStats]);
+
+    assert!(client
+        .execute("DECLARE c CURSOR FOR SELECT * FROM t")
+        .unwrap()
+        .is_ok());
+    let batch = client.execute("FETCH 100 FROM c").unwrap();
+    assert_eq!(rows(&batch), 100);
+    assert!(batch.notices().is_empty());
+    let batch = client.execute("FETCH 100 FROM c").unwrap();
+    assert_eq!(rows(&batch), ROWS / 2);
+    assert_eq!(batch.notices(), [Notice::StaleStats]);
+    // Fetching past the end reports nothing new.
+    let batch = client.execute("FETCH 100 FROM c").unwrap();
+    assert!(batch.notices().is_empty());
+}
+
+#[test]
+fn test_cross_product() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    for sql in [
+        "CREATE TABLE u (x INT PRIMARY KEY)",
+        "INSERT INTO u VALUES (1), (2), (3)",
+    ] {
+        assert!(client.execute(sql).unwrap().is_ok(), "{}", sql);
+    }
+    let cross = "SELECT * FROM t, u WHERE a < 2";
+    let response = client.execute(cross).unwrap();
+    assert_eq!(rows(&response), 6);
+    assert_eq!(response.notices(), [Notice::CrossProduct]);
+    let join = "SELECT * FROM t, u WHERE a = x AND b > 0";
+    let response = client.execute(join).unwrap();
+    assert_eq!(rows(&response), 3);
+    assert!(response.notices().is_empty());
+
+    match client.execute(&format!("EXPLAIN {}", cross)).unwrap() {
+        Response::QueryResult(QueryResult::MessageOnly(plan)) => {
+            let warning = format!("Warning: {}", Notice::CrossProduct);
+            assert!(plan.ends_with(&warning), "{}", plan);
+        }
+        r => panic!("Expected plan, got {:?}", r),
+    }
+
+    assert!(client
+        .execute("SET forbid_cross_products = on")
+        .unwrap()
+        .is_ok());
+    match client.execute(cross).unwrap() {
+        Response::QueryExecutionError(_, message) => {
+            assert!(message.contains("forbid_cross_products"), "{}", message)
+        }
+        r => panic!("Expected error, got {:?}", r),
+    }
+    assert_eq!(rows(&client.execute(join).unwrap()), 3);
+}
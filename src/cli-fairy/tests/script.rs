@@ -0,0 +1,185 @@
+//! Runs scripts against a server.
+
+use cli_fairy::script::{self, ScriptMode, ScriptOptions, ScriptSummary};
+use cli_fairy::{Client, Response};
+use common::QueryResult;
+use test_support::TestServer;
+
+/// Rows of the table `huge`.
+const HUGE_ROWS: usize = 2000;
+
+const CONTINUE: ScriptOptions = ScriptOptions {
+    mode: ScriptMode::ContinueOnError,
+    max_retained_rows: None,
+};
+
+/// Connects to the database `db` of the server, with the table `huge`.
+fn connect(server: &TestServer) -> Client {
+    let mut client = server.connect().unwrap();
+    for sql in [
+        "\\r db",
+        "\\c db",
+        "CREATE TABLE huge (a INT PRIMARY KEY)",
+        "CREATE TABLE t (a INT PRIMARY KEY)",
+    ] {
+        assert!(client.execute(sql).unwrap().is_ok(), "{}", sql);
+    }
+    let values: Vec<String> = (0..HUGE_ROWS).map(|i| format!("({})", i)).collect();
+    // Requests are read in a single buffer of 1 KiB.
+    for chunk in values.chunks(100) {
+        let sql = format!("INSERT INTO huge VALUES {}", chunk.join(", "));
+        assert!(client.execute(&sql).unwrap().is_ok());
+    }
+    client
+}
+
+/// Rows of the table `s`.
+fn s_rows(client: &mut Client) -> usize {
+    match client.execute("SELECT * FROM s").unwrap() {
+        Response::QueryResult(result @ QueryResult::Select { .. }) => {
+            result.get_tuples().unwrap().len()
+        }
+        response => panic!("Expected select result, got {:?}", response),
+    }
+}
+
+const SCRIPT: &str = "CREATE TABLE s (a INT PRIMARY KEY, b VARCHAR(10));\n\
+    INSERT INTO s VALUES (1, 'x');\n\n\
+    SELECT * FROM missing;\n\
+    INSERT INTO s VALUES (2, 'a;b');\n\
+    SELECT * FROM missing;";
+
+#[test]
+fn test_script_stops_on_error() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let outcome = client
+        .send_requests_from_buffer(SCRIPT.as_bytes(), ScriptOptions::default())
+        .unwrap();
+    assert_eq!(outcome.outcomes.len(), 3);
+    assert_eq!(outcome.responses().count(), 2);
+    let errors: Vec<String> = outcome.errors().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        vec!["statement 3 (line 4): Table missing does not exist"]
+    );
+    let summary = outcome.summary;
+    assert_eq!(
+        (summary.succeeded, summary.failed, summary.skipped),
+        (2, 1, 2)
+    );
+    assert_eq!(s_rows(&mut client), 1);
+}
+
+#[test]
+fn test_script_continues_on_error() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let outcome = client
+        .send_requests_from_buffer(SCRIPT.as_bytes(), CONTINUE)
+        .unwrap();
+    assert!(!outcome.is_ok());
+    assert!(outcome.outcomes[3].is_ok());
+    let errors: Vec<String> = outcome.errors().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        vec![
+            "statement 3 (line 4): Table missing does not exist",
+            "statement 5 (line 6): Table missing does not exist",
+        ]
+    );
+    // The semicolon inside the string does not split the statement.
+    assert_eq!(s_rows(&mut client), 2);
+}
+
+/// Two huge queries around a failing and a small statement.
+const HUGE_SCRIPT: &str = "SELECT * FROM huge;\n\
+    SELECT * FROM missing;\n\
+    INSERT INTO t VALUES (1);\n\
+    SELECT * FROM huge;";
+
+#[test]
+fn test_script_retained_rows_cap() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let options = ScriptOptions {
+        max_retained_rows: Some(1000),
+        ..CONTINUE
+    };
+    let outcome = client
+        .send_requests_from_buffer(HUGE_SCRIPT.as_bytes(), options)
+        .unwrap();
+
+    let retained: Vec<(usize, bool)> = outcome
+        .outcomes
+        .iter()
+        .filter_map(|o| o.as_ref().ok())
+        .map(|r| (script::response_rows(&r.response), r.truncated))
+        .collect();
+    // The first query uses up the whole budget.
+    assert_eq!(retained, vec![(1000, true), (0, false), (0, true)]);
+    assert_eq!(
+        outcome.summary,
+        ScriptSummary {
+            statements: 4,
+            succeeded: 3,
+            failed: 1,
+            skipped: 0,
+            rows: 2 * HUGE_ROWS,
+            truncated: 2,
+        }
+    );
+}
+
+#[test]
+fn test_run_script_with_callback() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let mut seen = Vec::new();
+    let summary = client
+        .run_script_with(HUGE_SCRIPT.as_bytes(), ScriptMode::StopOnError, |i, o| {
+            seen.push((i, o.map(|r| script::response_rows(&r)).map_err(|e| e.line)));
+        })
+        .unwrap();
+    assert_eq!(seen, vec![(1, Ok(HUGE_ROWS)), (2, Err(2))]);
+    assert_eq!(
+        summary,
+        ScriptSummary {
+            statements: 4,
+            succeeded: 1,
+            failed: 1,
+            skipped: 2,
+            rows: HUGE_ROWS,
+            truncated: 0,
+        }
+    );
+}
+
+#[test]
+fn test_script_explains_without_running() {
+    let server = TestServer::start();
+    let mut client = connect(&server);
+    let script = "EXPLAIN SELECT * FROM huge WHERE a < 10;\n\
+        EXPLAIN DELETE FROM huge WHERE a < 10;\n\
+        EXPLAIN INSERT INTO t VALUES (1), (2);";
+    let outcome = client
+        .send_requests_from_buffer(script.as_bytes(), ScriptOptions::default())
+        .unwrap();
+    let plans: Vec<String> = outcome
+        .responses()
+        .map(|response| match response {
+            Response::QueryResult(QueryResult::MessageOnly(plan)) => plan.clone(),
+            r => panic!("Expected a plan, got {:?}", r),
+        })
+        .collect();
+    assert_eq!(plans.len(), 3);
+    assert!(plans[0].contains("scan(huge, "), "{}", plans[0]);
+    assert!(plans[1].starts_with("Delete from huge\n"), "{}", plans[1]);
+    assert_eq!(plans[2], "Insert 2 rows into t");
+    match client.execute("SELECT * FROM huge").unwrap() {
+        Response::QueryResult(result) => {
+            assert_eq!(result.get_tuples().unwrap().len(), HUGE_ROWS)
+        }
+        r => panic!("Expected select result, got {:?}", r),
+    }
+}
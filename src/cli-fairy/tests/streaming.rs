@@ -0,0 +1,128 @@
+//! Select results larger than result_chunk_rows are sent in chunks, and the
+//! client reads them back as one result.
+
+use cli_fairy::{Client, Response};
+use common::commands::parse_command;
+use common::physical::config::ServerConfig;
+use common::{Field, QueryResult};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use test_support::TestServer;
+
+/// result_chunk_rows of the servers of the tests.
+const CHUNK_ROWS: usize = 1000;
+/// Length of the strings of the table `t`.
+const WIDTH: usize = 1000;
+/// The rows of `SELECT * FROM t, u`, of about `WIDTH` bytes each: some 10 MB,
+/// hundreds of times the 32 KiB the server writes at a time.
+const QUERY: &str = "SELECT * FROM t, u";
+const ROWS: usize = 100 * 100;
+
+fn start(result_chunk_rows: usize) -> TestServer {
+    TestServer::with_config(ServerConfig {
+        result_chunk_rows,
+        ..ServerConfig::default()
+    })
+}
+
+/// Connects to the database `db` of the server, with 100 rows in the tables
+/// `t` and `u`.
+fn connect(server: &TestServer) -> Client {
+    let mut client = server.connect().unwrap();
+    for sql in [
+        "\\r db",
+        "\\c db",
+        "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(1000))",
+        "CREATE TABLE u (c INT PRIMARY KEY)",
+    ] {
+        assert!(client.execute(sql).unwrap().is_ok(), "{}", sql);
+    }
+    let t: Vec<String> = (0..100)
+        .map(|i| format!("({}, '{}')", i, "x".repeat(WIDTH)))
+        .collect();
+    let u: Vec<String> = (0..100).map(|i| format!("({})", i)).collect();
+    for sql in [
+        format!("INSERT INTO t VALUES {}", t.join(", ")),
+        format!("INSERT INTO u VALUES {}", u.join(", ")),
+    ] {
+        assert!(client.execute(&sql).unwrap().is_ok());
+    }
+    client
+}
+
+/// Sends a request on a connection of its own, as the client does.
+fn send(stream: &mut TcpStream, sql: &str) {
+    let request = serde_cbor::to_vec(&parse_command(sql.to_string()).unwrap()).unwrap();
+    stream
+        .write_all(&(request.len() as u64).to_be_bytes())
+        .unwrap();
+    stream.write_all(&request).unwrap();
+}
+
+/// Reads a length-prefixed frame of a response.
+fn read_frame(stream: &mut TcpStream) -> Response {
+    let mut length = [0u8; 8];
+    stream.read_exact(&mut length).unwrap();
+    let mut frame = vec![0; u64::from_be_bytes(length) as usize];
+    stream.read_exact(&mut frame).unwrap();
+    serde_cbor::from_slice(&frame).unwrap()
+}
+
+fn rows(response: &Response) -> &Vec<common::Tuple> {
+    match response {
+        Response::QueryResult(result @ QueryResult::Select { .. }) => result.get_tuples().unwrap(),
+        r => panic!("Expected a select result, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_large_result_is_sent_in_chunks() {
+    let server = start(CHUNK_ROWS);
+    drop(connect(&server));
+
+    let config = server.client_config();
+    let mut stream = TcpStream::connect(format!("{}:{}", config.host, config.port)).unwrap();
+    send(&mut stream, "\\c db");
+    assert!(read_frame(&mut stream).is_ok());
+    send(&mut stream, QUERY);
+    match read_frame(&mut stream) {
+        Response::ResultHeader(header) => {
+            assert_eq!(header.get_schema().unwrap().attributes.len(), 3);
+            assert!(header.get_tuples().unwrap().is_empty());
+        }
+        r => panic!("Expected the header of a result, got {:?}", r),
+    }
+    let mut received = 0;
+    loop {
+        match read_frame(&mut stream) {
+            Response::ResultRows(rows) => {
+                assert_eq!(rows.len(), CHUNK_ROWS);
+                received += rows.len();
+            }
+            Response::ResultEnd => break,
+            r => panic!("Expected rows, got {:?}", r),
+        }
+    }
+    assert_eq!(received, ROWS);
+
+    // A result that fits in a chunk is sent in one message, as before.
+    send(&mut stream, "SELECT * FROM u");
+    assert_eq!(rows(&read_frame(&mut stream)).len(), 100);
+}
+
+#[test]
+fn test_client_collects_chunks() {
+    for result_chunk_rows in [CHUNK_ROWS, 0] {
+        let server = start(result_chunk_rows);
+        let mut client = connect(&server);
+        let response = client.execute(QUERY).unwrap();
+        let tuples = rows(&response);
+        assert_eq!(tuples.len(), ROWS);
+        assert!(tuples
+            .iter()
+            .all(|row| row.get_field(1) == Some(&Field::String("x".repeat(WIDTH)))));
+        // The connection is ready for the next statement.
+        let response = client.execute("SELECT * FROM u").unwrap();
+        assert_eq!(rows(&response).len(), 100);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ids::ContainerId, DataType};
+use crate::{ids::ContainerId, DataType, Field};
 
 /// Handle attributes. Pairs the name with the dtype.
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
@@ -9,6 +9,9 @@ pub struct Attribute {
     pub dtype: DataType,
     /// Attribute constraint
     pub constraint: Constraint,
+    /// Value of the attribute when an insert leaves it out, NULL if not set.
+    #[serde(default)]
+    pub default: Option<Field>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
@@ -35,6 +38,7 @@ impl Attribute {
             name,
             dtype,
             constraint: Constraint::None,
+            default: None,
         }
     }
 
@@ -43,6 +47,7 @@ impl Attribute {
             name,
             dtype,
             constraint,
+            default: None,
         }
     }
 
@@ -51,6 +56,7 @@ impl Attribute {
             name,
             dtype,
             constraint: Constraint::PrimaryKey,
+            default: None,
         }
     }
 
@@ -63,4 +69,15 @@ impl Attribute {
     pub fn dtype(&self) -> &DataType {
         &self.dtype
     }
+
+    /// Returns whether the attribute accepts NULL.
+    pub fn is_nullable(&self) -> bool {
+        !matches!(
+            self.constraint,
+            Constraint::PrimaryKey
+                | Constraint::NotNull
+                | Constraint::UniqueNotNull
+                | Constraint::NotNullFKey(_)
+        )
+    }
 }
@@ -1,9 +1,26 @@
+use crate::error::ObjectKind;
 use crate::ids::{ColumnId, ContainerId};
+use crate::physical::page_dictionary::ColumnEncoding;
 use crate::table::TableInfo;
+use crate::table_lock::{TableLockGuard, TableLockMode, TableLocks};
 use crate::{table::TableSchema, MAX_COLUMNS};
+use crate::{DataType, FairyError};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Maximum number of characters allowed in a table or column comment.
+pub const MAX_COMMENT_LENGTH: usize = 1024;
+
+fn validate_comment(comment: &Option<String>) -> Result<(), FairyError> {
+    match comment {
+        Some(c) if c.chars().count() > MAX_COMMENT_LENGTH => Err(FairyError::ValidationError(
+            format!("Comment exceeds {} characters", MAX_COMMENT_LENGTH),
+        )),
+        _ => Ok(()),
+    }
+}
 
 /// Generates temporary column ID based on table index and column index.
 /// Note that this temporary column ID is only used for sequential scans.
@@ -32,11 +49,20 @@ pub fn get_column_index_from_temp_col_id(col_id: ColumnId) -> ColumnId {
     (col_id % MAX_COLUMNS) as ColumnId
 }
 
+/// Number of distinct container ids.
+const CONTAINER_ID_SPACE: u32 = ContainerId::MAX as u32 + 1;
+
 // Reference: https://github.com/rotaki/decorrelator
 #[derive(Serialize, Deserialize, Debug)]
 struct ContainerIdGenerator {
-    next_id: ContainerId,
+    /// The lowest id that was never handed out. It equals `CONTAINER_ID_SPACE`
+    /// once every id was handed out.
+    next_id: u32,
     table_to_id: HashMap<String, ContainerId>,
+    /// Ids of dropped tables whose containers were removed. They are handed
+    /// out again, lowest first, before new ones.
+    #[serde(default)]
+    free_ids: BTreeSet<ContainerId>,
 }
 
 impl ContainerIdGenerator {
@@ -44,22 +70,53 @@ impl ContainerIdGenerator {
         ContainerIdGenerator {
             next_id: 0,
             table_to_id: HashMap::new(),
+            free_ids: BTreeSet::new(),
         }
     }
 
     /// Note that ContainerId is not unique across multiple databases.
-    /// Note that if table_name is not found, it will generate a new ContainerId.
+    /// Note that if table_name is not found, it will generate a new ContainerId
+    /// that skips the ids for which `in_use` returns true. Fails if every id is taken.
     /// Note the difference between `get_table_id` and `get_table_id_if_exists`.
-    fn get_table_id(&mut self, table_name: &str) -> ContainerId {
-        match self.table_to_id.get(table_name) {
-            Some(c_id) => *c_id,
-            None => {
-                let c_id = self.next_id;
-                self.next_id += 1;
-                self.table_to_id.insert(table_name.to_string(), c_id);
-                c_id
+    fn get_table_id(
+        &mut self,
+        table_name: &str,
+        in_use: impl Fn(ContainerId) -> bool,
+    ) -> Result<ContainerId, FairyError> {
+        if let Some(c_id) = self.table_to_id.get(table_name) {
+            return Ok(*c_id);
+        }
+        let c_id = self.allocate(in_use)?;
+        assert!(
+            self.table_to_id.values().all(|id| *id != c_id),
+            "container id {} is handed out twice",
+            c_id
+        );
+        self.table_to_id.insert(table_name.to_string(), c_id);
+        Ok(c_id)
+    }
+
+    /// Returns the lowest free id that is not in use, or else the next id
+    /// never handed out that is not in use.
+    fn allocate(
+        &mut self,
+        in_use: impl Fn(ContainerId) -> bool,
+    ) -> Result<ContainerId, FairyError> {
+        if let Some(c_id) = self.free_ids.iter().copied().find(|c_id| !in_use(*c_id)) {
+            self.free_ids.remove(&c_id);
+            return Ok(c_id);
+        }
+        while self.next_id < CONTAINER_ID_SPACE {
+            let c_id = self.next_id as ContainerId;
+            self.next_id += 1;
+            if !in_use(c_id) {
+                return Ok(c_id);
             }
         }
+        Err(FairyError::ExecutionError(format!(
+            "No container ids left: all {} ids are used by tables or by dropped tables whose storage is not released yet",
+            CONTAINER_ID_SPACE
+        )))
     }
 
     /// Returns the table index if the table exists. Note the difference between
@@ -69,12 +126,32 @@ impl ContainerIdGenerator {
     fn get_table_id_if_exists(&self, table_name: &str) -> Option<ContainerId> {
         self.table_to_id.get(table_name).cloned()
     }
+
+    /// Forgets the name of a removed table. The id is not reused until it is
+    /// released.
+    fn remove_table_id(&mut self, c_id: ContainerId) {
+        self.table_to_id.retain(|_, id| *id != c_id);
+    }
+
+    /// Makes the id of a removed table available to new tables.
+    fn release_table_id(&mut self, c_id: ContainerId) {
+        assert!(
+            self.table_to_id.values().all(|id| *id != c_id),
+            "container id {} is released while a table uses it",
+            c_id
+        );
+        if u32::from(c_id) < self.next_id {
+            self.free_ids.insert(c_id);
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Catalog {
     container_id_generator: Mutex<ContainerIdGenerator>,
     tables: RwLock<HashMap<ContainerId, TableInfo>>,
+    #[serde(skip)]
+    locks: TableLocks,
 }
 
 impl Catalog {
@@ -82,17 +159,37 @@ impl Catalog {
         Arc::new(Catalog {
             container_id_generator: Mutex::new(ContainerIdGenerator::new()),
             tables: RwLock::new(HashMap::new()),
+            locks: TableLocks::new(),
         })
     }
 
     /// Returns the table index for the given table name. If the table does not exist,
-    /// it will generate a new table index.
+    /// it will generate a new table index, and fails if every index is taken.
     /// Note the difference between `get_table_id` and `get_table_id_if_exists`
-    pub fn get_table_id(&self, name: &str) -> ContainerId {
+    pub fn get_table_id(&self, name: &str) -> Result<ContainerId, FairyError> {
+        let mut generator = self.container_id_generator.lock().unwrap();
+        generator.get_table_id(name, |_| false)
+    }
+
+    /// Returns the table index for the given table name like `get_table_id`, but
+    /// never generates an index for which `in_use` returns true. Databases share
+    /// the storage manager, so new tables must skip the container ids that the
+    /// tables of other databases already use.
+    ///
+    /// The ids of dropped tables are reused once they are released. Fails if
+    /// every id is taken.
+    pub fn get_new_table_id(
+        &self,
+        name: &str,
+        in_use: impl Fn(ContainerId) -> bool,
+    ) -> Result<ContainerId, FairyError> {
         let mut generator = self.container_id_generator.lock().unwrap();
-        generator.get_table_id(name)
+        generator.get_table_id(name, in_use)
     }
 
+    /// Adds a table to the catalog. Returns None if a table with the same
+    /// container id is already present. The check and the insert happen under
+    /// one write lock, so of several concurrent adds exactly one succeeds.
     pub fn add_table(&self, table_info: TableInfo) -> Option<()> {
         let mut tables = self.tables.write().unwrap();
         match tables.get(&table_info.c_id) {
@@ -105,11 +202,70 @@ impl Catalog {
         }
     }
 
+    /// Removes a table and its name from the catalog and returns it, if present.
+    /// The generator is locked before the tables, as in `get_cols`, so that a
+    /// name never resolves to a removed table.
+    pub fn remove_table(&self, c_id: ContainerId) -> Option<TableInfo> {
+        let mut generator = self.container_id_generator.lock().unwrap();
+        let mut tables = self.tables.write().unwrap();
+        generator.remove_table_id(c_id);
+        tables.remove(&c_id)
+    }
+
+    /// Makes the container id of a removed table available to new tables. The
+    /// storage manager must have removed the container first, so that a new
+    /// table never sees the pages of the old one.
+    pub fn release_table_id(&self, c_id: ContainerId) {
+        let mut generator = self.container_id_generator.lock().unwrap();
+        assert!(
+            !self.is_valid_table(c_id),
+            "container id {} is released while a table uses it",
+            c_id
+        );
+        generator.release_table_id(c_id);
+    }
+
+    /// Takes table locks for the duration of a statement, waiting up to `timeout`.
+    /// The locks are released when the returned guard is dropped.
+    pub fn lock_tables(
+        &self,
+        c_ids: &[ContainerId],
+        mode: TableLockMode,
+        timeout: Duration,
+    ) -> Result<TableLockGuard<'_>, FairyError> {
+        self.locks.lock(c_ids, mode, timeout).map_err(|c_id| {
+            let name = self
+                .get_table(c_id)
+                .map(|info| info.name)
+                .unwrap_or_else(|| c_id.to_string());
+            FairyError::ExecutionError(match mode {
+                TableLockMode::Shared => format!("table {} is locked by DDL", name),
+                TableLockMode::Exclusive => format!(
+                    "timed out after {:?} waiting for statements using table {}",
+                    timeout, name
+                ),
+            })
+        })
+    }
+
+    /// Returns true if any statement holds or waits for a lock on the table.
+    pub fn is_table_locked(&self, c_id: ContainerId) -> bool {
+        self.locks.is_locked(c_id)
+    }
+
     pub fn get_table(&self, c_id: ContainerId) -> Option<TableInfo> {
         let tables = self.tables.read().unwrap();
         tables.get(&c_id).cloned()
     }
 
+    /// Returns all tables in the catalog, ordered by container id.
+    pub fn get_tables(&self) -> Vec<TableInfo> {
+        let tables = self.tables.read().unwrap();
+        let mut res: Vec<TableInfo> = tables.values().cloned().collect();
+        res.sort_by_key(|info| info.c_id);
+        res
+    }
+
     pub fn get_table_names(&self) -> Vec<String> {
         let tables = self.tables.read().unwrap();
         tables.values().map(|info| info.name.clone()).collect()
@@ -120,6 +276,28 @@ impl Catalog {
         tables.get(&c_id).map(|info| info.schema.clone())
     }
 
+    /// Returns the schema generation of the table, if it exists.
+    pub fn get_schema_generation(&self, c_id: ContainerId) -> Option<u64> {
+        let tables = self.tables.read().unwrap();
+        tables.get(&c_id).map(|info| info.schema_generation)
+    }
+
+    /// Replaces the schema of a table and bumps its schema generation.
+    /// Returns the new generation.
+    pub fn update_table_schema(
+        &self,
+        c_id: ContainerId,
+        schema: TableSchema,
+    ) -> Result<u64, FairyError> {
+        let mut tables = self.tables.write().unwrap();
+        let table_info = tables
+            .get_mut(&c_id)
+            .ok_or(FairyError::ContainerDoesNotExist)?;
+        table_info.schema = schema;
+        table_info.schema_generation += 1;
+        Ok(table_info.schema_generation)
+    }
+
     pub fn is_valid_table(&self, c_id: ContainerId) -> bool {
         let tables = self.tables.read().unwrap();
         tables.contains_key(&c_id)
@@ -139,6 +317,135 @@ impl Catalog {
         self.get_table_id_if_exists(table_name).is_some()
     }
 
+    /// Sets the comment of a table. Passing `None` clears the comment.
+    pub fn set_table_comment(
+        &self,
+        c_id: ContainerId,
+        comment: Option<String>,
+    ) -> Result<(), FairyError> {
+        validate_comment(&comment)?;
+        let mut tables = self.tables.write().unwrap();
+        let table_info = tables
+            .get_mut(&c_id)
+            .ok_or(FairyError::ContainerDoesNotExist)?;
+        table_info.comment = comment;
+        Ok(())
+    }
+
+    /// Sets the comment of a column. Passing `None` clears the comment.
+    pub fn set_column_comment(
+        &self,
+        c_id: ContainerId,
+        column_name: &str,
+        comment: Option<String>,
+    ) -> Result<(), FairyError> {
+        validate_comment(&comment)?;
+        let mut tables = self.tables.write().unwrap();
+        let table_info = tables
+            .get_mut(&c_id)
+            .ok_or(FairyError::ContainerDoesNotExist)?;
+        if !table_info.schema.contains(column_name) {
+            return Err(FairyError::NotFound(
+                ObjectKind::Column,
+                format!("{}.{}", table_info.name, column_name),
+            ));
+        }
+        match comment {
+            Some(comment) => {
+                table_info
+                    .column_comments
+                    .insert(column_name.to_string(), comment);
+            }
+            None => {
+                table_info.column_comments.remove(column_name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the encoding of a column and returns the indexes of the dictionary
+    /// encoded columns of the table. Only string columns can be dictionary
+    /// encoded.
+    pub fn set_column_encoding(
+        &self,
+        c_id: ContainerId,
+        column_name: &str,
+        encoding: ColumnEncoding,
+    ) -> Result<Vec<usize>, FairyError> {
+        let mut tables = self.tables.write().unwrap();
+        let table_info = tables
+            .get_mut(&c_id)
+            .ok_or(FairyError::ContainerDoesNotExist)?;
+        let index = table_info
+            .schema
+            .get_field_index(column_name)
+            .ok_or_else(|| {
+                FairyError::NotFound(
+                    ObjectKind::Column,
+                    format!("{}.{}", table_info.name, column_name),
+                )
+            })?;
+        let dtype = table_info.schema.attributes[index].dtype();
+        if encoding == ColumnEncoding::Dictionary
+            && !matches!(dtype, DataType::Char(_) | DataType::String)
+        {
+            return Err(FairyError::ValidationError(format!(
+                "Dictionary encoding is only supported for string columns, {} is {}",
+                column_name, dtype
+            )));
+        }
+        match encoding {
+            ColumnEncoding::Plain => table_info.column_encodings.remove(column_name),
+            _ => table_info
+                .column_encodings
+                .insert(column_name.to_string(), encoding),
+        };
+        Ok(table_info.dictionary_columns())
+    }
+
+    /// Declares a group of columns whose joint distribution is kept in the
+    /// statistics and returns the indexes of the columns. Declaring a group
+    /// twice keeps one.
+    pub fn add_column_group(
+        &self,
+        c_id: ContainerId,
+        columns: &[String],
+    ) -> Result<Vec<usize>, FairyError> {
+        let mut tables = self.tables.write().unwrap();
+        let table_info = tables
+            .get_mut(&c_id)
+            .ok_or(FairyError::ContainerDoesNotExist)?;
+        if columns.len() < 2 {
+            return Err(FairyError::ValidationError(
+                "A column group needs at least two columns".to_string(),
+            ));
+        }
+        let mut indexes = Vec::with_capacity(columns.len());
+        for column in columns {
+            let index = table_info.schema.get_field_index(column).ok_or_else(|| {
+                FairyError::NotFound(
+                    ObjectKind::Column,
+                    format!("{}.{}", table_info.name, column),
+                )
+            })?;
+            if indexes.contains(&index) {
+                return Err(FairyError::ValidationError(format!(
+                    "Column {} appears twice in the column group",
+                    column
+                )));
+            }
+            indexes.push(index);
+        }
+        let names: Vec<String> = indexes
+            .iter()
+            .map(|i| table_info.schema.attributes[*i].name.clone())
+            .collect();
+        if !table_info.column_groups.contains(&names) {
+            table_info.column_groups.push(names);
+        }
+        Ok(indexes)
+    }
+
     pub fn is_valid_column(&self, c_id: ContainerId, column_name: &str) -> bool {
         let tables = self.tables.read().unwrap();
         if let Some(table_info) = tables.get(&c_id) {
@@ -167,3 +474,100 @@ impl Catalog {
 }
 
 pub type CatalogRef = Arc<Catalog>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(catalog: &Catalog, name: &str) -> Result<ContainerId, FairyError> {
+        let c_id = catalog.get_new_table_id(name, |_| false)?;
+        let schema = TableSchema::from_vecs(vec!["a"], vec![DataType::BigInt]);
+        catalog
+            .add_table(TableInfo::new(c_id, name.to_string(), schema))
+            .unwrap();
+        Ok(c_id)
+    }
+
+    fn drop_table(catalog: &Catalog, c_id: ContainerId) {
+        catalog.remove_table(c_id).unwrap();
+        catalog.release_table_id(c_id);
+    }
+
+    #[test]
+    fn test_ids_are_recycled() {
+        let catalog = Catalog::new();
+        let kept = add(&catalog, "kept").unwrap();
+        for i in 0..5000 {
+            let c_id = add(&catalog, &format!("temp_{}", i)).unwrap();
+            assert_ne!(c_id, kept);
+            assert!(c_id < 3, "id {} was not recycled", c_id);
+            drop_table(&catalog, c_id);
+        }
+        assert_eq!(catalog.get_table_id_if_exists("kept"), Some(kept));
+
+        // A removed table whose id is not released keeps its id.
+        let pending = add(&catalog, "pending").unwrap();
+        catalog.remove_table(pending).unwrap();
+        assert_ne!(add(&catalog, "next").unwrap(), pending);
+        catalog.release_table_id(pending);
+        assert_eq!(add(&catalog, "reuses").unwrap(), pending);
+    }
+
+    #[test]
+    fn test_ids_in_use_are_skipped() {
+        let catalog = Catalog::new();
+        let a = add(&catalog, "a").unwrap();
+        drop_table(&catalog, a);
+        // Another database took the released id meanwhile.
+        let b = catalog.get_new_table_id("b", |c_id| c_id <= a).unwrap();
+        assert_eq!(b, a + 1);
+        let c = catalog.get_new_table_id("c", |_| false).unwrap();
+        assert_eq!(c, a);
+    }
+
+    #[test]
+    fn test_exhausted_ids() {
+        let catalog = Catalog::new();
+        // Other databases use every id but the last one.
+        let last = catalog
+            .get_new_table_id("t", |c_id| c_id != ContainerId::MAX)
+            .unwrap();
+        assert_eq!(last, ContainerId::MAX);
+        let err = catalog.get_new_table_id("u", |_| false).unwrap_err();
+        assert!(err.to_string().contains("No container ids left"), "{}", err);
+
+        catalog.remove_table(last);
+        catalog.release_table_id(last);
+        assert_eq!(catalog.get_new_table_id("u", |_| false), Ok(last));
+    }
+
+    #[test]
+    fn test_allocator_state_is_persisted() {
+        let catalog = Catalog::new();
+        let ids: Vec<ContainerId> = (0..4)
+            .map(|i| add(&catalog, &format!("t{}", i)).unwrap())
+            .collect();
+        drop_table(&catalog, ids[1]);
+        drop_table(&catalog, ids[2]);
+
+        let json = serde_json::to_string(&catalog).unwrap();
+        let loaded: Catalog = serde_json::from_str(&json).unwrap();
+        assert_eq!(add(&loaded, "x").unwrap(), ids[1]);
+        assert_eq!(add(&loaded, "y").unwrap(), ids[2]);
+        assert_eq!(add(&loaded, "z").unwrap(), 4);
+
+        // Catalogs saved before ids were recycled have no free ids.
+        let old = json.replace(r#","free_ids":[1,2]"#, "");
+        assert_ne!(old, json);
+        let loaded: Catalog = serde_json::from_str(&old).unwrap();
+        assert_eq!(add(&loaded, "x").unwrap(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "released while a table uses it")]
+    fn test_release_of_live_id() {
+        let catalog = Catalog::new();
+        let c_id = add(&catalog, "t").unwrap();
+        catalog.release_table_id(c_id);
+    }
+}
@@ -4,7 +4,7 @@ type CommandTuple = (&'static str, u8, Command, &'static str);
 
 /// The list of all possible commands that the server can receive.
 /// Any new command must be added here and have the responding variant added to the Command enum.
-const COMMANDS: [CommandTuple; 19] = [
+const COMMANDS: [CommandTuple; 27] = [
     // System commands
     (
         "h",
@@ -52,7 +52,7 @@ const COMMANDS: [CommandTuple; 19] = [
         "l",
         0,
         Command::System(SystemCommand::ShowDatabases),
-        "Show the current databases",
+        "Show the databases with their number of tables and size",
     ),
     (
         "t",
@@ -60,6 +60,18 @@ const COMMANDS: [CommandTuple; 19] = [
         Command::System(SystemCommand::Test),
         "A no-op command for testing",
     ),
+    (
+        "progress",
+        0,
+        Command::System(SystemCommand::Progress),
+        "Show the statements running and waiting for their turn",
+    ),
+    (
+        "cancel",
+        1,
+        Command::System(SystemCommand::Cancel),
+        "Cancel the statement a client is running (client id from \\progress)",
+    ),
     // Database commands
     (
         "sql",
@@ -79,6 +91,42 @@ const COMMANDS: [CommandTuple; 19] = [
         Command::DB(DBCommand::ShowQueries),
         "Show all registered queries in the current database",
     ),
+    (
+        "d",
+        1,
+        Command::DB(DBCommand::DescribeTable),
+        "Describe the columns of a table, including comments (table name)",
+    ),
+    (
+        "storage_report",
+        0,
+        Command::DB(DBCommand::StorageReport),
+        "Report page utilization and fragmentation of each table",
+    ),
+    (
+        "reset_metrics",
+        0,
+        Command::DB(DBCommand::ResetMetrics),
+        "Reset the latency histograms shown in system.metrics",
+    ),
+    (
+        "set",
+        2,
+        Command::DB(DBCommand::Set),
+        "Change a setting of this session, like SET (name, value)",
+    ),
+    (
+        "last_profile",
+        0,
+        Command::DB(DBCommand::LastProfile),
+        "Show the rows and time of each operator of the last query (--profile_operators)",
+    ),
+    (
+        "cache_evict",
+        1,
+        Command::DB(DBCommand::CacheEvict),
+        "Drop a cached plan and its result (tree hash from system.plan_cache)",
+    ),
     (
         "register",
         2,
@@ -145,6 +193,10 @@ pub enum SystemCommand {
     Test,
     /// Help command.
     Help,
+    /// Lists the running and queued statements.
+    Progress,
+    /// Cancels the statement a client is running.
+    Cancel,
 }
 
 // impl std::fmt::Display for SystemCommand {
@@ -180,6 +232,18 @@ pub enum DBCommand {
     ShowTables,
     /// Show all registered queries in the current database.
     ShowQueries,
+    /// Describe the columns of a table.
+    DescribeTable,
+    /// Report page utilization and fragmentation of each table.
+    StorageReport,
+    /// Reset the latency histograms shown in system.metrics.
+    ResetMetrics,
+    /// Change a setting of the session.
+    Set,
+    /// Show the operator profile of the last query of the session.
+    LastProfile,
+    /// Drop a cached plan and its result.
+    CacheEvict,
     /// Generates a CSV file from a specified source.
     Generate,
     /// Import a CSV file into a specified table.
@@ -225,34 +289,124 @@ pub struct CommandWithArgs {
     pub args: Vec<String>,
 }
 
+/// Formats the command the way a client would type it, so that
+/// `parse_command(cmd.to_string())` returns an equal command.
+impl std::fmt::Display for CommandWithArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.command == Command::DB(DBCommand::ExecuteSQL) {
+            return write!(f, "{}", self.args.join(" "));
+        }
+        let name = COMMANDS
+            .iter()
+            .find(|c| c.2 == self.command)
+            .map(|c| c.0)
+            .unwrap_or_default();
+        write!(f, "\\{}", name)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Machine-readable category of an error response.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum ErrorCode {
+    /// The request failed while changing or reading the server state.
+    System,
+    /// The request failed while running a query.
+    QueryExecution,
+    /// The request tried to create an object whose name is taken.
+    AlreadyExists,
+    /// The request referenced an object that does not exist.
+    NotFound,
+    /// The SQL text of the request could not be parsed.
+    Syntax,
+    /// The server runs too many statements to take the request. It may
+    /// succeed later.
+    ServerBusy,
+    /// A value of the request is larger than the server's max_field_size.
+    FieldTooLarge,
+    /// The request would give two rows of a table the same value of a unique
+    /// key.
+    DuplicateKey,
+    /// The query was cancelled with `\cancel` before it finished.
+    QueryCancelled,
+    /// The query ran longer than the timeout of its session.
+    QueryTimeout,
+}
+
 /// Types of acceptable commands.
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Response {
     Ok,
+    /// The command succeeded and has nothing else to report.
+    Ack,
     SystemMsg(String),
-    SystemErr(String),
+    SystemErr(ErrorCode, String),
     QueryResult(crate::QueryResult),
-    QueryExecutionError(String),
+    QueryExecutionError(ErrorCode, String),
+    /// A query failed because of the SQL text at `position`.
+    QueryErrorAt {
+        code: ErrorCode,
+        message: String,
+        position: crate::error::SourcePosition,
+    },
     Shutdown(bool), // true if the request for shutdown comes from the client
     QuietOk,
-    QuietErr,
+    QuietErr {
+        code: ErrorCode,
+        message: String,
+    },
+    /// Starts a select result sent in chunks: the result without its rows. The
+    /// rows follow in `ResultRows` frames, then `ResultEnd`.
+    ResultHeader(crate::QueryResult),
+    /// Next rows of the result being sent in chunks.
+    ResultRows(Vec<crate::Tuple>),
+    /// Ends the result being sent in chunks.
+    ResultEnd,
 }
 
 impl Response {
     pub fn is_ok(&self) -> bool {
         match self {
             Response::Ok => true,
+            Response::Ack => true,
             Response::SystemMsg(_) => true,
-            Response::SystemErr(_) => false,
+            Response::SystemErr(..) => false,
             Response::QueryResult(_) => true,
-            Response::QueryExecutionError(_) => false,
+            Response::QueryExecutionError(..) => false,
+            Response::QueryErrorAt { .. } => false,
             Response::Shutdown(_) => true,
             Response::QuietOk => true,
-            Response::QuietErr => false,
+            Response::QuietErr { .. } => false,
+            Response::ResultHeader(_) => true,
+            Response::ResultRows(_) => true,
+            Response::ResultEnd => true,
+        }
+    }
+
+    /// The notices of a select result, empty for every other response.
+    pub fn notices(&self) -> &[crate::query::query_result::Notice] {
+        match self {
+            Response::QueryResult(result) => result.notices(),
+            _ => &[],
         }
     }
 }
 
+/// Returns whether the SQL text is `SHOW DATABASES`, ignoring case, extra
+/// whitespace and a trailing semicolon.
+fn is_show_databases(sql: &str) -> bool {
+    let words: Vec<&str> = sql
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect();
+    matches!(words.as_slice(), [show, databases]
+        if show.eq_ignore_ascii_case("show") && databases.eq_ignore_ascii_case("databases"))
+}
+
 pub fn parse_command(mut cmd: String) -> Option<CommandWithArgs> {
     if cmd.ends_with('\n') {
         cmd.pop();
@@ -261,6 +415,15 @@ pub fn parse_command(mut cmd: String) -> Option<CommandWithArgs> {
         }
     }
 
+    // SHOW DATABASES is not specific to a database, so it runs like `\l` even
+    // without a connection.
+    if is_show_databases(&cmd) {
+        return Some(CommandWithArgs {
+            command: Command::System(SystemCommand::ShowDatabases),
+            args: vec![],
+        });
+    }
+
     // Handle regular SQL commands not prefixed with '\'
     if !cmd.starts_with('\\') {
         return Some(CommandWithArgs {
@@ -340,6 +503,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_show_databases() {
+        let show_databases = CommandWithArgs {
+            command: Command::System(SystemCommand::ShowDatabases),
+            args: vec![],
+        };
+        for cmd in ["\\l", "SHOW DATABASES", "show  databases;"] {
+            assert_eq!(parse_command(cmd.to_string()).unwrap(), show_databases);
+        }
+        assert_eq!(
+            parse_command("SHOW TABLES".to_string()).unwrap().command,
+            Command::DB(DBCommand::ExecuteSQL)
+        );
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for cmd in [
+            "\\r name",
+            "\\dt",
+            "\\register q1 SELECT * FROM foo",
+            "SELECT * FROM foo",
+        ] {
+            let parsed = parse_command(cmd.to_string()).unwrap();
+            assert_eq!(parsed.to_string(), cmd);
+            assert_eq!(parse_command(parsed.to_string()).unwrap(), parsed);
+        }
+    }
+
     #[test]
     fn test_connect() {
         let connect: String = String::from("\\c name");
@@ -388,6 +580,18 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_describe_table() {
+        let describe: String = String::from("\\d foo\n");
+        assert_eq!(
+            CommandWithArgs {
+                command: Command::DB(DBCommand::DescribeTable),
+                args: vec!["foo".to_string()]
+            },
+            parse_command(describe).unwrap()
+        );
+    }
+
     #[test]
     fn test_bad_command() {
         let bad_command: String = String::from("\\bad\n");
@@ -2,15 +2,23 @@ use crate::attribute::Attribute;
 
 #[allow(unused_imports)]
 use crate::error::{c_err, FairyError};
-use crate::query::bytecode_expr::{And, FromBool, Or};
+use crate::query::bytecode_expr::{And, FromBool, Like, Nullable, Or, SqlOrd};
+use crate::query::like::like_match;
 use crate::BinaryOp;
-use chrono::{Duration, NaiveDate};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime};
+use std::borrow::Cow;
+use std::fmt::Write;
 use std::ops::{Add, Div, Mul, Sub};
 
 pub fn base_date() -> NaiveDate {
     NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
 }
 
+pub fn base_timestamp() -> NaiveDateTime {
+    base_date().and_hms_opt(0, 0, 0).unwrap()
+}
+
 pub fn null_string() -> String {
     String::from("NULL")
 }
@@ -38,12 +46,32 @@ pub fn f_decimal(f: f64) -> Field {
     Field::Decimal(whole, s)
 }
 
+pub fn f_float(f: f64) -> Field {
+    Field::Float(OrdF64(f))
+}
+
 pub fn f_date(s: &str) -> Field {
-    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
-    let days = date.signed_duration_since(base_date()).num_days();
-    Field::Date(days)
+    Field::from_str_to_date(s).unwrap()
 }
 
+pub fn f_timestamp(s: &str) -> Field {
+    Field::from_str_to_timestamp(s).unwrap()
+}
+
+/// Formats accepted for dates written as strings, in SQL literals and CSV files.
+/// Each is given as a chrono format and as shown to users.
+pub const DATE_FORMATS: &[(&str, &str)] = &[("%Y-%m-%d", "YYYY-MM-DD"), ("%Y/%m/%d", "YYYY/MM/DD")];
+
+/// Formats accepted for timestamps written as strings, besides RFC 3339
+/// timestamps with a time zone offset and the `DATE_FORMATS`, which are read
+/// as midnight. Seconds may have a fraction.
+pub const TIMESTAMP_FORMATS: &[(&str, &str)] = &[
+    ("%Y-%m-%d %H:%M:%S%.f", "YYYY-MM-DD HH:MM:SS"),
+    ("%Y-%m-%dT%H:%M:%S%.f", "YYYY-MM-DDTHH:MM:SS"),
+    ("%Y-%m-%d %H:%M", "YYYY-MM-DD HH:MM"),
+    ("%Y-%m-%dT%H:%M", "YYYY-MM-DDTHH:MM"),
+];
+
 /// Enumerate the supported dtypes.
 /// When adding a new dtype, make sure to add a corresponding field type.
 #[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
@@ -54,7 +82,9 @@ pub enum DataType {
     Char(u8), // Length
     String,
     Decimal(u32, u32), // Precision, Scale : Precision is total number of digits, scale is number of digits after decimal
+    Float64,
     Date,
+    Timestamp,
     Bool,
     Null,
 }
@@ -68,7 +98,9 @@ impl std::fmt::Display for DataType {
             DataType::Char(n) => write!(f, "char-fixed-{},", n),
             DataType::String => write!(f, "string"),
             DataType::Decimal(p, s) => write!(f, "decimal({},{})", p, s),
+            DataType::Float64 => write!(f, "double"),
             DataType::Date => write!(f, "date"),
+            DataType::Timestamp => write!(f, "timestamp"),
             DataType::Bool => write!(f, "bool"),
             DataType::Null => write!(f, "null"),
         }
@@ -88,7 +120,9 @@ impl From<&Field> for DataType {
             // (should get the number of digits in the whole number and the number
             // of digits after the decimal point)
             Field::Decimal(p, s) => DataType::Decimal(*p as u32, *s),
+            Field::Float(_) => DataType::Float64,
             Field::Date(_) => DataType::Date,
+            Field::Timestamp(_) => DataType::Timestamp,
             Field::Bool(_) => DataType::Bool,
             Field::Null => DataType::Null,
         }
@@ -106,11 +140,75 @@ impl DataType {
             DataType::Char(i) => Some(*i as usize),
             DataType::String => None,
             DataType::Decimal(_, _) => Some(12),
+            DataType::Float64 => Some(8),
             DataType::Date => Some(8),
+            DataType::Timestamp => Some(8),
             DataType::Bool => Some(1),
             DataType::Null => Some(1),
         }
     }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, DataType::BigInt | DataType::Int | DataType::SmallInt)
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, DataType::Decimal(_, _) | DataType::Float64)
+    }
+
+    /// Whether `CAST` converts values of this type to `to`. Everything can be
+    /// cast to a string, strings to every type, and numbers to each other. Some
+    /// values may still fail to convert, e.g. strings that do not parse.
+    pub fn can_cast_to(&self, to: &DataType) -> bool {
+        match (self, to) {
+            (_, DataType::Null) => false,
+            (DataType::Null, _) => true,
+            (_, DataType::String | DataType::Char(_)) => true,
+            (DataType::String | DataType::Char(_), _) => true,
+            (from, to) if from.is_numeric() && to.is_numeric() => true,
+            (DataType::Bool, to) | (to, DataType::Bool) if to.is_integer() => true,
+            (DataType::Date | DataType::Timestamp, DataType::Date | DataType::Timestamp) => true,
+            (from, to) => from == to,
+        }
+    }
+}
+
+/// A float ordered by `f64::total_cmp`, so that fields holding one can be
+/// sorted, grouped and hashed. 0.0 and -0.0 are equal, and NaN is equal to
+/// itself and greater than every other value.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OrdF64(pub f64);
+
+impl PartialEq for OrdF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for OrdF64 {}
+
+impl PartialOrd for OrdF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdF64 {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self.0 == other.0 {
+            std::cmp::Ordering::Equal
+        } else {
+            self.0.total_cmp(&other.0)
+        }
+    }
+}
+
+impl std::hash::Hash for OrdF64 {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Adding 0.0 turns -0.0 into 0.0.
+        (self.0 + 0.0).to_bits().hash(state)
+    }
 }
 
 /// For each of the dtypes, make sure that there is a corresponding field type.
@@ -122,7 +220,9 @@ pub enum Field {
     Char(u8, String), // Length, Value
     String(String),
     Decimal(i64, u32), // Whole, Scale : Whole is the integer part and fractional part combined, scale is number of digits after decimal
-    Date(i64),         // Days relative to 1970-01-01
+    Float(OrdF64),
+    Date(i64),      // Days relative to 1970-01-01
+    Timestamp(i64), // Microseconds relative to 1970-01-01 00:00:00
     Bool(bool),
     Null,
 }
@@ -133,20 +233,132 @@ impl FromBool for Field {
     }
 }
 
+impl Nullable for Field {
+    fn null() -> Self {
+        Field::Null
+    }
+
+    fn is_null(&self) -> bool {
+        *self == Field::Null
+    }
+}
+
+impl SqlOrd for Field {
+    /// Integers and decimals compare exactly. A float compares exactly with an
+    /// integral value, and by its float value otherwise. Other fields compare
+    /// by their own order.
+    fn sql_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let float_cmp = |l: f64, r: f64| OrdF64(l).cmp(&OrdF64(r));
+        match (self, other, self.exact_number(), other.exact_number()) {
+            (_, _, Some(l), Some(r)) => {
+                cmp_exact(l, r).unwrap_or_else(|| float_cmp(exact_to_f64(l), exact_to_f64(r)))
+            }
+            (Field::Float(OrdF64(f)), _, None, Some(exact)) => float_exact_cmp(*f, exact),
+            (_, Field::Float(OrdF64(f)), Some(exact), None) => float_exact_cmp(*f, exact).reverse(),
+            _ => self.cmp(other),
+        }
+    }
+}
+
+impl Field {
+    /// Whole number and scale of an integer or a decimal, without the trailing
+    /// zeros of the decimal.
+    fn exact_number(&self) -> Option<(i128, u32)> {
+        match self {
+            Field::BigInt(i) => Some((*i as i128, 0)),
+            Field::Int(i) => Some((*i as i128, 0)),
+            Field::SmallInt(i) => Some((*i as i128, 0)),
+            Field::Decimal(whole, scale) => {
+                let (mut whole, mut scale) = (*whole as i128, *scale);
+                while scale > 0 && whole % 10 == 0 {
+                    whole /= 10;
+                    scale -= 1;
+                }
+                Some((whole, scale))
+            }
+            _ => None,
+        }
+    }
+
+    /// Key for hashing values compared with `sql_cmp`: values it finds equal
+    /// have the same key. Integral numbers become a BIGINT and other numbers a
+    /// float, other fields are their own key.
+    pub fn sql_key(&self) -> Field {
+        match (self, self.exact_number()) {
+            (_, Some((whole, 0))) => Field::BigInt(whole as i64),
+            (_, Some(exact)) => f_float(exact_to_f64(exact)),
+            (Field::Float(OrdF64(f)), None) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Field::BigInt(*f as i64)
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Compares two numbers given as a whole number and a scale, None if scaling
+/// them to the same scale overflows.
+fn cmp_exact((lw, ls): (i128, u32), (rw, rs): (i128, u32)) -> Option<std::cmp::Ordering> {
+    let scale = ls.max(rs);
+    let l = 10i128.checked_pow(scale - ls)?.checked_mul(lw)?;
+    let r = 10i128.checked_pow(scale - rs)?.checked_mul(rw)?;
+    Some(l.cmp(&r))
+}
+
+fn exact_to_f64((whole, scale): (i128, u32)) -> f64 {
+    whole as f64 / 10f64.powi(scale as i32)
+}
+
+/// Compares a float with a number given as a whole number and a scale.
+fn float_exact_cmp(f: f64, exact: (i128, u32)) -> std::cmp::Ordering {
+    let integral = f.fract() == 0.0 && f.abs() < i64::MAX as f64;
+    integral
+        .then(|| cmp_exact((f as i128, 0), exact))
+        .flatten()
+        .unwrap_or_else(|| OrdF64(f).cmp(&OrdF64(exact_to_f64(exact))))
+}
+
 impl And for Field {
-    fn and(&self, other: &Self) -> Self {
+    fn and(&self, other: &Self) -> Result<Self, FairyError> {
         match (self, other) {
-            (Field::Bool(a), Field::Bool(b)) => Field::Bool(*a && *b),
-            _ => panic!("Expected bool"),
+            (Field::Bool(a), Field::Bool(b)) => Ok(Field::Bool(*a && *b)),
+            (Field::Bool(false), Field::Null) | (Field::Null, Field::Bool(false)) => {
+                Ok(Field::Bool(false))
+            }
+            (Field::Bool(_) | Field::Null, Field::Bool(_) | Field::Null) => Ok(Field::Null),
+            _ => Err(c_err("Expected bool")),
         }
     }
 }
 
+impl Like for Field {
+    fn like(&self, pattern: &Self) -> Result<Self, FairyError> {
+        match (self, pattern) {
+            (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
+            (
+                Field::String(s) | Field::Char(_, s),
+                Field::String(pattern) | Field::Char(_, pattern),
+            ) => Ok(Field::Bool(like_match(s, pattern))),
+            _ => Err(c_err("Expected string")),
+        }
+    }
+
+    fn not_like(&self, pattern: &Self) -> Result<Self, FairyError> {
+        Ok(match self.like(pattern)? {
+            Field::Bool(b) => Field::Bool(!b),
+            field => field,
+        })
+    }
+}
+
 impl Or for Field {
-    fn or(&self, other: &Self) -> Self {
+    fn or(&self, other: &Self) -> Result<Self, FairyError> {
         match (self, other) {
-            (Field::Bool(a), Field::Bool(b)) => Field::Bool(*a || *b),
-            _ => panic!("Expected bool"),
+            (Field::Bool(a), Field::Bool(b)) => Ok(Field::Bool(*a || *b)),
+            (Field::Bool(true), Field::Null) | (Field::Null, Field::Bool(true)) => {
+                Ok(Field::Bool(true))
+            }
+            (Field::Bool(_) | Field::Null, Field::Bool(_) | Field::Null) => Ok(Field::Null),
+            _ => Err(c_err("Expected bool")),
         }
     }
 }
@@ -156,6 +368,7 @@ impl Add for Field {
 
     fn add(self, other: Self) -> Self::Output {
         match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
             (Field::BigInt(a), Field::BigInt(b)) => Ok(Field::BigInt(a + b)),
             (Field::Decimal(a, s_l), Field::Decimal(b, s_r)) => {
                 // We adjust to the larger scale
@@ -172,7 +385,13 @@ impl Add for Field {
                 let adjusted_b = b * 10i64.pow(s_l);
                 Ok(Field::Decimal(a + adjusted_b, s_l))
             }
-            _ => panic!("Expected int or decimal"),
+            (Field::Timestamp(t), Field::BigInt(micros))
+            | (Field::BigInt(micros), Field::Timestamp(t)) => timestamp_op(t.checked_add(micros)),
+            (Field::Date(d), Field::BigInt(days)) | (Field::BigInt(days), Field::Date(d)) => {
+                date_op(d.checked_add(days))
+            }
+            (l @ Field::Float(_), r) | (l, r @ Field::Float(_)) => float_op(&l, &r, |a, b| a + b),
+            _ => Err(c_err("Expected int, decimal or float")),
         }
     }
 }
@@ -182,6 +401,7 @@ impl Sub for Field {
 
     fn sub(self, other: Self) -> Self::Output {
         match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
             (Field::BigInt(a), Field::BigInt(b)) => Ok(Field::BigInt(a - b)),
             (Field::Decimal(a, s_l), Field::Decimal(b, s_r)) => {
                 // We adjust to the larger scale
@@ -198,7 +418,15 @@ impl Sub for Field {
                 let adjusted_b = b * 10i64.pow(s_l);
                 Ok(Field::Decimal(a - adjusted_b, s_l))
             }
-            _ => Err(c_err("Expected int or decimal")),
+            (Field::Timestamp(t), Field::BigInt(micros)) => timestamp_op(t.checked_sub(micros)),
+            (Field::Timestamp(a), Field::Timestamp(b)) => a
+                .checked_sub(b)
+                .map(Field::BigInt)
+                .ok_or_else(|| c_err("Timestamp difference out of range")),
+            (Field::Date(d), Field::BigInt(days)) => date_op(d.checked_sub(days)),
+            (Field::Date(a), Field::Date(b)) => Ok(Field::BigInt(a - b)),
+            (l @ Field::Float(_), r) | (l, r @ Field::Float(_)) => float_op(&l, &r, |a, b| a - b),
+            _ => Err(c_err("Expected int, decimal or float")),
         }
     }
 }
@@ -208,6 +436,7 @@ impl Mul for Field {
 
     fn mul(self, other: Self) -> Self::Output {
         match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
             (Field::BigInt(a), Field::BigInt(b)) => Ok(Field::BigInt(a * b)),
             (Field::Decimal(a, s_l), Field::Decimal(b, s_r)) => {
                 // We adjust to the larger scale
@@ -235,7 +464,8 @@ impl Mul for Field {
                 let res = a * b;
                 Ok(Field::Decimal(res, s_l))
             }
-            _ => Err(c_err("Expected int or decimal")),
+            (l @ Field::Float(_), r) | (l, r @ Field::Float(_)) => float_op(&l, &r, |a, b| a * b),
+            _ => Err(c_err("Expected int, decimal or float")),
         }
     }
 }
@@ -245,6 +475,7 @@ impl Div for Field {
 
     fn div(self, other: Self) -> Self::Output {
         match (self, other) {
+            (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
             (Field::BigInt(a), Field::BigInt(b)) => {
                 if b == 0 {
                     return Err(c_err("Division by zero"));
@@ -291,11 +522,58 @@ impl Div for Field {
             (Field::Decimal(a, s_l), Field::BigInt(b)) => {
                 Field::Decimal(a, s_l) / Field::Decimal(b, 0)
             }
-            _ => Err(c_err("Expected int or decimal")),
+            (l @ Field::Float(_), r) | (l, r @ Field::Float(_)) => {
+                if r.to_f64() == Some(0.0) {
+                    return Err(c_err("Division by zero"));
+                }
+                float_op(&l, &r, |a, b| a / b)
+            }
+            _ => Err(c_err("Expected int, decimal or float")),
         }
     }
 }
 
+/// Applies `op` to two numbers, one of them a float, converted to floats.
+fn float_op(l: &Field, r: &Field, op: impl Fn(f64, f64) -> f64) -> Result<Field, FairyError> {
+    match (l.to_f64(), r.to_f64()) {
+        (Some(a), Some(b)) => Ok(f_float(op(a, b))),
+        _ => Err(c_err("Expected int, decimal or float")),
+    }
+}
+
+/// Length of a day in the microseconds of timestamps.
+pub const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// `n / d` rounded half away from zero.
+fn round_div(n: i64, d: i64) -> i64 {
+    let (q, r) = (n / d, n % d);
+    if r.abs() >= d - r.abs() {
+        q + n.signum()
+    } else {
+        q
+    }
+}
+
+/// A date shifted by a number of days, unless it is past the dates chrono
+/// represents.
+fn date_op(days: Option<i64>) -> Result<Field, FairyError> {
+    days.filter(|days| date_from_days(*days).is_some())
+        .map(Field::Date)
+        .ok_or_else(|| c_err("Date out of range"))
+}
+
+/// The date `days` days after 1970-01-01.
+pub fn date_from_days(days: i64) -> Option<NaiveDate> {
+    base_date().checked_add_signed(Duration::try_days(days)?)
+}
+
+/// A timestamp shifted by a number of microseconds, unless it overflowed.
+fn timestamp_op(micros: Option<i64>) -> Result<Field, FairyError> {
+    micros
+        .map(Field::Timestamp)
+        .ok_or_else(|| c_err("Timestamp out of range"))
+}
+
 impl Field {
     pub fn size(&self) -> usize {
         match self {
@@ -305,7 +583,9 @@ impl Field {
             Field::Char(i, _) => *i as usize,
             Field::String(s) => s.len(),
             Field::Date(_) => 8,
+            Field::Timestamp(_) => 8,
             Field::Decimal(_, _) => 12,
+            Field::Float(_) => 8,
             Field::Bool(_) => 1,
             Field::Null => 1,
         }
@@ -322,7 +602,7 @@ impl Field {
             Field::Char(i, s) => {
                 // Each char is 4 bytes and 0000 is \0
                 let mut bytes = vec![0; (*i as usize) * 4];
-                if s.len() > *i as usize {
+                if s.chars().count() > *i as usize {
                     panic!("String is too long for char field");
                 }
                 bytes[..s.len()].copy_from_slice(s.as_bytes());
@@ -335,11 +615,13 @@ impl Field {
                 result
             }
             Field::Date(x) => x.to_le_bytes().to_vec(),
+            Field::Timestamp(x) => x.to_le_bytes().to_vec(),
             Field::Decimal(whole, scale) => {
                 let mut bytes = whole.to_le_bytes().to_vec();
                 bytes.extend(scale.to_le_bytes().to_vec());
                 bytes
             }
+            Field::Float(OrdF64(x)) => x.to_le_bytes().to_vec(),
             Field::Bool(b) => {
                 if *b {
                     vec![1_u8]
@@ -392,6 +674,12 @@ impl Field {
                 })?);
                 Ok(Field::Date(value))
             }
+            DataType::Timestamp => {
+                let value = i64::from_le_bytes(bytes.try_into().map_err(|_| {
+                    FairyError::FairyError("Failed to convert bytes to i64.".to_string())
+                })?);
+                Ok(Field::Timestamp(value))
+            }
             DataType::Decimal(_, _) => {
                 let whole = i64::from_le_bytes(bytes[0..8].try_into().map_err(|_| {
                     FairyError::FairyError("Failed to convert bytes to i64.".to_string())
@@ -401,6 +689,12 @@ impl Field {
                 })?);
                 Ok(Field::Decimal(whole, scale))
             }
+            DataType::Float64 => {
+                let value = f64::from_le_bytes(bytes.try_into().map_err(|_| {
+                    FairyError::FairyError("Failed to convert bytes to f64.".to_string())
+                })?);
+                Ok(f_float(value))
+            }
             DataType::Bool => {
                 let value = bytes[0] == 1;
                 Ok(Field::Bool(value))
@@ -422,6 +716,19 @@ impl Field {
         }
     }
 
+    /// Value of a number as a float. Decimals and large integers may lose
+    /// precision.
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            Field::BigInt(i) => Some(*i as f64),
+            Field::Int(i) => Some(*i as f64),
+            Field::SmallInt(i) => Some(*i as f64),
+            Field::Decimal(whole, scale) => Some(*whole as f64 / 10f64.powi(*scale as i32)),
+            Field::Float(OrdF64(f)) => Some(*f),
+            _ => None,
+        }
+    }
+
     pub fn unwrap_string_field(&self) -> &str {
         match self {
             Field::String(s) => s,
@@ -456,7 +763,9 @@ impl Field {
             DataType::Char(i) => Field::from_str_to_char(field, *i),
             DataType::String => Field::from_str_to_string(field),
             DataType::Decimal(p, s) => Field::from_str_to_decimal(field, *p, *s),
+            DataType::Float64 => Field::from_str_to_float(field),
             DataType::Date => Field::from_str_to_date(field),
+            DataType::Timestamp => Field::from_str_to_timestamp(field),
             DataType::Bool => Field::from_str_to_bool(field),
             DataType::Null => Field::from_str_to_null(field),
         }
@@ -523,8 +832,36 @@ impl Field {
         }
     }
 
+    /// Parses a decimal with as many fractional digits as it is written with.
+    fn parse_decimal(field: &str) -> Result<Self, FairyError> {
+        let (negative, digits) = match field.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, field),
+        };
+        let scale = digits.split_once('.').map_or(0, |(_, f)| f.len()) as u32;
+        let invalid = || FairyError::ValidationError(format!("Invalid decimal field {}", field));
+        if digits.starts_with(['+', '-']) || scale > 18 {
+            return Err(invalid());
+        }
+        match Field::from_str_to_decimal(digits, 18, scale).map_err(|_| invalid())? {
+            Field::Decimal(whole, scale) if negative => Ok(Field::Decimal(-whole, scale)),
+            decimal => Ok(decimal),
+        }
+    }
+
+    pub fn from_str_to_float(field: &str) -> Result<Self, FairyError> {
+        match field.trim().parse::<f64>() {
+            Ok(f) => Ok(f_float(f)),
+            Err(_) => Err(FairyError::ValidationError(format!(
+                "Invalid float field {}",
+                field
+            ))),
+        }
+    }
+
+    /// A value of CHAR(`length`), which holds up to `length` characters.
     pub fn from_str_to_char(field: &str, length: u8) -> Result<Self, FairyError> {
-        if field.len() > length as usize {
+        if field.chars().count() > length as usize {
             return Err(FairyError::ValidationError(format!(
                 "Invalid char field {}",
                 field
@@ -538,19 +875,184 @@ impl Field {
         Ok(Field::String(value))
     }
 
+    /// Parses a date in any of the `DATE_FORMATS`.
     pub fn from_str_to_date(field: &str) -> Result<Self, FairyError> {
-        let value = NaiveDate::parse_from_str(field, "%Y-%m-%d");
-        if let Ok(date) = value {
+        let date = DATE_FORMATS
+            .iter()
+            .find_map(|(format, _)| NaiveDate::parse_from_str(field, format).ok());
+        if let Some(date) = date {
             let days = date.signed_duration_since(base_date()).num_days();
             Ok(Field::Date(days))
         } else {
+            let formats: Vec<&str> = DATE_FORMATS.iter().map(|(_, shown)| *shown).collect();
             Err(FairyError::ValidationError(format!(
-                "Invalid date field {}",
-                field
+                "Invalid date '{}', expected one of {}",
+                field,
+                formats.join(", ")
             )))
         }
     }
 
+    /// Parses a timestamp in any of the `TIMESTAMP_FORMATS`, an RFC 3339
+    /// timestamp, whose offset is applied to get UTC, or a date.
+    pub fn from_str_to_timestamp(field: &str) -> Result<Self, FairyError> {
+        let field = field.trim();
+        let timestamp = TIMESTAMP_FORMATS
+            .iter()
+            .find_map(|(format, _)| NaiveDateTime::parse_from_str(field, format).ok())
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(field)
+                    .ok()
+                    .map(|t| t.naive_utc())
+            })
+            .or_else(|| {
+                DATE_FORMATS.iter().find_map(|(format, _)| {
+                    NaiveDate::parse_from_str(field, format)
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })
+            });
+        let micros =
+            timestamp.and_then(|t| t.signed_duration_since(base_timestamp()).num_microseconds());
+        match micros {
+            Some(micros) => Ok(Field::Timestamp(micros)),
+            None => {
+                let formats: Vec<&str> = TIMESTAMP_FORMATS
+                    .iter()
+                    .chain(DATE_FORMATS)
+                    .map(|(_, shown)| *shown)
+                    .collect();
+                Err(FairyError::ValidationError(format!(
+                    "Invalid timestamp '{}', expected RFC 3339 or one of {}",
+                    field,
+                    formats.join(", ")
+                )))
+            }
+        }
+    }
+
+    /// Converts a literal stored in or compared with a column of type `dtype`.
+    /// Dates and timestamps are written as strings, so a string becomes a date
+    /// or a timestamp for a column of that type, 1 and 0 become true and false for a bool column, and integers
+    /// and decimals become floats for a float column. Other literals are
+    /// returned as they are.
+    pub fn coerce_to(self, dtype: &DataType) -> Result<Self, FairyError> {
+        match (dtype, self) {
+            (DataType::Date, Field::String(s)) => Field::from_str_to_date(&s),
+            (DataType::Timestamp, Field::String(s)) => Field::from_str_to_timestamp(&s),
+            (DataType::Bool, Field::BigInt(i)) => match i {
+                0 | 1 => Ok(Field::Bool(i == 1)),
+                _ => Err(FairyError::ValidationError(format!(
+                    "Invalid bool {}, expected true, false, 1 or 0",
+                    i
+                ))),
+            },
+            (DataType::Float64, field @ (Field::BigInt(_) | Field::Decimal(_, _))) => {
+                Ok(f_float(field.to_f64().unwrap()))
+            }
+            (_, field) => Ok(field),
+        }
+    }
+
+    /// Converts the field to `dtype` for `CAST`. Strings are parsed like the
+    /// values of a CSV file, decimals and floats are rounded to the scale of a
+    /// decimal or to an integer, and timestamps are truncated to dates. Values
+    /// that do not fit in `dtype`, and strings that do not parse, are errors.
+    pub fn cast_to(self, dtype: &DataType) -> Result<Self, FairyError> {
+        let from = DataType::from(&self);
+        if !from.can_cast_to(dtype) {
+            return Err(FairyError::ValidationError(format!(
+                "Cannot cast {} to {}",
+                from, dtype
+            )));
+        }
+        let out_of_range = |field: &Field| {
+            FairyError::ValidationError(format!("{} is out of range for {}", field, dtype))
+        };
+        match (dtype, self) {
+            (_, Field::Null) => Ok(Field::Null),
+            (DataType::String, Field::Char(_, s)) => {
+                Ok(Field::String(s.trim_end_matches('\0').to_string()))
+            }
+            (DataType::String, field) => Ok(Field::String(field.to_string())),
+            (DataType::Char(n), field) => match field.cast_to(&DataType::String)? {
+                Field::String(s) => Field::from_str_to_char(&s, *n),
+                _ => unreachable!(),
+            },
+            (_, Field::String(s) | Field::Char(_, s)) => {
+                let s = s.trim_end_matches('\0').trim();
+                match dtype {
+                    DataType::BigInt | DataType::Int | DataType::SmallInt => {
+                        Field::BigInt(Field::parse_int_from_str(s)?).cast_to(dtype)
+                    }
+                    DataType::Decimal(_, _) => Field::parse_decimal(s)?.cast_to(dtype),
+                    _ => Field::from_str(s, &Attribute::new(String::new(), dtype.clone())),
+                }
+            }
+            (DataType::BigInt | DataType::Int | DataType::SmallInt, field) => {
+                let i = match &field {
+                    Field::BigInt(i) => Some(*i),
+                    Field::Int(i) => Some(*i as i64),
+                    Field::SmallInt(i) => Some(*i as i64),
+                    Field::Bool(b) => Some(*b as i64),
+                    Field::Decimal(whole, scale) => Some(round_div(*whole, 10i64.pow(*scale))),
+                    Field::Float(OrdF64(f)) if f.is_finite() && f.abs() < i64::MAX as f64 => {
+                        Some(f.round() as i64)
+                    }
+                    _ => None,
+                };
+                match (dtype, i) {
+                    (DataType::BigInt, Some(i)) => Ok(Field::BigInt(i)),
+                    (DataType::Int, Some(i)) => match i32::try_from(i) {
+                        Ok(i) => Ok(Field::Int(i)),
+                        Err(_) => Err(out_of_range(&field)),
+                    },
+                    (DataType::SmallInt, Some(i)) => match i16::try_from(i) {
+                        Ok(i) => Ok(Field::SmallInt(i)),
+                        Err(_) => Err(out_of_range(&field)),
+                    },
+                    _ => Err(out_of_range(&field)),
+                }
+            }
+            (DataType::Decimal(p, s), field) => {
+                let whole = match &field {
+                    Field::Decimal(whole, scale) if scale > s => {
+                        Some(round_div(*whole, 10i64.pow(scale - s)))
+                    }
+                    Field::Decimal(whole, scale) => 10i64
+                        .checked_pow(s - scale)
+                        .and_then(|m| whole.checked_mul(m)),
+                    Field::Float(OrdF64(f)) => {
+                        let whole = (f * 10f64.powi(*s as i32)).round();
+                        (whole.is_finite() && whole.abs() < i64::MAX as f64).then_some(whole as i64)
+                    }
+                    field => {
+                        let i = field.clone().cast_to(&DataType::BigInt)?.unwrap_int_field();
+                        10i64.checked_pow(*s).and_then(|m| i.checked_mul(m))
+                    }
+                };
+                // A precision too large for i64 holds any value.
+                match whole {
+                    Some(whole) if 10i64.checked_pow(*p).is_none_or(|max| whole.abs() < max) => {
+                        Ok(Field::Decimal(whole, *s))
+                    }
+                    _ => Err(out_of_range(&field)),
+                }
+            }
+            (DataType::Float64, field) => Ok(f_float(field.to_f64().unwrap())),
+            (DataType::Bool, Field::Bool(b)) => Ok(Field::Bool(b)),
+            (DataType::Bool, field) => Ok(Field::Bool(field.to_f64().unwrap() != 0.0)),
+            (DataType::Date, Field::Timestamp(micros)) => {
+                Ok(Field::Date(micros.div_euclid(MICROS_PER_DAY)))
+            }
+            (DataType::Timestamp, Field::Date(days)) => days
+                .checked_mul(MICROS_PER_DAY)
+                .map(Field::Timestamp)
+                .ok_or_else(|| out_of_range(&Field::Date(days))),
+            (_, field) => Ok(field),
+        }
+    }
+
     pub fn from_str_to_bool(field: &str) -> Result<Self, FairyError> {
         let value = field.parse::<bool>();
         if let Ok(value) = value {
@@ -575,9 +1077,46 @@ impl Field {
     }
 }
 
-impl std::fmt::Display for Field {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
+/// How fields are rendered as text in result tables, CSV and JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayFormat {
+    /// Text shown for NULL.
+    pub null_text: Cow<'static, str>,
+    /// strftime format of dates.
+    pub date_format: Cow<'static, str>,
+    /// Character between the integer and the fractional digits of decimals.
+    pub decimal_separator: char,
+}
+
+impl DisplayFormat {
+    pub const DEFAULT: DisplayFormat = DisplayFormat {
+        null_text: Cow::Borrowed("NULL"),
+        date_format: Cow::Borrowed("%Y-%m-%d"),
+        decimal_separator: '.',
+    };
+
+    /// Checks that chrono understands every specifier of a date format.
+    pub fn validate_date_format(format: &str) -> Result<(), FairyError> {
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            return Err(FairyError::ValidationError(format!(
+                "Invalid date format {}",
+                format
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for DisplayFormat {
+    fn default() -> Self {
+        DisplayFormat::DEFAULT
+    }
+}
+
+impl Field {
+    /// Renders the field as text in the given format.
+    pub fn render(&self, format: &DisplayFormat) -> String {
+        match self {
             Field::BigInt(i) => i.to_string(),
             Field::Int(i) => i.to_string(),
             Field::SmallInt(i) => i.to_string(),
@@ -585,7 +1124,19 @@ impl std::fmt::Display for Field {
             Field::String(s) => s.to_string(),
             Field::Date(i) => {
                 let date = base_date() + Duration::days(*i);
-                date.format("%Y-%m-%d").to_string()
+                let mut s = String::new();
+                // An invalid format falls back to ISO dates instead of failing.
+                if write!(s, "{}", date.format(&format.date_format)).is_err() {
+                    s = date.format("%Y-%m-%d").to_string();
+                }
+                s
+            }
+            Field::Timestamp(micros) => {
+                match base_timestamp().checked_add_signed(Duration::microseconds(*micros)) {
+                    // Fractions of a second are only shown if there are any.
+                    Some(t) => t.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+                    None => micros.to_string(),
+                }
             }
             Field::Decimal(whole, scale) => {
                 let s = whole.to_string();
@@ -605,27 +1156,365 @@ impl std::fmt::Display for Field {
 
                 // Insert the decimal point
                 let mut result = padded_s;
-                result.insert(decimal_pos, '.');
+                result.insert(decimal_pos, format.decimal_separator);
 
                 result
             }
+            Field::Float(OrdF64(f)) => f
+                .to_string()
+                .replace('.', &format.decimal_separator.to_string()),
             Field::Bool(b) => b.to_string(),
-            Field::Null => null_string(),
-        };
-        write!(f, "{}", s)
+            Field::Null => format.null_text.to_string(),
+        }
     }
 }
 
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(&DisplayFormat::DEFAULT))
+    }
+}
+
+/// Returns whether `left op right` holds. A comparison with NULL is unknown,
+/// so it does not hold.
 pub fn compare_fields(op: BinaryOp, left: &Field, right: &Field) -> bool {
+    let comparison = !matches!(op, BinaryOp::And | BinaryOp::Or);
+    if comparison && (left.is_null() || right.is_null()) {
+        return false;
+    }
     match op {
-        BinaryOp::Eq => left == right,
-        BinaryOp::Neq => left != right,
-        BinaryOp::Gt => left > right,
-        BinaryOp::Ge => left >= right,
-        BinaryOp::Lt => left < right,
-        BinaryOp::Le => left <= right,
-        BinaryOp::And => left.and(right).unwrap_bool_field(),
-        BinaryOp::Or => left.or(right).unwrap_bool_field(),
+        BinaryOp::Eq => left.sql_cmp(right).is_eq(),
+        BinaryOp::Neq => left.sql_cmp(right).is_ne(),
+        BinaryOp::Gt => left.sql_cmp(right).is_gt(),
+        BinaryOp::Ge => left.sql_cmp(right).is_ge(),
+        BinaryOp::Lt => left.sql_cmp(right).is_lt(),
+        BinaryOp::Le => left.sql_cmp(right).is_le(),
+        BinaryOp::And => matches!(left.and(right), Ok(Field::Bool(true))),
+        BinaryOp::Or => matches!(left.or(right), Ok(Field::Bool(true))),
         _ => panic!("Unsupported comparison operation"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_arithmetic() {
+        let f = f_float;
+        assert_eq!((f(1.5) + f(2.25)).unwrap(), f(3.75));
+        assert_eq!((f(1.5) + f_int(2)).unwrap(), f(3.5));
+        assert_eq!((f_int(2) - f(0.5)).unwrap(), f(1.5));
+        assert_eq!((Field::Decimal(150, 2) * f(2.0)).unwrap(), f(3.0));
+        assert_eq!((f(3.0) / Field::Decimal(15, 1)).unwrap(), f(2.0));
+        assert_eq!((f_int(1) / f(4.0)).unwrap(), f(0.25));
+        assert_eq!((f(1.0) + Field::Null).unwrap(), Field::Null);
+        assert!((f(1.0) / f_int(0)).is_err());
+        assert!((f(1.0) + f_str("a")).is_err());
+
+        // Ints and decimals mixed without a float stay exact.
+        assert_eq!(
+            (f_int(1) + Field::Decimal(25, 1)).unwrap(),
+            Field::Decimal(35, 1)
+        );
+        assert_eq!(
+            (Field::Decimal(15, 1) * f_int(3)).unwrap(),
+            Field::Decimal(45, 1)
+        );
+    }
+
+    #[test]
+    fn test_float_order_and_equality() {
+        assert_eq!(f_float(0.0), f_float(-0.0));
+        assert_eq!(f_float(f64::NAN), f_float(f64::NAN));
+        assert!(f_float(-1.5) < f_float(2.0));
+        assert!(f_float(f64::INFINITY) < f_float(f64::NAN));
+
+        use std::hash::BuildHasher;
+        let state = std::collections::hash_map::RandomState::new();
+        assert_eq!(state.hash_one(f_float(0.0)), state.hash_one(f_float(-0.0)));
+    }
+
+    #[test]
+    fn test_float_conversions() {
+        let attr = Attribute::new("x".to_string(), DataType::Float64);
+        assert_eq!(Field::from_str("2.5", &attr).unwrap(), f_float(2.5));
+        assert_eq!(Field::from_str("1e3", &attr).unwrap(), f_float(1000.0));
+        assert!(Field::from_str("abc", &attr).is_err());
+
+        let bytes = f_float(-0.125).to_bytes();
+        assert_eq!(
+            Field::from_bytes(&bytes, &DataType::Float64).unwrap(),
+            f_float(-0.125)
+        );
+        assert_eq!(
+            f_int(3).coerce_to(&DataType::Float64).unwrap(),
+            f_float(3.0)
+        );
+        assert_eq!(
+            Field::Decimal(125, 2)
+                .coerce_to(&DataType::Float64)
+                .unwrap(),
+            f_float(1.25)
+        );
+
+        let format = DisplayFormat {
+            decimal_separator: ',',
+            ..DisplayFormat::DEFAULT
+        };
+        assert_eq!(f_float(1.5).render(&format), "1,5");
+        assert_eq!(f_float(1.5).to_string(), "1.5");
+    }
+
+    #[test]
+    fn test_timestamp_conversions() {
+        // CSV files hold timestamps as ISO 8601 strings.
+        let attr = Attribute::new("t".to_string(), DataType::Timestamp);
+        let noon = Field::Timestamp(19_724 * 86_400_000_000 + 12 * 3_600_000_000);
+        for s in [
+            "2024-01-02 12:00:00",
+            "2024-01-02T12:00:00",
+            "2024-01-02T12:00",
+            "2024-01-02T12:00:00Z",
+            "2024-01-02T14:00:00+02:00",
+        ] {
+            assert_eq!(Field::from_str(s, &attr).unwrap(), noon, "{}", s);
+        }
+        assert_eq!(
+            f_timestamp("2024/01/02"),
+            Field::Timestamp(19_724 * 86_400_000_000)
+        );
+        assert_eq!(
+            f_timestamp("1969-12-31 23:59:59.5"),
+            Field::Timestamp(-500_000)
+        );
+        assert!(Field::from_str("2024-01-02 24:00:00", &attr).is_err());
+        assert!(Field::from_str("yesterday", &attr).is_err());
+
+        let bytes = noon.to_bytes();
+        assert_eq!(
+            Field::from_bytes(&bytes, &DataType::Timestamp).unwrap(),
+            noon
+        );
+        assert_eq!(
+            f_str("2024-01-02 12:00")
+                .coerce_to(&DataType::Timestamp)
+                .unwrap(),
+            noon
+        );
+        assert_eq!(noon.to_string(), "2024-01-02 12:00:00");
+        assert_eq!(
+            f_timestamp("2024-01-02 12:00:00.123").to_string(),
+            "2024-01-02 12:00:00.123"
+        );
+    }
+
+    #[test]
+    fn test_cast_numbers() {
+        let cast_as = |dtype: DataType| move |f: Field| f.cast_to(&dtype);
+        let to_dec = cast_as(DataType::Decimal(10, 2));
+        assert_eq!(to_dec(f_int(3)).unwrap(), Field::Decimal(300, 2));
+        assert_eq!(to_dec(f_int(-3)).unwrap(), Field::Decimal(-300, 2));
+        // Extra fractional digits are rounded half away from zero.
+        assert_eq!(
+            to_dec(Field::Decimal(12345, 3)).unwrap(),
+            Field::Decimal(1235, 2)
+        );
+        assert_eq!(
+            to_dec(Field::Decimal(-12345, 3)).unwrap(),
+            Field::Decimal(-1235, 2)
+        );
+        assert_eq!(
+            to_dec(Field::Decimal(15, 1)).unwrap(),
+            Field::Decimal(150, 2)
+        );
+        assert_eq!(to_dec(f_float(0.125)).unwrap(), Field::Decimal(13, 2));
+        // 10 digits with 2 after the point leave 8 before it.
+        assert!(to_dec(f_int(100_000_000)).is_err());
+        assert_eq!(
+            to_dec(f_int(99_999_999)).unwrap(),
+            Field::Decimal(9_999_999_900, 2)
+        );
+        assert!(to_dec(f_float(f64::NAN)).is_err());
+
+        let to_int = cast_as(DataType::BigInt);
+        assert_eq!(to_int(Field::Decimal(250, 2)).unwrap(), f_int(3));
+        assert_eq!(to_int(Field::Decimal(-249, 2)).unwrap(), f_int(-2));
+        assert_eq!(to_int(f_float(-2.5)).unwrap(), f_int(-3));
+        assert_eq!(to_int(Field::Bool(true)).unwrap(), f_int(1));
+        assert!(to_int(f_float(1e20)).is_err());
+        assert_eq!(to_int(Field::Null).unwrap(), Field::Null);
+        assert_eq!(
+            f_int(70_000).cast_to(&DataType::Int).unwrap(),
+            Field::Int(70_000)
+        );
+        assert!(f_int(70_000).cast_to(&DataType::SmallInt).is_err());
+        assert_eq!(
+            Field::Decimal(125, 2).cast_to(&DataType::Float64).unwrap(),
+            f_float(1.25)
+        );
+    }
+
+    #[test]
+    fn test_cast_strings() {
+        let cast = |f: Field, dtype: DataType| f.cast_to(&dtype);
+        assert_eq!(cast(f_str(" 42 "), DataType::BigInt).unwrap(), f_int(42));
+        assert_eq!(cast(f_str("-7"), DataType::BigInt).unwrap(), f_int(-7));
+        assert!(cast(f_str("4.2"), DataType::BigInt).is_err());
+        assert!(cast(f_str("x"), DataType::BigInt).is_err());
+        assert_eq!(
+            cast(f_str("-0.125"), DataType::Decimal(10, 2)).unwrap(),
+            Field::Decimal(-13, 2)
+        );
+        assert!(cast(f_str("1.2.3"), DataType::Decimal(10, 2)).is_err());
+        assert_eq!(
+            cast(f_str("2024/02/29"), DataType::Date).unwrap(),
+            f_date("2024-02-29")
+        );
+        assert!(cast(f_str("2023-02-29"), DataType::Date).is_err());
+        assert_eq!(
+            cast(f_str("2024-02-29 10:00"), DataType::Timestamp).unwrap(),
+            f_timestamp("2024-02-29 10:00:00")
+        );
+        assert_eq!(
+            cast(f_str("true"), DataType::Bool).unwrap(),
+            Field::Bool(true)
+        );
+
+        // Fixed length strings read from a page are padded.
+        let padded = Field::Char(4, "ab\0\0".to_string());
+        assert_eq!(cast(padded, DataType::String).unwrap(), f_str("ab"));
+        assert_eq!(
+            cast(Field::Char(4, "12".to_string()), DataType::BigInt).unwrap(),
+            f_int(12)
+        );
+        assert_eq!(
+            cast(f_str("abc"), DataType::Char(3)).unwrap(),
+            Field::Char(3, "abc".to_string())
+        );
+        assert!(cast(f_str("abcd"), DataType::Char(3)).is_err());
+        // The length counts characters, not bytes.
+        assert_eq!(
+            cast(f_str("añé"), DataType::Char(3)).unwrap(),
+            Field::Char(3, "añé".to_string())
+        );
+        assert!(cast(f_str("añéx"), DataType::Char(3)).is_err());
+        assert_eq!(
+            cast(Field::Decimal(150, 2), DataType::String).unwrap(),
+            f_str("1.50")
+        );
+        assert_eq!(
+            cast(f_date("2024-02-29"), DataType::String).unwrap(),
+            f_str("2024-02-29")
+        );
+    }
+
+    #[test]
+    fn test_cast_dates_and_unsupported() {
+        let t = f_timestamp("2024-02-29 23:59:59");
+        assert_eq!(
+            t.clone().cast_to(&DataType::Date).unwrap(),
+            f_date("2024-02-29")
+        );
+        assert_eq!(
+            f_timestamp("1969-12-31 23:00:00")
+                .cast_to(&DataType::Date)
+                .unwrap(),
+            f_date("1969-12-31")
+        );
+        assert_eq!(
+            f_date("2024-02-29").cast_to(&DataType::Timestamp).unwrap(),
+            f_timestamp("2024-02-29")
+        );
+        for (field, dtype) in [
+            (t, DataType::BigInt),
+            (f_date("2024-02-29"), DataType::Bool),
+            (f_float(1.0), DataType::Bool),
+            (f_int(1), DataType::Date),
+        ] {
+            let err = field.cast_to(&dtype).unwrap_err();
+            assert!(err.to_string().contains("Cannot cast"), "{}", err);
+        }
+        assert!(DataType::Null.can_cast_to(&DataType::Date));
+        assert!(!DataType::Date.can_cast_to(&DataType::Null));
+    }
+
+    #[test]
+    fn test_timestamp_arithmetic() {
+        let t = f_timestamp("2024-01-02 12:00:00");
+        let hour = f_int(3_600_000_000);
+        assert_eq!(
+            (t.clone() + hour.clone()).unwrap(),
+            f_timestamp("2024-01-02 13:00:00")
+        );
+        assert_eq!(
+            (hour.clone() + t.clone()).unwrap(),
+            f_timestamp("2024-01-02 13:00:00")
+        );
+        assert_eq!(
+            (t.clone() - hour.clone()).unwrap(),
+            f_timestamp("2024-01-02 11:00:00")
+        );
+        assert_eq!(
+            (f_timestamp("2024-01-02 13:00:00") - t.clone()).unwrap(),
+            hour
+        );
+        assert_eq!((t.clone() + Field::Null).unwrap(), Field::Null);
+        assert!((t.clone() * f_int(2)).is_err());
+        assert!((Field::Timestamp(i64::MAX) + f_int(1)).is_err());
+        assert!(f_timestamp("2024-01-01") < t);
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        let d = f_date("2024-02-28");
+        assert_eq!((d.clone() + f_int(2)).unwrap(), f_date("2024-03-01"));
+        assert_eq!((f_int(2) + d.clone()).unwrap(), f_date("2024-03-01"));
+        assert_eq!((d.clone() - f_int(59)).unwrap(), f_date("2023-12-31"));
+        assert_eq!((f_date("2024-03-01") - d.clone()).unwrap(), f_int(2));
+        assert_eq!((d.clone() - Field::Null).unwrap(), Field::Null);
+        assert!((d.clone() + f_int(i64::MAX)).is_err());
+        assert!((d.clone() + f_int(1 << 40)).is_err());
+        assert!((d * f_int(2)).is_err());
+    }
+
+    #[test]
+    fn test_sql_cmp_across_numeric_types() {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        let dec = |whole, scale| Field::Decimal(whole, scale);
+        assert_eq!(f_int(1).sql_cmp(&dec(15, 1)), Less);
+        assert_eq!(f_int(2).sql_cmp(&dec(15, 1)), Greater);
+        assert_eq!(f_int(1).sql_cmp(&dec(1000, 3)), Equal);
+        assert_eq!(dec(150, 2).sql_cmp(&dec(15, 1)), Equal);
+        assert_eq!(dec(250, 2).sql_cmp(&f_float(2.5)), Equal);
+        assert_eq!(f_float(0.5).sql_cmp(&dec(325, 2)), Less);
+        assert_eq!(Field::Int(3).sql_cmp(&f_float(2.5)), Greater);
+        assert_eq!(Field::SmallInt(7).sql_cmp(&f_int(7)), Equal);
+        assert_eq!(f_float(f64::NAN).sql_cmp(&f_int(i64::MAX)), Greater);
+        assert_eq!(f_str("a").sql_cmp(&f_str("b")), Less);
+        assert!(compare_fields(BinaryOp::Gt, &f_int(2), &dec(15, 1)));
+        assert!(compare_fields(BinaryOp::Eq, &dec(100, 2), &f_float(1.0)));
+    }
+
+    #[test]
+    fn test_sql_key_matches_sql_cmp() {
+        let fields = [
+            f_int(1),
+            Field::Int(1),
+            Field::Decimal(100, 2),
+            f_float(1.0),
+            Field::Decimal(15, 1),
+            Field::Decimal(150, 2),
+            f_float(1.5),
+            f_float(-0.0),
+            Field::SmallInt(0),
+        ];
+        for l in &fields {
+            for r in &fields {
+                if l.sql_cmp(r).is_eq() {
+                    assert_eq!(l.sql_key(), r.sql_key(), "{:?} and {:?}", l, r);
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::commands::ErrorCode;
 use crate::ids::TransactionId;
 use std::error::Error;
 use std::fmt;
@@ -7,6 +8,65 @@ pub fn c_err(s: &str) -> FairyError {
     FairyError::FairyError(s.to_string())
 }
 
+/// Kind of catalog object named in an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Database,
+    Table,
+    Column,
+    Cursor,
+    PreparedStatement,
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectKind::Database => write!(f, "Database"),
+            ObjectKind::Table => write!(f, "Table"),
+            ObjectKind::Column => write!(f, "Column"),
+            ObjectKind::Cursor => write!(f, "Cursor"),
+            ObjectKind::PreparedStatement => write!(f, "Prepared statement"),
+        }
+    }
+}
+
+/// Location of an error in the SQL text of a statement. Lines and columns
+/// start at 1 and count characters; the offset is in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourcePosition {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourcePosition {
+    /// Position of byte `offset` of `sql`, which must be on a char boundary.
+    pub fn from_offset(sql: &str, offset: usize) -> Self {
+        let before = &sql[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        SourcePosition {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Position of the character at `line` and `column` of `sql`. Positions
+    /// past the end of a line or of the text are clamped to it.
+    pub fn from_line_column(sql: &str, line: usize, column: usize) -> Self {
+        let mut line_start = 0;
+        for text in sql.split('\n').take(line.max(1) - 1) {
+            line_start = (line_start + text.len() + 1).min(sql.len());
+        }
+        let text = sql[line_start..].split('\n').next().unwrap_or("");
+        let offset = text
+            .char_indices()
+            .nth(column.max(1) - 1)
+            .map_or(text.len(), |(i, _)| i);
+        Self::from_offset(sql, line_start + offset)
+    }
+}
+
 /// Custom error type.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FairyError {
@@ -32,6 +92,76 @@ pub enum FairyError {
     ContainerDoesNotExist,
     /// Invalid Operation
     InvalidOperation,
+    /// An object with the given name already exists
+    AlreadyExists(ObjectKind, String),
+    /// No object with the given name exists
+    NotFound(ObjectKind, String),
+    /// The SQL text could not be parsed.
+    SyntaxError(String),
+    /// A column reference matches no column in scope, with the closest name
+    /// that does if there is one.
+    UnknownColumn {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// An error located at a position of the statement's SQL text.
+    At(Box<FairyError>, SourcePosition),
+    /// The server was too busy to run the statement.
+    ServerBusy(String),
+    /// A value of `size` bytes is larger than the `limit` of max_field_size,
+    /// found on `line` of an imported file if it comes from one.
+    FieldTooLarge {
+        size: usize,
+        limit: usize,
+        line: Option<usize>,
+    },
+    /// Rows would share the `values` of the unique key made of `columns`.
+    DuplicateKey {
+        columns: Vec<String>,
+        values: Vec<String>,
+    },
+    /// The query was cancelled while it ran.
+    QueryCancelled,
+    /// The query ran for `elapsed_ms`, longer than its timeout, and was
+    /// cancelled while `operator` read its input.
+    QueryTimeout { elapsed_ms: u64, operator: String },
+}
+
+impl FairyError {
+    /// Returns the stable error code for errors that clients may want to
+    /// handle programmatically, or None for generic errors.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            FairyError::AlreadyExists(..) => Some(ErrorCode::AlreadyExists),
+            FairyError::NotFound(..) | FairyError::UnknownColumn { .. } => {
+                Some(ErrorCode::NotFound)
+            }
+            FairyError::SyntaxError(_) => Some(ErrorCode::Syntax),
+            FairyError::ServerBusy(_) => Some(ErrorCode::ServerBusy),
+            FairyError::FieldTooLarge { .. } => Some(ErrorCode::FieldTooLarge),
+            FairyError::DuplicateKey { .. } => Some(ErrorCode::DuplicateKey),
+            FairyError::QueryCancelled => Some(ErrorCode::QueryCancelled),
+            FairyError::QueryTimeout { .. } => Some(ErrorCode::QueryTimeout),
+            FairyError::At(e, _) => e.error_code(),
+            _ => None,
+        }
+    }
+
+    /// Attaches the position in the SQL text the error refers to.
+    pub fn at(self, position: SourcePosition) -> FairyError {
+        match self {
+            FairyError::At(e, _) => FairyError::At(e, position),
+            e => FairyError::At(Box::new(e), position),
+        }
+    }
+
+    /// Returns the position in the SQL text the error refers to, if known.
+    pub fn position(&self) -> Option<SourcePosition> {
+        match self {
+            FairyError::At(_, position) => Some(*position),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for FairyError {
@@ -51,6 +181,36 @@ impl fmt::Display for FairyError {
                 FairyError::StorageError => "Storage Error".to_string(),
                 FairyError::ContainerDoesNotExist => "Container Does Not Exist".to_string(),
                 FairyError::InvalidOperation => "Invalid Operation".to_string(),
+                FairyError::AlreadyExists(kind, name) =>
+                    format!("{} {} already exists", kind, name),
+                FairyError::NotFound(kind, name) => format!("{} {} does not exist", kind, name),
+                FairyError::SyntaxError(s) => format!("SQL error: {}", s),
+                FairyError::UnknownColumn { name, suggestion } => match suggestion {
+                    Some(s) => format!("Column {} does not exist, did you mean {}?", name, s),
+                    None => format!("Column {} does not exist", name),
+                },
+                FairyError::At(e, _) => e.to_string(),
+                FairyError::ServerBusy(s) => format!("Server busy: {}", s),
+                FairyError::FieldTooLarge { size, limit, line } => {
+                    let line = line.map_or(String::new(), |line| format!(" on line {}", line));
+                    format!(
+                        "Value of {} bytes{} exceeds max_field_size of {} bytes",
+                        size, line, limit
+                    )
+                }
+                FairyError::DuplicateKey { columns, values } => format!(
+                    "Duplicate key ({})=({}) violates a unique constraint",
+                    columns.join(", "),
+                    values.join(", ")
+                ),
+                FairyError::QueryCancelled => "cancelled".to_string(),
+                FairyError::QueryTimeout {
+                    elapsed_ms,
+                    operator,
+                } => format!(
+                    "Query timed out after {} ms while running {}",
+                    elapsed_ms, operator
+                ),
             }
         )
     }
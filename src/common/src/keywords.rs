@@ -0,0 +1,164 @@
+//! SQL keywords that cannot be used as table or column names without quoting.
+
+/// Keywords that must be quoted when used as a table or column name, sorted.
+/// Covers the reserved words of standard SQL that this database parses plus
+/// the keywords the SQL parser rejects or misreads as an unquoted identifier.
+pub const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "and",
+    "any",
+    "array",
+    "array_agg",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "cast",
+    "ceil",
+    "check",
+    "close",
+    "column",
+    "comment",
+    "constraint",
+    "convert",
+    "create",
+    "cross",
+    "cube",
+    "current_catalog",
+    "current_date",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "cursor",
+    "declare",
+    "default",
+    "delete",
+    "desc",
+    "directory",
+    "distinct",
+    "drop",
+    "else",
+    "end",
+    "except",
+    "exists",
+    "explain",
+    "extract",
+    "false",
+    "fetch",
+    "floor",
+    "for",
+    "foreign",
+    "from",
+    "full",
+    "group",
+    "having",
+    "in",
+    "inner",
+    "insert",
+    "intersect",
+    "interval",
+    "into",
+    "is",
+    "join",
+    "json_table",
+    "lateral",
+    "left",
+    "like",
+    "limit",
+    "listagg",
+    "local",
+    "localtime",
+    "localtimestamp",
+    "match",
+    "natural",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "outer",
+    "overlay",
+    "primary",
+    "references",
+    "right",
+    "rollup",
+    "safe_cast",
+    "select",
+    "session_user",
+    "set",
+    "some",
+    "struct",
+    "substring",
+    "table",
+    "then",
+    "to",
+    "top",
+    "trim",
+    "true",
+    "try_cast",
+    "union",
+    "unique",
+    "unnest",
+    "update",
+    "user",
+    "using",
+    "values",
+    "when",
+    "where",
+    "with",
+];
+
+/// Returns true if `name` is a reserved keyword, ignoring case.
+pub fn is_reserved_keyword(name: &str) -> bool {
+    RESERVED_KEYWORDS
+        .binary_search(&name.to_ascii_lowercase().as_str())
+        .is_ok()
+}
+
+/// Returns `name` as it has to be written in a SQL statement: in double quotes
+/// if it is a reserved keyword or not a plain identifier, as is otherwise.
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain && !is_reserved_keyword(name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Strips the double quotes around an identifier written as in a SQL statement.
+/// Unquoted identifiers are returned as is.
+pub fn unquote_identifier(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords_sorted() {
+        assert!(RESERVED_KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("orders"), "orders");
+        assert_eq!(quote_identifier("Order"), "\"Order\"");
+        assert_eq!(quote_identifier("select"), "\"select\"");
+        assert_eq!(quote_identifier("a b"), "\"a b\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        for name in ["orders", "select", "a b", "a\"b"] {
+            assert_eq!(unquote_identifier(&quote_identifier(name)), name);
+        }
+    }
+}
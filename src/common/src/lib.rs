@@ -14,15 +14,20 @@ pub mod commands;
 pub mod datatypes;
 pub mod error;
 pub mod ids;
+pub mod keywords;
+pub mod metrics;
 pub mod physical;
 pub mod rwlatch;
 pub mod table;
+pub mod table_lock;
 pub use table::TableSchema;
 pub mod traits;
 pub mod tuple;
 pub use tuple::Tuple;
+pub mod txn_stamps;
 pub mod query;
 pub mod util;
+pub mod workload;
 pub use util::common_test_util as testutil;
 
 /// Page size in bytes
@@ -32,6 +37,11 @@ pub const PAGE_SIZE: usize = 4096;
 pub const PAGE_SLOTS: usize = 50;
 // Maximum number of columns in a table
 pub const MAX_COLUMNS: usize = 100;
+/// Largest serialized tuple a heap page can hold: the page minus the page
+/// header, the heap page metadata and one slot entry. Tuples do not span pages.
+pub const MAX_TUPLE_SIZE: usize = PAGE_SIZE - 28;
+/// Maximum length of a table or column name in bytes
+pub const MAX_IDENTIFIER_LEN: usize = 63;
 
 // dir name of manager table
 pub const MANAGERS_DIR_NAME: &str = "managers";
@@ -56,4 +66,5 @@ pub use crate::error::{ConversionError, FairyError};
 
 pub use crate::datatypes::{DataType, Field};
 pub use crate::query::operation::{AggOp, BinaryOp};
+pub use crate::query::scalar_func::ScalarFunc;
 pub use query::query_result::QueryResult;
@@ -0,0 +1,312 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Each power of two is split into `2^SUB_BUCKET_BITS` linear sub-buckets, so a
+/// recorded value is reported with a relative error of at most 1/8.
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+/// Values below this are counted exactly, one bucket per value.
+const LINEAR_LIMIT: u64 = 2 * SUB_BUCKETS;
+const NUM_BUCKETS: usize =
+    LINEAR_LIMIT as usize + (64 - SUB_BUCKET_BITS as usize - 1) * SUB_BUCKETS as usize;
+
+/// Log-bucketed latency histogram in the style of HDR histograms.
+/// Recording is a couple of relaxed atomic adds and never takes a lock.
+/// Values are nanoseconds.
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; NUM_BUCKETS],
+    max: AtomicU64,
+}
+
+/// Point-in-time summary of a histogram. Latencies are in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < LINEAR_LIMIT {
+        return value as usize;
+    }
+    let exp = 63 - value.leading_zeros();
+    let sub = (value >> (exp - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    (LINEAR_LIMIT + (exp - SUB_BUCKET_BITS - 1) as u64 * SUB_BUCKETS + sub) as usize
+}
+
+/// Largest value that falls into the bucket at `index`.
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_LIMIT {
+        return index;
+    }
+    let exp = (index - LINEAR_LIMIT) / SUB_BUCKETS + SUB_BUCKET_BITS as u64 + 1;
+    let sub = (index - LINEAR_LIMIT) % SUB_BUCKETS;
+    let width = 1u64 << (exp - SUB_BUCKET_BITS as u64);
+    ((SUB_BUCKETS + sub) << (exp - SUB_BUCKET_BITS as u64)).saturating_add(width - 1)
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub const fn new() -> Self {
+        LatencyHistogram {
+            buckets: [const { AtomicU64::new(0) }; NUM_BUCKETS],
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        self.record_nanos(latency.as_nanos().try_into().unwrap_or(u64::MAX));
+    }
+
+    pub fn record_nanos(&self, nanos: u64) {
+        self.buckets[bucket_index(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Clears all recorded values. Values recorded concurrently with a reset may
+    /// or may not survive it.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Returns the value (in nanoseconds) below which `quantile` of the recorded
+    /// values fall, rounded up to the bucket boundary and capped at the maximum.
+    pub fn percentile(&self, quantile: f64) -> u64 {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+        let rank = ((quantile * total as f64).ceil() as u64).clamp(1, total);
+        let max = self.max.load(Ordering::Relaxed);
+        let mut seen = 0;
+        for (i, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_upper_bound(i).min(max);
+            }
+        }
+        max
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            count: self.count(),
+            p50_us: self.percentile(0.5) / 1000,
+            p90_us: self.percentile(0.9) / 1000,
+            p99_us: self.percentile(0.99) / 1000,
+            max_us: self.max.load(Ordering::Relaxed) / 1000,
+        }
+    }
+}
+
+/// Statement types the query latency histogram is tagged with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    Select,
+    Insert,
+    Ddl,
+    Other,
+}
+
+impl StatementKind {
+    pub const ALL: [StatementKind; 4] = [
+        StatementKind::Select,
+        StatementKind::Insert,
+        StatementKind::Ddl,
+        StatementKind::Other,
+    ];
+
+    /// Classifies a SQL statement by its leading keyword.
+    pub fn from_sql(sql: &str) -> Self {
+        let keyword = sql.split_whitespace().next().unwrap_or_default();
+        let is = |k: &str| keyword.eq_ignore_ascii_case(k);
+        if is("select") || is("with") {
+            StatementKind::Select
+        } else if is("insert") {
+            StatementKind::Insert
+        } else if ["create", "drop", "alter", "truncate", "comment"]
+            .iter()
+            .any(|k| is(k))
+        {
+            StatementKind::Ddl
+        } else {
+            StatementKind::Other
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatementKind::Select => "select",
+            StatementKind::Insert => "insert",
+            StatementKind::Ddl => "ddl",
+            StatementKind::Other => "other",
+        }
+    }
+}
+
+/// End-to-end query latency, one histogram per statement type.
+#[derive(Default)]
+pub struct QueryMetrics {
+    histograms: [LatencyHistogram; 4],
+    /// Statements run again after their transaction was aborted, counted once
+    /// per retry.
+    pub statement_retries: AtomicU64,
+}
+
+impl QueryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, kind: StatementKind, latency: Duration) {
+        self.histograms[kind as usize].record(latency);
+    }
+
+    pub fn get(&self, kind: StatementKind) -> &LatencyHistogram {
+        &self.histograms[kind as usize]
+    }
+
+    pub fn reset(&self) {
+        self.histograms.iter().for_each(LatencyHistogram::reset);
+        self.statement_retries.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Storage latencies. The buffer pool and its files are shared by all databases
+/// of a process, so these histograms are process-wide.
+pub struct StorageMetrics {
+    /// Time to bring a page that missed in the buffer pool in from disk.
+    pub page_fault: LatencyHistogram,
+    pub fsync: LatencyHistogram,
+    /// Time buffer pool requests spent retrying after a transient failure,
+    /// recorded once per request that succeeded after at least one retry.
+    pub bp_retry: LatencyHistogram,
+    /// Time spent retrying by buffer pool requests that ran out of retry budget.
+    pub bp_retry_exhausted: LatencyHistogram,
+    /// Records skipped by tolerant scans because they did not decode against
+    /// the schema of their table.
+    pub corrupt_records: AtomicU64,
+}
+
+static STORAGE_METRICS: StorageMetrics = StorageMetrics {
+    page_fault: LatencyHistogram::new(),
+    fsync: LatencyHistogram::new(),
+    bp_retry: LatencyHistogram::new(),
+    bp_retry_exhausted: LatencyHistogram::new(),
+    corrupt_records: AtomicU64::new(0),
+};
+
+pub fn storage_metrics() -> &'static StorageMetrics {
+    &STORAGE_METRICS
+}
+
+impl StorageMetrics {
+    pub fn reset(&self) {
+        self.page_fault.reset();
+        self.fsync.reset();
+        self.bp_retry.reset();
+        self.bp_retry_exhausted.reset();
+        self.corrupt_records.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Acquisitions of `RwLatch`es, recorded only with the `latch_stats` feature.
+/// Each acquisition records the time it waited for the latch, so the count is
+/// the number of acquisitions and the maximum is the longest wait.
+pub struct LatchMetrics {
+    pub shared: LatencyHistogram,
+    pub exclusive: LatencyHistogram,
+}
+
+static LATCH_METRICS: LatchMetrics = LatchMetrics {
+    shared: LatencyHistogram::new(),
+    exclusive: LatencyHistogram::new(),
+};
+
+pub fn latch_metrics() -> &'static LatchMetrics {
+    &LATCH_METRICS
+}
+
+impl LatchMetrics {
+    pub fn reset(&self) {
+        self.shared.reset();
+        self.exclusive.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        let mut prev = None;
+        for value in (0..4096).chain([u64::MAX / 3, u64::MAX]) {
+            let i = bucket_index(value);
+            assert!(i < NUM_BUCKETS);
+            assert!(value <= bucket_upper_bound(i));
+            // The upper bound is within 1/8 of the value.
+            assert!(bucket_upper_bound(i) - value <= value / SUB_BUCKETS);
+            if let Some(p) = prev {
+                assert!(i >= p);
+            }
+            prev = Some(i);
+        }
+    }
+
+    #[test]
+    fn test_percentiles_reflect_tail() {
+        let h = LatencyHistogram::new();
+        assert_eq!(h.snapshot(), HistogramSnapshot::default());
+        for i in 0..980 {
+            h.record(Duration::from_micros(100 + i % 10));
+        }
+        for _ in 0..20 {
+            h.record(Duration::from_millis(50));
+        }
+        let s = h.snapshot();
+        assert_eq!(s.count, 1000);
+        assert!((100..=125).contains(&s.p50_us), "{:?}", s);
+        assert!((100..=125).contains(&s.p90_us), "{:?}", s);
+        assert_eq!(s.p99_us, 50_000);
+        assert_eq!(s.max_us, 50_000);
+
+        h.reset();
+        assert_eq!(h.snapshot(), HistogramSnapshot::default());
+    }
+
+    #[test]
+    fn test_statement_kind() {
+        assert_eq!(StatementKind::from_sql("select 1"), StatementKind::Select);
+        assert_eq!(
+            StatementKind::from_sql("  INSERT INTO t VALUES (1)"),
+            StatementKind::Insert
+        );
+        assert_eq!(
+            StatementKind::from_sql("CREATE TABLE t (a INT)"),
+            StatementKind::Ddl
+        );
+        assert_eq!(StatementKind::from_sql("DESCRIBE t"), StatementKind::Other);
+    }
+}
@@ -19,7 +19,7 @@ const STARTING_COL_ID: ColumnId = 10000;
 /// -> project(@10003)
 ///   -> select(@10003=1 && @10000=2)
 ///    -> rename(@10003 <- @0, @10000 <- @1)
-///     -> scan("t1", [@0, @1])
+///     -> scan(t1, [@0, @1])
 /// ColIdGenerator will generate unique IDs for each column in the table. It assigns
 /// IDs starting from 10000. The IDs are used to represent columns in the logical plan.
 /// These IDs will be useful for self-joins, and other queries that contain temporary
@@ -2,10 +2,85 @@ use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use serde::Deserialize;
 use serde_json;
 
+use crate::datatypes::DisplayFormat;
+use crate::error::FairyError;
+
+/// How a parallel hash join distributes its build side across workers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HashJoinDistribution {
+    /// Broadcast small build sides and partition large ones.
+    #[default]
+    Auto,
+    /// Build one hash table and share it read-only with every worker.
+    Broadcast,
+    /// Partition both sides by key hash, each worker joins one partition.
+    Partitioned,
+}
+
+/// Where a heap page places the bytes of a new or grown value. Slot ids do not
+/// depend on the policy: a value always gets the lowest free slot id.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitPolicy {
+    /// Append below the lowest value in the page. Space freed by deletes is only
+    /// reused once the page runs out of room there and is compacted. Inserts are
+    /// cheap, but a page with many deletes is compacted often.
+    #[default]
+    FirstFit,
+    /// Place the value in the smallest free region that holds it, which can be a
+    /// hole left by a delete. The page is compacted only if no region is large
+    /// enough.
+    BestFit,
+}
+
+impl std::fmt::Display for FitPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitPolicy::FirstFit => write!(f, "first-fit"),
+            FitPolicy::BestFit => write!(f, "best-fit"),
+        }
+    }
+}
+
+/// When the heap store forces written pages to disk with fsync.
+///
+/// A statement commits when it returns to the client. What a crash (of the
+/// process or the machine) leaves on disk depends on the level:
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Durability {
+    /// Never fsync. Pages reach the OS when they are evicted or at shutdown. A
+    /// process crash loses nothing the OS already has, but a machine crash can
+    /// lose any committed write and can leave a file with only some of its pages
+    /// updated.
+    #[default]
+    None,
+    /// Commits arriving within `durability_batch_delay_ms` of each other share
+    /// one write-back of dirty pages and one fsync, and a commit returns only
+    /// after that fsync. Checkpoints (buffer pool flushes) fsync as well. After a
+    /// crash every commit that returned is on disk; commits still waiting for
+    /// their group may or may not be.
+    Batch,
+    /// Every commit writes back dirty pages and fsyncs before it returns, as do
+    /// checkpoints. After a crash every commit that returned is on disk.
+    Strict,
+}
+
+impl std::fmt::Display for Durability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Durability::None => write!(f, "none"),
+            Durability::Batch => write!(f, "batch"),
+            Durability::Strict => write!(f, "strict"),
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, Debug, Parser)]
 #[serde(default)]
 pub struct ServerConfig {
@@ -28,6 +103,14 @@ pub struct ServerConfig {
     /// Log level
     #[clap(short = 'v', long = "log_level", default_value = "warn")]
     pub log_level: String,
+    /// Write the tracing spans of statements to this file as JSON lines. Log
+    /// lines are then also written there, inside the spans they belong to
+    #[clap(long = "trace_file")]
+    pub trace_file: Option<PathBuf>,
+    /// Count the rows, next calls and time of the operators of every query, for
+    /// EXPLAIN ANALYZE and \last_profile (include for val = true)
+    #[clap(long = "profile_operators")]
+    pub profile_operators: bool,
     /// Query subsumption detection flag (include for val = true)
     #[clap(short = 'q', long = "query-subplan-detection")]
     pub subsumption_detection: bool,
@@ -37,6 +120,121 @@ pub struct ServerConfig {
     /// Purge entire db_state on shutdown (include for val = true, otherwise will attempt to persist)
     #[clap(long = "shutdown-purge")]
     pub shutdown_purge: bool,
+    /// Capture every executed statement to this file so the workload can be replayed
+    #[clap(long = "capture_file")]
+    pub capture_file: Option<PathBuf>,
+    /// How long a statement waits for a table locked by another statement, e.g. a
+    /// DROP TABLE waiting for running scans or a scan waiting for a DROP TABLE
+    #[clap(long = "ddl_lock_timeout_ms", default_value = "5000")]
+    pub ddl_lock_timeout_ms: u64,
+    /// How many times a statement outside an explicit transaction runs when its
+    /// transaction is aborted, e.g. by a deadlock. 1 disables retries
+    #[clap(long = "statement_retry_attempts", default_value = "3")]
+    pub statement_retry_attempts: u32,
+    /// Number of worker threads used by a hash join. With 1 the join runs serially
+    #[clap(long = "join_workers", default_value = "1")]
+    pub join_workers: usize,
+    /// Build side distribution of hash joins running on more than one worker
+    #[clap(long = "hash_join_distribution", value_enum, default_value = "auto")]
+    pub hash_join_distribution: HashJoinDistribution,
+    /// How long a page request keeps retrying when a frame latch is held by another thread
+    #[clap(long = "bp_latch_retry_ms", default_value = "1000")]
+    pub bp_latch_retry_ms: u64,
+    /// How long a page request keeps retrying when every buffer pool frame is in use.
+    /// After this the request fails with a buffer pool exhausted error
+    #[clap(long = "bp_evict_retry_ms", default_value = "10000")]
+    pub bp_evict_retry_ms: u64,
+    /// Number of cursors a session can have open at once
+    #[clap(long = "max_cursors_per_session", default_value = "16")]
+    pub max_cursors_per_session: usize,
+    /// How many times larger than estimated the materialized input of a join must be
+    /// before the join changes strategy at runtime. 0 disables adaptive joins
+    #[clap(long = "adaptive_join_factor", default_value = "10")]
+    pub adaptive_join_factor: f64,
+    /// When written pages are forced to disk with fsync: none, batch or strict
+    #[clap(long = "durability", value_enum, default_value = "none")]
+    pub durability: Durability,
+    /// Longest time a commit waits for other commits to share its fsync with the
+    /// batch durability level
+    #[clap(long = "durability_batch_delay_ms", default_value = "10")]
+    pub durability_batch_delay_ms: u64,
+    /// Where heap pages place new values: first-fit or best-fit
+    #[clap(long = "page_fit_policy", value_enum, default_value = "first-fit")]
+    pub page_fit_policy: FitPolicy,
+    /// Text results show for NULL unless a session changes it
+    #[clap(long = "null_text", default_value = "NULL")]
+    pub null_text: String,
+    /// strftime format results show dates in unless a session changes it
+    #[clap(long = "date_format", default_value = "%Y-%m-%d")]
+    pub date_format: String,
+    /// Decimal separator results show unless a session changes it
+    #[clap(long = "decimal_separator", default_value = ".")]
+    pub decimal_separator: char,
+    /// How often the background maintenance thread runs
+    #[clap(long = "maintenance_interval_ms", default_value = "10000")]
+    pub maintenance_interval_ms: u64,
+    /// Pages automatic compaction may visit per minute across all tables of a
+    /// database. 0 disables automatic compaction
+    #[clap(long = "compaction_pages_per_minute", default_value = "600")]
+    pub compaction_pages_per_minute: usize,
+    /// Automatic compaction pauses while a database runs more statements per
+    /// second than this
+    #[clap(long = "compaction_max_queries_per_sec", default_value = "50")]
+    pub compaction_max_queries_per_sec: f64,
+    /// Number of statements of all clients that run at once. Others wait in a
+    /// queue for their turn. 0 runs every statement right away
+    #[clap(long = "max_running_statements", default_value = "32")]
+    pub max_running_statements: usize,
+    /// Number of statements that may wait for their turn. Beyond it statements
+    /// fail with a server busy error
+    #[clap(long = "max_queued_statements", default_value = "128")]
+    pub max_queued_statements: usize,
+    /// How long a statement waits for its turn before it fails with a server
+    /// busy error
+    #[clap(long = "admission_timeout_ms", default_value = "10000")]
+    pub admission_timeout_ms: u64,
+    /// How long a query may run before it is cancelled. Sessions can change it
+    /// with SET timeout. 0 means no limit
+    #[clap(long = "query_timeout_ms", default_value = "0")]
+    pub query_timeout_ms: u64,
+    /// How long ANALYZE may scan a table before it is cancelled, instead of the
+    /// query timeout of the session. 0 means no limit
+    #[clap(long = "analyze_timeout_ms", default_value = "0")]
+    pub analyze_timeout_ms: u64,
+    /// Directory operators spill temporary data to. Defaults to the tmp directory
+    /// of server_path, another volume keeps spills from filling the data disk
+    #[clap(long = "temp_path")]
+    pub temp_path: Option<PathBuf>,
+    /// Bytes of temporary data the statements of all clients may spill at once.
+    /// A statement that needs more fails. 0 means no limit
+    #[clap(long = "temp_space_budget_bytes", default_value = "0")]
+    pub temp_space_budget_bytes: u64,
+    /// Bytes of rows a sort keeps in memory. Larger inputs are sorted in runs
+    /// spilled to the temporary space and merged
+    #[clap(long = "sort_memory_bytes", default_value = "67108864")]
+    pub sort_memory_bytes: usize,
+    /// Rows a spool keeps in memory, e.g. of a scan shared by several parts of
+    /// a plan or the result of a subquery. The rest spill to the temporary space
+    #[clap(long = "spool_memory_rows", default_value = "100000")]
+    pub spool_memory_rows: usize,
+    /// Most rows a hash join is expected to build its table over. Equi-joins
+    /// whose inputs are both estimated larger are planned as sort-merge joins,
+    /// whose sorts spill to the temporary space. 0 means no limit
+    #[clap(long = "hash_join_max_build_rows", default_value = "1000000")]
+    pub hash_join_max_build_rows: usize,
+    /// Largest LIMIT of an ORDER BY that keeps the first rows in a heap while
+    /// it reads its input, instead of sorting all of it. 0 always sorts
+    #[clap(long = "top_n_max_rows", default_value = "10000")]
+    pub top_n_max_rows: usize,
+    /// Largest string value, in bytes, that statements, imports and requests
+    /// may carry
+    #[clap(long = "max_field_size", default_value = "16777216")]
+    pub max_field_size: usize,
+    /// Most rows of a select result the server sends in one message. Larger
+    /// results are sent in chunks of this many rows. 0 sends every result in
+    /// one message
+    #[clap(long = "result_chunk_rows", default_value = "10000")]
+    pub result_chunk_rows: usize,
 }
 
 impl Default for ServerConfig {
@@ -47,9 +245,42 @@ impl Default for ServerConfig {
             db_path: "fairy_data/persist/default/".into(),
             log_file: "".to_owned(),
             log_level: "warning".to_owned(),
+            trace_file: None,
+            profile_operators: false,
             subsumption_detection: false,
             config_file: None,
             shutdown_purge: false,
+            capture_file: None,
+            ddl_lock_timeout_ms: 5000,
+            statement_retry_attempts: 3,
+            join_workers: 1,
+            hash_join_distribution: HashJoinDistribution::Auto,
+            bp_latch_retry_ms: 1000,
+            bp_evict_retry_ms: 10000,
+            max_cursors_per_session: 16,
+            adaptive_join_factor: 10.0,
+            durability: Durability::None,
+            durability_batch_delay_ms: 10,
+            page_fit_policy: FitPolicy::FirstFit,
+            null_text: DisplayFormat::DEFAULT.null_text.into_owned(),
+            date_format: DisplayFormat::DEFAULT.date_format.into_owned(),
+            decimal_separator: DisplayFormat::DEFAULT.decimal_separator,
+            maintenance_interval_ms: 10000,
+            compaction_pages_per_minute: 600,
+            compaction_max_queries_per_sec: 50.0,
+            max_running_statements: 32,
+            max_queued_statements: 128,
+            admission_timeout_ms: 10000,
+            query_timeout_ms: 0,
+            analyze_timeout_ms: 0,
+            temp_path: None,
+            temp_space_budget_bytes: 0,
+            sort_memory_bytes: 64 * 1024 * 1024,
+            spool_memory_rows: 100_000,
+            hash_join_max_build_rows: 1_000_000,
+            top_n_max_rows: 10_000,
+            max_field_size: 16 * 1024 * 1024,
+            result_chunk_rows: 10000,
         }
     }
 }
@@ -60,13 +291,38 @@ impl ServerConfig {
     }
 
     pub fn temporary() -> Self {
-        let base_dir = tempfile::tempdir().unwrap().into_path();
+        let base_dir = tempfile::tempdir().unwrap().keep();
         ServerConfig {
             db_path: base_dir,
             ..ServerConfig::default()
         }
     }
 
+    /// Directory operators spill temporary data to.
+    pub fn temp_dir(&self) -> PathBuf {
+        match &self.temp_path {
+            Some(path) => path.clone(),
+            None => self.db_path.join("tmp"),
+        }
+    }
+
+    /// Format sessions render results in until they change it. An invalid
+    /// date format is replaced by the default one.
+    pub fn display_format(&self) -> DisplayFormat {
+        let date_format = match DisplayFormat::validate_date_format(&self.date_format) {
+            Ok(()) => self.date_format.clone().into(),
+            Err(e) => {
+                warn!("{}, using the default date format", e);
+                DisplayFormat::DEFAULT.date_format
+            }
+        };
+        DisplayFormat {
+            null_text: self.null_text.clone().into(),
+            date_format,
+            decimal_separator: self.decimal_separator,
+        }
+    }
+
     /// Loads configuration from a JSON file, using default values for any unspecified options.
     ///
     /// # Arguments
@@ -142,7 +398,7 @@ impl ServerConfig {
     }
 }
 
-#[derive(Parser, Deserialize, Debug)]
+#[derive(Clone, Parser, Deserialize, Debug)]
 pub struct ClientConfig {
     /// Server IP address
     #[clap(short = 'o', long = "host", default_value = "")]
@@ -153,6 +409,23 @@ pub struct ClientConfig {
     /// Optional script to run
     #[clap(short = 's', long = "script", default_value = "")]
     pub script: String,
+    /// Keep running the script after a statement fails and report all failures
+    #[clap(long = "continue_on_error")]
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /// Database to connect to once the connection is established
+    #[clap(short = 'd', long = "database", default_value = "")]
+    #[serde(default)]
+    pub database: String,
+    /// Connection URL of the form fairydb://host:port/dbname. Overrides the
+    /// host, port and database options.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// How long to wait for the response to a request before giving up, 0 to
+    /// wait for as long as it takes
+    #[clap(long = "response_timeout_ms", default_value = "0")]
+    #[serde(default)]
+    pub response_timeout_ms: u64,
 }
 
 impl Default for ClientConfig {
@@ -161,33 +434,84 @@ impl Default for ClientConfig {
             host: "127.0.0.1".to_owned(),
             port: "3333".to_owned(),
             script: "".to_owned(),
+            continue_on_error: false,
+            database: "".to_owned(),
+            url: None,
+            response_timeout_ms: 0,
         }
     }
 }
 
+/// Scheme of client connection URLs.
+const URL_SCHEME: &str = "fairydb://";
+
 impl ClientConfig {
     pub fn resolved() -> Self {
         let cli_config = ClientConfig::parse();
+        match cli_config.with_url() {
+            Ok(config) => config.with_defaults(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
 
-        if !cli_config.host.is_empty() && !cli_config.port.is_empty() {
-            return cli_config;
+    /// Parses a connection URL of the form `fairydb://host[:port][/dbname]`.
+    /// Missing parts take the default host and port and select no database.
+    pub fn from_url(url: &str) -> Result<Self, FairyError> {
+        ClientConfig {
+            url: Some(url.to_owned()),
+            ..Default::default()
         }
+        .with_url()
+        .map(ClientConfig::with_defaults)
+    }
 
-        // Load from ServerConfig
-        // let server_config = ServerConfig::from_command_line();
+    /// Replaces the host, port and database with the parts of the URL if one
+    /// was given.
+    fn with_url(self) -> Result<Self, FairyError> {
+        let url = match &self.url {
+            Some(url) => url,
+            None => return Ok(self),
+        };
+        let invalid = |reason: &str| {
+            FairyError::ValidationError(format!("Invalid connection URL {}: {}", url, reason))
+        };
+        let rest = url
+            .strip_prefix(URL_SCHEME)
+            .ok_or_else(|| invalid("expected it to start with fairydb://"))?;
+        let (address, database) = rest.split_once('/').unwrap_or((rest, ""));
+        let (host, port) = address.split_once(':').unwrap_or((address, ""));
+        if !port.is_empty() && port.parse::<u16>().is_err() {
+            return Err(invalid("the port is not a number"));
+        }
+        if database.contains('/') {
+            return Err(invalid("the database name contains '/'"));
+        }
+        Ok(ClientConfig {
+            host: host.to_owned(),
+            port: port.to_owned(),
+            database: database.to_owned(),
+            ..self
+        })
+    }
 
+    /// Fills in the default host and port if they were not given.
+    fn with_defaults(self) -> Self {
+        let default = ClientConfig::default();
         ClientConfig {
-            host: if !cli_config.host.is_empty() {
-                cli_config.host
+            host: if !self.host.is_empty() {
+                self.host
             } else {
-                "127.0.0.1".to_owned()
+                default.host
             },
-            port: if !cli_config.port.is_empty() {
-                cli_config.port
+            port: if !self.port.is_empty() {
+                self.port
             } else {
-                "3333".to_owned()
+                default.port
             },
-            script: cli_config.script,
+            ..self
         }
     }
 }
@@ -294,4 +618,29 @@ mod tests {
         assert_eq!(config.log_level, "warning");
         assert!(!config.subsumption_detection);
     }
+
+    #[test]
+    fn test_client_config_from_url() {
+        let config = ClientConfig::from_url("fairydb://localhost:4444/mydb").unwrap();
+        assert_eq!(config.host, "localhost");
+        assert_eq!(config.port, "4444");
+        assert_eq!(config.database, "mydb");
+
+        let config = ClientConfig::from_url("fairydb://localhost").unwrap();
+        assert_eq!(config.host, "localhost");
+        assert_eq!(config.port, "3333");
+        assert_eq!(config.database, "");
+
+        let config = ClientConfig::from_url("fairydb:///mydb").unwrap();
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, "3333");
+        assert_eq!(config.database, "mydb");
+    }
+
+    #[test]
+    fn test_client_config_from_invalid_url() {
+        assert!(ClientConfig::from_url("postgres://localhost:4444/mydb").is_err());
+        assert!(ClientConfig::from_url("fairydb://localhost:port/mydb").is_err());
+        assert!(ClientConfig::from_url("fairydb://localhost:4444/my/db").is_err());
+    }
 }
@@ -1,5 +1,6 @@
 pub mod col_id_generator;
 pub mod config;
+pub mod page_dictionary;
 pub mod small_string;
 pub mod tuple_conv0;
 pub mod tuple_conv1;
@@ -0,0 +1,298 @@
+use crate::query::bytecode_expr::ByteCodeExpr;
+use crate::{FairyError, Field, Tuple};
+use std::fmt;
+use std::str::FromStr;
+
+/// Most distinct values the dictionary of a page keeps for one column. Values
+/// of the column that do not fit are stored inline in their row.
+pub const DICTIONARY_CAPACITY: usize = 64;
+
+/// First byte of a row stored with the dictionary of its page. It is not a
+/// valid first byte of CBOR, so rows stored as plain tuples never start with it.
+pub const ENCODED_ROW_MARKER: u8 = 0x1c;
+
+/// How the values of a column are stored in the pages of its table.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColumnEncoding {
+    /// Every row holds its own value.
+    #[default]
+    Plain,
+    /// Every page keeps the distinct values of the column and rows hold the
+    /// index of their value.
+    Dictionary,
+}
+
+impl fmt::Display for ColumnEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnEncoding::Plain => write!(f, "plain"),
+            ColumnEncoding::Dictionary => write!(f, "dictionary"),
+        }
+    }
+}
+
+impl FromStr for ColumnEncoding {
+    type Err = FairyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" | "none" => Ok(ColumnEncoding::Plain),
+            "dictionary" => Ok(ColumnEncoding::Dictionary),
+            _ => Err(FairyError::ValidationError(format!(
+                "Unknown encoding {}, expected plain or dictionary",
+                s
+            ))),
+        }
+    }
+}
+
+/// Distinct values of one column on a page.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ColumnDictionary {
+    pub column: u16,
+    pub values: Vec<Field>,
+    /// Whether some row of the page holds a value of the column inline, because
+    /// the dictionary was full or the row was written before the dictionary.
+    pub inline: bool,
+}
+
+/// Dictionary of the dictionary encoded columns of a page.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PageDictionary {
+    columns: Vec<ColumnDictionary>,
+}
+
+/// Stored form of a row encoded with a page dictionary: the tuple with every
+/// encoded value replaced by its index in the dictionary, and the indexes of
+/// the encoded fields.
+#[derive(Serialize, Deserialize)]
+struct DictionaryTuple(Tuple, Vec<u16>);
+
+/// Whether `bytes` is a row encoded with the dictionary of its page.
+pub fn is_encoded(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&ENCODED_ROW_MARKER)
+}
+
+impl PageDictionary {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FairyError> {
+        serde_cbor::from_slice(bytes).map_err(|e| FairyError::SerializationError(e.to_string()))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_cbor::to_vec(self).unwrap()
+    }
+
+    pub fn column(&self, column: usize) -> Option<&ColumnDictionary> {
+        self.columns.iter().find(|c| c.column as usize == column)
+    }
+
+    /// Adds an empty dictionary for each of `columns` that has none yet.
+    /// `inline` tells whether the page already holds rows, whose values of
+    /// these columns are inline.
+    pub fn add_columns(&mut self, columns: &[usize], inline: bool) {
+        for &column in columns {
+            if self.column(column).is_none() {
+                self.columns.push(ColumnDictionary {
+                    column: column as u16,
+                    values: Vec::new(),
+                    inline,
+                });
+            }
+        }
+    }
+
+    /// Encodes a row given as plain tuple bytes, adding its values to the
+    /// dictionary while there is room. Values that do not fit are kept inline.
+    /// Rows that are not tuples, or without any value to encode, are returned
+    /// as they are.
+    pub fn encode(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let Ok(mut tuple) = Tuple::try_from_bytes(bytes) else {
+            return bytes.to_vec();
+        };
+        let mut encoded = Vec::new();
+        for dict in &mut self.columns {
+            let i = dict.column as usize;
+            let Some(field) = tuple.field_vals.get_mut(i) else {
+                continue;
+            };
+            if *field == Field::Null {
+                continue;
+            }
+            let index = match dict.values.iter().position(|v| v == field) {
+                Some(index) => index,
+                None if dict.values.len() < DICTIONARY_CAPACITY => {
+                    dict.values.push(field.clone());
+                    dict.values.len() - 1
+                }
+                None => {
+                    dict.inline = true;
+                    continue;
+                }
+            };
+            *field = Field::BigInt(index as i64);
+            encoded.push(i as u16);
+        }
+        if encoded.is_empty() {
+            return bytes.to_vec();
+        }
+        let mut row = vec![ENCODED_ROW_MARKER];
+        serde_cbor::to_writer(&mut row, &DictionaryTuple(tuple, encoded)).unwrap();
+        row
+    }
+
+    /// Decodes a row stored on the page into plain tuple bytes. Rows that are
+    /// not encoded are returned as they are.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<u8>, FairyError> {
+        if !is_encoded(bytes) {
+            return Ok(bytes.to_vec());
+        }
+        let DictionaryTuple(mut tuple, encoded): DictionaryTuple =
+            serde_cbor::from_slice(&bytes[1..])
+                .map_err(|e| FairyError::SerializationError(e.to_string()))?;
+        for i in encoded {
+            let value = match (self.column(i as usize), tuple.get_field(i as usize)) {
+                (Some(dict), Some(Field::BigInt(index))) => dict.values.get(*index as usize),
+                _ => None,
+            };
+            let value = value.ok_or_else(|| {
+                FairyError::SerializationError(format!(
+                    "Field {} does not reference the page dictionary",
+                    i
+                ))
+            })?;
+            tuple.field_vals[i as usize] = value.clone();
+        }
+        Ok(tuple.to_bytes())
+    }
+}
+
+/// Condition on a single column of a table, evaluated on rows of `width`
+/// fields that are NULL except for that column.
+#[derive(Clone, Debug)]
+struct ColumnPredicate {
+    column: usize,
+    width: usize,
+    expr: ByteCodeExpr,
+}
+
+impl ColumnPredicate {
+    /// Whether a row whose column holds `value` may satisfy the condition.
+    /// Conditions that fail to evaluate may.
+    fn may_hold(&self, value: &Field) -> bool {
+        let mut fields = vec![Field::Null; self.width];
+        fields[self.column] = value.clone();
+        !matches!(
+            self.expr.try_eval(&Tuple::new(fields)),
+            Ok(Field::Bool(false) | Field::Null)
+        )
+    }
+}
+
+/// Conjuncts of the filter of a scan that each reference one dictionary
+/// encoded column. A page whose dictionary has no value satisfying one of them,
+/// and no inline value of its column, holds no row the scan returns.
+#[derive(Clone, Debug, Default)]
+pub struct PageFilter {
+    predicates: Vec<ColumnPredicate>,
+}
+
+impl PageFilter {
+    /// Adds the condition `expr` on `column`. It is evaluated on rows of
+    /// `width` fields.
+    pub fn add(&mut self, column: usize, width: usize, expr: ByteCodeExpr) {
+        self.predicates.push(ColumnPredicate {
+            column,
+            width,
+            expr,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.predicates.is_empty()
+    }
+
+    /// Whether some row of a page with dictionary `dict` may satisfy the filter.
+    pub fn may_match(&self, dict: &PageDictionary) -> bool {
+        self.predicates.iter().all(|p| match dict.column(p.column) {
+            // Rows without a value of the column hold NULL.
+            Some(values) if !values.inline => std::iter::once(&Field::Null)
+                .chain(&values.values)
+                .any(|v| p.may_hold(v)),
+            _ => true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::bytecode_expr::{colidx_expr, ByteCodes};
+
+    fn row(id: i64, color: &str) -> Vec<u8> {
+        Tuple::new(vec![Field::BigInt(id), Field::String(color.to_string())]).to_bytes()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut dict = PageDictionary::default();
+        dict.add_columns(&[1], false);
+        let plain = row(7, "purple");
+        let encoded = dict.encode(&plain);
+        assert!(is_encoded(&encoded));
+        assert_eq!(dict.column(1).unwrap().values.len(), 1);
+        assert_eq!(dict.decode(&encoded).unwrap(), plain);
+        // A repeated value is not added again.
+        dict.encode(&row(8, "purple"));
+        assert_eq!(dict.column(1).unwrap().values.len(), 1);
+
+        // NULL is not encoded.
+        let null = Tuple::new(vec![Field::BigInt(9), Field::Null]).to_bytes();
+        assert_eq!(dict.encode(&null), null);
+        assert_eq!(dict.decode(&null).unwrap(), null);
+
+        let dict = PageDictionary::from_bytes(&dict.to_bytes()).unwrap();
+        assert_eq!(dict.decode(&encoded).unwrap(), plain);
+        assert!(PageDictionary::default().decode(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_full_dictionary_keeps_values_inline() {
+        let mut dict = PageDictionary::default();
+        dict.add_columns(&[1], false);
+        for i in 0..DICTIONARY_CAPACITY {
+            assert!(is_encoded(&dict.encode(&row(0, &i.to_string()))));
+        }
+        assert!(!dict.column(1).unwrap().inline);
+        let overflow = row(0, "overflow");
+        assert_eq!(dict.encode(&overflow), overflow);
+        assert!(dict.column(1).unwrap().inline);
+        assert_eq!(dict.column(1).unwrap().values.len(), DICTIONARY_CAPACITY);
+    }
+
+    #[test]
+    fn test_page_filter() {
+        // color = 'red'
+        let mut expr = colidx_expr(1);
+        let i = expr.add_literal(Field::String("red".to_string()));
+        expr.add_code(ByteCodes::PushLit as usize);
+        expr.add_code(i);
+        expr.add_code(ByteCodes::Eq as usize);
+        let mut filter = PageFilter::default();
+        filter.add(1, 2, expr);
+
+        let mut dict = PageDictionary::default();
+        dict.add_columns(&[1], false);
+        dict.encode(&row(0, "blue"));
+        assert!(!filter.may_match(&dict));
+        dict.encode(&row(0, "red"));
+        assert!(filter.may_match(&dict));
+
+        // Inline values may match.
+        let mut dict = PageDictionary::default();
+        dict.add_columns(&[1], true);
+        dict.encode(&row(0, "blue"));
+        assert!(filter.may_match(&dict));
+        // So may pages without a dictionary for the column.
+        assert!(filter.may_match(&PageDictionary::default()));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ids::ContainerId;
+use crate::query::like::{like_match, literal_prefix};
 use crate::FairyError;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -60,6 +61,13 @@ pub struct StringManager {
     memory: Arc<RwLock<Vec<u8>>>,
     free_regions: Arc<RwLock<Vec<FreeRegion>>>,
     capacity: usize,
+    /// Largest string, in bytes, that can be allocated.
+    #[serde(default = "no_field_size_limit")]
+    max_field_size: usize,
+}
+
+fn no_field_size_limit() -> usize {
+    usize::MAX
 }
 
 impl std::fmt::Debug for StringManager {
@@ -97,7 +105,7 @@ fn calculate_offset_size(offset: usize) -> u8 {
 impl StringManager {
     /// Create an instance of a string manager
     /// Capacity: The capacity of the string manager (xtx should this be usize)
-    pub fn new(_config: &'static ServerConfig, capacity: usize, container_id: ContainerId) -> Self {
+    pub fn new(config: &'static ServerConfig, capacity: usize, container_id: ContainerId) -> Self {
         // when we care about persistence, first check a fixed file location to "bring back" the old manager
         //   similar to stat manager's new() methods.
         Self {
@@ -105,6 +113,7 @@ impl StringManager {
             memory: Arc::new(RwLock::new(vec![0; capacity])),
             free_regions: Arc::new(RwLock::new(vec![FreeRegion::new(capacity, 0)])),
             capacity,
+            max_field_size: config.max_field_size,
         }
     }
 
@@ -135,6 +144,11 @@ impl StringManager {
     /// Returns: An optional offset which is where the information was inserted (xtx should this be usize?)
     /// XTX Update usize
     fn allocate(&self, bytes: &[u8]) -> Option<usize> {
+        // Strings larger than max_field_size are refused before any ingress
+        // path gets here, this is the last line.
+        if bytes.len() > self.max_field_size {
+            return None;
+        }
         let mut free_regions = self.free_regions.write().unwrap();
         let size = bytes.len();
 
@@ -315,6 +329,13 @@ impl StringManager {
         &self,
         offset: usize,
     ) -> Result<String, std::string::FromUtf8Error> {
+        String::from_utf8(self.get_bytes_at_offset(offset))
+    }
+
+    /// Reads the bytes stored at a given offset, like `get_string_at_offset`.
+    /// The suffix of a long string may start inside a multi-byte character, so
+    /// it is only valid UTF-8 together with its prefix.
+    fn get_bytes_at_offset(&self, offset: usize) -> Vec<u8> {
         let memory = self.memory.read().expect("Failed to lock memory");
         let length_bytes = &memory[offset..offset + LENGTH_SIZE];
         let length = u32::from_le_bytes(
@@ -323,9 +344,7 @@ impl StringManager {
                 .expect("Failed to convert length bytes to u32"),
         ) as usize;
         // Extracting the string data based on the read length.
-        let string_data = &memory[offset + LENGTH_SIZE..offset + LENGTH_SIZE + length];
-        // Converting the byte slice to a Rust String.
-        String::from_utf8(string_data.to_vec())
+        memory[offset + LENGTH_SIZE..offset + LENGTH_SIZE + length].to_vec()
     }
 
     ///Finds the total amount of free space left inside the memory
@@ -492,6 +511,22 @@ impl SmallString {
         string_manager.compare_strings(self_offset, other_offset)
     }
 
+    /// Returns true if the string matches the LIKE pattern `pattern`.
+    /// For a long string, the prefix stored inline is checked against the
+    /// literal prefix of the pattern first, so the suffix is only read from
+    /// the string manager if the prefix can match.
+    pub fn like(&self, pattern: &str) -> Result<bool, std::string::FromUtf8Error> {
+        if !self.is_short() {
+            let required = literal_prefix(pattern);
+            let prefix = &self.data[..self.extract_prefix_len()];
+            let n = prefix.len().min(required.len());
+            if prefix[..n] != required.as_bytes()[..n] {
+                return Ok(false);
+            }
+        }
+        Ok(like_match(&self.to_string()?, pattern))
+    }
+
     /// Converts the SmallString content to a Rust String.
     /// This method handles both short and long strings and returns a Result
     /// because the conversion from bytes to String can fail if the bytes are not valid UTF-8.
@@ -506,20 +541,15 @@ impl SmallString {
             false => {
                 // Long string
                 // Access the StringManager and use it to retrieve the string based on the offset stored in this SmallString.
-                let manager_lock = string_manager;
-                let manager = manager_lock; // Dereference the MutexGuard to get the manager.
-                                            // Extract the prefix length and the actual prefix.
                 let prefix_len = self.extract_prefix_len();
-                let prefix = std::str::from_utf8(&self.data[..prefix_len])
-                    .expect("failed to convert to utf8");
+                let mut bytes = self.data[..prefix_len].to_vec();
 
                 // Extract the offset where the string is stored in the manager's memory.
                 let offset = self.extract_suffix_offset();
-                // Use the manager to retrieve the suffix from its managed memory.
-                let suffix = manager.get_string_at_offset(offset).unwrap();
-
-                // Combine prefix and suffix into a single String
-                Ok(format!("{}{}", prefix, suffix))
+                // The prefix and the suffix may split a character, so they are
+                // joined before they are decoded.
+                bytes.extend(string_manager.get_bytes_at_offset(offset));
+                String::from_utf8(bytes)
             }
         }
     }
@@ -756,6 +786,17 @@ mod tests {
         assert_eq!(my_string.to_string().unwrap(), content);
     }
 
+    #[test]
+    fn test_long_string_split_inside_character() {
+        let string_manager = get_test_string_manager();
+        // The 3-byte characters do not end at the end of the prefix.
+        let content = format!("a{}", "가".repeat(20));
+        let my_string = SmallString::new(&content, string_manager).unwrap();
+        assert_eq!(my_string.to_string().unwrap(), content);
+        assert_eq!(my_string.as_bytes().unwrap(), content.as_bytes());
+        assert!(my_string.like("a가%가").unwrap());
+    }
+
     #[test]
     fn test_string_comparison_short_long() {
         let string_manager = get_test_string_manager();
@@ -768,6 +809,27 @@ mod tests {
         assert!(short_string.compare(&long_string) == Ordering::Less);
     }
 
+    #[test]
+    fn test_like() {
+        let string_manager = get_test_string_manager();
+        let short_string = SmallString::new("100% cotton", string_manager).unwrap();
+        assert!(short_string.like("100\\%%").unwrap());
+        assert!(short_string.like("%cotton").unwrap());
+        assert!(!short_string.like("1000%").unwrap());
+        let long_string = SmallString::new(
+            "this is a very long string that exceeds the max short length",
+            string_manager,
+        )
+        .unwrap();
+        assert!(long_string.like("this is%length").unwrap());
+        assert!(long_string.like("%long string%").unwrap());
+        assert!(long_string.like("this_is a very long string%").unwrap());
+        assert!(!long_string.like("that is%").unwrap());
+        assert!(!long_string
+            .like("this is a very long string that%width")
+            .unwrap());
+    }
+
     #[test]
     fn test_from_bytes_and_as_bytes_short() {
         let string_manager = get_test_string_manager();
@@ -909,6 +971,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_allocate_over_max_field_size() {
+        let config = ServerConfig {
+            max_field_size: MAX_SHORT_LEN + 1,
+            ..ServerConfig::temporary()
+        };
+        let string_manager: &'static StringManager = Box::leak(Box::new(StringManager::new(
+            Box::leak(Box::new(config)),
+            1024 * 1024,
+            0,
+        )));
+        let data = "a".repeat(MAX_SHORT_LEN + 1);
+        assert!(SmallString::new(data.as_str(), string_manager).is_some());
+        let data = "a".repeat(MAX_SHORT_LEN + 2);
+        assert!(SmallString::new(data.as_str(), string_manager).is_none());
+    }
+
     #[test]
     fn test_empty_string_length() {
         let string_manager = get_test_string_manager();
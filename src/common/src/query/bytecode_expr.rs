@@ -1,16 +1,34 @@
-use crate::{tuple::Tuple, FairyError, Field};
+use crate::{tuple::Tuple, DataType, FairyError, Field, ScalarFunc};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::ops::{Add, Div, Mul, Sub};
 
 pub trait FromBool {
     fn from_bool(b: bool) -> Self;
 }
 
-pub trait And {
-    fn and(&self, other: &Self) -> Self;
+pub trait SqlOrd {
+    /// Order of two values in a comparison of a SQL expression. Numbers of
+    /// different types compare by their value.
+    fn sql_cmp(&self, other: &Self) -> Ordering;
 }
 
-pub trait Or {
-    fn or(&self, other: &Self) -> Self;
+pub trait Nullable {
+    fn null() -> Self;
+    fn is_null(&self) -> bool;
+}
+
+pub trait And: Sized {
+    fn and(&self, other: &Self) -> Result<Self, FairyError>;
+}
+
+pub trait Or: Sized {
+    fn or(&self, other: &Self) -> Result<Self, FairyError>;
+}
+
+pub trait Like: Sized {
+    fn like(&self, pattern: &Self) -> Result<Self, FairyError>;
+    fn not_like(&self, pattern: &Self) -> Result<Self, FairyError>;
 }
 
 pub enum ByteCodes {
@@ -32,9 +50,27 @@ pub enum ByteCodes {
     // LOGICAL OPERATIONS
     And,
     Or,
+    // PATTERN MATCHING
+    Like,
+    NotLike,
+    // LIST MEMBERSHIP
+    InList,
+    NotInList,
+    // NULL TESTS
+    IsNull,
+    IsNotNull,
+    // CONVERSION
+    Cast,
+    // FUNCTIONS
+    Call,
+    // BRANCHING
+    Jump,
+    JumpIfNotTrue,
+    Dup,
+    Pop,
 }
 
-const STATIC_DISPATCHER: [DispatchFn<Field>; 14] = [
+const STATIC_DISPATCHER: [DispatchFn<Field>; 26] = [
     // CONTROL FLOW
     PUSH_LIT_FN,
     PUSH_FIELD_FN,
@@ -53,8 +89,77 @@ const STATIC_DISPATCHER: [DispatchFn<Field>; 14] = [
     // LOGICAL OPERATIONS
     AND_FN,
     OR_FN,
+    // PATTERN MATCHING
+    LIKE_FN,
+    NOT_LIKE_FN,
+    // LIST MEMBERSHIP
+    IN_LIST_FN,
+    NOT_IN_LIST_FN,
+    // NULL TESTS
+    IS_NULL_FN,
+    IS_NOT_NULL_FN,
+    // CONVERSION
+    CAST_FN,
+    // FUNCTIONS
+    CALL_FN,
+    // BRANCHING
+    JUMP_FN,
+    JUMP_IF_NOT_TRUE_FN,
+    DUP_FN,
+    POP_FN,
 ];
 
+/// Lists longer than this are looked up in a hash set rather than scanned.
+const IN_LIST_HASH_THRESHOLD: usize = 8;
+
+/// Values of an `IN` list, built once when the expression is compiled. Long
+/// lists are hashed by `Field::sql_key`, which is equal for numbers of
+/// different types with the same value. Values sharing a key are told apart
+/// with `sql_cmp`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InList {
+    values: Vec<Field>,
+    set: Option<HashMap<Field, Vec<Field>>>,
+    has_null: bool,
+}
+
+impl InList {
+    pub fn new(values: Vec<Field>) -> Self {
+        let has_null = values.contains(&Field::Null);
+        let values: Vec<Field> = values.into_iter().filter(|v| *v != Field::Null).collect();
+        let set = (values.len() > IN_LIST_HASH_THRESHOLD).then(|| {
+            let mut set: HashMap<Field, Vec<Field>> = HashMap::new();
+            for val in &values {
+                set.entry(val.sql_key()).or_default().push(val.clone());
+            }
+            set
+        });
+        InList {
+            values,
+            set,
+            has_null,
+        }
+    }
+
+    /// Value of `val IN (list)`: true if the list has the value, NULL if the
+    /// value is NULL or the list has NULL and not the value, false otherwise.
+    pub fn contains(&self, val: &Field) -> Field {
+        if *val == Field::Null {
+            return Field::Null;
+        }
+        let candidates = match &self.set {
+            Some(set) => set.get(&val.sql_key()).map_or(&[][..], Vec::as_slice),
+            None => &self.values,
+        };
+        let found = candidates.iter().any(|item| item.sql_cmp(val).is_eq());
+        match (found, self.has_null) {
+            (true, _) => Field::Bool(true),
+            (false, true) => Field::Null,
+            (false, false) => Field::Bool(false),
+        }
+    }
+}
+
 // Utility functions
 pub fn colidx_expr(colidx: usize) -> ByteCodeExpr {
     let mut expr = ByteCodeExpr::new();
@@ -67,6 +172,11 @@ pub fn colidx_expr(colidx: usize) -> ByteCodeExpr {
 pub struct ByteCodeExpr {
     pub bytecodes: Vec<usize>,
     pub literals: Vec<Field>,
+    #[serde(default)]
+    pub lists: Vec<InList>,
+    /// Target types of the casts.
+    #[serde(default)]
+    pub types: Vec<DataType>,
 }
 
 impl ByteCodeExpr {
@@ -74,6 +184,8 @@ impl ByteCodeExpr {
         ByteCodeExpr {
             bytecodes: Vec::new(),
             literals: Vec::new(),
+            lists: Vec::new(),
+            types: Vec::new(),
         }
     }
 
@@ -87,11 +199,42 @@ impl ByteCodeExpr {
         i
     }
 
+    pub fn add_list(&mut self, list: InList) -> usize {
+        let i = self.lists.len();
+        self.lists.push(list);
+        i
+    }
+
+    pub fn add_type(&mut self, dtype: DataType) -> usize {
+        let i = self.types.len();
+        self.types.push(dtype);
+        i
+    }
+
+    /// Adds a jump with `code`, which is `Jump` or `JumpIfNotTrue`, and returns
+    /// the position of its target to be set with `patch_jump`.
+    pub fn add_jump(&mut self, code: ByteCodes) -> usize {
+        self.add_code(code as usize);
+        self.add_code(usize::MAX);
+        self.bytecodes.len() - 1
+    }
+
+    /// Makes the jump whose target is at `pos` go to the next code added.
+    pub fn patch_jump(&mut self, pos: usize) {
+        self.bytecodes[pos] = self.bytecodes.len();
+    }
+
     fn is_empty(&self) -> bool {
         self.bytecodes.is_empty()
     }
 
     pub fn eval(&self, record: &Tuple) -> Field {
+        self.try_eval(record).unwrap()
+    }
+
+    /// Evaluates the expression on `record`, returning an error instead of
+    /// panicking if an operation fails, e.g. on division by zero.
+    pub fn try_eval(&self, record: &Tuple) -> Result<Field, FairyError> {
         if self.is_empty() {
             panic!("Cannot evaluate empty expression")
         }
@@ -100,16 +243,28 @@ impl ByteCodeExpr {
         let record = &record.field_vals;
         let bytecodes = &self.bytecodes;
         let literals = &self.literals;
+        let lists = &self.lists;
+        let types = &self.types;
         while i < bytecodes.len() {
             let opcode = bytecodes[i];
             i += 1;
-            STATIC_DISPATCHER[opcode](bytecodes, &mut i, &mut stack, literals, record);
+            STATIC_DISPATCHER[opcode](
+                bytecodes, &mut i, &mut stack, literals, lists, types, record,
+            )?;
         }
-        stack.pop().unwrap()
+        Ok(stack.pop().unwrap())
     }
 }
 
-type DispatchFn<T> = fn(&[usize], &mut usize, &mut Vec<T>, &[T], &[T]);
+type DispatchFn<T> = fn(
+    &[usize],
+    &mut usize,
+    &mut Vec<T>,
+    &[T],
+    &[InList],
+    &[DataType],
+    &[T],
+) -> Result<(), FairyError>;
 const PUSH_LIT_FN: DispatchFn<Field> = push_lit;
 const PUSH_FIELD_FN: DispatchFn<Field> = push_field;
 const ADD_FN: DispatchFn<Field> = add;
@@ -124,18 +279,34 @@ const LTE_FN: DispatchFn<Field> = lte;
 const GTE_FN: DispatchFn<Field> = gte;
 const AND_FN: DispatchFn<Field> = and;
 const OR_FN: DispatchFn<Field> = or;
+const LIKE_FN: DispatchFn<Field> = like;
+const NOT_LIKE_FN: DispatchFn<Field> = not_like;
+const IN_LIST_FN: DispatchFn<Field> = in_list;
+const NOT_IN_LIST_FN: DispatchFn<Field> = not_in_list;
+const IS_NULL_FN: DispatchFn<Field> = is_null;
+const IS_NOT_NULL_FN: DispatchFn<Field> = is_not_null;
+const CAST_FN: DispatchFn<Field> = cast;
+const CALL_FN: DispatchFn<Field> = call;
+const JUMP_FN: DispatchFn<Field> = jump;
+const JUMP_IF_NOT_TRUE_FN: DispatchFn<Field> = jump_if_not_true;
+const DUP_FN: DispatchFn<Field> = dup;
+const POP_FN: DispatchFn<Field> = pop;
 
 fn push_field<T>(
     bytecodes: &[usize],
     i: &mut usize,
     stack: &mut Vec<T>,
     _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
     record: &[T],
-) where
+) -> Result<(), FairyError>
+where
     T: Clone,
 {
     stack.push(record[bytecodes[*i]].clone());
     *i += 1;
+    Ok(())
 }
 
 fn push_lit<T>(
@@ -143,118 +314,613 @@ fn push_lit<T>(
     i: &mut usize,
     stack: &mut Vec<T>,
     literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
     _record: &[T],
-) where
+) -> Result<(), FairyError>
+where
     T: Clone,
 {
     stack.push(literals[bytecodes[*i]].clone());
     *i += 1;
+    Ok(())
 }
 
-fn add<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn add<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: Add<Output = Result<T, FairyError>> + Clone,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push((l + r).unwrap());
+    stack.push((l + r)?);
+    Ok(())
 }
 
-fn sub<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn sub<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: Sub<Output = Result<T, FairyError>> + Clone,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push((l - r).unwrap());
+    stack.push((l - r)?);
+    Ok(())
 }
 
-fn mul<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn mul<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: Mul<Output = Result<T, FairyError>> + Clone,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push((l * r).unwrap());
+    stack.push((l * r)?);
+    Ok(())
 }
 
-fn div<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn div<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: Div<Output = Result<T, FairyError>> + Clone,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push((l / r).unwrap());
+    stack.push((l / r)?);
+    Ok(())
+}
+
+/// Result of comparing `l` and `r`, true if `holds` accepts their order and
+/// NULL if either is NULL.
+fn compare<T>(l: &T, r: &T, holds: impl Fn(Ordering) -> bool) -> T
+where
+    T: SqlOrd + FromBool + Nullable,
+{
+    if l.is_null() || r.is_null() {
+        T::null()
+    } else {
+        T::from_bool(holds(l.sql_cmp(r)))
+    }
 }
 
-fn eq<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn eq<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialEq + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l == r));
+    stack.push(compare(&l, &r, Ordering::is_eq));
+    Ok(())
 }
 
-fn neq<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn neq<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialEq + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l != r));
+    stack.push(compare(&l, &r, Ordering::is_ne));
+    Ok(())
 }
 
-fn lt<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn lt<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialOrd + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l < r));
+    stack.push(compare(&l, &r, Ordering::is_lt));
+    Ok(())
 }
 
-fn gt<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn gt<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialOrd + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l > r));
+    stack.push(compare(&l, &r, Ordering::is_gt));
+    Ok(())
 }
 
-fn lte<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn lte<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialOrd + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l <= r));
+    stack.push(compare(&l, &r, Ordering::is_le));
+    Ok(())
 }
 
-fn gte<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn gte<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
-    T: PartialOrd + Clone + FromBool,
+    T: SqlOrd + Clone + FromBool + Nullable,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(T::from_bool(l >= r));
+    stack.push(compare(&l, &r, Ordering::is_ge));
+    Ok(())
 }
 
-fn and<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn and<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: PartialEq + Clone + And,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(l.and(&r));
+    stack.push(l.and(&r)?);
+    Ok(())
 }
 
-fn or<T>(_bytecodes: &[usize], _i: &mut usize, stack: &mut Vec<T>, _literals: &[T], _record: &[T])
+fn or<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
 where
     T: PartialEq + Clone + Or,
 {
     let r = stack.pop().unwrap();
     let l = stack.pop().unwrap();
-    stack.push(l.or(&r));
+    stack.push(l.or(&r)?);
+    Ok(())
+}
+
+fn like<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: PartialEq + Clone + Like,
+{
+    let r = stack.pop().unwrap();
+    let l = stack.pop().unwrap();
+    stack.push(l.like(&r)?);
+    Ok(())
+}
+
+fn not_like<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: PartialEq + Clone + Like,
+{
+    let r = stack.pop().unwrap();
+    let l = stack.pop().unwrap();
+    stack.push(l.not_like(&r)?);
+    Ok(())
+}
+
+fn in_list(
+    bytecodes: &[usize],
+    i: &mut usize,
+    stack: &mut Vec<Field>,
+    _literals: &[Field],
+    lists: &[InList],
+    _types: &[DataType],
+    _record: &[Field],
+) -> Result<(), FairyError> {
+    let val = stack.pop().unwrap();
+    stack.push(lists[bytecodes[*i]].contains(&val));
+    *i += 1;
+    Ok(())
+}
+
+fn not_in_list(
+    bytecodes: &[usize],
+    i: &mut usize,
+    stack: &mut Vec<Field>,
+    _literals: &[Field],
+    lists: &[InList],
+    _types: &[DataType],
+    _record: &[Field],
+) -> Result<(), FairyError> {
+    let val = stack.pop().unwrap();
+    stack.push(match lists[bytecodes[*i]].contains(&val) {
+        Field::Bool(b) => Field::Bool(!b),
+        field => field,
+    });
+    *i += 1;
+    Ok(())
+}
+
+fn is_null<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: FromBool + Nullable,
+{
+    let val = stack.pop().unwrap();
+    stack.push(T::from_bool(val.is_null()));
+    Ok(())
+}
+
+fn is_not_null<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: FromBool + Nullable,
+{
+    let val = stack.pop().unwrap();
+    stack.push(T::from_bool(!val.is_null()));
+    Ok(())
+}
+
+fn cast(
+    bytecodes: &[usize],
+    i: &mut usize,
+    stack: &mut Vec<Field>,
+    _literals: &[Field],
+    _lists: &[InList],
+    types: &[DataType],
+    _record: &[Field],
+) -> Result<(), FairyError> {
+    let val = stack.pop().unwrap();
+    stack.push(val.cast_to(&types[bytecodes[*i]])?);
+    *i += 1;
+    Ok(())
+}
+
+/// Calls the scalar function whose code follows the opcode on as many
+/// arguments from the top of the stack as the code after that.
+fn call(
+    bytecodes: &[usize],
+    i: &mut usize,
+    stack: &mut Vec<Field>,
+    _literals: &[Field],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[Field],
+) -> Result<(), FairyError> {
+    let func = ScalarFunc::ALL[bytecodes[*i]];
+    let args = stack.split_off(stack.len() - bytecodes[*i + 1]);
+    stack.push(func.eval(&args)?);
+    *i += 2;
+    Ok(())
+}
+
+fn jump<T>(
+    bytecodes: &[usize],
+    i: &mut usize,
+    _stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError> {
+    *i = bytecodes[*i];
+    Ok(())
+}
+
+/// Pops a condition and jumps unless it is true, so that NULL counts as false.
+fn jump_if_not_true<T>(
+    bytecodes: &[usize],
+    i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: PartialEq + FromBool,
+{
+    let val = stack.pop().unwrap();
+    if val == T::from_bool(true) {
+        *i += 1;
+    } else {
+        *i = bytecodes[*i];
+    }
+    Ok(())
+}
+
+fn dup<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError>
+where
+    T: Clone,
+{
+    stack.push(stack.last().unwrap().clone());
+    Ok(())
+}
+
+fn pop<T>(
+    _bytecodes: &[usize],
+    _i: &mut usize,
+    stack: &mut Vec<T>,
+    _literals: &[T],
+    _lists: &[InList],
+    _types: &[DataType],
+    _record: &[T],
+) -> Result<(), FairyError> {
+    stack.pop().unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_list_expr(list: Vec<Field>, negated: bool) -> ByteCodeExpr {
+        let mut expr = colidx_expr(0);
+        let i = expr.add_list(InList::new(list));
+        let code = if negated {
+            ByteCodes::NotInList
+        } else {
+            ByteCodes::InList
+        };
+        expr.add_code(code as usize);
+        expr.add_code(i);
+        expr
+    }
+
+    /// `val = l1 OR val = l2 OR ...` with SQL semantics, where a comparison
+    /// with NULL is NULL.
+    fn or_chain(val: &Field, list: &[Field]) -> Field {
+        list.iter().fold(Field::Bool(false), |acc, item| {
+            let test = match (val, item) {
+                (Field::Null, _) | (_, Field::Null) => Field::Null,
+                _ => Field::Bool(val == item),
+            };
+            acc.or(&test).unwrap()
+        })
+    }
+
+    fn not(val: Field) -> Field {
+        match val {
+            Field::Bool(b) => Field::Bool(!b),
+            field => field,
+        }
+    }
+
+    #[test]
+    fn test_in_list_matches_or_chain() {
+        let short = vec![
+            Field::BigInt(1),
+            Field::BigInt(3),
+            Field::BigInt(3),
+            Field::Null,
+        ];
+        // Long enough to be looked up in the hash set.
+        let long: Vec<Field> = (0..40)
+            .map(|i| Field::BigInt(i % 20 * 2))
+            .chain([Field::Null, Field::Null])
+            .collect();
+        let no_null: Vec<Field> = (0..40).map(|i| Field::BigInt(i % 20 * 2)).collect();
+        for list in [short, long, no_null] {
+            let in_expr = in_list_expr(list.clone(), false);
+            let not_in_expr = in_list_expr(list.clone(), true);
+            for probe in (-1..45).map(Field::BigInt).chain([Field::Null]) {
+                let tuple = Tuple::new(vec![probe.clone()]);
+                let expected = or_chain(&probe, &list);
+                assert_eq!(in_expr.eval(&tuple), expected, "{:?} IN {:?}", probe, list);
+                assert_eq!(
+                    not_in_expr.eval(&tuple),
+                    not(expected),
+                    "{:?} NOT IN {:?}",
+                    probe,
+                    list
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_comparison_with_null_is_null() {
+        let codes = [
+            ByteCodes::Eq as usize,
+            ByteCodes::Neq as usize,
+            ByteCodes::Lt as usize,
+            ByteCodes::Gt as usize,
+            ByteCodes::Lte as usize,
+            ByteCodes::Gte as usize,
+        ];
+        let tuple = Tuple::new(vec![Field::Null, Field::BigInt(1)]);
+        for code in codes {
+            for (l, r) in [(0, 1), (1, 0), (0, 0)] {
+                let mut expr = colidx_expr(l);
+                expr.add_code(ByteCodes::PushField as usize);
+                expr.add_code(r);
+                expr.add_code(code);
+                assert_eq!(expr.eval(&tuple), Field::Null);
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_null() {
+        for (val, is_null) in [(Field::Null, true), (Field::BigInt(0), false)] {
+            let tuple = Tuple::new(vec![val]);
+            let mut expr = colidx_expr(0);
+            expr.add_code(ByteCodes::IsNull as usize);
+            assert_eq!(expr.eval(&tuple), Field::Bool(is_null));
+            let mut expr = colidx_expr(0);
+            expr.add_code(ByteCodes::IsNotNull as usize);
+            assert_eq!(expr.eval(&tuple), Field::Bool(!is_null));
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut expr = colidx_expr(0);
+        let i = expr.add_type(DataType::BigInt);
+        expr.add_code(ByteCodes::Cast as usize);
+        expr.add_code(i);
+        let eval = |val: Field| expr.try_eval(&Tuple::new(vec![val]));
+        assert_eq!(
+            eval(Field::String("12".to_string())).unwrap(),
+            Field::BigInt(12)
+        );
+        assert_eq!(eval(Field::Decimal(-125, 2)).unwrap(), Field::BigInt(-1));
+        assert_eq!(eval(Field::Null).unwrap(), Field::Null);
+        // A value that does not convert fails the evaluation.
+        assert!(eval(Field::String("twelve".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_and_or_of_non_bool_fails() {
+        // col0 AND col1, col0 OR col1
+        for code in [ByteCodes::And, ByteCodes::Or] {
+            let mut expr = colidx_expr(0);
+            expr.add_code(ByteCodes::PushField as usize);
+            expr.add_code(1);
+            expr.add_code(code as usize);
+            let eval = |l: Field, r: Field| expr.try_eval(&Tuple::new(vec![l, r]));
+            assert!(eval(Field::Bool(true), Field::Null).is_ok());
+            assert!(eval(Field::BigInt(1), Field::Bool(true)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_call() {
+        // SUBSTR(col0, col1, 2)
+        let mut expr = colidx_expr(0);
+        expr.add_code(ByteCodes::PushField as usize);
+        expr.add_code(1);
+        let i = expr.add_literal(Field::BigInt(2));
+        expr.add_code(ByteCodes::PushLit as usize);
+        expr.add_code(i);
+        expr.add_code(ByteCodes::Call as usize);
+        expr.add_code(ScalarFunc::Substr as usize);
+        expr.add_code(3);
+        let eval = |s: &str, start: i64| {
+            expr.try_eval(&Tuple::new(vec![
+                Field::String(s.to_string()),
+                Field::BigInt(start),
+            ]))
+        };
+        assert_eq!(eval("añb", 2).unwrap(), Field::String("ñb".to_string()));
+        assert_eq!(eval("añb", 9).unwrap(), Field::String(String::new()));
+    }
+
+    #[test]
+    fn test_long_in_list_uses_set() {
+        let list = |len: i64| InList::new((0..len).map(|i| Field::BigInt(i * 2)).collect());
+        let threshold = IN_LIST_HASH_THRESHOLD as i64;
+        assert!(list(threshold).set.is_none());
+        assert!(list(threshold + 1).set.is_some());
+        assert!(list(1_000).set.is_some());
+        // NULLs are not members of the set, so they do not count toward its length.
+        let mut values: Vec<Field> = (0..threshold).map(Field::BigInt).collect();
+        values.push(Field::Null);
+        assert!(InList::new(values).set.is_none());
+    }
 }
@@ -9,7 +9,7 @@ use std::collections::{HashMap, HashSet};
 
 use crate::{
     attribute::Attribute, ids::ColumnId, table::TableSchema, traits::plan::Plan, BinaryOp,
-    DataType, Field,
+    DataType, Field, ScalarFunc,
 };
 
 use super::{
@@ -29,14 +29,53 @@ pub enum Expression<P: Plan> {
         left: Box<Expression<P>>,
         right: Box<Expression<P>>,
     },
+    /// `CASE [expr] WHEN .. THEN .. ELSE else_expr END`. With `expr` each
+    /// `when` is a value compared to it, otherwise a condition. Only the branch
+    /// that is taken is evaluated.
     Case {
-        expr: Box<Expression<P>>,
+        expr: Option<Box<Expression<P>>>,
         whens: Vec<(Expression<P>, Expression<P>)>,
         else_expr: Box<Expression<P>>,
     },
     Subquery {
         expr: Box<P>,
     },
+    /// `expr [NOT] IN (list)` for a list of literals.
+    InList {
+        expr: Box<Expression<P>>,
+        list: Vec<Field>,
+        negated: bool,
+    },
+    /// `expr IS [NOT] NULL`. Unlike comparisons it is never NULL.
+    IsNull {
+        expr: Box<Expression<P>>,
+        negated: bool,
+    },
+    /// `CAST(expr AS dtype)`. Fails on values that are not valid for `dtype`.
+    Cast {
+        expr: Box<Expression<P>>,
+        dtype: DataType,
+    },
+    /// Call of a scalar function, e.g. `UPPER(expr)`.
+    ScalarFunction {
+        func: ScalarFunc,
+        args: Vec<Expression<P>>,
+    },
+    /// `expr op ANY (subquery)`, or `expr op ALL (subquery)` if `all` is set.
+    /// `IN` is `= ANY` and `NOT IN` is `<> ALL`.
+    Quantified {
+        expr: Box<Expression<P>>,
+        op: BinaryOp,
+        all: bool,
+        subquery: Box<P>,
+    },
+    /// Placeholder `$index` of a prepared statement, counted from 1, that
+    /// `EXECUTE` binds to a value. `dtype` is the type of what the placeholder
+    /// is compared with, if the translator knows it.
+    Parameter {
+        index: usize,
+        dtype: Option<DataType>,
+    },
 }
 
 impl<P: Plan> Expression<P> {
@@ -75,22 +114,153 @@ impl<P: Plan> Expression<P> {
         }
     }
 
+    pub fn in_list(expr: Expression<P>, list: Vec<Field>, negated: bool) -> Expression<P> {
+        Expression::InList {
+            expr: Box::new(expr),
+            list,
+            negated,
+        }
+    }
+
+    pub fn is_null(expr: Expression<P>, negated: bool) -> Expression<P> {
+        Expression::IsNull {
+            expr: Box::new(expr),
+            negated,
+        }
+    }
+
+    pub fn cast(expr: Expression<P>, dtype: DataType) -> Expression<P> {
+        Expression::Cast {
+            expr: Box::new(expr),
+            dtype,
+        }
+    }
+
+    pub fn scalar_function(func: ScalarFunc, args: Vec<Expression<P>>) -> Expression<P> {
+        Expression::ScalarFunction { func, args }
+    }
+
+    pub fn case(
+        expr: Option<Expression<P>>,
+        whens: Vec<(Expression<P>, Expression<P>)>,
+        else_expr: Expression<P>,
+    ) -> Expression<P> {
+        Expression::Case {
+            expr: expr.map(Box::new),
+            whens,
+            else_expr: Box::new(else_expr),
+        }
+    }
+
     pub fn subquery(expr: P) -> Expression<P> {
         Expression::Subquery {
             expr: Box::new(expr),
         }
     }
 
+    pub fn quantified(expr: Expression<P>, op: BinaryOp, all: bool, subquery: P) -> Expression<P> {
+        Expression::Quantified {
+            expr: Box::new(expr),
+            op,
+            all,
+            subquery: Box::new(subquery),
+        }
+    }
+
     pub fn has_subquery(&self) -> bool {
         match self {
             Expression::ColRef { id: _ } => false,
-            Expression::Field { val: _ } => false,
+            Expression::Field { val: _ } | Expression::Parameter { .. } => false,
             Expression::Binary { left, right, .. } => left.has_subquery() || right.has_subquery(),
-            Expression::Case { .. } => {
-                // Currently, we don't support subqueries in the case expression
-                false
+            Expression::Case {
+                expr,
+                whens,
+                else_expr,
+            } => {
+                expr.as_ref().is_some_and(|expr| expr.has_subquery())
+                    || whens
+                        .iter()
+                        .any(|(when, then)| when.has_subquery() || then.has_subquery())
+                    || else_expr.has_subquery()
             }
             Expression::Subquery { expr: _ } => true,
+            Expression::InList { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. } => expr.has_subquery(),
+            Expression::ScalarFunction { args, .. } => args.iter().any(|arg| arg.has_subquery()),
+            Expression::Quantified { .. } => true,
+        }
+    }
+
+    /// Returns true if the expression has a subquery that refers to columns of
+    /// the enclosing query.
+    pub fn has_correlated_subquery(&self) -> bool {
+        match self {
+            Expression::Binary { left, right, .. } => {
+                left.has_correlated_subquery() || right.has_correlated_subquery()
+            }
+            Expression::Case {
+                expr,
+                whens,
+                else_expr,
+            } => {
+                expr.as_ref()
+                    .is_some_and(|expr| expr.has_correlated_subquery())
+                    || whens.iter().any(|(when, then)| {
+                        when.has_correlated_subquery() || then.has_correlated_subquery()
+                    })
+                    || else_expr.has_correlated_subquery()
+            }
+            Expression::Subquery { expr } => !expr.free().is_empty(),
+            Expression::InList { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. } => expr.has_correlated_subquery(),
+            Expression::ScalarFunction { args, .. } => {
+                args.iter().any(|arg| arg.has_correlated_subquery())
+            }
+            Expression::Quantified { expr, subquery, .. } => {
+                expr.has_correlated_subquery() || !subquery.free().is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Plans of the subqueries in the expression.
+    pub fn subqueries(&self) -> Vec<&P> {
+        match self {
+            Expression::ColRef { .. } | Expression::Field { .. } | Expression::Parameter { .. } => {
+                vec![]
+            }
+            Expression::Binary { left, right, .. } => {
+                let mut plans = left.subqueries();
+                plans.extend(right.subqueries());
+                plans
+            }
+            Expression::Case {
+                expr,
+                whens,
+                else_expr,
+            } => {
+                let mut plans = expr.as_ref().map_or(vec![], |expr| expr.subqueries());
+                for (when, then) in whens {
+                    plans.extend(when.subqueries());
+                    plans.extend(then.subqueries());
+                }
+                plans.extend(else_expr.subqueries());
+                plans
+            }
+            Expression::Subquery { expr } => vec![expr],
+            Expression::InList { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. } => expr.subqueries(),
+            Expression::ScalarFunction { args, .. } => {
+                args.iter().flat_map(|arg| arg.subqueries()).collect()
+            }
+            Expression::Quantified { expr, subquery, .. } => {
+                let mut plans = expr.subqueries();
+                plans.push(subquery);
+                plans
+            }
         }
     }
 
@@ -138,6 +308,7 @@ impl<P: Plan> Expression<P> {
                 }
             }
             Expression::Field { val } => Expression::Field { val },
+            Expression::Parameter { index, dtype } => Expression::Parameter { index, dtype },
             Expression::Binary { op, left, right } => Expression::Binary {
                 op,
                 left: Box::new(left.replace_variables(src_to_dest)),
@@ -148,7 +319,7 @@ impl<P: Plan> Expression<P> {
                 whens,
                 else_expr,
             } => Expression::Case {
-                expr: Box::new(expr.replace_variables(src_to_dest)),
+                expr: expr.map(|expr| Box::new(expr.replace_variables(src_to_dest))),
                 whens: whens
                     .into_iter()
                     .map(|(when, then)| {
@@ -163,6 +334,34 @@ impl<P: Plan> Expression<P> {
             Expression::Subquery { expr } => Expression::Subquery {
                 expr: Box::new(expr.replace_variables(src_to_dest)),
             },
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => Expression::in_list(expr.replace_variables(src_to_dest), list, negated),
+            Expression::IsNull { expr, negated } => {
+                Expression::is_null(expr.replace_variables(src_to_dest), negated)
+            }
+            Expression::Cast { expr, dtype } => {
+                Expression::cast(expr.replace_variables(src_to_dest), dtype)
+            }
+            Expression::ScalarFunction { func, args } => Expression::scalar_function(
+                func,
+                args.into_iter()
+                    .map(|arg| arg.replace_variables(src_to_dest))
+                    .collect(),
+            ),
+            Expression::Quantified {
+                expr,
+                op,
+                all,
+                subquery,
+            } => Expression::quantified(
+                expr.replace_variables(src_to_dest),
+                op,
+                all,
+                subquery.replace_variables(src_to_dest),
+            ),
         }
     }
 
@@ -181,6 +380,7 @@ impl<P: Plan> Expression<P> {
                 }
             }
             Expression::Field { val } => Expression::Field { val },
+            Expression::Parameter { index, dtype } => Expression::Parameter { index, dtype },
             Expression::Binary { op, left, right } => Expression::Binary {
                 op,
                 left: Box::new(left.replace_variables_with_exprs(src_to_dest)),
@@ -191,7 +391,7 @@ impl<P: Plan> Expression<P> {
                 whens,
                 else_expr,
             } => Expression::Case {
-                expr: Box::new(expr.replace_variables_with_exprs(src_to_dest)),
+                expr: expr.map(|expr| Box::new(expr.replace_variables_with_exprs(src_to_dest))),
                 whens: whens
                     .into_iter()
                     .map(|(when, then)| {
@@ -207,6 +407,39 @@ impl<P: Plan> Expression<P> {
                 // Do nothing for subquery
                 expr,
             },
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => Expression::in_list(
+                expr.replace_variables_with_exprs(src_to_dest),
+                list,
+                negated,
+            ),
+            Expression::IsNull { expr, negated } => {
+                Expression::is_null(expr.replace_variables_with_exprs(src_to_dest), negated)
+            }
+            Expression::Cast { expr, dtype } => {
+                Expression::cast(expr.replace_variables_with_exprs(src_to_dest), dtype)
+            }
+            Expression::ScalarFunction { func, args } => Expression::scalar_function(
+                func,
+                args.into_iter()
+                    .map(|arg| arg.replace_variables_with_exprs(src_to_dest))
+                    .collect(),
+            ),
+            Expression::Quantified {
+                expr,
+                op,
+                all,
+                subquery,
+            } => Expression::Quantified {
+                expr: Box::new(expr.replace_variables_with_exprs(src_to_dest)),
+                op,
+                all,
+                // Do nothing for subquery
+                subquery,
+            },
         }
     }
 
@@ -228,6 +461,9 @@ impl<P: Plan> Expression<P> {
             Expression::Field { val } => {
                 out.push_str(&format!("{}", val));
             }
+            Expression::Parameter { index, .. } => {
+                out.push_str(&format!("${}", index));
+            }
             Expression::Binary { op, left, right } => {
                 left.print_inner(indent, out);
                 out.push_str(&format!("{}", op));
@@ -238,8 +474,11 @@ impl<P: Plan> Expression<P> {
                 whens,
                 else_expr,
             } => {
-                out.push_str("case ");
-                expr.print_inner(indent, out);
+                out.push_str("case");
+                if let Some(expr) = expr {
+                    out.push(' ');
+                    expr.print_inner(indent, out);
+                }
                 for (when, then) in whens {
                     out.push_str(" when ");
                     when.print_inner(indent, out);
@@ -255,6 +494,54 @@ impl<P: Plan> Expression<P> {
                 expr.print_inner(indent + 6, out);
                 out.push_str(&format!("{})", " ".repeat(indent + 4)));
             }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                expr.print_inner(indent, out);
+                let list: Vec<String> = list.iter().map(|val| val.to_string()).collect();
+                out.push_str(&format!(
+                    " {}IN ({})",
+                    if *negated { "NOT " } else { "" },
+                    list.join(", ")
+                ));
+            }
+            Expression::IsNull { expr, negated } => {
+                expr.print_inner(indent, out);
+                out.push_str(if *negated { " IS NOT NULL" } else { " IS NULL" });
+            }
+            Expression::Cast { expr, dtype } => {
+                out.push_str("cast(");
+                expr.print_inner(indent, out);
+                out.push_str(&format!(" as {})", dtype));
+            }
+            Expression::ScalarFunction { func, args } => {
+                out.push_str(&format!("{}(", func.to_string().to_lowercase()));
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    arg.print_inner(indent, out);
+                }
+                out.push(')');
+            }
+            Expression::Quantified {
+                expr,
+                op,
+                all,
+                subquery,
+            } => {
+                expr.print_inner(indent, out);
+                out.push_str(&format!(
+                    "{}{}λ.{:?}(\n",
+                    op,
+                    if *all { "ALL " } else { "ANY " },
+                    subquery.free()
+                ));
+                subquery.print_inner(indent + 6, out);
+                out.push_str(&format!("{})", " ".repeat(indent + 4)));
+            }
         }
     }
 
@@ -276,6 +563,10 @@ impl<P: Plan> Expression<P> {
                 src_schema.get_attribute(*offset).unwrap().clone()
             }
             Self::Field { val } => Attribute::new(self.pretty_string(), val.into()),
+            Self::Parameter { dtype, .. } => Attribute::new(
+                self.pretty_string(),
+                dtype.clone().unwrap_or(DataType::Null),
+            ),
             Self::Binary { op, left, right: _ } => {
                 let left_attr = left.to_attr(src_schema, col_id_to_offset);
                 match op {
@@ -287,9 +578,31 @@ impl<P: Plan> Expression<P> {
                     | BinaryOp::Gt
                     | BinaryOp::Ge
                     | BinaryOp::And
-                    | BinaryOp::Or => Attribute::new(self.pretty_string(), DataType::Bool),
+                    | BinaryOp::Or
+                    | BinaryOp::Like
+                    | BinaryOp::NotLike => Attribute::new(self.pretty_string(), DataType::Bool),
                 }
             }
+            Self::InList { .. } | Self::IsNull { .. } | Self::Quantified { .. } => {
+                Attribute::new(self.pretty_string(), DataType::Bool)
+            }
+            Self::Cast { dtype, .. } => Attribute::new(self.pretty_string(), dtype.clone()),
+            Self::ScalarFunction { func, .. } => {
+                Attribute::new(self.pretty_string(), func.return_type())
+            }
+            Self::Case {
+                whens, else_expr, ..
+            } => {
+                // The type of the first result that is not NULL.
+                let dtype = whens
+                    .iter()
+                    .map(|(_, then)| then)
+                    .chain([else_expr.as_ref()])
+                    .map(|result| result.to_attr(src_schema, col_id_to_offset).dtype().clone())
+                    .find(|dtype| *dtype != DataType::Null)
+                    .unwrap_or(DataType::Null);
+                Attribute::new(self.pretty_string(), dtype)
+            }
             _ => unimplemented!(),
         }
     }
@@ -318,7 +631,7 @@ impl<P: Plan> Expression<P> {
                 set.insert(*id);
                 set
             }
-            Expression::Field { val: _ } => HashSet::new(),
+            Expression::Field { val: _ } | Expression::Parameter { .. } => HashSet::new(),
             Expression::Binary { left, right, .. } => {
                 let mut set = left.free();
                 set.extend(right.free());
@@ -329,7 +642,7 @@ impl<P: Plan> Expression<P> {
                 whens,
                 else_expr,
             } => {
-                let mut set = expr.free();
+                let mut set = expr.as_ref().map_or(HashSet::new(), |expr| expr.free());
                 for (when, then) in whens {
                     set.extend(when.free());
                     set.extend(then.free());
@@ -338,6 +651,17 @@ impl<P: Plan> Expression<P> {
                 set
             }
             Expression::Subquery { expr } => expr.free(),
+            Expression::InList { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. } => expr.free(),
+            Expression::ScalarFunction { args, .. } => {
+                args.iter().flat_map(|arg| arg.free()).collect()
+            }
+            Expression::Quantified { expr, subquery, .. } => {
+                let mut set = expr.free();
+                set.extend(subquery.free());
+                set
+            }
         }
     }
 
@@ -362,6 +686,10 @@ impl Expression<LogicalRelExpr> {
         match self {
             Self::ColRef { id } => Expression::col_ref(*id),
             Self::Field { val } => Expression::Field { val: val.clone() },
+            Self::Parameter { index, dtype } => Expression::Parameter {
+                index: *index,
+                dtype: dtype.clone(),
+            },
             Self::Binary { op, left, right } => Expression::binary(
                 *op,
                 left.to_physical_expression(),
@@ -372,7 +700,9 @@ impl Expression<LogicalRelExpr> {
                 whens,
                 else_expr,
             } => Expression::Case {
-                expr: Box::new(expr.to_physical_expression()),
+                expr: expr
+                    .as_ref()
+                    .map(|expr| Box::new(expr.to_physical_expression())),
                 whens: whens
                     .iter()
                     .map(|(e1, e2)| (e1.to_physical_expression(), e2.to_physical_expression()))
@@ -380,6 +710,34 @@ impl Expression<LogicalRelExpr> {
                 else_expr: Box::new(else_expr.to_physical_expression()),
             },
             Self::Subquery { expr } => Expression::subquery(expr.to_physical_plan()),
+            Self::InList {
+                expr,
+                list,
+                negated,
+            } => Expression::in_list(expr.to_physical_expression(), list.clone(), *negated),
+            Self::IsNull { expr, negated } => {
+                Expression::is_null(expr.to_physical_expression(), *negated)
+            }
+            Self::Cast { expr, dtype } => {
+                Expression::cast(expr.to_physical_expression(), dtype.clone())
+            }
+            Self::ScalarFunction { func, args } => Expression::scalar_function(
+                *func,
+                args.iter()
+                    .map(|arg| arg.to_physical_expression())
+                    .collect(),
+            ),
+            Self::Quantified {
+                expr,
+                op,
+                all,
+                subquery,
+            } => Expression::quantified(
+                expr.to_physical_expression(),
+                *op,
+                *all,
+                subquery.to_physical_plan(),
+            ),
         }
     }
 }
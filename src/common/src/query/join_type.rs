@@ -5,6 +5,18 @@ pub enum JoinType {
     RightOuter,
     FullOuter,
     CrossJoin,
+    /// The rows of the left input that match a row of the right input.
+    Semi,
+    /// The rows of the left input that match no row of the right input.
+    Anti,
+}
+
+impl JoinType {
+    /// Whether the join returns rows of its left input only, without the
+    /// columns of the right input.
+    pub fn outputs_left_only(&self) -> bool {
+        matches!(self, JoinType::Semi | JoinType::Anti)
+    }
 }
 
 impl std::fmt::Display for JoinType {
@@ -15,6 +27,8 @@ impl std::fmt::Display for JoinType {
             JoinType::RightOuter => write!(f, "right_outer"),
             JoinType::FullOuter => write!(f, "full_outer"),
             JoinType::CrossJoin => write!(f, "cross"),
+            JoinType::Semi => write!(f, "semi"),
+            JoinType::Anti => write!(f, "anti"),
         }
     }
 }
@@ -0,0 +1,147 @@
+//! Matching of SQL LIKE patterns. `%` matches any sequence of characters, `_`
+//! matches a single character and `\` makes the character after it literal.
+
+/// Escape character of the patterns the functions below take.
+pub const LIKE_ESCAPE: char = '\\';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    /// `%`
+    Any,
+    /// `_`
+    One,
+    Literal(char),
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            // A trailing escape character is taken literally.
+            LIKE_ESCAPE => Token::Literal(chars.next().unwrap_or(LIKE_ESCAPE)),
+            c => Token::Literal(c),
+        });
+    }
+    tokens
+}
+
+/// Returns true if `s` matches `pattern`.
+pub fn like_match(s: &str, pattern: &str) -> bool {
+    let tokens = tokenize(pattern);
+    let s: Vec<char> = s.chars().collect();
+    let (mut si, mut ti) = (0, 0);
+    // Position after the last `%` and the character of `s` it was tried at.
+    let mut backtrack: Option<(usize, usize)> = None;
+    while si < s.len() {
+        match tokens.get(ti) {
+            Some(Token::Any) => {
+                ti += 1;
+                backtrack = Some((ti, si));
+            }
+            Some(Token::One) => {
+                ti += 1;
+                si += 1;
+            }
+            Some(Token::Literal(c)) if *c == s[si] => {
+                ti += 1;
+                si += 1;
+            }
+            _ => match backtrack {
+                // Let the last `%` match one more character.
+                Some((t, start)) => {
+                    ti = t;
+                    si = start + 1;
+                    backtrack = Some((t, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[ti..].iter().all(|t| *t == Token::Any)
+}
+
+/// Characters every string matching `pattern` starts with.
+pub fn literal_prefix(pattern: &str) -> String {
+    tokenize(pattern)
+        .into_iter()
+        .map_while(|t| match t {
+            Token::Literal(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrites a pattern written with `escape` as its escape character to one
+/// escaped with `LIKE_ESCAPE`.
+pub fn with_escape(pattern: &str, escape: char) -> String {
+    if escape == LIKE_ESCAPE {
+        return pattern.to_string();
+    }
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == escape {
+            out.push(LIKE_ESCAPE);
+            out.push(chars.next().unwrap_or(escape));
+        } else if c == LIKE_ESCAPE {
+            out.push(LIKE_ESCAPE);
+            out.push(LIKE_ESCAPE);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(like_match("apple", "apple"));
+        assert!(!like_match("apple", "appl"));
+        assert!(like_match("apple", "app%"));
+        assert!(like_match("apple", "%ple"));
+        assert!(like_match("apple", "%pl%"));
+        assert!(like_match("apple", "a%p%e"));
+        assert!(!like_match("apple", "%x%"));
+        assert!(like_match("apple", "_pple"));
+        assert!(like_match("apple", "a___e"));
+        assert!(!like_match("apple", "a__e"));
+        assert!(like_match("", "%"));
+        assert!(!like_match("", "_"));
+        assert!(like_match("aaab", "%a%b"));
+        assert!(like_match("abcabd", "%abd"));
+        assert!(like_match("héllo", "h_llo"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(like_match("100%", "100\\%"));
+        assert!(!like_match("1000", "100\\%"));
+        assert!(like_match("a_b", "a\\_b"));
+        assert!(!like_match("axb", "a\\_b"));
+        assert!(like_match("a\\b", "a\\\\b"));
+        assert!(like_match("50% off", "%\\%%"));
+        assert!(!like_match("50 off", "%\\%%"));
+    }
+
+    #[test]
+    fn test_literal_prefix() {
+        assert_eq!(literal_prefix("abc%d"), "abc");
+        assert_eq!(literal_prefix("a\\%b_"), "a%b");
+        assert_eq!(literal_prefix("%abc"), "");
+        assert_eq!(literal_prefix("abc"), "abc");
+    }
+
+    #[test]
+    fn test_with_escape() {
+        assert_eq!(with_escape("10!%", '!'), "10\\%");
+        assert_eq!(with_escape("a\\b!_", '!'), "a\\\\b\\_");
+        assert_eq!(with_escape("a\\%", '\\'), "a\\%");
+    }
+}
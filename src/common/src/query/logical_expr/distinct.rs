@@ -0,0 +1,10 @@
+use super::prelude::*;
+
+impl LogicalRelExpr {
+    /// Remove duplicate rows from the current logical relational expression.
+    pub fn distinct(self) -> LogicalRelExpr {
+        LogicalRelExpr::Distinct {
+            src: Box::new(self),
+        }
+    }
+}
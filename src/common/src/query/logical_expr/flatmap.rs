@@ -3,7 +3,6 @@
 use super::prelude::*;
 use crate::physical::col_id_generator::ColIdGeneratorRef;
 use crate::query::rules::{Rule, RulesRef};
-use crate::Field;
 use std::collections::HashSet;
 
 impl LogicalRelExpr {
@@ -139,15 +138,14 @@ impl LogicalRelExpr {
                             counts.into_iter().map(|id| {
                                 (
                                     id,
-                                    Expression::Case {
-                                        expr: Box::new(Expression::col_ref(id)),
-                                        whens: [(
-                                            Expression::Field { val: Field::Null },
+                                    Expression::case(
+                                        None,
+                                        vec![(
+                                            Expression::is_null(Expression::col_ref(id), false),
                                             Expression::int(0),
-                                        )]
-                                        .to_vec(),
-                                        else_expr: Box::new(Expression::col_ref(id)),
-                                    },
+                                        )],
+                                        Expression::col_ref(id),
+                                    ),
                                 )
                             }),
                         );
@@ -102,9 +102,135 @@ impl LogicalRelExpr {
                         false, // TODO: Check if `is_wildcard` should be set to False
                     )
             }
-            Expression::Field { .. } | Expression::ColRef { .. } => {
+            Expression::Field { .. } | Expression::ColRef { .. } | Expression::Parameter { .. } => {
                 self.map(true, enabled_rules, col_id_gen, vec![(id, expr)])
             }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                // Hoist the tested expression, then test it
+                let expr_id = col_id_gen.next();
+                let att = self.att();
+                self.hoist(enabled_rules, col_id_gen, expr_id, *expr)
+                    .map(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        [(
+                            id,
+                            Expression::in_list(Expression::col_ref(expr_id), list, negated),
+                        )],
+                    )
+                    .project(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        att.into_iter().chain([id]).collect(),
+                        false,
+                    )
+            }
+            Expression::IsNull { expr, negated } => {
+                // Hoist the tested expression, then test it
+                let expr_id = col_id_gen.next();
+                let att = self.att();
+                self.hoist(enabled_rules, col_id_gen, expr_id, *expr)
+                    .map(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        [(
+                            id,
+                            Expression::is_null(Expression::col_ref(expr_id), negated),
+                        )],
+                    )
+                    .project(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        att.into_iter().chain([id]).collect(),
+                        false,
+                    )
+            }
+            Expression::Cast { expr, dtype } => {
+                // Hoist the converted expression, then convert it
+                let expr_id = col_id_gen.next();
+                let att = self.att();
+                self.hoist(enabled_rules, col_id_gen, expr_id, *expr)
+                    .map(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        [(id, Expression::cast(Expression::col_ref(expr_id), dtype))],
+                    )
+                    .project(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        att.into_iter().chain([id]).collect(),
+                        false,
+                    )
+            }
+            Expression::ScalarFunction { func, args } => {
+                // Hoist the arguments, then call the function on them
+                let arg_ids: Vec<_> = args.iter().map(|_| col_id_gen.next()).collect();
+                let att = self.att();
+                let plan = args
+                    .into_iter()
+                    .zip(&arg_ids)
+                    .fold(self, |plan, (arg, arg_id)| {
+                        plan.hoist(enabled_rules, col_id_gen, *arg_id, arg)
+                    });
+                let args = arg_ids.into_iter().map(Expression::col_ref).collect();
+                plan.map(
+                    true,
+                    enabled_rules,
+                    col_id_gen,
+                    [(id, Expression::scalar_function(func, args))],
+                )
+                .project(
+                    true,
+                    enabled_rules,
+                    col_id_gen,
+                    att.into_iter().chain([id]).collect(),
+                    false,
+                )
+            }
+            Expression::Quantified {
+                expr,
+                op,
+                all,
+                subquery,
+            } => {
+                // Only the tested expression can be correlated, the translator
+                // turns correlated quantified subqueries into joins.
+                assert!(subquery.free().is_empty());
+                let expr_id = col_id_gen.next();
+                let att = self.att();
+                self.hoist(enabled_rules, col_id_gen, expr_id, *expr)
+                    .map(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        [(
+                            id,
+                            Expression::quantified(
+                                Expression::col_ref(expr_id),
+                                op,
+                                all,
+                                *subquery,
+                            ),
+                        )],
+                    )
+                    .project(
+                        true,
+                        enabled_rules,
+                        col_id_gen,
+                        att.into_iter().chain([id]).collect(),
+                        false,
+                    )
+            }
             Expression::Case { .. } => {
                 panic!("Case expression is not supported in hoist")
             }
@@ -30,11 +30,11 @@ impl LogicalRelExpr {
             .flat_map(|expr| expr.split_conjunction())
             .collect();
 
+        // The predicates of an outer, semi or anti join only decide which rows
+        // match, the rows of its preserved side are kept or dropped as a whole.
+        // So they are only pushed down into the other side.
         if optimize {
-            if matches!(
-                join_type,
-                JoinType::Inner | JoinType::LeftOuter | JoinType::CrossJoin
-            ) {
+            if matches!(join_type, JoinType::Inner | JoinType::CrossJoin) {
                 // Notice the difference from rotaki/decorrelator. Determine which
                 // predicates can be pushed down to the left and right sides respectively.
                 let (push_down_to_left, keep): (
@@ -80,7 +80,11 @@ impl LogicalRelExpr {
 
             if matches!(
                 join_type,
-                JoinType::Inner | JoinType::RightOuter | JoinType::CrossJoin
+                JoinType::Inner
+                    | JoinType::LeftOuter
+                    | JoinType::CrossJoin
+                    | JoinType::Semi
+                    | JoinType::Anti
             ) {
                 let (push_down, keep): (Vec<_>, Vec<_>) =
                     predicates.iter().partition(|pred| pred.bound_by(&other));
@@ -0,0 +1,11 @@
+use super::prelude::*;
+
+impl LogicalRelExpr {
+    /// Keep the first `limit` rows of the current logical relational expression.
+    pub fn limit(self, limit: usize) -> LogicalRelExpr {
+        LogicalRelExpr::Limit {
+            src: Box::new(self),
+            limit,
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::{HashMap, HashSet};
 
 use crate::{
     ids::{ColumnId, ContainerId},
+    keywords::quote_identifier,
     physical_expr::physical_rel_expr::PhysicalRelExpr,
     query::{expr::Expression, join_type::JoinType},
     traits::plan::Plan,
@@ -33,11 +34,19 @@ pub enum LogicalRelExpr {
         src: Box<LogicalRelExpr>,
         cols: Vec<ColumnId>,
     },
-    // TODO: The current translator does not support ORDER BY
     OrderBy {
         src: Box<LogicalRelExpr>,
         cols: Vec<(ColumnId, bool, bool)>, // (column_id, asc, nulls_first)
     },
+    Distinct {
+        // Removes duplicate rows
+        src: Box<LogicalRelExpr>,
+    },
+    Limit {
+        // Keeps the first `limit` rows
+        src: Box<LogicalRelExpr>,
+        limit: usize,
+    },
     Aggregate {
         src: Box<LogicalRelExpr>,
         group_by: Vec<ColumnId>,
@@ -59,6 +68,12 @@ pub enum LogicalRelExpr {
         src: Box<LogicalRelExpr>,
         src_to_dest: HashMap<ColumnId, ColumnId>, // (src_column_id, dest_column_id)
     },
+    Union {
+        // Appends the rows of right to the rows of left. The columns of right
+        // are matched to those of left by position and named after them.
+        left: Box<LogicalRelExpr>,
+        right: Box<LogicalRelExpr>,
+    },
 }
 
 impl Plan for LogicalRelExpr {
@@ -118,6 +133,13 @@ impl Plan for LogicalRelExpr {
                     })
                     .collect(),
             },
+            LogicalRelExpr::Distinct { src } => LogicalRelExpr::Distinct {
+                src: Box::new(src.replace_variables(src_to_dest)),
+            },
+            LogicalRelExpr::Limit { src, limit } => LogicalRelExpr::Limit {
+                src: Box::new(src.replace_variables(src_to_dest)),
+                limit,
+            },
             LogicalRelExpr::Aggregate {
                 src,
                 group_by,
@@ -169,6 +191,10 @@ impl Plan for LogicalRelExpr {
                     })
                     .collect(),
             },
+            LogicalRelExpr::Union { left, right } => LogicalRelExpr::Union {
+                left: Box::new(left.replace_variables(src_to_dest)),
+                right: Box::new(right.replace_variables(src_to_dest)),
+            },
         }
     }
 
@@ -179,7 +205,11 @@ impl Plan for LogicalRelExpr {
                 table_name,
                 column_names,
             } => {
-                out.push_str(&format!("{}-> scan({:?}, ", " ".repeat(indent), table_name,));
+                out.push_str(&format!(
+                    "{}-> scan({}, ",
+                    " ".repeat(indent),
+                    quote_identifier(table_name)
+                ));
                 let mut split = "";
                 out.push('[');
                 for col in column_names {
@@ -232,6 +262,14 @@ impl Plan for LogicalRelExpr {
                 out.push_str(&format!("{}-> order_by({:?})\n", " ".repeat(indent), cols));
                 src.print_inner(indent + 2, out);
             }
+            LogicalRelExpr::Distinct { src } => {
+                out.push_str(&format!("{}-> distinct\n", " ".repeat(indent)));
+                src.print_inner(indent + 2, out);
+            }
+            LogicalRelExpr::Limit { src, limit } => {
+                out.push_str(&format!("{}-> limit({})\n", " ".repeat(indent), limit));
+                src.print_inner(indent + 2, out);
+            }
             LogicalRelExpr::Aggregate {
                 src,
                 group_by,
@@ -288,6 +326,11 @@ impl Plan for LogicalRelExpr {
                 out.push_str(")\n");
                 src.print_inner(indent + 2, out);
             }
+            LogicalRelExpr::Union { left, right } => {
+                out.push_str(&format!("{}-> union\n", " ".repeat(indent)));
+                left.print_inner(indent + 2, out);
+                right.print_inner(indent + 2, out);
+            }
         }
     }
 
@@ -341,6 +384,7 @@ impl Plan for LogicalRelExpr {
                 }
                 set.difference(&src.att()).cloned().collect()
             }
+            LogicalRelExpr::Distinct { src } | LogicalRelExpr::Limit { src, .. } => src.free(),
             LogicalRelExpr::Aggregate {
                 src,
                 group_by,
@@ -369,6 +413,11 @@ impl Plan for LogicalRelExpr {
                 set.difference(&input.att()).cloned().collect()
             }
             LogicalRelExpr::Rename { src, .. } => src.free(),
+            LogicalRelExpr::Union { left, right } => {
+                let mut set = left.free();
+                set.extend(right.free());
+                set
+            }
         }
     }
 
@@ -387,13 +436,22 @@ impl Plan for LogicalRelExpr {
                 column_names,
             } => column_names.iter().cloned().collect(),
             LogicalRelExpr::Select { src, .. } => src.att(),
-            LogicalRelExpr::Join { left, right, .. } => {
+            LogicalRelExpr::Join {
+                join_type,
+                left,
+                right,
+                ..
+            } => {
                 let mut set = left.att();
-                set.extend(right.att());
+                if !join_type.outputs_left_only() {
+                    set.extend(right.att());
+                }
                 set
             }
             LogicalRelExpr::Project { cols, .. } => cols.iter().cloned().collect(),
-            LogicalRelExpr::OrderBy { src, .. } => src.att(),
+            LogicalRelExpr::OrderBy { src, .. }
+            | LogicalRelExpr::Distinct { src }
+            | LogicalRelExpr::Limit { src, .. } => src.att(),
             LogicalRelExpr::Aggregate {
                 group_by, aggrs, ..
             } => {
@@ -422,6 +480,8 @@ impl Plan for LogicalRelExpr {
                 }
                 set
             }
+            // The columns of right are renamed to those of left.
+            LogicalRelExpr::Union { left, .. } => left.att(),
         }
     }
 }
@@ -462,35 +522,15 @@ impl LogicalRelExpr {
                 left,
                 right,
                 predicates,
-            } => {
-                let predicates: Vec<Expression<PhysicalRelExpr>> = predicates
+            } => PhysicalRelExpr::join(
+                *join_type,
+                left.to_physical_plan(),
+                right.to_physical_plan(),
+                predicates
                     .iter()
                     .map(|e| e.to_physical_expression())
-                    .collect();
-                let predicates = vec![Expression::combine_preds(predicates.as_slice())];
-
-                if predicates.len() == 1 {
-                    if let Expression::Binary {
-                        op: crate::BinaryOp::Eq,
-                        left: left_key,
-                        right: right_key,
-                    } = &predicates[0]
-                    {
-                        debug!(
-                            "Join predicate is an binary equality predicate between {:?} and {:?}",
-                            left_key, right_key
-                        );
-                    }
-                }
-
-                PhysicalRelExpr::NestedLoopJoin {
-                    join_type: *join_type,
-                    left: Box::new(left.to_physical_plan()),
-                    right: Box::new(right.to_physical_plan()),
-                    predicates,
-                    tree_hash: None, // this is only for identification -- not needed for QO
-                }
-            }
+                    .collect(),
+            ),
             Self::Project { src, cols } => PhysicalRelExpr::Project {
                 src: Box::new(src.to_physical_plan()),
                 cols: cols.clone(),
@@ -501,6 +541,15 @@ impl LogicalRelExpr {
                 cols: cols.clone(),
                 tree_hash: None,
             },
+            Self::Distinct { src } => PhysicalRelExpr::HashDistinct {
+                src: Box::new(src.to_physical_plan()),
+                tree_hash: None,
+            },
+            Self::Limit { src, limit } => PhysicalRelExpr::Limit {
+                src: Box::new(src.to_physical_plan()),
+                limit: *limit,
+                tree_hash: None,
+            },
             Self::Aggregate {
                 src,
                 group_by,
@@ -529,6 +578,11 @@ impl LogicalRelExpr {
                 src_to_dest: src_to_dest.clone(),
                 tree_hash: None,
             },
+            Self::Union { left, right } => PhysicalRelExpr::Union {
+                left: Box::new(left.to_physical_plan()),
+                right: Box::new(right.to_physical_plan()),
+                tree_hash: None,
+            },
         }
     }
 }
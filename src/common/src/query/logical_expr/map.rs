@@ -22,8 +22,9 @@ impl LogicalRelExpr {
         if optimize {
             if enabled_rules.is_enabled(&Rule::Hoist) {
                 for i in 0..exprs.len() {
-                    // Only hoist expressions with subqueries
-                    if exprs[i].1.has_subquery() {
+                    // Only hoist expressions with correlated subqueries. Uncorrelated
+                    // ones are run once before the query and replaced by their value.
+                    if exprs[i].1.has_correlated_subquery() {
                         let (id, expr) = exprs.swap_remove(i);
                         return self.map(true, enabled_rules, col_id_gen, exprs).hoist(
                             enabled_rules,
@@ -46,15 +47,15 @@ impl LogicalRelExpr {
                     // This is beneficial because we can now pushdown projections
                     // and remove the intermediate map if it is not needed. For example,
                     // if the projection is on @2, we can remove the first map to @1.
-                    // If there is a subquery, then it should have already been hoisted.
+                    // If there is a correlated subquery, then it should have already been hoisted.
                     #[cfg(debug_assertions)]
                     {
-                        // Check that none of the expressions have subqueries
+                        // Check that none of the expressions have correlated subqueries
                         for (_, expr) in &existing_exprs {
-                            assert!(!expr.has_subquery());
+                            assert!(!expr.has_correlated_subquery());
                         }
                         for (_, expr) in &exprs {
-                            assert!(!expr.has_subquery());
+                            assert!(!expr.has_correlated_subquery());
                         }
                     }
 
@@ -1,15 +1,19 @@
 // Reference: https://github.com/rotaki/decorrelator
 
 mod aggregate;
+mod distinct;
 mod flatmap;
 mod hoist;
 mod join;
+mod limit;
 mod logical_rel_expr;
 mod map;
+mod order_by;
 mod project;
 mod rename;
 mod scan;
 mod select;
+mod union;
 
 pub mod prelude {
     pub use super::logical_rel_expr::LogicalRelExpr;
@@ -0,0 +1,15 @@
+use super::prelude::*;
+
+impl LogicalRelExpr {
+    /// Sort the rows of the current logical relational expression.
+    /// cols: (column_id, asc, nulls_first)
+    pub fn order_by(self, cols: Vec<(ColumnId, bool, bool)>) -> LogicalRelExpr {
+        if cols.is_empty() {
+            return self;
+        }
+        LogicalRelExpr::OrderBy {
+            src: Box::new(self),
+            cols,
+        }
+    }
+}
@@ -96,6 +96,14 @@ impl LogicalRelExpr {
                         .select(true, enabled_rules, col_id_gen, predicates)
                         .project(false, enabled_rules, col_id_gen, cols, false)
                 }
+                LogicalRelExpr::OrderBy { src, cols: keys } => {
+                    // The necessary columns are the sort keys and the projection columns
+                    let free: HashSet<usize> = keys.iter().map(|(id, _, _)| *id).collect();
+                    let new_cols = union(&free, cols.clone());
+                    src.project(true, enabled_rules, col_id_gen, new_cols, false)
+                        .order_by(keys)
+                        .project(false, enabled_rules, col_id_gen, cols, false)
+                }
                 LogicalRelExpr::Join {
                     join_type,
                     left,
@@ -13,7 +13,10 @@ impl LogicalRelExpr {
         _enabled_rules: &RulesRef,
         col_id_gen: &ColIdGeneratorRef,
     ) -> (LogicalRelExpr, HashMap<usize, usize>) {
-        let atts = self.att();
+        // New IDs follow the order of the old ones, so that the columns of a
+        // table get IDs in the order of the table.
+        let mut atts: Vec<usize> = self.att().into_iter().collect();
+        atts.sort();
         let cols: HashMap<usize, usize> = atts
             .into_iter()
             .map(|old_col_id| (old_col_id, col_id_gen.next()))
@@ -31,8 +31,10 @@ impl LogicalRelExpr {
                     preds.append(&mut predicates);
                     src.select(true, enabled_rules, col_id_gen, preds)
                 }
+                // Filtering the output of an outer join also drops padded rows,
+                // so only the predicates of an inner join take the filter's.
                 LogicalRelExpr::Join {
-                    join_type,
+                    join_type: join_type @ (JoinType::Inner | JoinType::CrossJoin),
                     left,
                     right,
                     predicates: mut preds,
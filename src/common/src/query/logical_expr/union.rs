@@ -0,0 +1,13 @@
+use super::prelude::*;
+
+impl LogicalRelExpr {
+    /// Append the rows of `right` to the rows of the current logical relational
+    /// expression. The columns of `right` are matched to those of the current
+    /// expression by position.
+    pub fn union(self, right: LogicalRelExpr) -> LogicalRelExpr {
+        LogicalRelExpr::Union {
+            left: Box::new(self),
+            right: Box::new(right),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod bytecode_expr;
 pub mod expr;
 pub mod join_type;
+pub mod like;
 pub mod logical_expr;
 pub mod operation;
 pub mod origin_expr;
@@ -8,3 +9,4 @@ pub mod physical_expr;
 pub mod query_registrar;
 pub mod query_result;
 pub mod rules;
+pub mod scalar_func;
@@ -20,6 +20,8 @@ pub enum BinaryOp {
     Ge,
     And,
     Or,
+    Like,
+    NotLike,
 }
 
 impl std::fmt::Display for BinaryOp {
@@ -37,6 +39,8 @@ impl std::fmt::Display for BinaryOp {
             BinaryOp::Ge => write!(f, ">="),
             BinaryOp::And => write!(f, "&&"),
             BinaryOp::Or => write!(f, "||"),
+            BinaryOp::Like => write!(f, "LIKE"),
+            BinaryOp::NotLike => write!(f, "NOT LIKE"),
         }
     }
 }
@@ -48,6 +52,11 @@ pub enum AggOp {
     Max,
     Min,
     Sum,
+    /// Sample standard deviation.
+    StdDev,
+    /// Sample variance.
+    Variance,
+    Median,
 }
 
 impl std::fmt::Display for AggOp {
@@ -59,16 +68,35 @@ impl std::fmt::Display for AggOp {
             Max => write!(f, "MAX"),
             Min => write!(f, "MIN"),
             Sum => write!(f, "SUM"),
+            StdDev => write!(f, "STDDEV"),
+            Variance => write!(f, "VARIANCE"),
+            Median => write!(f, "MEDIAN"),
         }
     }
 }
 
 impl AggOp {
+    /// The aggregation function called `name` in SQL, if any.
+    pub fn from_name(name: &str) -> Option<AggOp> {
+        use AggOp::*;
+        match name.to_uppercase().as_str() {
+            "AVG" => Some(Avg),
+            "COUNT" => Some(Count),
+            "MAX" => Some(Max),
+            "MIN" => Some(Min),
+            "SUM" => Some(Sum),
+            "STDDEV" => Some(StdDev),
+            "VARIANCE" => Some(Variance),
+            "MEDIAN" => Some(Median),
+            _ => None,
+        }
+    }
+
     pub fn to_attr(&self, src_att: &Attribute) -> Attribute {
         let new_name = format!("{}({})", self, src_att.name);
         match self {
             AggOp::Avg => {
-                if matches!(&src_att.dtype, DataType::Decimal(_, _)) {
+                if matches!(&src_att.dtype, DataType::Decimal(_, _) | DataType::Float64) {
                     Attribute::new(new_name, src_att.dtype.clone())
                 } else {
                     Attribute::new(
@@ -78,6 +106,17 @@ impl AggOp {
                 }
             }
             AggOp::Count => Attribute::new(new_name, DataType::BigInt),
+            // Floats for floats, decimals of the default scale otherwise.
+            AggOp::StdDev | AggOp::Variance | AggOp::Median => {
+                if src_att.dtype == DataType::Float64 {
+                    Attribute::new(new_name, DataType::Float64)
+                } else {
+                    Attribute::new(
+                        new_name,
+                        DataType::Decimal(default_decimal_precision(), default_decimal_scale()),
+                    )
+                }
+            }
             _ => Attribute::new(new_name, src_att.dtype.clone()),
         }
     }
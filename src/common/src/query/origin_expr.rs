@@ -4,7 +4,7 @@ use crate::{
     ids::{ColumnId, ContainerId},
     logical_expr::prelude::{Expression, LogicalRelExpr},
     physical_expr::physical_rel_expr::PhysicalRelExpr,
-    BinaryOp, Field,
+    BinaryOp, DataType, Field, ScalarFunc,
 };
 
 /// Similar to Expression<P> and MemoExpression. Its purpose is to map the uniquely
@@ -33,18 +33,41 @@ pub enum OriginExpression {
         right: Box<Self>,
     },
     Case {
-        expr: Box<Self>,
+        expr: Option<Box<Self>>,
         whens: Vec<(Self, Self)>,
         else_expr: Box<Self>,
     },
-    // Does not support subquery for now
+    /// Value of an uncorrelated subquery, or of a comparison with one, only
+    /// known once the query runs.
+    Subquery,
+    /// Parameter of a prepared statement, only known once it is executed.
+    Parameter,
+    InList {
+        expr: Box<Self>,
+        list: Vec<Field>,
+        negated: bool,
+    },
+    IsNull {
+        expr: Box<Self>,
+        negated: bool,
+    },
+    Cast {
+        expr: Box<Self>,
+        dtype: DataType,
+    },
+    ScalarFunction {
+        func: ScalarFunc,
+        args: Vec<Self>,
+    },
 }
 
 impl OriginExpression {
     pub fn get_base_ids_and_index(&self) -> Vec<(ContainerId, usize)> {
         match self {
             OriginExpression::BaseCidAndIndex { cid, index } => vec![(*cid, *index)],
-            OriginExpression::Field { val: _ } => vec![],
+            OriginExpression::Field { val: _ }
+            | OriginExpression::Subquery
+            | OriginExpression::Parameter => vec![],
             OriginExpression::Binary { left, right, .. } => {
                 let mut res = left.get_base_ids_and_index();
                 res.extend(right.get_base_ids_and_index());
@@ -55,7 +78,9 @@ impl OriginExpression {
                 whens,
                 else_expr,
             } => {
-                let mut res = expr.get_base_ids_and_index();
+                let mut res = expr
+                    .as_ref()
+                    .map_or(vec![], |expr| expr.get_base_ids_and_index());
                 for (when, then) in whens {
                     res.extend(when.get_base_ids_and_index());
                     res.extend(then.get_base_ids_and_index());
@@ -63,6 +88,13 @@ impl OriginExpression {
                 res.extend(else_expr.get_base_ids_and_index());
                 res
             }
+            OriginExpression::InList { expr, .. }
+            | OriginExpression::IsNull { expr, .. }
+            | OriginExpression::Cast { expr, .. } => expr.get_base_ids_and_index(),
+            OriginExpression::ScalarFunction { args, .. } => args
+                .iter()
+                .flat_map(|arg| arg.get_base_ids_and_index())
+                .collect(),
             // DerivedColRef should already been resolved to BaseCidAndIndex before
             // by calling the `get_origin` function in the environment.
             OriginExpression::DerivedColRef { .. } => {
@@ -77,6 +109,7 @@ impl From<Expression<LogicalRelExpr>> for OriginExpression {
         match expr {
             Expression::ColRef { id } => OriginExpression::DerivedColRef { col_id: id },
             Expression::Field { val } => OriginExpression::Field { val },
+            Expression::Parameter { .. } => OriginExpression::Parameter,
             Expression::Binary { op, left, right } => OriginExpression::Binary {
                 op,
                 left: Box::new((*left).into()),
@@ -87,16 +120,37 @@ impl From<Expression<LogicalRelExpr>> for OriginExpression {
                 whens,
                 else_expr,
             } => OriginExpression::Case {
-                expr: Box::new((*expr).into()),
+                expr: expr.map(|expr| Box::new((*expr).into())),
                 whens: whens
                     .iter()
                     .map(|(when, then)| ((when.clone()).into(), (then.clone()).into()))
                     .collect(),
                 else_expr: Box::new((*else_expr).into()),
             },
-            Expression::Subquery { .. } => {
-                unimplemented!("Subquery is currently not supported in OriginExpression")
+            Expression::Subquery { .. } | Expression::Quantified { .. } => {
+                OriginExpression::Subquery
             }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => OriginExpression::InList {
+                expr: Box::new((*expr).into()),
+                list,
+                negated,
+            },
+            Expression::IsNull { expr, negated } => OriginExpression::IsNull {
+                expr: Box::new((*expr).into()),
+                negated,
+            },
+            Expression::Cast { expr, dtype } => OriginExpression::Cast {
+                expr: Box::new((*expr).into()),
+                dtype,
+            },
+            Expression::ScalarFunction { func, args } => OriginExpression::ScalarFunction {
+                func,
+                args: args.into_iter().map(|arg| arg.into()).collect(),
+            },
         }
     }
 }
@@ -106,6 +160,7 @@ impl From<Expression<PhysicalRelExpr>> for OriginExpression {
         match expr {
             Expression::ColRef { id } => OriginExpression::DerivedColRef { col_id: id },
             Expression::Field { val } => OriginExpression::Field { val },
+            Expression::Parameter { .. } => OriginExpression::Parameter,
             Expression::Binary { op, left, right } => OriginExpression::Binary {
                 op,
                 left: Box::new((*left).into()),
@@ -116,16 +171,37 @@ impl From<Expression<PhysicalRelExpr>> for OriginExpression {
                 whens,
                 else_expr,
             } => OriginExpression::Case {
-                expr: Box::new((*expr).into()),
+                expr: expr.map(|expr| Box::new((*expr).into())),
                 whens: whens
                     .iter()
                     .map(|(when, then)| ((when.clone()).into(), (then.clone()).into()))
                     .collect(),
                 else_expr: Box::new((*else_expr).into()),
             },
-            Expression::Subquery { .. } => {
-                unimplemented!("Subquery is currently not supported in OriginExpression")
+            Expression::Subquery { .. } | Expression::Quantified { .. } => {
+                OriginExpression::Subquery
             }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => OriginExpression::InList {
+                expr: Box::new((*expr).into()),
+                list,
+                negated,
+            },
+            Expression::IsNull { expr, negated } => OriginExpression::IsNull {
+                expr: Box::new((*expr).into()),
+                negated,
+            },
+            Expression::Cast { expr, dtype } => OriginExpression::Cast {
+                expr: Box::new((*expr).into()),
+                dtype,
+            },
+            Expression::ScalarFunction { func, args } => OriginExpression::ScalarFunction {
+                func,
+                args: args.into_iter().map(|arg| arg.into()).collect(),
+            },
         }
     }
 }
@@ -145,13 +221,25 @@ impl From<OriginExpression> for Expression<PhysicalRelExpr> {
                 whens,
                 else_expr,
             } => Expression::Case {
-                expr: Box::new((*expr).into()),
+                expr: expr.map(|expr| Box::new((*expr).into())),
                 whens: whens
                     .iter()
                     .map(|(when, then)| ((when.clone()).into(), (then.clone()).into()))
                     .collect(),
                 else_expr: Box::new((*else_expr).into()),
             },
+            OriginExpression::InList {
+                expr,
+                list,
+                negated,
+            } => Expression::in_list((*expr).into(), list, negated),
+            OriginExpression::IsNull { expr, negated } => {
+                Expression::is_null((*expr).into(), negated)
+            }
+            OriginExpression::Cast { expr, dtype } => Expression::cast((*expr).into(), dtype),
+            OriginExpression::ScalarFunction { func, args } => {
+                Expression::scalar_function(func, args.into_iter().map(|arg| arg.into()).collect())
+            }
             // DerivedColRef should already been resolved to BaseCidAndIndex before
             // calling this function. Call the `get_origin` function in the environment.
             _ => unimplemented!(),
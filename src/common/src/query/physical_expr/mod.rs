@@ -1,2 +1,3 @@
 pub mod physical_rel_expr;
 mod physical_rel_expr_hashing_tests;
+pub mod plan_schema;
@@ -13,9 +13,10 @@ use std::{
 use crate::{
     error::c_err,
     ids::{ColumnId, ContainerId},
+    keywords::quote_identifier,
     logical_expr::prelude::{Expression, JoinType},
     traits::plan::Plan,
-    AggOp, FairyError,
+    AggOp, BinaryOp, FairyError,
 };
 
 #[derive(Debug, Clone)]
@@ -75,12 +76,37 @@ pub enum PhysicalRelExpr {
         cols: Vec<(ColumnId, bool, bool)>, // (column_id, asc, nulls_first)
         tree_hash: Option<u64>,            // Optional hash code for representing the plan
     },
+    HashDistinct {
+        // Removes duplicate rows, keeping the first of each
+        src: Box<PhysicalRelExpr>,
+        tree_hash: Option<u64>, // Optional hash code for representing the plan
+    },
+    Limit {
+        // Keeps the first `limit` rows
+        src: Box<PhysicalRelExpr>,
+        limit: usize,
+        tree_hash: Option<u64>, // Optional hash code for representing the plan
+    },
+    TopN {
+        // Like Sort followed by Limit, but only the first `limit` rows are kept
+        src: Box<PhysicalRelExpr>,
+        cols: Vec<(ColumnId, bool, bool)>, // (column_id, asc, nulls_first)
+        limit: usize,
+        tree_hash: Option<u64>, // Optional hash code for representing the plan
+    },
     HashAggregate {
         src: Box<PhysicalRelExpr>,
         group_by: Vec<ColumnId>,
         aggrs: Vec<(ColumnId, (ColumnId, AggOp))>, // (dest_column_id, (src_column_id, agg_op)
         tree_hash: Option<u64>,                    // Optional hash code for representing the plan
     },
+    StreamAggregate {
+        // Like HashAggregate, but the input arrives grouped, see `output_order`
+        src: Box<PhysicalRelExpr>,
+        group_by: Vec<ColumnId>,
+        aggrs: Vec<(ColumnId, (ColumnId, AggOp))>, // (dest_column_id, (src_column_id, agg_op)
+        tree_hash: Option<u64>,                    // Optional hash code for representing the plan
+    },
     Map {
         // Appends new columns to the result
         // This is the only operator that can have a reference to the columns of
@@ -100,6 +126,13 @@ pub enum PhysicalRelExpr {
         src_to_dest: HashMap<ColumnId, ColumnId>, // (src_column_id, dest_column_id)
         tree_hash: Option<u64>,                   // Optional hash code for representing the plan
     },
+    Union {
+        // Returns the rows of left followed by the rows of right, whose columns
+        // are matched to those of left by position
+        left: Box<PhysicalRelExpr>,
+        right: Box<PhysicalRelExpr>,
+        tree_hash: Option<u64>, // Optional hash code for representing the plan
+    },
 }
 
 impl Plan for PhysicalRelExpr {
@@ -226,6 +259,35 @@ impl Plan for PhysicalRelExpr {
                     .collect(),
                 tree_hash,
             },
+            PhysicalRelExpr::HashDistinct { src, tree_hash } => PhysicalRelExpr::HashDistinct {
+                src: Box::new(src.replace_variables(src_to_dest)),
+                tree_hash,
+            },
+            PhysicalRelExpr::Limit {
+                src,
+                limit,
+                tree_hash,
+            } => PhysicalRelExpr::Limit {
+                src: Box::new(src.replace_variables(src_to_dest)),
+                limit,
+                tree_hash,
+            },
+            PhysicalRelExpr::TopN {
+                src,
+                cols,
+                limit,
+                tree_hash,
+            } => PhysicalRelExpr::TopN {
+                src: Box::new(src.replace_variables(src_to_dest)),
+                cols: cols
+                    .into_iter()
+                    .map(|(id, asc, nulls_first)| {
+                        (*src_to_dest.get(&id).unwrap_or(&id), asc, nulls_first)
+                    })
+                    .collect(),
+                limit,
+                tree_hash,
+            },
             PhysicalRelExpr::HashAggregate {
                 src,
                 group_by,
@@ -248,6 +310,28 @@ impl Plan for PhysicalRelExpr {
                     .collect(),
                 tree_hash,
             },
+            PhysicalRelExpr::StreamAggregate {
+                src,
+                group_by,
+                aggrs,
+                tree_hash,
+            } => PhysicalRelExpr::StreamAggregate {
+                src: Box::new(src.replace_variables(src_to_dest)),
+                group_by: group_by
+                    .into_iter()
+                    .map(|id| *src_to_dest.get(&id).unwrap_or(&id))
+                    .collect(),
+                aggrs: aggrs
+                    .into_iter()
+                    .map(|(id, (src_id, op))| {
+                        (
+                            *src_to_dest.get(&id).unwrap_or(&id),
+                            (*src_to_dest.get(&src_id).unwrap_or(&src_id), op),
+                        )
+                    })
+                    .collect(),
+                tree_hash,
+            },
             PhysicalRelExpr::Map {
                 input,
                 exprs,
@@ -291,6 +375,15 @@ impl Plan for PhysicalRelExpr {
                     .collect(),
                 tree_hash,
             },
+            PhysicalRelExpr::Union {
+                left,
+                right,
+                tree_hash,
+            } => PhysicalRelExpr::Union {
+                left: Box::new(left.replace_variables(src_to_dest)),
+                right: Box::new(right.replace_variables(src_to_dest)),
+                tree_hash,
+            },
         }
     }
 
@@ -302,7 +395,11 @@ impl Plan for PhysicalRelExpr {
                 column_names,
                 ..
             } => {
-                out.push_str(&format!("{}-> scan({:?}, ", " ".repeat(indent), table_name,));
+                out.push_str(&format!(
+                    "{}-> scan({}, ",
+                    " ".repeat(indent),
+                    quote_identifier(table_name)
+                ));
                 let mut split = "";
                 out.push('[');
                 for col in column_names {
@@ -428,13 +525,42 @@ impl Plan for PhysicalRelExpr {
                 out.push_str(&format!("{}-> order_by({:?})\n", " ".repeat(indent), cols));
                 src.print_inner(indent + 2, out);
             }
+            PhysicalRelExpr::HashDistinct { src, .. } => {
+                out.push_str(&format!("{}-> hash_distinct\n", " ".repeat(indent)));
+                src.print_inner(indent + 2, out);
+            }
+            PhysicalRelExpr::Limit { src, limit, .. } => {
+                out.push_str(&format!("{}-> limit({})\n", " ".repeat(indent), limit));
+                src.print_inner(indent + 2, out);
+            }
+            PhysicalRelExpr::TopN {
+                src, cols, limit, ..
+            } => {
+                out.push_str(&format!(
+                    "{}-> top_n({}, {:?})\n",
+                    " ".repeat(indent),
+                    limit,
+                    cols
+                ));
+                src.print_inner(indent + 2, out);
+            }
             PhysicalRelExpr::HashAggregate {
                 src,
                 group_by,
                 aggrs,
                 ..
+            }
+            | PhysicalRelExpr::StreamAggregate {
+                src,
+                group_by,
+                aggrs,
+                ..
             } => {
-                out.push_str(&format!("{}-> aggregate(", " ".repeat(indent)));
+                let strategy = match self {
+                    PhysicalRelExpr::StreamAggregate { .. } => "stream",
+                    _ => "hash",
+                };
+                out.push_str(&format!("{}-> {}_aggregate(", " ".repeat(indent), strategy));
                 out.push_str("group_by: [");
                 let mut split = "";
                 for col in group_by {
@@ -486,6 +612,11 @@ impl Plan for PhysicalRelExpr {
                 out.push_str(")\n");
                 src.print_inner(indent + 2, out);
             }
+            PhysicalRelExpr::Union { left, right, .. } => {
+                out.push_str(&format!("{}-> union\n", " ".repeat(indent)));
+                left.print_inner(indent + 2, out);
+                right.print_inner(indent + 2, out);
+            }
         }
     }
 
@@ -552,18 +683,27 @@ impl Plan for PhysicalRelExpr {
                 }
                 set.difference(&src.att()).cloned().collect()
             }
-            PhysicalRelExpr::Sort { src, cols, .. } => {
+            PhysicalRelExpr::Sort { src, cols, .. } | PhysicalRelExpr::TopN { src, cols, .. } => {
                 let mut set = src.free();
                 for (id, _, _) in cols {
                     set.insert(*id);
                 }
                 set.difference(&src.att()).cloned().collect()
             }
+            PhysicalRelExpr::HashDistinct { src, .. } | PhysicalRelExpr::Limit { src, .. } => {
+                src.free()
+            }
             PhysicalRelExpr::HashAggregate {
                 src,
                 group_by,
                 aggrs,
                 ..
+            }
+            | PhysicalRelExpr::StreamAggregate {
+                src,
+                group_by,
+                aggrs,
+                ..
             } => {
                 let mut set = src.free();
                 for id in group_by {
@@ -587,6 +727,11 @@ impl Plan for PhysicalRelExpr {
                 set.difference(&input.att()).cloned().collect()
             }
             PhysicalRelExpr::Rename { src, .. } => src.free(),
+            PhysicalRelExpr::Union { left, right, .. } => {
+                let mut set = left.free();
+                set.extend(right.free());
+                set
+            }
         }
     }
 
@@ -606,18 +751,46 @@ impl Plan for PhysicalRelExpr {
                 tree_hash: _,
             } => column_names.iter().cloned().collect(),
             PhysicalRelExpr::Select { src, .. } => src.att(),
-            PhysicalRelExpr::CrossJoin { left, right, .. }
-            | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
-            | PhysicalRelExpr::HashJoin { left, right, .. }
-            | PhysicalRelExpr::SortMergeJoin { left, right, .. } => {
+            PhysicalRelExpr::CrossJoin {
+                join_type,
+                left,
+                right,
+                ..
+            }
+            | PhysicalRelExpr::NestedLoopJoin {
+                join_type,
+                left,
+                right,
+                ..
+            }
+            | PhysicalRelExpr::HashJoin {
+                join_type,
+                left,
+                right,
+                ..
+            }
+            | PhysicalRelExpr::SortMergeJoin {
+                join_type,
+                left,
+                right,
+                ..
+            } => {
                 let mut set = left.att();
-                set.extend(right.att());
+                if !join_type.outputs_left_only() {
+                    set.extend(right.att());
+                }
                 set
             }
             PhysicalRelExpr::Project { cols, .. } => cols.iter().cloned().collect(),
-            PhysicalRelExpr::Sort { src, .. } => src.att(),
+            PhysicalRelExpr::Sort { src, .. }
+            | PhysicalRelExpr::HashDistinct { src, .. }
+            | PhysicalRelExpr::Limit { src, .. }
+            | PhysicalRelExpr::TopN { src, .. } => src.att(),
             PhysicalRelExpr::HashAggregate {
                 group_by, aggrs, ..
+            }
+            | PhysicalRelExpr::StreamAggregate {
+                group_by, aggrs, ..
             } => {
                 let mut set: HashSet<usize> = group_by.iter().cloned().collect();
                 set.extend(aggrs.iter().map(|(id, _)| *id));
@@ -644,6 +817,8 @@ impl Plan for PhysicalRelExpr {
                 }
                 set
             }
+            // The columns of right are renamed to those of left.
+            PhysicalRelExpr::Union { left, .. } => left.att(),
         }
     }
 }
@@ -656,6 +831,78 @@ fn compute_hash(data: &str) -> u64 {
     hasher.finish()
 }
 
+/// Whether `pred` is an equality between an expression over the columns of
+/// `left` and one over the columns of `right`.
+fn is_join_key(
+    pred: &Expression<PhysicalRelExpr>,
+    left: &PhysicalRelExpr,
+    right: &PhysicalRelExpr,
+) -> bool {
+    let Expression::Binary {
+        op: BinaryOp::Eq,
+        left: a,
+        right: b,
+    } = pred
+    else {
+        return false;
+    };
+    let over = |e: &Expression<PhysicalRelExpr>, plan: &PhysicalRelExpr| {
+        !e.free().is_empty() && e.bound_by(plan)
+    };
+    (over(a, left) && over(b, right)) || (over(a, right) && over(b, left))
+}
+
+/// Address of a node, which identifies it as long as its plan is not moved.
+fn node_id(node: &PhysicalRelExpr) -> usize {
+    node as *const PhysicalRelExpr as usize
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of nodes hashed by this thread.
+    pub(crate) static NODES_HASHED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Hashes of the nodes of a plan, computed by `PhysicalRelExpr::compute_hashes`.
+///
+/// Nodes are identified by their address, so the hashes are only valid for the plan
+/// they were computed from, while it is neither moved nor changed. Plans kept in a
+/// `Box` or `Arc` can be moved around freely.
+#[derive(Debug, Clone)]
+pub struct PlanHashes {
+    root: u64,
+    nodes: HashMap<usize, u64>,
+    /// Child indexes leading from the root to the topmost node with each hash.
+    paths: HashMap<u64, Vec<usize>>,
+}
+
+impl PlanHashes {
+    /// Hash of the whole plan.
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Hash of a node of the plan.
+    pub fn get(&self, node: &PhysicalRelExpr) -> Result<u64, FairyError> {
+        self.nodes.get(&node_id(node)).copied().ok_or_else(|| {
+            c_err(&format!(
+                "no hash was computed for plan node {}",
+                node.label()
+            ))
+        })
+    }
+
+    /// Returns the topmost node of `plan` with the given hash, where `plan` is the
+    /// plan the hashes were computed from.
+    pub fn find<'a>(&self, plan: &'a PhysicalRelExpr, hash: u64) -> Option<&'a PhysicalRelExpr> {
+        let mut node = plan;
+        for i in self.paths.get(&hash)? {
+            node = *node.children().get(*i)?;
+        }
+        Some(node)
+    }
+}
+
 impl PhysicalRelExpr {
     pub fn pretty_print(&self) {
         println!("{}", self.pretty_string());
@@ -676,7 +923,11 @@ impl PhysicalRelExpr {
         if let PhysicalRelExpr::Select { src, .. }
         | PhysicalRelExpr::Project { src, .. }
         | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
         | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. }
         | PhysicalRelExpr::Map { input: src, .. }
         | PhysicalRelExpr::FlatMap { input: src, .. }
         | PhysicalRelExpr::Rename { src, .. } = self
@@ -687,7 +938,8 @@ impl PhysicalRelExpr {
         if let PhysicalRelExpr::CrossJoin { left, right, .. }
         | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
         | PhysicalRelExpr::HashJoin { left, right, .. }
-        | PhysicalRelExpr::SortMergeJoin { left, right, .. } = self
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } = self
         {
             left.get_tables_involved(container_ids);
             right.get_tables_involved(container_ids);
@@ -696,6 +948,224 @@ impl PhysicalRelExpr {
         if let PhysicalRelExpr::FlatMap { func, .. } = self {
             func.get_tables_involved(container_ids);
         }
+
+        for subquery in self.expr_subqueries() {
+            subquery.get_tables_involved(container_ids);
+        }
+    }
+
+    /// Get the columns read by every scan in the expression, keyed by table.
+    /// Column ids are the temporary scan ids, see `get_column_index_from_temp_col_id`.
+    pub fn get_scanned_columns(&self, columns: &mut HashMap<ContainerId, HashSet<ColumnId>>) {
+        if let PhysicalRelExpr::Scan {
+            cid, column_names, ..
+        } = self
+        {
+            columns
+                .entry(*cid)
+                .or_default()
+                .extend(column_names.iter().copied());
+        }
+
+        if let PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. }
+        | PhysicalRelExpr::Map { input: src, .. }
+        | PhysicalRelExpr::FlatMap { input: src, .. }
+        | PhysicalRelExpr::Rename { src, .. } = self
+        {
+            src.get_scanned_columns(columns);
+        }
+
+        if let PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } = self
+        {
+            left.get_scanned_columns(columns);
+            right.get_scanned_columns(columns);
+        }
+
+        if let PhysicalRelExpr::FlatMap { func, .. } = self {
+            func.get_scanned_columns(columns);
+        }
+
+        for subquery in self.expr_subqueries() {
+            subquery.get_scanned_columns(columns);
+        }
+    }
+
+    /// Joins `left` and `right` on the conjunction of `predicates`. A join with
+    /// an equality between the two inputs becomes a hash join keyed by its
+    /// equalities, which evaluates the other predicates over the matches. An
+    /// inner or cross join without predicates becomes a cross join. Outer joins
+    /// keep their type, and pad the rows that match nothing with NULLs.
+    pub fn join(
+        join_type: JoinType,
+        left: PhysicalRelExpr,
+        right: PhysicalRelExpr,
+        predicates: Vec<Expression<Self>>,
+    ) -> Self {
+        let (left, right) = (Box::new(left), Box::new(right));
+        let outer = !matches!(join_type, JoinType::Inner | JoinType::CrossJoin);
+        let predicates: Vec<Expression<Self>> = predicates
+            .into_iter()
+            .flat_map(|pred| pred.split_conjunction())
+            .collect();
+        if predicates.is_empty() && !outer {
+            return PhysicalRelExpr::CrossJoin {
+                join_type: JoinType::CrossJoin,
+                left,
+                right,
+                predicates,
+                tree_hash: None,
+            };
+        }
+        let join_type = if outer { join_type } else { JoinType::Inner };
+        if predicates
+            .iter()
+            .any(|pred| is_join_key(pred, &left, &right))
+        {
+            return PhysicalRelExpr::HashJoin {
+                join_type,
+                left,
+                right,
+                predicates,
+                tree_hash: None,
+            };
+        }
+        PhysicalRelExpr::NestedLoopJoin {
+            join_type,
+            left,
+            right,
+            predicates,
+            tree_hash: None,
+        }
+    }
+
+    /// Whether the plan or one of its subqueries joins two inputs without a
+    /// predicate relating them, pairing every row of one with every row of the
+    /// other.
+    pub fn has_cross_product(&self) -> bool {
+        let unrelated = match self {
+            PhysicalRelExpr::CrossJoin {
+                left,
+                right,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::NestedLoopJoin {
+                left,
+                right,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::HashJoin {
+                left,
+                right,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::SortMergeJoin {
+                left,
+                right,
+                predicates,
+                ..
+            } => !predicates.iter().any(|pred| {
+                pred.intersect_with(left.as_ref()) && pred.intersect_with(right.as_ref())
+            }),
+            _ => false,
+        };
+        unrelated
+            || self
+                .children()
+                .into_iter()
+                .chain(self.expr_subqueries())
+                .any(|plan| plan.has_cross_product())
+    }
+
+    /// Ordering the output of the expression is known to satisfy, as
+    /// (column_id, asc, nulls_first) keys, most significant first. Rows that
+    /// agree on a prefix of the key columns are adjacent. Empty if the output
+    /// has no known order.
+    pub fn output_order(&self) -> Vec<(ColumnId, bool, bool)> {
+        match self {
+            PhysicalRelExpr::Sort { cols, .. } | PhysicalRelExpr::TopN { cols, .. } => cols.clone(),
+            PhysicalRelExpr::Select { src, .. }
+            | PhysicalRelExpr::HashDistinct { src, .. }
+            | PhysicalRelExpr::Limit { src, .. } => src.output_order(),
+            PhysicalRelExpr::Map { input, .. } => input.output_order(),
+            PhysicalRelExpr::Project { src, cols, .. } => src
+                .output_order()
+                .into_iter()
+                .take_while(|(id, _, _)| cols.contains(id))
+                .collect(),
+            PhysicalRelExpr::Rename {
+                src, src_to_dest, ..
+            } => src
+                .output_order()
+                .into_iter()
+                .map(|(id, asc, nulls_first)| {
+                    (*src_to_dest.get(&id).unwrap_or(&id), asc, nulls_first)
+                })
+                .collect(),
+            // Groups are emitted in the order of the input.
+            PhysicalRelExpr::StreamAggregate { src, group_by, .. } => src
+                .output_order()
+                .into_iter()
+                .take_while(|(id, _, _)| group_by.contains(id))
+                .collect(),
+            // Both inputs are sorted ascending on the join keys and merged, so
+            // the output is ordered on the keys of the left input.
+            PhysicalRelExpr::SortMergeJoin {
+                left, predicates, ..
+            } => {
+                let left_att = left.att();
+                predicates
+                    .iter()
+                    .map_while(|predicate| match predicate {
+                        Expression::Binary {
+                            op: BinaryOp::Eq,
+                            left: l,
+                            right: r,
+                        } => match (l.as_ref(), r.as_ref()) {
+                            (Expression::ColRef { id: l }, Expression::ColRef { id: r }) => {
+                                [l, r].into_iter().find(|id| left_att.contains(id))
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .map(|id| (*id, true, false))
+                    .collect()
+            }
+            // Other joins may switch strategy while they run, so their order is
+            // unknown.
+            _ => vec![],
+        }
+    }
+
+    /// Plans of the subqueries in the expressions of this node, not of its children.
+    fn expr_subqueries(&self) -> Vec<&PhysicalRelExpr> {
+        match self {
+            PhysicalRelExpr::Select { predicates, .. }
+            | PhysicalRelExpr::CrossJoin { predicates, .. }
+            | PhysicalRelExpr::NestedLoopJoin { predicates, .. }
+            | PhysicalRelExpr::HashJoin { predicates, .. }
+            | PhysicalRelExpr::SortMergeJoin { predicates, .. } => {
+                predicates.iter().flat_map(|p| p.subqueries()).collect()
+            }
+            PhysicalRelExpr::Map { exprs, .. } => {
+                exprs.iter().flat_map(|(_, e)| e.subqueries()).collect()
+            }
+            _ => vec![],
+        }
     }
 
     fn set_tree_hash(&mut self, hash_val: u64) -> Result<(), FairyError> {
@@ -708,10 +1178,15 @@ impl PhysicalRelExpr {
             | PhysicalRelExpr::SortMergeJoin { tree_hash, .. }
             | PhysicalRelExpr::Project { tree_hash, .. }
             | PhysicalRelExpr::Sort { tree_hash, .. }
+            | PhysicalRelExpr::HashDistinct { tree_hash, .. }
+            | PhysicalRelExpr::Limit { tree_hash, .. }
+            | PhysicalRelExpr::TopN { tree_hash, .. }
             | PhysicalRelExpr::HashAggregate { tree_hash, .. }
+            | PhysicalRelExpr::StreamAggregate { tree_hash, .. }
             | PhysicalRelExpr::Map { tree_hash, .. }
             | PhysicalRelExpr::FlatMap { tree_hash, .. }
-            | PhysicalRelExpr::Rename { tree_hash, .. } => {
+            | PhysicalRelExpr::Rename { tree_hash, .. }
+            | PhysicalRelExpr::Union { tree_hash, .. } => {
                 *tree_hash = Some(hash_val);
                 Ok(())
             } // Cannot reach this all are covered currently
@@ -729,11 +1204,16 @@ impl PhysicalRelExpr {
             | PhysicalRelExpr::SortMergeJoin { tree_hash, .. }
             | PhysicalRelExpr::Project { tree_hash, .. }
             | PhysicalRelExpr::Sort { tree_hash, .. }
+            | PhysicalRelExpr::HashDistinct { tree_hash, .. }
+            | PhysicalRelExpr::Limit { tree_hash, .. }
+            | PhysicalRelExpr::TopN { tree_hash, .. }
             | PhysicalRelExpr::HashAggregate { tree_hash, .. }
+            | PhysicalRelExpr::StreamAggregate { tree_hash, .. }
             | PhysicalRelExpr::Map { tree_hash, .. }
             | PhysicalRelExpr::FlatMap { tree_hash, .. }
-            | PhysicalRelExpr::Rename { tree_hash, .. } => {
-                tree_hash.ok_or_else(|| c_err("tree_hash not set"))
+            | PhysicalRelExpr::Rename { tree_hash, .. }
+            | PhysicalRelExpr::Union { tree_hash, .. } => {
+                tree_hash.ok_or_else(|| c_err(&format!("tree_hash not set on {}", self.label())))
             } // Commenting as all are covered currently
               // _ => Err(c_err("set_hash not implemented for expr enum type")),
         }
@@ -743,30 +1223,128 @@ impl PhysicalRelExpr {
     /// acts as a unique finger print for our tree and maintains join commutativity
     /// and is column-rename-agnostic, among other things.
     ///
-    /// This should only ever be called once. Will throw an error if any already hashed
-    /// node is getting hashed.
+    /// The hash of every node is stored in its `tree_hash`. If the plan was already
+    /// hashed, the stored hash is returned and nothing is hashed again.
     pub fn hash_plan(&mut self) -> Result<u64, FairyError> {
-        self.hash_node(None)
+        if let Ok(hash) = self.get_tree_hash() {
+            return Ok(hash);
+        }
+        let hashes = self.compute_hashes()?;
+        self.store_hashes(&hashes)?;
+        Ok(hashes.root())
+    }
+
+    /// Computes the hashes `hash_plan` would store in the nodes of the plan, without
+    /// changing the plan.
+    pub fn compute_hashes(&self) -> Result<PlanHashes, FairyError> {
+        let mut nodes = HashMap::new();
+        let root = self.hash_node(&mut HashMap::new(), &mut nodes)?;
+        let mut hashes = PlanHashes {
+            root,
+            nodes,
+            paths: HashMap::new(),
+        };
+        // bfs so that the path kept for a hash leads to its topmost node
+        let mut queue = VecDeque::from([(self, vec![])]);
+        while let Some((node, path)) = queue.pop_front() {
+            let hash = hashes.get(node)?;
+            for (i, child) in node.children().into_iter().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                queue.push_back((child, child_path));
+            }
+            hashes.paths.entry(hash).or_insert(path);
+        }
+        Ok(hashes)
+    }
+
+    fn store_hashes(&mut self, hashes: &PlanHashes) -> Result<(), FairyError> {
+        self.set_tree_hash(hashes.get(self)?)?;
+        for child in self.children_mut() {
+            child.store_hashes(hashes)?;
+        }
+        Ok(())
+    }
+
+    /// Inputs of the node, left to right.
+    pub fn children(&self) -> Vec<&PhysicalRelExpr> {
+        match self {
+            PhysicalRelExpr::Scan { .. } => vec![],
+            PhysicalRelExpr::Select { src, .. }
+            | PhysicalRelExpr::Project { src, .. }
+            | PhysicalRelExpr::Sort { src, .. }
+            | PhysicalRelExpr::HashDistinct { src, .. }
+            | PhysicalRelExpr::Limit { src, .. }
+            | PhysicalRelExpr::TopN { src, .. }
+            | PhysicalRelExpr::Rename { src, .. }
+            | PhysicalRelExpr::HashAggregate { src, .. }
+            | PhysicalRelExpr::StreamAggregate { src, .. } => vec![src],
+            PhysicalRelExpr::Map { input, .. } => vec![input],
+            PhysicalRelExpr::FlatMap { input, func, .. } => vec![input, func],
+            PhysicalRelExpr::CrossJoin { left, right, .. }
+            | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+            | PhysicalRelExpr::HashJoin { left, right, .. }
+            | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+            | PhysicalRelExpr::Union { left, right, .. } => vec![left, right],
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut PhysicalRelExpr> {
+        match self {
+            PhysicalRelExpr::Scan { .. } => vec![],
+            PhysicalRelExpr::Select { src, .. }
+            | PhysicalRelExpr::Project { src, .. }
+            | PhysicalRelExpr::Sort { src, .. }
+            | PhysicalRelExpr::HashDistinct { src, .. }
+            | PhysicalRelExpr::Limit { src, .. }
+            | PhysicalRelExpr::TopN { src, .. }
+            | PhysicalRelExpr::Rename { src, .. }
+            | PhysicalRelExpr::HashAggregate { src, .. }
+            | PhysicalRelExpr::StreamAggregate { src, .. } => vec![src],
+            PhysicalRelExpr::Map { input, .. } => vec![input],
+            PhysicalRelExpr::FlatMap { input, func, .. } => vec![input, func],
+            PhysicalRelExpr::CrossJoin { left, right, .. }
+            | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+            | PhysicalRelExpr::HashJoin { left, right, .. }
+            | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+            | PhysicalRelExpr::Union { left, right, .. } => vec![left, right],
+        }
+    }
+
+    /// First line of the printed node, used to point at it in errors.
+    fn label(&self) -> String {
+        let mut out = String::new();
+        self.print_inner(0, &mut out);
+        let line = out.lines().next().unwrap_or_default().trim();
+        line.trim_start_matches("-> ").to_string()
     }
 
     /// Get hash on specific node of expression tree. General idea is that we recursively get
     /// root hashes for children to build up a unique tree identifier, and make sure that in
     /// cases with multiple children, there is a canonical ordering (which differs from merkle
-    /// tree hashes).
+    /// tree hashes). The hash of every node is added to `hashes`, keyed by its address.
     ///
     /// We also pass up a column id renaming map to normalize hashes that refer to renamed
     /// columns. The map is updated accordingly (to map col_ids unique to queries to common
     /// ones) as we recurse. When calling this function from the outside, rename_map should
-    /// be None.
+    /// be empty.
     fn hash_node(
-        &mut self,
-        rename_map: Option<&mut HashMap<ColumnId, ColumnId>>,
+        &self,
+        rename_map: &mut HashMap<ColumnId, ColumnId>,
+        hashes: &mut HashMap<usize, u64>,
     ) -> Result<u64, FairyError> {
-        // root_map declared to avoid going out of scope
-        let mut root_map = HashMap::new();
-        // rename map will be null upon intial call to function so we unwrap into an empty map if that was the case
-        let rename_map = rename_map.unwrap_or(&mut root_map);
+        #[cfg(test)]
+        NODES_HASHED.with(|n| n.set(n.get() + 1));
+        let res = self.hash_node_inner(rename_map, hashes)?;
+        hashes.insert(node_id(self), res);
+        Ok(res)
+    }
 
+    fn hash_node_inner(
+        &self,
+        rename_map: &mut HashMap<ColumnId, ColumnId>,
+        hashes: &mut HashMap<usize, u64>,
+    ) -> Result<u64, FairyError> {
         match self {
             PhysicalRelExpr::Scan {
                 cid,
@@ -781,15 +1359,14 @@ impl PhysicalRelExpr {
                     .collect();
                 renamed_column_names.sort();
                 let identifier = format!("{}{}{:?}", cid, table_name, renamed_column_names);
-                // set and pass up subtree's hash value
+                // pass up subtree's hash value
                 let res = compute_hash(&identifier);
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::Select {
                 src, predicates, ..
             } => {
-                let src_hash = src.hash_node(Some(rename_map))?;
+                let src_hash = src.hash_node(rename_map, hashes)?;
                 // use rename_map to rephrase predicate vector
                 let mut renamed_predicates: Vec<Expression<PhysicalRelExpr>> = predicates
                     .iter()
@@ -798,9 +1375,8 @@ impl PhysicalRelExpr {
                 // define canonical predicate ordering for hash
                 renamed_predicates.sort_by_key(|a| a.pretty_string());
                 let predicates_hash = compute_hash(&format!("{:?}", renamed_predicates));
-                // set and pass up subtree's hash value
+                // pass up subtree's hash value
                 let res = src_hash ^ predicates_hash;
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::CrossJoin {
@@ -831,8 +1407,8 @@ impl PhysicalRelExpr {
                 predicates,
                 ..
             } => {
-                let left_hash = left.hash_node(Some(rename_map))?;
-                let right_hash = right.hash_node(Some(rename_map))?;
+                let left_hash = left.hash_node(rename_map, hashes)?;
+                let right_hash = right.hash_node(rename_map, hashes)?;
 
                 // NOT USED ANYMORE BECAUSE XOR IS COMMUTATIVE - might use for tree mathcing later so keeping here
                 // // sort hashes to have commutativity in identifier
@@ -851,13 +1427,12 @@ impl PhysicalRelExpr {
                 renamed_predicates.sort_by_key(|a| a.pretty_string());
                 let predicates_hash = compute_hash(&format!("{:?}", renamed_predicates));
                 let join_type_hash = compute_hash(&format!("{}", join_type));
-                // set and pass up subtree's hash value
+                // pass up subtree's hash value
                 let res = predicates_hash ^ join_type_hash ^ left_hash ^ right_hash;
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::Project { src, cols, .. } => {
-                let src_hash = src.hash_node(Some(rename_map))?;
+                let src_hash = src.hash_node(rename_map, hashes)?;
                 // rename col_id aliases and sort for consistency across queries
                 let mut renamed_cols: Vec<ColumnId> = cols
                     .iter()
@@ -865,32 +1440,49 @@ impl PhysicalRelExpr {
                     .collect();
                 renamed_cols.sort();
                 let cols_hash = compute_hash(&format!("{:?}", renamed_cols));
-                // set and pass up subtree's hash value
+                // pass up subtree's hash value
                 let res = src_hash ^ cols_hash;
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::Sort { src, cols, .. } => {
-                let src_hash = src.hash_node(Some(rename_map))?;
+                let src_hash = src.hash_node(rename_map, hashes)?;
                 // we don't sort cols because vec order defines col priorities for sorting
                 let cols_hash = compute_hash(&format!("{:?}", cols));
-                // set and pass up subtree's hash value
+                // pass up subtree's hash value
                 let res = src_hash ^ cols_hash;
-                self.set_tree_hash(res)?;
+                Ok(res)
+            }
+            PhysicalRelExpr::HashDistinct { src, .. } => {
+                let src_hash = src.hash_node(rename_map, hashes)?;
+                // pass up subtree's hash value
+                let res = src_hash ^ compute_hash("distinct");
+                Ok(res)
+            }
+            PhysicalRelExpr::Limit { src, limit, .. } => {
+                let src_hash = src.hash_node(rename_map, hashes)?;
+                let res = src_hash ^ compute_hash(&format!("limit{}", limit));
+                Ok(res)
+            }
+            PhysicalRelExpr::TopN {
+                src, cols, limit, ..
+            } => {
+                let src_hash = src.hash_node(rename_map, hashes)?;
+                // the same rows as a sort below a limit
+                let cols_hash = compute_hash(&format!("{:?}", cols));
+                let res = src_hash ^ cols_hash ^ compute_hash(&format!("limit{}", limit));
                 Ok(res)
             }
             PhysicalRelExpr::Rename {
                 src, src_to_dest, ..
             } => {
-                let src_hash = src.hash_node(Some(rename_map))?;
+                let src_hash = src.hash_node(rename_map, hashes)?;
                 // update map before we go back up the stack
                 for (src, dest) in src_to_dest {
                     // reverse map order because we care about reverse mapping for hash use in ancestor nodes
                     rename_map.insert(*dest, *src);
                 }
                 // we want to make the rename node "invisible" in our hash so we ignore its non-src fields
-                // set and pass up subtree's hash value
-                self.set_tree_hash(src_hash)?;
+                // pass up subtree's hash value
                 Ok(src_hash)
             }
             PhysicalRelExpr::HashAggregate {
@@ -898,17 +1490,24 @@ impl PhysicalRelExpr {
                 group_by,
                 aggrs,
                 ..
+            }
+            | PhysicalRelExpr::StreamAggregate {
+                src,
+                group_by,
+                aggrs,
+                ..
             } => {
-                let src_hash = src.hash_node(Some(rename_map))?;
-                group_by.sort(); // order doesn't matter for group_by
+                let src_hash = src.hash_node(rename_map, hashes)?;
+                // order doesn't matter for group_by
+                let mut group_by = group_by.clone();
+                group_by.sort();
                 let gb_hash = compute_hash(&format!("{:?}", group_by));
                 let aggr_hash = compute_hash(&format!("{:?}", aggrs));
                 let res = src_hash ^ gb_hash ^ aggr_hash;
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::Map { input, exprs, .. } => {
-                let input_hash = input.hash_node(Some(rename_map))?;
+                let input_hash = input.hash_node(rename_map, hashes)?;
                 let renamed_expr: Vec<(usize, Expression<PhysicalRelExpr>)> = exprs
                     .iter()
                     .map(|(n, expr)| (*n, expr.clone().replace_variables(rename_map)))
@@ -916,14 +1515,20 @@ impl PhysicalRelExpr {
                 let expr_hash = compute_hash(&format!("{:?}", renamed_expr));
 
                 let res = input_hash ^ expr_hash;
-                self.set_tree_hash(res)?;
                 Ok(res)
             }
             PhysicalRelExpr::FlatMap { input, func, .. } => {
-                let input_hash = input.hash_node(Some(rename_map))?;
-                let func_hash = func.hash_node(Some(rename_map))?;
+                let input_hash = input.hash_node(rename_map, hashes)?;
+                let func_hash = func.hash_node(rename_map, hashes)?;
                 let res = input_hash ^ func_hash;
-                self.set_tree_hash(res)?;
+                Ok(res)
+            }
+            PhysicalRelExpr::Union { left, right, .. } => {
+                let left_hash = left.hash_node(rename_map, hashes)?;
+                let right_hash = right.hash_node(rename_map, hashes)?;
+                // the order of the inputs is the order of the output, so the
+                // hashes are not combined commutatively
+                let res = left_hash ^ right_hash.rotate_left(1) ^ compute_hash("union");
                 Ok(res)
             } // Commenting as all are covered
               // _ => Err(c_err(
@@ -932,148 +1537,25 @@ impl PhysicalRelExpr {
         }
     }
 
-    /// Returns level-ordered representation of a plan tree's node hashes. Will hash the
-    /// tree of that hasn't been done.
+    /// Returns level-ordered representation of a plan tree's node hashes. The plan must
+    /// have been hashed with `hash_plan`.
     pub fn get_hash_vec(&self) -> Result<Vec<(u64, &PhysicalRelExpr)>, FairyError> {
         // bfs traverse and populate
         let mut hashes = Vec::new();
-        let mut queue = VecDeque::new();
-        queue.push_back(self);
-        while !queue.is_empty() {
-            let node = queue.pop_front().unwrap();
-            // check tree + children root hashes to find match
-            match node {
-                PhysicalRelExpr::Scan { tree_hash, .. } => {
-                    hashes.push((tree_hash.unwrap(), node));
-                }
-                PhysicalRelExpr::Select { src, tree_hash, .. }
-                | PhysicalRelExpr::Project { src, tree_hash, .. }
-                | PhysicalRelExpr::Sort { src, tree_hash, .. }
-                | PhysicalRelExpr::Rename { src, tree_hash, .. }
-                | PhysicalRelExpr::HashAggregate { src, tree_hash, .. } => {
-                    hashes.push((tree_hash.unwrap(), node));
-                    // add next level to back of queue
-                    queue.push_back(src);
-                }
-                PhysicalRelExpr::CrossJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::NestedLoopJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::HashJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::SortMergeJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                } => {
-                    hashes.push((tree_hash.unwrap(), node));
-                    // add next level to back of queue
-                    queue.push_back(left);
-                    queue.push_back(right);
-                }
-                _ => {
-                    return Err(c_err(&format!(
-                        "haven't implemented hash match for specific PhysicalRelExpr type: {:?}",
-                        node
-                    )));
-                }
-            }
+        let mut queue = VecDeque::from([self]);
+        while let Some(node) = queue.pop_front() {
+            hashes.push((node.get_tree_hash()?, node));
+            // add next level to back of queue
+            queue.extend(node.children());
         }
         Ok(hashes)
     }
 
-    #[allow(dead_code)]
-    /// Looks for the given hash value within tree. We try to return the topmost we ignore renames
-    /// and flag with the node right below (because that is likely what is stored) since
-    /// rename(X).tree_hash = X.tree_hash.
-    fn find_hash_in_tree(&self, hash_val: u64) -> Result<Option<&PhysicalRelExpr>, FairyError> {
-        // bfs through plan and stop when the first non-rename match is found
-        let mut queue = VecDeque::new();
-        queue.push_back(self);
-
-        while !queue.is_empty() {
-            let node = queue.pop_front().unwrap();
-            // check tree + children root hashes to find match
-            match node {
-                PhysicalRelExpr::Scan { tree_hash, .. } => {
-                    if tree_hash.unwrap() == hash_val {
-                        return Ok(Some(node));
-                    }
-                    return Ok(None);
-                }
-                PhysicalRelExpr::Select { src, tree_hash, .. }
-                | PhysicalRelExpr::Project { src, tree_hash, .. }
-                | PhysicalRelExpr::Sort { src, tree_hash, .. }
-                | PhysicalRelExpr::Rename { src, tree_hash, .. } => {
-                    if tree_hash.unwrap() == hash_val {
-                        return Ok(Some(node));
-                    }
-                    queue.push_back(src);
-                }
-                PhysicalRelExpr::CrossJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::NestedLoopJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::HashJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                }
-                | PhysicalRelExpr::SortMergeJoin {
-                    left,
-                    right,
-                    tree_hash,
-                    ..
-                } => {
-                    if tree_hash.unwrap() == hash_val {
-                        return Ok(Some(node));
-                    }
-                    // add next level to back of queue
-                    queue.push_back(left);
-                    queue.push_back(right);
-                }
-                // PhysicalRelExpr::Rename { src, .. } => {
-                //     // tree_hash doesn't matter here because we never want to flag a match when root is a rename node
-                //     queue.push_back(src);
-                // }
-                _ => {
-                    return Err(c_err(&format!(
-                        "haven't implemented hash match for specific PhysicalRelExpr type: {:?}",
-                        node
-                    )));
-                }
-            }
-        }
-        Ok(None)
-    }
-
     /// Indentifies overlapping elements in current plan with a list of other plans. Will give precedence to
     /// self when identifying subplans (i.e. we iterate through branches of self and compare those hashes to other's hashes)
     ///
-    /// We pass a map of other candidates with their BFS-order hashes so that we can compare all candidates to each
-    /// node of the og plan as we iterate.
+    /// `hashes` are the hashes of self and every candidate is passed with its own hashes, computed once by
+    /// `compute_hashes`, so that each node of the og plan is compared to all candidates without hashing them again.
     ///
     /// The return value is a vector with elements of form (a: PhysicalRelExpr, b: PhysicalRelExpr, c: PhysicalRelExpr) where
     /// `b` is the part of the original plan which matches with `c`, the part of the cached plan, `a`, that matches with `b`.
@@ -1082,64 +1564,36 @@ impl PhysicalRelExpr {
     ///
     /// The end result here should match the general highest level subtrees of the og plan and the cached plans, giving preference
     /// to the og plan when prioritizing high matches. NOTE: these are potential because of (highly unlikely) hash collisions.
-    pub fn identify_potential_tree_overlaps<'a>(
+    pub fn identify_potential_tree_overlaps<'a, 'b>(
         &'a self,
-        cached_plans_with_hashes: Vec<(&'a PhysicalRelExpr, Vec<(u64, &'a PhysicalRelExpr)>)>,
+        hashes: &PlanHashes,
+        cached_plans_with_hashes: &[(&'b PhysicalRelExpr, &PlanHashes)],
     ) -> Result<
         Vec<(
+            &'b PhysicalRelExpr,
             &'a PhysicalRelExpr,
-            &'a PhysicalRelExpr,
-            &'a PhysicalRelExpr,
+            &'b PhysicalRelExpr,
         )>,
         FairyError,
     > {
         let mut overlaps = vec![];
 
         // bfs through current plan and don't queue if a cache match is found for a node
-        let mut queue = VecDeque::new();
-        queue.push_back(self);
-
-        while !queue.is_empty() {
-            let node = queue.pop_front().unwrap();
-            let hash_val = node.get_tree_hash()?;
+        let mut queue = VecDeque::from([self]);
+        while let Some(node) = queue.pop_front() {
+            let hash_val = hashes.get(node)?;
 
             let mut found_match = false;
-
-            for (cached_plan, hash_vec) in &cached_plans_with_hashes {
-                for (h, cached_pp_subplan) in hash_vec {
-                    if *h == hash_val {
-                        overlaps.push((*cached_plan, node, *cached_pp_subplan));
-                        found_match = true;
-                        break; // don't match more than one subtree of the cached_plan
-                    }
+            for (cached_plan, cached_hashes) in cached_plans_with_hashes {
+                // only the topmost matching subtree of the cached_plan is kept
+                if let Some(cached_pp_subplan) = cached_hashes.find(cached_plan, hash_val) {
+                    overlaps.push((*cached_plan, node, cached_pp_subplan));
+                    found_match = true;
                 }
             }
-            if found_match {
-                continue; // don't continue iteration down a parent branch that's been matched
-            }
-
-            match node {
-                PhysicalRelExpr::Scan { .. } => {}
-                PhysicalRelExpr::Select { src, .. }
-                | PhysicalRelExpr::Project { src, .. }
-                | PhysicalRelExpr::Sort { src, .. }
-                | PhysicalRelExpr::Rename { src, .. }
-                | PhysicalRelExpr::HashAggregate { src, .. } => {
-                    queue.push_back(src);
-                }
-                PhysicalRelExpr::CrossJoin { left, right, .. }
-                | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
-                | PhysicalRelExpr::HashJoin { left, right, .. }
-                | PhysicalRelExpr::SortMergeJoin { left, right, .. } => {
-                    queue.push_back(left);
-                    queue.push_back(right);
-                }
-                _ => {
-                    return Err(c_err(&format!(
-                        "haven't implemented hash match for specific PhysicalRelExpr type: {:?}",
-                        node
-                    )));
-                }
+            if !found_match {
+                // don't continue iteration down a parent branch that's been matched
+                queue.extend(node.children());
             }
         }
         Ok(overlaps)
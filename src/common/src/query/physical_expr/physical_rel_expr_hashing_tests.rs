@@ -550,4 +550,114 @@ mod test {
             "merkle hashes should be identical for identical trees"
         );
     }
+
+    fn join_tree() -> PhysicalRelExpr {
+        PhysicalRelExpr::HashAggregate {
+            tree_hash: None,
+            src: Box::new(PhysicalRelExpr::HashJoin {
+                join_type: JoinType::Inner,
+                left: Box::new(PhysicalRelExpr::Scan {
+                    cid: 1,
+                    table_name: "left_table".to_string(),
+                    column_names: vec![1, 2],
+                    tree_hash: None,
+                }),
+                right: Box::new(PhysicalRelExpr::Scan {
+                    cid: 2,
+                    table_name: "right_table".to_string(),
+                    column_names: vec![3, 4],
+                    tree_hash: None,
+                }),
+                predicates: vec![Expression::col_ref(1).eq(Expression::col_ref(3))],
+                tree_hash: None,
+            }),
+            group_by: vec![4, 2],
+            aggrs: vec![(5, (1, AggOp::Count))],
+        }
+    }
+
+    #[test]
+    fn test_hash_plan_twice_keeps_node_hashes() {
+        let mut tree = join_tree();
+        let hash = tree.hash_plan().unwrap();
+        let hash_vec: Vec<u64> = tree
+            .get_hash_vec()
+            .unwrap()
+            .iter()
+            .map(|(h, _)| *h)
+            .collect();
+        assert_eq!(hash_vec.len(), 4);
+
+        // hashing again returns the stored hash and leaves the children alone
+        assert_eq!(tree.hash_plan().unwrap(), hash);
+        let rehash_vec: Vec<u64> = tree
+            .get_hash_vec()
+            .unwrap()
+            .iter()
+            .map(|(h, _)| *h)
+            .collect();
+        assert_eq!(rehash_vec, hash_vec);
+    }
+
+    #[test]
+    fn test_compute_hashes_leaves_plan_unchanged() {
+        let tree = join_tree();
+        let before = format!("{:?}", tree);
+        let hashes = tree.compute_hashes().unwrap();
+        assert_eq!(
+            format!("{:?}", tree),
+            before,
+            "group_by must not be sorted in place"
+        );
+        assert!(tree.get_tree_hash().is_err());
+
+        // same hashes as the ones hash_plan stores
+        let mut hashed = tree.clone();
+        assert_eq!(hashes.root(), hashed.hash_plan().unwrap());
+        for (h, _) in hashed.get_hash_vec().unwrap() {
+            let node = hashes.find(&tree, h).unwrap();
+            assert_eq!(hashes.get(node).unwrap(), h);
+        }
+    }
+
+    #[test]
+    fn test_find_returns_topmost_node() {
+        let scan = PhysicalRelExpr::Scan {
+            cid: 1,
+            table_name: "test_table".to_string(),
+            column_names: vec![2, 3],
+            tree_hash: None,
+        };
+        // renames are invisible to the hash, so both nodes have the same hash
+        let tree = PhysicalRelExpr::Rename {
+            src: Box::new(scan),
+            src_to_dest: [(2, 12)].into(),
+            tree_hash: None,
+        };
+        let hashes = tree.compute_hashes().unwrap();
+        assert!(matches!(
+            hashes.find(&tree, hashes.root()),
+            Some(PhysicalRelExpr::Rename { .. })
+        ));
+        assert!(hashes.find(&tree, hashes.root() ^ 1).is_none());
+    }
+
+    #[test]
+    fn test_missing_hash_names_node() {
+        let tree = join_tree();
+        let err = tree.get_hash_vec().unwrap_err().to_string();
+        assert!(
+            err.ends_with("on hash_aggregate(group_by: [@4, @2], aggrs: [@5 <- Count(@1)])"),
+            "{}",
+            err
+        );
+
+        let other = join_tree();
+        let hashes = other.compute_hashes().unwrap();
+        let PhysicalRelExpr::HashAggregate { src, .. } = &tree else {
+            unreachable!()
+        };
+        let err = hashes.get(src).unwrap_err().to_string();
+        assert!(err.ends_with("plan node Hash inner_join(@1=@3)"), "{}", err);
+    }
 }
@@ -0,0 +1,720 @@
+//! Output schemas of physical plan nodes.
+//!
+//! Every node of a physical plan produces tuples whose columns are identified by
+//! a `ColumnId`. `derive_schema` walks the plan bottom-up and assigns each of
+//! those columns a data type and a nullability flag, type checking the
+//! expressions of the plan on the way. Type errors are therefore reported when
+//! the plan is built rather than when the offending tuple is evaluated.
+
+use crate::{
+    catalog::{get_column_index_from_temp_col_id, CatalogRef},
+    ids::ColumnId,
+    logical_expr::prelude::{Expression, JoinType},
+    AggOp, BinaryOp, DataType, FairyError, Field,
+};
+
+use super::physical_rel_expr::PhysicalRelExpr;
+
+/// A single output column of a physical plan node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanColumn {
+    pub id: ColumnId,
+    pub dtype: DataType,
+    pub nullable: bool,
+}
+
+impl PlanColumn {
+    pub fn new(id: ColumnId, dtype: DataType, nullable: bool) -> Self {
+        Self {
+            id,
+            dtype,
+            nullable,
+        }
+    }
+}
+
+/// The ordered output columns of a physical plan node.
+pub type PlanSchema = Vec<PlanColumn>;
+
+fn type_err(msg: String) -> FairyError {
+    FairyError::ValidationError(msg)
+}
+
+fn is_numeric(dtype: &DataType) -> bool {
+    matches!(
+        dtype,
+        DataType::BigInt
+            | DataType::Int
+            | DataType::SmallInt
+            | DataType::Decimal(_, _)
+            | DataType::Float64
+    )
+}
+
+fn is_string(dtype: &DataType) -> bool {
+    matches!(dtype, DataType::Char(_) | DataType::String)
+}
+
+/// Returns true if values of the two types may be stored in the same column.
+/// Integer widths and string representations must match exactly, except that
+/// fixed and variable length strings are interchangeable. Decimals match
+/// regardless of precision and scale, since a decimal field carries its own scale.
+pub fn dtypes_compatible(a: &DataType, b: &DataType) -> bool {
+    match (a, b) {
+        (DataType::Decimal(_, _), DataType::Decimal(_, _)) => true,
+        (a, b) if is_string(a) && is_string(b) => true,
+        (a, b) => a == b,
+    }
+}
+
+/// Returns true if `field` is a valid value for a column of type `dtype`.
+/// `Null` is accepted for every type.
+pub fn field_matches_dtype(field: &Field, dtype: &DataType) -> bool {
+    match field {
+        Field::Null => true,
+        _ => *dtype == DataType::Null || dtypes_compatible(&DataType::from(field), dtype),
+    }
+}
+
+fn find_column(schema: &[PlanColumn], id: ColumnId) -> Result<&PlanColumn, FairyError> {
+    schema
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| type_err(format!("Column {} is not produced by the input", id)))
+}
+
+/// Type of an arithmetic expression over the given operand types, if defined.
+/// Only `BigInt`, `Decimal` and `Float64` support arithmetic; mixing an int with a
+/// decimal yields a decimal, and mixing either with a float yields a float.
+fn arithmetic_type(l: &DataType, r: &DataType) -> Option<DataType> {
+    match (l, r) {
+        (DataType::Null, other) | (other, DataType::Null)
+            if matches!(
+                other,
+                DataType::BigInt | DataType::Decimal(_, _) | DataType::Float64 | DataType::Null
+            ) =>
+        {
+            Some(other.clone())
+        }
+        (DataType::BigInt, DataType::BigInt) => Some(DataType::BigInt),
+        (DataType::Decimal(p_l, s_l), DataType::Decimal(p_r, s_r)) => {
+            Some(DataType::Decimal(*p_l.max(p_r), *s_l.max(s_r)))
+        }
+        (DataType::Decimal(p, s), DataType::BigInt)
+        | (DataType::BigInt, DataType::Decimal(p, s)) => Some(DataType::Decimal(*p, *s)),
+        (DataType::Float64, DataType::BigInt | DataType::Decimal(_, _) | DataType::Float64)
+        | (DataType::BigInt | DataType::Decimal(_, _), DataType::Float64) => {
+            Some(DataType::Float64)
+        }
+        _ => None,
+    }
+}
+
+/// Type of adding to or subtracting from a timestamp or date, if defined. A
+/// number of microseconds may be added to or subtracted from a timestamp, and a
+/// number of days from a date. Subtracting two timestamps yields the
+/// microseconds between them, and two dates the days between them.
+fn timestamp_arithmetic_type(op: BinaryOp, l: &DataType, r: &DataType) -> Option<DataType> {
+    match (op, l, r) {
+        (BinaryOp::Add, DataType::Timestamp, DataType::BigInt | DataType::Null)
+        | (BinaryOp::Add, DataType::BigInt | DataType::Null, DataType::Timestamp)
+        | (BinaryOp::Sub, DataType::Timestamp, DataType::BigInt | DataType::Null) => {
+            Some(DataType::Timestamp)
+        }
+        (BinaryOp::Add, DataType::Date, DataType::BigInt | DataType::Null)
+        | (BinaryOp::Add, DataType::BigInt | DataType::Null, DataType::Date)
+        | (BinaryOp::Sub, DataType::Date, DataType::BigInt | DataType::Null) => {
+            Some(DataType::Date)
+        }
+        (BinaryOp::Sub, DataType::Date, DataType::Date) => Some(DataType::BigInt),
+        (BinaryOp::Sub, DataType::Timestamp, DataType::Timestamp) => Some(DataType::BigInt),
+        _ => None,
+    }
+}
+
+/// Type of a column holding values of the two types, as the result of a CASE
+/// expression or of a UNION, if they may be stored in the same column. A NULL
+/// value takes the type of the others.
+pub fn common_dtype(l: &DataType, r: &DataType) -> Option<DataType> {
+    match (l, r) {
+        (DataType::Null, other) | (other, DataType::Null) => Some(other.clone()),
+        (DataType::Decimal(_, _), DataType::Decimal(_, _)) => arithmetic_type(l, r),
+        (l, r) if is_string(l) && is_string(r) && l != r => Some(DataType::String),
+        _ => dtypes_compatible(l, r).then(|| l.clone()),
+    }
+}
+
+/// Returns true if values of the two types may be compared with each other.
+pub fn comparable(l: &DataType, r: &DataType) -> bool {
+    *l == DataType::Null
+        || *r == DataType::Null
+        || (is_numeric(l) && is_numeric(r))
+        || dtypes_compatible(l, r)
+}
+
+/// Derives the type and nullability of `expr` evaluated over tuples of `input`.
+pub fn derive_expr_type(
+    expr: &Expression<PhysicalRelExpr>,
+    input: &[PlanColumn],
+) -> Result<(DataType, bool), FairyError> {
+    match expr {
+        Expression::ColRef { id } => {
+            let col = find_column(input, *id)?;
+            Ok((col.dtype.clone(), col.nullable))
+        }
+        Expression::Field { val } => Ok((DataType::from(val), *val == Field::Null)),
+        // Bound to a value of the type when the statement is executed, any
+        // other placeholder to a value of any type.
+        Expression::Parameter { dtype, .. } => Ok((dtype.clone().unwrap_or(DataType::Null), true)),
+        Expression::Binary { op, left, right } => {
+            let (l, l_null) = derive_expr_type(left, input)?;
+            let (r, r_null) = derive_expr_type(right, input)?;
+            let dtype = match op {
+                BinaryOp::Add | BinaryOp::Sub => {
+                    timestamp_arithmetic_type(*op, &l, &r).or_else(|| arithmetic_type(&l, &r))
+                }
+                BinaryOp::Mul | BinaryOp::Div => arithmetic_type(&l, &r),
+                BinaryOp::Eq
+                | BinaryOp::Neq
+                | BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::Le
+                | BinaryOp::Ge => comparable(&l, &r).then_some(DataType::Bool),
+                BinaryOp::And | BinaryOp::Or => {
+                    let boolean = |t: &DataType| matches!(t, DataType::Bool | DataType::Null);
+                    (boolean(&l) && boolean(&r)).then_some(DataType::Bool)
+                }
+                BinaryOp::Like | BinaryOp::NotLike => {
+                    let string = |t: &DataType| {
+                        matches!(t, DataType::String | DataType::Char(_) | DataType::Null)
+                    };
+                    (string(&l) && string(&r)).then_some(DataType::Bool)
+                }
+            };
+            match dtype {
+                Some(dtype) => Ok((dtype, l_null || r_null)),
+                None => Err(type_err(format!("Cannot apply {} to {} and {}", op, l, r))),
+            }
+        }
+        Expression::InList { expr, list, .. } => {
+            let (dtype, nullable) = derive_expr_type(expr, input)?;
+            match list
+                .iter()
+                .find(|val| !comparable(&dtype, &DataType::from(*val)))
+            {
+                Some(val) => Err(type_err(format!("Cannot compare {} to {}", dtype, val))),
+                None => Ok((DataType::Bool, nullable || list.contains(&Field::Null))),
+            }
+        }
+        Expression::IsNull { expr, .. } => {
+            derive_expr_type(expr, input)?;
+            Ok((DataType::Bool, false))
+        }
+        Expression::Cast { expr, dtype } => {
+            let (from, nullable) = derive_expr_type(expr, input)?;
+            if !from.can_cast_to(dtype) {
+                return Err(type_err(format!("Cannot cast {} to {}", from, dtype)));
+            }
+            Ok((dtype.clone(), nullable))
+        }
+        Expression::ScalarFunction { func, args } => {
+            let mut types = Vec::with_capacity(args.len());
+            let mut nullable = false;
+            for arg in args {
+                let (dtype, arg_nullable) = derive_expr_type(arg, input)?;
+                types.push(dtype);
+                nullable |= arg_nullable;
+            }
+            func.check_args(&types)?;
+            Ok((func.return_type(), nullable))
+        }
+        Expression::Case {
+            expr,
+            whens,
+            else_expr,
+        } => {
+            let operand = expr
+                .as_ref()
+                .map(|expr| derive_expr_type(expr, input))
+                .transpose()?;
+            let (mut dtype, mut nullable) = derive_expr_type(else_expr, input)?;
+            for (when, then) in whens.iter().rev() {
+                let (when_type, _) = derive_expr_type(when, input)?;
+                match &operand {
+                    Some((operand, _)) if !comparable(operand, &when_type) => {
+                        return Err(type_err(format!(
+                            "Cannot compare {} to {}",
+                            operand, when_type
+                        )));
+                    }
+                    None if !matches!(when_type, DataType::Bool | DataType::Null) => {
+                        return Err(type_err(format!(
+                            "CASE condition {} evaluates to {}, expected bool",
+                            when.pretty_string(),
+                            when_type
+                        )));
+                    }
+                    _ => {}
+                }
+                let (then_type, then_null) = derive_expr_type(then, input)?;
+                dtype = common_dtype(&then_type, &dtype).ok_or_else(|| {
+                    type_err(format!(
+                        "CASE results have incompatible types {} and {}",
+                        then_type, dtype
+                    ))
+                })?;
+                nullable |= then_null;
+            }
+            Ok((dtype, nullable))
+        }
+        Expression::Subquery { .. } | Expression::Quantified { .. } => {
+            Err(type_err("Subquery expressions are not supported".into()))
+        }
+    }
+}
+
+/// Checks that a predicate over `input` evaluates to a boolean.
+fn check_predicate(
+    pred: &Expression<PhysicalRelExpr>,
+    input: &[PlanColumn],
+) -> Result<(), FairyError> {
+    match derive_expr_type(pred, input)?.0 {
+        DataType::Bool | DataType::Null => Ok(()),
+        other => Err(type_err(format!(
+            "Predicate {} evaluates to {}, expected bool",
+            pred.pretty_string(),
+            other
+        ))),
+    }
+}
+
+fn join_schema(join_type: &JoinType, left: &PlanSchema, right: &PlanSchema) -> PlanSchema {
+    let (left_nullable, right_nullable) = match join_type {
+        JoinType::LeftOuter => (false, true),
+        JoinType::RightOuter => (true, false),
+        JoinType::FullOuter => (true, true),
+        JoinType::Inner | JoinType::CrossJoin | JoinType::Semi | JoinType::Anti => (false, false),
+    };
+    let side = |cols: &PlanSchema, nullable: bool| {
+        cols.iter()
+            .map(|c| PlanColumn::new(c.id, c.dtype.clone(), c.nullable || nullable))
+            .collect::<Vec<_>>()
+    };
+    let mut schema = side(left, left_nullable);
+    schema.extend(side(right, right_nullable));
+    schema
+}
+
+/// Schema of the rows of `left` followed by the rows of `right`, whose columns
+/// are matched to those of `left` by position.
+fn union_schema(left: &PlanSchema, right: &PlanSchema) -> Result<PlanSchema, FairyError> {
+    if left.len() != right.len() {
+        return Err(type_err(format!(
+            "UNION inputs have {} and {} columns",
+            left.len(),
+            right.len()
+        )));
+    }
+    left.iter()
+        .zip(right)
+        .map(|(l, r)| {
+            let dtype = common_dtype(&l.dtype, &r.dtype).ok_or_else(|| {
+                type_err(format!(
+                    "UNION column {} has incompatible types {} and {}",
+                    l.id, l.dtype, r.dtype
+                ))
+            })?;
+            Ok(PlanColumn::new(l.id, dtype, l.nullable || r.nullable))
+        })
+        .collect()
+}
+
+impl PhysicalRelExpr {
+    /// Derives the output schema of this plan.
+    pub fn derive_schema(&self, catalog: &CatalogRef) -> Result<PlanSchema, FairyError> {
+        self.derive_schema_with(catalog, &mut |_, _| {})
+    }
+
+    /// Derives the output schema of this plan, calling `visit` with every node and
+    /// its schema in post-order. Each node's schema is computed exactly once.
+    pub fn derive_schema_with<F: FnMut(&PhysicalRelExpr, &PlanSchema)>(
+        &self,
+        catalog: &CatalogRef,
+        visit: &mut F,
+    ) -> Result<PlanSchema, FairyError> {
+        let children = match self {
+            PhysicalRelExpr::Scan { .. } => vec![],
+            PhysicalRelExpr::Select { src, .. }
+            | PhysicalRelExpr::Project { src, .. }
+            | PhysicalRelExpr::Sort { src, .. }
+            | PhysicalRelExpr::HashDistinct { src, .. }
+            | PhysicalRelExpr::Limit { src, .. }
+            | PhysicalRelExpr::TopN { src, .. }
+            | PhysicalRelExpr::HashAggregate { src, .. }
+            | PhysicalRelExpr::StreamAggregate { src, .. }
+            | PhysicalRelExpr::Rename { src, .. } => vec![src.derive_schema_with(catalog, visit)?],
+            PhysicalRelExpr::Map { input, .. } => vec![input.derive_schema_with(catalog, visit)?],
+            PhysicalRelExpr::FlatMap { input, func, .. } => vec![
+                input.derive_schema_with(catalog, visit)?,
+                func.derive_schema_with(catalog, visit)?,
+            ],
+            PhysicalRelExpr::CrossJoin { left, right, .. }
+            | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+            | PhysicalRelExpr::HashJoin { left, right, .. }
+            | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+            | PhysicalRelExpr::Union { left, right, .. } => vec![
+                left.derive_schema_with(catalog, visit)?,
+                right.derive_schema_with(catalog, visit)?,
+            ],
+        };
+        let schema = self.derive_node_schema(&children, catalog)?;
+        visit(self, &schema);
+        Ok(schema)
+    }
+
+    /// Derives the output schema of this node given the schemas of its children.
+    fn derive_node_schema(
+        &self,
+        children: &[PlanSchema],
+        catalog: &CatalogRef,
+    ) -> Result<PlanSchema, FairyError> {
+        match self {
+            PhysicalRelExpr::Scan {
+                cid,
+                table_name,
+                column_names,
+                ..
+            } => {
+                let table = catalog
+                    .get_table_schema(*cid)
+                    .ok_or_else(|| type_err(format!("Table {} does not exist", table_name)))?;
+                column_names
+                    .iter()
+                    .map(|id| {
+                        let idx = get_column_index_from_temp_col_id(*id);
+                        let attr = table.get_attribute(idx).ok_or_else(|| {
+                            type_err(format!("Table {} has no column {}", table_name, idx))
+                        })?;
+                        Ok(PlanColumn::new(*id, attr.dtype.clone(), attr.is_nullable()))
+                    })
+                    .collect()
+            }
+            PhysicalRelExpr::Select { predicates, .. } => {
+                for pred in predicates {
+                    check_predicate(pred, &children[0])?;
+                }
+                Ok(children[0].clone())
+            }
+            PhysicalRelExpr::Sort { cols, .. } | PhysicalRelExpr::TopN { cols, .. } => {
+                for (id, _, _) in cols {
+                    find_column(&children[0], *id)?;
+                }
+                Ok(children[0].clone())
+            }
+            PhysicalRelExpr::HashDistinct { .. } | PhysicalRelExpr::Limit { .. } => {
+                Ok(children[0].clone())
+            }
+            PhysicalRelExpr::CrossJoin {
+                join_type,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::NestedLoopJoin {
+                join_type,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::HashJoin {
+                join_type,
+                predicates,
+                ..
+            }
+            | PhysicalRelExpr::SortMergeJoin {
+                join_type,
+                predicates,
+                ..
+            } => {
+                let schema = join_schema(join_type, &children[0], &children[1]);
+                for pred in predicates {
+                    check_predicate(pred, &schema)?;
+                }
+                // Semi and anti joins only output the rows of their left input.
+                if join_type.outputs_left_only() {
+                    return Ok(children[0].clone());
+                }
+                Ok(schema)
+            }
+            PhysicalRelExpr::Project { cols, .. } => cols
+                .iter()
+                .map(|id| find_column(&children[0], *id).cloned())
+                .collect(),
+            PhysicalRelExpr::HashAggregate {
+                group_by, aggrs, ..
+            }
+            | PhysicalRelExpr::StreamAggregate {
+                group_by, aggrs, ..
+            } => {
+                let mut schema = group_by
+                    .iter()
+                    .map(|id| find_column(&children[0], *id).cloned())
+                    .collect::<Result<PlanSchema, _>>()?;
+                for (dest, (src, op)) in aggrs {
+                    let col = find_column(&children[0], *src)?;
+                    let dtype = match op {
+                        AggOp::Count => DataType::BigInt,
+                        AggOp::Avg => match col.dtype {
+                            DataType::Decimal(_, _) | DataType::Float64 => col.dtype.clone(),
+                            _ => DataType::Decimal(
+                                crate::datatypes::default_decimal_precision(),
+                                crate::datatypes::default_decimal_scale(),
+                            ),
+                        },
+                        AggOp::StdDev | AggOp::Variance | AggOp::Median => match col.dtype {
+                            DataType::Float64 => DataType::Float64,
+                            _ => DataType::Decimal(
+                                crate::datatypes::default_decimal_precision(),
+                                crate::datatypes::default_decimal_scale(),
+                            ),
+                        },
+                        AggOp::Max | AggOp::Min | AggOp::Sum => col.dtype.clone(),
+                    };
+                    if !matches!(op, AggOp::Count | AggOp::Max | AggOp::Min)
+                        && !is_numeric(&col.dtype)
+                    {
+                        return Err(type_err(format!("Cannot apply {} to {}", op, col.dtype)));
+                    }
+                    // An aggregate over an empty group yields NULL, except COUNT.
+                    schema.push(PlanColumn::new(*dest, dtype, *op != AggOp::Count));
+                }
+                Ok(schema)
+            }
+            PhysicalRelExpr::Map { exprs, .. } => {
+                let mut schema = children[0].clone();
+                for (id, expr) in exprs {
+                    let (dtype, nullable) = derive_expr_type(expr, &children[0])?;
+                    schema.push(PlanColumn::new(*id, dtype, nullable));
+                }
+                Ok(schema)
+            }
+            PhysicalRelExpr::FlatMap { .. } => {
+                let mut schema = children[0].clone();
+                schema.extend(children[1].iter().cloned());
+                Ok(schema)
+            }
+            PhysicalRelExpr::Rename { src_to_dest, .. } => Ok(children[0]
+                .iter()
+                .map(|c| {
+                    let id = *src_to_dest.get(&c.id).unwrap_or(&c.id);
+                    PlanColumn::new(id, c.dtype.clone(), c.nullable)
+                })
+                .collect()),
+            PhysicalRelExpr::Union { .. } => union_schema(&children[0], &children[1]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        attribute::Attribute,
+        catalog::Catalog,
+        table::{TableInfo, TableSchema},
+    };
+
+    fn setup() -> (CatalogRef, PhysicalRelExpr) {
+        let catalog = Catalog::new();
+        let cid = catalog.get_table_id("t").unwrap();
+        let schema = TableSchema::new(vec![
+            Attribute::new_pk("a".to_string(), DataType::BigInt),
+            Attribute::new("b".to_string(), DataType::Decimal(10, 2)),
+            Attribute::new("c".to_string(), DataType::Date),
+            Attribute::new("d".to_string(), DataType::Bool),
+        ]);
+        catalog
+            .add_table(TableInfo::new(cid, "t".to_string(), schema))
+            .unwrap();
+        let base = cid as ColumnId * 100;
+        let scan = PhysicalRelExpr::Scan {
+            cid,
+            table_name: "t".to_string(),
+            column_names: vec![base, base + 1, base + 2, base + 3],
+            tree_hash: None,
+        };
+        let rename = PhysicalRelExpr::Rename {
+            src: Box::new(scan),
+            src_to_dest: (0..4).map(|i| (base + i, i)).collect(),
+            tree_hash: None,
+        };
+        (catalog, rename)
+    }
+
+    fn map(input: PhysicalRelExpr, expr: Expression<PhysicalRelExpr>) -> PhysicalRelExpr {
+        PhysicalRelExpr::Map {
+            input: Box::new(input),
+            exprs: vec![(10, expr)],
+            tree_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_derive_scan_and_map() {
+        let (catalog, plan) = setup();
+        let schema = plan.derive_schema(&catalog).unwrap();
+        assert_eq!(schema[0], PlanColumn::new(0, DataType::BigInt, false));
+        assert_eq!(schema[2], PlanColumn::new(2, DataType::Date, true));
+
+        let plan = map(plan, Expression::col_ref(0).add(Expression::col_ref(1)));
+        let schema = plan.derive_schema(&catalog).unwrap();
+        assert_eq!(schema.len(), 5);
+        assert_eq!(
+            schema[4],
+            PlanColumn::new(10, DataType::Decimal(10, 2), true)
+        );
+    }
+
+    #[test]
+    fn test_derive_rejects_ill_typed_expression() {
+        let (catalog, plan) = setup();
+        let plan = map(plan, Expression::col_ref(2).add(Expression::col_ref(3)));
+        let err = plan.derive_schema(&catalog).unwrap_err();
+        assert_eq!(
+            err,
+            FairyError::ValidationError("Cannot apply + to date and bool".to_string())
+        );
+
+        let (catalog, plan) = setup();
+        let plan = PhysicalRelExpr::Select {
+            src: Box::new(plan),
+            predicates: vec![Expression::col_ref(0).add(Expression::int(1))],
+            tree_hash: None,
+        };
+        assert!(plan.derive_schema(&catalog).is_err());
+    }
+
+    #[test]
+    fn test_derive_case() {
+        let (catalog, plan) = setup();
+        let gt = |id, val| Expression::binary(BinaryOp::Gt, Expression::col_ref(id), val);
+        // A missing ELSE is NULL and makes the result nullable.
+        let case = Expression::case(
+            None,
+            vec![(gt(0, Expression::int(5)), Expression::int(1))],
+            Expression::Field { val: Field::Null },
+        );
+        let schema = map(plan.clone(), case).derive_schema(&catalog).unwrap();
+        assert_eq!(schema[4], PlanColumn::new(10, DataType::BigInt, true));
+
+        let case = Expression::case(
+            Some(Expression::col_ref(0)),
+            vec![(Expression::int(1), Expression::col_ref(1))],
+            Expression::Field {
+                val: Field::Decimal(5, 1),
+            },
+        );
+        let schema = map(plan.clone(), case).derive_schema(&catalog).unwrap();
+        assert_eq!(schema[4].dtype, DataType::Decimal(10, 2));
+
+        let case = Expression::case(
+            None,
+            vec![(gt(0, Expression::int(5)), Expression::col_ref(2))],
+            Expression::col_ref(3),
+        );
+        let err = map(plan.clone(), case).derive_schema(&catalog).unwrap_err();
+        assert_eq!(
+            err,
+            FairyError::ValidationError(
+                "CASE results have incompatible types date and bool".to_string()
+            )
+        );
+
+        let case = Expression::case(
+            None,
+            vec![(Expression::col_ref(0), Expression::int(1))],
+            Expression::int(0),
+        );
+        assert!(map(plan.clone(), case).derive_schema(&catalog).is_err());
+        let case = Expression::case(
+            Some(Expression::col_ref(2)),
+            vec![(Expression::int(1), Expression::int(1))],
+            Expression::int(0),
+        );
+        assert!(map(plan, case).derive_schema(&catalog).is_err());
+    }
+
+    #[test]
+    fn test_derive_aggregate_and_outer_join() {
+        let (catalog, plan) = setup();
+        let agg = PhysicalRelExpr::HashAggregate {
+            src: Box::new(plan.clone()),
+            group_by: vec![2],
+            aggrs: vec![(20, (0, AggOp::Count)), (21, (0, AggOp::Avg))],
+            tree_hash: None,
+        };
+        let schema = agg.derive_schema(&catalog).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                PlanColumn::new(2, DataType::Date, true),
+                PlanColumn::new(20, DataType::BigInt, false),
+                PlanColumn::new(21, DataType::Decimal(10, 4), true),
+            ]
+        );
+
+        let join = PhysicalRelExpr::CrossJoin {
+            join_type: JoinType::LeftOuter,
+            left: Box::new(plan.clone()),
+            right: Box::new(plan),
+            predicates: vec![],
+            tree_hash: None,
+        };
+        let mut visited = 0;
+        let schema = join
+            .derive_schema_with(&catalog, &mut |_, _| visited += 1)
+            .unwrap();
+        assert_eq!(visited, 5);
+        assert!(!schema[0].nullable);
+        assert!(schema[4].nullable);
+    }
+
+    #[test]
+    fn test_derive_union() {
+        let (catalog, plan) = setup();
+        let project = |cols: Vec<ColumnId>| PhysicalRelExpr::Project {
+            src: Box::new(plan.clone()),
+            cols,
+            tree_hash: None,
+        };
+        let union = |left, right| PhysicalRelExpr::Union {
+            left: Box::new(left),
+            right: Box::new(right),
+            tree_hash: None,
+        };
+        // Columns keep the ids of the left input and are nullable if either is.
+        let schema = union(project(vec![0, 1]), project(vec![0, 1]))
+            .derive_schema(&catalog)
+            .unwrap();
+        assert_eq!(schema[0], PlanColumn::new(0, DataType::BigInt, false));
+        let schema = union(project(vec![0]), project(vec![0, 1])).derive_schema(&catalog);
+        assert!(schema.is_err());
+        let null = PhysicalRelExpr::Project {
+            src: Box::new(map(plan.clone(), Expression::Field { val: Field::Null })),
+            cols: vec![10],
+            tree_hash: None,
+        };
+        let schema = union(project(vec![0]), null)
+            .derive_schema(&catalog)
+            .unwrap();
+        assert_eq!(schema, vec![PlanColumn::new(0, DataType::BigInt, true)]);
+
+        let err = union(project(vec![0]), project(vec![2]))
+            .derive_schema(&catalog)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            FairyError::ValidationError(
+                "UNION column 0 has incompatible types bigint and date".to_string()
+            )
+        );
+    }
+}
@@ -1,15 +1,149 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, RwLock};
 
+use chrono::{DateTime, Utc};
+
+use crate::catalog::{get_column_index_from_temp_col_id, Catalog};
 use crate::ids::{ContainerId, LogicalTimeStamp, TransactionId};
-use crate::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use crate::physical_expr::physical_rel_expr::{PhysicalRelExpr, PlanHashes};
 use crate::traits::transaction_manager_trait::TransactionManagerTrait;
-use crate::FairyError;
+use crate::{DataType, FairyError};
+
+/// Schema generation of a table read by a cached plan, together with the
+/// columns the plan reads from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaStamp {
+    pub c_id: ContainerId,
+    pub generation: u64,
+    pub columns: Vec<(String, DataType)>,
+}
+
+/// Outcome of validating a cached plan's schema stamps against the catalog.
+#[derive(Debug, PartialEq)]
+pub enum StampCheck {
+    /// No referenced table changed, the cached plan can be used as is.
+    Valid,
+    /// A referenced table changed but every column the plan reads still
+    /// exists with the same type, so the query can be re-planned.
+    Stale,
+    /// The plan reads a column (or table) that is gone or was retyped.
+    Invalid(String),
+}
+
+impl SchemaStamp {
+    /// Stamps every table scanned by `plan` with its current schema generation.
+    pub fn from_plan(plan: &PhysicalRelExpr, catalog: &Catalog) -> Vec<SchemaStamp> {
+        let mut scanned = HashMap::new();
+        plan.get_scanned_columns(&mut scanned);
+        let mut stamps: Vec<SchemaStamp> = scanned
+            .into_iter()
+            .filter_map(|(c_id, col_ids)| {
+                let table = catalog.get_table(c_id)?;
+                let mut indices: Vec<usize> = col_ids
+                    .into_iter()
+                    .map(|col_id| get_column_index_from_temp_col_id(col_id) as usize)
+                    .collect();
+                indices.sort_unstable();
+                let columns = indices
+                    .into_iter()
+                    .filter_map(|i| table.schema.get_attribute(i))
+                    .map(|attr| (attr.name.clone(), attr.dtype.clone()))
+                    .collect();
+                Some(SchemaStamp {
+                    c_id,
+                    generation: table.schema_generation,
+                    columns,
+                })
+            })
+            .collect();
+        stamps.sort_by_key(|stamp| stamp.c_id);
+        stamps
+    }
+
+    /// Validates the stamp against the current catalog.
+    pub fn check(&self, catalog: &Catalog) -> StampCheck {
+        let table = match catalog.get_table(self.c_id) {
+            Some(table) => table,
+            None => return StampCheck::Invalid(format!("table {} was dropped", self.c_id)),
+        };
+        if table.schema_generation == self.generation {
+            return StampCheck::Valid;
+        }
+        for (name, dtype) in &self.columns {
+            let current = table
+                .schema
+                .get_field_index(name)
+                .and_then(|i| table.schema.get_attribute(i));
+            match current {
+                None => {
+                    return StampCheck::Invalid(format!(
+                        "column {}.{} was dropped",
+                        table.name, name
+                    ))
+                }
+                Some(attr) if attr.dtype != *dtype => {
+                    return StampCheck::Invalid(format!(
+                        "column {}.{} changed type from {} to {}",
+                        table.name, name, dtype, attr.dtype
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        StampCheck::Stale
+    }
+}
+
+/// Hash identifying a physical plan tree in the plan cache.
+pub fn plan_hash(plan: &PhysicalRelExpr) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", plan).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// When a registered query was planned and how often its cached result was used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryUsage {
+    pub registered_at: DateTime<Utc>,
+    pub hits: u64,
+}
+
+/// Copy of everything the registrar knows about one registered query.
+#[derive(Debug, Clone)]
+pub struct RegisteredQueryInfo {
+    pub name: String,
+    pub sql: Option<String>,
+    pub plan_hash: u64,
+    pub tables: Vec<ContainerId>,
+    pub schema_stamps: Vec<SchemaStamp>,
+    pub result_path: Option<String>,
+    pub usage: Option<QueryUsage>,
+}
+
+/// Part of a plan that the plan of a registered query computes as well.
+#[derive(Debug, Clone)]
+pub struct PlanOverlap<'a> {
+    pub query_name: String,
+    /// Subplan of the plan that was looked up.
+    pub subplan: &'a PhysicalRelExpr,
+    /// Matching subplan of the registered query's plan.
+    pub cached_subplan: PhysicalRelExpr,
+}
+
+/// Hashes of a registered plan, computed once when the plan is registered.
+struct RegisteredHashes {
+    plan_hash: u64,
+    nodes: PlanHashes,
+}
 
 pub struct QueryStateRegistrar {
-    // maps query name to tuple: (query plan hash, plan)
+    // maps query name to plan
     pub query_plans: Arc<RwLock<HashMap<String, Arc<PhysicalRelExpr>>>>,
+    // maps query name to the hashes of its plan
+    query_plan_hashes: Arc<RwLock<HashMap<String, RegisteredHashes>>>,
     // unused rn (json path is passed as "")
     query_filenames: Arc<RwLock<HashMap<String, String>>>,
     // unused rn (passing 0)
@@ -22,6 +156,10 @@ pub struct QueryStateRegistrar {
     query_result_filenames: Arc<RwLock<HashMap<String, String>>>,
     // maps query name to its tid
     query_tids: Arc<RwLock<HashMap<String, TransactionId>>>,
+    // maps query name to the schema generations of the tables its plan reads
+    query_schema_stamps: Arc<RwLock<HashMap<String, Vec<SchemaStamp>>>>,
+    // maps query name to its registration time and cache hit count
+    query_usage: Arc<RwLock<HashMap<String, QueryUsage>>>,
 }
 
 impl Default for QueryStateRegistrar {
@@ -34,12 +172,15 @@ impl QueryStateRegistrar {
     pub fn new() -> Self {
         QueryStateRegistrar {
             query_plans: Arc::new(RwLock::new(HashMap::new())),
+            query_plan_hashes: Arc::new(RwLock::new(HashMap::new())),
             query_filenames: Arc::new(RwLock::new(HashMap::new())),
             query_watermarks: Arc::new(RwLock::new(HashMap::new())),
             in_progress_queries: Arc::new(RwLock::new(HashMap::new())),
             query_result_filenames: Arc::new(RwLock::new(HashMap::new())),
             sql_to_query_name: Arc::new(RwLock::new(HashMap::new())),
             query_tids: Arc::new(RwLock::new(HashMap::new())),
+            query_schema_stamps: Arc::new(RwLock::new(HashMap::new())),
+            query_usage: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -55,6 +196,7 @@ impl QueryStateRegistrar {
             let mut watermarks = self.query_watermarks.write().unwrap();
             plans.clear();
             drop(plans);
+            self.query_plan_hashes.write().unwrap().clear();
             // remove files pointed to by query result serializations
             for file_path in files.values() {
                 if let Err(e) = fs::remove_file(file_path) {
@@ -83,11 +225,7 @@ impl QueryStateRegistrar {
         json_path: String,
         query_plan: Arc<PhysicalRelExpr>,
     ) -> Result<(), FairyError> {
-        self.query_plans
-            .write()
-            .unwrap()
-            // we can clone because the plan's contents being the same is all we care about when registering
-            .insert(query_name.clone(), query_plan);
+        self.insert_plan(&query_name, query_plan)?;
         self.query_filenames
             .write()
             .unwrap()
@@ -102,6 +240,8 @@ impl QueryStateRegistrar {
     ///
     /// * `query_name` - Query name to register.
     /// * `query_plan` - Query plan to register.
+    /// * `schema_stamps` - Schema generations of the tables the plan reads.
+    #[allow(clippy::too_many_arguments)]
     pub fn register_query_with_result(
         &self,
         query_name: String,
@@ -110,13 +250,9 @@ impl QueryStateRegistrar {
         query_plan: Arc<PhysicalRelExpr>,
         query_result_path: String,
         query_tid: TransactionId,
+        schema_stamps: Vec<SchemaStamp>,
     ) -> Result<(), FairyError> {
-        self.query_plans
-            .write()
-            .unwrap()
-            // we can clone because the plan's contents being the same is all we care about when registering.
-            // we also assume that any passed plan has been optimized and thus hashed, so passing it into our map is all good.
-            .insert(query_name.clone(), query_plan);
+        self.insert_plan(&query_name, query_plan)?;
         self.query_filenames
             .write()
             .unwrap()
@@ -133,10 +269,44 @@ impl QueryStateRegistrar {
             .write()
             .unwrap()
             .insert(query_name.clone(), query_tid);
+        self.query_schema_stamps
+            .write()
+            .unwrap()
+            .insert(query_name.clone(), schema_stamps);
+        self.query_usage.write().unwrap().insert(
+            query_name.clone(),
+            QueryUsage {
+                registered_at: Utc::now(),
+                hits: 0,
+            },
+        );
         self.query_watermarks.write().unwrap().insert(query_name, 0);
         Ok(())
     }
 
+    /// Stores the plan of a query together with its hashes, so that looking plans
+    /// up never hashes a registered plan again.
+    fn insert_plan(
+        &self,
+        query_name: &str,
+        query_plan: Arc<PhysicalRelExpr>,
+    ) -> Result<(), FairyError> {
+        // the plan is behind an Arc, so the node addresses the hashes are keyed by stay valid
+        let hashes = RegisteredHashes {
+            plan_hash: plan_hash(&query_plan),
+            nodes: query_plan.compute_hashes()?,
+        };
+        self.query_plan_hashes
+            .write()
+            .unwrap()
+            .insert(query_name.to_string(), hashes);
+        self.query_plans
+            .write()
+            .unwrap()
+            .insert(query_name.to_string(), query_plan);
+        Ok(())
+    }
+
     /// Begin running a registered query.
     ///
     /// # Arguments
@@ -244,6 +414,7 @@ impl QueryStateRegistrar {
 
     pub fn purge_query_with_name(&self, query_name: &String) -> Result<(), FairyError> {
         self.query_plans.write().unwrap().remove(query_name);
+        self.query_plan_hashes.write().unwrap().remove(query_name);
         self.query_filenames.write().unwrap().remove(query_name);
         self.query_watermarks.write().unwrap().remove(query_name);
 
@@ -260,6 +431,8 @@ impl QueryStateRegistrar {
             .remove(query_name);
 
         self.query_tids.write().unwrap().remove(query_name);
+        self.query_schema_stamps.write().unwrap().remove(query_name);
+        self.query_usage.write().unwrap().remove(query_name);
 
         // linear search, could optimize w 2 hashmaps but for now may not b worth cuz move away from string matching
         // will either use bimap or ditch string approach eventually. I'm not convinced we even need it now
@@ -319,6 +492,173 @@ impl QueryStateRegistrar {
         }
     }
 
+    /// Validates the schema stamps of a registered query against the catalog.
+    /// Returns None if the query is not registered.
+    pub fn check_schema_stamps(
+        &self,
+        query_name: &String,
+        catalog: &Catalog,
+    ) -> Result<Option<StampCheck>, FairyError> {
+        let stamps = self.query_schema_stamps.read().unwrap();
+        let stamps = match stamps.get(query_name) {
+            Some(stamps) => stamps,
+            None => return Ok(None),
+        };
+        let mut res = StampCheck::Valid;
+        for stamp in stamps {
+            match stamp.check(catalog) {
+                StampCheck::Valid => {}
+                StampCheck::Stale => res = StampCheck::Stale,
+                invalid => return Ok(Some(invalid)),
+            }
+        }
+        Ok(Some(res))
+    }
+
+    /// Hash of the plan of a registered query, or None if it is not registered.
+    pub fn get_plan_hash_from_name(&self, query_name: &str) -> Option<u64> {
+        self.query_plan_hashes
+            .read()
+            .unwrap()
+            .get(query_name)
+            .map(|hashes| hashes.plan_hash)
+    }
+
+    /// Counts a use of the cached result of a registered query.
+    pub fn record_hit(&self, query_name: &str) {
+        if let Some(usage) = self.query_usage.write().unwrap().get_mut(query_name) {
+            usage.hits += 1;
+        }
+    }
+
+    /// Copies the state of every registered query, sorted by name. Each map is
+    /// only locked (for reading) while it is copied, so running queries are held
+    /// up no longer than the copy takes.
+    pub fn snapshot(&self) -> Vec<RegisteredQueryInfo> {
+        let plans: Vec<(String, Arc<PhysicalRelExpr>)> = self
+            .query_plans
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, plan)| (name.clone(), plan.clone()))
+            .collect();
+        let plan_hashes: HashMap<String, u64> = self
+            .query_plan_hashes
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, hashes)| (name.clone(), hashes.plan_hash))
+            .collect();
+        let sqls: HashMap<String, String> = self
+            .sql_to_query_name
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(sql, name)| (name.clone(), sql.clone()))
+            .collect();
+        let mut stamps = self.query_schema_stamps.read().unwrap().clone();
+        let mut result_paths = self.query_result_filenames.read().unwrap().clone();
+        let mut usage = self.query_usage.read().unwrap().clone();
+
+        let mut infos: Vec<RegisteredQueryInfo> = plans
+            .into_iter()
+            .map(|(name, plan)| {
+                let mut tables = Vec::new();
+                plan.get_tables_involved(&mut tables);
+                tables.sort_unstable();
+                tables.dedup();
+                RegisteredQueryInfo {
+                    sql: sqls.get(&name).cloned(),
+                    plan_hash: plan_hashes
+                        .get(&name)
+                        .copied()
+                        .unwrap_or_else(|| plan_hash(&plan)),
+                    tables,
+                    schema_stamps: stamps.remove(&name).unwrap_or_default(),
+                    result_path: result_paths.remove(&name),
+                    usage: usage.remove(&name),
+                    name,
+                }
+            })
+            .collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Removes the registered queries that read table `c_id`, along with their
+    /// cached results. Returns their names.
+    pub fn purge_queries_on_table(&self, c_id: ContainerId) -> Result<Vec<String>, FairyError> {
+        let names: Vec<String> = self
+            .query_plans
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, plan)| {
+                let mut tables = Vec::new();
+                plan.get_tables_involved(&mut tables);
+                tables.contains(&c_id)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &names {
+            self.purge_query_with_name(name)?;
+        }
+        Ok(names)
+    }
+
+    /// Removes the registered query whose plan has the given hash. Returns the
+    /// name of the removed query, or None if no plan has that hash.
+    pub fn evict_plan(&self, hash: u64) -> Result<Option<String>, FairyError> {
+        let name = self
+            .query_plan_hashes
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, hashes)| hashes.plan_hash == hash)
+            .map(|(name, _)| name.clone());
+        match name {
+            Some(name) => {
+                self.purge_query_with_name(&name)?;
+                Ok(Some(name))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the parts of `plan` that registered plans compute as well, giving
+    /// precedence to the largest parts of `plan`. Only `plan` is hashed, the
+    /// hashes of registered plans were computed when they were registered.
+    pub fn find_overlaps<'a>(
+        &self,
+        plan: &'a PhysicalRelExpr,
+    ) -> Result<Vec<PlanOverlap<'a>>, FairyError> {
+        let hashes = plan.compute_hashes()?;
+        let plans = self.query_plans.read().unwrap();
+        let plan_hashes = self.query_plan_hashes.read().unwrap();
+        let mut names = Vec::new();
+        let mut cached = Vec::new();
+        for (name, cached_plan) in plans.iter() {
+            if let Some(cached_hashes) = plan_hashes.get(name) {
+                names.push(name);
+                cached.push((cached_plan.as_ref(), &cached_hashes.nodes));
+            }
+        }
+        let overlaps = plan.identify_potential_tree_overlaps(&hashes, &cached)?;
+        Ok(overlaps
+            .into_iter()
+            .filter_map(|(cached_plan, subplan, cached_subplan)| {
+                let i = cached
+                    .iter()
+                    .position(|(p, _)| std::ptr::eq(*p, cached_plan))?;
+                Some(PlanOverlap {
+                    query_name: names[i].clone(),
+                    subplan,
+                    cached_subplan: cached_subplan.clone(),
+                })
+            })
+            .collect())
+    }
+
     // // maybe we use physical plans to get matching queries later...
     // pub fn get_query_with_pp(&self, pp: &PhysicalRelExpr) -> Option<String> {
     //     let query_plans = self.query_plans.read().unwrap();
@@ -330,3 +670,100 @@ impl QueryStateRegistrar {
     //     None
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::physical_expr::physical_rel_expr::NODES_HASHED;
+    use crate::query::expr::Expression;
+    use crate::query::join_type::JoinType;
+
+    fn scan(cid: ContainerId) -> PhysicalRelExpr {
+        PhysicalRelExpr::Scan {
+            cid,
+            table_name: format!("table_{}", cid),
+            column_names: vec![1, 2],
+            tree_hash: None,
+        }
+    }
+
+    /// Scan of table `cid` under `depth` selections.
+    fn filtered_scan(cid: ContainerId, depth: i64) -> PhysicalRelExpr {
+        (0..depth).fold(scan(cid), |src, i| PhysicalRelExpr::Select {
+            src: Box::new(src),
+            predicates: vec![Expression::col_ref(2).eq(Expression::int(i))],
+            tree_hash: None,
+        })
+    }
+
+    /// Number of plan nodes hashed while running `f`.
+    fn nodes_hashed<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = NODES_HASHED.with(|n| n.get());
+        let res = f();
+        (res, NODES_HASHED.with(|n| n.get()) - before)
+    }
+
+    #[test]
+    fn test_find_overlaps_only_hashes_the_plan() {
+        let registrar = QueryStateRegistrar::new();
+        // 5 nodes, the left input of the join is part of every registered plan
+        let plan = PhysicalRelExpr::HashJoin {
+            join_type: JoinType::Inner,
+            left: Box::new(filtered_scan(1, 2)),
+            right: Box::new(scan(2)),
+            predicates: vec![],
+            tree_hash: None,
+        };
+
+        let mut registered = Vec::new();
+        for depth in [2, 50, 200] {
+            let name = format!("q{:04}", depth);
+            registered.push(name.clone());
+            let cached = Arc::new(filtered_scan(1, depth));
+            let (res, hashed) =
+                nodes_hashed(|| registrar.register_query(name, String::new(), cached));
+            res.unwrap();
+            assert_eq!(hashed, depth as usize + 1);
+
+            // however large the registered plans are, only the plan is hashed
+            let (overlaps, hashed) = nodes_hashed(|| registrar.find_overlaps(&plan));
+            assert_eq!(hashed, 5);
+            let mut names: Vec<String> = overlaps
+                .unwrap()
+                .into_iter()
+                .map(|overlap| {
+                    assert!(std::ptr::eq(overlap.subplan, plan_left(&plan)));
+                    assert_eq!(
+                        format!("{:?}", overlap.cached_subplan),
+                        format!("{:?}", filtered_scan(1, 2))
+                    );
+                    overlap.query_name
+                })
+                .collect();
+            names.sort();
+            assert_eq!(names, registered);
+        }
+    }
+
+    fn plan_left(plan: &PhysicalRelExpr) -> &PhysicalRelExpr {
+        match plan {
+            PhysicalRelExpr::HashJoin { left, .. } => left,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_plan_hash_is_computed_at_registration() {
+        let registrar = QueryStateRegistrar::new();
+        let plan = Arc::new(filtered_scan(1, 3));
+        let expected = plan_hash(&plan);
+        registrar
+            .register_query("q".to_string(), String::new(), plan)
+            .unwrap();
+        let (infos, hashed) = nodes_hashed(|| registrar.snapshot());
+        assert_eq!(hashed, 0);
+        assert_eq!(infos[0].plan_hash, expected);
+        registrar.reset().unwrap();
+        assert!(registrar.query_plan_hashes.read().unwrap().is_empty());
+    }
+}
@@ -1,6 +1,9 @@
+use crate::datatypes::{DisplayFormat, OrdF64};
 use crate::table::TableSchema;
 use crate::tuple::Tuple;
 use crate::FairyError;
+use crate::Field;
+use std::borrow::Cow;
 use std::fmt;
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
@@ -11,6 +14,46 @@ pub struct PagingInfo {
     pub page_size: u32,
 }
 
+/// Condition of a select result that a client may act on, e.g. to fetch the
+/// rest of a truncated result or to discount a cached one.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Notice {
+    /// Only the first `rows` rows were returned, as the session's
+    /// `max_result_rows` allows.
+    Truncated { rows: usize },
+    /// The rows are the cached result of the registered query whose plan has
+    /// hash `hash`.
+    CacheHit { hash: u64 },
+    /// A join changed strategy while the query ran because an input was much
+    /// larger than estimated.
+    AdaptiveJoinSwap,
+    /// A table the query reads changed much since its column groups were
+    /// analyzed, so the plan may be poor.
+    StaleStats,
+    /// The plan joins two inputs without a predicate relating them, pairing
+    /// every row of one with every row of the other.
+    CrossProduct,
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notice::Truncated { rows } => {
+                write!(f, "Result truncated to {} rows by max_result_rows", rows)
+            }
+            Notice::CacheHit { hash } => write!(f, "Cached result of plan {:016x}", hash),
+            Notice::AdaptiveJoinSwap => write!(f, "A join changed strategy at runtime"),
+            Notice::StaleStats => write!(f, "Column group statistics are stale, run ANALYZE"),
+            Notice::CrossProduct => {
+                write!(
+                    f,
+                    "The plan has a cross product, a join without a predicate"
+                )
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub enum QueryResult {
     MessageOnly(String),
@@ -18,10 +61,22 @@ pub enum QueryResult {
         schema: TableSchema,
         result: Vec<Tuple>,
         paging_info: Option<PagingInfo>,
+        /// Note printed below the rows, e.g. about rows the query skipped.
+        #[serde(default)]
+        footer: Option<String>,
+        /// Format the session renders the rows in, if not the default one.
+        #[serde(default)]
+        format: Option<DisplayFormat>,
+        /// Conditions of the result a client may act on.
+        #[serde(default)]
+        notices: Vec<Notice>,
     },
     Insert {
         inserted: usize,
         table_name: String,
+        /// Note reported with the insert, e.g. that it was retried.
+        #[serde(default)]
+        notice: Option<String>,
     },
 }
 
@@ -35,6 +90,96 @@ impl QueryResult {
             schema: schema.clone(),
             result,
             paging_info, // Set the passed paging info
+            footer: None,
+            format: None,
+            notices: Vec::new(),
+        }
+    }
+
+    /// Attaches a note printed below the rows of a select result. Other
+    /// results are left unchanged.
+    pub fn with_footer(mut self, note: String) -> Self {
+        if let QueryResult::Select { footer, .. } = &mut self {
+            *footer = Some(note);
+        }
+        self
+    }
+
+    /// Appends a note to the result: below the rows of a select result, as a
+    /// line of its own to a message, or to the notice of an insert.
+    pub fn with_notice(mut self, note: String) -> Self {
+        let append = |existing: Option<&String>| match existing {
+            Some(existing) => format!("{}\n{}", existing, note),
+            None => note.clone(),
+        };
+        match &mut self {
+            QueryResult::Select { footer, .. } => *footer = Some(append(footer.as_ref())),
+            QueryResult::MessageOnly(message) => *message = append(Some(&*message)),
+            QueryResult::Insert { notice, .. } => *notice = Some(append(notice.as_ref())),
+        }
+        self
+    }
+
+    /// Sets the format the rows of a select result are rendered in. Other
+    /// results are left unchanged.
+    pub fn with_format(mut self, display_format: DisplayFormat) -> Self {
+        if let QueryResult::Select { format, .. } = &mut self {
+            *format = (display_format != DisplayFormat::DEFAULT).then_some(display_format);
+        }
+        self
+    }
+
+    /// The format the rows are rendered in unless the client overrides it.
+    pub fn get_format(&self) -> &DisplayFormat {
+        match self {
+            QueryResult::Select {
+                format: Some(format),
+                ..
+            } => format,
+            _ => &DisplayFormat::DEFAULT,
+        }
+    }
+
+    /// Adds `added` to the notices of a select result. Other results are left
+    /// unchanged.
+    pub fn with_notices(mut self, added: impl IntoIterator<Item = Notice>) -> Self {
+        if let QueryResult::Select { notices, .. } = &mut self {
+            notices.extend(added);
+        }
+        self
+    }
+
+    /// The notices of a select result, empty for other results.
+    pub fn notices(&self) -> &[Notice] {
+        match self {
+            QueryResult::Select { notices, .. } => notices,
+            _ => &[],
+        }
+    }
+
+    /// Keeps the first `max` rows of a select result, with a notice if rows
+    /// were dropped. A `max` of 0 keeps every row, as do the batches of a
+    /// cursor, whose size FETCH chooses.
+    pub fn with_max_rows(mut self, max: usize) -> Self {
+        if let QueryResult::Select {
+            result,
+            paging_info: None,
+            notices,
+            ..
+        } = &mut self
+        {
+            if max > 0 && result.len() > max {
+                result.truncate(max);
+                notices.push(Notice::Truncated { rows: max });
+            }
+        }
+        self
+    }
+
+    pub fn get_footer(&self) -> Option<&str> {
+        match self {
+            QueryResult::Select { footer, .. } => footer.as_deref(),
+            _ => None,
         }
     }
 
@@ -46,6 +191,7 @@ impl QueryResult {
         QueryResult::Insert {
             inserted,
             table_name,
+            notice: None,
         }
     }
 
@@ -63,6 +209,14 @@ impl QueryResult {
         }
     }
 
+    /// Appends rows to a select result, e.g. a chunk of a result the server
+    /// sends in chunks. Other results are left unchanged.
+    pub fn extend_rows(&mut self, rows: Vec<Tuple>) {
+        if let QueryResult::Select { result, .. } = self {
+            result.extend(rows);
+        }
+    }
+
     /// Merge results
     pub fn merge_results(&mut self, other: QueryResult) -> Result<(), FairyError> {
         match (self, other) {
@@ -70,12 +224,12 @@ impl QueryResult {
                 QueryResult::Select {
                     schema: schema1,
                     result: result1,
-                    paging_info: _,
+                    ..
                 },
                 QueryResult::Select {
                     schema: schema2,
                     result: mut result2,
-                    paging_info: _,
+                    ..
                 },
             ) => {
                 if *schema1 != schema2 {
@@ -93,8 +247,70 @@ impl QueryResult {
     }
 }
 
-impl fmt::Display for QueryResult {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// Renders a field for display, escaping control characters (e.g. newlines in
+/// free-text comments) so that they cannot break the column alignment.
+fn render_field(item: &Field, format: &DisplayFormat) -> String {
+    let s = item.render(format);
+    if !s.chars().any(char::is_control) {
+        return s;
+    }
+    s.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Joins fields into a CSV line, quoting the ones that contain a comma, a
+/// quote or a line break.
+pub fn csv_line<'a>(fields: impl Iterator<Item = Cow<'a, str>>) -> String {
+    fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.into_owned()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// A field as a JSON value: NULL, booleans, integers and finite floats map to
+/// their JSON counterparts, everything else is rendered to a string.
+fn json_value(field: &Field, format: &DisplayFormat) -> serde_json::Value {
+    match field {
+        Field::Null => serde_json::Value::Null,
+        Field::Bool(b) => serde_json::Value::Bool(*b),
+        Field::BigInt(i) => (*i).into(),
+        Field::Int(i) => (*i).into(),
+        Field::SmallInt(i) => (*i).into(),
+        Field::Float(OrdF64(f)) => match serde_json::Number::from_f64(*f) {
+            Some(n) => serde_json::Value::Number(n),
+            None => serde_json::Value::String(field.render(format)),
+        },
+        _ => serde_json::Value::String(field.render(format)),
+    }
+}
+
+impl QueryResult {
+    /// Renders the result as an aligned table with the fields in `format`.
+    pub fn render_table(&self, format: &DisplayFormat) -> String {
+        let mut table = self.render_table_rows(format, true);
+        if let Some(footer) = self.get_footer() {
+            table.push_str(footer);
+            table.push('\n');
+        }
+        table
+    }
+
+    /// Renders the rows as aligned columns, under the column names if
+    /// `header`, with the fields in `format` and without the footer.
+    pub fn render_table_rows(&self, format: &DisplayFormat, header: bool) -> String {
         let schema = self.get_schema().cloned().unwrap_or_default();
         let tuples = self.get_tuples().cloned().unwrap_or_default();
 
@@ -102,17 +318,17 @@ impl fmt::Display for QueryResult {
         let mut column_widths: Vec<usize> = schema
             .attributes
             .iter()
-            .map(|attr| attr.name.len())
+            .map(|attr| attr.name.chars().count())
             .collect();
         for tuple in tuples.iter() {
             for (i, item) in tuple.field_vals.iter().enumerate() {
-                let item_str = format!("{}", item);
-                column_widths[i] = column_widths[i].max(item_str.len());
+                let item_str = render_field(item, format);
+                column_widths[i] = column_widths[i].max(item_str.chars().count());
             }
         }
 
         // padding for attribute headers
-        let header: String = schema
+        let header_line: String = schema
             .attributes
             .iter()
             .enumerate()
@@ -129,7 +345,11 @@ impl fmt::Display for QueryResult {
                     .iter()
                     .enumerate()
                     .map(|(i, item)| {
-                        format!("{:width$}", format!("{}", item), width = column_widths[i])
+                        format!(
+                            "{:width$}",
+                            render_field(item, format),
+                            width = column_widths[i]
+                        )
                     })
                     .collect::<Vec<String>>()
                     .join("    ")
@@ -137,7 +357,169 @@ impl fmt::Display for QueryResult {
             .collect::<Vec<String>>()
             .join("\n");
 
-        // Write the final formatted table
-        write!(f, "\n{}\n{}\n{}\n", header, "-".repeat(header.len()), rows)
+        if !header {
+            return format!("{}\n", rows);
+        }
+        format!(
+            "\n{}\n{}\n{}\n",
+            header_line,
+            "-".repeat(header_line.chars().count()),
+            rows
+        )
+    }
+
+    /// Renders the rows as CSV, headed by the column names, with the fields in
+    /// `format`.
+    pub fn render_csv(&self, format: &DisplayFormat) -> String {
+        let mut csv = String::new();
+        if let Some(schema) = self.get_schema() {
+            let names = schema.attributes.iter().map(|a| Cow::from(a.name.as_str()));
+            csv.push_str(&csv_line(names));
+            csv.push('\n');
+        }
+        for tuple in self.get_tuples().into_iter().flatten() {
+            csv.push_str(&tuple.to_csv_with(format));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Renders the rows as a JSON array holding one object per row.
+    pub fn render_json(&self, format: &DisplayFormat) -> String {
+        serde_json::Value::Array(self.json_rows(format)).to_string()
+    }
+
+    /// The rows as JSON objects keyed by column name.
+    pub fn json_rows(&self, format: &DisplayFormat) -> Vec<serde_json::Value> {
+        let names: Vec<String> = self
+            .get_schema()
+            .map(|schema| schema.attributes.iter().map(|a| a.name.clone()).collect())
+            .unwrap_or_default();
+        self.get_tuples()
+            .into_iter()
+            .flatten()
+            .map(|tuple| {
+                let values = tuple.field_vals.iter().map(|f| json_value(f, format));
+                serde_json::Value::Object(names.iter().cloned().zip(values).collect())
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render_table(self.get_format()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::base_date;
+    use crate::DataType;
+    use chrono::NaiveDate;
+
+    /// Two rows holding a date, a decimal and a nullable string.
+    fn result() -> QueryResult {
+        let schema = TableSchema::from_vecs(
+            vec!["day", "price", "note"],
+            vec![DataType::Date, DataType::Decimal(5, 2), DataType::String],
+        );
+        let day = (NaiveDate::from_ymd_opt(2024, 3, 5).unwrap() - base_date()).num_days();
+        let rows = vec![
+            Tuple::new(vec![
+                Field::Date(day),
+                Field::Decimal(12345, 2),
+                Field::String("a, b".to_string()),
+            ]),
+            Tuple::new(vec![
+                Field::Date(day + 1),
+                Field::Decimal(5, 2),
+                Field::Null,
+            ]),
+        ];
+        QueryResult::new_select_result(&schema, rows, None)
+    }
+
+    fn european() -> DisplayFormat {
+        DisplayFormat {
+            null_text: "<null>".into(),
+            date_format: "%d.%m.%Y".into(),
+            decimal_separator: ',',
+        }
+    }
+
+    #[test]
+    fn test_render_table() {
+        let result = result();
+        assert_eq!(
+            result.render_table(&DisplayFormat::DEFAULT),
+            "\nday           price     note\n\
+             ----------------------------\n\
+             2024-03-05    123.45    a, b\n\
+             2024-03-06    0.05      NULL\n"
+        );
+        assert_eq!(
+            result.render_table(&european()),
+            "\nday           price     note  \n\
+             ------------------------------\n\
+             05.03.2024    123,45    a, b  \n\
+             06.03.2024    0,05      <null>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_csv_and_json() {
+        let result = result();
+        assert_eq!(
+            result.render_csv(&DisplayFormat::DEFAULT),
+            "day,price,note\n2024-03-05,123.45,\"a, b\"\n2024-03-06,0.05,NULL\n"
+        );
+        let format = DisplayFormat {
+            date_format: "%b %-d, %Y".into(),
+            ..european()
+        };
+        assert_eq!(
+            result.render_csv(&format),
+            "day,price,note\n\"Mar 5, 2024\",\"123,45\",\"a, b\"\n\"Mar 6, 2024\",\"0,05\",<null>\n"
+        );
+        assert_eq!(
+            result.render_json(&format),
+            r#"[{"day":"Mar 5, 2024","note":"a, b","price":"123,45"},{"day":"Mar 6, 2024","note":null,"price":"0,05"}]"#
+        );
+    }
+
+    #[test]
+    fn test_display_uses_attached_format() {
+        let result = result().with_format(european());
+        assert_eq!(result.get_format(), &european());
+        assert!(result.to_string().contains("05.03.2024    123,45"));
+        // The default format is not carried along.
+        let result = result.with_format(DisplayFormat::DEFAULT);
+        assert!(matches!(result, QueryResult::Select { format: None, .. }));
+        assert!(DisplayFormat::validate_date_format("%Y-%Q").is_err());
+    }
+
+    #[test]
+    fn test_max_rows() {
+        assert_eq!(result().with_max_rows(0), result());
+        assert_eq!(result().with_max_rows(2), result());
+        let truncated = result().with_max_rows(1);
+        assert_eq!(truncated.get_tuples().unwrap().len(), 1);
+        assert_eq!(truncated.notices(), [Notice::Truncated { rows: 1 }]);
+
+        // Cursor batches are left whole.
+        let paging_info = PagingInfo {
+            current_page: 1,
+            total_pages: 1,
+            has_next_page: false,
+            page_size: 2,
+        };
+        let batch = QueryResult::new_select_result(
+            result().get_schema().unwrap(),
+            result().get_tuples().unwrap().clone(),
+            Some(paging_info),
+        );
+        assert!(batch.with_max_rows(1).notices().is_empty());
     }
 }
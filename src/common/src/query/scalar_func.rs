@@ -0,0 +1,382 @@
+//! Scalar functions, which compute a value from the values of one row. The
+//! string functions work on characters, not bytes, so multi-byte UTF-8 is
+//! never split.
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    datatypes::{date_from_days, MICROS_PER_DAY},
+    DataType, FairyError, Field,
+};
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ScalarFunc {
+    /// `UPPER(s)`
+    Upper,
+    /// `LOWER(s)`
+    Lower,
+    /// `LENGTH(s)`, the number of characters.
+    Length,
+    /// `SUBSTR(s, start [, count])`. Positions start at 1, characters before
+    /// the first one and after the last one are ignored.
+    Substr,
+    /// `TRIM([BOTH] [chars FROM] s)`, removes `chars` (spaces by default) from
+    /// both ends.
+    Trim,
+    /// `TRIM(LEADING [chars FROM] s)`
+    LTrim,
+    /// `TRIM(TRAILING [chars FROM] s)`
+    RTrim,
+    /// `EXTRACT(YEAR FROM d)` of a date or timestamp.
+    Year,
+    /// `EXTRACT(MONTH FROM d)`, from 1 to 12.
+    Month,
+    /// `EXTRACT(DAY FROM d)`, the day of the month.
+    Day,
+}
+
+impl std::fmt::Display for ScalarFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ScalarFunc::*;
+        match self {
+            Upper => write!(f, "UPPER"),
+            Lower => write!(f, "LOWER"),
+            Length => write!(f, "LENGTH"),
+            Substr => write!(f, "SUBSTR"),
+            Trim => write!(f, "TRIM"),
+            LTrim => write!(f, "LTRIM"),
+            RTrim => write!(f, "RTRIM"),
+            Year => write!(f, "YEAR"),
+            Month => write!(f, "MONTH"),
+            Day => write!(f, "DAY"),
+        }
+    }
+}
+
+impl ScalarFunc {
+    /// All functions, in the order of their codes in bytecode.
+    pub const ALL: [ScalarFunc; 10] = [
+        ScalarFunc::Upper,
+        ScalarFunc::Lower,
+        ScalarFunc::Length,
+        ScalarFunc::Substr,
+        ScalarFunc::Trim,
+        ScalarFunc::LTrim,
+        ScalarFunc::RTrim,
+        ScalarFunc::Year,
+        ScalarFunc::Month,
+        ScalarFunc::Day,
+    ];
+
+    /// The function called `name` in SQL, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "UPPER" => Some(ScalarFunc::Upper),
+            "LOWER" => Some(ScalarFunc::Lower),
+            "LENGTH" | "CHAR_LENGTH" => Some(ScalarFunc::Length),
+            "SUBSTR" | "SUBSTRING" => Some(ScalarFunc::Substr),
+            "TRIM" => Some(ScalarFunc::Trim),
+            "LTRIM" => Some(ScalarFunc::LTrim),
+            "RTRIM" => Some(ScalarFunc::RTrim),
+            "YEAR" => Some(ScalarFunc::Year),
+            "MONTH" => Some(ScalarFunc::Month),
+            "DAY" => Some(ScalarFunc::Day),
+            _ => None,
+        }
+    }
+
+    /// Checks that the function takes `n` arguments.
+    pub fn check_arity(&self, n: usize) -> Result<(), FairyError> {
+        let (min, max) = match self {
+            ScalarFunc::Upper
+            | ScalarFunc::Lower
+            | ScalarFunc::Length
+            | ScalarFunc::Year
+            | ScalarFunc::Month
+            | ScalarFunc::Day => (1, 1),
+            ScalarFunc::Substr => (2, 3),
+            ScalarFunc::Trim | ScalarFunc::LTrim | ScalarFunc::RTrim => (1, 2),
+        };
+        if n < min || n > max {
+            return Err(FairyError::ValidationError(format!(
+                "{} takes {} arguments, got {}",
+                self,
+                if min == max {
+                    min.to_string()
+                } else {
+                    format!("{} to {}", min, max)
+                },
+                n
+            )));
+        }
+        Ok(())
+    }
+
+    /// Type of the result.
+    pub fn return_type(&self) -> DataType {
+        match self {
+            ScalarFunc::Length | ScalarFunc::Year | ScalarFunc::Month | ScalarFunc::Day => {
+                DataType::BigInt
+            }
+            _ => DataType::String,
+        }
+    }
+
+    /// Checks the number and types of the arguments. The first argument is a
+    /// date or timestamp for the parts of dates, and a string otherwise. The
+    /// positions of SUBSTR are integers and the characters of TRIM a string.
+    pub fn check_args(&self, args: &[DataType]) -> Result<(), FairyError> {
+        self.check_arity(args.len())?;
+        for (i, dtype) in args.iter().enumerate() {
+            let ok = match (self, i) {
+                (ScalarFunc::Substr, 1 | 2) => matches!(
+                    dtype,
+                    DataType::BigInt | DataType::Int | DataType::SmallInt | DataType::Null
+                ),
+                (ScalarFunc::Year | ScalarFunc::Month | ScalarFunc::Day, _) => {
+                    matches!(dtype, DataType::Date | DataType::Timestamp | DataType::Null)
+                }
+                _ => matches!(dtype, DataType::String | DataType::Char(_) | DataType::Null),
+            };
+            if !ok {
+                return Err(FairyError::ValidationError(format!(
+                    "Invalid argument {} of {}: {}",
+                    i + 1,
+                    self,
+                    dtype
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the function to `args`. The result is NULL if any argument is.
+    pub fn eval(&self, args: &[Field]) -> Result<Field, FairyError> {
+        if args.contains(&Field::Null) {
+            return Ok(Field::Null);
+        }
+        if let ScalarFunc::Year | ScalarFunc::Month | ScalarFunc::Day = self {
+            let date = date_arg(self, args, 0)?;
+            return Ok(Field::BigInt(match self {
+                ScalarFunc::Year => date.year() as i64,
+                ScalarFunc::Month => date.month() as i64,
+                _ => date.day() as i64,
+            }));
+        }
+        let s = string_arg(self, args, 0)?;
+        Ok(match self {
+            ScalarFunc::Upper => Field::String(s.to_uppercase()),
+            ScalarFunc::Lower => Field::String(s.to_lowercase()),
+            ScalarFunc::Length => Field::BigInt(s.chars().count() as i64),
+            ScalarFunc::Substr => {
+                let start = int_arg(self, args, 1)?;
+                let count = args.get(2).map(|_| int_arg(self, args, 2)).transpose()?;
+                Field::String(substr(s, start, count)?)
+            }
+            ScalarFunc::Trim | ScalarFunc::LTrim | ScalarFunc::RTrim => {
+                let chars: Vec<char> = match args.get(1) {
+                    Some(_) => string_arg(self, args, 1)?.chars().collect(),
+                    None => vec![' '],
+                };
+                let trimmed = match self {
+                    ScalarFunc::Trim => s.trim_matches(chars.as_slice()),
+                    ScalarFunc::LTrim => s.trim_start_matches(chars.as_slice()),
+                    _ => s.trim_end_matches(chars.as_slice()),
+                };
+                Field::String(trimmed.to_string())
+            }
+            ScalarFunc::Year | ScalarFunc::Month | ScalarFunc::Day => unreachable!(),
+        })
+    }
+}
+
+/// The string argument at `i`, without the padding of a CHAR.
+fn string_arg<'a>(func: &ScalarFunc, args: &'a [Field], i: usize) -> Result<&'a str, FairyError> {
+    match &args[i] {
+        Field::String(s) => Ok(s),
+        Field::Char(_, s) => Ok(s.trim_end_matches('\0')),
+        arg => Err(FairyError::ValidationError(format!(
+            "{} expects a string, got {}",
+            func, arg
+        ))),
+    }
+}
+
+/// The date argument at `i`, or the date of the timestamp argument at `i`.
+fn date_arg(func: &ScalarFunc, args: &[Field], i: usize) -> Result<NaiveDate, FairyError> {
+    let days = match &args[i] {
+        Field::Date(days) => *days,
+        Field::Timestamp(micros) => micros.div_euclid(MICROS_PER_DAY),
+        arg => {
+            return Err(FairyError::ValidationError(format!(
+                "{} expects a date, got {}",
+                func, arg
+            )))
+        }
+    };
+    date_from_days(days).ok_or_else(|| {
+        FairyError::ValidationError(format!("{} of {} is out of range", func, args[i]))
+    })
+}
+
+fn int_arg(func: &ScalarFunc, args: &[Field], i: usize) -> Result<i64, FairyError> {
+    match &args[i] {
+        Field::BigInt(n) => Ok(*n),
+        Field::Int(n) => Ok(*n as i64),
+        Field::SmallInt(n) => Ok(*n as i64),
+        arg => Err(FairyError::ValidationError(format!(
+            "{} expects an integer, got {}",
+            func, arg
+        ))),
+    }
+}
+
+/// The `count` characters of `s` from position `start`, counting from 1, or
+/// all characters from `start` without `count`. Only the part of the range
+/// that lies within `s` is returned, so it may be empty.
+fn substr(s: &str, start: i64, count: Option<i64>) -> Result<String, FairyError> {
+    let end = match count {
+        Some(count) if count < 0 => {
+            return Err(FairyError::ValidationError(format!(
+                "Negative SUBSTR length {}",
+                count
+            )))
+        }
+        Some(count) => start.saturating_add(count),
+        None => i64::MAX,
+    };
+    let first = start.max(1);
+    if end <= first {
+        return Ok(String::new());
+    }
+    Ok(s.chars()
+        .skip((first - 1) as usize)
+        .take((end - first) as usize)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{f_date, f_int, f_str, f_timestamp};
+
+    #[test]
+    fn test_length_counts_characters() {
+        assert_eq!(
+            ScalarFunc::Length.eval(&[f_str("abc")]).unwrap(),
+            Field::BigInt(3)
+        );
+        assert_eq!(
+            ScalarFunc::Length.eval(&[f_str("안녕")]).unwrap(),
+            Field::BigInt(2)
+        );
+        assert_eq!(
+            ScalarFunc::Length.eval(&[f_str("")]).unwrap(),
+            Field::BigInt(0)
+        );
+        // The padding of a CHAR is not counted.
+        let padded = Field::Char(5, "ab\0\0\0".to_string());
+        assert_eq!(
+            ScalarFunc::Length.eval(&[padded]).unwrap(),
+            Field::BigInt(2)
+        );
+    }
+
+    #[test]
+    fn test_substr() {
+        let substr = |args: &[Field]| ScalarFunc::Substr.eval(args).unwrap();
+        assert_eq!(substr(&[f_str("hello"), f_int(2), f_int(3)]), f_str("ell"));
+        assert_eq!(substr(&[f_str("hello"), f_int(3)]), f_str("llo"));
+        assert_eq!(
+            substr(&[f_str("héllo wörld"), f_int(2), f_int(4)]),
+            f_str("éllo")
+        );
+        // Out of range positions only keep the characters within the string.
+        assert_eq!(substr(&[f_str("hello"), f_int(0), f_int(3)]), f_str("he"));
+        assert_eq!(substr(&[f_str("hello"), f_int(-5), f_int(3)]), f_str(""));
+        assert_eq!(substr(&[f_str("hello"), f_int(4), f_int(10)]), f_str("lo"));
+        assert_eq!(substr(&[f_str("hello"), f_int(6)]), f_str(""));
+        assert_eq!(
+            substr(&[f_str("hello"), f_int(i64::MAX), f_int(i64::MAX)]),
+            f_str("")
+        );
+        assert_eq!(substr(&[f_str("hello"), f_int(i64::MIN)]), f_str("hello"));
+        assert_eq!(substr(&[f_str("hello"), Field::Null]), Field::Null);
+        assert!(ScalarFunc::Substr
+            .eval(&[f_str("hello"), f_int(1), f_int(-1)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_case_and_trim() {
+        assert_eq!(
+            ScalarFunc::Upper.eval(&[f_str("straße")]).unwrap(),
+            f_str("STRASSE")
+        );
+        assert_eq!(
+            ScalarFunc::Lower.eval(&[f_str("ÀBC")]).unwrap(),
+            f_str("àbc")
+        );
+        assert_eq!(
+            ScalarFunc::Trim.eval(&[f_str("  a b  ")]).unwrap(),
+            f_str("a b")
+        );
+        assert_eq!(
+            ScalarFunc::LTrim.eval(&[f_str("  a  ")]).unwrap(),
+            f_str("a  ")
+        );
+        assert_eq!(
+            ScalarFunc::RTrim.eval(&[f_str("  a  ")]).unwrap(),
+            f_str("  a")
+        );
+        assert_eq!(
+            ScalarFunc::Trim
+                .eval(&[f_str("xyaxy"), f_str("yx")])
+                .unwrap(),
+            f_str("a")
+        );
+        assert_eq!(
+            ScalarFunc::Trim.eval(&[f_str("éaé"), f_str("é")]).unwrap(),
+            f_str("a")
+        );
+        assert!(ScalarFunc::Upper.eval(&[f_int(1)]).is_err());
+    }
+
+    #[test]
+    fn test_check_args() {
+        assert!(ScalarFunc::Length.check_args(&[DataType::Char(4)]).is_ok());
+        assert!(ScalarFunc::Substr
+            .check_args(&[DataType::String, DataType::BigInt, DataType::Null])
+            .is_ok());
+        assert!(ScalarFunc::Substr.check_args(&[DataType::String]).is_err());
+        assert!(ScalarFunc::Upper.check_args(&[DataType::BigInt]).is_err());
+        assert!(ScalarFunc::Substr
+            .check_args(&[DataType::String, DataType::String])
+            .is_err());
+    }
+
+    #[test]
+    fn test_date_parts() {
+        let parts = |val: Field| {
+            [ScalarFunc::Year, ScalarFunc::Month, ScalarFunc::Day]
+                .map(|func| func.eval(std::slice::from_ref(&val)).unwrap())
+        };
+        assert_eq!(parts(f_date("2024-02-29")), [2024, 2, 29].map(f_int));
+        assert_eq!(parts(f_date("1969-12-31")), [1969, 12, 31].map(f_int));
+        assert_eq!(
+            parts(f_timestamp("1969-12-31 23:59:59")),
+            [1969, 12, 31].map(f_int)
+        );
+        assert_eq!(ScalarFunc::Year.eval(&[Field::Null]).unwrap(), Field::Null);
+        assert!(ScalarFunc::Year.eval(&[f_str("2024-01-01")]).is_err());
+        assert!(ScalarFunc::Month.check_args(&[DataType::String]).is_err());
+    }
+
+    #[test]
+    fn test_codes() {
+        for (code, func) in ScalarFunc::ALL.iter().enumerate() {
+            assert_eq!(*func as usize, code);
+        }
+    }
+}
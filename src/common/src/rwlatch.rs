@@ -1,13 +1,24 @@
-use std::sync::atomic::{AtomicI16, Ordering};
+#[cfg(feature = "latch_stats")]
+use crate::metrics::latch_metrics;
+use std::sync::atomic::{AtomicI16, AtomicU16, Ordering};
 
+/// Reader-writer spin latch that prefers writers: once a thread is waiting in
+/// `exclusive`, new shared acquisitions wait (or fail, for `try_shared`) until
+/// it got the latch, so a steady stream of readers cannot starve it.
+///
+/// With the `latch_stats` feature, acquisitions and the time spent waiting for
+/// them are recorded in `metrics::latch_metrics`.
 pub struct RwLatch {
     pub cnt: AtomicI16,
+    /// Number of threads waiting in `exclusive`.
+    writers_waiting: AtomicU16,
 }
 
 impl Default for RwLatch {
     fn default() -> Self {
         RwLatch {
             cnt: AtomicI16::new(0), // Up to 2^15 readers or 1 writer
+            writers_waiting: AtomicU16::new(0),
         }
     }
 }
@@ -46,10 +57,13 @@ impl RwLatch {
     }
 
     pub fn shared(&self) {
+        #[cfg(feature = "latch_stats")]
+        let start = std::time::Instant::now();
         let mut expected: i16;
         loop {
             expected = self.cnt.load(Ordering::Acquire);
             if expected >= 0
+                && self.writers_waiting.load(Ordering::Acquire) == 0
                 && self.cnt.compare_exchange(
                     expected,
                     expected + 1,
@@ -61,13 +75,15 @@ impl RwLatch {
             }
             std::hint::spin_loop();
         }
+        #[cfg(feature = "latch_stats")]
+        latch_metrics().shared.record(start.elapsed());
     }
 
     pub fn try_shared(&self) -> bool {
         let mut expected: i16;
         loop {
             expected = self.cnt.load(Ordering::Acquire);
-            if expected < 0 {
+            if expected < 0 || self.writers_waiting.load(Ordering::Acquire) > 0 {
                 return false;
             }
             if self.cnt.compare_exchange(
@@ -77,12 +93,17 @@ impl RwLatch {
                 Ordering::Acquire,
             ) == Ok(expected)
             {
+                #[cfg(feature = "latch_stats")]
+                latch_metrics().shared.record_nanos(0);
                 return true;
             }
         }
     }
 
     pub fn exclusive(&self) {
+        #[cfg(feature = "latch_stats")]
+        let start = std::time::Instant::now();
+        self.writers_waiting.fetch_add(1, Ordering::AcqRel);
         let mut expected: i16;
         loop {
             expected = self.cnt.load(Ordering::Acquire);
@@ -96,6 +117,9 @@ impl RwLatch {
             }
             std::hint::spin_loop();
         }
+        self.writers_waiting.fetch_sub(1, Ordering::AcqRel);
+        #[cfg(feature = "latch_stats")]
+        latch_metrics().exclusive.record(start.elapsed());
     }
 
     pub fn try_exclusive(&self) -> bool {
@@ -110,6 +134,8 @@ impl RwLatch {
                 .compare_exchange(expected, -1, Ordering::AcqRel, Ordering::Acquire)
                 == Ok(expected)
             {
+                #[cfg(feature = "latch_stats")]
+                latch_metrics().exclusive.record_nanos(0);
                 return true;
             }
         }
@@ -176,6 +202,8 @@ impl RwLatch {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
     use std::{cell::UnsafeCell, thread};
 
     use super::*;
@@ -245,4 +273,60 @@ mod tests {
         });
         assert_eq!(counter.counter.read(), 0);
     }
+
+    #[test]
+    fn test_writer_not_starved_by_readers() {
+        // Readers overlap so the latch is never free unless new readers wait
+        // for the writer. If the writer starves, the readers are stopped after
+        // the deadline so that the test fails instead of hanging.
+        let counter = RwLatchProtectedCounter::default();
+        let stop = AtomicBool::new(false);
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let finished = thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        counter.rwlatch.shared();
+                        let _ = counter.counter.read();
+                        thread::sleep(Duration::from_micros(50));
+                        counter.rwlatch.release_shared();
+                    }
+                });
+            }
+            thread::sleep(Duration::from_millis(20));
+            s.spawn(|| {
+                for _ in 0..10 {
+                    counter.rwlatch.exclusive();
+                    counter.counter.increment();
+                    counter.rwlatch.release_exclusive();
+                }
+                done_tx.send(()).unwrap();
+            });
+            let res = done_rx.recv_timeout(Duration::from_secs(5));
+            stop.store(true, Ordering::Relaxed);
+            res
+        });
+        assert!(finished.is_ok(), "writer starved by readers");
+        assert_eq!(counter.counter.read(), 10);
+    }
+
+    #[test]
+    fn test_try_shared_fails_while_writer_waits() {
+        let latch = RwLatch::default();
+        latch.shared();
+        thread::scope(|s| {
+            s.spawn(|| {
+                latch.exclusive();
+                latch.release_exclusive();
+            });
+            while latch.writers_waiting.load(Ordering::Acquire) == 0 {
+                thread::yield_now();
+            }
+            assert!(!latch.try_shared());
+            latch.release_shared();
+        });
+        assert!(latch.try_shared());
+        latch.release_shared();
+        assert!(!latch.is_locked());
+    }
 }
@@ -1,7 +1,11 @@
+use crate::datatypes::OrdF64;
+use crate::physical::page_dictionary::ColumnEncoding;
 use crate::{attribute::Attribute, ids::ContainerId};
-use crate::{Constraint, DataType};
+use crate::{Constraint, DataType, FairyError, Field, Tuple};
+use crate::{MAX_COLUMNS, MAX_IDENTIFIER_LEN, MAX_TUPLE_SIZE};
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
+use std::collections::HashMap;
 
 /// Table implementation.
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -11,11 +15,67 @@ pub struct TableInfo {
     pub name: String,
     /// Table schema.
     pub schema: TableSchema,
+    /// Free-text comment set with `COMMENT ON TABLE`.
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Free-text comments set with `COMMENT ON COLUMN`, keyed by column name.
+    #[serde(default)]
+    pub column_comments: HashMap<String, String>,
+    /// Encodings set with `ALTER COLUMN .. SET (encoding = ..)`, keyed by column
+    /// name. Columns without one are plain.
+    #[serde(default)]
+    pub column_encodings: HashMap<String, ColumnEncoding>,
+    /// Whether rows are stamped with the id of the transaction that wrote them,
+    /// set with `CREATE TABLE .. WITH (txn_stamps = on)`.
+    #[serde(default)]
+    pub txn_stamps: bool,
+    /// Generation of the schema, bumped on every schema change. Cached plans are
+    /// stamped with it so they can detect that the table changed underneath them.
+    #[serde(default)]
+    pub schema_generation: u64,
+    /// Groups of columns whose joint distribution is kept in the statistics,
+    /// declared with `ANALYZE t (a, b)`.
+    #[serde(default)]
+    pub column_groups: Vec<Vec<String>>,
+    /// Columns of each unique key, by position in the schema: the primary key
+    /// and the columns or groups of columns declared UNIQUE. No two rows may
+    /// have the same values of a key.
+    #[serde(default)]
+    pub unique_keys: Vec<Vec<usize>>,
 }
 
 impl TableInfo {
     pub fn new(c_id: ContainerId, name: String, schema: TableSchema) -> Self {
-        TableInfo { c_id, name, schema }
+        let unique_keys = schema_unique_keys(&schema);
+        TableInfo {
+            c_id,
+            name,
+            schema,
+            comment: None,
+            column_comments: HashMap::new(),
+            column_encodings: HashMap::new(),
+            txn_stamps: false,
+            schema_generation: 0,
+            column_groups: Vec::new(),
+            unique_keys,
+        }
+    }
+
+    /// Returns the comment of the given column, if any.
+    pub fn get_column_comment(&self, column_name: &str) -> Option<&String> {
+        self.column_comments.get(column_name)
+    }
+
+    /// Returns the indexes of the dictionary encoded columns.
+    pub fn dictionary_columns(&self) -> Vec<usize> {
+        self.schema
+            .attributes()
+            .enumerate()
+            .filter(|(_, attr)| {
+                self.column_encodings.get(attr.name()) == Some(&ColumnEncoding::Dictionary)
+            })
+            .map(|(i, _)| i)
+            .collect()
     }
 }
 
@@ -142,10 +202,122 @@ impl TableSchema {
         Self::new(attrs)
     }
 
+    /// Returns a copy of the schema whose attributes all accept NULL, with
+    /// their constraints dropped, as for the columns an outer join pads.
+    pub fn nullable(&self) -> Self {
+        let attrs = self
+            .attributes
+            .iter()
+            .map(|attr| Attribute {
+                constraint: Constraint::None,
+                ..attr.clone()
+            })
+            .collect();
+        Self::new(attrs)
+    }
+
     /// Returns the length of the schema.
     pub fn size(&self) -> usize {
         self.attributes.len()
     }
+
+    /// Returns the serialized size in bytes of the smallest row every table with
+    /// this schema must be able to store: fixed-width columns hold values of their
+    /// full declared width and variable-length columns are empty.
+    pub fn min_tuple_size(&self) -> usize {
+        let fields = self
+            .attributes
+            .iter()
+            .map(|attr| widest_fixed_field(attr.dtype()))
+            .collect();
+        let mut tuple = Tuple::new(fields);
+        tuple.tid = u64::MAX;
+        tuple.to_bytes().len()
+    }
+
+    /// Checks that tables with this schema can be created: the number of columns,
+    /// the column names and the width of the smallest row must be within limits.
+    pub fn validate(&self) -> Result<(), FairyError> {
+        if self.attributes.len() > MAX_COLUMNS {
+            return Err(FairyError::ValidationError(format!(
+                "table has {} columns, the limit is {}",
+                self.attributes.len(),
+                MAX_COLUMNS
+            )));
+        }
+        for attr in &self.attributes {
+            validate_identifier(attr.name())?;
+        }
+        let size = self.min_tuple_size();
+        if size > MAX_TUPLE_SIZE {
+            return Err(FairyError::ValidationError(format!(
+                "minimum row size of {} bytes exceeds the page limit of {} bytes",
+                size, MAX_TUPLE_SIZE
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a table or column name is not empty and at most
+/// `MAX_IDENTIFIER_LEN` bytes long.
+pub fn validate_identifier(name: &str) -> Result<(), FairyError> {
+    if name.is_empty() {
+        return Err(FairyError::ValidationError(
+            "identifier must not be empty".to_string(),
+        ));
+    }
+    if name.len() > MAX_IDENTIFIER_LEN {
+        return Err(FairyError::ValidationError(format!(
+            "identifier {} is {} bytes long, the limit is {}",
+            name,
+            name.len(),
+            MAX_IDENTIFIER_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Unique keys the constraints of the columns of `schema` declare: the primary
+/// key, made of all its columns, and each UNIQUE column.
+fn schema_unique_keys(schema: &TableSchema) -> Vec<Vec<usize>> {
+    let mut keys = Vec::new();
+    let primary: Vec<usize> = schema
+        .attributes()
+        .enumerate()
+        .filter(|(_, attr)| attr.constraint == Constraint::PrimaryKey)
+        .map(|(i, _)| i)
+        .collect();
+    if !primary.is_empty() {
+        keys.push(primary);
+    }
+    for (i, attr) in schema.attributes().enumerate() {
+        if matches!(
+            attr.constraint,
+            Constraint::Unique | Constraint::UniqueNotNull
+        ) {
+            keys.push(vec![i]);
+        }
+    }
+    keys
+}
+
+/// Value of the given type that takes the most space among values of a fixed
+/// width, or an empty value for variable-length types.
+fn widest_fixed_field(dtype: &DataType) -> Field {
+    match dtype {
+        DataType::BigInt => Field::BigInt(i64::MIN),
+        DataType::Int => Field::Int(i32::MIN),
+        DataType::SmallInt => Field::SmallInt(i16::MIN),
+        DataType::Char(n) => Field::Char(*n, "x".repeat(*n as usize)),
+        DataType::String => Field::String(String::new()),
+        DataType::Decimal(_, s) => Field::Decimal(i64::MIN, *s),
+        DataType::Float64 => Field::Float(OrdF64(f64::MIN)),
+        DataType::Date => Field::Date(i64::MIN),
+        DataType::Timestamp => Field::Timestamp(i64::MIN),
+        DataType::Bool => Field::Bool(false),
+        DataType::Null => Field::Null,
+    }
 }
 
 impl std::fmt::Display for TableSchema {
@@ -158,3 +330,63 @@ impl std::fmt::Display for TableSchema {
         write!(f, "{}", res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `chars` columns of type `Char(255)` followed by one `Char(last)` column.
+    fn char_schema(chars: usize, last: u8) -> TableSchema {
+        let mut attrs: Vec<Attribute> = (0..chars)
+            .map(|i| Attribute::new(format!("c{}", i), DataType::Char(255)))
+            .collect();
+        attrs.push(Attribute::new("last".to_string(), DataType::Char(last)));
+        TableSchema::new(attrs)
+    }
+
+    #[test]
+    fn test_min_tuple_size_boundary() {
+        let last = (0..255)
+            .find(|&n| char_schema(15, n).min_tuple_size() == MAX_TUPLE_SIZE)
+            .expect("no schema of exactly MAX_TUPLE_SIZE");
+        assert!(char_schema(15, last).validate().is_ok());
+        let err = char_schema(15, last + 1).validate().unwrap_err();
+        assert!(
+            err.to_string().contains(&format!(
+                "minimum row size of {} bytes exceeds the page limit of {} bytes",
+                MAX_TUPLE_SIZE + 1,
+                MAX_TUPLE_SIZE
+            )),
+            "{}",
+            err
+        );
+        // A full-width row of the schema serializes to exactly the computed size.
+        let fields = (0..15)
+            .map(|_| Field::Char(255, "y".repeat(255)))
+            .chain([Field::Char(last, "y".repeat(last as usize))])
+            .collect();
+        let mut tuple = Tuple::new(fields);
+        tuple.tid = u64::MAX;
+        assert_eq!(tuple.to_bytes().len(), MAX_TUPLE_SIZE);
+    }
+
+    #[test]
+    fn test_column_and_identifier_limits() {
+        let attrs = |n: usize| -> Vec<Attribute> {
+            (0..n)
+                .map(|i| Attribute::new(format!("c{}", i), DataType::Int))
+                .collect()
+        };
+        assert!(TableSchema::new(attrs(MAX_COLUMNS)).validate().is_ok());
+        let err = TableSchema::new(attrs(MAX_COLUMNS + 1))
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("101 columns, the limit is 100"));
+
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN)).is_ok());
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN + 1)).is_err());
+        assert!(validate_identifier("").is_err());
+        let long = TableSchema::from_vecs(vec![&"a".repeat(64)], vec![DataType::Int]);
+        assert!(long.validate().is_err());
+    }
+}
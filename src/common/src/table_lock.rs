@@ -0,0 +1,200 @@
+use crate::ids::ContainerId;
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Mode in which a statement locks the tables it touches. Queries and DML take
+/// shared locks for the duration of the statement, DDL takes exclusive locks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableLockMode {
+    Shared,
+    Exclusive,
+}
+
+#[derive(Default, Debug)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+    /// Number of exclusive requests waiting. New shared requests queue behind
+    /// them so that DDL is not starved by a stream of queries.
+    waiting_writers: usize,
+}
+
+impl LockState {
+    fn is_free(&self) -> bool {
+        self.readers == 0 && !self.writer && self.waiting_writers == 0
+    }
+}
+
+/// Table-level shared/exclusive locks.
+#[derive(Default, Debug)]
+pub struct TableLocks {
+    tables: Mutex<HashMap<ContainerId, LockState>>,
+    released: Condvar,
+}
+
+/// Holds the locks taken by one statement and releases them when dropped.
+pub struct TableLockGuard<'a> {
+    locks: &'a TableLocks,
+    mode: TableLockMode,
+    held: Vec<ContainerId>,
+}
+
+impl TableLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks `c_ids` in `mode`, waiting up to `timeout` for each of them.
+    /// Tables are locked in container id order so that two statements locking
+    /// overlapping sets of tables cannot deadlock. On timeout every lock taken so
+    /// far is released and the id of the table that could not be locked is
+    /// returned.
+    pub fn lock(
+        &self,
+        c_ids: &[ContainerId],
+        mode: TableLockMode,
+        timeout: Duration,
+    ) -> Result<TableLockGuard<'_>, ContainerId> {
+        let mut c_ids = c_ids.to_vec();
+        c_ids.sort_unstable();
+        c_ids.dedup();
+        let deadline = Instant::now() + timeout;
+        let mut guard = TableLockGuard {
+            locks: self,
+            mode,
+            held: Vec::with_capacity(c_ids.len()),
+        };
+        for c_id in c_ids {
+            if !self.lock_one(c_id, mode, deadline) {
+                return Err(c_id);
+            }
+            guard.held.push(c_id);
+        }
+        Ok(guard)
+    }
+
+    fn lock_one(&self, c_id: ContainerId, mode: TableLockMode, deadline: Instant) -> bool {
+        let mut tables = self.tables.lock().unwrap();
+        if mode == TableLockMode::Exclusive {
+            tables.entry(c_id).or_default().waiting_writers += 1;
+        }
+        loop {
+            let state = tables.entry(c_id).or_default();
+            let granted = match mode {
+                TableLockMode::Shared if !state.writer && state.waiting_writers == 0 => {
+                    state.readers += 1;
+                    true
+                }
+                TableLockMode::Exclusive if !state.writer && state.readers == 0 => {
+                    state.waiting_writers -= 1;
+                    state.writer = true;
+                    true
+                }
+                _ => false,
+            };
+            if granted {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                if mode == TableLockMode::Exclusive {
+                    state.waiting_writers -= 1;
+                    // Readers queued behind this request may proceed now.
+                    self.released.notify_all();
+                }
+                if state.is_free() {
+                    tables.remove(&c_id);
+                }
+                return false;
+            }
+            tables = self
+                .released
+                .wait_timeout(tables, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn unlock(&self, c_ids: &[ContainerId], mode: TableLockMode) {
+        let mut tables = self.tables.lock().unwrap();
+        for c_id in c_ids {
+            let state = tables
+                .get_mut(c_id)
+                .expect("releasing a lock that is not held");
+            match mode {
+                TableLockMode::Shared => state.readers -= 1,
+                TableLockMode::Exclusive => state.writer = false,
+            }
+            if state.is_free() {
+                tables.remove(c_id);
+            }
+        }
+        self.released.notify_all();
+    }
+
+    /// Returns true if any statement holds or waits for a lock on the table.
+    pub fn is_locked(&self, c_id: ContainerId) -> bool {
+        self.tables.lock().unwrap().contains_key(&c_id)
+    }
+}
+
+impl Drop for TableLockGuard<'_> {
+    fn drop(&mut self) {
+        self.locks.unlock(&self.held, self.mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_shared_and_exclusive() {
+        let locks = TableLocks::new();
+        let r1 = locks.lock(&[1, 0], TableLockMode::Shared, TIMEOUT).unwrap();
+        let r2 = locks.lock(&[1], TableLockMode::Shared, TIMEOUT).unwrap();
+        let short = Duration::from_millis(10);
+        assert_eq!(
+            locks.lock(&[2, 1], TableLockMode::Exclusive, short).err(),
+            Some(1)
+        );
+        // The timed out request released table 2 again.
+        assert!(!locks.is_locked(2));
+        drop(r1);
+        drop(r2);
+        let w = locks.lock(&[1], TableLockMode::Exclusive, short).unwrap();
+        assert!(locks.lock(&[1], TableLockMode::Shared, short).is_err());
+        assert!(locks.lock(&[0], TableLockMode::Shared, short).is_ok());
+        drop(w);
+        assert!(!locks.is_locked(0));
+        assert!(!locks.is_locked(1));
+    }
+
+    #[test]
+    fn test_exclusive_waits_for_readers() {
+        let locks = Arc::new(TableLocks::new());
+        let reader = locks.lock(&[0], TableLockMode::Shared, TIMEOUT).unwrap();
+        let writer = {
+            let locks = locks.clone();
+            thread::spawn(move || {
+                let _w = locks.lock(&[0], TableLockMode::Exclusive, TIMEOUT).unwrap();
+                Instant::now()
+            })
+        };
+        // Wait until the writer queued, after which new readers have to wait too.
+        while locks
+            .lock(&[0], TableLockMode::Shared, Duration::ZERO)
+            .is_ok()
+        {
+            thread::yield_now();
+        }
+        let released = Instant::now();
+        drop(reader);
+        assert!(writer.join().unwrap() >= released);
+    }
+}
@@ -14,6 +14,10 @@ pub trait StatManagerTrait {
 
     fn register_table(&self, c_id: ContainerId, schema: TableSchema) -> Result<(), FairyError>;
 
+    /// Forgets the statistics of a dropped table, so that a table that later
+    /// gets the same container id starts without any.
+    fn remove_table(&self, c_id: ContainerId) -> Result<(), FairyError>;
+
     fn updated_record(
         &self,
         tuple: &Tuple,
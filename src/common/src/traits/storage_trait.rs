@@ -1,8 +1,51 @@
+use crate::physical::page_dictionary::PageFilter;
 use crate::{physical::config::ServerConfig, prelude::*};
 
 // TODO: What does ContainerId add as a type? If nothing, then make it u16 and make it easier for clients of
 // TODO: storage managers to use them
 
+/// Space usage summary of a single container, used to spot tables that are
+/// fragmented or would benefit from a rewrite.
+/// Page counts are estimated from a sample of pages for large containers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageReport {
+    pub container_id: ContainerId,
+    /// Number of data pages allocated to the container.
+    pub num_pages: usize,
+    /// Number of data pages that were actually read to build the report.
+    pub sampled_pages: usize,
+    /// Number of pages that are less than 25/50/75 percent utilized.
+    pub pages_under_25: usize,
+    pub pages_under_50: usize,
+    pub pages_under_75: usize,
+    /// Number of live records.
+    pub num_records: usize,
+    /// Average size in bytes of a live record.
+    pub avg_record_size: usize,
+    /// Number of slots whose record has been deleted and not reused.
+    pub dead_slots: usize,
+    /// Bytes on data pages that do not hold live records or their metadata.
+    pub wasted_bytes: usize,
+    /// Number of data pages without live records, e.g. left by compaction.
+    pub empty_pages: usize,
+}
+
+/// What one round of online compaction of a container did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionOutcome {
+    /// Pages read by the round, whether or not they were changed.
+    pub pages_visited: usize,
+    /// Pages whose values were moved together to close holes or whose trailing
+    /// free slots were dropped.
+    pub pages_compacted: usize,
+    /// Values moved to another page. Their value ids changed.
+    pub values_relocated: usize,
+    /// Pages left without values by relocation.
+    pub pages_emptied: usize,
+    /// Page the next round should start at.
+    pub next_page: PageId,
+}
+
 /// The trait for a storage manager in FairyDB.
 /// A StorageManager should impl Drop also so a storage manager can clean up on shut down and
 /// for testing storage managers to remove any state.
@@ -79,10 +122,32 @@ pub trait StorageTrait {
 
     fn create_table(&self, container_id: ContainerId) -> Result<(), FairyError>;
 
+    /// Returns whether a container with the given id exists.
+    fn container_exists(&self, container_id: ContainerId) -> bool;
+
+    /// Returns whether a new container may not take the given id: a container
+    /// with the id exists, or a removed one is not fully released yet.
+    fn container_id_in_use(&self, container_id: ContainerId) -> bool {
+        self.container_exists(container_id)
+    }
+
+    /// Deletes all the values of a container, which stays in place, and returns
+    /// how many there were. Scans that are running see some or none of the
+    /// values deleted.
+    fn truncate_container(&self, container_id: ContainerId) -> Result<usize, FairyError>;
+
     /// Remove the container and all stored values in the container.
-    /// If the container is persisted remove the underlying files
+    /// If the container is persisted the underlying files are removed by
+    /// `release_removed_containers`.
     fn remove_container(&self, container_id: ContainerId) -> Result<(), FairyError>;
 
+    /// Frees the memory and files of removed containers, after which their ids
+    /// are no longer in use. Containers still read by open iterators are kept
+    /// for a later call.
+    fn release_removed_containers(&self) -> Result<(), FairyError> {
+        Ok(())
+    }
+
     /// Get an iterator that returns all valid records
     fn get_iterator(
         &self,
@@ -91,6 +156,19 @@ pub trait StorageTrait {
         perm: Permissions,
     ) -> Self::ValIterator;
 
+    /// Like `get_iterator`, but pages whose dictionary shows that none of their
+    /// rows satisfies `filter` may be skipped without being read. Storage
+    /// managers without page dictionaries return every value.
+    fn get_filtered_iterator(
+        &self,
+        container_id: ContainerId,
+        tid: TransactionId,
+        perm: Permissions,
+        _filter: PageFilter,
+    ) -> Self::ValIterator {
+        self.get_iterator(container_id, tid, perm)
+    }
+
     /// Get an iterator starting from a particular value id
     fn get_iterator_from(
         &self,
@@ -115,6 +193,52 @@ pub trait StorageTrait {
     /// for testing. clear anything cache in memory for performance
     fn clear_cache(&self);
 
+    /// Report on the space usage of a container. Only storage managers that
+    /// organize values in pages support this.
+    fn storage_report(&self, _container_id: ContainerId) -> Result<StorageReport, FairyError> {
+        Err(FairyError::InvalidOperation)
+    }
+
+    /// Returns the number of pages of a container, including the pages that
+    /// are not data pages. Storage managers that do not organize values in
+    /// pages return None.
+    fn num_pages(&self, _container_id: ContainerId) -> Option<PageId> {
+        None
+    }
+
+    /// Compacts up to `max_pages` data pages of a container, starting at
+    /// `start_page` (wrapping around to the first page past the end). Holes
+    /// within pages are closed and the values of nearly empty pages are moved
+    /// to other pages of the round. Moved values get new value ids, so the
+    /// caller must make sure that nobody holds value ids of the container, e.g.
+    /// by locking its table exclusively. Only storage managers that organize
+    /// values in pages support this.
+    fn compact(
+        &self,
+        _container_id: ContainerId,
+        _start_page: PageId,
+        _max_pages: usize,
+    ) -> Result<CompactionOutcome, FairyError> {
+        Err(FairyError::InvalidOperation)
+    }
+
+    /// Stores the values of `columns` of the tuples of a container with a
+    /// dictionary per page from now on. Values already stored keep their form.
+    /// Storage managers that do not organize values in pages ignore this.
+    fn set_dictionary_columns(
+        &self,
+        _container_id: ContainerId,
+        _columns: Vec<usize>,
+    ) -> Result<(), FairyError> {
+        Ok(())
+    }
+
+    /// Called when transaction `tid` commits. Returns once its writes are as
+    /// durable as the storage manager's durability level requires.
+    fn commit(&self, _tid: TransactionId) -> Result<(), FairyError> {
+        Ok(())
+    }
+
     /// Call shutdown to persist state or clean up. Will be called by drop in addition to explicitly.
     /// Shutdown also needs to persist the state of the storage trait to disk, allowing the storage
     /// to retain state after the db is rerun.
@@ -126,5 +250,5 @@ pub trait StorageTrait {
     ///
     /// JSON serialization should be sufficient for this. The serialized data can be written within the
     /// storage path passed in during instantiation.
-    fn shutdown(&self);
+    fn shutdown(&self) -> Result<(), FairyError>;
 }
@@ -1,4 +1,7 @@
-use crate::{ids::TidType, ids::ValueId, ConversionError, Field};
+use crate::datatypes::DisplayFormat;
+use crate::query::query_result::csv_line;
+use crate::{ids::TidType, ids::ValueId, ConversionError, FairyError, Field};
+use std::ops::Range;
 
 /// Tuple type.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -122,12 +125,159 @@ impl Tuple {
         serde_cbor::from_slice(bytes).unwrap()
     }
 
+    /// Like `from_bytes`, but fails instead of panicking if the bytes are not a
+    /// serialized tuple.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, FairyError> {
+        serde_cbor::from_slice(bytes).map_err(|e| FairyError::SerializationError(e.to_string()))
+    }
+
     pub fn to_csv(&self) -> String {
-        let mut res = Vec::new();
-        for field in &self.field_vals {
-            res.push(field.to_string());
+        self.to_csv_with(&DisplayFormat::DEFAULT)
+    }
+
+    /// The fields as a CSV line, rendered in `format`.
+    pub fn to_csv_with(&self, format: &DisplayFormat) -> String {
+        csv_line(
+            self.field_vals
+                .iter()
+                .map(|field| field.render(format).into()),
+        )
+    }
+}
+
+/// A serialized tuple whose fields are found without being decoded, so that
+/// only the ones that are needed are decoded.
+pub struct RawTuple<'a> {
+    bytes: &'a [u8],
+    tid: TidType,
+    /// Range of the bytes of each field.
+    fields: Vec<Range<usize>>,
+}
+
+impl<'a> RawTuple<'a> {
+    /// Finds the fields of the serialized tuple `bytes`, skipping over each by
+    /// the length in its header. Returns None if the bytes are not a
+    /// serialized tuple.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let (major, entries, mut pos) = cbor_header(bytes, 0)?;
+        if major != CBOR_MAP {
+            return None;
+        }
+        let (mut tid, mut fields) = (None, None);
+        for _ in 0..entries {
+            let (key_major, _, key_start) = cbor_header(bytes, pos)?;
+            pos = cbor_skip(bytes, pos)?;
+            let key = match key_major {
+                CBOR_TEXT => bytes.get(key_start..pos)?,
+                _ => &[],
+            };
+            match key {
+                b"tid" => {
+                    let (major, value, end) = cbor_header(bytes, pos)?;
+                    if major != CBOR_UINT {
+                        return None;
+                    }
+                    tid = Some(value as TidType);
+                    pos = end;
+                }
+                b"field_vals" => {
+                    let (major, len, mut start) = cbor_header(bytes, pos)?;
+                    if major != CBOR_ARRAY {
+                        return None;
+                    }
+                    let mut ranges = Vec::new();
+                    for _ in 0..len {
+                        let end = cbor_skip(bytes, start)?;
+                        ranges.push(start..end);
+                        start = end;
+                    }
+                    fields = Some(ranges);
+                    pos = start;
+                }
+                _ => pos = cbor_skip(bytes, pos)?,
+            }
+        }
+        Some(RawTuple {
+            bytes,
+            tid: tid?,
+            fields: fields?,
+        })
+    }
+
+    pub fn tid(&self) -> TidType {
+        self.tid
+    }
+
+    /// Number of fields of the tuple.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Decodes field `i`. Tuples written before a column was added have fewer
+    /// fields, the missing ones are NULL.
+    pub fn field(&self, i: usize) -> Result<Field, FairyError> {
+        match self.fields.get(i) {
+            Some(range) => serde_cbor::from_slice(&self.bytes[range.clone()])
+                .map_err(|e| FairyError::SerializationError(format!("field {}: {}", i, e))),
+            None => Ok(Field::Null),
+        }
+    }
+}
+
+const CBOR_UINT: u8 = 0;
+const CBOR_TEXT: u8 = 3;
+const CBOR_ARRAY: u8 = 4;
+const CBOR_MAP: u8 = 5;
+
+/// Reads the header of the CBOR item at `pos`: its major type, its argument
+/// (a length or a value) and the position after the header. Returns None for
+/// indefinite lengths, which serialized tuples do not use.
+fn cbor_header(bytes: &[u8], pos: usize) -> Option<(u8, u64, usize)> {
+    let first = *bytes.get(pos)?;
+    let (major, info) = (first >> 5, first & 0x1f);
+    let len = match info {
+        0..=23 => return Some((major, info as u64, pos + 1)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let arg = bytes
+        .get(pos + 1..pos + 1 + len)?
+        .iter()
+        .fold(0u64, |acc, b| acc << 8 | *b as u64);
+    Some((major, arg, pos + 1 + len))
+}
+
+/// Position after the CBOR item at `pos`.
+fn cbor_skip(bytes: &[u8], pos: usize) -> Option<usize> {
+    let (major, arg, mut pos) = cbor_header(bytes, pos)?;
+    match major {
+        // Integers, floats and simple values are all in the header.
+        CBOR_UINT | 1 | 7 => Some(pos),
+        // Byte and text strings.
+        2 | CBOR_TEXT => pos
+            .checked_add(usize::try_from(arg).ok()?)
+            .filter(|end| *end <= bytes.len()),
+        CBOR_ARRAY | CBOR_MAP => {
+            let items = if major == CBOR_MAP {
+                arg.checked_mul(2)?
+            } else {
+                arg
+            };
+            for _ in 0..items {
+                pos = cbor_skip(bytes, pos)?;
+            }
+            Some(pos)
         }
-        res.join(",")
+        // A tag and the item it applies to.
+        6 => cbor_skip(bytes, pos),
+        _ => None,
     }
 }
 
@@ -164,3 +314,46 @@ impl ConvertedResult {
         self.unconverted.clear();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_raw_tuple() {
+        let fields = vec![
+            Field::BigInt(-7),
+            Field::String("a longer string value".to_string()),
+            Field::Null,
+            Field::Char(3, "abc".to_string()),
+            Field::Int(i32::MAX),
+            Field::Decimal(12345, 2),
+            Field::Bool(true),
+        ];
+        let mut tuple = Tuple::new(fields.clone());
+        tuple.tid = TidType::MAX;
+        let bytes = tuple.to_bytes();
+        let raw = RawTuple::new(&bytes).unwrap();
+        assert_eq!(raw.tid(), TidType::MAX);
+        assert_eq!(raw.len(), fields.len());
+        for (i, field) in fields.iter().enumerate() {
+            assert_eq!(&raw.field(i).unwrap(), field);
+        }
+        assert_eq!(raw.field(fields.len()).unwrap(), Field::Null);
+        assert!(RawTuple::new(&bytes[..bytes.len() - 1]).is_none());
+        assert!(RawTuple::new(b"not a tuple").is_none());
+
+        // Well-formed, but the field is not a Field.
+        #[derive(Serialize)]
+        struct NotATuple {
+            tid: TidType,
+            field_vals: Vec<u8>,
+        }
+        let bytes = serde_cbor::to_vec(&NotATuple {
+            tid: 1,
+            field_vals: vec![5],
+        })
+        .unwrap();
+        assert!(RawTuple::new(&bytes).unwrap().field(0).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::ids::{PageId, TidType, TransactionId};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Transactions writing to tables created `WITH (txn_stamps = on)`, whose rows
+/// carry the id of the transaction that created them. Snapshots taken from it
+/// tell which of those rows were committed when the snapshot was taken.
+#[derive(Default, Debug)]
+pub struct StampedWrites {
+    state: Mutex<WriteState>,
+}
+
+#[derive(Default, Debug)]
+struct WriteState {
+    /// Transactions that write stamped rows and have not ended yet.
+    in_progress: HashSet<TidType>,
+    /// Transactions that began writing after each live snapshot was taken.
+    late: Vec<Weak<Mutex<HashSet<TidType>>>>,
+}
+
+/// A write of stamped rows by one transaction. It counts as committed once
+/// `commit` is called. A write dropped before that failed, but there is no
+/// rollback: its rows stay in the table and snapshots taken after it ended see
+/// them like plain scans do. Snapshots taken while it was running never do.
+pub struct StampedWrite<'a> {
+    writes: &'a StampedWrites,
+    tid: TidType,
+    committed: bool,
+}
+
+/// The stamped rows of a table that were committed at some point.
+#[derive(Debug)]
+pub struct TxnSnapshot {
+    /// Transactions that had not committed when the snapshot was taken.
+    hidden: HashSet<TidType>,
+    /// Transactions that began writing after the snapshot was taken.
+    late: Arc<Mutex<HashSet<TidType>>>,
+    /// Number of pages of the table when the snapshot was taken. Later pages
+    /// only hold rows written after it.
+    num_pages: Option<PageId>,
+}
+
+impl StampedWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `tid` as writing stamped rows. Must be called before the first
+    /// row is written.
+    pub fn begin(&self, tid: TransactionId) -> StampedWrite<'_> {
+        let mut state = self.state.lock().unwrap();
+        if state.in_progress.insert(tid.id()) {
+            state.late.retain(|late| match late.upgrade() {
+                Some(late) => {
+                    late.lock().unwrap().insert(tid.id());
+                    true
+                }
+                None => false,
+            });
+        }
+        StampedWrite {
+            writes: self,
+            tid: tid.id(),
+            committed: false,
+        }
+    }
+
+    /// Takes a snapshot of the committed stamped rows of a table. `num_pages`
+    /// returns the number of pages of the table. It is called once the writers
+    /// are noted, so every row of a transaction committed before is on one of
+    /// those pages.
+    pub fn snapshot(&self, num_pages: impl FnOnce() -> Option<PageId>) -> TxnSnapshot {
+        let late = Arc::new(Mutex::new(HashSet::new()));
+        let hidden = {
+            let mut state = self.state.lock().unwrap();
+            state.late.push(Arc::downgrade(&late));
+            state.in_progress.clone()
+        };
+        TxnSnapshot {
+            hidden,
+            late,
+            num_pages: num_pages(),
+        }
+    }
+
+    fn end(&self, tid: TidType, committed: bool) {
+        if !committed {
+            debug!("Stamped write of transaction {} failed", tid);
+        }
+        self.state.lock().unwrap().in_progress.remove(&tid);
+    }
+}
+
+impl StampedWrite<'_> {
+    /// Marks the rows of the transaction as committed.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for StampedWrite<'_> {
+    fn drop(&mut self) {
+        self.writes.end(self.tid, self.committed);
+    }
+}
+
+impl TxnSnapshot {
+    /// Whether a row stamped with `tid` was committed when the snapshot was taken.
+    pub fn is_visible(&self, tid: TidType) -> bool {
+        !self.hidden.contains(&tid) && !self.late.lock().unwrap().contains(&tid)
+    }
+
+    /// Whether rows on page `page_id` may have been written before the snapshot.
+    pub fn covers_page(&self, page_id: Option<PageId>) -> bool {
+        match (self.num_pages, page_id) {
+            (Some(num_pages), Some(page_id)) => page_id < num_pages,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_visibility() {
+        let writes = StampedWrites::new();
+        let (t1, t2, t3, t4) = (
+            TransactionId::new(),
+            TransactionId::new(),
+            TransactionId::new(),
+            TransactionId::new(),
+        );
+        writes.begin(t1).commit();
+        let running = writes.begin(t2);
+        drop(writes.begin(t3));
+
+        let snapshot = writes.snapshot(|| Some(3));
+        // Committing after the snapshot does not make the rows visible.
+        running.commit();
+        let late = writes.begin(t4);
+        assert!(snapshot.is_visible(t1.id()));
+        assert!(!snapshot.is_visible(t2.id()));
+        // The failed write ended before the snapshot and its rows stay.
+        assert!(snapshot.is_visible(t3.id()));
+        assert!(!snapshot.is_visible(t4.id()));
+        // Rows written before stamps were turned on have none.
+        assert!(snapshot.is_visible(0));
+        assert!(snapshot.covers_page(Some(2)));
+        assert!(!snapshot.covers_page(Some(3)));
+
+        late.commit();
+        let later = writes.snapshot(|| None);
+        assert!(later.is_visible(t2.id()));
+        assert!(later.is_visible(t4.id()));
+        assert!(later.covers_page(Some(100)));
+    }
+
+    #[test]
+    fn test_dropped_snapshots_are_forgotten() {
+        let writes = StampedWrites::new();
+        drop(writes.snapshot(|| None));
+        let _snapshot = writes.snapshot(|| None);
+        writes.begin(TransactionId::new()).commit();
+        assert_eq!(writes.state.lock().unwrap().late.len(), 1);
+    }
+
+    #[test]
+    fn test_failed_writes_are_forgotten() {
+        let writes = StampedWrites::new();
+        let failing = TransactionId::new();
+        let write = writes.begin(failing);
+        let during = writes.snapshot(|| None);
+        drop(write);
+        let after = writes.snapshot(|| None);
+        // Only the snapshot taken while the write was running skips its rows.
+        assert!(!during.is_visible(failing.id()));
+        assert!(after.is_visible(failing.id()));
+        for _ in 0..100 {
+            drop(writes.begin(TransactionId::new()));
+        }
+        assert!(writes.state.lock().unwrap().in_progress.is_empty());
+    }
+}
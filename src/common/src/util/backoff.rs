@@ -0,0 +1,137 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Number of doubling spin rounds before a spinning backoff starts yielding the
+/// thread.
+const SPIN_LIMIT: u32 = 6;
+
+/// How a backoff waits between two attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    /// Spin with an exponentially growing number of `spin_loop` hints for the
+    /// first rounds, then yield the thread every round.
+    Spin,
+    /// Sleep for a random time between half the delay and the full delay, with
+    /// the delay doubling every round up to `max_delay`.
+    Sleep {
+        delay: Duration,
+        max_delay: Duration,
+    },
+}
+
+/// Exponential backoff between the attempts of an operation that failed for a
+/// transient reason, e.g. a latch that could not be granted or a transaction
+/// that was aborted. The random jitter of the sleeping backoff keeps waiting
+/// threads from retrying in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    wait: Wait,
+    step: u32,
+}
+
+impl Backoff {
+    /// A backoff for waits much shorter than a time slice, such as waiting for
+    /// a latch held for a few instructions.
+    pub fn spinning() -> Self {
+        Backoff {
+            wait: Wait::Spin,
+            step: 0,
+        }
+    }
+
+    /// A backoff that sleeps, starting from `initial_delay` and doubling up to
+    /// `max_delay`.
+    pub fn sleeping(initial_delay: Duration, max_delay: Duration) -> Self {
+        Backoff {
+            wait: Wait::Sleep {
+                delay: initial_delay,
+                max_delay,
+            },
+            step: 0,
+        }
+    }
+
+    /// Wait for one round.
+    pub fn snooze(&mut self) {
+        match &mut self.wait {
+            Wait::Spin if self.step <= SPIN_LIMIT => {
+                for _ in 0..1 << self.step {
+                    std::hint::spin_loop();
+                }
+            }
+            Wait::Spin => std::thread::yield_now(),
+            Wait::Sleep { delay, max_delay } => {
+                let nanos = delay.as_nanos() as u64;
+                let jitter = rand::rng().random_range(0..=nanos / 2);
+                std::thread::sleep(Duration::from_nanos(nanos - jitter));
+                *delay = (*delay * 2).min(*max_delay);
+            }
+        }
+        self.step += 1;
+    }
+
+    /// Calls `attempt` with the number of retries so far until it succeeds or
+    /// `should_retry` returns false for its error and the number of retries,
+    /// waiting a round between two attempts. Returns the last result and the
+    /// number of retries it took.
+    pub fn retry<T, E>(
+        mut self,
+        mut attempt: impl FnMut(u32) -> Result<T, E>,
+        mut should_retry: impl FnMut(&E, u32) -> bool,
+    ) -> (Result<T, E>, u32) {
+        let mut retries = 0;
+        loop {
+            match attempt(retries) {
+                Err(e) if should_retry(&e, retries) => {}
+                res => return (res, retries),
+            }
+            self.snooze();
+            retries += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_until_success() {
+        let (res, retries) = Backoff::spinning().retry(
+            |retries| if retries == 2 { Ok(retries) } else { Err(()) },
+            |_, _| true,
+        );
+        assert_eq!(res, Ok(2));
+        assert_eq!(retries, 2);
+    }
+
+    #[test]
+    fn test_retry_gives_up() {
+        let mut attempts = 0;
+        let (res, retries): (Result<(), _>, _) = Backoff::spinning().retry(
+            |_| {
+                attempts += 1;
+                Err(attempts)
+            },
+            |_, retries| retries < 10,
+        );
+        assert_eq!(res, Err(11));
+        assert_eq!(retries, 10);
+    }
+
+    #[test]
+    fn test_sleeping_delay_is_capped() {
+        let max_delay = Duration::from_micros(4);
+        let mut backoff = Backoff::sleeping(Duration::from_micros(1), max_delay);
+        for _ in 0..5 {
+            backoff.snooze();
+        }
+        assert_eq!(
+            backoff.wait,
+            Wait::Sleep {
+                delay: max_delay,
+                max_delay
+            }
+        );
+    }
+}
@@ -8,6 +8,8 @@ use rand::seq::IndexedRandom;
 use rand::{rng, Rng, SeedableRng};
 use std::env;
 
+pub use super::table_gen::{load_table, ColumnDist, TableGen};
+
 pub fn get_rng() -> SmallRng {
     match env::var("FAIRY_SEED") {
         Ok(seed_str) => match seed_str.parse::<u64>() {
@@ -29,6 +31,21 @@ pub fn get_rng() -> SmallRng {
     }
 }
 
+/// Seed and step count for the randomized harnesses. The seed is taken from
+/// `FAIRY_SEED` when set, so a failing run can be replayed, and the number of
+/// steps from `FAIRY_FUZZ_STEPS`, defaulting to `default_steps`.
+pub fn get_fuzz_config(default_steps: usize) -> (u64, usize) {
+    let seed = env::var("FAIRY_SEED")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| rng().random::<u64>());
+    let steps = env::var("FAIRY_FUZZ_STEPS")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(default_steps);
+    (seed, steps)
+}
+
 pub fn init() {
     // To change the log level for tests change the filter_level
     let _ = env_logger::builder()
@@ -80,6 +97,7 @@ pub fn gen_random_tuples_fixed(
         name: String::from("id"),
         dtype: DataType::BigInt,
         constraint: crate::Constraint::PrimaryKey,
+        default: None,
     });
 
     let fixed_dtypes = [
@@ -92,6 +110,7 @@ pub fn gen_random_tuples_fixed(
             name: format!("ia{}", i),
             dtype: fixed_dtypes.choose(rng).unwrap().clone(),
             constraint: crate::Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -111,6 +130,7 @@ pub fn gen_random_tuples_var_and_null(
         name: String::from("id"),
         dtype: DataType::BigInt,
         constraint: crate::Constraint::PrimaryKey,
+        default: None,
     });
 
     let fixed_dtypes = [
@@ -124,6 +144,7 @@ pub fn gen_random_tuples_var_and_null(
             name: format!("ia{}", i),
             dtype: fixed_dtypes.choose(rng).unwrap().clone(),
             constraint: crate::Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -233,6 +254,7 @@ pub fn gen_table_for_test_tuples(c_id: ContainerId, table_name: String) -> Table
         name: String::from("id"),
         dtype: DataType::BigInt,
         constraint: crate::Constraint::PrimaryKey,
+        default: None,
     };
     attributes.push(pk_attr);
 
@@ -241,6 +263,7 @@ pub fn gen_table_for_test_tuples(c_id: ContainerId, table_name: String) -> Table
             name: format!("ia{}", n),
             dtype: DataType::BigInt,
             constraint: crate::Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -249,6 +272,7 @@ pub fn gen_table_for_test_tuples(c_id: ContainerId, table_name: String) -> Table
             name: format!("sa{}", n),
             dtype: DataType::String,
             constraint: crate::Constraint::None,
+            default: None,
         };
         attributes.push(attr);
     }
@@ -1,9 +1,11 @@
 use crate::table::TableSchema;
 use crate::FairyError;
 use crate::{tuple::Tuple, Field};
-use csv::{Reader, StringRecord};
+use csv::{ByteRecord, Reader};
+use std::borrow::Cow;
 use std::io::Read;
 use std::result::Result;
+use std::str::FromStr;
 
 pub trait DataReader {
     /// Read and return the next record from the data source.
@@ -11,9 +13,37 @@ pub trait DataReader {
     fn read_next(&mut self) -> Result<Option<Tuple>, FairyError>;
 }
 
+/// What to do with string data that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Reject the row, naming its line.
+    #[default]
+    Strict,
+    /// Replace every invalid sequence with U+FFFD.
+    Lossy,
+}
+
+impl FromStr for Utf8Policy {
+    type Err = FairyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Utf8Policy::Strict),
+            "lossy" => Ok(Utf8Policy::Lossy),
+            _ => Err(FairyError::ValidationError(format!(
+                "Unknown UTF-8 policy {}, expected strict or lossy",
+                s
+            ))),
+        }
+    }
+}
+
 pub struct CsvReader<R: Read> {
     schema: TableSchema,
     rdr: Reader<R>,
+    utf8: Utf8Policy,
+    /// Largest field, in bytes, a record may have.
+    max_field_size: usize,
 }
 
 impl<R: Read> CsvReader<R> {
@@ -31,28 +61,77 @@ impl<R: Read> CsvReader<R> {
         Ok(CsvReader {
             schema: schema.clone(),
             rdr,
+            utf8: Utf8Policy::default(),
+            max_field_size: usize::MAX,
         })
     }
+
+    pub fn with_utf8_policy(mut self, utf8: Utf8Policy) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    /// Rejects records with a field larger than `max_field_size` bytes,
+    /// naming their line.
+    pub fn with_max_field_size(mut self, max_field_size: usize) -> Self {
+        self.max_field_size = max_field_size;
+        self
+    }
 }
 
 impl<R: Read> DataReader for CsvReader<R> {
     fn read_next(&mut self) -> Result<Option<Tuple>, FairyError> {
-        match self.rdr.records().next() {
-            Some(Ok(record)) => {
-                let tuple = convert_to_tuple(&record, &self.schema)?;
+        let mut record = ByteRecord::new();
+        match self.rdr.read_byte_record(&mut record) {
+            Ok(true) => {
+                let largest = record.iter().map(<[u8]>::len).max().unwrap_or(0);
+                if largest > self.max_field_size {
+                    return Err(FairyError::FieldTooLarge {
+                        size: largest,
+                        limit: self.max_field_size,
+                        line: Some(record.position().map_or(0, |p| p.line() as usize)),
+                    });
+                }
+                let tuple = convert_to_tuple(&record, &self.schema, self.utf8)?;
                 Ok(Some(tuple))
             }
-            Some(Err(e)) => Err(FairyError::IOError(e.to_string())),
-            None => Ok(None),
+            Ok(false) => Ok(None),
+            Err(e) => Err(FairyError::IOError(e.to_string())),
         }
     }
 }
 
 // Helper function to convert a CSV record to a Tuple
-fn convert_to_tuple(record: &StringRecord, schema: &TableSchema) -> Result<Tuple, FairyError> {
+fn convert_to_tuple(
+    record: &ByteRecord,
+    schema: &TableSchema,
+    utf8: Utf8Policy,
+) -> Result<Tuple, FairyError> {
     let mut fields = Vec::new();
-    for (str_field, attr) in record.iter().zip(schema.attributes()) {
-        fields.push(Field::from_str(str_field, attr)?)
+    for (i, (bytes, attr)) in record.iter().zip(schema.attributes()).enumerate() {
+        let str_field = match (std::str::from_utf8(bytes), utf8) {
+            (Ok(s), _) => Cow::Borrowed(s),
+            (Err(_), Utf8Policy::Lossy) => String::from_utf8_lossy(bytes),
+            (Err(e), Utf8Policy::Strict) => {
+                let line = record.position().map_or(0, |p| p.line());
+                return Err(FairyError::ValidationError(format!(
+                    "Invalid UTF-8 on line {} in field {}: {}",
+                    line,
+                    i + 1,
+                    e
+                )));
+            }
+        };
+        let field = Field::from_str(&str_field, attr)?;
+        if field == Field::Null && !attr.is_nullable() {
+            let line = record.position().map_or(0, |p| p.line());
+            return Err(FairyError::ExecutionError(format!(
+                "Null value in column {} on line {} violates the not-null constraint",
+                attr.name(),
+                line
+            )));
+        }
+        fields.push(field)
     }
     Ok(Tuple::new(fields))
 }
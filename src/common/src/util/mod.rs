@@ -1,3 +1,6 @@
+pub mod backoff;
 pub mod common_test_util;
 pub mod data_reader;
+pub mod suggest;
+pub mod table_gen;
 pub mod vec_compare;
@@ -0,0 +1,62 @@
+/// Number of single-character insertions, deletions and substitutions needed
+/// to turn `a` into `b`, ignoring case.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Returns the candidate closest to `name`, if one is close enough to be a
+/// plausible misspelling of it. Ties go to the candidate that comes first in
+/// sorted order so that suggestions are deterministic.
+pub fn closest_match<'a, I>(name: &str, candidates: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|c| !c.eq_ignore_ascii_case(name))
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min()
+        .map(|(_, c)| c.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("order_id", "ordr_id"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("Name", "name"), 0);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let names = ["order_id", "orders.order_id", "customer", "amount"];
+        assert_eq!(
+            closest_match("ordr_id", names),
+            Some("order_id".to_string())
+        );
+        assert_eq!(
+            closest_match("customr", names),
+            Some("customer".to_string())
+        );
+        assert_eq!(closest_match("zzz", names), None);
+    }
+}
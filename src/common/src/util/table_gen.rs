@@ -0,0 +1,444 @@
+use crate::catalog::Catalog;
+use crate::error::ObjectKind;
+use crate::ids::TransactionId;
+use crate::table::TableInfo;
+use crate::traits::stat_manager_trait::StatManagerTrait;
+use crate::traits::storage_trait::StorageTrait;
+use crate::{Attribute, Constraint, DataType, FairyError, Field, TableSchema, Tuple};
+use rand::distr::Alphanumeric;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// How the values of a generated column are drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnDist {
+    /// `start`, `start + 1`, ... in row order, as for keys.
+    Sequential { start: i64 },
+    /// Integers drawn uniformly from `min..=max`.
+    Uniform { min: i64, max: i64 },
+    /// Integers from `min..min + cardinality`, where the k-th value is drawn
+    /// with a probability proportional to `1 / k^skew`. `min` is the most
+    /// frequent value and a skew of 0 is uniform.
+    Zipf {
+        min: i64,
+        cardinality: u64,
+        skew: f64,
+    },
+    /// Strings drawn uniformly from a pool of `cardinality` random alphanumeric
+    /// strings of `min_len..=max_len` bytes. `SmallString` stores strings of
+    /// more than 31 bytes out of line.
+    Strings {
+        cardinality: usize,
+        min_len: usize,
+        max_len: usize,
+    },
+    /// Dates drawn uniformly from `from..=to`, written as `YYYY-MM-DD`.
+    /// Timestamps also get a random time of day.
+    Dates {
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+impl ColumnDist {
+    /// The distribution of a column that none was given for.
+    fn default_for(attr: &Attribute) -> Self {
+        match attr.dtype() {
+            _ if attr.constraint == Constraint::PrimaryKey => ColumnDist::Sequential { start: 0 },
+            DataType::Bool => ColumnDist::Uniform { min: 0, max: 1 },
+            DataType::String => ColumnDist::Strings {
+                cardinality: 100,
+                min_len: 1,
+                max_len: 20,
+            },
+            DataType::Char(n) => ColumnDist::Strings {
+                cardinality: 100,
+                min_len: 1,
+                max_len: *n as usize,
+            },
+            DataType::Date | DataType::Timestamp => ColumnDist::Dates {
+                from: "2000-01-01",
+                to: "2029-12-31",
+            },
+            _ => ColumnDist::Uniform { min: 0, max: 999 },
+        }
+    }
+}
+
+/// Draws the values of one column.
+enum Sampler {
+    Sequential(i64),
+    Uniform(i64, i64),
+    /// The first value and the cumulative weights of the values.
+    Zipf(i64, Vec<f64>),
+    Pool(Vec<String>),
+    Dates(i64, i64),
+}
+
+impl Sampler {
+    fn new(dist: &ColumnDist, rng: &mut SmallRng) -> Self {
+        match dist {
+            ColumnDist::Sequential { start } => Sampler::Sequential(*start),
+            ColumnDist::Uniform { min, max } => {
+                assert!(min <= max, "Empty range {}..={}", min, max);
+                Sampler::Uniform(*min, *max)
+            }
+            ColumnDist::Zipf {
+                min,
+                cardinality,
+                skew,
+            } => {
+                assert!(*cardinality > 0, "Zipf distribution without values");
+                let mut total = 0.0;
+                let cdf = (1..=*cardinality)
+                    .map(|k| {
+                        total += 1.0 / (k as f64).powf(*skew);
+                        total
+                    })
+                    .collect();
+                Sampler::Zipf(*min, cdf)
+            }
+            ColumnDist::Strings {
+                cardinality,
+                min_len,
+                max_len,
+            } => {
+                assert!(min_len <= max_len, "Empty range {}..={}", min_len, max_len);
+                let mut pool = std::collections::HashSet::new();
+                let mut attempts = 0;
+                while pool.len() < *cardinality {
+                    let len = rng.random_range(*min_len..=*max_len);
+                    let s: String = (&mut *rng)
+                        .sample_iter(Alphanumeric)
+                        .take(len)
+                        .map(char::from)
+                        .collect();
+                    pool.insert(s);
+                    attempts += 1;
+                    assert!(
+                        attempts < cardinality * 100,
+                        "Cannot make {} distinct strings of {}..={} bytes",
+                        cardinality,
+                        min_len,
+                        max_len
+                    );
+                }
+                // Sorted, so the pool only depends on the seed.
+                let mut pool: Vec<String> = pool.into_iter().collect();
+                pool.sort();
+                Sampler::Pool(pool)
+            }
+            ColumnDist::Dates { from, to } => {
+                let days = |date: &str| match Field::from_str_to_date(date) {
+                    Ok(Field::Date(days)) => days,
+                    _ => panic!("Invalid date {}", date),
+                };
+                let (from, to) = (days(from), days(to));
+                assert!(from <= to, "Empty range {}..={}", from, to);
+                Sampler::Dates(from, to)
+            }
+        }
+    }
+
+    fn sample(&self, row: usize, dtype: &DataType, rng: &mut SmallRng) -> Field {
+        let int = match self {
+            Sampler::Sequential(start) => start + row as i64,
+            Sampler::Uniform(min, max) => rng.random_range(*min..=*max),
+            Sampler::Zipf(min, cdf) => {
+                let u = rng.random::<f64>() * cdf[cdf.len() - 1];
+                let k = cdf.partition_point(|&c| c <= u).min(cdf.len() - 1);
+                min + k as i64
+            }
+            Sampler::Pool(pool) => {
+                let s = pool[rng.random_range(0..pool.len())].clone();
+                return cast(Field::String(s), dtype);
+            }
+            Sampler::Dates(from, to) => {
+                let date = Field::Date(rng.random_range(*from..=*to));
+                return match cast(date, dtype) {
+                    Field::Timestamp(micros) => {
+                        Field::Timestamp(micros + rng.random_range(0..86_400_000_000))
+                    }
+                    field => field,
+                };
+            }
+        };
+        cast(Field::BigInt(int), dtype)
+    }
+}
+
+fn cast(field: Field, dtype: &DataType) -> Field {
+    let value = field.to_string();
+    field
+        .cast_to(dtype)
+        .unwrap_or_else(|e| panic!("Cannot generate {} for {}: {}", value, dtype, e))
+}
+
+/// A column of a generated table.
+#[derive(Debug, Clone)]
+struct ColumnGen {
+    dist: ColumnDist,
+    null_frac: f64,
+}
+
+/// Generates the rows of a table from a seed, so that a test sees the same rows
+/// on every run. The values of each column follow a `ColumnDist`, by default
+/// one fitting the type of the column, and are NULL with a given probability.
+#[derive(Debug, Clone)]
+pub struct TableGen {
+    schema: TableSchema,
+    columns: Vec<ColumnGen>,
+    seed: u64,
+}
+
+impl TableGen {
+    pub fn new(schema: TableSchema, seed: u64) -> Self {
+        let columns = schema
+            .attributes()
+            .map(|attr| ColumnGen {
+                dist: ColumnDist::default_for(attr),
+                null_frac: 0.0,
+            })
+            .collect();
+        TableGen {
+            schema,
+            columns,
+            seed,
+        }
+    }
+
+    /// Draws the values of column `name` from `dist`.
+    pub fn column(mut self, name: &str, dist: ColumnDist) -> Self {
+        let i = self.column_index(name);
+        self.columns[i].dist = dist;
+        self
+    }
+
+    /// Makes about a fraction `null_frac` of the values of column `name` NULL.
+    pub fn nulls(mut self, name: &str, null_frac: f64) -> Self {
+        let i = self.column_index(name);
+        self.columns[i].null_frac = null_frac;
+        self
+    }
+
+    fn column_index(&self, name: &str) -> usize {
+        self.schema
+            .get_field_index(name)
+            .unwrap_or_else(|| panic!("No column {} to generate", name))
+    }
+
+    pub fn schema(&self) -> &TableSchema {
+        &self.schema
+    }
+
+    /// Generates `n` rows. Each column draws from its own random numbers, so
+    /// changing the distribution of one column leaves the others as they were.
+    pub fn generate(&self, n: usize) -> Vec<Tuple> {
+        let columns: Vec<Vec<Field>> = self
+            .columns
+            .iter()
+            .zip(self.schema.attributes())
+            .enumerate()
+            .map(|(i, (column, attr))| {
+                let mut rng = SmallRng::seed_from_u64(self.seed.wrapping_add(i as u64));
+                let sampler = Sampler::new(&column.dist, &mut rng);
+                (0..n)
+                    .map(|row| {
+                        if column.null_frac > 0.0 && rng.random::<f64>() < column.null_frac {
+                            Field::Null
+                        } else {
+                            sampler.sample(row, attr.dtype(), &mut rng)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        (0..n)
+            .map(|row| Tuple::new(columns.iter().map(|c| c[row].clone()).collect()))
+            .collect()
+    }
+}
+
+/// Adds table `name` with the schema of `gen` to `catalog`, creates it in `sm`
+/// and inserts `n` generated rows in one transaction, which are also recorded
+/// in `stats`. Returns the table and the rows.
+pub fn load_table<S: StorageTrait, T: StatManagerTrait>(
+    catalog: &Catalog,
+    sm: &S,
+    stats: &T,
+    name: &str,
+    gen: &TableGen,
+    n: usize,
+) -> Result<(TableInfo, Vec<Tuple>), FairyError> {
+    let c_id = catalog.get_table_id(name)?;
+    let table = TableInfo::new(c_id, name.to_string(), gen.schema().clone());
+    catalog
+        .add_table(table.clone())
+        .ok_or_else(|| FairyError::AlreadyExists(ObjectKind::Table, name.to_string()))?;
+    sm.create_table(c_id)?;
+    stats.register_table(c_id, table.schema.clone())?;
+    let tuples = gen.generate(n);
+    let tid = TransactionId::new();
+    let value_ids = sm.insert_values(c_id, tuples.iter().map(Tuple::to_bytes).collect(), tid);
+    sm.commit(tid)?;
+    for (tuple, value_id) in tuples.iter().zip(value_ids) {
+        stats.new_record(tuple, value_id)?;
+    }
+    Ok((table, tuples))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn counts(tuples: &[Tuple], column: usize) -> HashMap<Field, usize> {
+        let mut counts = HashMap::new();
+        for tuple in tuples {
+            *counts.entry(tuple.field_vals[column].clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    fn schema() -> TableSchema {
+        let mut schema = TableSchema::from_vecs(
+            vec!["id", "n", "s", "d"],
+            vec![
+                DataType::BigInt,
+                DataType::Int,
+                DataType::String,
+                DataType::Date,
+            ],
+        );
+        schema.attributes[0].constraint = Constraint::PrimaryKey;
+        schema
+    }
+
+    #[test]
+    fn test_same_seed_same_rows() {
+        let gen = TableGen::new(schema(), 7).nulls("n", 0.1);
+        let rows = gen.generate(500);
+        assert_eq!(rows, gen.generate(500));
+        assert_ne!(
+            rows,
+            TableGen::new(schema(), 8).nulls("n", 0.1).generate(500)
+        );
+        // Other columns keep their values when one column changes.
+        let changed = gen
+            .clone()
+            .column("s", ColumnDist::Uniform { min: 0, max: 5 })
+            .generate(500);
+        for (a, b) in rows.iter().zip(&changed) {
+            assert_eq!(a.field_vals[1], b.field_vals[1]);
+            assert_eq!(a.field_vals[3], b.field_vals[3]);
+        }
+    }
+
+    #[test]
+    fn test_default_distributions() {
+        let rows = TableGen::new(schema(), 1).generate(1000);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row.field_vals[0], Field::BigInt(i as i64));
+            assert!(matches!(row.field_vals[1], Field::Int(0..=999)));
+            assert!(matches!(&row.field_vals[2], Field::String(s) if (1..=20).contains(&s.len())));
+            assert!(matches!(row.field_vals[3], Field::Date(_)));
+        }
+        assert_eq!(counts(&rows, 2).len(), 100);
+    }
+
+    #[test]
+    fn test_uniform_and_nulls() {
+        let rows = TableGen::new(schema(), 2)
+            .column("n", ColumnDist::Uniform { min: 10, max: 19 })
+            .nulls("n", 0.2)
+            .generate(10000);
+        let counts = counts(&rows, 1);
+        let nulls = counts[&Field::Null];
+        assert!((1700..=2300).contains(&nulls), "{}", nulls);
+        assert_eq!(counts.len(), 11);
+        for i in 10..20 {
+            let count = counts[&Field::Int(i)];
+            assert!((650..=950).contains(&count), "{}: {}", i, count);
+        }
+    }
+
+    #[test]
+    fn test_zipf() {
+        let rows = TableGen::new(schema(), 3)
+            .column(
+                "n",
+                ColumnDist::Zipf {
+                    min: 1,
+                    cardinality: 100,
+                    skew: 1.0,
+                },
+            )
+            .generate(20000);
+        let counts = counts(&rows, 1);
+        let count = |i| counts.get(&Field::Int(i)).copied().unwrap_or(0);
+        // The first value has a weight of 1 out of the harmonic number H(100),
+        // about 5.19, and the second half that.
+        assert!((3500..=4200).contains(&count(1)), "{}", count(1));
+        assert!((1650..=2200).contains(&count(2)), "{}", count(2));
+        assert!(count(1) > count(10) * 5);
+        assert!(counts.keys().all(|f| matches!(f, Field::Int(1..=100))));
+    }
+
+    #[test]
+    fn test_string_pool() {
+        let rows = TableGen::new(schema(), 4)
+            .column(
+                "s",
+                ColumnDist::Strings {
+                    cardinality: 40,
+                    min_len: 32,
+                    max_len: 64,
+                },
+            )
+            .generate(2000);
+        let counts = counts(&rows, 2);
+        assert_eq!(counts.len(), 40);
+        for s in counts.keys() {
+            assert!(matches!(s, Field::String(s) if (32..=64).contains(&s.len())));
+        }
+    }
+
+    #[test]
+    fn test_dates_and_other_types() {
+        let schema = TableSchema::from_vecs(
+            vec!["d", "t", "c", "m"],
+            vec![
+                DataType::Date,
+                DataType::Timestamp,
+                DataType::Char(4),
+                DataType::Decimal(10, 2),
+            ],
+        );
+        let from = Field::from_str_to_date("2024-02-01").unwrap();
+        let to = Field::from_str_to_date("2024-02-29").unwrap();
+        let rows = TableGen::new(schema, 5)
+            .column(
+                "d",
+                ColumnDist::Dates {
+                    from: "2024-02-01",
+                    to: "2024-02-29",
+                },
+            )
+            .column("m", ColumnDist::Uniform { min: 5, max: 5 })
+            .generate(500);
+        for row in &rows {
+            let d = &row.field_vals[0];
+            assert!(*d >= from && *d <= to, "{}", d);
+            assert!(matches!(row.field_vals[1], Field::Timestamp(_)));
+            assert!(matches!(row.field_vals[2], Field::Char(4, _)));
+            assert_eq!(row.field_vals[3], Field::Decimal(500, 2));
+        }
+        assert_eq!(counts(&rows, 0).len(), 29);
+    }
+
+    #[test]
+    #[should_panic(expected = "No column x to generate")]
+    fn test_unknown_column() {
+        let _ = TableGen::new(schema(), 6).nulls("x", 0.5);
+    }
+}
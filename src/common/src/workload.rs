@@ -0,0 +1,154 @@
+use crate::commands::Response;
+use crate::error::c_err;
+use crate::{FairyError, QueryResult};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// One executed statement of a captured workload.
+/// A capture file holds one JSON encoded statement per line, in execution order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedStatement {
+    /// Time between the start of the capture and the start of the statement.
+    pub offset_us: u64,
+    /// Id of the session (client connection) that issued the statement.
+    pub session_id: u64,
+    /// The statement as typed by the client, either SQL or a `\` command.
+    pub statement: String,
+    pub latency_us: u64,
+    pub ok: bool,
+    /// Number of rows returned or inserted, if the statement produced a result.
+    pub result_count: Option<usize>,
+}
+
+/// Appends the statements executed by a server to a capture file.
+pub struct WorkloadCapture {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl WorkloadCapture {
+    /// Creates (or truncates) the capture file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, FairyError> {
+        let file = File::create(path)?;
+        Ok(WorkloadCapture {
+            start: Instant::now(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Records a statement that started at `started` and produced `response`.
+    /// The line is flushed right away so that the file is complete as soon as
+    /// the client has received the response.
+    pub fn record(
+        &self,
+        session_id: u64,
+        statement: &str,
+        started: Instant,
+        response: &Response,
+    ) -> Result<(), FairyError> {
+        let captured = CapturedStatement {
+            offset_us: duration_us(started.saturating_duration_since(self.start)),
+            session_id,
+            statement: statement.to_string(),
+            latency_us: duration_us(started.elapsed()),
+            ok: response.is_ok(),
+            result_count: result_count(response),
+        };
+        let line = serde_json::to_string(&captured)
+            .map_err(|e| FairyError::SerializationError(e.to_string()))?;
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads all statements of a capture file.
+pub fn read_workload<P: AsRef<Path>>(path: P) -> Result<Vec<CapturedStatement>, FairyError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut statements = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let statement = serde_json::from_str(&line)
+            .map_err(|e| c_err(&format!("Invalid capture line {}: {}", i + 1, e)))?;
+        statements.push(statement);
+    }
+    Ok(statements)
+}
+
+/// Returns the number of rows returned or inserted by a statement, if any.
+pub fn result_count(response: &Response) -> Option<usize> {
+    match response {
+        Response::QueryResult(QueryResult::Select { result, .. }) => Some(result.len()),
+        Response::QueryResult(QueryResult::Insert { inserted, .. }) => Some(*inserted),
+        _ => None,
+    }
+}
+
+/// Returns true if the statement changes the server or database state.
+pub fn is_mutating(statement: &str) -> bool {
+    const MUTATING_SQL: [&str; 8] = [
+        "insert", "update", "delete", "create", "drop", "alter", "truncate", "comment",
+    ];
+    const MUTATING_COMMANDS: [&str; 5] = ["r", "reset", "i", "generate", "commit"];
+    let statement = statement.trim_start();
+    match statement.strip_prefix('\\') {
+        Some(command) => {
+            let name = command.split_whitespace().next().unwrap_or_default();
+            MUTATING_COMMANDS.contains(&name)
+        }
+        None => {
+            let keyword = statement.split_whitespace().next().unwrap_or_default();
+            MUTATING_SQL.iter().any(|k| k.eq_ignore_ascii_case(keyword))
+        }
+    }
+}
+
+fn duration_us(d: Duration) -> u64 {
+    d.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataType, TableSchema};
+
+    #[test]
+    fn test_capture_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.jsonl");
+        let capture = WorkloadCapture::create(&path).unwrap();
+        let schema = TableSchema::from_vecs(vec!["a"], vec![DataType::Int]);
+        let select = Response::QueryResult(QueryResult::new_select_result(&schema, vec![], None));
+        capture
+            .record(1, "SELECT * FROM foo", Instant::now(), &select)
+            .unwrap();
+        capture
+            .record(2, "\\c db", Instant::now(), &Response::Ok)
+            .unwrap();
+
+        let statements = read_workload(&path).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].session_id, 1);
+        assert_eq!(statements[0].statement, "SELECT * FROM foo");
+        assert_eq!(statements[0].result_count, Some(0));
+        assert_eq!(statements[1].result_count, None);
+        assert!(statements[0].offset_us <= statements[1].offset_us);
+    }
+
+    #[test]
+    fn test_is_mutating() {
+        assert!(is_mutating("INSERT INTO foo VALUES (1)"));
+        assert!(is_mutating("  create table foo (a int primary key)"));
+        assert!(is_mutating("\\r db"));
+        assert!(!is_mutating("SELECT * FROM foo"));
+        assert!(!is_mutating("\\c db"));
+        assert!(!is_mutating("\\dt"));
+    }
+}
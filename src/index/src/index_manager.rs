@@ -1,3 +1,9 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use common::ids::Permissions;
+use common::prelude::*;
+use common::traits::storage_trait::StorageTrait;
 use common::{physical::config::ServerConfig, FairyError};
 use log::info;
 
@@ -8,6 +14,17 @@ pub struct IndexManager {
     config: &'static ServerConfig,
     sm: &'static StorageManager,
     tm: &'static TransactionManager,
+    /// Lock of each table with unique keys, held from checking the keys of new
+    /// rows until they are inserted.
+    unique_locks: Mutex<HashMap<ContainerId, Arc<Mutex<()>>>>,
+}
+
+/// Values of the columns `key` of `tuple`, or None if one of them is NULL. Rows
+/// with a NULL in a key do not conflict with any row.
+fn key_values(key: &[usize], tuple: &Tuple) -> Option<Vec<Field>> {
+    key.iter()
+        .map(|&i| tuple.get_field(i).filter(|f| **f != Field::Null).cloned())
+        .collect()
 }
 
 impl IndexManager {
@@ -16,20 +33,102 @@ impl IndexManager {
         sm: &'static StorageManager,
         tm: &'static TransactionManager,
     ) -> Self {
-        Self { config, sm, tm }
+        Self {
+            config,
+            sm,
+            tm,
+            unique_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs `insert`, which stores `tuples` into `table`, once their unique keys
+    /// are checked. Fails without running it if two of the tuples, or one of
+    /// them and a row of the table, have the same value of a unique key. Other
+    /// inserts into the table wait until `insert` returns.
+    pub fn insert_unique<R>(
+        &self,
+        table: &TableInfo,
+        tuples: &[Tuple],
+        tid: TransactionId,
+        insert: impl FnOnce() -> Result<R, FairyError>,
+    ) -> Result<R, FairyError> {
+        if table.unique_keys.is_empty() {
+            return insert();
+        }
+        let lock = self
+            .unique_locks
+            .lock()
+            .unwrap()
+            .entry(table.c_id)
+            .or_default()
+            .clone();
+        let _guard = lock.lock().unwrap();
+        self.check_unique_keys(table, tuples, tid)?;
+        insert()
+    }
+
+    /// Checks the keys of `tuples` against each other, then against the rows
+    /// of `table` in one scan. Only the keys of `tuples` are kept in memory.
+    fn check_unique_keys(
+        &self,
+        table: &TableInfo,
+        tuples: &[Tuple],
+        tid: TransactionId,
+    ) -> Result<(), FairyError> {
+        let mut new_values = Vec::with_capacity(table.unique_keys.len());
+        for key in &table.unique_keys {
+            let mut values = HashSet::new();
+            for tuple in tuples {
+                if let Some(v) = key_values(key, tuple) {
+                    if let Some(v) = values.replace(v) {
+                        return Err(duplicate_key_error(&table.schema, key, &v));
+                    }
+                }
+            }
+            new_values.push(values);
+        }
+        for (bytes, _) in self.sm.get_iterator(table.c_id, tid, Permissions::ReadOnly) {
+            let tuple = Tuple::try_from_bytes(&bytes)?;
+            for (key, values) in table.unique_keys.iter().zip(&new_values) {
+                if let Some(v) = key_values(key, &tuple) {
+                    if values.contains(&v) {
+                        return Err(duplicate_key_error(&table.schema, key, &v));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the lock of a dropped table.
+    pub fn remove_table(&self, c_id: ContainerId) {
+        self.unique_locks.lock().unwrap().remove(&c_id);
     }
 
     pub fn shutdown(&self) -> Result<(), FairyError> {
         info!("TODO: index manager shutdown is a stub");
         // DO NOT TOUCH sm OR tm, THEY COULD BE SHUT DOWN ALREADY
-        // TODO: implement shutdown after index manager is working since there's nothing to shutdown rn
         Ok(())
     }
 
     pub fn reset(&self) -> Result<(), FairyError> {
-        info!("TODO: index manager reset is a stub");
         // DO NOT TOUCH sm OR tm, THEY COULD BE SHUT DOWN ALREADY
-        // TODO: implement reset after index manager is working since there's nothing to reset rn
+        self.unique_locks.lock().unwrap().clear();
         Ok(())
     }
 }
+
+/// Error for rows sharing the values `values` of the unique key `key`.
+fn duplicate_key_error(schema: &TableSchema, key: &[usize], values: &[Field]) -> FairyError {
+    FairyError::DuplicateKey {
+        columns: key
+            .iter()
+            .map(|&i| {
+                schema
+                    .get_attribute(i)
+                    .map_or_else(|| i.to_string(), |a| a.name().to_string())
+            })
+            .collect(),
+        values: values.iter().map(Field::to_string).collect(),
+    }
+}
@@ -0,0 +1,82 @@
+use common::{ids::ColumnId, physical_expr::physical_rel_expr::PhysicalRelExpr};
+use std::collections::HashSet;
+
+/// Replaces hash aggregates whose input is already ordered on the group by
+/// columns with stream aggregates, which hold one group at a time.
+pub fn use_stream_aggregates(plan: &mut PhysicalRelExpr) {
+    match plan {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => use_stream_aggregates(src),
+        PhysicalRelExpr::Map { input, .. } => use_stream_aggregates(input),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            use_stream_aggregates(input);
+            use_stream_aggregates(func);
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } => {
+            use_stream_aggregates(left);
+            use_stream_aggregates(right);
+        }
+    }
+    if let PhysicalRelExpr::HashAggregate {
+        src,
+        group_by,
+        aggrs,
+        tree_hash,
+    } = plan
+    {
+        let order: Vec<ColumnId> = src.output_order().iter().map(|(id, _, _)| *id).collect();
+        if groups_adjacent(&order, group_by) {
+            *plan = PhysicalRelExpr::StreamAggregate {
+                src: src.clone(),
+                group_by: group_by.clone(),
+                aggrs: aggrs.clone(),
+                tree_hash: *tree_hash,
+            };
+        }
+    }
+}
+
+/// Returns true if input ordered on `order` has the rows of each group of
+/// `group_by` next to each other, i.e. the group by columns are a prefix of
+/// the order.
+fn groups_adjacent(order: &[ColumnId], group_by: &[ColumnId]) -> bool {
+    let mut missing: HashSet<&ColumnId> = group_by.iter().collect();
+    for id in order {
+        if missing.is_empty() {
+            break;
+        }
+        if !group_by.contains(id) {
+            return false;
+        }
+        missing.remove(id);
+    }
+    !group_by.is_empty() && missing.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_groups_adjacent() {
+        assert!(groups_adjacent(&[1, 2], &[1]));
+        assert!(groups_adjacent(&[1, 2], &[2, 1]));
+        assert!(groups_adjacent(&[1, 1, 2, 3], &[1, 2]));
+        assert!(!groups_adjacent(&[1, 2], &[2]));
+        assert!(!groups_adjacent(&[1], &[1, 2]));
+        assert!(!groups_adjacent(&[], &[1]));
+        assert!(!groups_adjacent(&[1], &[]));
+    }
+}
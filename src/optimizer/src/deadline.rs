@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+/// Default time budget for planning a single query.
+pub const DEFAULT_PLANNING_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Tracks the time spent planning a query against a budget.
+/// Enumeration steps should call `is_exceeded` between steps and fall back to
+/// the heuristic plan for the remaining subproblems once the budget is spent.
+/// A zero budget disables the deadline.
+pub struct PlanningDeadline {
+    start: Instant,
+    budget: Duration,
+}
+
+impl PlanningDeadline {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            budget,
+        }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        !self.budget.is_zero() && self.elapsed() > self.budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_budget_never_exceeds() {
+        let deadline = PlanningDeadline::new(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(!deadline.is_exceeded());
+    }
+
+    #[test]
+    fn test_deadline_exceeds() {
+        let deadline = PlanningDeadline::new(Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(deadline.is_exceeded());
+    }
+}
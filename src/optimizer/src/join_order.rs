@@ -0,0 +1,121 @@
+use common::{
+    logical_expr::prelude::{Expression, JoinType},
+    physical_expr::physical_rel_expr::PhysicalRelExpr,
+    BinaryOp,
+};
+use queryexe::stats::stats_view::StatsView;
+
+use crate::deadline::PlanningDeadline;
+
+/// Swaps the inputs of inner equi-joins so that the input with fewer estimated
+/// rows is the one the join materializes: the right input of a nested loop
+/// join and the left input of a hash join. Joins whose inputs cannot both be
+/// estimated keep their order, and so do all joins left once `deadline` is
+/// exceeded.
+pub fn order_join_inputs(
+    plan: &mut PhysicalRelExpr,
+    stats: &StatsView,
+    deadline: &PlanningDeadline,
+) {
+    reorder(plan, &Reorder { stats, deadline }, false);
+}
+
+struct Reorder<'a> {
+    stats: &'a StatsView<'a>,
+    deadline: &'a PlanningDeadline,
+}
+
+impl Reorder<'_> {
+    /// Returns true if the inputs of an inner equi-join should be swapped
+    /// because `materialized` is estimated to produce more rows than `other`.
+    fn should_swap(
+        &self,
+        join_type: &JoinType,
+        predicates: &[Expression<PhysicalRelExpr>],
+        materialized: &PhysicalRelExpr,
+        other: &PhysicalRelExpr,
+    ) -> bool {
+        is_inner_equi_join(join_type, predicates)
+            && !self.deadline.is_exceeded()
+            && larger(self.stats, materialized, other)
+    }
+}
+
+/// Swapping the inputs of a join swaps its columns, so only joins below a
+/// projection or aggregation, which pick their columns by id, are reordered.
+fn reorder(plan: &mut PhysicalRelExpr, ctx: &Reorder, picked: bool) {
+    match plan {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => reorder(src, ctx, true),
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. } => reorder(src, ctx, picked),
+        PhysicalRelExpr::Map { input, .. } => reorder(input, ctx, picked),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            reorder(input, ctx, picked);
+            reorder(func, ctx, picked);
+        }
+        PhysicalRelExpr::NestedLoopJoin {
+            join_type,
+            left,
+            right,
+            predicates,
+            ..
+        } => {
+            reorder(left, ctx, picked);
+            reorder(right, ctx, picked);
+            if picked && ctx.should_swap(join_type, predicates, right, left) {
+                std::mem::swap(left, right);
+            }
+        }
+        PhysicalRelExpr::HashJoin {
+            join_type,
+            left,
+            right,
+            predicates,
+            ..
+        } => {
+            reorder(left, ctx, picked);
+            reorder(right, ctx, picked);
+            if picked && ctx.should_swap(join_type, predicates, left, right) {
+                std::mem::swap(left, right);
+            }
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. } => {
+            reorder(left, ctx, picked);
+            reorder(right, ctx, picked);
+        }
+        // The columns of the inputs of a union are matched by position.
+        PhysicalRelExpr::Union { left, right, .. } => {
+            reorder(left, ctx, false);
+            reorder(right, ctx, false);
+        }
+    }
+}
+
+fn is_inner_equi_join(join_type: &JoinType, predicates: &[Expression<PhysicalRelExpr>]) -> bool {
+    matches!(join_type, JoinType::Inner)
+        && predicates.iter().any(|pred| {
+            matches!(
+                pred,
+                Expression::Binary {
+                    op: BinaryOp::Eq,
+                    ..
+                }
+            )
+        })
+}
+
+/// Returns true if `a` is estimated to produce more rows than `b`.
+fn larger(stats: &StatsView, a: &PhysicalRelExpr, b: &PhysicalRelExpr) -> bool {
+    match (stats.estimate_rows(a), stats.estimate_rows(b)) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}
@@ -0,0 +1,162 @@
+use common::{
+    ids::ColumnId,
+    logical_expr::prelude::{Expression, JoinType},
+    physical_expr::physical_rel_expr::PhysicalRelExpr,
+    traits::plan::Plan,
+    BinaryOp,
+};
+use queryexe::stats::stats_view::StatsView;
+
+/// Replaces inner hash joins on column equalities with sort-merge joins when
+/// both inputs are already ordered on the join columns, so that neither needs
+/// a sort, or when both inputs are estimated to have more than
+/// `max_build_rows` rows, too many to build a hash table over. A limit of 0
+/// never replaces a join for its size.
+pub fn use_merge_joins(plan: &mut PhysicalRelExpr, stats: &StatsView, max_build_rows: usize) {
+    match plan {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => {
+            use_merge_joins(src, stats, max_build_rows)
+        }
+        PhysicalRelExpr::Map { input, .. } => use_merge_joins(input, stats, max_build_rows),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            use_merge_joins(input, stats, max_build_rows);
+            use_merge_joins(func, stats, max_build_rows);
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } => {
+            use_merge_joins(left, stats, max_build_rows);
+            use_merge_joins(right, stats, max_build_rows);
+        }
+    }
+    if let PhysicalRelExpr::HashJoin {
+        join_type: JoinType::Inner,
+        left,
+        right,
+        predicates,
+        tree_hash,
+    } = plan
+    {
+        let Some(keys) = merge_keys(predicates, left, right) else {
+            return;
+        };
+        let (left_keys, right_keys): (Vec<ColumnId>, Vec<ColumnId>) = keys.into_iter().unzip();
+        let presorted = sorted_on(&left.output_order(), &left_keys)
+            && sorted_on(&right.output_order(), &right_keys);
+        if presorted || too_large(stats, left, right, max_build_rows) {
+            *plan = PhysicalRelExpr::SortMergeJoin {
+                join_type: JoinType::Inner,
+                left: left.clone(),
+                right: right.clone(),
+                predicates: predicates.clone(),
+                tree_hash: *tree_hash,
+            };
+        }
+    }
+}
+
+/// The columns of `left` and of `right` that `predicates` equate, or None if
+/// one of the predicates is not an equality between a column of each input.
+fn merge_keys(
+    predicates: &[Expression<PhysicalRelExpr>],
+    left: &PhysicalRelExpr,
+    right: &PhysicalRelExpr,
+) -> Option<Vec<(ColumnId, ColumnId)>> {
+    let (left_att, right_att) = (left.att(), right.att());
+    predicates
+        .iter()
+        .map(|pred| match pred {
+            Expression::Binary {
+                op: BinaryOp::Eq,
+                left: a,
+                right: b,
+            } => match (a.as_ref(), b.as_ref()) {
+                (Expression::ColRef { id: a }, Expression::ColRef { id: b }) => {
+                    if left_att.contains(a) && right_att.contains(b) {
+                        Some((*a, *b))
+                    } else if left_att.contains(b) && right_att.contains(a) {
+                        Some((*b, *a))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|keys| !keys.is_empty())
+}
+
+/// Returns true if input ordered on `order` is sorted ascending on `keys`, in
+/// that order.
+fn sorted_on(order: &[(ColumnId, bool, bool)], keys: &[ColumnId]) -> bool {
+    keys.len() <= order.len()
+        && keys
+            .iter()
+            .zip(order)
+            .all(|(key, (id, asc, _))| key == id && *asc)
+}
+
+/// Returns true if both inputs are estimated to have more than `max_build_rows`
+/// rows, so a hash join could not build over either.
+fn too_large(
+    stats: &StatsView,
+    left: &PhysicalRelExpr,
+    right: &PhysicalRelExpr,
+    max_build_rows: usize,
+) -> bool {
+    max_build_rows > 0
+        && [left, right].into_iter().all(|input| {
+            stats
+                .estimate_rows(input)
+                .is_some_and(|rows| rows > max_build_rows)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sorted_on() {
+        assert!(sorted_on(&[(1, true, false), (2, true, true)], &[1]));
+        assert!(sorted_on(&[(1, true, false), (2, true, true)], &[1, 2]));
+        assert!(!sorted_on(&[(1, true, false)], &[2]));
+        assert!(!sorted_on(&[(1, false, true)], &[1]));
+        assert!(!sorted_on(&[(1, true, false)], &[1, 2]));
+        assert!(!sorted_on(&[(2, true, false), (1, true, false)], &[1, 2]));
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let scan = |cols: Vec<ColumnId>| PhysicalRelExpr::Scan {
+            cid: 0,
+            table_name: "t".to_string(),
+            column_names: cols,
+            tree_hash: None,
+        };
+        let (left, right) = (scan(vec![1, 2]), scan(vec![3]));
+        let eq =
+            |a, b| Expression::binary(BinaryOp::Eq, Expression::col_ref(a), Expression::col_ref(b));
+        assert_eq!(
+            merge_keys(&[eq(3, 1), eq(2, 3)], &left, &right),
+            Some(vec![(1, 3), (2, 3)])
+        );
+        assert_eq!(merge_keys(&[eq(1, 2)], &left, &right), None);
+        assert_eq!(merge_keys(&[], &left, &right), None);
+        let lt = Expression::binary(BinaryOp::Lt, Expression::col_ref(1), Expression::col_ref(3));
+        assert_eq!(merge_keys(&[eq(1, 3), lt], &left, &right), None);
+    }
+}
@@ -1,2 +1,8 @@
+pub mod aggregate_strategy;
 pub mod cost;
+pub mod deadline;
+pub mod join_order;
+pub mod join_strategy;
 pub mod mock_optimizer;
+pub mod sort_elimination;
+pub mod top_n;
@@ -1,11 +1,17 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::Cell, cell::RefCell, rc::Rc};
 
 use common::{
     physical_expr::physical_rel_expr::PhysicalRelExpr, query::query_registrar::QueryStateRegistrar,
 };
-use queryexe::{query::translate_and_validate::Query, Managers};
+use queryexe::{query::translate_and_validate::Query, stats::stats_view::StatsView, Managers};
 
+use crate::aggregate_strategy::use_stream_aggregates;
 use crate::cost::CostModel;
+use crate::deadline::{PlanningDeadline, DEFAULT_PLANNING_TIMEOUT};
+use crate::join_order::order_join_inputs;
+use crate::join_strategy::use_merge_joins;
+use crate::sort_elimination::remove_redundant_sorts;
+use crate::top_n::use_top_n;
 
 pub struct MockOptimizer<C: CostModel> {
     /// Cost model used to estimate the cost of a plan. Using `Rc` to allow
@@ -15,25 +21,83 @@ pub struct MockOptimizer<C: CostModel> {
     _cost_model: Rc<RefCell<C>>,
 
     ///Managers
-    _managers: &'static Managers,
+    managers: &'static Managers,
+
+    /// Whether the last call to `optimize` ran over the planning budget.
+    last_planning_truncated: Cell<bool>,
 }
 
 impl<C: CostModel + 'static> MockOptimizer<C> {
     pub fn new(cost_model: C, managers: &'static Managers) -> Self {
         Self {
             _cost_model: Rc::new(RefCell::new(cost_model)),
-            _managers: managers,
+            managers,
+            last_planning_truncated: Cell::new(false),
         }
     }
 
-    /// Optimize a logical plan and return the optimized physical plan
+    /// Returns true if the last optimized plan skipped some optimizations
+    /// because the planning budget was exceeded.
+    pub fn last_planning_truncated(&self) -> bool {
+        self.last_planning_truncated.get()
+    }
+
+    /// Optimize a logical plan within the default planning budget and return
+    /// the optimized physical plan
     pub fn optimize(
+        &self,
+        plan: &Query,
+        query_registrar: Option<&'static QueryStateRegistrar>,
+    ) -> PhysicalRelExpr {
+        self.optimize_with_stats(
+            plan,
+            query_registrar,
+            &StatsView::new(self.managers.stats),
+            &PlanningDeadline::new(DEFAULT_PLANNING_TIMEOUT),
+        )
+    }
+
+    /// Like `optimize`, but reads the statistics through `stats`, which may
+    /// overlay the real statistics with a session's hypothetical ones, and
+    /// plans within `deadline`. Once the deadline is exceeded the remaining
+    /// passes are skipped, leaving the plan as the passes so far made it.
+    pub fn optimize_with_stats(
         &self,
         plan: &Query,
         _query_registrar: Option<&'static QueryStateRegistrar>,
+        stats: &StatsView,
+        deadline: &PlanningDeadline,
     ) -> PhysicalRelExpr {
         // environment isn't important in a non-optimizing context
+        // The mock optimizer does not enumerate join orders, so the left-deep
+        // plan from the translator is the heuristic plan used on truncation too.
         let logical_plan = plan.get_plan();
-        logical_plan.to_physical_plan()
+        let mut physical_plan = logical_plan.to_physical_plan();
+        let max_build_rows = self.managers.config.hash_join_max_build_rows;
+        let top_n_max_rows = self.managers.config.top_n_max_rows;
+        let passes: [&dyn Fn(&mut PhysicalRelExpr); 5] = [
+            &|plan| order_join_inputs(plan, stats, deadline),
+            &|plan| use_merge_joins(plan, stats, max_build_rows),
+            &use_stream_aggregates,
+            &remove_redundant_sorts,
+            // Sorts the previous passes removed are not replaced.
+            &|plan| use_top_n(plan, top_n_max_rows),
+        ];
+        for pass in passes {
+            if deadline.is_exceeded() {
+                break;
+            }
+            pass(&mut physical_plan);
+        }
+        let truncated = deadline.is_exceeded();
+        if truncated {
+            log::warn!(
+                "Planning took {:?}, exceeding the budget of {:?}",
+                deadline.elapsed(),
+                deadline.budget()
+            );
+        }
+        self.last_planning_truncated.set(truncated);
+        physical_plan
     }
 }
@@ -0,0 +1,137 @@
+use common::{ids::ColumnId, physical_expr::physical_rel_expr::PhysicalRelExpr};
+
+/// Removes sorts whose input is already ordered on their keys, e.g. a sort
+/// above another sort on the same columns or above a stream aggregate on them.
+pub fn remove_redundant_sorts(plan: &mut PhysicalRelExpr) {
+    match plan {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => remove_redundant_sorts(src),
+        PhysicalRelExpr::Map { input, .. } => remove_redundant_sorts(input),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            remove_redundant_sorts(input);
+            remove_redundant_sorts(func);
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } => {
+            remove_redundant_sorts(left);
+            remove_redundant_sorts(right);
+        }
+    }
+    if let PhysicalRelExpr::Sort { src, cols, .. } = plan {
+        if satisfies(&src.output_order(), cols) {
+            *plan = *src.clone();
+        }
+    }
+}
+
+/// Returns true if input ordered on `order` is also ordered on `required`,
+/// i.e. `required` is a prefix of `order`.
+fn satisfies(order: &[(ColumnId, bool, bool)], required: &[(ColumnId, bool, bool)]) -> bool {
+    order.starts_with(required)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::logical_expr::prelude::{BinaryOp, Expression, JoinType};
+
+    fn scan(cols: Vec<usize>) -> Box<PhysicalRelExpr> {
+        Box::new(PhysicalRelExpr::Scan {
+            cid: 0,
+            table_name: "t".to_string(),
+            column_names: cols,
+            tree_hash: None,
+        })
+    }
+
+    fn sort(src: Box<PhysicalRelExpr>, cols: Vec<(usize, bool, bool)>) -> Box<PhysicalRelExpr> {
+        Box::new(PhysicalRelExpr::Sort {
+            src,
+            cols,
+            tree_hash: None,
+        })
+    }
+
+    fn count_sorts(plan: &PhysicalRelExpr) -> usize {
+        match plan {
+            PhysicalRelExpr::Sort { src, .. } => 1 + count_sorts(src),
+            PhysicalRelExpr::Project { src, .. } | PhysicalRelExpr::Select { src, .. } => {
+                count_sorts(src)
+            }
+            PhysicalRelExpr::SortMergeJoin { left, right, .. } => {
+                count_sorts(left) + count_sorts(right)
+            }
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_satisfies() {
+        assert!(satisfies(
+            &[(1, true, false), (2, false, true)],
+            &[(1, true, false)]
+        ));
+        assert!(satisfies(&[(1, true, false)], &[]));
+        assert!(!satisfies(&[(1, true, false)], &[(1, false, true)]));
+        assert!(!satisfies(&[(1, true, false)], &[(2, true, false)]));
+        assert!(!satisfies(
+            &[(1, true, false)],
+            &[(1, true, false), (2, true, false)]
+        ));
+    }
+
+    #[test]
+    fn test_sort_on_prefix_of_sort_is_removed() {
+        let inner = sort(scan(vec![1, 2]), vec![(1, true, false), (2, true, false)]);
+        let project = Box::new(PhysicalRelExpr::Project {
+            src: inner,
+            cols: vec![1, 2],
+            tree_hash: None,
+        });
+        let mut plan = *sort(project, vec![(1, true, false)]);
+        remove_redundant_sorts(&mut plan);
+        assert_eq!(count_sorts(&plan), 1);
+        assert!(matches!(plan, PhysicalRelExpr::Project { .. }));
+    }
+
+    #[test]
+    fn test_sort_in_other_direction_is_kept() {
+        let inner = sort(scan(vec![1, 2]), vec![(1, true, false)]);
+        let mut plan = *sort(inner, vec![(1, false, true)]);
+        remove_redundant_sorts(&mut plan);
+        assert_eq!(count_sorts(&plan), 2);
+    }
+
+    #[test]
+    fn test_sort_on_merge_join_keys_is_removed() {
+        let join = Box::new(PhysicalRelExpr::SortMergeJoin {
+            join_type: JoinType::Inner,
+            left: scan(vec![1]),
+            right: scan(vec![2]),
+            predicates: vec![Expression::binary(
+                BinaryOp::Eq,
+                Expression::col_ref(2),
+                Expression::col_ref(1),
+            )],
+            tree_hash: None,
+        });
+        let mut plan = *sort(join.clone(), vec![(1, true, false)]);
+        remove_redundant_sorts(&mut plan);
+        assert_eq!(count_sorts(&plan), 0);
+
+        let mut plan = *sort(join, vec![(2, true, false)]);
+        remove_redundant_sorts(&mut plan);
+        assert_eq!(count_sorts(&plan), 1);
+    }
+}
@@ -149,19 +149,19 @@ pub fn get_test_catalog() -> Arc<Catalog> {
     let catalog = Catalog::new();
     let t1_table_name = String::from(T1_TABLE_NAME);
     let t1_schema = TableSchema::from_vecs(A_COLS.to_vec(), A_TYPES.to_vec());
-    let t1_cid = catalog.get_table_id(&t1_table_name);
+    let t1_cid = catalog.get_table_id(&t1_table_name).unwrap();
     let t1_table = TableInfo::new(t1_cid, t1_table_name, t1_schema);
     catalog.add_table(t1_table);
 
     let t2_table_name = String::from(T2_TABLE_NAME);
     let t2_schema = TableSchema::from_vecs(B_COLS.to_vec(), B_TYPES.to_vec());
-    let t2_cid = catalog.get_table_id(&t2_table_name);
+    let t2_cid = catalog.get_table_id(&t2_table_name).unwrap();
     let t2_table = TableInfo::new(t2_cid, t2_table_name, t2_schema);
     catalog.add_table(t2_table);
 
     let t3_table_name = String::from(T3_TABLE_NAME);
     let t3_schema = TableSchema::from_vecs(C_COLS.to_vec(), C_TYPES.to_vec());
-    let t3_cid = catalog.get_table_id(&t3_table_name);
+    let t3_cid = catalog.get_table_id(&t3_table_name).unwrap();
     let t3_table = TableInfo::new(t3_cid, t3_table_name, t3_schema);
     catalog.add_table(t3_table);
 
@@ -477,7 +477,9 @@ pub fn get_opiterator_after_optimization_alt(
     for (i, tuple_count) in tuple_counts.iter().enumerate() {
         let (table, tuples) = gen_test_table_and_tuples(
             &mut rng,
-            catalog.get_table_id(format!("test_table_{}", i).as_str()),
+            catalog
+                .get_table_id(format!("test_table_{}", i).as_str())
+                .unwrap(),
             *tuple_count,
         );
         catalog.add_table(table.clone());
@@ -0,0 +1,143 @@
+use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+
+/// Replaces sorts below a limit of at most `max_rows` rows with top-n
+/// operators, which keep only the first rows while they read their input
+/// instead of sorting all of it. Projections, maps and renames between the
+/// limit and the sort keep the number and the order of the rows, so the limit
+/// may be above them. A maximum of 0 never replaces a sort.
+pub fn use_top_n(plan: &mut PhysicalRelExpr, max_rows: usize) {
+    match plan {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => use_top_n(src, max_rows),
+        PhysicalRelExpr::Map { input, .. } => use_top_n(input, max_rows),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            use_top_n(input, max_rows);
+            use_top_n(func, max_rows);
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } => {
+            use_top_n(left, max_rows);
+            use_top_n(right, max_rows);
+        }
+    }
+    if let PhysicalRelExpr::Limit { src, limit, .. } = plan {
+        if max_rows == 0 || *limit > max_rows {
+            return;
+        }
+        let limit = *limit;
+        let Some(sort) = sort_below(src) else {
+            return;
+        };
+        if let PhysicalRelExpr::Sort { src, cols, .. } = sort {
+            *sort = PhysicalRelExpr::TopN {
+                src: src.clone(),
+                cols: cols.clone(),
+                limit,
+                tree_hash: None,
+            };
+        }
+        *plan = *src.clone();
+    }
+}
+
+/// The sort that `plan` is, or that is below the projections, maps and
+/// renames at the top of `plan`, if any.
+fn sort_below(plan: &mut PhysicalRelExpr) -> Option<&mut PhysicalRelExpr> {
+    match plan {
+        PhysicalRelExpr::Sort { .. } => Some(plan),
+        PhysicalRelExpr::Project { src, .. } | PhysicalRelExpr::Rename { src, .. } => {
+            sort_below(src)
+        }
+        PhysicalRelExpr::Map { input, .. } => sort_below(input),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scan() -> Box<PhysicalRelExpr> {
+        Box::new(PhysicalRelExpr::Scan {
+            cid: 0,
+            table_name: "t".to_string(),
+            column_names: vec![1, 2],
+            tree_hash: None,
+        })
+    }
+
+    fn sort(src: Box<PhysicalRelExpr>) -> Box<PhysicalRelExpr> {
+        Box::new(PhysicalRelExpr::Sort {
+            src,
+            cols: vec![(1, false, true)],
+            tree_hash: None,
+        })
+    }
+
+    fn project(src: Box<PhysicalRelExpr>) -> Box<PhysicalRelExpr> {
+        Box::new(PhysicalRelExpr::Project {
+            src,
+            cols: vec![2],
+            tree_hash: None,
+        })
+    }
+
+    fn limit(src: Box<PhysicalRelExpr>, limit: usize) -> PhysicalRelExpr {
+        PhysicalRelExpr::Limit {
+            src,
+            limit,
+            tree_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_sort_below_projection_becomes_top_n() {
+        let mut plan = limit(project(sort(scan())), 10);
+        use_top_n(&mut plan, 100);
+        let PhysicalRelExpr::Project { src, .. } = plan else {
+            panic!("expected a projection: {:?}", plan);
+        };
+        let PhysicalRelExpr::TopN {
+            src, cols, limit, ..
+        } = *src
+        else {
+            panic!("expected a top-n: {:?}", src);
+        };
+        assert_eq!(cols, vec![(1, false, true)]);
+        assert_eq!(limit, 10);
+        assert!(matches!(*src, PhysicalRelExpr::Scan { .. }));
+    }
+
+    #[test]
+    fn test_sort_is_kept() {
+        // Above the maximum, or disabled.
+        for max_rows in [5, 0] {
+            let mut plan = limit(sort(scan()), 10);
+            use_top_n(&mut plan, max_rows);
+            assert!(matches!(plan, PhysicalRelExpr::Limit { .. }));
+        }
+        // DISTINCT may drop rows between the sort and the limit.
+        let distinct = Box::new(PhysicalRelExpr::HashDistinct {
+            src: sort(scan()),
+            tree_hash: None,
+        });
+        let mut plan = limit(distinct, 10);
+        use_top_n(&mut plan, 100);
+        assert!(matches!(plan, PhysicalRelExpr::Limit { .. }));
+        // Without a sort the limit stays.
+        let mut plan = limit(project(scan()), 10);
+        use_top_n(&mut plan, 100);
+        assert!(matches!(plan, PhysicalRelExpr::Limit { .. }));
+    }
+}
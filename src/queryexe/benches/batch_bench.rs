@@ -0,0 +1,105 @@
+//! Runs a selection and a projection over a scan of a table with millions of
+//! rows, planned as for a query, once pulling a tuple at a time with next() and
+//! once in batches with next_batch().
+
+use common::ids::TransactionId;
+use common::logical_expr::prelude::Expression;
+use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use common::testutil::{load_table, ColumnDist, TableGen};
+use common::{BinaryOp, DataType, TableSchema};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use queryexe::opiterator::{OpIterator, BATCH_SIZE};
+use queryexe::query::planner::physical_plan_to_op_iterator;
+use queryexe::testutil::TestSetup;
+
+const ROWS: usize = 2_000_000;
+
+pub fn batch_bench(c: &mut Criterion) {
+    let setup = TestSetup::new_empty();
+    let schema = TableSchema::from_vecs(vec!["a", "b"], vec![DataType::BigInt; 2]);
+    let gen = TableGen::new(schema, 1)
+        .column(
+            "a",
+            ColumnDist::Uniform {
+                min: 0,
+                max: 4_000_000,
+            },
+        )
+        .column("b", ColumnDist::Sequential { start: 0 });
+    let managers = setup.managers;
+    let (table, _) = load_table(
+        &setup.catalog,
+        managers.sm,
+        managers.stats,
+        "big",
+        &gen,
+        ROWS,
+    )
+    .unwrap();
+    // SELECT b FROM big WHERE a < b, a comparison of columns that stays in a
+    // filter above the scan.
+    let plan = PhysicalRelExpr::Project {
+        cols: vec![1],
+        src: Box::new(PhysicalRelExpr::Select {
+            predicates: vec![Expression::binary(
+                BinaryOp::Lt,
+                Expression::col_ref(0),
+                Expression::col_ref(1),
+            )],
+            src: Box::new(PhysicalRelExpr::Scan {
+                cid: table.c_id,
+                table_name: "big".to_string(),
+                column_names: vec![0, 1],
+                tree_hash: None,
+            }),
+            tree_hash: None,
+        }),
+        tree_hash: None,
+    };
+    let open = || -> Box<dyn OpIterator> {
+        let tid = TransactionId::new();
+        let mut iter =
+            physical_plan_to_op_iterator(managers, &setup.catalog, &plan, tid, 0).unwrap();
+        iter.configure(false);
+        iter.open().unwrap();
+        assert!(iter.batches());
+        iter
+    };
+
+    let mut group = c.benchmark_group("select_project_scan");
+    group.sample_size(10);
+    group.bench_function("rows", |b| {
+        b.iter(|| {
+            let mut iter = open();
+            let mut rows = 0;
+            while let Some(t) = iter.next().unwrap() {
+                black_box(t);
+                rows += 1;
+            }
+            iter.close().unwrap();
+            rows
+        })
+    });
+    group.bench_function("batches", |b| {
+        b.iter(|| {
+            let mut iter = open();
+            let mut rows = 0;
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            loop {
+                iter.next_batch(&mut batch, BATCH_SIZE).unwrap();
+                if batch.is_empty() {
+                    break;
+                }
+                rows += batch.len();
+                black_box(&batch);
+                batch.clear();
+            }
+            iter.close().unwrap();
+            rows
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, batch_bench);
+criterion_main!(benches);
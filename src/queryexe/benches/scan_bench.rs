@@ -0,0 +1,103 @@
+//! Scans a wide table with a predicate that matches a single row, once with the
+//! predicate in a filter above the scan and once pushed into the scan, which
+//! checks it before decoding the records. Then reads a single column of every
+//! row, once by decoding whole records and once by decoding only that column.
+
+use common::ids::TransactionId;
+use common::logical_expr::prelude::Expression;
+use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use common::table::TableInfo;
+use common::testutil::{load_table, ColumnDist, TableGen};
+use common::{BinaryOp, DataType, Field, TableSchema};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use queryexe::opiterator::{Filter, OpIterator, ScanPredicate, SeqScan};
+use queryexe::query::planner::convert_expr_to_bytecode;
+use queryexe::testutil::TestSetup;
+use queryexe::Managers;
+
+const COLUMNS: usize = 32;
+const ROWS: usize = 20_000;
+const KEY: i64 = 12_345;
+
+fn count(mut iter: Box<dyn OpIterator>) -> usize {
+    iter.configure(false);
+    iter.open().unwrap();
+    let mut rows = 0;
+    while iter.next().unwrap().is_some() {
+        rows += 1;
+    }
+    iter.close().unwrap();
+    rows
+}
+
+fn scan(managers: &'static Managers, table: &TableInfo) -> SeqScan {
+    let tid = TransactionId::new();
+    SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None)
+}
+
+pub fn scan_bench(c: &mut Criterion) {
+    let setup = TestSetup::new_empty();
+    let names: Vec<String> = (0..COLUMNS).map(|i| format!("c{}", i)).collect();
+    let schema = TableSchema::from_vecs(
+        names.iter().map(String::as_str).collect(),
+        vec![DataType::BigInt; COLUMNS],
+    );
+    let mut gen = TableGen::new(schema, 1).column("c0", ColumnDist::Sequential { start: 0 });
+    for name in &names[1..] {
+        gen = gen.column(
+            name,
+            ColumnDist::Uniform {
+                min: 0,
+                max: 1_000_000,
+            },
+        );
+    }
+    let managers = setup.managers;
+    let (table, _) = load_table(
+        &setup.catalog,
+        managers.sm,
+        managers.stats,
+        "wide",
+        &gen,
+        ROWS,
+    )
+    .unwrap();
+
+    c.bench_function("select_above_scan", |b| {
+        b.iter(|| {
+            let pred = Expression::<PhysicalRelExpr>::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(Expression::ColRef { id: 0 }),
+                right: Box::new(Expression::Field {
+                    val: Field::BigInt(KEY),
+                }),
+            };
+            let expr = convert_expr_to_bytecode(pred, None).unwrap();
+            let filter = Filter::new(expr, table.schema.clone(), Box::new(scan(managers, &table)));
+            assert_eq!(count(black_box(Box::new(filter))), 1);
+        })
+    });
+
+    c.bench_function("pushed_into_scan", |b| {
+        b.iter(|| {
+            let mut predicate = ScanPredicate::default();
+            predicate.add(0, BinaryOp::Eq, Field::BigInt(KEY));
+            let scan = scan(managers, &table).with_predicate(predicate);
+            assert_eq!(count(black_box(Box::new(scan))), 1);
+        })
+    });
+
+    c.bench_function("decode_all_columns", |b| {
+        b.iter(|| assert_eq!(count(black_box(Box::new(scan(managers, &table)))), ROWS))
+    });
+
+    c.bench_function("decode_one_column", |b| {
+        b.iter(|| {
+            let scan = scan(managers, &table).with_columns(vec![0]);
+            assert_eq!(count(black_box(Box::new(scan))), ROWS);
+        })
+    });
+}
+
+criterion_group!(benches, scan_bench);
+criterion_main!(benches);
@@ -1,20 +1,23 @@
 #[macro_use]
 extern crate log;
 
+pub mod lifecycle;
 pub mod mutator;
 pub mod opiterator;
 pub mod query;
 pub mod stats;
+pub mod temp_space;
 pub mod testutil;
 
+use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::lifecycle::{Component, LifecycleError};
 use crate::stats::reservoir_stat_manager::ReservoirStatManager;
+use crate::temp_space::{TempSpace, TempSpaceRef};
 use common::physical::{config::ServerConfig, small_string::StringManager};
-use common::traits::stat_manager_trait::StatManagerTrait;
-use common::traits::storage_trait::StorageTrait;
-use common::traits::transaction_manager_trait::TransactionManagerTrait;
-use common::{prelude::*, MANAGERS_DIR_NAME};
+use common::MANAGERS_DIR_NAME;
 pub use index::IndexManager;
 pub use storage::{StorageManager, STORAGE_DIR};
 pub use txn_manager::mock_tm::MockTransactionManager as TransactionManager;
@@ -31,6 +34,8 @@ pub struct Managers {
     pub im: &'static IndexManager,
     pub stats: &'static ReservoirStatManager,
     pub strm: &'static StringManager,
+    /// Temporary files of the operators of all statements.
+    pub temp: TempSpaceRef,
     pub path: PathBuf,
 }
 
@@ -52,26 +57,70 @@ impl Managers {
             im,
             stats,
             strm,
+            temp: Arc::new(TempSpace::from_config(config)),
             path,
         }
     }
 
-    pub fn shutdown(&self) {
-        // reverse order of boot-up just to be safe
-        self.strm.shutdown().unwrap();
-        self.stats.shutdown().unwrap(); // refer to this to see how and to where things are being serialized
-        self.im.shutdown().unwrap();
-        self.tm.shutdown().unwrap();
-        self.sm.shutdown();
+    /// The managers in the order they are shut down and reset, the reverse of
+    /// boot-up. The storage manager comes last, so that it flushes what the
+    /// others wrote through it.
+    fn components(&self) -> [Component<'_>; 5] {
+        [
+            Component {
+                name: "strings",
+                manager: self.strm,
+                persist_dir: None,
+            },
+            Component {
+                // refer to this to see how and to where things are being serialized
+                name: "stats",
+                manager: self.stats,
+                persist_dir: Some(self.path.clone()),
+            },
+            Component {
+                name: "index",
+                manager: self.im,
+                persist_dir: None,
+            },
+            Component {
+                name: "transactions",
+                manager: self.tm,
+                persist_dir: None,
+            },
+            Component {
+                name: "storage",
+                manager: self.sm,
+                persist_dir: Some(self.config.db_path.join(STORAGE_DIR)),
+            },
+        ]
     }
 
-    pub fn reset(&self) -> Result<(), FairyError> {
-        // not responsible for clearing serialized data. see caller for that.
-        self.strm.reset()?;
-        self.stats.reset()?;
-        self.im.reset()?;
-        self.tm.reset()?;
-        self.sm.reset()?;
-        Ok(())
+    /// Shuts down every manager, even if one before it fails, after checking
+    /// that their directories are writable.
+    pub fn shutdown(&self) -> Result<(), LifecycleError> {
+        lifecycle::shutdown_all(&self.components())
+    }
+
+    /// Resets every manager, even if one before it fails, and removes the state
+    /// they persisted.
+    pub fn reset(&self) -> Result<(), LifecycleError> {
+        let mut failures = match lifecycle::reset_all(&self.components()) {
+            Ok(()) => Vec::new(),
+            Err(e) => e.failures,
+        };
+        // Fails if the directory was never created, which is fine.
+        fs::remove_dir_all(&self.path).ok();
+        if let Err(e) = fs::create_dir_all(&self.path) {
+            failures.push(("stats", e.into()));
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(LifecycleError {
+                action: "Reset",
+                failures,
+            })
+        }
     }
 }
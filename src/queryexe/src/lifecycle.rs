@@ -0,0 +1,254 @@
+//! Shutting down and resetting the managers. Every manager is tried even when
+//! an earlier one fails, and the failures are reported together.
+
+use crate::stats::reservoir_stat_manager::ReservoirStatManager;
+use crate::{IndexManager, StorageManager, TransactionManager};
+use common::physical::small_string::StringManager;
+use common::traits::stat_manager_trait::StatManagerTrait;
+use common::traits::storage_trait::StorageTrait;
+use common::traits::transaction_manager_trait::TransactionManagerTrait;
+use common::FairyError;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A manager that persists its state on shutdown and clears it on reset.
+pub trait ManagerLifecycle {
+    fn shutdown(&self) -> Result<(), FairyError>;
+
+    fn reset(&self) -> Result<(), FairyError>;
+}
+
+/// A manager as `Managers` shuts it down or resets it.
+pub struct Component<'a> {
+    /// Name of the manager in errors.
+    pub name: &'static str,
+    pub manager: &'a dyn ManagerLifecycle,
+    /// Directory the manager writes its state to on shutdown, if any.
+    pub persist_dir: Option<PathBuf>,
+}
+
+/// The managers that failed a shutdown or a reset, with their errors.
+#[derive(Debug)]
+pub struct LifecycleError {
+    /// "Shutdown" or "Reset".
+    pub action: &'static str,
+    /// Name and error of each failed manager, in the order they were tried.
+    pub failures: Vec<(&'static str, FairyError)>,
+}
+
+impl LifecycleError {
+    /// Names of the managers that failed.
+    pub fn components(&self) -> Vec<&'static str> {
+        self.failures.iter().map(|(name, _)| *name).collect()
+    }
+}
+
+impl fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed for ", self.action)?;
+        for (i, (name, e)) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", name, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<LifecycleError> for FairyError {
+    fn from(e: LifecycleError) -> Self {
+        FairyError::FairyError(e.to_string())
+    }
+}
+
+/// Shuts down `components` in order. The persistence directories are checked
+/// first, and a manager whose directory is not writable is not shut down, so
+/// that it does not leave a partial file behind. The others are shut down
+/// whether or not the ones before them failed.
+pub fn shutdown_all(components: &[Component]) -> Result<(), LifecycleError> {
+    let mut failures = Vec::new();
+    let mut writable = Vec::with_capacity(components.len());
+    for c in components {
+        let check = c.persist_dir.as_deref().map_or(Ok(()), check_writable);
+        writable.push(check.is_ok());
+        if let Err(e) = check {
+            failures.push((c.name, e));
+        }
+    }
+    for (c, writable) in components.iter().zip(writable) {
+        if writable {
+            if let Err(e) = c.manager.shutdown() {
+                failures.push((c.name, e));
+            }
+        }
+    }
+    finish("Shutdown", failures)
+}
+
+/// Resets `components` in order, whether or not the ones before them failed.
+pub fn reset_all(components: &[Component]) -> Result<(), LifecycleError> {
+    let failures = components
+        .iter()
+        .filter_map(|c| c.manager.reset().err().map(|e| (c.name, e)))
+        .collect();
+    finish("Reset", failures)
+}
+
+fn finish(
+    action: &'static str,
+    failures: Vec<(&'static str, FairyError)>,
+) -> Result<(), LifecycleError> {
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(LifecycleError { action, failures })
+    }
+}
+
+/// Checks that files can be created in `dir`, creating it if needed.
+fn check_writable(dir: &Path) -> Result<(), FairyError> {
+    fs::create_dir_all(dir)
+        .and_then(|_| tempfile::tempfile_in(dir))
+        .map(|_| ())
+        .map_err(|e| FairyError::IOError(format!("{} is not writable: {}", dir.display(), e)))
+}
+
+impl ManagerLifecycle for StorageManager {
+    fn shutdown(&self) -> Result<(), FairyError> {
+        StorageTrait::shutdown(self)
+    }
+
+    fn reset(&self) -> Result<(), FairyError> {
+        StorageTrait::reset(self)
+    }
+}
+
+impl ManagerLifecycle for TransactionManager {
+    fn shutdown(&self) -> Result<(), FairyError> {
+        TransactionManagerTrait::shutdown(self)
+    }
+
+    fn reset(&self) -> Result<(), FairyError> {
+        TransactionManagerTrait::reset(self)
+    }
+}
+
+impl ManagerLifecycle for IndexManager {
+    fn shutdown(&self) -> Result<(), FairyError> {
+        IndexManager::shutdown(self)
+    }
+
+    fn reset(&self) -> Result<(), FairyError> {
+        IndexManager::reset(self)
+    }
+}
+
+impl ManagerLifecycle for ReservoirStatManager {
+    fn shutdown(&self) -> Result<(), FairyError> {
+        StatManagerTrait::shutdown(self)
+    }
+
+    fn reset(&self) -> Result<(), FairyError> {
+        StatManagerTrait::reset(self)
+    }
+}
+
+impl ManagerLifecycle for StringManager {
+    fn shutdown(&self) -> Result<(), FairyError> {
+        StringManager::shutdown(self)
+    }
+
+    fn reset(&self) -> Result<(), FairyError> {
+        StringManager::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Manager that records its shutdown, and fails it if `fails` is set.
+    #[derive(Default)]
+    struct Fake {
+        fails: bool,
+        shut_down: AtomicBool,
+    }
+
+    impl ManagerLifecycle for Fake {
+        fn shutdown(&self) -> Result<(), FairyError> {
+            self.shut_down.store(true, Ordering::SeqCst);
+            if self.fails {
+                return Err(FairyError::IOError("cannot write stats".to_string()));
+            }
+            Ok(())
+        }
+
+        fn reset(&self) -> Result<(), FairyError> {
+            Ok(())
+        }
+    }
+
+    fn component<'a>(name: &'static str, manager: &'a Fake, dir: Option<&Path>) -> Component<'a> {
+        Component {
+            name,
+            manager,
+            persist_dir: dir.map(Path::to_path_buf),
+        }
+    }
+
+    #[test]
+    fn test_failure_does_not_stop_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let strings = Fake::default();
+        let stats = Fake {
+            fails: true,
+            ..Fake::default()
+        };
+        let storage = Fake::default();
+        let err = shutdown_all(&[
+            component("strings", &strings, None),
+            component("stats", &stats, Some(dir.path())),
+            component("storage", &storage, Some(&dir.path().join("storage"))),
+        ])
+        .unwrap_err();
+        assert!(storage.shut_down.load(Ordering::SeqCst));
+        assert!(strings.shut_down.load(Ordering::SeqCst));
+        assert_eq!(err.components(), ["stats"]);
+        assert_eq!(
+            err.to_string(),
+            "Shutdown failed for stats: cannot write stats"
+        );
+    }
+
+    #[test]
+    fn test_unwritable_dir_is_checked_first() {
+        let dir = tempfile::tempdir().unwrap();
+        // A file where the directory of the stats should be.
+        let stats_dir = dir.path().join("stats");
+        fs::write(&stats_dir, "").unwrap();
+        let stats = Fake::default();
+        let storage = Fake::default();
+        let err = shutdown_all(&[
+            component("stats", &stats, Some(&stats_dir)),
+            component("storage", &storage, Some(dir.path())),
+        ])
+        .unwrap_err();
+        assert_eq!(err.components(), ["stats"]);
+        assert!(!stats.shut_down.load(Ordering::SeqCst));
+        assert!(storage.shut_down.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_reset_removes_persisted_state() {
+        let managers = crate::testutil::new_test_managers();
+        let stat_file = managers.path.join("stats.json");
+        fs::create_dir_all(&managers.path).unwrap();
+        fs::write(&stat_file, "{}").unwrap();
+        managers.reset().unwrap();
+        assert!(managers.path.is_dir());
+        assert!(!stat_file.exists());
+    }
+}
@@ -1,44 +1,92 @@
+use crate::query::translate_and_validate::number_literal;
 use crate::Managers;
 
 use common::{
-    datatypes::{default_decimal_precision, default_decimal_scale},
     prelude::*,
     traits::storage_trait::StorageTrait,
     traits::{stat_manager_trait::StatManagerTrait, state_tracker_trait::StateTrackerTrait},
     tuple::ConvertedResult,
-    ConversionError,
+    ConversionError, MAX_TUPLE_SIZE,
 };
-use sqlparser::ast::{Value, Values};
+use sqlparser::ast::{Expr, Value, Values};
 
+/// Inserts tuples into a table. If the table has `txn_stamps`, the header of
+/// every tuple is set to the id of the inserting transaction. Fails, inserting
+/// none, if the tuples would repeat a value of a unique key of the table.
+/// Returns the ids of the inserted records.
 pub(crate) fn insert_validated_tuples(
-    table_id: ContainerId,
-    tuples: &Vec<Tuple>,
+    table: &TableInfo,
+    tuples: &mut [Tuple],
     txn_id: TransactionId,
     managers: &'static Managers,
-) -> Result<usize, FairyError> {
+) -> Result<Vec<ValueId>, FairyError> {
+    let table_id = table.c_id;
     let mut tuples_bytes = Vec::new();
-    for t in tuples {
-        tuples_bytes.push(t.to_bytes());
+    for t in tuples.iter_mut() {
+        if table.txn_stamps {
+            t.tid = txn_id.id();
+        }
+        let bytes = t.to_bytes();
+        if bytes.len() > MAX_TUPLE_SIZE {
+            return Err(FairyError::ExecutionError(format!(
+                "Row of {} bytes exceeds the page limit of {} bytes",
+                bytes.len(),
+                MAX_TUPLE_SIZE
+            )));
+        }
+        tuples_bytes.push(bytes);
     }
-    let inserted = managers.sm.insert_values(table_id, tuples_bytes, txn_id);
-    info!("TODO call tm and im for insert_values");
-    let insert_count = inserted.len();
-    if insert_count == tuples.len() {
+    let inserted = managers.im.insert_unique(table, tuples, txn_id, || {
+        Ok(managers.sm.insert_values(table_id, tuples_bytes, txn_id))
+    })?;
+    info!("TODO call tm for insert_values");
+    if inserted.len() == tuples.len() {
         for (t, v) in tuples.iter().zip(inserted.iter()) {
             managers.stats.new_record(t, *v)?;
         }
         managers.stats.set_ts(table_id, txn_id.id());
-        Ok(insert_count)
+        Ok(inserted)
     } else {
         Err(FairyError::ExecutionError(format!(
             "Attempting to insert {} tuples and only {} were inserted",
             tuples.len(),
-            insert_count
+            inserted.len()
         )))
     }
 }
 
-/// Check new or updated records to ensure that they do not break any constraints
+/// Deletes the records `value_ids` of a table. The statistics follow the deletes.
+pub(crate) fn delete_records(
+    value_ids: &[ValueId],
+    txn_id: TransactionId,
+    managers: &'static Managers,
+) -> Result<usize, FairyError> {
+    for id in value_ids {
+        managers.sm.delete_value(*id, txn_id)?;
+        managers.stats.deleted_record(id)?;
+        managers.stats.set_ts(id.container_id, txn_id.id());
+    }
+    Ok(value_ids.len())
+}
+
+/// Deletes all the records of a table at once, starting its statistics over.
+/// Returns the number of records deleted.
+pub(crate) fn truncate_table(
+    table_id: ContainerId,
+    schema: &TableSchema,
+    txn_id: TransactionId,
+    managers: &'static Managers,
+) -> Result<usize, FairyError> {
+    let deleted = managers.sm.truncate_container(table_id)?;
+    managers.stats.remove_table(table_id)?;
+    managers.stats.register_table(table_id, schema.clone())?;
+    managers.stats.set_ts(table_id, txn_id.id());
+    Ok(deleted)
+}
+
+/// Check new or updated records to ensure that they do not break any constraints.
+/// With `col_order`, records hold the values of those columns of the schema,
+/// in that order, and the other columns take their default.
 pub(crate) fn validate_tuples(
     _table_id: &ContainerId,
     schema: &TableSchema,
@@ -46,25 +94,40 @@ pub(crate) fn validate_tuples(
     mut values: ConvertedResult,
     _txn_id: &TransactionId,
 ) -> Result<ConvertedResult, FairyError> {
-    if col_order.is_some() {
-        return Err(FairyError::FairyError(String::from(
-            "Col ordering not supported",
-        )));
-    }
-    let mut values_to_remove: Vec<(usize, Vec<ConversionError>)> = Vec::new();
-    warn!("PK, FK, Unique constraints not checked");
-    for (i, rec) in values.converted.iter().enumerate() {
+    warn!("FK constraints not checked");
+    let expected = col_order.as_ref().map_or(schema.size(), Vec::len);
+    let mut valid = Vec::with_capacity(values.converted.len());
+    for (i, mut rec) in std::mem::take(&mut values.converted)
+        .into_iter()
+        .enumerate()
+    {
+        let mut errors = Vec::new();
+        if rec.len() != expected {
+            errors.push(ConversionError::FieldConstraintError(
+                rec.len(),
+                format!("expected {} values", expected),
+            ));
+        } else if let Some(order) = &col_order {
+            let mut row: Vec<Field> = schema
+                .attributes()
+                .map(|attr| attr.default.clone().unwrap_or(Field::Null))
+                .collect();
+            for (j, field) in order.iter().zip(rec.field_vals.drain(..)) {
+                row[*j] = field;
+            }
+            rec.field_vals = row;
+        }
+        // Dates and timestamps are written as strings, which only the schema
+        // tells apart.
+        for (field, attr) in rec.field_vals.iter_mut().zip(schema.attributes()) {
+            *field = std::mem::replace(field, Field::Null).coerce_to(&attr.dtype)?;
+        }
         for (j, (field, attr)) in (rec.field_vals()).zip(schema.attributes()).enumerate() {
             if let Field::Null = field {
-                match attr.constraint {
-                    common::Constraint::NotNull
-                    | common::Constraint::UniqueNotNull
-                    | common::Constraint::PrimaryKey
-                    | common::Constraint::NotNullFKey(_) => {
-                        values_to_remove.push((i, vec![ConversionError::NullFieldNotAllowed(j)]));
-                    }
-                    _ => continue, // Null value so nothing to check
+                if !attr.is_nullable() {
+                    errors.push(ConversionError::NullFieldNotAllowed(j));
                 }
+                continue;
             }
             match (&attr.dtype, field) {
                 (DataType::BigInt, Field::BigInt(_v)) => {
@@ -76,26 +139,83 @@ pub(crate) fn validate_tuples(
                 (DataType::Date, Field::Date(_v)) => {
                     // Nothing for now
                 }
+                (DataType::Timestamp, Field::Timestamp(_v)) => {
+                    // Nothing for now
+                }
                 (DataType::Decimal(_, _), Field::Decimal(_, _)) => {
                     // Nothing for now
                 }
+                (DataType::Float64, Field::Float(_)) => {
+                    // Nothing for now
+                }
                 (DataType::Bool, Field::Bool(_v)) => {
                     // Nothing for now
                 }
                 _ => {
                     debug!("Wrong field: {} for attr type: {}", field, &attr.dtype);
-                    values_to_remove.push((i, vec![ConversionError::WrongType]));
+                    errors.push(ConversionError::WrongType);
                 }
             }
         }
+        // Invalid records are reported with all of their problems
+        if errors.is_empty() {
+            valid.push(rec);
+        } else {
+            values.unconverted.push((i, errors));
+        }
     }
-    // Remove in reverse order records that were invalid
-    for i in values_to_remove.iter().rev() {
-        values.converted.remove(i.0);
-    }
+    values.converted = valid;
     Ok(values)
 }
 
+/// Describes the records `validate_tuples` rejected. A NULL in a column that
+/// does not accept it is reported by the name of the column.
+pub(crate) fn invalid_records_error(
+    schema: &TableSchema,
+    unconverted: &[(usize, Vec<ConversionError>)],
+) -> FairyError {
+    for (i, errors) in unconverted {
+        for error in errors {
+            if let ConversionError::NullFieldNotAllowed(j) = error {
+                let name = schema.get_attribute(*j).map_or("", |attr| attr.name());
+                return FairyError::ExecutionError(format!(
+                    "Null value in column {} of row {} violates the not-null constraint",
+                    name,
+                    i + 1
+                ));
+            }
+        }
+    }
+    FairyError::ValidationError(format!("Some records were not valid: {:?}", unconverted))
+}
+
+/// Converts a literal of an insert or a DEFAULT clause. The outer error is for
+/// an expression that is not a literal, the inner one for a literal that does
+/// not convert.
+pub(crate) fn convert_literal(expr: &Expr) -> Result<Result<Field, ConversionError>, FairyError> {
+    match expr {
+        Expr::Value(value) => Ok(match value {
+            //TODO merged - add schema and check type
+            Value::Number(val, _long) => {
+                number_literal(val).map_err(|_| ConversionError::ParseError)
+            }
+            Value::DoubleQuotedString(val) | Value::SingleQuotedString(val) => {
+                Field::from_str_to_string(val).map_err(|_| ConversionError::ParseError)
+            }
+            Value::Boolean(b) => Ok(Field::Bool(*b)),
+            Value::Null => Ok(Field::Null),
+            _ => Err(ConversionError::UnsupportedType),
+        }),
+        Expr::TypedString {
+            data_type: sqlparser::ast::DataType::Date,
+            value,
+        } => Ok(Ok(Field::from_str_to_date(value)?)),
+        _ => Err(FairyError::FairyError(String::from(
+            "Only values supported in insert",
+        ))),
+    }
+}
+
 /// Convert data from SQL parser insert and convert to internal representation
 pub(crate) fn convert_insert_vals(values: &Values) -> Result<ConvertedResult, FairyError> {
     let mut res = ConvertedResult {
@@ -106,48 +226,9 @@ pub(crate) fn convert_insert_vals(values: &Values) -> Result<ConvertedResult, Fa
     for (i, val) in values.rows.iter().enumerate() {
         let mut fields = Vec::new();
         for field in val {
-            if let sqlparser::ast::Expr::Value(value) = field {
-                match value {
-                    Value::Number(val, _long) => {
-                        let parts = val.split('.').collect::<Vec<&str>>();
-                        if parts.len() > 2 {
-                            res.unconverted.push((i, vec![ConversionError::ParseError]));
-                        } else if parts.len() == 2 {
-                            if let Ok(field) = Field::from_str_to_decimal(
-                                val,
-                                default_decimal_precision(),
-                                default_decimal_scale(),
-                            ) {
-                                fields.push(field);
-                            } else {
-                                res.unconverted.push((i, vec![ConversionError::ParseError]));
-                            }
-                        } else if let Ok(i) = Field::parse_int_from_str::<i64>(val) {
-                            //TODO merged - add schema and check type
-                            fields.push(Field::BigInt(i));
-                        } else {
-                            res.unconverted.push((i, vec![ConversionError::ParseError]));
-                        }
-                    }
-                    Value::DoubleQuotedString(val) | Value::SingleQuotedString(val) => {
-                        if let Ok(field) = Field::from_str_to_string(val) {
-                            fields.push(field);
-                        } else {
-                            res.unconverted.push((i, vec![ConversionError::ParseError]));
-                        }
-                    }
-                    Value::Null => {
-                        fields.push(Field::Null);
-                    }
-                    _ => {
-                        res.unconverted
-                            .push((i, vec![ConversionError::UnsupportedType]));
-                    }
-                }
-            } else {
-                return Err(FairyError::FairyError(String::from(
-                    "Only values supported in insert",
-                )));
+            match convert_literal(field)? {
+                Ok(field) => fields.push(field),
+                Err(e) => res.unconverted.push((i, vec![e])),
             }
         }
         res.converted.push(Tuple::new(fields));
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Change of strategy a join makes at runtime because one of its inputs turned
+/// out much larger than estimated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adaptation {
+    /// A hash join built its table over the probe side and probed it with the
+    /// planned build side.
+    SwappedBuildSide,
+    /// A nested loop join hashed its inner side after the first pass and probed
+    /// it for the remaining outer rows.
+    HashedInnerSide,
+}
+
+impl fmt::Display for Adaptation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Adaptation::SwappedBuildSide => write!(f, "swapped build side"),
+            Adaptation::HashedInnerSide => write!(f, "switched to hash join"),
+        }
+    }
+}
+
+/// Decides when an adaptive join changes strategy and records the decision so
+/// that it can be reported after the query ran.
+#[derive(Debug, Clone)]
+pub struct AdaptivePolicy {
+    /// Estimated rows of the input that is materialized: the build side of a
+    /// hash join or the inner side of a nested loop join.
+    estimated_rows: usize,
+    /// Estimated rows of the other input, if known.
+    other_estimated_rows: Option<usize>,
+    /// How many times larger than estimated the input must be.
+    factor: f64,
+    decision: Arc<Mutex<Option<Adaptation>>>,
+}
+
+impl AdaptivePolicy {
+    pub fn new(estimated_rows: usize, other_estimated_rows: Option<usize>, factor: f64) -> Self {
+        Self {
+            estimated_rows,
+            other_estimated_rows,
+            factor,
+            decision: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns true if `actual_rows` exceeds the estimate by more than the factor.
+    /// An estimate of zero rows counts as one.
+    pub fn exceeded(&self, actual_rows: usize) -> bool {
+        actual_rows as f64 > self.estimated_rows.max(1) as f64 * self.factor
+    }
+
+    /// Returns true if the other input is expected to be smaller than
+    /// `actual_rows`, or if there is no estimate for it.
+    pub fn other_side_smaller(&self, actual_rows: usize) -> bool {
+        self.other_estimated_rows
+            .is_none_or(|estimate| estimate < actual_rows)
+    }
+
+    pub fn record(&self, adaptation: Adaptation) {
+        *self.decision.lock().unwrap() = Some(adaptation);
+    }
+
+    /// The adaptation made by the join, if any. Shared by every clone of the policy.
+    pub fn decision(&self) -> Option<Adaptation> {
+        *self.decision.lock().unwrap()
+    }
+}
@@ -1,7 +1,6 @@
 use super::OpIterator;
 use crate::Managers;
-#[allow(unused_imports)]
-use common::datatypes::f_decimal; // For generating a decimal field
+use common::datatypes::{default_decimal_scale, f_decimal, f_float, OrdF64};
 use common::query::bytecode_expr::ByteCodeExpr;
 use common::{AggOp, FairyError, Field, TableSchema, Tuple};
 use std::cmp::{max, min};
@@ -30,12 +29,14 @@ pub struct Aggregate {
     // States (Need to reset on close)
     /// Boolean if the iterator is open.
     open: bool,
-    /// Accumulator for the aggregation. Key:groupby values. Value: (count, aggregated values).
-    acc: HashMap<Vec<Field>, (usize, Vec<Field>)>, // groupby values -> (count, aggregate values)
+    /// Accumulator for the aggregation. Key:groupby values. Value: aggregated values.
+    acc: HashMap<Vec<Field>, Vec<AggState>>, // groupby values -> aggregate values
     /// Accumulator iter
     acc_iter: Vec<Tuple>,
     /// Index of the current tuple in the accumulator iter
     index: usize,
+    /// Most groups held in the accumulator at once.
+    peak_groups: usize,
 }
 
 impl Aggregate {
@@ -71,6 +72,7 @@ impl Aggregate {
             acc: HashMap::new(),
             acc_iter: Vec::new(),
             index: 0,
+            peak_groups: 0,
         }
     }
 
@@ -84,7 +86,13 @@ impl Aggregate {
     /// * `acc` - Current accumulated value.
     fn merge_fields(op: AggOp, field_val: &Field, acc: &mut Field) -> Result<(), FairyError> {
         match op {
+            // NULL values, such as the columns an outer join pads, are not counted.
+            AggOp::Count if *field_val == Field::Null => {}
             AggOp::Count => *acc = (acc.clone() + Field::BigInt(1))?,
+            // NULL values are neither the smallest nor the largest value, and a
+            // group whose values were all NULL so far takes the first other one.
+            AggOp::Max | AggOp::Min if *field_val == Field::Null => {}
+            AggOp::Max | AggOp::Min if *acc == Field::Null => *acc = field_val.clone(),
             AggOp::Max => {
                 let max = max(acc.clone(), field_val.clone());
                 *acc = max;
@@ -93,12 +101,15 @@ impl Aggregate {
                 let min = min(acc.clone(), field_val.clone());
                 *acc = min;
             }
+            // NULL values do not contribute to the running sum, and a group
+            // whose values were all NULL so far has no sum.
+            AggOp::Sum if *field_val == Field::Null => {}
+            AggOp::Sum if *acc == Field::Null => *acc = (Field::BigInt(0) + field_val.clone())?,
             AggOp::Sum => {
                 *acc = (acc.clone() + field_val.clone())?;
             }
-            AggOp::Avg => {
-                *acc = (acc.clone() + field_val.clone())?; // This will be divided by the count later
-            }
+            // Accumulated in a state of their own, see `AggState`.
+            AggOp::Avg | AggOp::StdDev | AggOp::Variance | AggOp::Median => {}
         }
         Ok(())
     }
@@ -122,29 +133,179 @@ impl Aggregate {
             .iter()
             .map(|expr| expr.eval(tuple))
             .collect::<Vec<Field>>();
-        let entry = self.acc.entry(group_key.clone()).or_insert_with(|| {
-            // initial agg fields
-            let mut init = Vec::with_capacity(self.ops.len());
-            for (op, expr) in self.ops.iter().zip(self.agg_expr.iter()) {
-                let first_val = expr.eval(tuple);
-                let f = match op {
-                    AggOp::Count => Field::BigInt(0),
-                    AggOp::Sum | AggOp::Avg => Field::BigInt(0),
-                    AggOp::Max | AggOp::Min => first_val.clone(),
-                };
-                init.push(f);
-            }
-            (0usize, init) // (count, agg fields)
-        });
+        let entry = self
+            .acc
+            .entry(group_key)
+            .or_insert_with(|| init_group(&self.ops));
+        merge_into_group(&self.ops, &self.agg_expr, tuple, entry);
+        self.peak_groups = self.peak_groups.max(self.acc.len());
+    }
 
-        // increment tuple count
-        entry.0 += 1;
+    /// Most groups the operator held at once.
+    pub fn peak_groups(&self) -> usize {
+        self.peak_groups
+    }
+}
 
-        for (i, op) in self.ops.iter().enumerate() {
-            let val = self.agg_expr[i].eval(tuple);
-            Self::merge_fields(*op, &val, &mut entry.1[i]).unwrap();
+/// Running value of an aggregation over the tuples of a group so far.
+#[derive(Clone)]
+pub(super) enum AggState {
+    /// The count, sum, minimum or maximum so far, NULL while there is no value.
+    Field(Field),
+    /// Sum and number of the values of AVG so far.
+    Mean { sum: Field, n: usize },
+    /// Number of values of STDDEV or VARIANCE, their mean and the sum of their
+    /// squared differences from it, updated in one pass with Welford's
+    /// algorithm. `float` is set once a value is a float.
+    Moments {
+        n: usize,
+        mean: f64,
+        m2: f64,
+        float: bool,
+    },
+    /// Values of MEDIAN, which are only ordered once the group is complete.
+    Values { values: Vec<f64>, float: bool },
+}
+
+impl AggState {
+    /// Adds a value of the aggregation `op`. NULL values are skipped.
+    fn merge(&mut self, op: AggOp, val: &Field) -> Result<(), FairyError> {
+        let is_float = matches!(val, Field::Float(_));
+        match self {
+            AggState::Field(acc) => return Aggregate::merge_fields(op, val, acc),
+            AggState::Mean { sum, n } => {
+                if *val != Field::Null {
+                    *n += 1;
+                    return Aggregate::merge_fields(AggOp::Sum, val, sum);
+                }
+            }
+            AggState::Moments { n, mean, m2, float } => {
+                if let Some(x) = val.to_f64() {
+                    *n += 1;
+                    let delta = x - *mean;
+                    *mean += delta / *n as f64;
+                    *m2 += delta * (x - *mean);
+                    *float |= is_float;
+                }
+            }
+            AggState::Values { values, float } => {
+                if let Some(x) = val.to_f64() {
+                    values.push(x);
+                    *float |= is_float;
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+/// Result of STDDEV, VARIANCE or MEDIAN: a float over floats, a decimal of the
+/// default scale, rounded, over other numbers.
+fn statistic(value: f64, float: bool) -> Field {
+    if float {
+        f_float(value)
+    } else {
+        let scale = default_decimal_scale();
+        Field::Decimal((value * 10f64.powi(scale as i32)).round() as i64, scale)
+    }
+}
+
+/// Middle value of `values`, or the mean of the two middle ones for an even
+/// number of values.
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(sorted[mid]),
+        _ => Some((sorted[mid - 1] + sorted[mid]) / 2.0),
+    }
+}
+
+/// Initial aggregated values of a group, before any of its tuples.
+pub(super) fn init_group(ops: &[AggOp]) -> Vec<AggState> {
+    let mut init = Vec::with_capacity(ops.len());
+    for op in ops {
+        let f = match op {
+            AggOp::Count => AggState::Field(Field::BigInt(0)),
+            AggOp::Sum | AggOp::Max | AggOp::Min => AggState::Field(Field::Null),
+            AggOp::Avg => AggState::Mean {
+                sum: Field::Null,
+                n: 0,
+            },
+            AggOp::StdDev | AggOp::Variance => AggState::Moments {
+                n: 0,
+                mean: 0.0,
+                m2: 0.0,
+                float: false,
+            },
+            AggOp::Median => AggState::Values {
+                values: Vec::new(),
+                float: false,
+            },
+        };
+        init.push(f);
+    }
+    init
+}
+
+/// Adds `tuple` to a group's aggregated values.
+pub(super) fn merge_into_group(
+    ops: &[AggOp],
+    agg_expr: &[ByteCodeExpr],
+    tuple: &Tuple,
+    group: &mut [AggState],
+) {
+    for (i, op) in ops.iter().enumerate() {
+        let val = agg_expr[i].eval(tuple);
+        group[i].merge(*op, &val).unwrap();
+    }
+}
+
+/// Output row of a group: the group by values followed by the aggregates.
+pub(super) fn group_row(ops: &[AggOp], key: Vec<Field>, agg: &[AggState]) -> Tuple {
+    let mut row = key;
+    for (op, state) in ops.iter().zip(agg) {
+        let out_field = match (op, state) {
+            (_, AggState::Field(f)) => f.clone(),
+            (_, AggState::Mean { sum, n }) => match sum {
+                Field::BigInt(v) => f_decimal((*v as f64) / (*n as f64)),
+                Field::Decimal(d, _) => f_decimal((*d as f64) / (*n as f64)),
+                Field::Float(OrdF64(f)) => f_float(f / (*n as f64)),
+                // Only NULL values, or none at all.
+                Field::Null => Field::Null,
+                _ => panic!("AVG on non-numeric"),
+            },
+            // The sample variance is undefined for fewer than two values.
+            (_, AggState::Moments { n, .. }) if *n < 2 => Field::Null,
+            (op, AggState::Moments { n, m2, float, .. }) => {
+                let variance = m2 / (*n - 1) as f64;
+                match op {
+                    AggOp::StdDev => statistic(variance.sqrt(), *float),
+                    _ => statistic(variance, *float),
+                }
+            }
+            (_, AggState::Values { values, float }) => {
+                median(values).map_or(Field::Null, |m| statistic(m, *float))
+            }
+        };
+        row.push(out_field);
     }
+    Tuple::new(row)
+}
+
+/// Output row of an aggregate without group by over no tuples: counts are 0
+/// and the other aggregates NULL.
+pub(super) fn empty_input_row(ops: &[AggOp]) -> Tuple {
+    let row = ops
+        .iter()
+        .map(|op| match op {
+            AggOp::Count => Field::BigInt(0),
+            _ => Field::Null,
+        })
+        .collect();
+    Tuple::new(row)
 }
 
 impl OpIterator for Aggregate {
@@ -165,30 +326,18 @@ impl OpIterator for Aggregate {
             let groups = self
                 .acc
                 .iter()
-                .map(|(k, (cnt, agg))| (k.clone(), *cnt, agg.clone()))
+                .map(|(k, agg)| (k.clone(), agg.clone()))
                 .collect::<Vec<_>>();
             // Might add this groups.sort_by(|(k1, _, _), (k2, _, _)| k1.cmp(k2));
 
             // Output
             self.acc_iter.clear();
-            for (key, cnt, agg) in groups {
-                let mut row = key.clone();
-                for (i, op) in self.ops.iter().enumerate() {
-                    let out_field = match op {
-                        AggOp::Avg => {
-                            let sum_f = &agg[i];
-                            let avg = match sum_f {
-                                Field::BigInt(v) => (*v as f64) / (cnt as f64),
-                                Field::Decimal(d, _) => (*d as f64) / (cnt as f64),
-                                _ => panic!("AVG on non-numeric"),
-                            };
-                            f_decimal(avg)
-                        }
-                        _ => agg[i].clone(),
-                    };
-                    row.push(out_field);
-                }
-                self.acc_iter.push(Tuple::new(row));
+            for (key, agg) in groups {
+                self.acc_iter.push(group_row(&self.ops, key, &agg));
+            }
+            // Without group by, the aggregates have a row even for no input.
+            if self.groupby_expr.is_empty() && self.acc_iter.is_empty() {
+                self.acc_iter.push(empty_input_row(&self.ops));
             }
 
             self.index = 0;
@@ -432,6 +581,133 @@ mod test {
             assert_eq!(t[3], Tuple::new(vec![f_int(2), f_int(5), f_decimal(5.5)]));
         }
 
+        #[test]
+        fn test_statistics() {
+            // Input:
+            // 1 1 3 E
+            // 2 1 3 G
+            // 3 1 4 A
+            // 4 2 4 G
+            // 5 2 5 G
+            // 6 2 5 G
+            let agg = vec![colidx_expr(0), colidx_expr(0), colidx_expr(0)];
+            let ops = vec![AggOp::StdDev, AggOp::Variance, AggOp::Median];
+            let t = run_aggregate(vec![colidx_expr(1)], agg.clone(), ops.clone());
+            // Output:
+            // 1 1.0 1.0 2.0
+            // 2 1.0 1.0 5.0
+            assert_eq!(t.len(), 2);
+            let one = f_decimal(1.0);
+            assert_eq!(
+                t[0],
+                Tuple::new(vec![f_int(1), one.clone(), one.clone(), f_decimal(2.0)])
+            );
+            assert_eq!(
+                t[1],
+                Tuple::new(vec![f_int(2), one.clone(), one, f_decimal(5.0)])
+            );
+
+            // Without group by: sqrt(3.5), rounded to the scale, 3.5 and the
+            // mean of the middle values 3 and 4.
+            let t = run_aggregate(vec![], agg, ops.clone());
+            assert_eq!(
+                t,
+                vec![Tuple::new(vec![
+                    Field::Decimal(18708, 4),
+                    f_decimal(3.5),
+                    f_decimal(3.5)
+                ])]
+            );
+
+            // A single value has no sample variance.
+            let agg = vec![colidx_expr(2), colidx_expr(2), colidx_expr(2)];
+            let t = run_aggregate(vec![colidx_expr(3)], agg, ops);
+            assert_eq!(
+                t[0],
+                Tuple::new(vec![f_str("A"), Field::Null, Field::Null, f_decimal(4.0)])
+            );
+        }
+
+        #[test]
+        fn test_statistics_skip_nulls() {
+            let tuples = [(1, None), (2, Some(1.5)), (2, None), (2, Some(2.5))]
+                .into_iter()
+                .map(|(g, v)| Tuple::new(vec![f_int(g), v.map_or(Field::Null, f_float)]))
+                .collect();
+            let mut iter = Aggregate::new(
+                new_test_managers(),
+                vec![colidx_expr(0)],
+                vec![colidx_expr(1), colidx_expr(1), colidx_expr(1)],
+                vec![AggOp::StdDev, AggOp::Variance, AggOp::Median],
+                TableSchema::new(vec![]),
+                Box::new(TupleIterator::new(tuples, TableSchema::new(vec![]))),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut iter, true).unwrap();
+            // A group without values has none of the statistics, and the
+            // statistics of floats are floats.
+            assert_eq!(
+                t,
+                vec![
+                    Tuple::new(vec![f_int(1), Field::Null, Field::Null, Field::Null]),
+                    Tuple::new(vec![
+                        f_int(2),
+                        f_float(0.5f64.sqrt()),
+                        f_float(0.5),
+                        f_float(2.0)
+                    ]),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_nulls() {
+            // Group 1 has only NULL values, group 2 has NULL values among 2 and 4.
+            let tuples = [(1, None), (1, None), (2, Some(4)), (2, None), (2, Some(2))]
+                .into_iter()
+                .map(|(g, v)| Tuple::new(vec![f_int(g), v.map_or(Field::Null, f_int)]))
+                .collect();
+            let ops = vec![
+                AggOp::Count,
+                AggOp::Sum,
+                AggOp::Avg,
+                AggOp::Min,
+                AggOp::Max,
+                AggOp::StdDev,
+                AggOp::Variance,
+                AggOp::Median,
+            ];
+            let mut iter = Aggregate::new(
+                new_test_managers(),
+                vec![colidx_expr(0)],
+                vec![colidx_expr(1); ops.len()],
+                ops,
+                TableSchema::new(vec![]),
+                Box::new(TupleIterator::new(tuples, TableSchema::new(vec![]))),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut iter, true).unwrap();
+            let mut all_null = vec![f_int(1), f_int(0)];
+            all_null.extend(vec![Field::Null; 7]);
+            assert_eq!(
+                t,
+                vec![
+                    Tuple::new(all_null),
+                    Tuple::new(vec![
+                        f_int(2),
+                        f_int(2),
+                        f_int(6),
+                        f_decimal(3.0),
+                        f_int(2),
+                        f_int(4),
+                        Field::Decimal(14142, 4),
+                        f_decimal(2.0),
+                        f_decimal(3.0),
+                    ]),
+                ]
+            );
+        }
+
         #[test]
         fn test_multi_column_aggregation() {
             // Input:
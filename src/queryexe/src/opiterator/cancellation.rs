@@ -0,0 +1,86 @@
+use common::FairyError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cancellation token of a query, shared by the operators of its plan and the
+/// session that runs it. Cancelling any clone cancels them all. The scans, the
+/// hash join builds and the sorts check it while they read their input, so a
+/// cancelled query stops without reading the rest of its tables.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    /// Start of the query and how long it may run, if it has a timeout.
+    timeout: Option<(Instant, Duration)>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also cancels the query once it ran for `timeout` from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some((Instant::now(), timeout));
+        self
+    }
+
+    /// Drops the timeout, the query runs until it is cancelled.
+    pub fn without_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// Asks the query to stop. The operators fail the next time they check.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails with `QueryCancelled` once the query was cancelled, or with
+    /// `QueryTimeout` naming `operator`, the operator that checks, once it ran
+    /// for its timeout.
+    pub fn check(&self, operator: &str) -> Result<(), FairyError> {
+        if self.is_cancelled() {
+            return Err(FairyError::QueryCancelled);
+        }
+        if let Some((start, timeout)) = self.timeout {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(FairyError::QueryTimeout {
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    operator: operator.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let cancellation = Cancellation::new();
+        assert!(cancellation.check("scan").is_ok());
+        let clone = cancellation.clone();
+        clone.cancel();
+        assert!(matches!(
+            cancellation.check("scan"),
+            Err(FairyError::QueryCancelled)
+        ));
+
+        let timed_out = Cancellation::new().with_timeout(Duration::ZERO);
+        match timed_out.check("sort") {
+            Err(FairyError::QueryTimeout { operator, .. }) => assert_eq!(operator, "sort"),
+            r => panic!("Expected a timeout, got {:?}", r),
+        }
+        let later = Cancellation::new().with_timeout(Duration::from_secs(3600));
+        assert!(later.check("sort").is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+use super::OpIterator;
+use common::{FairyError, Field, TableSchema, Tuple};
+use std::collections::HashSet;
+
+/// Duplicate elimination operator. Returns the first of each set of equal
+/// tuples as it reads them, so the output keeps the order of the input. Two
+/// NULLs are equal here, unlike in predicates.
+pub struct Distinct {
+    // Parameters (No need to reset on close)
+    /// Schema of the child.
+    schema: TableSchema,
+    /// Child operator passing data into operator.
+    child: Box<dyn OpIterator>,
+
+    // States (Need to reset on close)
+    /// Boolean determining if iterator is open.
+    open: bool,
+    /// Values of the tuples returned so far.
+    seen: HashSet<Vec<Field>>,
+}
+
+impl Distinct {
+    /// Distinct constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the child.
+    /// * `child` - Child OpIterator passing data into the operator.
+    pub fn new(schema: TableSchema, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            schema,
+            child,
+            open: false,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+/// Value of the field that equal fields share. Decimals of different scales
+/// may be equal, so they are compared without trailing zeros.
+fn distinct_key(field: &Field) -> Field {
+    match field {
+        Field::Decimal(whole, scale) => {
+            let (mut whole, mut scale) = (*whole, *scale);
+            while scale > 0 && whole % 10 == 0 {
+                whole /= 10;
+                scale -= 1;
+            }
+            Field::Decimal(whole, scale)
+        }
+        field => field.clone(),
+    }
+}
+
+impl OpIterator for Distinct {
+    fn configure(&mut self, will_rewind: bool) {
+        self.child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.child.open()?;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        while let Some(t) = self.child.next()? {
+            let key = t.field_vals.iter().map(distinct_key).collect();
+            if self.seen.insert(key) {
+                return Ok(Some(t));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.child.close()?;
+        self.seen.clear();
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()?;
+        self.seen.clear();
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::datatypes::{f_decimal, f_int, f_str};
+    use common::{Attribute, DataType};
+
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use crate::testutil::execute_iter;
+
+    fn get_iter(tuples: Vec<Vec<Field>>) -> Box<dyn OpIterator> {
+        let schema = TableSchema::new(vec![
+            Attribute::new("a".to_string(), DataType::BigInt),
+            Attribute::new("b".to_string(), DataType::String),
+        ]);
+        let tuples = tuples.into_iter().map(Tuple::new).collect();
+        let mut iter = Box::new(Distinct::new(
+            schema.clone(),
+            Box::new(TupleIterator::new(tuples, schema)),
+        ));
+        iter.configure(false);
+        iter
+    }
+
+    fn run(tuples: Vec<Vec<Field>>) -> Vec<Vec<Field>> {
+        let mut iter = get_iter(tuples);
+        execute_iter(&mut *iter, false)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.field_vals)
+            .collect()
+    }
+
+    #[test]
+    fn test_distinct_keeps_first() {
+        let t = run(vec![
+            vec![f_int(2), f_str("b")],
+            vec![f_int(1), f_str("a")],
+            vec![f_int(2), f_str("b")],
+            vec![f_int(2), f_str("c")],
+            vec![f_int(1), f_str("a")],
+        ]);
+        assert_eq!(
+            t,
+            vec![
+                vec![f_int(2), f_str("b")],
+                vec![f_int(1), f_str("a")],
+                vec![f_int(2), f_str("c")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nulls_are_equal() {
+        let t = run(vec![
+            vec![Field::Null, f_str("a")],
+            vec![Field::Null, f_str("a")],
+            vec![Field::Null, Field::Null],
+            vec![Field::Null, Field::Null],
+        ]);
+        assert_eq!(
+            t,
+            vec![
+                vec![Field::Null, f_str("a")],
+                vec![Field::Null, Field::Null]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decimals_and_dates() {
+        let t = run(vec![
+            vec![f_decimal(1.5), Field::Date(10)],
+            vec![Field::Decimal(150, 2), Field::Date(10)],
+            vec![f_decimal(1.5), Field::Date(11)],
+        ]);
+        assert_eq!(
+            t,
+            vec![
+                vec![f_decimal(1.5), Field::Date(10)],
+                vec![f_decimal(1.5), Field::Date(11)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut iter = get_iter(vec![vec![f_int(1), f_str("a")], vec![f_int(1), f_str("a")]]);
+        iter.configure(true);
+        let t_before = execute_iter(&mut *iter, false).unwrap();
+        iter.rewind().unwrap();
+        let t_after = execute_iter(&mut *iter, false).unwrap();
+        assert_eq!(t_before.len(), 1);
+        assert_eq!(t_before, t_after);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let mut iter = get_iter(vec![]);
+        let _ = iter.next();
+    }
+}
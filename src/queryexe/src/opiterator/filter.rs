@@ -16,6 +16,8 @@ pub struct Filter {
     // States (Need to reset on close)
     /// Boolean determining if iterator is open.
     open: bool,
+    /// Tuples of the child read by next_batch() that are not checked yet.
+    batch: Vec<Tuple>,
 }
 
 impl Filter {
@@ -31,10 +33,24 @@ impl Filter {
             schema,
             predicate,
             child,
+            batch: Vec::new(),
         }
     }
 }
 
+/// Whether all of `predicates` hold over `tuple`. A predicate that evaluates to
+/// NULL does not hold.
+pub(super) fn satisfies(predicates: &[ByteCodeExpr], tuple: &Tuple) -> Result<bool, FairyError> {
+    for pred in predicates {
+        match pred.try_eval(tuple)? {
+            Field::Bool(true) => {}
+            Field::Bool(false) | Field::Null => return Ok(false),
+            _ => return Err(c_err("Predicate did not evaluate to a boolean")),
+        }
+    }
+    Ok(true)
+}
+
 impl OpIterator for Filter {
     fn configure(&mut self, will_rewind: bool) {
         self.child.configure(will_rewind);
@@ -53,25 +69,43 @@ impl OpIterator for Filter {
             panic!("Operator has not been opened")
         }
 
-        let mut res = None;
         while let Some(t) = self.child.next()? {
-            match self.predicate.eval(&t) {
-                Field::Bool(b) => {
-                    if b {
-                        res = Some(t);
-                        break;
-                    }
-                }
-                _ => {
-                    return Err(c_err("Predicate did not evaluate to a boolean"));
+            if satisfies(std::slice::from_ref(&self.predicate), &t)? {
+                return Ok(Some(t));
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        // Reads batches until one has a tuple that satisfies the predicate.
+        loop {
+            self.child.next_batch(&mut self.batch, max)?;
+            if self.batch.is_empty() {
+                return Ok(());
+            }
+            let before = out.len();
+            for t in self.batch.drain(..) {
+                if satisfies(std::slice::from_ref(&self.predicate), &t)? {
+                    out.push(t);
                 }
             }
+            if out.len() > before {
+                return Ok(());
+            }
         }
-        Ok(res)
+    }
+
+    fn batches(&self) -> bool {
+        self.child.batches()
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
         self.child.close()?;
+        self.batch.clear();
         self.open = false;
         Ok(())
     }
@@ -97,8 +131,8 @@ mod test {
 
     use super::*;
     use crate::opiterator::TupleIterator;
-    use crate::testutil::execute_iter;
     use crate::testutil::TestTuples;
+    use crate::testutil::{execute_batches, execute_iter};
 
     fn get_iter(predicate: ByteCodeExpr) -> Box<dyn OpIterator> {
         let setup = TestTuples::new("");
@@ -202,6 +236,43 @@ mod test {
                 Tuple::new(vec![f_int(5), f_int(2), f_int(5), f_str("G")])
             );
         }
+
+        #[test]
+        fn test_batches() {
+            let expected = execute_iter(&mut *get_iter(get_predicate()), false).unwrap();
+            // With a batch of 1, the third tuple fails and the next is read.
+            for max in [1, 2, 3, 10] {
+                let mut iter = get_iter(get_predicate());
+                assert_eq!(execute_batches(&mut *iter, max).unwrap(), expected);
+            }
+        }
+
+        #[test]
+        fn test_null_predicate_matches_nothing() {
+            let mut predicate = ByteCodeExpr::new();
+            predicate.add_code(ByteCodes::PushLit as usize);
+            let i = predicate.add_literal(Field::Null);
+            predicate.add_code(i);
+            assert!(run_filter(predicate).is_empty());
+        }
+
+        #[test]
+        fn test_division_by_zero_is_an_error() {
+            // col(0) / 0 = 1
+            let mut predicate = ByteCodeExpr::new();
+            predicate.add_code(ByteCodes::PushField as usize);
+            predicate.add_code(0);
+            predicate.add_code(ByteCodes::PushLit as usize);
+            let i = predicate.add_literal(Field::BigInt(0));
+            predicate.add_code(i);
+            predicate.add_code(ByteCodes::Div as usize);
+            predicate.add_code(ByteCodes::PushLit as usize);
+            let i = predicate.add_literal(Field::BigInt(1));
+            predicate.add_code(i);
+            predicate.add_code(ByteCodes::Eq as usize);
+            let mut iter = get_iter(predicate);
+            assert!(execute_iter(&mut *iter, true).is_err());
+        }
     }
 
     mod opiterator_test {
@@ -1,11 +1,26 @@
-use super::OpIterator;
+use super::filter::satisfies;
+use super::{Adaptation, AdaptivePolicy, Cancellation, OpIterator};
 use crate::Managers;
 
+use common::logical_expr::prelude::JoinType;
 use common::query::bytecode_expr::ByteCodeExpr;
 use common::{FairyError, Field, TableSchema, Tuple};
 use std::collections::HashMap;
 
-/// Hash equi-join implementation. (You can add any other fields that you think are neccessary)
+/// Hash equi-join implementation.
+///
+/// The hash table is built over one child, keyed by the values of its key
+/// expressions, and probed with the tuples of the other. Tuples with a NULL key
+/// never match. Matching pairs are joined and kept if the residual predicates
+/// hold over the joined tuple.
+///
+/// An outer join pads the tuples of its outer side(s) that matched nothing with
+/// NULLs: unmatched probe tuples once their bucket is exhausted, and unmatched
+/// build tuples, tracked by a flag per tuple, once probing is done.
+///
+/// A semi or anti join returns left tuples only. Its table is always built over
+/// the left child, and the right child is probed until every left tuple has
+/// matched, so that a right child with many matches is not read to the end.
 pub struct HashEqJoin {
     #[allow(dead_code)]
     // Static objects (No need to reset on close)
@@ -13,17 +28,54 @@ pub struct HashEqJoin {
 
     // Parameters (No need to reset on close)
     schema: TableSchema,
-    left_expr: ByteCodeExpr,
-    right_expr: ByteCodeExpr,
+    left_keys: Vec<ByteCodeExpr>,
+    right_keys: Vec<ByteCodeExpr>,
     left_child: Box<dyn OpIterator>,
     right_child: Box<dyn OpIterator>,
-    // TODO: KATHIR add filter here
+    residual: Vec<ByteCodeExpr>,
+    join_type: JoinType,
+    // Whether the table is built over the right child from the start
+    build_right: bool,
+    adaptive: Option<AdaptivePolicy>,
+    will_rewind: bool,
+    cancellation: Cancellation,
 
     // States (Need to reset on close)
     open: bool,
-    join_map: HashMap<Field, Vec<Tuple>>,
+    // Build tuples by key, each with whether it matched a probe tuple
+    join_map: HashMap<Vec<Field>, Vec<(Tuple, bool)>>,
+    // Build tuples with a NULL key, kept only to be returned unmatched
+    null_keyed: Vec<Tuple>,
+    // Build tuples with a key that did not match yet
+    unmatched_keyed: usize,
     current_tuple: Option<Tuple>,
-    current_idx: usize, // Index of the tuple in the current bucket (Vec<Tuple>)
+    current_key: Option<Vec<Field>>,
+    current_idx: usize, // Index of the tuple in the current bucket
+    // Whether the current probe tuple matched a build tuple
+    probe_matched: bool,
+    // Padded build tuples that matched nothing, once probing is done
+    unmatched: Option<std::vec::IntoIter<Tuple>>,
+    // Whether the table was built over the right child, probed with the left
+    swapped: bool,
+    // Probe tuples read ahead, probed before the rest of the probe child: the
+    // left tuples read before swapping, or a batch of the probe child
+    pending: std::vec::IntoIter<Tuple>,
+    // Number of probe tuples read at once, set by next_batch()
+    probe_batch: usize,
+}
+
+/// Values of `keys` over `tuple`, or None if one is NULL, since a NULL key is
+/// equal to no key.
+fn eval_key(keys: &[ByteCodeExpr], tuple: &Tuple) -> Result<Option<Vec<Field>>, FairyError> {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        let value = key.try_eval(tuple)?;
+        if value == Field::Null {
+            return Ok(None);
+        }
+        values.push(value);
+    }
+    Ok(Some(values))
 }
 
 impl HashEqJoin {
@@ -31,15 +83,16 @@ impl HashEqJoin {
     ///
     /// # Arguments
     ///
-    /// * `left_index` - Index of the left field in join condition.
-    /// * `right_index` - Index of the right field in join condition.
+    /// * `schema` - Output schema, the left schema followed by the right one.
+    /// * `left_keys` - Key expressions over the left child.
+    /// * `right_keys` - Key expressions over the right child, equal to the left ones for a match.
     /// * `left_child` - Left child of join operator.
-    /// * `right_child` - Left child of join operator.
+    /// * `right_child` - Right child of join operator.
     pub fn new(
         managers: &'static Managers,
         schema: TableSchema,
-        left_expr: ByteCodeExpr,
-        right_expr: ByteCodeExpr,
+        left_keys: Vec<ByteCodeExpr>,
+        right_keys: Vec<ByteCodeExpr>,
         left_child: Box<dyn OpIterator>,
         right_child: Box<dyn OpIterator>,
     ) -> Self {
@@ -47,21 +100,247 @@ impl HashEqJoin {
             managers,
             open: false,
             schema,
-            left_expr,
-            right_expr,
+            left_keys,
+            right_keys,
             left_child,
             right_child,
+            residual: Vec::new(),
+            join_type: JoinType::Inner,
+            build_right: false,
+            adaptive: None,
+            will_rewind: false,
+            cancellation: Cancellation::default(),
             join_map: HashMap::new(),
+            null_keyed: Vec::new(),
+            unmatched_keyed: 0,
             current_tuple: None,
+            current_key: None,
             current_idx: 0,
+            probe_matched: false,
+            unmatched: None,
+            swapped: false,
+            pending: Vec::new().into_iter(),
+            probe_batch: 1,
         }
     }
+
+    /// Sets the predicates that must also hold over a joined tuple, besides the
+    /// equality of the keys.
+    pub fn with_residual(mut self, residual: Vec<ByteCodeExpr>) -> Self {
+        self.residual = residual;
+        self
+    }
+
+    /// Sets the join type, `JoinType::Inner` by default. The outer join types
+    /// also return the unmatched tuples of their outer side(s), padded. The
+    /// semi and anti joins return the matched and the unmatched left tuples.
+    pub fn with_join_type(mut self, join_type: JoinType) -> Self {
+        self.join_type = join_type;
+        self
+    }
+
+    /// Builds the table over the right child instead of the left one, for a
+    /// right child expected to be smaller. Ignored by semi and anti joins.
+    pub fn with_build_right(mut self) -> Self {
+        self.build_right = true;
+        self
+    }
+
+    /// Lets the join swap its build and probe sides when the left child turns
+    /// out much larger than estimated. A join that will be rewound never swaps,
+    /// since its left child cannot be rewound.
+    pub fn with_adaptation(mut self, policy: AdaptivePolicy) -> Self {
+        self.adaptive = Some(policy);
+        self
+    }
+
+    /// Fails the join once `cancellation` is cancelled or its query timed out.
+    /// It is checked for every tuple the table is built from.
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Whether the table is built over the right child from the start.
+    fn builds_right(&self) -> bool {
+        self.build_right && !self.join_type.outputs_left_only()
+    }
+
+    fn should_swap(&self, built: usize) -> bool {
+        !self.will_rewind
+            && !self.join_type.outputs_left_only()
+            && self
+                .adaptive
+                .as_ref()
+                .is_some_and(|p| p.exceeded(built) && p.other_side_smaller(built))
+    }
+
+    /// Whether unmatched left tuples are returned, padded.
+    fn pads_left(&self) -> bool {
+        matches!(self.join_type, JoinType::LeftOuter | JoinType::FullOuter)
+    }
+
+    /// Whether unmatched right tuples are returned, padded.
+    fn pads_right(&self) -> bool {
+        matches!(self.join_type, JoinType::RightOuter | JoinType::FullOuter)
+    }
+
+    /// Pads `tuple` of the left child, or of the right one if not `is_left`,
+    /// with NULLs for the columns of the other child.
+    fn pad(&self, tuple: &Tuple, is_left: bool) -> Tuple {
+        if is_left {
+            let nulls = vec![Field::Null; self.right_child.get_schema().size()];
+            tuple.merge(&Tuple::new(nulls))
+        } else {
+            let nulls = vec![Field::Null; self.left_child.get_schema().size()];
+            Tuple::new(nulls).merge(tuple)
+        }
+    }
+
+    /// Adds a build tuple to the table under `key`. A tuple with a NULL key
+    /// matches nothing, and is only kept if the join returns unmatched tuples.
+    fn insert(&mut self, key: Option<Vec<Field>>, tuple: Tuple) {
+        match key {
+            Some(key) => {
+                self.join_map.entry(key).or_default().push((tuple, false));
+                self.unmatched_keyed += 1;
+            }
+            None if self.pads_left() || self.pads_right() || self.join_type == JoinType::Anti => {
+                self.null_keyed.push(tuple)
+            }
+            None => {}
+        }
+    }
+
+    /// Builds the table over the right child.
+    fn build_over_right(&mut self) -> Result<(), FairyError> {
+        while let Some(r) = self.right_child.next()? {
+            self.cancellation.check("hash_join")?;
+            let key = eval_key(&self.right_keys, &r)?;
+            self.insert(key, r);
+        }
+        self.swapped = true;
+        Ok(())
+    }
+
+    /// Rebuilds the table over the right child. The left tuples read so far are
+    /// probed first, then the rest of the left child.
+    fn swap_sides(&mut self) -> Result<(), FairyError> {
+        let mut pending: Vec<Tuple> = self
+            .join_map
+            .drain()
+            .flat_map(|(_, b)| b.into_iter().map(|(t, _)| t))
+            .collect();
+        pending.append(&mut self.null_keyed);
+        self.unmatched_keyed = 0;
+        self.build_over_right()?;
+        self.pending = pending.into_iter();
+        if let Some(policy) = &self.adaptive {
+            policy.record(Adaptation::SwappedBuildSide);
+        }
+        Ok(())
+    }
+
+    fn next_probe(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if let Some(t) = self.pending.next() {
+            return Ok(Some(t));
+        }
+        let probe_child = if self.swapped {
+            &mut self.left_child
+        } else {
+            &mut self.right_child
+        };
+        if self.probe_batch <= 1 {
+            return probe_child.next();
+        }
+        let mut batch = Vec::with_capacity(self.probe_batch);
+        probe_child.next_batch(&mut batch, self.probe_batch)?;
+        self.pending = batch.into_iter();
+        Ok(self.pending.next())
+    }
+
+    /// Moves to the next probe tuple and its key.
+    fn advance_probe(&mut self) -> Result<(), FairyError> {
+        self.current_tuple = self.next_probe()?;
+        self.current_key = match &self.current_tuple {
+            Some(probe) if self.swapped => eval_key(&self.left_keys, probe)?,
+            Some(probe) => eval_key(&self.right_keys, probe)?,
+            None => None,
+        };
+        self.current_idx = 0;
+        self.probe_matched = false;
+        Ok(())
+    }
+
+    /// The build tuples that matched no probe tuple, if the join returns them:
+    /// as they are for an anti join, padded for an outer join.
+    fn unmatched_build_tuples(&self) -> Vec<Tuple> {
+        let build_is_left = !self.swapped;
+        let returned = match self.join_type {
+            JoinType::Anti => true,
+            _ if build_is_left => self.pads_left(),
+            _ => self.pads_right(),
+        };
+        if !returned {
+            return Vec::new();
+        }
+        let unmatched = self
+            .join_map
+            .values()
+            .flatten()
+            .filter(|(_, matched)| !matched)
+            .map(|(t, _)| t)
+            .chain(&self.null_keyed);
+        if self.join_type == JoinType::Anti {
+            unmatched.cloned().collect()
+        } else {
+            unmatched.map(|t| self.pad(t, build_is_left)).collect()
+        }
+    }
+
+    /// Next tuple of a semi or anti join, which probes the table, built over
+    /// the left child, with the right child. A semi join returns a left tuple
+    /// when it first matches, an anti join the left tuples that never matched.
+    fn next_left_only(&mut self) -> Result<Option<Tuple>, FairyError> {
+        while let Some(ref probe) = self.current_tuple {
+            let bucket = self
+                .current_key
+                .as_ref()
+                .and_then(|key| self.join_map.get_mut(key));
+            if let Some((built, matched)) = bucket.and_then(|b| b.get_mut(self.current_idx)) {
+                self.current_idx += 1;
+                if *matched || !satisfies(&self.residual, &built.merge(probe))? {
+                    continue;
+                }
+                *matched = true;
+                self.unmatched_keyed -= 1;
+                if self.join_type == JoinType::Semi {
+                    return Ok(Some(built.clone()));
+                }
+                continue;
+            }
+            if self.unmatched_keyed == 0 {
+                // Every left tuple matched, the rest of the right child cannot
+                // change the result.
+                self.current_tuple = None;
+                break;
+            }
+            self.advance_probe()?;
+        }
+        if self.unmatched.is_none() {
+            self.unmatched = Some(self.unmatched_build_tuples().into_iter());
+        }
+        Ok(self.unmatched.as_mut().and_then(|u| u.next()))
+    }
 }
 
 impl OpIterator for HashEqJoin {
     fn configure(&mut self, will_rewind: bool) {
-        self.left_child.configure(false); // left child will never be rewound by HJ
-        self.right_child.configure(will_rewind);
+        self.will_rewind = will_rewind;
+        // The build child is never rewound by HJ, the probe child is
+        let builds_right = self.builds_right();
+        self.left_child.configure(will_rewind && builds_right);
+        self.right_child.configure(will_rewind && !builds_right);
     }
 
     fn open(&mut self) -> Result<(), FairyError> {
@@ -69,12 +348,22 @@ impl OpIterator for HashEqJoin {
             self.left_child.open()?;
             self.right_child.open()?;
 
-            while let Some(l) = self.left_child.next()? {
-                let key = self.left_expr.eval(&l);
-                self.join_map.entry(key).or_default().push(l);
+            if self.builds_right() {
+                self.build_over_right()?;
+            } else {
+                let mut built = 0;
+                while let Some(l) = self.left_child.next()? {
+                    self.cancellation.check("hash_join")?;
+                    let key = eval_key(&self.left_keys, &l)?;
+                    self.insert(key, l);
+                    built += 1;
+                    if self.should_swap(built) {
+                        self.swap_sides()?;
+                        break;
+                    }
+                }
             }
-            self.current_tuple = self.right_child.next()?;
-            self.current_idx = 0;
+            self.advance_probe()?;
             self.open = true;
         }
         Ok(())
@@ -84,19 +373,58 @@ impl OpIterator for HashEqJoin {
         if !self.open {
             panic!("Operator has not been opened")
         }
-        while let Some(ref right) = self.current_tuple {
-            let key = self.right_expr.eval(right);
-            if let Some(bucket) = self.join_map.get(&key) {
-                if self.current_idx < bucket.len() {
-                    let left = &bucket[self.current_idx];
-                    self.current_idx += 1;
-                    return Ok(Some(left.merge(right)));
+        if self.join_type.outputs_left_only() {
+            return self.next_left_only();
+        }
+        while let Some(ref probe) = self.current_tuple {
+            let bucket = self
+                .current_key
+                .as_ref()
+                .and_then(|key| self.join_map.get_mut(key));
+            if let Some((built, matched)) = bucket.and_then(|b| b.get_mut(self.current_idx)) {
+                self.current_idx += 1;
+                let joined = if self.swapped {
+                    probe.merge(built)
+                } else {
+                    built.merge(probe)
+                };
+                if satisfies(&self.residual, &joined)? {
+                    *matched = true;
+                    self.probe_matched = true;
+                    return Ok(Some(joined));
                 }
+                continue;
+            }
+            let pads_probe = if self.swapped {
+                self.pads_left()
+            } else {
+                self.pads_right()
+            };
+            let padded = (pads_probe && !self.probe_matched).then(|| self.pad(probe, self.swapped));
+            self.advance_probe()?;
+            if padded.is_some() {
+                return Ok(padded);
+            }
+        }
+        if self.unmatched.is_none() {
+            self.unmatched = Some(self.unmatched_build_tuples().into_iter());
+        }
+        Ok(self.unmatched.as_mut().and_then(|u| u.next()))
+    }
+
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        self.probe_batch = max;
+        for _ in 0..max {
+            match self.next()? {
+                Some(tuple) => out.push(tuple),
+                None => break,
             }
-            self.current_tuple = self.right_child.next()?;
-            self.current_idx = 0;
         }
-        Ok(None)
+        Ok(())
+    }
+
+    fn batches(&self) -> bool {
+        self.left_child.batches() && self.right_child.batches()
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
@@ -104,8 +432,16 @@ impl OpIterator for HashEqJoin {
             self.left_child.close()?;
             self.right_child.close()?;
             self.join_map.clear();
+            self.null_keyed.clear();
+            self.unmatched_keyed = 0;
             self.current_tuple = None;
+            self.current_key = None;
             self.current_idx = 0;
+            self.probe_matched = false;
+            self.unmatched = None;
+            self.swapped = false;
+            self.pending = Vec::new().into_iter();
+            self.probe_batch = 1;
             self.open = false;
         }
         Ok(())
@@ -115,10 +451,25 @@ impl OpIterator for HashEqJoin {
         if !self.open {
             panic!("Operator has not been opened")
         }
-        self.right_child.rewind()?;
-        self.current_tuple = self.right_child.next()?;
-        self.current_idx = 0;
-        Ok(())
+        if self.builds_right() {
+            self.left_child.rewind()?;
+        } else if self.swapped {
+            // Only happens if the join was configured not to be rewound.
+            return Err(FairyError::ExecutionError(
+                "Cannot rewind a hash join that swapped its build side".to_string(),
+            ));
+        } else {
+            self.right_child.rewind()?;
+        }
+        // Probe tuples read ahead are read again.
+        self.pending = Vec::new().into_iter();
+        self.unmatched_keyed = 0;
+        for (_, matched) in self.join_map.values_mut().flatten() {
+            *matched = false;
+            self.unmatched_keyed += 1;
+        }
+        self.unmatched = None;
+        self.advance_probe()
     }
 
     fn get_schema(&self) -> &TableSchema {
@@ -130,10 +481,12 @@ impl OpIterator for HashEqJoin {
 mod test {
     use super::super::TupleIterator;
     use super::*;
-    use crate::testutil::execute_iter;
     use crate::testutil::new_test_managers;
     use crate::testutil::TestTuples;
+    use crate::testutil::{execute_batches, execute_iter};
     use common::query::bytecode_expr::{ByteCodeExpr, ByteCodes};
+    use common::testutil::{ColumnDist, TableGen};
+    use common::DataType;
 
     fn get_join_predicate() -> (ByteCodeExpr, ByteCodeExpr) {
         // Joining two tables each containing the following tuples:
@@ -165,8 +518,8 @@ mod test {
         let mut iter = Box::new(HashEqJoin::new(
             managers,
             setup.schema.clone(),
-            left_expr,
-            right_expr,
+            vec![left_expr],
+            vec![right_expr],
             Box::new(TupleIterator::new(
                 setup.tuples.clone(),
                 setup.schema.clone(),
@@ -269,6 +622,350 @@ mod test {
                 ])
             );
         }
+
+        #[test]
+        fn test_swap_build_side() {
+            // Tuples (id, key) with Zipf distributed keys, joined on key.
+            let schema = TableSchema::from_vecs(vec!["id", "key"], vec![DataType::BigInt; 2]);
+            let input = |seed, n| {
+                TableGen::new(schema.clone(), seed)
+                    .column("id", ColumnDist::Sequential { start: 0 })
+                    .column(
+                        "key",
+                        ColumnDist::Zipf {
+                            min: 0,
+                            cardinality: 50,
+                            skew: 1.0,
+                        },
+                    )
+                    .generate(n)
+            };
+            let (left, right) = (input(1, 2000), input(2, 100));
+            let key = || {
+                let mut expr = ByteCodeExpr::new();
+                expr.add_code(ByteCodes::PushField as usize);
+                expr.add_code(1);
+                expr
+            };
+            let join = |policy: Option<&AdaptivePolicy>, will_rewind| {
+                let mut iter = HashEqJoin::new(
+                    new_test_managers(),
+                    schema.merge(&schema),
+                    vec![key()],
+                    vec![key()],
+                    Box::new(TupleIterator::new(left.clone(), schema.clone())),
+                    Box::new(TupleIterator::new(right.clone(), schema.clone())),
+                );
+                if let Some(policy) = policy {
+                    iter = iter.with_adaptation(policy.clone());
+                }
+                iter.configure(will_rewind);
+                execute_iter(&mut iter, true).unwrap()
+            };
+            let expected = join(None, false);
+            assert!(expected.len() > left.len());
+
+            // The left child has 2000 rows against an estimate of 1.
+            let policy = AdaptivePolicy::new(1, Some(1), 2.0);
+            assert_eq!(join(Some(&policy), false), expected);
+            assert_eq!(policy.decision(), Some(Adaptation::SwappedBuildSide));
+
+            // A join that will be rewound keeps its sides.
+            let policy = AdaptivePolicy::new(1, Some(1), 2.0);
+            assert_eq!(join(Some(&policy), true), expected);
+            assert_eq!(policy.decision(), None);
+        }
+    }
+
+    mod random_join_test {
+        use super::super::super::{CrossJoin, Filter, NestedLoopJoin};
+        use super::*;
+
+        fn field(i: usize) -> ByteCodeExpr {
+            let mut expr = ByteCodeExpr::new();
+            expr.add_code(ByteCodes::PushField as usize);
+            expr.add_code(i);
+            expr
+        }
+
+        /// Compares the fields `l` and `r` of a tuple with `op`.
+        fn compare(l: usize, op: ByteCodes, r: usize) -> ByteCodeExpr {
+            let mut expr = field(l);
+            expr.add_code(ByteCodes::PushField as usize);
+            expr.add_code(r);
+            expr.add_code(op as usize);
+            expr
+        }
+
+        /// Tuples (id, a, b) with few distinct keys (a, b), so that the build
+        /// side has duplicate keys, and a NULL `a` in every fifth tuple.
+        fn input(seed: u64, n: usize) -> (TableSchema, Vec<Tuple>) {
+            let schema = TableSchema::from_vecs(vec!["id", "a", "b"], vec![DataType::BigInt; 3]);
+            let mut tuples = TableGen::new(schema.clone(), seed)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .column("a", ColumnDist::Uniform { min: 0, max: 4 })
+                .column("b", ColumnDist::Uniform { min: 0, max: 2 })
+                .generate(n);
+            for tuple in tuples.iter_mut().step_by(5) {
+                tuple.field_vals[1] = Field::Null;
+            }
+            (schema, tuples)
+        }
+
+        fn sorted(mut tuples: Vec<Tuple>) -> Vec<Tuple> {
+            tuples.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            tuples
+        }
+
+        /// Joins on `l.a = r.a AND l.b = r.b AND l.id < r.id`, with a cross join
+        /// filtered by the predicates, then with hash joins building over each side.
+        #[test]
+        fn test_matches_filtered_cross_join() {
+            for seed in 0..5 {
+                let (schema, left) = input(seed, 60 + 10 * seed as usize);
+                let (_, right) = input(seed + 100, 80);
+                let joined = schema.merge(&schema);
+                let scan = |tuples: &Vec<Tuple>| -> Box<dyn OpIterator> {
+                    Box::new(TupleIterator::new(tuples.clone(), schema.clone()))
+                };
+                let residual = || compare(0, ByteCodes::Lt, 3);
+
+                let mut expected: Box<dyn OpIterator> =
+                    Box::new(CrossJoin::new(joined.clone(), scan(&left), scan(&right)));
+                for pred in [
+                    compare(1, ByteCodes::Eq, 4),
+                    compare(2, ByteCodes::Eq, 5),
+                    residual(),
+                ] {
+                    expected = Box::new(Filter::new(pred, joined.clone(), expected));
+                }
+                expected.configure(false);
+                let expected = sorted(execute_iter(&mut *expected, true).unwrap());
+                assert!(!expected.is_empty());
+
+                for build_right in [false, true] {
+                    let mut join = HashEqJoin::new(
+                        new_test_managers(),
+                        joined.clone(),
+                        vec![field(1), field(2)],
+                        vec![field(1), field(2)],
+                        scan(&left),
+                        scan(&right),
+                    )
+                    .with_residual(vec![residual()]);
+                    if build_right {
+                        join = join.with_build_right();
+                    }
+                    join.configure(true);
+                    let result = execute_iter(&mut join, false).unwrap();
+                    assert_eq!(sorted(result), expected, "seed {}", seed);
+                    // Rewinding probes the same table again.
+                    join.rewind().unwrap();
+                    let result = execute_iter(&mut join, true).unwrap();
+                    assert_eq!(sorted(result), expected, "seed {}", seed);
+                }
+            }
+        }
+
+        /// Outer joins on the same predicates, against nested-loop joins
+        /// evaluating all of them over the pairs, with the table built over
+        /// either side and built over the left side, then swapped.
+        #[test]
+        fn test_outer_joins_match_nested_loop_join() {
+            let (schema, left) = input(3, 70);
+            let (_, right) = input(4, 50);
+            let joined = schema.merge(&schema);
+            let scan = |tuples: &Vec<Tuple>| -> Box<dyn OpIterator> {
+                Box::new(TupleIterator::new(tuples.clone(), schema.clone()))
+            };
+            let residual = || compare(0, ByteCodes::Lt, 3);
+            let padded = |t: &Tuple, i: usize| t.field_vals[i] == Field::Null;
+            for join_type in [
+                JoinType::LeftOuter,
+                JoinType::RightOuter,
+                JoinType::FullOuter,
+            ] {
+                let mut expected =
+                    NestedLoopJoin::without_key(scan(&left), scan(&right), joined.clone())
+                        .with_residual(vec![
+                            compare(1, ByteCodes::Eq, 4),
+                            compare(2, ByteCodes::Eq, 5),
+                            residual(),
+                        ])
+                        .with_join_type(join_type);
+                expected.configure(false);
+                let expected = sorted(execute_iter(&mut expected, true).unwrap());
+                // Ids are never NULL, so a NULL id is a padded tuple.
+                assert_eq!(
+                    expected.iter().any(|t| padded(t, 3)),
+                    join_type != JoinType::RightOuter
+                );
+                assert_eq!(
+                    expected.iter().any(|t| padded(t, 0)),
+                    join_type != JoinType::LeftOuter
+                );
+
+                for variant in ["build left", "build right", "swapped"] {
+                    let policy = AdaptivePolicy::new(1, Some(1), 2.0);
+                    let mut join = HashEqJoin::new(
+                        new_test_managers(),
+                        joined.clone(),
+                        vec![field(1), field(2)],
+                        vec![field(1), field(2)],
+                        scan(&left),
+                        scan(&right),
+                    )
+                    .with_residual(vec![residual()])
+                    .with_join_type(join_type);
+                    match variant {
+                        "build right" => join = join.with_build_right(),
+                        "swapped" => join = join.with_adaptation(policy.clone()),
+                        _ => {}
+                    }
+                    join.configure(variant != "swapped");
+                    let result = execute_iter(&mut join, false).unwrap();
+                    assert_eq!(sorted(result), expected, "{:?} {}", join_type, variant);
+                    if variant == "swapped" {
+                        assert_eq!(policy.decision(), Some(Adaptation::SwappedBuildSide));
+                        continue;
+                    }
+                    // Rewinding pads the same tuples again.
+                    join.rewind().unwrap();
+                    let result = execute_iter(&mut join, true).unwrap();
+                    assert_eq!(sorted(result), expected, "{:?} {}", join_type, variant);
+                }
+            }
+        }
+
+        /// Semi and anti joins on the same predicates, against nested-loop
+        /// joins evaluating all of them over the pairs.
+        #[test]
+        fn test_semi_and_anti_joins_match_nested_loop_join() {
+            let (schema, left) = input(3, 70);
+            let (_, right) = input(4, 50);
+            let scan = |tuples: &Vec<Tuple>| -> Box<dyn OpIterator> {
+                Box::new(TupleIterator::new(tuples.clone(), schema.clone()))
+            };
+            let residual = || compare(0, ByteCodes::Lt, 3);
+            let mut outputs = Vec::new();
+            for join_type in [JoinType::Semi, JoinType::Anti] {
+                let mut expected =
+                    NestedLoopJoin::without_key(scan(&left), scan(&right), schema.clone())
+                        .with_residual(vec![
+                            compare(1, ByteCodes::Eq, 4),
+                            compare(2, ByteCodes::Eq, 5),
+                            residual(),
+                        ])
+                        .with_join_type(join_type);
+                expected.configure(false);
+                let expected = sorted(execute_iter(&mut expected, true).unwrap());
+                outputs.push(expected.len());
+
+                // The build side is the left one either way.
+                for build_right in [false, true] {
+                    let mut join = HashEqJoin::new(
+                        new_test_managers(),
+                        schema.clone(),
+                        vec![field(1), field(2)],
+                        vec![field(1), field(2)],
+                        scan(&left),
+                        scan(&right),
+                    )
+                    .with_residual(vec![residual()])
+                    .with_join_type(join_type);
+                    if build_right {
+                        join = join.with_build_right();
+                    }
+                    join.configure(true);
+                    let result = execute_iter(&mut join, false).unwrap();
+                    assert_eq!(sorted(result), expected, "{:?}", join_type);
+                    join.rewind().unwrap();
+                    let result = execute_iter(&mut join, true).unwrap();
+                    assert_eq!(sorted(result), expected, "{:?}", join_type);
+                }
+            }
+            // Every left tuple is output by exactly one of the joins.
+            assert!(outputs.iter().all(|n| *n > 0));
+            assert_eq!(outputs.iter().sum::<usize>(), left.len());
+        }
+
+        /// Every join type, read in batches that probe a batch of the probe
+        /// child at a time, against the same join read a tuple at a time.
+        #[test]
+        fn test_batches_match_rows() {
+            let (schema, left) = input(5, 70);
+            let (_, right) = input(6, 50);
+            let joined = schema.merge(&schema);
+            let scan = |tuples: &Vec<Tuple>| -> Box<dyn OpIterator> {
+                Box::new(TupleIterator::new(tuples.clone(), schema.clone()))
+            };
+            for join_type in [
+                JoinType::Inner,
+                JoinType::LeftOuter,
+                JoinType::FullOuter,
+                JoinType::Semi,
+                JoinType::Anti,
+            ] {
+                let out_schema = if join_type.outputs_left_only() {
+                    schema.clone()
+                } else {
+                    joined.clone()
+                };
+                for variant in ["build left", "build right", "swapped"] {
+                    let join = || {
+                        let join = HashEqJoin::new(
+                            new_test_managers(),
+                            out_schema.clone(),
+                            vec![field(1), field(2)],
+                            vec![field(1), field(2)],
+                            scan(&left),
+                            scan(&right),
+                        )
+                        .with_residual(vec![compare(0, ByteCodes::Lt, 3)])
+                        .with_join_type(join_type);
+                        let mut join = match variant {
+                            "build right" => join.with_build_right(),
+                            "swapped" => join.with_adaptation(AdaptivePolicy::new(1, Some(1), 2.0)),
+                            _ => join,
+                        };
+                        join.configure(variant != "swapped");
+                        join
+                    };
+                    let expected = execute_iter(&mut join(), true).unwrap();
+                    assert!(!expected.is_empty(), "{:?} {}", join_type, variant);
+                    for max in [1, 7, 1000] {
+                        let mut join = join();
+                        let result = execute_batches(&mut join, max).unwrap();
+                        assert_eq!(sorted(result), expected, "{:?} {}", join_type, variant);
+                        if variant == "swapped" {
+                            continue;
+                        }
+                        // The probe tuples read ahead are probed again.
+                        join.rewind().unwrap();
+                        let result = execute_iter(&mut join, true).unwrap();
+                        assert_eq!(result, expected, "{:?} {}", join_type, variant);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_null_keys_never_match() {
+            let (schema, tuples) = input(7, 20);
+            let key = || vec![field(1)];
+            let mut join = HashEqJoin::new(
+                new_test_managers(),
+                schema.merge(&schema),
+                key(),
+                key(),
+                Box::new(TupleIterator::new(tuples.clone(), schema.clone())),
+                Box::new(TupleIterator::new(tuples, schema)),
+            );
+            join.configure(false);
+            let result = execute_iter(&mut join, true).unwrap();
+            assert!(!result.is_empty());
+            assert!(result.iter().all(|t| t.field_vals[1] != Field::Null));
+        }
     }
 
     mod opiterator_test {
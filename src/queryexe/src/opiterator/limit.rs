@@ -0,0 +1,114 @@
+use super::OpIterator;
+use common::{FairyError, TableSchema, Tuple};
+
+/// Returns the first `limit` tuples of the child, which is not read any
+/// further once they are returned.
+pub struct Limit {
+    // Parameters (No need to reset on close)
+    /// Schema of the child.
+    schema: TableSchema,
+    /// Child operator passing data into operator.
+    child: Box<dyn OpIterator>,
+    /// Number of tuples to return.
+    limit: usize,
+
+    // States (Need to reset on close)
+    /// Boolean determining if iterator is open.
+    open: bool,
+    /// Number of tuples returned so far.
+    returned: usize,
+}
+
+impl Limit {
+    /// Limit constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Number of tuples to return.
+    /// * `schema` - Schema of the child.
+    /// * `child` - Child OpIterator passing data into the operator.
+    pub fn new(limit: usize, schema: TableSchema, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            schema,
+            child,
+            limit,
+            open: false,
+            returned: 0,
+        }
+    }
+}
+
+impl OpIterator for Limit {
+    fn configure(&mut self, will_rewind: bool) {
+        self.child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.child.open()?;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if self.returned == self.limit {
+            return Ok(None);
+        }
+        let t = self.child.next()?;
+        if t.is_some() {
+            self.returned += 1;
+        }
+        Ok(t)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.child.close()?;
+        self.returned = 0;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()?;
+        self.returned = 0;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use crate::testutil::{execute_iter, TestTuples};
+
+    #[test]
+    fn test_limit() {
+        let setup = TestTuples::new("");
+        for (limit, expected) in [(0, 0), (2, 2), (6, 6), (10, 6)] {
+            let mut iter = Limit::new(
+                limit,
+                setup.schema.clone(),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+            );
+            iter.configure(true);
+            let t = execute_iter(&mut iter, false).unwrap();
+            assert_eq!(t, setup.tuples[..expected]);
+            iter.rewind().unwrap();
+            assert_eq!(execute_iter(&mut iter, false).unwrap(), t);
+        }
+    }
+}
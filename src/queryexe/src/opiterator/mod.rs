@@ -1,29 +1,55 @@
+pub use self::adaptive::{Adaptation, AdaptivePolicy};
 pub use self::aggregate::Aggregate;
+pub use self::cancellation::Cancellation;
 pub use self::cross_join::CrossJoin;
+pub use self::distinct::Distinct;
 pub use self::filter::Filter;
 pub use self::hash_join::HashEqJoin;
+pub use self::limit::Limit;
 pub use self::nested_loop_join::NestedLoopJoin;
+pub use self::parallel_hash_join::{ParallelHashEqJoin, BROADCAST_MAX_BUILD_TUPLES};
 pub use self::project::Project;
-pub use self::seqscan::SeqScan;
+pub use self::schema_check::{check_schema, SchemaCheck};
+pub use self::seqscan::{CorruptRecordMode, RecordCheck, ScanPredicate, SeqScan};
 pub use self::sort::Sort;
 pub use self::sort_merge_join::SortMergeJoin;
+pub use self::spool::{SharedSpool, Spool, SpoolBuffer};
+pub use self::stream_aggregate::StreamAggregate;
+pub use self::top_n::TopN;
+pub use self::traced::{OperatorStats, Traced};
 pub use self::tuple_iterator::TupleIterator;
+pub use self::union::Union;
 pub use self::update::Update;
 use common::{FairyError, TableSchema, Tuple};
 
+mod adaptive;
 mod aggregate;
+mod cancellation;
 mod cross_join;
+mod distinct;
 mod filter;
 mod hash_join;
+mod limit;
 mod nested_loop_join;
+mod parallel_hash_join;
 mod project;
+mod schema_check;
 mod seqscan;
 mod sort;
 mod sort_merge_join;
+mod spool;
+mod stream_aggregate;
+mod top_n;
+mod traced;
 mod tuple_iterator;
+mod union;
 mod update;
 
-pub trait OpIterator {
+/// Number of tuples asked for at once from a plan whose operators all produce
+/// batches.
+pub const BATCH_SIZE: usize = 1024;
+
+pub trait OpIterator: Send {
     /// conifgure the opiterator
     ///
     /// will_rewind indicates whether we will rewind the operator in the future
@@ -72,6 +98,31 @@ pub trait OpIterator {
     /// Panic if iterator is not open.
     fn next(&mut self) -> Result<Option<Tuple>, FairyError>;
 
+    /// Advances the iterator by up to `max` tuples, appending them to `out`.
+    ///
+    /// Appends no tuple only when iteration is finished. By default this calls
+    /// next() once per tuple; operators that return true from batches()
+    /// produce the whole batch at once.
+    ///
+    /// # Panics
+    ///
+    /// Panic if iterator is not open.
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        for _ in 0..max {
+            match self.next()? {
+                Some(tuple) => out.push(tuple),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether next_batch() of this operator, and of the operators it reads
+    /// from, produces batches rather than one tuple per call to next().
+    fn batches(&self) -> bool {
+        false
+    }
+
     /// Resets the states of the operator.
     ///
     /// Only operations that can be performed after close is open() and close().
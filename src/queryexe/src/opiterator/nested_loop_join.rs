@@ -1,23 +1,46 @@
-use super::OpIterator;
+use super::filter::satisfies;
+use super::{Adaptation, AdaptivePolicy, OpIterator};
 
 #[allow(unused_imports)]
 use common::datatypes::compare_fields; // QO compare fields with op
+use common::logical_expr::prelude::JoinType;
 use common::query::bytecode_expr::ByteCodeExpr;
-use common::{BinaryOp, FairyError, TableSchema, Tuple};
+use common::{BinaryOp, FairyError, Field, TableSchema, Tuple};
+use std::collections::HashMap;
 
 /// Nested loop join implementation. (You can add any other fields that you think are neccessary)
+///
+/// A pair of tuples matches if the key comparison, if any, and the residual
+/// predicates hold. An outer join pads the unmatched left tuples with NULLs once
+/// the right child is exhausted for them, and the unmatched right tuples in a
+/// last pass over the right child. A semi or anti join returns a left tuple, on
+/// its own, once it finds a match or once the right child is exhausted without
+/// one.
 pub struct NestedLoopJoin {
     // Parameters (No need to reset on close)
     schema: TableSchema,
-    op: BinaryOp,
-    left_expr: ByteCodeExpr,
-    right_expr: ByteCodeExpr,
+    // Comparison of a key over the left child with one over the right child
+    key: Option<(BinaryOp, ByteCodeExpr, ByteCodeExpr)>,
+    residual: Vec<ByteCodeExpr>,
+    join_type: JoinType,
     left_child: Box<dyn OpIterator>,
     right_child: Box<dyn OpIterator>,
+    adaptive: Option<AdaptivePolicy>,
 
     // States (Need to reset on close)
     open: bool,
     current_tuple: Option<Tuple>, // Current tuple in left table
+    inner_rows: usize,            // Right tuples read in the first pass so far
+    first_pass: bool,
+    // Right tuples by join key, once the join switched to hashing them
+    inner_map: Option<HashMap<Field, Vec<Tuple>>>,
+    current_idx: usize, // Index of the tuple in the current bucket of inner_map
+    left_matched: bool, // Whether the current left tuple matched a right tuple
+    right_pos: usize,   // Position of the next right tuple in the current pass
+    // Whether the right tuple at each position matched a left tuple
+    right_matched: Vec<bool>,
+    // Whether the last pass over the right child, padding it, started
+    padding_right: bool,
 }
 
 impl NestedLoopJoin {
@@ -37,17 +60,158 @@ impl NestedLoopJoin {
         left_child: Box<dyn OpIterator>,
         right_child: Box<dyn OpIterator>,
         schema: TableSchema,
+    ) -> Self {
+        let mut join = Self::without_key(left_child, right_child, schema);
+        join.key = Some((op, left_expr, right_expr));
+        join
+    }
+
+    /// Creates a nested-loop join whose tuples match if the residual predicates
+    /// hold, for predicates that do not split into a key per child.
+    pub fn without_key(
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        schema: TableSchema,
     ) -> Self {
         Self {
-            op,
-            left_expr,
-            right_expr,
+            key: None,
+            residual: Vec::new(),
+            join_type: JoinType::Inner,
             open: false,
             schema,
             left_child,
             right_child,
+            adaptive: None,
             current_tuple: None,
+            inner_rows: 0,
+            first_pass: true,
+            inner_map: None,
+            current_idx: 0,
+            left_matched: false,
+            right_pos: 0,
+            right_matched: Vec::new(),
+            padding_right: false,
+        }
+    }
+
+    /// Sets the predicates that must also hold over a joined tuple.
+    pub fn with_residual(mut self, residual: Vec<ByteCodeExpr>) -> Self {
+        self.residual = residual;
+        self
+    }
+
+    /// Sets the join type, `JoinType::Inner` by default. The outer join types
+    /// also return the unmatched tuples of their outer side(s), padded. The
+    /// semi and anti joins return the matched and the unmatched left tuples.
+    pub fn with_join_type(mut self, join_type: JoinType) -> Self {
+        self.join_type = join_type;
+        self
+    }
+
+    /// Lets an equi-join hash its inner side once the first pass over it turns
+    /// out much larger than estimated, instead of rescanning it for every outer
+    /// row. Only an inner join on the key alone is hashed.
+    pub fn with_adaptation(mut self, policy: AdaptivePolicy) -> Self {
+        if matches!(self.key, Some((BinaryOp::Eq, _, _))) {
+            self.adaptive = Some(policy);
+        }
+        self
+    }
+
+    /// The left and right tuples joined, if they match.
+    fn join_pair(&self, left: &Tuple, right: &Tuple) -> Result<Option<Tuple>, FairyError> {
+        if let Some((op, left_expr, right_expr)) = &self.key {
+            let lval = left_expr.try_eval(left)?;
+            let rval = right_expr.try_eval(right)?;
+            if !compare_fields(*op, &lval, &rval) {
+                return Ok(None);
+            }
+        }
+        let joined = left.merge(right);
+        Ok(satisfies(&self.residual, &joined)?.then_some(joined))
+    }
+
+    /// Whether unmatched left tuples are returned, padded.
+    fn pads_left(&self) -> bool {
+        matches!(self.join_type, JoinType::LeftOuter | JoinType::FullOuter)
+    }
+
+    /// Whether unmatched right tuples are returned, padded.
+    fn pads_right(&self) -> bool {
+        matches!(self.join_type, JoinType::RightOuter | JoinType::FullOuter)
+    }
+
+    /// Returns the next right tuple that matched no left tuple, padded, from a
+    /// last pass over the right child.
+    fn next_unmatched_right(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.padding_right {
+            self.padding_right = true;
+            self.right_child.rewind()?;
+            self.right_pos = 0;
+        }
+        while let Some(right) = self.right_child.next()? {
+            let pos = self.right_pos;
+            self.right_pos += 1;
+            if !self.right_matched.get(pos).copied().unwrap_or(false) {
+                let nulls = vec![Field::Null; self.left_child.get_schema().size()];
+                return Ok(Some(Tuple::new(nulls).merge(&right)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Called at the end of the first pass over the right child, once the next
+    /// left tuple was read.
+    fn end_first_pass(&mut self) -> Result<(), FairyError> {
+        self.first_pass = false;
+        let policy = match &self.adaptive {
+            Some(policy)
+                if self.current_tuple.is_some()
+                    && matches!(self.join_type, JoinType::Inner | JoinType::CrossJoin)
+                    && self.residual.is_empty()
+                    && policy.exceeded(self.inner_rows) =>
+            {
+                policy.clone()
+            }
+            _ => return Ok(()),
+        };
+        let Some((_, _, right_expr)) = &self.key else {
+            return Ok(());
+        };
+        let mut inner_map: HashMap<Field, Vec<Tuple>> = HashMap::new();
+        self.right_child.rewind()?;
+        while let Some(right) = self.right_child.next()? {
+            let key = right_expr.try_eval(&right)?;
+            // Only the equality join is hashed, and a NULL key equals no key.
+            if key != Field::Null {
+                inner_map.entry(key).or_default().push(right);
+            }
+        }
+        self.inner_map = Some(inner_map);
+        self.current_idx = 0;
+        policy.record(Adaptation::HashedInnerSide);
+        Ok(())
+    }
+
+    /// Probes the hashed right tuples with the remaining left tuples.
+    fn next_hashed(&mut self) -> Result<Option<Tuple>, FairyError> {
+        let inner_map = self.inner_map.as_ref().unwrap();
+        let Some((_, left_expr, _)) = &self.key else {
+            return Ok(None);
+        };
+        while let Some(ref left) = self.current_tuple {
+            let key = left_expr.try_eval(left)?;
+            if let Some(bucket) = inner_map.get(&key) {
+                if self.current_idx < bucket.len() {
+                    let right = &bucket[self.current_idx];
+                    self.current_idx += 1;
+                    return Ok(Some(left.merge(right)));
+                }
+            }
+            self.current_tuple = self.left_child.next()?;
+            self.current_idx = 0;
         }
+        Ok(None)
     }
 }
 
@@ -73,26 +237,65 @@ impl OpIterator for NestedLoopJoin {
         if !self.open {
             panic!("Operator has not been opened")
         }
+        if self.inner_map.is_some() {
+            return self.next_hashed();
+        }
 
         // outer loop over left tuple
         while let Some(left) = self.current_tuple.clone() {
             // inner loop over right tuples
             while let Some(right) = self.right_child.next()? {
-                let lval = self.left_expr.eval(&left);
-                let rval = self.right_expr.eval(&right);
-                if compare_fields(self.op, &lval, &rval) {
-                    return Ok(Some(left.clone().merge(&right)));
+                let pos = self.right_pos;
+                self.right_pos += 1;
+                if self.first_pass {
+                    self.inner_rows += 1;
+                }
+                if let Some(joined) = self.join_pair(&left, &right)? {
+                    self.left_matched = true;
+                    if self.join_type.outputs_left_only() {
+                        // The first match decides.
+                        break;
+                    }
+                    if self.pads_right() {
+                        if self.right_matched.len() <= pos {
+                            self.right_matched.resize(pos + 1, false);
+                        }
+                        self.right_matched[pos] = true;
+                    }
+                    return Ok(Some(joined));
                 }
             }
+            // What the left tuple returns on its own, matched or not
+            let finished = match self.join_type {
+                JoinType::Semi => self.left_matched.then_some(left),
+                JoinType::Anti => (!self.left_matched).then_some(left),
+                _ => (self.pads_left() && !self.left_matched).then(|| {
+                    let nulls = vec![Field::Null; self.right_child.get_schema().size()];
+                    left.merge(&Tuple::new(nulls))
+                }),
+            };
+            self.left_matched = false;
 
             // Continue
             self.current_tuple = self.left_child.next()?;
-            if self.current_tuple.is_none() {
-                break;
+            if self.first_pass {
+                self.end_first_pass()?;
+                if self.inner_map.is_some() {
+                    return self.next_hashed();
+                }
+            }
+            if self.current_tuple.is_some() {
+                self.right_child.rewind()?;
+                self.right_pos = 0;
+            }
+            if finished.is_some() {
+                return Ok(finished);
             }
-            self.right_child.rewind()?;
         }
 
+        if self.pads_right() {
+            return self.next_unmatched_right();
+        }
         Ok(None)
     }
 
@@ -102,6 +305,14 @@ impl OpIterator for NestedLoopJoin {
             self.right_child.close()?;
             self.open = false;
             self.current_tuple = None;
+            self.inner_rows = 0;
+            self.first_pass = true;
+            self.inner_map = None;
+            self.current_idx = 0;
+            self.left_matched = false;
+            self.right_pos = 0;
+            self.right_matched.clear();
+            self.padding_right = false;
         }
         Ok(())
     }
@@ -113,6 +324,16 @@ impl OpIterator for NestedLoopJoin {
         self.left_child.rewind()?;
         self.right_child.rewind()?;
         self.current_tuple = self.left_child.next()?;
+        // The hashed right tuples stay valid, a first pass that did not finish
+        // starts over.
+        self.current_idx = 0;
+        if self.first_pass {
+            self.inner_rows = 0;
+        }
+        self.left_matched = false;
+        self.right_pos = 0;
+        self.right_matched.clear();
+        self.padding_right = false;
         Ok(())
     }
 
@@ -272,6 +493,140 @@ mod test {
                 ])
             );
         }
+
+        #[test]
+        fn test_hash_inner_side() {
+            let setup = TestTuples::new("");
+            let (left_expr, right_expr) = get_join_predicate();
+            let expected =
+                run_nested_loop_join(BinaryOp::Eq, left_expr.clone(), right_expr.clone());
+            let new_join = |op, policy: &AdaptivePolicy| {
+                NestedLoopJoin::new(
+                    op,
+                    left_expr.clone(),
+                    right_expr.clone(),
+                    Box::new(TupleIterator::new(
+                        setup.tuples.clone(),
+                        setup.schema.clone(),
+                    )),
+                    Box::new(TupleIterator::new(
+                        setup.tuples.clone(),
+                        setup.schema.clone(),
+                    )),
+                    setup.schema.clone(),
+                )
+                .with_adaptation(policy.clone())
+            };
+
+            // The right child has 6 rows against an estimate of 1.
+            let policy = AdaptivePolicy::new(1, None, 2.0);
+            let mut iter = new_join(BinaryOp::Eq, &policy);
+            iter.configure(true);
+            let unsorted = execute_iter(&mut iter, false).unwrap();
+            assert_eq!(policy.decision(), Some(Adaptation::HashedInnerSide));
+            // Rewinding reuses the hashed right tuples and keeps the order.
+            iter.rewind().unwrap();
+            let mut rewound = Vec::new();
+            while let Some(t) = iter.next().unwrap() {
+                rewound.push(t);
+            }
+            assert_eq!(rewound, unsorted);
+            let mut sorted = unsorted;
+            sorted.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            assert_eq!(sorted, expected);
+
+            // Within the estimate, or for a comparison other than equality, the
+            // join keeps rescanning.
+            let policy = AdaptivePolicy::new(6, None, 2.0);
+            let mut iter = new_join(BinaryOp::Eq, &policy);
+            iter.configure(false);
+            assert_eq!(execute_iter(&mut iter, true).unwrap(), expected);
+            assert_eq!(policy.decision(), None);
+            let policy = AdaptivePolicy::new(1, None, 2.0);
+            let mut iter = new_join(BinaryOp::Lt, &policy);
+            iter.configure(false);
+            execute_iter(&mut iter, true).unwrap();
+            assert_eq!(policy.decision(), None);
+        }
+    }
+
+    mod outer_join_test {
+        use super::*;
+        use common::DataType;
+
+        fn key(i: usize) -> ByteCodeExpr {
+            let mut expr = ByteCodeExpr::new();
+            expr.add_code(ByteCodes::PushField as usize);
+            expr.add_code(i);
+            expr
+        }
+
+        fn rows(keys: &[Option<i64>]) -> Vec<Tuple> {
+            keys.iter()
+                .map(|k| Tuple::new(vec![k.map_or(Field::Null, Field::BigInt)]))
+                .collect()
+        }
+
+        /// Joins `left.k = right.k` over left keys 1, 2, 3, NULL and right
+        /// keys 2, 3, 3, 4, NULL.
+        fn run_join(join_type: JoinType) -> Vec<Tuple> {
+            let schema = TableSchema::from_vecs(vec!["k"], vec![DataType::BigInt]);
+            let left = rows(&[Some(1), Some(2), Some(3), None]);
+            let right = rows(&[Some(2), Some(3), Some(3), Some(4), None]);
+            let mut iter = NestedLoopJoin::new(
+                BinaryOp::Eq,
+                key(0),
+                key(0),
+                Box::new(TupleIterator::new(left, schema.clone())),
+                Box::new(TupleIterator::new(right, schema.clone())),
+                if join_type.outputs_left_only() {
+                    schema.clone()
+                } else {
+                    schema.merge(&schema)
+                },
+            )
+            .with_join_type(join_type);
+            iter.configure(true);
+            let t = execute_iter(&mut iter, true).unwrap();
+            // Rewinding pads the same tuples again.
+            iter.rewind().unwrap();
+            assert_eq!(execute_iter(&mut iter, true).unwrap(), t);
+            t
+        }
+
+        fn pairs(keys: &[(Option<i64>, Option<i64>)]) -> Vec<Tuple> {
+            let mut t: Vec<Tuple> = keys
+                .iter()
+                .map(|(l, r)| rows(&[*l])[0].merge(&rows(&[*r])[0]))
+                .collect();
+            t.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            t
+        }
+
+        #[test]
+        fn test_outer_joins() {
+            let matches = [(Some(2), Some(2)), (Some(3), Some(3)), (Some(3), Some(3))];
+            let left_pads = [(Some(1), None), (None, None)];
+            let right_pads = [(None, Some(4)), (None, None)];
+            assert_eq!(run_join(JoinType::Inner), pairs(&matches));
+            assert_eq!(
+                run_join(JoinType::LeftOuter),
+                pairs(&[&matches[..], &left_pads].concat())
+            );
+            assert_eq!(
+                run_join(JoinType::RightOuter),
+                pairs(&[&matches[..], &right_pads].concat())
+            );
+            assert_eq!(
+                run_join(JoinType::FullOuter),
+                pairs(&[&matches[..], &left_pads, &right_pads].concat())
+            );
+            // Each left tuple once, whether it matches one or two tuples.
+            assert_eq!(run_join(JoinType::Semi), rows(&[Some(2), Some(3)]));
+            let mut unmatched = rows(&[Some(1), None]);
+            unmatched.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            assert_eq!(run_join(JoinType::Anti), unmatched);
+        }
     }
 
     mod opiterator_test {
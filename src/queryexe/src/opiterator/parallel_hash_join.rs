@@ -0,0 +1,387 @@
+use super::OpIterator;
+
+use common::physical::config::HashJoinDistribution;
+use common::query::bytecode_expr::ByteCodeExpr;
+use common::{FairyError, Field, TableSchema, Tuple};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::thread;
+
+/// Largest build side, in tuples, that `HashJoinDistribution::Auto` broadcasts.
+pub const BROADCAST_MAX_BUILD_TUPLES: usize = 10_000;
+
+type JoinMap<'a> = HashMap<Field, Vec<&'a Tuple>>;
+
+/// Hash equi-join that runs its build and probe phases on several workers.
+///
+/// Both children are drained on the calling thread, since operators are not
+/// `Send`. With a broadcast distribution the hash table is built once and shared
+/// read-only by all workers, each probing a slice of the probe side. With a
+/// partitioned distribution both sides are split by key hash and every worker
+/// builds and probes its own partition. Either way no lock is taken while probing.
+pub struct ParallelHashEqJoin {
+    // Parameters (No need to reset on close)
+    schema: TableSchema,
+    left_expr: ByteCodeExpr,
+    right_expr: ByteCodeExpr,
+    left_child: Box<dyn OpIterator>,
+    right_child: Box<dyn OpIterator>,
+    workers: usize,
+    requested: HashJoinDistribution,
+
+    // States (Need to reset on close)
+    open: bool,
+    distribution: Option<HashJoinDistribution>,
+    hash_table_builds: usize,
+    output: Vec<Tuple>,
+    index: usize,
+}
+
+impl ParallelHashEqJoin {
+    /// Constructor for a parallel hash equi-join operator. The left child is the
+    /// build side.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Output schema.
+    /// * `left_expr` - Join key of the left (build) side.
+    /// * `right_expr` - Join key of the right (probe) side.
+    /// * `left_child` - Left child of join operator.
+    /// * `right_child` - Right child of join operator.
+    /// * `workers` - Number of worker threads.
+    /// * `distribution` - Requested distribution, `Auto` decides by build size.
+    pub fn new(
+        schema: TableSchema,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        workers: usize,
+        distribution: HashJoinDistribution,
+    ) -> Self {
+        Self {
+            schema,
+            left_expr,
+            right_expr,
+            left_child,
+            right_child,
+            workers: workers.max(1),
+            requested: distribution,
+            open: false,
+            distribution: None,
+            hash_table_builds: 0,
+            output: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Distribution chosen when the operator was opened.
+    pub fn distribution(&self) -> Option<HashJoinDistribution> {
+        self.distribution
+    }
+
+    /// Number of hash tables built since the operator was opened.
+    pub fn hash_table_builds(&self) -> usize {
+        self.hash_table_builds
+    }
+
+    fn choose_distribution(&self, build_len: usize) -> HashJoinDistribution {
+        match self.requested {
+            HashJoinDistribution::Auto if build_len <= BROADCAST_MAX_BUILD_TUPLES => {
+                HashJoinDistribution::Broadcast
+            }
+            HashJoinDistribution::Auto => HashJoinDistribution::Partitioned,
+            requested => requested,
+        }
+    }
+
+    fn broadcast_join(
+        &self,
+        build: &[Tuple],
+        probe: &[Tuple],
+    ) -> Result<Vec<Vec<Tuple>>, FairyError> {
+        let map = &build_map(build, &self.left_expr)?;
+        let right_expr = &self.right_expr;
+        let chunk_len = probe.len().div_ceil(self.workers).max(1);
+        thread::scope(|s| {
+            let handles: Vec<_> = probe
+                .chunks(chunk_len)
+                .map(|chunk| s.spawn(move || probe_map(map, chunk, right_expr)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    fn partitioned_join(
+        &self,
+        build: &[Tuple],
+        probe: &[Tuple],
+    ) -> Result<Vec<Vec<Tuple>>, FairyError> {
+        let build_parts = partition(build, &self.left_expr, self.workers)?;
+        let probe_parts = partition(probe, &self.right_expr, self.workers)?;
+        let (left_expr, right_expr) = (&self.left_expr, &self.right_expr);
+        thread::scope(|s| {
+            let handles: Vec<_> = build_parts
+                .iter()
+                .zip(probe_parts.iter())
+                .map(|(build, probe)| {
+                    s.spawn(|| {
+                        let map = build_map(build.iter().copied(), left_expr)?;
+                        probe_map(&map, probe.iter().copied(), right_expr)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+}
+
+fn build_map<'a>(
+    tuples: impl IntoIterator<Item = &'a Tuple>,
+    expr: &ByteCodeExpr,
+) -> Result<JoinMap<'a>, FairyError> {
+    let mut map: JoinMap = HashMap::new();
+    for t in tuples {
+        // A NULL key is equal to no key, so the tuple cannot join.
+        let key = expr.try_eval(t)?;
+        if key != Field::Null {
+            map.entry(key).or_default().push(t);
+        }
+    }
+    Ok(map)
+}
+
+fn probe_map<'a>(
+    map: &JoinMap,
+    tuples: impl IntoIterator<Item = &'a Tuple>,
+    expr: &ByteCodeExpr,
+) -> Result<Vec<Tuple>, FairyError> {
+    let mut out = Vec::new();
+    for right in tuples {
+        if let Some(bucket) = map.get(&expr.try_eval(right)?) {
+            out.extend(bucket.iter().map(|left| left.merge(right)));
+        }
+    }
+    Ok(out)
+}
+
+fn partition<'a>(
+    tuples: &'a [Tuple],
+    expr: &ByteCodeExpr,
+    parts: usize,
+) -> Result<Vec<Vec<&'a Tuple>>, FairyError> {
+    let mut res = vec![Vec::new(); parts];
+    for t in tuples {
+        let mut hasher = DefaultHasher::new();
+        expr.try_eval(t)?.hash(&mut hasher);
+        res[(hasher.finish() % parts as u64) as usize].push(t);
+    }
+    Ok(res)
+}
+
+fn drain(child: &mut Box<dyn OpIterator>) -> Result<Vec<Tuple>, FairyError> {
+    let mut tuples = Vec::new();
+    while let Some(t) = child.next()? {
+        tuples.push(t);
+    }
+    Ok(tuples)
+}
+
+impl OpIterator for ParallelHashEqJoin {
+    fn configure(&mut self, _will_rewind: bool) {
+        // Both children are drained once, rewinds replay the materialized output.
+        self.left_child.configure(false);
+        self.right_child.configure(false);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.left_child.open()?;
+            self.right_child.open()?;
+            let build = drain(&mut self.left_child)?;
+            let probe = drain(&mut self.right_child)?;
+
+            let distribution = self.choose_distribution(build.len());
+            debug!(
+                "Hash join on {} workers with {:?} distribution ({} build tuples)",
+                self.workers,
+                distribution,
+                build.len()
+            );
+            let parts = match distribution {
+                HashJoinDistribution::Partitioned => {
+                    self.hash_table_builds = self.workers;
+                    self.partitioned_join(&build, &probe)?
+                }
+                _ => {
+                    self.hash_table_builds = 1;
+                    self.broadcast_join(&build, &probe)?
+                }
+            };
+            self.output = parts.into_iter().flatten().collect();
+            self.distribution = Some(distribution);
+            self.index = 0;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let t = self.output.get(self.index).cloned();
+        if t.is_some() {
+            self.index += 1;
+        }
+        Ok(t)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        if self.open {
+            self.left_child.close()?;
+            self.right_child.close()?;
+            self.output.clear();
+            self.index = 0;
+            self.distribution = None;
+            self.hash_table_builds = 0;
+            self.open = false;
+        }
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.index = 0;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{HashEqJoin, TupleIterator};
+    use super::*;
+    use crate::testutil::{execute_iter, new_test_managers};
+    use common::query::bytecode_expr::ByteCodes;
+    use common::testutil::{ColumnDist, TableGen};
+    use std::collections::HashMap;
+
+    fn key_expr(column: usize) -> ByteCodeExpr {
+        let mut expr = ByteCodeExpr::new();
+        expr.add_code(ByteCodes::PushField as usize);
+        expr.add_code(column);
+        expr
+    }
+
+    /// Tuples `(id, key)` with `keys` Zipf distributed keys, joined on the
+    /// second column.
+    fn input(len: i64, keys: u64, seed: u64) -> (TableSchema, Vec<Tuple>) {
+        let schema = TableSchema::from_vecs(
+            vec!["id", "key"],
+            vec![common::DataType::BigInt, common::DataType::BigInt],
+        );
+        let tuples = TableGen::new(schema.clone(), seed)
+            .column("id", ColumnDist::Sequential { start: 0 })
+            .column(
+                "key",
+                ColumnDist::Zipf {
+                    min: 0,
+                    cardinality: keys,
+                    skew: 0.5,
+                },
+            )
+            .generate(len as usize);
+        (schema, tuples)
+    }
+
+    /// Number of tuples of the join of `build` and `probe` on their keys.
+    fn join_size(build: &[Tuple], probe: &[Tuple]) -> usize {
+        let mut keys: HashMap<&Field, usize> = HashMap::new();
+        for tuple in build {
+            *keys.entry(&tuple.field_vals[1]).or_default() += 1;
+        }
+        probe
+            .iter()
+            .map(|tuple| keys.get(&tuple.field_vals[1]).copied().unwrap_or(0))
+            .sum()
+    }
+
+    fn parallel_join(
+        workers: usize,
+        distribution: HashJoinDistribution,
+        build_len: i64,
+    ) -> (Vec<Tuple>, ParallelHashEqJoin) {
+        let (schema, build) = input(build_len, 7, 1);
+        let (_, probe) = input(500, 11, 2);
+        let mut join = ParallelHashEqJoin::new(
+            schema.merge(&schema),
+            key_expr(1),
+            key_expr(1),
+            Box::new(TupleIterator::new(build, schema.clone())),
+            Box::new(TupleIterator::new(probe, schema)),
+            workers,
+            distribution,
+        );
+        join.configure(false);
+        let res = execute_iter(&mut join, true).unwrap();
+        (res, join)
+    }
+
+    fn serial_join(build_len: i64) -> Vec<Tuple> {
+        let (schema, build) = input(build_len, 7, 1);
+        let (_, probe) = input(500, 11, 2);
+        let mut join = HashEqJoin::new(
+            new_test_managers(),
+            schema.merge(&schema),
+            vec![key_expr(1)],
+            vec![key_expr(1)],
+            Box::new(TupleIterator::new(build, schema.clone())),
+            Box::new(TupleIterator::new(probe, schema)),
+        );
+        join.configure(false);
+        execute_iter(&mut join, true).unwrap()
+    }
+
+    #[test]
+    fn test_distributions_match_serial_join() {
+        let expected = serial_join(200);
+        let (_, build) = input(200, 7, 1);
+        let (_, probe) = input(500, 11, 2);
+        assert_eq!(expected.len(), join_size(&build, &probe));
+        for distribution in [
+            HashJoinDistribution::Broadcast,
+            HashJoinDistribution::Partitioned,
+        ] {
+            let (res, join) = parallel_join(4, distribution, 200);
+            assert_eq!(join.distribution(), Some(distribution));
+            assert_eq!(res, expected);
+        }
+    }
+
+    #[test]
+    fn test_broadcast_builds_once() {
+        for workers in [1, 2, 4, 8] {
+            let (_, join) = parallel_join(workers, HashJoinDistribution::Broadcast, 200);
+            assert_eq!(join.hash_table_builds(), 1);
+            let (_, join) = parallel_join(workers, HashJoinDistribution::Partitioned, 200);
+            assert_eq!(join.hash_table_builds(), workers);
+        }
+    }
+
+    #[test]
+    fn test_auto_distribution() {
+        let (_, join) = parallel_join(4, HashJoinDistribution::Auto, 200);
+        assert_eq!(join.distribution(), Some(HashJoinDistribution::Broadcast));
+        let large = BROADCAST_MAX_BUILD_TUPLES as i64 + 1;
+        let (res, join) = parallel_join(4, HashJoinDistribution::Auto, large);
+        assert_eq!(join.distribution(), Some(HashJoinDistribution::Partitioned));
+        assert_eq!(res, serial_join(large));
+    }
+}
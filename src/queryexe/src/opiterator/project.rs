@@ -11,6 +11,8 @@ pub struct Project {
     child: Box<dyn OpIterator>,
     // States (Need to reset on close)
     open: bool,
+    // Tuples of the child read by next_batch() that are not projected yet
+    batch: Vec<Tuple>,
 }
 
 impl Project {
@@ -20,10 +22,20 @@ impl Project {
             open: false,
             schema,
             child,
+            batch: Vec::new(),
         }
     }
 }
 
+/// Evaluates `fields` over `tuple`.
+fn project(fields: &[ByteCodeExpr], tuple: &Tuple) -> Result<Tuple, FairyError> {
+    let mut new_field_vals = Vec::with_capacity(fields.len());
+    for expr in fields {
+        new_field_vals.push(expr.try_eval(tuple)?);
+    }
+    Ok(Tuple::new(new_field_vals))
+}
+
 impl OpIterator for Project {
     fn configure(&mut self, will_rewind: bool) {
         self.child.configure(will_rewind);
@@ -42,21 +54,31 @@ impl OpIterator for Project {
             panic!("Operator has not been opened")
         }
 
-        if let Some(tuple) = self.child.next()? {
-            let mut new_field_vals = Vec::with_capacity(self.fields.len());
-            for expr in &self.fields {
-                let t = expr.eval(&tuple);
-                new_field_vals.push(t);
-            }
-            let t = Tuple::new(new_field_vals);
-            return Ok(Some(t));
+        match self.child.next()? {
+            Some(tuple) => Ok(Some(project(&self.fields, &tuple)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.next_batch(&mut self.batch, max)?;
+        out.reserve(self.batch.len());
+        for tuple in self.batch.drain(..) {
+            out.push(project(&self.fields, &tuple)?);
         }
+        Ok(())
+    }
 
-        Ok(None)
+    fn batches(&self) -> bool {
+        self.child.batches()
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
         self.child.close()?;
+        self.batch.clear();
         self.open = false;
         Ok(())
     }
@@ -78,7 +100,7 @@ impl OpIterator for Project {
 mod test {
     use super::*;
     use crate::opiterator::TupleIterator;
-    use crate::testutil::{execute_iter, TestTuples};
+    use crate::testutil::{execute_batches, execute_iter, TestTuples};
     use common::query::bytecode_expr::{ByteCodeExpr, ByteCodes};
     use common::TableSchema;
 
@@ -169,6 +191,15 @@ mod test {
             assert_eq!(t[4], Tuple::new(vec![f_int(35), f_str("G")]));
             assert_eq!(t[5], Tuple::new(vec![f_int(40), f_str("G")]));
         }
+
+        #[test]
+        fn test_batches() {
+            let expected = run_projection(get_fields_expression());
+            for max in [1, 4, 10] {
+                let mut iter = get_iter(get_fields_expression());
+                assert_eq!(execute_batches(&mut *iter, max).unwrap(), expected);
+            }
+        }
     }
 
     mod opiterator_test {
@@ -0,0 +1,212 @@
+use super::OpIterator;
+use common::physical_expr::plan_schema::{dtypes_compatible, field_matches_dtype, PlanSchema};
+use common::{FairyError, TableSchema, Tuple};
+
+/// Checks that `schema` has the columns of the derived plan schema `expected`.
+///
+/// # Arguments
+///
+/// * `expected` - Schema derived from the physical plan.
+/// * `schema` - Schema declared by an operator.
+pub fn check_schema(expected: &PlanSchema, schema: &TableSchema) -> Result<(), FairyError> {
+    if expected.len() != schema.size() {
+        return Err(FairyError::ExecutionError(format!(
+            "Operator produces {} columns, plan expects {}",
+            schema.size(),
+            expected.len()
+        )));
+    }
+    for (i, (col, attr)) in expected.iter().zip(schema.attributes()).enumerate() {
+        if !dtypes_compatible(&col.dtype, attr.dtype()) {
+            return Err(FairyError::ExecutionError(format!(
+                "Column {} ({}) has type {}, plan expects {}",
+                i,
+                attr.name(),
+                attr.dtype(),
+                col.dtype
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the fields of `tuple` have the types of the derived plan schema.
+fn check_tuple(expected: &PlanSchema, tuple: &Tuple) -> Result<(), FairyError> {
+    if tuple.len() != expected.len() {
+        return Err(FairyError::ExecutionError(format!(
+            "Tuple has {} fields, plan expects {}",
+            tuple.len(),
+            expected.len()
+        )));
+    }
+    for (i, (col, field)) in expected.iter().zip(tuple.field_vals()).enumerate() {
+        if !field_matches_dtype(field, &col.dtype) {
+            return Err(FairyError::ExecutionError(format!(
+                "Field {} is {:?}, plan expects {}",
+                i, field, col.dtype
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Validates the output of its child against the schema derived from the plan.
+///
+/// The declared schema of the child is checked when the operator is opened and
+/// the field types of the first tuple are checked when it is produced, so a
+/// malformed plan fails before any result reaches the client.
+pub struct SchemaCheck {
+    // Parameters (No need to reset on close)
+    /// Schema derived from the physical plan.
+    expected: PlanSchema,
+    /// Child operator whose output is checked.
+    child: Box<dyn OpIterator>,
+
+    // States (Need to reset on close)
+    /// Boolean determining if iterator is open.
+    open: bool,
+    /// Whether the first tuple since opening has been checked.
+    checked_tuple: bool,
+}
+
+impl SchemaCheck {
+    /// SchemaCheck constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - Schema derived from the physical plan.
+    /// * `child` - Child OpIterator whose output is checked.
+    pub fn new(expected: PlanSchema, child: Box<dyn OpIterator>) -> Self {
+        Self {
+            expected,
+            child,
+            open: false,
+            checked_tuple: false,
+        }
+    }
+}
+
+impl OpIterator for SchemaCheck {
+    fn configure(&mut self, will_rewind: bool) {
+        self.child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            check_schema(&self.expected, self.child.get_schema())?;
+            self.child.open()?;
+            self.checked_tuple = false;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let tuple = self.child.next()?;
+        if !self.checked_tuple {
+            if let Some(t) = &tuple {
+                check_tuple(&self.expected, t)?;
+                self.checked_tuple = true;
+            }
+        }
+        Ok(tuple)
+    }
+
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let before = out.len();
+        self.child.next_batch(out, max)?;
+        if !self.checked_tuple {
+            if let Some(t) = out.get(before) {
+                check_tuple(&self.expected, t)?;
+                self.checked_tuple = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn batches(&self) -> bool {
+        self.child.batches()
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.child.close()?;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.child.rewind()
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        self.child.get_schema()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use common::physical_expr::plan_schema::PlanColumn;
+    use common::{attribute::Attribute, DataType, Field};
+
+    fn expected() -> PlanSchema {
+        vec![
+            PlanColumn::new(0, DataType::BigInt, false),
+            PlanColumn::new(1, DataType::String, true),
+        ]
+    }
+
+    fn iter(tuples: Vec<Tuple>, dtypes: Vec<DataType>) -> Box<dyn OpIterator> {
+        let attrs = dtypes
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| Attribute::new(format!("c{}", i), d))
+            .collect();
+        Box::new(TupleIterator::new(tuples, TableSchema::new(attrs)))
+    }
+
+    #[test]
+    fn test_matching_schema_passes() {
+        let tuples = vec![
+            Tuple::new(vec![Field::BigInt(1), Field::String("a".into())]),
+            Tuple::new(vec![Field::BigInt(2), Field::Null]),
+        ];
+        let child = iter(tuples, vec![DataType::BigInt, DataType::Char(8)]);
+        let mut check = SchemaCheck::new(expected(), child);
+        check.open().unwrap();
+        assert!(check.next().unwrap().is_some());
+        assert!(check.next().unwrap().is_some());
+        assert!(check.next().unwrap().is_none());
+        check.close().unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_schema_rejected_at_open() {
+        let child = iter(vec![], vec![DataType::Date, DataType::String]);
+        let mut check = SchemaCheck::new(expected(), child);
+        assert!(matches!(check.open(), Err(FairyError::ExecutionError(_))));
+
+        let child = iter(vec![], vec![DataType::BigInt]);
+        let mut check = SchemaCheck::new(expected(), child);
+        assert!(matches!(check.open(), Err(FairyError::ExecutionError(_))));
+    }
+
+    #[test]
+    fn test_mismatched_tuple_rejected() {
+        let tuples = vec![Tuple::new(vec![Field::Bool(true), Field::Null])];
+        let child = iter(tuples, vec![DataType::BigInt, DataType::String]);
+        let mut check = SchemaCheck::new(expected(), child);
+        check.open().unwrap();
+        assert!(matches!(check.next(), Err(FairyError::ExecutionError(_))));
+    }
+}
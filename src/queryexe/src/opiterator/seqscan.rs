@@ -1,11 +1,103 @@
-use super::OpIterator;
+use super::{Cancellation, OpIterator};
 use crate::{Managers, StorageManager};
+use common::datatypes::compare_fields;
 use common::ids::Permissions;
 use common::ids::{ContainerId, TransactionId};
+use common::metrics::storage_metrics;
+use common::physical::page_dictionary::PageFilter;
+use common::physical_expr::plan_schema::field_matches_dtype;
 use common::prelude::ValueId;
 use common::query::bytecode_expr::ByteCodeExpr;
 use common::traits::storage_trait::StorageTrait;
-use common::{FairyError, Field, TableSchema, Tuple};
+use common::tuple::RawTuple;
+use common::txn_stamps::TxnSnapshot;
+use common::{error::c_err, BinaryOp, FairyError, Field, TableSchema, Tuple};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How a scan treats a record that does not decode against the schema of its table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptRecordMode {
+    /// Fail the scan with the location of the record.
+    #[default]
+    Strict,
+    /// Skip the record and count it, so that the readable rows of a damaged
+    /// table can still be extracted.
+    Tolerant,
+}
+
+impl fmt::Display for CorruptRecordMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorruptRecordMode::Strict => write!(f, "strict"),
+            CorruptRecordMode::Tolerant => write!(f, "tolerant"),
+        }
+    }
+}
+
+/// Corrupt record handling shared by the scans of a plan. Counts the records
+/// skipped by every clone.
+#[derive(Debug, Clone, Default)]
+pub struct RecordCheck {
+    mode: CorruptRecordMode,
+    skipped: Arc<AtomicUsize>,
+}
+
+impl RecordCheck {
+    pub fn new(mode: CorruptRecordMode) -> Self {
+        Self {
+            mode,
+            skipped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn mode(&self) -> CorruptRecordMode {
+        self.mode
+    }
+
+    /// Number of corrupt records skipped so far.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    fn skip(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        storage_metrics()
+            .corrupt_records
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Comparisons of columns of a table with constants that a scan checks on the
+/// bytes of each record, so that the records that fail one are not decoded.
+#[derive(Debug, Clone, Default)]
+pub struct ScanPredicate {
+    /// Column of the table, comparison and constant of each condition.
+    comparisons: Vec<(usize, BinaryOp, Field)>,
+}
+
+impl ScanPredicate {
+    /// Adds the condition `column op value`, where `op` is a comparison.
+    pub fn add(&mut self, column: usize, op: BinaryOp, value: Field) {
+        self.comparisons.push((column, op, value));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comparisons.is_empty()
+    }
+
+    /// Returns true if the fields of the record satisfy every condition. Only
+    /// the compared fields are decoded.
+    fn matches(&self, record: &RawTuple) -> Result<bool, FairyError> {
+        for (column, op, value) in &self.comparisons {
+            if !compare_fields(*op, &record.field(*column)?, value) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
 
 /// Sequential scan operator
 pub struct SeqScan {
@@ -16,6 +108,20 @@ pub struct SeqScan {
     transaction_id: TransactionId,
     filter: Option<ByteCodeExpr>,
     projection: Option<Vec<ByteCodeExpr>>,
+    /// Schema of the table the records are checked against, if any.
+    table_schema: Option<TableSchema>,
+    records: RecordCheck,
+    /// Conditions of the filter above the scan that let pages be skipped by
+    /// their dictionary.
+    page_filter: PageFilter,
+    /// Snapshot limiting the scan to the rows committed when it was taken.
+    snapshot: Option<Arc<TxnSnapshot>>,
+    /// Conditions the records must satisfy, checked before they are decoded.
+    predicate: ScanPredicate,
+    /// Columns of the table that are decoded, in the order they are returned,
+    /// or None to decode whole records.
+    columns: Option<Vec<usize>>,
+    cancellation: Cancellation,
 
     // States (Need to reset on close)
     open: bool,
@@ -49,8 +155,239 @@ impl SeqScan {
             file_iter: None,
             filter,
             projection,
+            table_schema: None,
+            records: RecordCheck::default(),
+            page_filter: PageFilter::default(),
+            snapshot: None,
+            predicate: ScanPredicate::default(),
+            columns: None,
+            cancellation: Cancellation::default(),
+        }
+    }
+
+    /// Only decodes `columns` of the table and returns them in that order. The
+    /// other fields of the records are skipped without being decoded, so they
+    /// are not checked against the schema of the table. As with a projection,
+    /// the tuples returned have no value id.
+    pub fn with_columns(mut self, columns: Vec<usize>) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Only returns the records that satisfy `predicate`. Records that fail it
+    /// are skipped before they are decoded, so they are not checked against
+    /// the schema of the table.
+    pub fn with_predicate(mut self, predicate: ScanPredicate) -> Self {
+        self.predicate = predicate;
+        self
+    }
+
+    /// Skips the pages whose dictionary shows that none of their records
+    /// satisfies `page_filter`. The records of the other pages are all returned.
+    pub fn with_page_filter(mut self, page_filter: PageFilter) -> Self {
+        self.page_filter = page_filter;
+        self
+    }
+
+    /// Only returns the records of the table that were committed when
+    /// `snapshot` was taken. The table must stamp its records with the id of the
+    /// transaction that wrote them.
+    pub fn with_snapshot(mut self, snapshot: Arc<TxnSnapshot>) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Fails the scan once `cancellation` is cancelled or its query timed out.
+    /// It is checked before every record is read.
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Iterator over the whole table.
+    fn table_iter(&self) -> <StorageManager as StorageTrait>::ValIterator {
+        self.managers.sm.get_filtered_iterator(
+            self.container_id,
+            self.transaction_id,
+            Permissions::ReadOnly,
+            self.page_filter.clone(),
+        )
+    }
+
+    /// Checks every record against the schema of the table before it is used,
+    /// handling the ones that do not match as `records` says. Records that are
+    /// not tuples at all are always reported.
+    pub fn with_record_check(mut self, table_schema: &TableSchema, records: RecordCheck) -> Self {
+        self.table_schema = Some(table_schema.clone());
+        self.records = records;
+        self
+    }
+
+    /// Reads records from `file_iter` until one is returned.
+    fn next_tuple(
+        &mut self,
+        file_iter: &mut <StorageManager as StorageTrait>::ValIterator,
+    ) -> Result<Option<Tuple>, FairyError> {
+        loop {
+            self.cancellation.check("scan")?;
+            let Some((bytes, id)) = file_iter.next() else {
+                break;
+            };
+            self.index = Some(id);
+            if let Some(snapshot) = &self.snapshot {
+                if !snapshot.covers_page(id.page_id) {
+                    // Pages are read in order, the rest were added after the snapshot.
+                    break;
+                }
+            }
+            // Create the tuple
+            let record = read_record(
+                &bytes,
+                &self.predicate,
+                self.columns.as_deref(),
+                self.table_schema.as_ref(),
+            );
+            let mut tuple = match record {
+                Ok(Some(tuple)) => tuple,
+                Ok(None) => continue,
+                Err(_) if self.records.mode() == CorruptRecordMode::Tolerant => {
+                    self.records.skip();
+                    continue;
+                }
+                Err(reason) => {
+                    return Err(FairyError::ExecutionError(format!(
+                        "Corrupt record in container {} page {} slot {}: {}",
+                        id.container_id,
+                        id.page_id.map_or("?".to_string(), |p| p.to_string()),
+                        id.slot_id.map_or("?".to_string(), |s| s.to_string()),
+                        reason
+                    )))
+                }
+            };
+            if self.columns.is_none() {
+                tuple.value_id = Some(id);
+            }
+
+            if self
+                .snapshot
+                .as_ref()
+                .is_some_and(|snapshot| !snapshot.is_visible(tuple.tid))
+            {
+                continue;
+            }
+
+            if let Some(filter) = &self.filter {
+                match filter.try_eval(&tuple)? {
+                    Field::Bool(b) => {
+                        if !b {
+                            continue;
+                        }
+                    }
+                    Field::Null => continue,
+                    _ => return Err(c_err("Filter must evaluate to a boolean")),
+                }
+            }
+
+            if let Some(projection) = &self.projection {
+                let mut new_field_vals = Vec::with_capacity(projection.len());
+                for expr in projection {
+                    let t = expr.try_eval(&tuple)?;
+                    new_field_vals.push(t);
+                }
+                return Ok(Some(Tuple::new(new_field_vals)));
+            } else {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Decodes a record, checking its fields against the table schema if there is one.
+fn decode_record(bytes: &[u8], table_schema: Option<&TableSchema>) -> Result<Tuple, String> {
+    let tuple = Tuple::try_from_bytes(bytes)
+        .map_err(|e| format!("{} bytes do not decode to a tuple: {}", bytes.len(), e))?;
+    let Some(schema) = table_schema else {
+        return Ok(tuple);
+    };
+    // Records written before a column was added have fewer fields.
+    if tuple.len() > schema.size() {
+        return Err(format!(
+            "record has {} fields, table has {} columns",
+            tuple.len(),
+            schema.size()
+        ));
+    }
+    for (attr, field) in schema.attributes().zip(tuple.field_vals()) {
+        if !field_matches_dtype(field, attr.dtype()) {
+            return Err(format!(
+                "field {} is {:?}, column has type {}",
+                attr.name(),
+                field,
+                attr.dtype()
+            ));
+        }
+    }
+    Ok(tuple)
+}
+
+/// Decodes a record, or returns None if it fails `predicate`. With `columns`,
+/// only those fields are decoded. The decoded fields are checked against the
+/// table schema if there is one.
+fn read_record(
+    bytes: &[u8],
+    predicate: &ScanPredicate,
+    columns: Option<&[usize]>,
+    table_schema: Option<&TableSchema>,
+) -> Result<Option<Tuple>, String> {
+    if predicate.is_empty() && columns.is_none() {
+        return decode_record(bytes, table_schema).map(Some);
+    }
+    let record = RawTuple::new(bytes)
+        .ok_or_else(|| format!("{} bytes do not decode to a tuple", bytes.len()))?;
+    if !predicate.matches(&record).map_err(|e| e.to_string())? {
+        return Ok(None);
+    }
+    match columns {
+        Some(columns) => read_columns(&record, columns, table_schema).map(Some),
+        None => decode_record(bytes, table_schema).map(Some),
+    }
+}
+
+/// Decodes `columns` of a record, checking them against the table schema if
+/// there is one.
+fn read_columns(
+    record: &RawTuple,
+    columns: &[usize],
+    table_schema: Option<&TableSchema>,
+) -> Result<Tuple, String> {
+    if let Some(schema) = table_schema {
+        if record.len() > schema.size() {
+            return Err(format!(
+                "record has {} fields, table has {} columns",
+                record.len(),
+                schema.size()
+            ));
+        }
+    }
+    let mut fields = Vec::with_capacity(columns.len());
+    for &column in columns {
+        let field = record.field(column).map_err(|e| e.to_string())?;
+        if let Some(attr) = table_schema.and_then(|schema| schema.get_attribute(column)) {
+            if !field_matches_dtype(&field, attr.dtype()) {
+                return Err(format!(
+                    "field {} is {:?}, column has type {}",
+                    attr.name(),
+                    field,
+                    attr.dtype()
+                ));
+            }
         }
+        fields.push(field);
     }
+    let mut tuple = Tuple::new(fields);
+    tuple.tid = record.tid();
+    Ok(tuple)
 }
 
 impl OpIterator for SeqScan {
@@ -68,11 +405,7 @@ impl OpIterator for SeqScan {
                     index,
                 ))
             } else {
-                Some(self.managers.sm.get_iterator(
-                    self.container_id,
-                    self.transaction_id,
-                    Permissions::ReadOnly,
-                ))
+                Some(self.table_iter())
             };
         }
         self.open = true;
@@ -83,46 +416,40 @@ impl OpIterator for SeqScan {
         if !self.open {
             panic!("Operator has not been opened")
         }
-        let file_iter = self
+        let mut file_iter = self
             .file_iter
-            .as_mut()
+            .take()
             .expect("File iterator should be set on open");
+        let res = self.next_tuple(&mut file_iter);
+        self.file_iter = Some(file_iter);
+        res
+    }
 
-        for (bytes, id) in file_iter.by_ref() {
-            // Create the tuple
-            println!(
-                "DEBUG: SeqScan.next() got bytes.len={} value_id={:?}",
-                bytes.len(),
-                id
-            );
-            let mut tuple = Tuple::from_bytes(&bytes);
-            tuple.value_id = Some(id);
-            self.index = Some(id);
-
-            if let Some(filter) = &self.filter {
-                match filter.eval(&tuple) {
-                    Field::Bool(b) => {
-                        if !b {
-                            continue;
-                        }
-                    }
-                    _ => panic!("Filter must evaluate to a boolean"),
-                }
-            }
-
-            if let Some(projection) = &self.projection {
-                let mut new_field_vals = Vec::with_capacity(projection.len());
-                for expr in projection {
-                    let t = expr.eval(&tuple);
-                    new_field_vals.push(t);
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let mut file_iter = self
+            .file_iter
+            .take()
+            .expect("File iterator should be set on open");
+        let mut res = Ok(());
+        for _ in 0..max {
+            match self.next_tuple(&mut file_iter) {
+                Ok(Some(tuple)) => out.push(tuple),
+                Ok(None) => break,
+                Err(e) => {
+                    res = Err(e);
+                    break;
                 }
-                return Ok(Some(Tuple::new(new_field_vals)));
-            } else {
-                return Ok(Some(tuple));
             }
         }
-        println!("DEBUG: SeqScan.next() → end of data");
-        Ok(None)
+        self.file_iter = Some(file_iter);
+        res
+    }
+
+    fn batches(&self) -> bool {
+        true
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
@@ -136,11 +463,7 @@ impl OpIterator for SeqScan {
         if !self.open {
             panic!("Operator has not been opened")
         }
-        self.file_iter = Some(self.managers.sm.get_iterator(
-            self.container_id,
-            self.transaction_id,
-            Permissions::ReadOnly,
-        ));
+        self.file_iter = Some(self.table_iter());
         self.index = None;
         Ok(())
     }
@@ -154,8 +477,13 @@ impl OpIterator for SeqScan {
 #[allow(unused_must_use)]
 mod test {
     use super::*;
-    use crate::testutil::{execute_iter, new_test_managers, TestTuples};
-    use common::ids::TransactionId;
+    use crate::testutil::{
+        execute_batches, execute_iter, new_test_managers, TestSetup, TestTuples,
+    };
+    use common::ids::{TidType, TransactionId};
+    use common::testutil::{load_table, ColumnDist, TableGen};
+    use common::txn_stamps::StampedWrites;
+    use common::DataType;
 
     fn get_iter() -> Box<dyn OpIterator> {
         // Create test SM with a container
@@ -175,22 +503,168 @@ mod test {
         iter
     }
 
-    fn run_scan() -> Vec<Tuple> {
-        let mut iter = get_iter();
-        execute_iter(&mut *iter, false).unwrap()
-    }
-
     mod scan_test {
         use super::*;
 
         #[test]
         fn test_scan() {
-            let tuples = run_scan();
-            let expected = TestTuples::new("");
-            for (t, e) in tuples.iter().zip(expected.tuples.iter()) {
+            let setup = TestSetup::new_empty();
+            let schema = TableSchema::from_vecs(
+                vec!["id", "name", "day"],
+                vec![DataType::BigInt, DataType::String, DataType::Date],
+            );
+            // Names of up to 60 bytes, and days that are NULL a fifth of the time.
+            let gen = TableGen::new(schema, 9)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .column(
+                    "name",
+                    ColumnDist::Strings {
+                        cardinality: 50,
+                        min_len: 20,
+                        max_len: 60,
+                    },
+                )
+                .nulls("day", 0.2);
+            let managers = setup.managers;
+            let (table, expected) =
+                load_table(&setup.catalog, managers.sm, managers.stats, "t", &gen, 1000).unwrap();
+            let tid = TransactionId::new();
+            let mut iter = SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None);
+            iter.configure(false);
+            let tuples = execute_iter(&mut iter, false).unwrap();
+            assert_eq!(tuples.len(), expected.len());
+            for (t, e) in tuples.iter().zip(&expected) {
                 assert_eq!(t.field_vals, e.field_vals)
             }
         }
+
+        #[test]
+        fn test_predicate() {
+            let setup = TestSetup::new_empty();
+            let schema = TableSchema::from_vecs(
+                vec!["id", "name", "v"],
+                vec![DataType::BigInt, DataType::String, DataType::Int],
+            );
+            let gen = TableGen::new(schema, 3)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .column(
+                    "v",
+                    ColumnDist::Zipf {
+                        min: 0,
+                        cardinality: 20,
+                        skew: 1.0,
+                    },
+                )
+                .nulls("v", 0.2);
+            let managers = setup.managers;
+            let (table, expected) =
+                load_table(&setup.catalog, managers.sm, managers.stats, "t", &gen, 1000).unwrap();
+            let tid = TransactionId::new();
+            for (op, val) in [
+                (BinaryOp::Eq, Field::Int(3)),
+                (BinaryOp::Gt, Field::Int(10)),
+                (BinaryOp::Neq, Field::Int(0)),
+            ] {
+                let mut predicate = ScanPredicate::default();
+                predicate.add(2, op, val.clone());
+                predicate.add(0, BinaryOp::Lt, Field::BigInt(800));
+                let mut iter = SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None)
+                    .with_predicate(predicate);
+                iter.configure(false);
+                let tuples = execute_iter(&mut iter, false).unwrap();
+                let matching: Vec<&Vec<Field>> = expected
+                    .iter()
+                    .filter(|t| {
+                        compare_fields(op, &t.field_vals[2], &val)
+                            && compare_fields(BinaryOp::Lt, &t.field_vals[0], &Field::BigInt(800))
+                    })
+                    .map(|t| &t.field_vals)
+                    .collect();
+                assert!(!matching.is_empty());
+                assert_eq!(
+                    tuples.iter().map(|t| &t.field_vals).collect::<Vec<_>>(),
+                    matching
+                );
+            }
+        }
+
+        #[test]
+        fn test_columns() {
+            let setup = TestSetup::new_empty();
+            let schema = TableSchema::from_vecs(
+                vec!["id", "name", "v"],
+                vec![DataType::BigInt, DataType::String, DataType::Int],
+            );
+            let gen = TableGen::new(schema, 5)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .nulls("name", 0.3)
+                .nulls("v", 0.2);
+            let managers = setup.managers;
+            let (table, _) =
+                load_table(&setup.catalog, managers.sm, managers.stats, "t", &gen, 500).unwrap();
+            let tid = TransactionId::new();
+            let mut full = SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None);
+            full.configure(false);
+            let full = execute_iter(&mut full, false).unwrap();
+            for columns in [vec![2, 0], vec![1], vec![0, 1, 2]] {
+                let mut predicate = ScanPredicate::default();
+                predicate.add(0, BinaryOp::Gt, Field::BigInt(100));
+                let mut iter = SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None)
+                    .with_columns(columns.clone())
+                    .with_predicate(predicate);
+                iter.configure(false);
+                let tuples = execute_iter(&mut iter, false).unwrap();
+                let expected: Vec<(TidType, Vec<Field>)> = full
+                    .iter()
+                    .filter(|t| compare_fields(BinaryOp::Gt, &t.field_vals[0], &Field::BigInt(100)))
+                    .map(|t| {
+                        (
+                            t.tid,
+                            columns.iter().map(|&i| t.field_vals[i].clone()).collect(),
+                        )
+                    })
+                    .collect();
+                assert_eq!(expected.len(), 399);
+                assert_eq!(
+                    tuples
+                        .into_iter()
+                        .map(|t| (t.tid, t.field_vals))
+                        .collect::<Vec<_>>(),
+                    expected
+                );
+            }
+        }
+
+        #[test]
+        fn test_batches() {
+            let setup = TestSetup::new_empty();
+            let schema = TableSchema::from_vecs(
+                vec!["id", "name", "v"],
+                vec![DataType::BigInt, DataType::String, DataType::Int],
+            );
+            let gen = TableGen::new(schema, 9)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .nulls("v", 0.2);
+            let managers = setup.managers;
+            let (table, _) =
+                load_table(&setup.catalog, managers.sm, managers.stats, "t", &gen, 700).unwrap();
+            let tid = TransactionId::new();
+            let scan = || {
+                let mut predicate = ScanPredicate::default();
+                predicate.add(0, BinaryOp::Gt, Field::BigInt(50));
+                let mut iter = SeqScan::new(managers, &table.schema, &table.c_id, tid, None, None)
+                    .with_predicate(predicate);
+                iter.configure(false);
+                iter
+            };
+            let expected = execute_iter(&mut scan(), false).unwrap();
+            assert_eq!(expected.len(), 649);
+            for max in [1, 100, 1000] {
+                let mut iter = scan();
+                assert!(iter.batches());
+                assert_eq!(execute_batches(&mut iter, max).unwrap(), expected);
+            }
+        }
     }
 
     mod opiterator_test {
@@ -223,6 +697,103 @@ mod test {
             iter.close().unwrap();
         }
 
+        #[test]
+        fn test_corrupt_records() {
+            let managers = new_test_managers();
+            let cid = 0;
+            managers.sm.create_table(cid).unwrap();
+            let setup = TestTuples::new("");
+            let tid = TransactionId::new();
+            for t in &setup.tuples[..3] {
+                managers.sm.insert_value(cid, t.to_bytes(), tid);
+            }
+            // Not a tuple at all, then a tuple whose first field has the wrong type.
+            let garbage = managers.sm.insert_value(cid, vec![0xff, 0x13, 0x00], tid);
+            let mistyped = Tuple::new(vec![Field::String("x".to_string())]);
+            managers.sm.insert_value(cid, mistyped.to_bytes(), tid);
+            for t in &setup.tuples[3..] {
+                managers.sm.insert_value(cid, t.to_bytes(), tid);
+            }
+            let mut strict = SeqScan::new(managers, &setup.schema, &cid, tid, None, None)
+                .with_record_check(&setup.schema, RecordCheck::new(CorruptRecordMode::Strict));
+            strict.configure(false);
+            let err = execute_iter(&mut strict, false).unwrap_err();
+            let expected = format!(
+                "Corrupt record in container {} page {} slot {}: 3 bytes do not decode to a tuple",
+                cid,
+                garbage.page_id.unwrap(),
+                garbage.slot_id.unwrap()
+            );
+            match err {
+                FairyError::ExecutionError(msg) => assert!(msg.starts_with(&expected), "{}", msg),
+                e => panic!("Expected execution error, got {:?}", e),
+            }
+
+            let before = storage_metrics().corrupt_records.load(Ordering::Relaxed);
+            let records = RecordCheck::new(CorruptRecordMode::Tolerant);
+            let mut tolerant = SeqScan::new(managers, &setup.schema, &cid, tid, None, None)
+                .with_record_check(&setup.schema, records.clone());
+            tolerant.configure(false);
+            let tuples = execute_iter(&mut tolerant, false).unwrap();
+            assert_eq!(
+                tuples.iter().map(|t| &t.field_vals).collect::<Vec<_>>(),
+                setup
+                    .tuples
+                    .iter()
+                    .map(|t| &t.field_vals)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(records.skipped(), 2);
+            assert!(storage_metrics().corrupt_records.load(Ordering::Relaxed) >= before + 2);
+        }
+
+        #[test]
+        fn test_snapshot() {
+            let managers = new_test_managers();
+            let cid = 0;
+            managers.sm.create_table(cid).unwrap();
+            let setup = TestTuples::new("");
+            let writes = StampedWrites::new();
+            let insert = |tuples: &[Tuple], tid: TransactionId| {
+                for t in tuples {
+                    let mut t = t.clone();
+                    t.tid = tid.id();
+                    managers.sm.insert_value(cid, t.to_bytes(), tid);
+                }
+            };
+            let (committed, running, late) = (
+                TransactionId::new(),
+                TransactionId::new(),
+                TransactionId::new(),
+            );
+            let write = writes.begin(committed);
+            insert(&setup.tuples[..3], committed);
+            write.commit();
+            let write = writes.begin(running);
+            insert(&setup.tuples[3..4], running);
+
+            let snapshot = Arc::new(writes.snapshot(|| managers.sm.num_pages(cid)));
+            write.commit();
+            let _write = writes.begin(late);
+            insert(&setup.tuples[4..], late);
+            let scan = |snapshot: Arc<TxnSnapshot>| {
+                let mut iter = SeqScan::new(managers, &setup.schema, &cid, late, None, None)
+                    .with_snapshot(snapshot);
+                iter.configure(false);
+                let tuples = execute_iter(&mut iter, false).unwrap();
+                tuples.into_iter().map(|t| t.field_vals).collect::<Vec<_>>()
+            };
+            let fields = |tuples: &[Tuple]| {
+                tuples
+                    .iter()
+                    .map(|t| t.field_vals.clone())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(scan(snapshot), fields(&setup.tuples[..3]));
+            let snapshot = writes.snapshot(|| managers.sm.num_pages(cid));
+            assert_eq!(scan(Arc::new(snapshot)), fields(&setup.tuples[..4]));
+        }
+
         #[test]
         fn test_rewind() {
             let mut iter = get_iter();
@@ -1,12 +1,25 @@
-use super::OpIterator;
+use super::{Cancellation, OpIterator};
+use crate::temp_space::TempReservation;
 use crate::Managers;
 use common::query::bytecode_expr::ByteCodeExpr;
 
 use common::{FairyError, Field, TableSchema, Tuple};
 
 use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+
+pub(super) type SortFields = Vec<(ByteCodeExpr, bool, bool)>; // (field, asc, nulls_first)
 
 /// Sort operator
+///
+/// Tuples are sorted in memory while they take at most `memory_limit` bytes.
+/// Larger inputs are cut into runs of that size, which are sorted and written
+/// to files of the temporary space, and then merged. The files are removed
+/// and their space released when the operator is closed or fails.
 pub struct Sort {
     #[allow(dead_code)]
     // Static objects (No need to reset on close)
@@ -14,34 +27,245 @@ pub struct Sort {
 
     // Parameters (No need to reset on close)
     schema: TableSchema,
-    fields: Vec<(ByteCodeExpr, bool)>, // (field, asc)
+    fields: Arc<SortFields>,
     child: Box<dyn OpIterator>,
     will_rewind: bool,
+    memory_limit: usize,
+    cancellation: Cancellation,
 
     // States (Need to reset on close)
     open: bool,
     sorted_data: Vec<(Vec<Field>, Tuple)>,
     index: usize, // Stores the index of next tuple to return
+    temp: TempReservation,
+    runs: Vec<SortRun>,
+    merge: BinaryHeap<MergeHead>,
+    readers: Vec<RunReader>,
+}
+
+/// Sorted run of the input written to a file of the temporary space, as the
+/// length of every tuple followed by its bytes.
+struct SortRun {
+    file: NamedTempFile,
+    tuples: usize,
+}
+
+/// Position of the merge in a run.
+struct RunReader {
+    reader: BufReader<File>,
+    remaining: usize,
+}
+
+impl RunReader {
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        self.remaining -= 1;
+        Ok(Some(Tuple::try_from_bytes(&bytes)?))
+    }
+}
+
+/// Next tuple of a run during the merge. The heap holds the head of every run
+/// that is not exhausted, ordered so that the next tuple to return is on top.
+struct MergeHead {
+    key: Vec<Field>,
+    tuple: Tuple,
+    run: usize,
+    fields: Arc<SortFields>,
+}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, so the order is reversed. Ties go to the
+        // earlier run.
+        compare_rows(&self.fields, &other.key, &self.key).then(other.run.cmp(&self.run))
+    }
 }
 
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeHead {}
+
 impl Sort {
+    /// Sort operator constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `managers` - Managers of the server, whose configuration bounds the memory of the sort.
+    /// * `fields` - Sort keys, with their direction and whether NULLs come first.
+    /// * `schema` - Schema of the child.
+    /// * `child` - Child operator whose tuples are sorted.
+    /// * `temp` - Temporary space the runs are written to.
     pub fn new(
         managers: &'static Managers,
-        fields: Vec<(ByteCodeExpr, bool)>,
+        fields: SortFields,
         schema: TableSchema,
         child: Box<dyn OpIterator>,
+        temp: TempReservation,
     ) -> Self {
         Self {
             managers,
             open: false,
             schema,
-            fields,
+            fields: Arc::new(fields),
             sorted_data: Vec::new(),
             child,
             index: 0,
             will_rewind: true,
+            memory_limit: managers.config.sort_memory_bytes,
+            cancellation: Cancellation::default(),
+            temp,
+            runs: Vec::new(),
+            merge: BinaryHeap::new(),
+            readers: Vec::new(),
         }
     }
+
+    /// Sets the bytes of tuples the sort keeps in memory, instead of
+    /// `sort_memory_bytes` of the configuration.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Fails the sort once `cancellation` is cancelled or its query timed out.
+    /// It is checked for every tuple read from the child or written to a run,
+    /// and the runs written so far are removed.
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Reads the child, sorting it in memory or into runs.
+    fn sort_input(&mut self) -> Result<(), FairyError> {
+        self.child.open()?;
+        let mut bytes = 0;
+        while let Some(tuple) = self.child.next()? {
+            self.cancellation.check("sort")?;
+            let sort_key = self.fields.iter().map(|(f, _, _)| f.eval(&tuple)).collect();
+            bytes += tuple.size();
+            self.sorted_data.push((sort_key, tuple));
+            if bytes > self.memory_limit {
+                self.spill_run()?;
+                bytes = 0;
+            }
+        }
+        self.child.close()?;
+        if self.runs.is_empty() {
+            let fields = &self.fields;
+            // Sort it by reverse order so that we can pop the elements from
+            // the back when returning the tuples by next().
+            // Note that pop is O(1), but remove(0) is O(n)
+            self.sorted_data
+                .sort_by(|(a, _), (b, _)| compare_rows(fields, b, a));
+            self.index = self.sorted_data.len(); // index of the last element
+        } else {
+            if !self.sorted_data.is_empty() {
+                self.spill_run()?;
+            }
+            self.start_merge()?;
+        }
+        Ok(())
+    }
+
+    /// Sorts the tuples in memory and writes them to a new run.
+    fn spill_run(&mut self) -> Result<(), FairyError> {
+        let fields = &self.fields;
+        self.sorted_data
+            .sort_by(|(a, _), (b, _)| compare_rows(fields, a, b));
+        let mut writer = BufWriter::new(self.temp.create_file()?);
+        let tuples = self.sorted_data.len();
+        for (_, tuple) in self.sorted_data.drain(..) {
+            self.cancellation.check("sort")?;
+            let bytes = tuple.to_bytes();
+            self.temp.grow(4 + bytes.len() as u64)?;
+            writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            writer.write_all(&bytes)?;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        self.runs.push(SortRun { file, tuples });
+        Ok(())
+    }
+
+    /// Starts merging the runs from their first tuples.
+    fn start_merge(&mut self) -> Result<(), FairyError> {
+        self.merge.clear();
+        self.readers.clear();
+        for run in &self.runs {
+            self.readers.push(RunReader {
+                reader: BufReader::new(run.file.reopen()?),
+                remaining: run.tuples,
+            });
+        }
+        for run in 0..self.readers.len() {
+            self.push_head(run)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the next tuple of run `run`, if any, to the merge.
+    fn push_head(&mut self, run: usize) -> Result<(), FairyError> {
+        if let Some(tuple) = self.readers[run].next()? {
+            let key = self.fields.iter().map(|(f, _, _)| f.eval(&tuple)).collect();
+            self.merge.push(MergeHead {
+                key,
+                tuple,
+                run,
+                fields: self.fields.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Removes the runs and the tuples kept in memory.
+    fn clear(&mut self) {
+        self.sorted_data.clear();
+        self.index = 0;
+        self.merge.clear();
+        self.readers.clear();
+        self.runs.clear();
+        self.temp.release();
+    }
+}
+
+/// Compares two sort keys in the order they are returned. NULLs come first or
+/// last regardless of the direction.
+fn compare_keys(a: &Field, b: &Field, asc: bool, nulls_first: bool) -> Ordering {
+    match (a, b) {
+        (Field::Null, Field::Null) => Ordering::Equal,
+        (Field::Null, _) if nulls_first => Ordering::Less,
+        (Field::Null, _) => Ordering::Greater,
+        (_, Field::Null) if nulls_first => Ordering::Greater,
+        (_, Field::Null) => Ordering::Less,
+        _ if asc => a.cmp(b),
+        _ => b.cmp(a),
+    }
+}
+
+/// Compares the sort keys of two tuples in the order they are returned.
+pub(super) fn compare_rows(fields: &SortFields, a: &[Field], b: &[Field]) -> Ordering {
+    for (i, (_, asc, nulls_first)) in fields.iter().enumerate() {
+        let res = compare_keys(&a[i], &b[i], *asc, *nulls_first);
+        if res != Ordering::Equal {
+            return res;
+        }
+    }
+    Ordering::Equal
 }
 
 impl OpIterator for Sort {
@@ -52,34 +276,10 @@ impl OpIterator for Sort {
 
     fn open(&mut self) -> Result<(), FairyError> {
         if !self.open {
-            self.child.open()?;
-            while let Some(tuple) = self.child.next()? {
-                let mut sort_key = Vec::new();
-                for (field, _) in &self.fields {
-                    sort_key.push(field.eval(&tuple));
-                }
-                self.sorted_data.push((sort_key, tuple));
+            if let Err(e) = self.sort_input() {
+                self.clear();
+                return Err(e);
             }
-            self.child.close()?;
-            self.sorted_data
-                .sort_by(|(a_sort_key, _), (b_sort_key, _)| {
-                    for (i, (_, asc)) in self.fields.iter().enumerate() {
-                        // Sort it by reverse order so that we can
-                        // pop the elements from the back when
-                        // returning the tuples by next().
-                        // Note that pop is O(1), but remove(0) is O(n)
-                        let res = if !*asc {
-                            a_sort_key[i].cmp(&b_sort_key[i])
-                        } else {
-                            b_sort_key[i].cmp(&a_sort_key[i])
-                        };
-                        if res != Ordering::Equal {
-                            return res;
-                        }
-                    }
-                    Ordering::Equal
-                });
-            self.index = self.sorted_data.len(); // index of the last element
             self.open = true;
         }
         Ok(())
@@ -89,6 +289,16 @@ impl OpIterator for Sort {
         if !self.open {
             panic!("Operator has not been opened")
         }
+        if !self.runs.is_empty() {
+            // The runs stay on disk, so a rewind merges them again.
+            return match self.merge.pop() {
+                None => Ok(None),
+                Some(head) => {
+                    self.push_head(head.run)?;
+                    Ok(Some(head.tuple))
+                }
+            };
+        }
         if self.will_rewind {
             // do not consume the iterator
             if self.index == 0 {
@@ -110,11 +320,9 @@ impl OpIterator for Sort {
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
-        self.child.close()?;
-        self.sorted_data.clear();
-        self.index = 0;
+        self.clear();
         self.open = false;
-        Ok(())
+        self.child.close()
     }
 
     fn rewind(&mut self) -> Result<(), FairyError> {
@@ -124,6 +332,9 @@ impl OpIterator for Sort {
         if !self.will_rewind {
             panic!("Cannot rewind a Sort operator with will_rewind set to false")
         }
+        if !self.runs.is_empty() {
+            return self.start_merge();
+        }
         self.index = self.sorted_data.len();
         Ok(())
     }
@@ -141,10 +352,16 @@ mod test {
 
     use super::*;
     use crate::opiterator::TupleIterator;
+    use crate::temp_space::TempSpace;
     use crate::testutil::execute_iter;
     use crate::testutil::TestTuples;
+    use common::ids::TransactionId;
 
-    fn get_iter(fields: Vec<(ByteCodeExpr, bool)>) -> Box<dyn OpIterator> {
+    fn temp(managers: &'static Managers) -> TempReservation {
+        TempReservation::new(managers.temp.clone(), TransactionId::new())
+    }
+
+    fn get_iter(fields: Vec<(ByteCodeExpr, bool, bool)>) -> Box<dyn OpIterator> {
         let setup = TestTuples::new("");
         let managers = crate::testutil::new_test_managers();
         let mut iter = Box::new(Sort::new(
@@ -152,12 +369,13 @@ mod test {
             fields,
             setup.schema.clone(),
             Box::new(TupleIterator::new(setup.tuples, setup.schema)),
+            temp(managers),
         ));
         iter.configure(false);
         iter
     }
 
-    fn get_sort_fields() -> Vec<(ByteCodeExpr, bool)> {
+    fn get_sort_fields() -> Vec<(ByteCodeExpr, bool, bool)> {
         // Input:
         // 1 1 3 E
         // 2 1 3 G
@@ -173,10 +391,10 @@ mod test {
         // 3 1 4 A
         // 2 1 3 G
         // 1 1 3 E
-        vec![(colidx_expr(1), false), (colidx_expr(0), false)]
+        vec![(colidx_expr(1), false, true), (colidx_expr(0), false, true)]
     }
 
-    fn run_sort(fields: Vec<(ByteCodeExpr, bool)>) -> Vec<Tuple> {
+    fn run_sort(fields: Vec<(ByteCodeExpr, bool, bool)>) -> Vec<Tuple> {
         let mut iter = get_iter(fields);
         execute_iter(&mut *iter, false).unwrap()
     }
@@ -220,6 +438,158 @@ mod test {
         }
     }
 
+    mod nulls_test {
+        use super::*;
+
+        fn sorted_keys(asc: bool, nulls_first: bool) -> Vec<Field> {
+            let setup = TestTuples::new("");
+            let tuples = [Field::Null, f_int(2), Field::Null, f_int(1)]
+                .into_iter()
+                .map(|key| Tuple::new(vec![f_int(0), key, f_int(0), f_str("A")]))
+                .collect();
+            let managers = crate::testutil::new_test_managers();
+            let mut iter = Sort::new(
+                managers,
+                vec![(colidx_expr(1), asc, nulls_first)],
+                setup.schema.clone(),
+                Box::new(TupleIterator::new(tuples, setup.schema)),
+                temp(managers),
+            );
+            iter.configure(false);
+            execute_iter(&mut iter, false)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.get_field(1).unwrap().clone())
+                .collect()
+        }
+
+        #[test]
+        fn test_nulls_last() {
+            assert_eq!(
+                sorted_keys(true, false),
+                vec![f_int(1), f_int(2), Field::Null, Field::Null]
+            );
+            assert_eq!(
+                sorted_keys(false, false),
+                vec![f_int(2), f_int(1), Field::Null, Field::Null]
+            );
+        }
+
+        #[test]
+        fn test_nulls_first() {
+            assert_eq!(
+                sorted_keys(true, true),
+                vec![Field::Null, Field::Null, f_int(1), f_int(2)]
+            );
+            assert_eq!(
+                sorted_keys(false, true),
+                vec![Field::Null, Field::Null, f_int(2), f_int(1)]
+            );
+        }
+    }
+
+    mod external_sort_test {
+        use super::*;
+        use common::{PAGE_SIZE, PAGE_SLOTS};
+        use std::path::Path;
+
+        /// Memory limit of the sorts, the size of a page of slots.
+        const MEMORY_LIMIT: usize = PAGE_SLOTS * PAGE_SIZE;
+        const TUPLES: i64 = 20_000;
+
+        /// Tuples taking several times `MEMORY_LIMIT`, in no particular order.
+        fn large_input() -> (TableSchema, Vec<Tuple>) {
+            let schema = TestTuples::new("").schema;
+            let tuples: Vec<Tuple> = (0..TUPLES)
+                .map(|i| {
+                    let i = (i * 7919) % TUPLES;
+                    let payload = format!("{:0>40}", i);
+                    Tuple::new(vec![f_int(i), f_int(i % 7), f_int(0), f_str(&payload)])
+                })
+                .collect();
+            let size: usize = tuples.iter().map(Tuple::size).sum();
+            assert!(size > 4 * MEMORY_LIMIT, "{}", size);
+            (schema, tuples)
+        }
+
+        fn temp_files(dir: &Path) -> usize {
+            std::fs::read_dir(dir).map_or(0, |files| files.count())
+        }
+
+        #[test]
+        fn test_sort_larger_than_memory() {
+            let managers = crate::testutil::new_test_managers();
+            let (schema, tuples) = large_input();
+            // By the second column, and the first one descending.
+            let fields = vec![(colidx_expr(1), true, true), (colidx_expr(0), false, true)];
+            let mut iter = Sort::new(
+                managers,
+                fields,
+                schema.clone(),
+                Box::new(TupleIterator::new(tuples.clone(), schema)),
+                temp(managers),
+            )
+            .with_memory_limit(MEMORY_LIMIT);
+            iter.configure(true);
+            let sorted = execute_iter(&mut iter, false).unwrap();
+            let temp_dir = managers.config.temp_dir();
+            assert!(temp_files(&temp_dir) > 4);
+            assert!(managers.temp.used() > 0);
+
+            let mut expected = tuples;
+            expected.sort_by(|a, b| {
+                let key = |t: &Tuple| {
+                    (
+                        t.get_field(1).cloned(),
+                        std::cmp::Reverse(t.get_field(0).cloned()),
+                    )
+                };
+                key(a).cmp(&key(b))
+            });
+            assert_eq!(sorted, expected);
+
+            iter.rewind().unwrap();
+            let mut again = Vec::new();
+            while let Some(t) = iter.next().unwrap() {
+                again.push(t);
+            }
+            assert_eq!(again, expected);
+
+            iter.close().unwrap();
+            assert_eq!(temp_files(&temp_dir), 0);
+            assert_eq!(managers.temp.used(), 0);
+        }
+
+        #[test]
+        fn test_runs_removed_on_error() {
+            let managers = crate::testutil::new_test_managers();
+            let dir = tempfile::tempdir().unwrap();
+            // Room for about two runs.
+            let space = Arc::new(TempSpace::new(
+                dir.path().to_path_buf(),
+                3 * MEMORY_LIMIT as u64,
+            ));
+            let (schema, tuples) = large_input();
+            let mut iter = Sort::new(
+                managers,
+                vec![(colidx_expr(0), true, true)],
+                schema.clone(),
+                Box::new(TupleIterator::new(tuples, schema)),
+                TempReservation::new(space.clone(), TransactionId::new()),
+            )
+            .with_memory_limit(MEMORY_LIMIT);
+            iter.configure(false);
+            let err = iter.open().unwrap_err();
+            assert!(
+                err.to_string().contains("Temporary space exhausted"),
+                "{}",
+                err
+            );
+            assert_eq!(temp_files(dir.path()), 0);
+            assert_eq!(space.used(), 0);
+        }
+    }
+
     mod opiterator_test {
         use super::*;
 
@@ -25,6 +25,33 @@ fn compare_keys<I: Iterator<Item = bool>>(
     Ordering::Equal
 }
 
+/// Next tuple of a sorted child whose key holds no NULL, with its key. Tuples
+/// with a NULL key equal no key, so they are skipped.
+fn next_keyed(
+    child: &mut dyn OpIterator,
+    exprs: &[(ByteCodeExpr, bool)],
+) -> Result<Option<(Vec<Field>, Tuple)>, FairyError> {
+    'tuples: while let Some(tuple) = child.next()? {
+        let mut key = Vec::with_capacity(exprs.len());
+        for (expr, _) in exprs {
+            let value = expr.try_eval(&tuple)?;
+            if value == Field::Null {
+                continue 'tuples;
+            }
+            key.push(value);
+        }
+        return Ok(Some((key, tuple)));
+    }
+    Ok(None)
+}
+
+/// Sort-merge equi-join.
+///
+/// Both children must produce their tuples sorted on their key expressions, in
+/// the direction of each key; the planner puts a sort below a child that is not
+/// already ordered. The children are merged on their keys. The right tuples
+/// sharing a key are kept in memory while every left tuple with that key is
+/// joined with each of them.
 pub struct SortMergeJoin {
     #[allow(dead_code)]
     // Static objects (No need to reset on close)
@@ -36,23 +63,30 @@ pub struct SortMergeJoin {
     right_expr: Vec<(ByteCodeExpr, bool)>,
     left_child: Box<dyn OpIterator>,
     right_child: Box<dyn OpIterator>,
-    will_rewind: bool,
 
     // States (Reset on close)
     open: bool,
-    left_child_read: bool,
-    right_child_read: bool,
-    left_sorted_data: Vec<(Vec<Field>, Tuple)>,
-    right_sorted_data: Vec<(Vec<Field>, Tuple)>,
-    l_first: usize,
-    r_first: usize,
-    l_end: usize,
-    r_end: usize,
-    l_cursor: usize,
-    r_cursor: usize,
+    // Next left and right tuples not merged yet, with their keys
+    left_next: Option<(Vec<Field>, Tuple)>,
+    right_next: Option<(Vec<Field>, Tuple)>,
+    // Key of the current group, and the right tuples that have it
+    group_key: Vec<Field>,
+    group: Vec<Tuple>,
+    // Left tuple joined with the group, and the index of its next match
+    current_left: Option<Tuple>,
+    group_idx: usize,
 }
 
 impl SortMergeJoin {
+    /// Constructor for a sort-merge join operator.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Output schema, the left schema followed by the right one.
+    /// * `left_expr` - Key expressions over the left child, with whether it is sorted ascending on each.
+    /// * `right_expr` - Key expressions over the right child, in the same directions.
+    /// * `left_child` - Left child of join operator, sorted on its keys.
+    /// * `right_child` - Right child of join operator, sorted on its keys.
     pub fn new(
         managers: &'static Managers,
         schema: TableSchema,
@@ -69,6 +103,15 @@ impl SortMergeJoin {
         if left_expr.is_empty() {
             return Err(c_err("SMJ: Join predicate cannot be empty"));
         }
+        if left_expr
+            .iter()
+            .zip(&right_expr)
+            .any(|((_, l_asc), (_, r_asc))| l_asc != r_asc)
+        {
+            return Err(c_err(
+                "SMJ: Left and right keys must be sorted in the same direction",
+            ));
+        }
         Ok(Self {
             managers,
             schema,
@@ -76,88 +119,91 @@ impl SortMergeJoin {
             right_expr,
             left_child,
             right_child,
-            will_rewind: true,
             open: false,
-            left_sorted_data: vec![],
-            right_sorted_data: vec![],
-            l_first: 0,
-            l_end: 0,
-            l_cursor: 0,
-            r_first: 0,
-            r_end: 0,
-            r_cursor: 0,
-            left_child_read: false,
-            right_child_read: false,
+            left_next: None,
+            right_next: None,
+            group_key: Vec::new(),
+            group: Vec::new(),
+            current_left: None,
+            group_idx: 0,
         })
     }
+
+    /// Reads the first tuple of each child and forgets the current group.
+    fn start(&mut self) -> Result<(), FairyError> {
+        self.left_next = next_keyed(&mut *self.left_child, &self.left_expr)?;
+        self.right_next = next_keyed(&mut *self.right_child, &self.right_expr)?;
+        self.group_key.clear();
+        self.group.clear();
+        self.current_left = None;
+        self.group_idx = 0;
+        Ok(())
+    }
+
+    /// Makes the next left tuple the one joined with the group, if it has the
+    /// key of the group. Returns false once the left tuples of the group are
+    /// all joined.
+    fn next_left_of_group(&mut self) -> Result<bool, FairyError> {
+        match self.left_next.take() {
+            Some((key, tuple)) if key == self.group_key => {
+                self.current_left = Some(tuple);
+                self.group_idx = 0;
+                self.left_next = next_keyed(&mut *self.left_child, &self.left_expr)?;
+                Ok(true)
+            }
+            next => {
+                self.left_next = next;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Advances both children to the next key they share, and reads the right
+    /// tuples with that key into the group. Returns false if there is none.
+    fn next_group(&mut self) -> Result<bool, FairyError> {
+        let ascs = || self.left_expr.iter().map(|(_, asc)| *asc);
+        loop {
+            let (Some((l_key, _)), Some((r_key, _))) = (&self.left_next, &self.right_next) else {
+                return Ok(false);
+            };
+            match compare_keys(l_key, r_key, ascs()) {
+                Ordering::Less => {
+                    self.left_next = next_keyed(&mut *self.left_child, &self.left_expr)?;
+                }
+                Ordering::Greater => {
+                    self.right_next = next_keyed(&mut *self.right_child, &self.right_expr)?;
+                }
+                Ordering::Equal => break,
+            }
+        }
+        self.group.clear();
+        while let Some((key, tuple)) = self.right_next.take() {
+            if self.group.is_empty() {
+                self.group_key = key;
+            } else if key != self.group_key {
+                self.right_next = Some((key, tuple));
+                break;
+            }
+            self.group.push(tuple);
+            self.right_next = next_keyed(&mut *self.right_child, &self.right_expr)?;
+        }
+        Ok(true)
+    }
 }
 
 impl OpIterator for SortMergeJoin {
     fn configure(&mut self, will_rewind: bool) {
-        self.will_rewind = will_rewind;
-        // will_rewind is false for both children because the sort is stateful and rewinding sort operator does not rewind child
-        self.left_child.configure(false);
-        self.right_child.configure(false);
+        // Both children are read once per pass, and read again on rewind
+        self.left_child.configure(will_rewind);
+        self.right_child.configure(will_rewind);
     }
 
     fn open(&mut self) -> Result<(), FairyError> {
         if !self.open {
-            if !self.left_child_read {
-                self.left_child.open()?;
-                while let Some(left_tuple) = self.left_child.next()? {
-                    let mut sort_key = Vec::new();
-                    for (field, _) in &self.left_expr {
-                        sort_key.push(field.eval(&left_tuple));
-                    }
-                    self.left_sorted_data.push((sort_key, left_tuple));
-                }
-                self.left_child.close()?;
-                self.left_child_read = true;
-                self.left_sorted_data
-                    .sort_by(|(a_sort_key, _), (b_sort_key, _)| {
-                        for (i, (_, asc)) in self.left_expr.iter().enumerate() {
-                            let res = if *asc {
-                                a_sort_key[i].cmp(&b_sort_key[i])
-                            } else {
-                                b_sort_key[i].cmp(&a_sort_key[i])
-                            };
-                            if res != Ordering::Equal {
-                                return res;
-                            }
-                        }
-                        Ordering::Equal
-                    });
-            }
-            if !self.right_child_read {
-                self.right_child.open()?;
-                while let Some(right_tuple) = self.right_child.next()? {
-                    let mut sort_key = Vec::new();
-                    for (field, _) in &self.right_expr {
-                        sort_key.push(field.eval(&right_tuple));
-                    }
-                    self.right_sorted_data.push((sort_key, right_tuple));
-                }
-                self.right_child.close()?;
-                self.right_child_read = true;
-                self.right_sorted_data
-                    .sort_by(|(a_sort_key, _), (b_sort_key, _)| {
-                        for (i, (_, asc)) in self.right_expr.iter().enumerate() {
-                            let res = if *asc {
-                                a_sort_key[i].cmp(&b_sort_key[i])
-                            } else {
-                                b_sort_key[i].cmp(&a_sort_key[i])
-                            };
-                            if res != Ordering::Equal {
-                                return res;
-                            }
-                        }
-                        Ordering::Equal
-                    });
-            }
+            self.left_child.open()?;
+            self.right_child.open()?;
+            self.start()?;
             self.open = true;
-
-            // dbg!("original left_sorted_data.len() = {}", self.left_sorted_data.len());
-            // dbg!("original right_sorted_data.len() = {}", self.right_sorted_data.len());
         }
         Ok(())
     }
@@ -167,121 +213,36 @@ impl OpIterator for SortMergeJoin {
             panic!("Operator has not been opened")
         }
         loop {
-            // If l_cursor \in [l_first, l_end) and r_cursor \in [r_first, r_end), then we have a match
-            // dbg!("********************** next **********************");
-            // dbg!((
-            //     self.l_first,
-            //     self.l_end,
-            //     self.l_cursor,
-            //     self.r_first,
-            //     self.r_end,
-            //     self.r_cursor
-            // ));
-            if self.l_first <= self.l_cursor
-                && self.l_cursor < self.l_end
-                && self.r_first <= self.r_cursor
-                && self.r_cursor < self.r_end
-            {
-                // dbg!("returning tuple with cursor ({}/{}, {}/{})", self.l_cursor, self.left_sorted_data.len(), self.r_cursor, self.right_sorted_data.len());
-                let left_tuple = &self.left_sorted_data[self.l_cursor].1;
-                let right_tuple = &self.right_sorted_data[self.r_cursor].1;
-                let new_tuple = left_tuple.merge(right_tuple);
-                self.r_cursor += 1;
-                if self.r_cursor == self.r_end {
-                    self.r_cursor = self.r_first;
-                    self.l_cursor += 1;
+            if let Some(left) = &self.current_left {
+                if let Some(right) = self.group.get(self.group_idx) {
+                    self.group_idx += 1;
+                    return Ok(Some(left.merge(right)));
                 }
-                return Ok(Some(new_tuple));
-            }
-            // Finished reading from range [..., l_end) and [..., r_end)
-            self.l_first = self.l_end;
-            self.l_cursor = self.l_first;
-            self.r_first = self.r_end;
-            self.r_cursor = self.r_first;
-
-            if !self.will_rewind {
-                // remove the tuples we have already used for join from the sorted data
-                // this means all the data in range [..., l_end) and [..., r_end) can be deleted
-                self.left_sorted_data.drain(..self.l_end);
-                self.l_first = 0;
-                self.l_end = 0;
-                self.l_cursor = 0;
-                self.right_sorted_data.drain(..self.r_end);
-                self.r_first = 0;
-                self.r_end = 0;
-                self.r_cursor = 0;
-                // dbg!("left_sorted_data.len() = {}", self.left_sorted_data.len());
-                // dbg!("right_sorted_data.len() = {}", self.right_sorted_data.len());
+                self.current_left = None;
             }
-
-            // Generate a new range of left and right tuples that have equal sort keys
-            // dbg!("********************** generate new range **********************");
-            while self.l_first < self.left_sorted_data.len()
-                && self.r_first < self.right_sorted_data.len()
-            {
-                let l_tuple = &self.left_sorted_data[self.l_first];
-                let r_tuple = &self.right_sorted_data[self.r_first];
-                let ascs = self.left_expr.iter().map(|(_, asc)| *asc);
-                match compare_keys(&l_tuple.0, &r_tuple.0, ascs.clone()) {
-                    Ordering::Less => {
-                        self.l_first += 1;
-                    }
-                    Ordering::Greater => {
-                        self.r_first += 1;
-                    }
-                    Ordering::Equal => {
-                        // Find the range of tuples in left and right that are equal to l_tuple and r_tuple
-                        let mut temp_left = self.l_first;
-                        let mut temp_right = self.r_first;
-                        while temp_left < self.left_sorted_data.len()
-                            && compare_keys(
-                                &l_tuple.0,
-                                &self.left_sorted_data[temp_left].0,
-                                ascs.clone(),
-                            ) == Ordering::Equal
-                        {
-                            temp_left += 1;
-                        }
-                        while temp_right < self.right_sorted_data.len()
-                            && compare_keys(
-                                &r_tuple.0,
-                                &self.right_sorted_data[temp_right].0,
-                                ascs.clone(),
-                            ) == Ordering::Equal
-                        {
-                            temp_right += 1;
-                        }
-                        self.l_end = temp_left;
-                        self.r_end = temp_right;
-                        self.l_cursor = self.l_first;
-                        self.r_cursor = self.r_first;
-                        break;
-                    }
-                }
+            if self.next_left_of_group()? {
+                continue;
             }
-            // dbg!((
-            //     self.l_first,
-            //     self.l_end,
-            //     self.l_cursor,
-            //     self.r_first,
-            //     self.r_end,
-            //     self.r_cursor
-            // ));
-            if self.l_first == self.left_sorted_data.len()
-                || self.r_first == self.right_sorted_data.len()
-            {
+            // The next left tuple starts the next group
+            if !self.next_group()? {
                 return Ok(None);
             }
+            self.next_left_of_group()?;
         }
     }
 
     fn close(&mut self) -> Result<(), FairyError> {
-        // Children operators are closed in open()
-        self.r_first = 0;
-        self.l_first = 0;
-        self.right_sorted_data.clear();
-        self.left_sorted_data.clear();
-        self.open = false;
+        if self.open {
+            self.left_child.close()?;
+            self.right_child.close()?;
+            self.left_next = None;
+            self.right_next = None;
+            self.group_key.clear();
+            self.group.clear();
+            self.current_left = None;
+            self.group_idx = 0;
+            self.open = false;
+        }
         Ok(())
     }
 
@@ -289,13 +250,9 @@ impl OpIterator for SortMergeJoin {
         if !self.open {
             panic!("Operator has not been opened")
         }
-        self.l_first = 0;
-        self.l_end = 0;
-        self.l_cursor = 0;
-        self.r_first = 0;
-        self.r_end = 0;
-        self.r_cursor = 0;
-        Ok(())
+        self.left_child.rewind()?;
+        self.right_child.rewind()?;
+        self.start()
     }
 
     fn get_schema(&self) -> &TableSchema {
@@ -305,13 +262,16 @@ impl OpIterator for SortMergeJoin {
 
 #[cfg(test)]
 mod test {
-    use super::super::TupleIterator;
+    use super::super::{Sort, TupleIterator};
     use super::*;
+    use crate::temp_space::TempReservation;
     use crate::testutil::execute_iter;
     use crate::testutil::new_test_managers;
     use crate::testutil::TestTuples;
+    use common::ids::TransactionId;
     use common::query::bytecode_expr::{ByteCodeExpr, ByteCodes};
-    use common::Field;
+    use common::testutil::{ColumnDist, TableGen};
+    use common::{DataType, Field};
 
     #[allow(clippy::type_complexity)]
     fn get_join_predicate() -> (Vec<(ByteCodeExpr, bool)>, Vec<(ByteCodeExpr, bool)>) {
@@ -340,26 +300,47 @@ mod test {
         (left_expr, right_expr)
     }
 
+    /// `tuples` sorted on `keys`, as the planner sorts the children of a
+    /// sort-merge join. Inputs of more than a few hundred tuples are sorted in
+    /// runs.
+    fn sorted_input(
+        managers: &'static Managers,
+        keys: &[(ByteCodeExpr, bool)],
+        schema: &TableSchema,
+        tuples: Vec<Tuple>,
+    ) -> Box<dyn OpIterator> {
+        let fields = keys
+            .iter()
+            .map(|(expr, asc)| (expr.clone(), *asc, false))
+            .collect();
+        Box::new(
+            Sort::new(
+                managers,
+                fields,
+                schema.clone(),
+                Box::new(TupleIterator::new(tuples, schema.clone())),
+                TempReservation::new(managers.temp.clone(), TransactionId::new()),
+            )
+            .with_memory_limit(16 * 1024),
+        )
+    }
+
     fn get_iter(
         left_expr: Vec<(ByteCodeExpr, bool)>,
         right_expr: Vec<(ByteCodeExpr, bool)>,
     ) -> Box<dyn OpIterator> {
         let setup = TestTuples::new("");
         let managers = new_test_managers();
+        let left = sorted_input(managers, &left_expr, &setup.schema, setup.tuples.clone());
+        let right = sorted_input(managers, &right_expr, &setup.schema, setup.tuples.clone());
         let mut iter = Box::new(
             SortMergeJoin::new(
                 managers,
                 setup.schema.clone(),
                 left_expr,
                 right_expr,
-                Box::new(TupleIterator::new(
-                    setup.tuples.clone(),
-                    setup.schema.clone(),
-                )),
-                Box::new(TupleIterator::new(
-                    setup.tuples.clone(),
-                    setup.schema.clone(),
-                )),
+                left,
+                right,
             )
             .unwrap(),
         );
@@ -453,6 +434,100 @@ mod test {
         }
     }
 
+    mod merge_test {
+        use super::*;
+
+        fn key(i: usize) -> Vec<(ByteCodeExpr, bool)> {
+            let mut expr = ByteCodeExpr::new();
+            expr.add_code(ByteCodes::PushField as usize);
+            expr.add_code(i);
+            vec![(expr, true)]
+        }
+
+        /// Joins `left` and `right`, tuples (id, key), on their keys.
+        fn merge_join(left: Vec<Tuple>, right: Vec<Tuple>) -> Vec<Tuple> {
+            let schema = TableSchema::from_vecs(vec!["id", "key"], vec![DataType::BigInt; 2]);
+            let managers = new_test_managers();
+            let mut iter = SortMergeJoin::new(
+                managers,
+                schema.merge(&schema),
+                key(1),
+                key(1),
+                sorted_input(managers, &key(1), &schema, left),
+                sorted_input(managers, &key(1), &schema, right),
+            )
+            .unwrap();
+            iter.configure(false);
+            execute_iter(&mut iter, true).unwrap()
+        }
+
+        /// The join computed by comparing every pair of tuples.
+        fn pairwise_join(left: &[Tuple], right: &[Tuple]) -> Vec<Tuple> {
+            let mut joined: Vec<Tuple> = left
+                .iter()
+                .flat_map(|l| right.iter().map(move |r| (l, r)))
+                .filter(|(l, r)| {
+                    l.field_vals[1] != Field::Null && l.field_vals[1] == r.field_vals[1]
+                })
+                .map(|(l, r)| l.merge(r))
+                .collect();
+            joined.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            joined
+        }
+
+        fn input(seed: u64, n: usize, dist: ColumnDist) -> Vec<Tuple> {
+            let schema = TableSchema::from_vecs(vec!["id", "key"], vec![DataType::BigInt; 2]);
+            TableGen::new(schema, seed)
+                .column("id", ColumnDist::Sequential { start: 0 })
+                .column("key", dist)
+                .generate(n)
+        }
+
+        #[test]
+        fn test_skewed_duplicates() {
+            // Most tuples of both sides share a handful of keys, one of them
+            // repeated thousands of times.
+            let skewed = ColumnDist::Zipf {
+                min: 0,
+                cardinality: 20,
+                skew: 2.0,
+            };
+            let mut left = input(1, 3000, skewed.clone());
+            let right = input(2, 300, skewed);
+            let top = left
+                .iter()
+                .filter(|t| t.field_vals[1] == Field::BigInt(0))
+                .count();
+            assert!(top > 1000, "{}", top);
+            // NULL keys join with nothing, not even other NULL keys.
+            for tuple in left.iter_mut().step_by(50) {
+                tuple.field_vals[1] = Field::Null;
+            }
+            let mut right_nulls = right.clone();
+            right_nulls[0].field_vals[1] = Field::Null;
+
+            let expected = pairwise_join(&left, &right_nulls);
+            assert!(expected.len() > left.len() * 10);
+            assert_eq!(merge_join(left, right_nulls), expected);
+        }
+
+        #[test]
+        fn test_empty_inputs() {
+            let tuples = input(3, 50, ColumnDist::Uniform { min: 0, max: 5 });
+            assert!(merge_join(Vec::new(), tuples.clone()).is_empty());
+            assert!(merge_join(tuples, Vec::new()).is_empty());
+            assert!(merge_join(Vec::new(), Vec::new()).is_empty());
+        }
+
+        #[test]
+        fn test_disjoint_keys() {
+            let low = input(4, 50, ColumnDist::Uniform { min: 0, max: 9 });
+            let high = input(5, 50, ColumnDist::Uniform { min: 10, max: 19 });
+            assert!(merge_join(low.clone(), high.clone()).is_empty());
+            assert!(merge_join(high, low).is_empty());
+        }
+    }
+
     mod opiterator_test {
         use super::*;
 
@@ -0,0 +1,268 @@
+use super::OpIterator;
+use crate::temp_space::TempReservation;
+use common::error::c_err;
+use common::{FairyError, TableSchema, Tuple};
+use std::io::{BufWriter, Write};
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Mutex};
+use tempfile::NamedTempFile;
+
+/// Materialized output of a subplan that is shared by several consumers.
+///
+/// The child is run to completion the first time any consumer opens the spool.
+/// The first `memory_limit` tuples are kept in memory and the rest are written
+/// to a file of the temporary space, which is removed and whose space is
+/// released when the buffer is dropped.
+pub struct SpoolBuffer {
+    child: Box<dyn OpIterator>,
+    memory_limit: usize,
+    materialized: bool,
+    tuples: Vec<Tuple>,
+    temp: TempReservation,
+    spill: Option<NamedTempFile>,
+    spill_len: u64,
+}
+
+/// Handle to a spool buffer shared between the consumers of a single plan.
+pub type SharedSpool = Arc<Mutex<SpoolBuffer>>;
+
+impl SpoolBuffer {
+    /// Creates a shared spool over `child`.
+    ///
+    /// # Arguments
+    ///
+    /// * `child` - Subplan whose output is spooled.
+    /// * `memory_limit` - Number of tuples kept in memory before spilling.
+    /// * `temp` - Temporary space the spilled tuples are written to.
+    pub fn new_shared(
+        child: Box<dyn OpIterator>,
+        memory_limit: usize,
+        temp: TempReservation,
+    ) -> SharedSpool {
+        Arc::new(Mutex::new(Self {
+            child,
+            memory_limit,
+            materialized: false,
+            tuples: Vec::new(),
+            temp,
+            spill: None,
+            spill_len: 0,
+        }))
+    }
+
+    /// Runs the child to completion unless that already happened.
+    fn materialize(&mut self) -> Result<(), FairyError> {
+        if self.materialized {
+            return Ok(());
+        }
+        self.child.configure(false);
+        self.child.open()?;
+        let mut writer = None;
+        while let Some(t) = self.child.next()? {
+            if self.tuples.len() < self.memory_limit {
+                self.tuples.push(t);
+                continue;
+            }
+            let bytes = t.to_bytes();
+            self.temp.grow(4 + bytes.len() as u64)?;
+            if writer.is_none() {
+                writer = Some(BufWriter::new(self.temp.create_file()?));
+            }
+            let w = writer.as_mut().unwrap();
+            w.write_all(&(bytes.len() as u32).to_le_bytes())
+                .map_err(io_err)?;
+            w.write_all(&bytes).map_err(io_err)?;
+            self.spill_len += 4 + bytes.len() as u64;
+        }
+        self.child.close()?;
+        if let Some(w) = writer {
+            self.spill = Some(w.into_inner().map_err(|e| io_err(e.into_error()))?);
+        }
+        self.materialized = true;
+        Ok(())
+    }
+
+    /// Returns the tuple at `index` if it is kept in memory, or reads the spilled
+    /// tuple at byte `offset` and advances `offset` past it.
+    fn get(&self, index: usize, offset: &mut u64) -> Result<Option<Tuple>, FairyError> {
+        if let Some(t) = self.tuples.get(index) {
+            return Ok(Some(t.clone()));
+        }
+        let file = match &self.spill {
+            Some(file) if *offset < self.spill_len => file.as_file(),
+            _ => return Ok(None),
+        };
+        let mut len = [0u8; 4];
+        file.read_exact_at(&mut len, *offset).map_err(io_err)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        file.read_exact_at(&mut bytes, *offset + 4)
+            .map_err(io_err)?;
+        *offset += 4 + bytes.len() as u64;
+        Ok(Some(Tuple::from_bytes(&bytes)))
+    }
+
+    /// Returns true if part of the output was written to disk.
+    pub fn has_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+}
+
+fn io_err(e: std::io::Error) -> FairyError {
+    c_err(&format!("Spool I/O error: {}", e))
+}
+
+/// Reads the output of a shared spool. Every consumer keeps its own position,
+/// so the spooled subplan is executed only once however many consumers read it.
+pub struct Spool {
+    // Parameters (No need to reset on close)
+    schema: TableSchema,
+    buffer: SharedSpool,
+
+    // States (Need to reset on close)
+    open: bool,
+    index: usize,
+    spill_offset: u64,
+}
+
+impl Spool {
+    /// Spool consumer constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the spooled subplan.
+    /// * `buffer` - Shared spool buffer to read from.
+    pub fn new(schema: TableSchema, buffer: SharedSpool) -> Self {
+        Self {
+            schema,
+            buffer,
+            open: false,
+            index: 0,
+            spill_offset: 0,
+        }
+    }
+}
+
+impl OpIterator for Spool {
+    fn configure(&mut self, _will_rewind: bool) {
+        // The spool is materialized, so it can always be rewound.
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.buffer.lock().unwrap().materialize()?;
+            self.index = 0;
+            self.spill_offset = 0;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let t = self
+            .buffer
+            .lock()
+            .unwrap()
+            .get(self.index, &mut self.spill_offset)?;
+        if t.is_some() {
+            self.index += 1;
+        }
+        Ok(t)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.index = 0;
+        self.spill_offset = 0;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.index = 0;
+        self.spill_offset = 0;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use crate::temp_space::TempSpace;
+    use crate::testutil::{execute_iter, TestTuples};
+    use common::ids::TransactionId;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn shared_spool(memory_limit: usize, budget: u64) -> (TestTuples, SharedSpool, TempDir) {
+        let tuples = TestTuples::new("");
+        let child = Box::new(TupleIterator::new(
+            tuples.tuples.clone(),
+            tuples.schema.clone(),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let space = Arc::new(TempSpace::new(dir.path().join("tmp"), budget));
+        let temp = TempReservation::new(space, TransactionId::new());
+        let buffer = SpoolBuffer::new_shared(child, memory_limit, temp);
+        (tuples, buffer, dir)
+    }
+
+    fn files_in(dir: &Path) -> usize {
+        std::fs::read_dir(dir).map_or(0, |files| files.count())
+    }
+
+    #[test]
+    fn test_consumers_read_same_output() {
+        let (expected, buffer, _dir) = shared_spool(usize::MAX, 0);
+        let mut first = Spool::new(expected.schema.clone(), buffer.clone());
+        let mut second = Spool::new(expected.schema.clone(), buffer.clone());
+        assert_eq!(execute_iter(&mut first, false).unwrap(), expected.tuples);
+        assert_eq!(execute_iter(&mut second, false).unwrap(), expected.tuples);
+        assert!(!buffer.lock().unwrap().has_spilled());
+    }
+
+    #[test]
+    fn test_spill_and_rewind() {
+        let (expected, buffer, dir) = shared_spool(2, 0);
+        let mut first = Spool::new(expected.schema.clone(), buffer.clone());
+        let mut second = Spool::new(expected.schema.clone(), buffer.clone());
+        assert_eq!(execute_iter(&mut first, false).unwrap(), expected.tuples);
+        assert!(buffer.lock().unwrap().has_spilled());
+        assert_eq!(files_in(&dir.path().join("tmp")), 1);
+
+        // Interleaved reads keep independent positions.
+        second.open().unwrap();
+        first.rewind().unwrap();
+        for t in &expected.tuples {
+            assert_eq!(second.next().unwrap().as_ref(), Some(t));
+            assert_eq!(first.next().unwrap().as_ref(), Some(t));
+        }
+        assert_eq!(first.next().unwrap(), None);
+        assert_eq!(second.next().unwrap(), None);
+
+        drop((first, second, buffer));
+        assert_eq!(files_in(&dir.path().join("tmp")), 0);
+    }
+
+    #[test]
+    fn test_spill_over_budget_fails() {
+        let (expected, buffer, dir) = shared_spool(2, 8);
+        let mut spool = Spool::new(expected.schema.clone(), buffer.clone());
+        let err = spool.open().unwrap_err();
+        assert!(
+            err.to_string().contains("Temporary space exhausted"),
+            "{}",
+            err
+        );
+        assert_eq!(files_in(&dir.path().join("tmp")), 0);
+    }
+}
@@ -0,0 +1,340 @@
+use super::aggregate::{empty_input_row, group_row, init_group, merge_into_group, AggState};
+use super::OpIterator;
+use common::query::bytecode_expr::ByteCodeExpr;
+use common::{AggOp, FairyError, Field, TableSchema, Tuple};
+
+/// Group by values and aggregated values of a group.
+type Group = (Vec<Field>, Vec<AggState>);
+
+/// Aggregate operator for input that arrives grouped: the tuples of a group
+/// are adjacent, e.g. because the input is sorted on the group by fields. A
+/// group is emitted as soon as the next group starts, so only one group is
+/// held at a time and the output keeps the order of the input.
+pub struct StreamAggregate {
+    // Parameters (No need to reset on close)
+    /// Output schema of the form [groupby_field attributes ..., agg_field attributes ...]).
+    schema: TableSchema,
+    /// Group by fields
+    groupby_expr: Vec<ByteCodeExpr>,
+    /// Aggregated fields.
+    agg_expr: Vec<ByteCodeExpr>,
+    /// Aggregation operations.
+    ops: Vec<AggOp>,
+    /// Child operator to get the data from.
+    child: Box<dyn OpIterator>,
+    /// If true, then the operator will be rewinded in the future.
+    will_rewind: bool,
+
+    // States (Need to reset on close)
+    /// Boolean if the iterator is open.
+    open: bool,
+    /// The group being read.
+    group: Option<Group>,
+    /// Whether a row was returned since the operator was opened or rewound.
+    returned_row: bool,
+    /// Most groups held at once.
+    peak_groups: usize,
+}
+
+impl StreamAggregate {
+    /// Streaming aggregator constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `groupby_expr` - List of `ByteCodeExpr`s to groupby over. The child must return the tuples of a group next to each other.
+    /// * `agg_expr` - List of `ByteCodeExpr`s to aggregate over.
+    /// * `ops` - List of `AggOp`s to apply to `agg_expr`.
+    /// * `schema` - TableSchema of the form [groupby_field attributes ..., agg_field attributes ...]).
+    /// * `child` - Child operator to get the data from.
+    pub fn new(
+        groupby_expr: Vec<ByteCodeExpr>,
+        agg_expr: Vec<ByteCodeExpr>,
+        ops: Vec<AggOp>,
+        schema: TableSchema,
+        child: Box<dyn OpIterator>,
+    ) -> Self {
+        assert!(ops.len() == agg_expr.len());
+
+        Self {
+            schema,
+            groupby_expr,
+            agg_expr,
+            ops,
+            child,
+            will_rewind: true,
+            open: false,
+            group: None,
+            returned_row: false,
+            peak_groups: 0,
+        }
+    }
+
+    /// Most groups the operator held at once.
+    pub fn peak_groups(&self) -> usize {
+        self.peak_groups
+    }
+
+    /// Output row of the group being read, if any, leaving no group.
+    fn finish_group(&mut self) -> Option<Tuple> {
+        let row = self
+            .group
+            .take()
+            .map(|(key, agg)| group_row(&self.ops, key, &agg));
+        self.returned_row |= row.is_some();
+        row
+    }
+}
+
+impl OpIterator for StreamAggregate {
+    fn configure(&mut self, will_rewind: bool) {
+        self.will_rewind = will_rewind;
+        // The input is not buffered, so rewinding rewinds the child.
+        self.child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.child.open()?;
+            self.group = None;
+            self.returned_row = false;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        while let Some(tuple) = self.child.next()? {
+            let key = self
+                .groupby_expr
+                .iter()
+                .map(|expr| expr.eval(&tuple))
+                .collect::<Vec<Field>>();
+            if let Some((group_key, group)) = self.group.as_mut() {
+                if *group_key == key {
+                    merge_into_group(&self.ops, &self.agg_expr, &tuple, group);
+                    continue;
+                }
+            }
+            // The tuple starts a new group, so the previous one is complete.
+            let done = self.finish_group();
+            let mut group = init_group(&self.ops);
+            merge_into_group(&self.ops, &self.agg_expr, &tuple, &mut group);
+            self.group = Some((key, group));
+            self.peak_groups = self.peak_groups.max(1);
+            if done.is_some() {
+                return Ok(done);
+            }
+        }
+        // The last group ends with the input.
+        if let Some(row) = self.finish_group() {
+            return Ok(Some(row));
+        }
+        // Without group by, the aggregates have a row even for no input.
+        if self.groupby_expr.is_empty() && !self.returned_row {
+            self.returned_row = true;
+            return Ok(Some(empty_input_row(&self.ops)));
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        if self.open {
+            self.child.close()?;
+            self.group = None;
+            self.open = false;
+        }
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if !self.will_rewind {
+            panic!("Cannot rewind a StreamAggregate with will_rewind set to false")
+        }
+        self.child.rewind()?;
+        self.group = None;
+        self.returned_row = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Aggregate, TupleIterator};
+    use super::*;
+    use crate::testutil::{execute_iter, new_test_managers};
+    use common::{
+        datatypes::f_int,
+        query::bytecode_expr::colidx_expr,
+        testutil::{ColumnDist, TableGen},
+        DataType, Field,
+    };
+
+    /// `groups` groups of `per_group` tuples (group, value), sorted on group.
+    fn sorted_input(groups: i64, per_group: i64) -> TupleIterator {
+        let tuples = (0..groups)
+            .flat_map(|g| (0..per_group).map(move |v| Tuple::new(vec![f_int(g), f_int(v)])))
+            .collect();
+        let schema = TableSchema::new(vec![]);
+        TupleIterator::new(tuples, schema)
+    }
+
+    /// 5000 tuples (group, value) with Zipf distributed groups and a tenth of
+    /// the values NULL, sorted on group.
+    fn generated_input() -> Vec<Tuple> {
+        let schema = TableSchema::from_vecs(vec!["g", "v"], vec![DataType::BigInt; 2]);
+        let mut tuples = TableGen::new(schema, 11)
+            .column(
+                "g",
+                ColumnDist::Zipf {
+                    min: 0,
+                    cardinality: 1000,
+                    skew: 0.8,
+                },
+            )
+            .column("v", ColumnDist::Uniform { min: -50, max: 50 })
+            .nulls("v", 0.1)
+            .generate(5000);
+        tuples.sort_by(|a, b| a.field_vals[0].cmp(&b.field_vals[0]));
+        tuples
+    }
+
+    fn stream_iter(child: TupleIterator, ops: Vec<AggOp>) -> StreamAggregate {
+        let agg_expr = ops.iter().map(|_| colidx_expr(1)).collect();
+        let mut iter = StreamAggregate::new(
+            vec![colidx_expr(0)],
+            agg_expr,
+            ops,
+            TableSchema::new(vec![]),
+            Box::new(child),
+        );
+        iter.configure(false);
+        iter
+    }
+
+    #[test]
+    fn test_matches_hash_aggregate() {
+        let ops = vec![AggOp::Count, AggOp::Sum, AggOp::Min, AggOp::Max, AggOp::Avg];
+        let input = generated_input();
+        let child = || TupleIterator::new(input.clone(), TableSchema::new(vec![]));
+        let mut stream = stream_iter(child(), ops.clone());
+        let streamed = execute_iter(&mut stream, false).unwrap();
+
+        let mut hash = Aggregate::new(
+            new_test_managers(),
+            vec![colidx_expr(0)],
+            ops.iter().map(|_| colidx_expr(1)).collect(),
+            ops,
+            TableSchema::new(vec![]),
+            Box::new(child()),
+        );
+        hash.configure(false);
+        let hashed = execute_iter(&mut hash, true).unwrap();
+
+        // The groups come out in the order of the input.
+        assert_eq!(streamed, hashed);
+        let mut groups: Vec<&Field> = input.iter().map(|t| &t.field_vals[0]).collect();
+        groups.dedup();
+        assert_eq!(streamed.len(), groups.len());
+        // The first groups are the most frequent ones.
+        assert!(streamed[0].field_vals[1] > streamed[groups.len() - 1].field_vals[1]);
+        let counted: i64 = streamed
+            .iter()
+            .map(|t| match t.field_vals[1] {
+                Field::BigInt(count) => count,
+                _ => panic!("Expected a count"),
+            })
+            .sum();
+        // COUNT skips the NULL values.
+        let values = input.iter().filter(|t| t.field_vals[1] != Field::Null);
+        assert_eq!(counted, values.count() as i64);
+        assert_eq!(hash.peak_groups(), groups.len());
+        assert_eq!(stream.peak_groups(), 1);
+    }
+
+    #[test]
+    fn test_peak_is_constant() {
+        for groups in [10, 10000] {
+            let mut iter = stream_iter(sorted_input(groups, 3), vec![AggOp::Count]);
+            let t = execute_iter(&mut iter, true).unwrap();
+            assert_eq!(t.len(), groups as usize);
+            assert_eq!(iter.peak_groups(), 1);
+        }
+    }
+
+    #[test]
+    fn test_single_group() {
+        let mut iter = stream_iter(sorted_input(1, 5), vec![AggOp::Sum]);
+        let t = execute_iter(&mut iter, true).unwrap();
+        assert_eq!(t, vec![Tuple::new(vec![f_int(0), f_int(10)])]);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let mut iter = stream_iter(sorted_input(0, 5), vec![AggOp::Count]);
+        let t = execute_iter(&mut iter, true).unwrap();
+        assert!(t.is_empty());
+        assert_eq!(iter.peak_groups(), 0);
+    }
+
+    /// Without input there are no groups, but one row without group by, the
+    /// same as the hash aggregate returns, also after a rewind.
+    #[test]
+    fn test_empty_input_matches_hash_aggregate() {
+        let ops = vec![AggOp::Count, AggOp::Sum, AggOp::Min, AggOp::Max, AggOp::Avg];
+        let agg_expr = || ops.iter().map(|_| colidx_expr(1)).collect::<Vec<_>>();
+        let run = |iter: &mut dyn OpIterator| {
+            iter.configure(true);
+            let t = execute_iter(iter, false).unwrap();
+            iter.rewind().unwrap();
+            assert_eq!(execute_iter(iter, false).unwrap(), t);
+            t
+        };
+        for groupby_expr in [vec![colidx_expr(0)], vec![]] {
+            let mut stream = StreamAggregate::new(
+                groupby_expr.clone(),
+                agg_expr(),
+                ops.clone(),
+                TableSchema::new(vec![]),
+                Box::new(sorted_input(0, 5)),
+            );
+            let mut hash = Aggregate::new(
+                new_test_managers(),
+                groupby_expr.clone(),
+                agg_expr(),
+                ops.clone(),
+                TableSchema::new(vec![]),
+                Box::new(sorted_input(0, 5)),
+            );
+            let streamed = run(&mut stream);
+            assert_eq!(streamed, run(&mut hash));
+            let expected = if groupby_expr.is_empty() {
+                let nulls = vec![Field::Null; 4];
+                vec![Tuple::new([vec![f_int(0)], nulls].concat())]
+            } else {
+                vec![]
+            };
+            assert_eq!(streamed, expected);
+        }
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut iter = stream_iter(sorted_input(4, 2), vec![AggOp::Max]);
+        iter.configure(true);
+        let t_before = execute_iter(&mut iter, true).unwrap();
+        iter.rewind().unwrap();
+        let t_after = execute_iter(&mut iter, true).unwrap();
+        assert_eq!(t_before.len(), 4);
+        assert_eq!(t_before, t_after);
+    }
+}
@@ -0,0 +1,277 @@
+use super::sort::{compare_rows, SortFields};
+use super::OpIterator;
+use common::{FairyError, Field, TableSchema, Tuple};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+/// Returns the first `limit` tuples of the child in the order of the sort
+/// keys, like a sort followed by a limit. The child is read once, and only the
+/// `limit` first tuples so far are kept, in a heap whose top is the last of
+/// them. Tuples with equal keys are returned in the order they were read.
+pub struct TopN {
+    // Parameters (No need to reset on close)
+    schema: TableSchema,
+    fields: Arc<SortFields>,
+    child: Box<dyn OpIterator>,
+    limit: usize,
+
+    // States (Need to reset on close)
+    open: bool,
+    /// First tuples of the input, in the order they are returned.
+    sorted: Vec<Tuple>,
+    index: usize,
+    /// Most tuples held in the heap at once.
+    peak_rows: usize,
+}
+
+/// Tuple kept by the operator, `seq` being its position in the input.
+struct HeapEntry {
+    key: Vec<Field>,
+    seq: usize,
+    tuple: Tuple,
+    fields: Arc<SortFields>,
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // The tuple returned last is the greatest, so it is on top of the heap.
+        compare_rows(&self.fields, &self.key, &other.key).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl TopN {
+    /// TopN operator constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - Sort keys, with their direction and whether NULLs come first.
+    /// * `limit` - Number of tuples to return.
+    /// * `schema` - Schema of the child.
+    /// * `child` - Child operator whose first tuples are returned.
+    pub fn new(
+        fields: SortFields,
+        limit: usize,
+        schema: TableSchema,
+        child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self {
+            schema,
+            fields: Arc::new(fields),
+            child,
+            limit,
+            open: false,
+            sorted: Vec::new(),
+            index: 0,
+            peak_rows: 0,
+        }
+    }
+
+    /// Most tuples the operator held at once.
+    pub fn peak_rows(&self) -> usize {
+        self.peak_rows
+    }
+
+    /// Reads the child, keeping its first `limit` tuples.
+    fn read_input(&mut self) -> Result<(), FairyError> {
+        self.child.open()?;
+        let mut heap = BinaryHeap::with_capacity(self.limit);
+        let mut seq = 0;
+        while self.limit > 0 {
+            let Some(tuple) = self.child.next()? else {
+                break;
+            };
+            let entry = HeapEntry {
+                key: self.fields.iter().map(|(f, _, _)| f.eval(&tuple)).collect(),
+                seq,
+                tuple,
+                fields: self.fields.clone(),
+            };
+            seq += 1;
+            if heap.len() < self.limit {
+                heap.push(entry);
+                self.peak_rows = self.peak_rows.max(heap.len());
+            } else if heap.peek().is_some_and(|last| entry < *last) {
+                heap.pop();
+                heap.push(entry);
+            }
+        }
+        self.child.close()?;
+        self.sorted = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|entry| entry.tuple)
+            .collect();
+        self.index = 0;
+        Ok(())
+    }
+}
+
+impl OpIterator for TopN {
+    fn configure(&mut self, _will_rewind: bool) {
+        // The kept tuples are returned again on a rewind, so the child is
+        // read once.
+        self.child.configure(false);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.read_input()?;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        let t = self.sorted.get(self.index).cloned();
+        if t.is_some() {
+            self.index += 1;
+        }
+        Ok(t)
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.sorted.clear();
+        self.index = 0;
+        self.open = false;
+        self.child.close()
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.index = 0;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Sort, TupleIterator};
+    use super::*;
+    use crate::temp_space::TempReservation;
+    use crate::testutil::{execute_iter, new_test_managers};
+    use common::ids::TransactionId;
+    use common::query::bytecode_expr::colidx_expr;
+    use common::testutil::{ColumnDist, TableGen};
+    use common::DataType;
+
+    /// 10000 tuples (id, a, b) with few distinct values of a and b, and a
+    /// tenth of the values of a NULL.
+    fn generated_input() -> Vec<Tuple> {
+        let schema = TableSchema::from_vecs(vec!["id", "a", "b"], vec![DataType::BigInt; 3]);
+        TableGen::new(schema, 7)
+            .column("id", ColumnDist::Sequential { start: 0 })
+            .column("a", ColumnDist::Uniform { min: 0, max: 20 })
+            .column("b", ColumnDist::Uniform { min: 0, max: 5 })
+            .nulls("a", 0.1)
+            .generate(10000)
+    }
+
+    fn fields(asc: bool, nulls_first: bool) -> SortFields {
+        vec![
+            (colidx_expr(1), asc, nulls_first),
+            (colidx_expr(2), !asc, !nulls_first),
+        ]
+    }
+
+    fn top_n(input: &[Tuple], fields: SortFields, limit: usize) -> TopN {
+        let schema = TableSchema::new(vec![]);
+        let child = TupleIterator::new(input.to_vec(), schema.clone());
+        let mut iter = TopN::new(fields, limit, schema, Box::new(child));
+        iter.configure(true);
+        iter
+    }
+
+    /// First `limit` tuples of `input` sorted by a full sort.
+    fn sorted(input: &[Tuple], fields: SortFields, limit: usize) -> Vec<Tuple> {
+        let managers = new_test_managers();
+        let schema = TableSchema::new(vec![]);
+        let mut sort = Sort::new(
+            managers,
+            fields,
+            schema.clone(),
+            Box::new(TupleIterator::new(input.to_vec(), schema)),
+            TempReservation::new(managers.temp.clone(), TransactionId::new()),
+        );
+        sort.configure(false);
+        let mut t = execute_iter(&mut sort, false).unwrap();
+        t.truncate(limit);
+        t
+    }
+
+    /// Keys of the sort columns of `tuples`.
+    fn keys(tuples: &[Tuple]) -> Vec<Vec<Field>> {
+        tuples.iter().map(|t| t.field_vals[1..].to_vec()).collect()
+    }
+
+    #[test]
+    fn test_matches_sort() {
+        let input = generated_input();
+        for (asc, nulls_first) in [(true, false), (true, true), (false, true), (false, false)] {
+            for limit in [1, 10, 1000] {
+                let mut iter = top_n(&input, fields(asc, nulls_first), limit);
+                let t = execute_iter(&mut iter, false).unwrap();
+                assert_eq!(
+                    keys(&t),
+                    keys(&sorted(&input, fields(asc, nulls_first), limit))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ties_keep_input_order() {
+        let input = generated_input();
+        let mut iter = top_n(&input, fields(true, false), 100);
+        let t = execute_iter(&mut iter, false).unwrap();
+        for pair in t.windows(2) {
+            if pair[0].field_vals[1..] == pair[1].field_vals[1..] {
+                assert!(pair[0].field_vals[0] < pair[1].field_vals[0]);
+            }
+        }
+    }
+
+    /// The input is read once and only `limit` tuples are held: the child is
+    /// not rewound, and a rewind returns the same tuples again.
+    #[test]
+    fn test_single_pass_and_bounded_memory() {
+        let input = generated_input();
+        let mut iter = top_n(&input, fields(false, true), 10);
+        let t = execute_iter(&mut iter, false).unwrap();
+        assert_eq!(t.len(), 10);
+        assert_eq!(iter.peak_rows(), 10);
+        iter.rewind().unwrap();
+        assert_eq!(execute_iter(&mut iter, false).unwrap(), t);
+
+        let mut iter = top_n(&input, fields(true, false), 0);
+        assert!(execute_iter(&mut iter, false).unwrap().is_empty());
+        assert_eq!(iter.peak_rows(), 0);
+
+        let mut iter = top_n(&input[..5], fields(true, false), 10);
+        assert_eq!(execute_iter(&mut iter, false).unwrap().len(), 5);
+        assert_eq!(iter.peak_rows(), 5);
+    }
+}
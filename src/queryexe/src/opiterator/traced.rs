@@ -0,0 +1,242 @@
+use super::OpIterator;
+use common::ids::ContainerId;
+use common::{FairyError, TableSchema, Tuple};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Counters of an operator that are collected while its plan runs with
+/// operator profiling on. Unlike the row count of the span, they add up over
+/// every time the operator is rewound or opened again.
+#[derive(Debug, Default)]
+pub struct OperatorStats {
+    rows: AtomicU64,
+    next_calls: AtomicU64,
+    loops: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl OperatorStats {
+    /// Tuples the operator produced.
+    pub fn rows(&self) -> u64 {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    /// Calls to `next` or `next_batch` of the operator, including the one
+    /// that found the end.
+    pub fn next_calls(&self) -> u64 {
+        self.next_calls.load(Ordering::Relaxed)
+    }
+
+    /// Passes over the input of the operator: the open and each rewind, e.g.
+    /// once per outer row for the inner side of a nested loop join.
+    pub fn loops(&self) -> u64 {
+        self.loops.load(Ordering::Relaxed)
+    }
+
+    /// Wall time spent in the operator and the operators below it.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    fn add_time(&self, start: Instant) {
+        let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+/// Records the work of its child in an `operator` tracing span.
+///
+/// The span is created when the operator is opened and is entered while the
+/// child opens, produces tuples, rewinds and closes, so the spans of the
+/// operators below it and of the pages they fault in nest inside it. It ends
+/// when the operator is closed, with the number of tuples produced.
+///
+/// With `stats`, the calls to the child and the time spent in them are also
+/// counted, so they can be reported once the plan ran.
+pub struct Traced {
+    // Parameters (No need to reset on close)
+    /// Name of the operator, e.g. `hash_join`.
+    name: &'static str,
+    /// Table read by the operator, if it is a scan.
+    table: Option<ContainerId>,
+    /// Child operator that is traced.
+    child: Box<dyn OpIterator>,
+    /// Counters of the operator if it is profiled.
+    stats: Option<Arc<OperatorStats>>,
+
+    // States (Need to reset on close)
+    /// Span of the operator while it is open.
+    span: Option<Span>,
+    /// Tuples produced since the operator was opened.
+    rows: u64,
+}
+
+impl Traced {
+    /// Traced constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the operator recorded in its span.
+    /// * `table` - Table read by the operator, if it is a scan.
+    /// * `child` - Child OpIterator that is traced.
+    /// * `stats` - Counters of the operator, if it is profiled.
+    pub fn new(
+        name: &'static str,
+        table: Option<ContainerId>,
+        child: Box<dyn OpIterator>,
+        stats: Option<Arc<OperatorStats>>,
+    ) -> Self {
+        Self {
+            name,
+            table,
+            child,
+            stats,
+            span: None,
+            rows: 0,
+        }
+    }
+
+    /// Runs `f` on the child inside the span of the operator, adding the time
+    /// it takes to the counters of the operator if it is profiled.
+    fn in_span<T>(&mut self, f: impl FnOnce(&mut Box<dyn OpIterator>) -> T) -> T {
+        let start = self.stats.is_some().then(Instant::now);
+        let res = match &self.span {
+            Some(span) => span.in_scope(|| f(&mut self.child)),
+            None => f(&mut self.child),
+        };
+        if let (Some(stats), Some(start)) = (&self.stats, start) {
+            stats.add_time(start);
+        }
+        res
+    }
+}
+
+impl OpIterator for Traced {
+    fn configure(&mut self, will_rewind: bool) {
+        self.child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if self.span.is_none() {
+            let span = tracing::info_span!(
+                "operator",
+                operator = self.name,
+                table = Empty,
+                rows = Empty
+            );
+            if let Some(table) = self.table {
+                span.record("table", table);
+            }
+            self.span = Some(span);
+            self.rows = 0;
+        }
+        if let Some(stats) = &self.stats {
+            stats.loops.fetch_add(1, Ordering::Relaxed);
+        }
+        self.in_span(|child| child.open())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        let tuple = self.in_span(|child| child.next())?;
+        if tuple.is_some() {
+            self.rows += 1;
+        }
+        if let Some(stats) = &self.stats {
+            stats.next_calls.fetch_add(1, Ordering::Relaxed);
+            if tuple.is_some() {
+                stats.rows.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(tuple)
+    }
+
+    fn next_batch(&mut self, out: &mut Vec<Tuple>, max: usize) -> Result<(), FairyError> {
+        let before = out.len();
+        self.in_span(|child| child.next_batch(out, max))?;
+        let rows = (out.len() - before) as u64;
+        self.rows += rows;
+        if let Some(stats) = &self.stats {
+            stats.next_calls.fetch_add(1, Ordering::Relaxed);
+            stats.rows.fetch_add(rows, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn batches(&self) -> bool {
+        self.child.batches()
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        let res = self.in_span(|child| child.close());
+        if let Some(span) = self.span.take() {
+            span.record("rows", self.rows);
+        }
+        res
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if let Some(stats) = &self.stats {
+            stats.loops.fetch_add(1, Ordering::Relaxed);
+        }
+        self.in_span(|child| child.rewind())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        self.child.get_schema()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use common::{attribute::Attribute, DataType, Field};
+
+    #[test]
+    fn test_passes_tuples_through_and_counts_them() {
+        let tuples = vec![
+            Tuple::new(vec![Field::BigInt(1)]),
+            Tuple::new(vec![Field::BigInt(2)]),
+        ];
+        let schema = TableSchema::new(vec![Attribute::new("a".into(), DataType::BigInt)]);
+        let child = Box::new(TupleIterator::new(tuples.clone(), schema));
+        let mut traced = Traced::new("scan", Some(1), child, None);
+        traced.open().unwrap();
+        let mut out = Vec::new();
+        while let Some(t) = traced.next().unwrap() {
+            out.push(t);
+        }
+        assert_eq!(out, tuples);
+        assert_eq!(traced.rows, 2);
+        traced.close().unwrap();
+        assert!(traced.span.is_none());
+
+        // Opening again starts a new count.
+        traced.open().unwrap();
+        assert_eq!(traced.rows, 0);
+    }
+
+    #[test]
+    fn test_stats_add_up_over_rewinds() {
+        let tuples = vec![
+            Tuple::new(vec![Field::BigInt(1)]),
+            Tuple::new(vec![Field::BigInt(2)]),
+        ];
+        let schema = TableSchema::new(vec![Attribute::new("a".into(), DataType::BigInt)]);
+        let child = Box::new(TupleIterator::new(tuples, schema));
+        let stats = Arc::new(OperatorStats::default());
+        let mut traced = Traced::new("scan", None, child, Some(stats.clone()));
+        traced.configure(true);
+        traced.open().unwrap();
+        while traced.next().unwrap().is_some() {}
+        traced.rewind().unwrap();
+        while traced.next().unwrap().is_some() {}
+        traced.close().unwrap();
+        assert_eq!(stats.rows(), 4);
+        assert_eq!(stats.next_calls(), 6);
+        assert_eq!(stats.loops(), 2);
+    }
+}
@@ -0,0 +1,175 @@
+use super::OpIterator;
+use common::{FairyError, TableSchema, Tuple};
+
+/// Concatenation operator. Returns the tuples of the left child followed by the
+/// tuples of the right child, which must have the same number of fields.
+/// Duplicates are kept, UNION without ALL puts a `Distinct` on top.
+pub struct Union {
+    // Parameters (No need to reset on close)
+    /// Schema of the output, which is the schema of the left child.
+    schema: TableSchema,
+    /// Child operator returning the first tuples.
+    left_child: Box<dyn OpIterator>,
+    /// Child operator returning the tuples after those of the left child.
+    right_child: Box<dyn OpIterator>,
+
+    // States (Need to reset on close)
+    /// Boolean determining if iterator is open.
+    open: bool,
+    /// Whether the left child has returned all its tuples.
+    left_done: bool,
+}
+
+impl Union {
+    /// Union constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - Schema of the output.
+    /// * `left_child` - Child OpIterator whose tuples come first.
+    /// * `right_child` - Child OpIterator whose tuples come last.
+    pub fn new(
+        schema: TableSchema,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+    ) -> Self {
+        Self {
+            schema,
+            left_child,
+            right_child,
+            open: false,
+            left_done: false,
+        }
+    }
+}
+
+impl OpIterator for Union {
+    fn configure(&mut self, will_rewind: bool) {
+        self.left_child.configure(will_rewind);
+        self.right_child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            self.left_child.open()?;
+            self.right_child.open()?;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        if !self.left_done {
+            match self.left_child.next()? {
+                Some(t) => return Ok(Some(t)),
+                None => self.left_done = true,
+            }
+        }
+        self.right_child.next()
+    }
+
+    fn close(&mut self) -> Result<(), FairyError> {
+        self.left_child.close()?;
+        self.right_child.close()?;
+        self.left_done = false;
+        self.open = false;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), FairyError> {
+        if !self.open {
+            panic!("Operator has not been opened")
+        }
+        self.left_child.rewind()?;
+        self.right_child.rewind()?;
+        self.left_done = false;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::datatypes::{f_int, f_str};
+    use common::{Attribute, DataType, Field};
+
+    use super::*;
+    use crate::opiterator::TupleIterator;
+    use crate::testutil::execute_iter;
+
+    fn get_iter(left: Vec<Vec<Field>>, right: Vec<Vec<Field>>) -> Box<dyn OpIterator> {
+        let schema = TableSchema::new(vec![
+            Attribute::new("a".to_string(), DataType::BigInt),
+            Attribute::new("b".to_string(), DataType::String),
+        ]);
+        let child = |tuples: Vec<Vec<Field>>| {
+            let tuples = tuples.into_iter().map(Tuple::new).collect();
+            Box::new(TupleIterator::new(tuples, schema.clone()))
+        };
+        let mut iter = Box::new(Union::new(schema.clone(), child(left), child(right)));
+        iter.configure(false);
+        iter
+    }
+
+    fn run(iter: &mut dyn OpIterator) -> Vec<Vec<Field>> {
+        execute_iter(iter, false)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.field_vals)
+            .collect()
+    }
+
+    #[test]
+    fn test_union_concatenates() {
+        let mut iter = get_iter(
+            vec![vec![f_int(2), f_str("b")], vec![f_int(1), f_str("a")]],
+            vec![vec![f_int(2), f_str("b")], vec![f_int(3), Field::Null]],
+        );
+        assert_eq!(
+            run(&mut *iter),
+            vec![
+                vec![f_int(2), f_str("b")],
+                vec![f_int(1), f_str("a")],
+                vec![f_int(2), f_str("b")],
+                vec![f_int(3), Field::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_children() {
+        let mut iter = get_iter(vec![], vec![vec![f_int(1), f_str("a")]]);
+        assert_eq!(run(&mut *iter), vec![vec![f_int(1), f_str("a")]]);
+        let mut iter = get_iter(vec![vec![f_int(1), f_str("a")]], vec![]);
+        assert_eq!(run(&mut *iter), vec![vec![f_int(1), f_str("a")]]);
+        let mut iter = get_iter(vec![], vec![]);
+        assert!(run(&mut *iter).is_empty());
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut iter = get_iter(
+            vec![vec![f_int(1), f_str("a")]],
+            vec![vec![f_int(2), f_str("b")]],
+        );
+        iter.configure(true);
+        let t_before = run(&mut *iter);
+        iter.rewind().unwrap();
+        let t_after = run(&mut *iter);
+        assert_eq!(t_before.len(), 2);
+        assert_eq!(t_before, t_after);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_next_not_open() {
+        let mut iter = get_iter(vec![], vec![]);
+        let _ = iter.next();
+    }
+}
@@ -2,6 +2,7 @@ use crate::mutator;
 use crate::opiterator::*;
 use crate::Managers;
 
+use common::physical_expr::plan_schema::PlanSchema;
 use common::prelude::*;
 use common::tuple::ConvertedResult;
 use common::util::data_reader::DataReader;
@@ -12,6 +13,8 @@ use sqlparser::ast::Values;
 pub struct Executor {
     /// Executor state
     pub plan: Option<Box<dyn OpIterator>>,
+    /// Schema derived from the physical plan the opiterator was built from, if any.
+    pub plan_schema: Option<PlanSchema>,
     pub managers: &'static Managers,
 }
 
@@ -30,18 +33,34 @@ impl Executor {
     pub fn new_ref(managers: &'static Managers) -> Self {
         Self {
             plan: None,
+            plan_schema: None,
             managers,
         }
     }
 
     pub fn configure_query(&mut self, opiterator: Box<dyn OpIterator>) {
         self.plan = Some(opiterator);
+        self.plan_schema = None;
+    }
+
+    /// Like `configure_query`, but `execute` first checks that the header of the
+    /// opiterator agrees with `plan_schema`, the schema derived for the plan's root.
+    pub fn configure_query_with_schema(
+        &mut self,
+        opiterator: Box<dyn OpIterator>,
+        plan_schema: PlanSchema,
+    ) {
+        self.plan = Some(opiterator);
+        self.plan_schema = Some(plan_schema);
     }
 
     /// Consumes the opiterator and stores the result in a QueryResult.    
     pub fn execute(&mut self) -> Result<QueryResult, FairyError> {
         let mut opiterator = self.plan.take().unwrap();
         let schema = opiterator.get_schema().clone(); // clone the schema for returning
+        if let Some(plan_schema) = self.plan_schema.take() {
+            check_schema(&plan_schema, &schema)?;
+        }
 
         let mut res = Vec::new();
         // TODO: Magic number
@@ -50,74 +69,146 @@ impl Executor {
 
         opiterator.configure(false);
         opiterator.open()?;
-        while let Some(t) = opiterator.next()? {
-            res.push(t);
+        if opiterator.batches() {
+            loop {
+                let before = res.len();
+                opiterator.next_batch(&mut res, BATCH_SIZE)?;
+                if res.len() == before {
+                    break;
+                }
+            }
+        } else {
+            while let Some(t) = opiterator.next()? {
+                res.push(t);
+            }
         }
         opiterator.close()?;
 
         Ok(QueryResult::new_select_result(&schema, res, None)) // Setting paging_info as None.
     }
 
+    /// Inserts the rows of a VALUES clause. With `col_order`, the rows hold the
+    /// values of those columns of the schema, and the others take their default.
     pub fn import_tuples(
         &self,
         values: &Values,
-        _table_name: &str,
-        table_id: &ContainerId,
-        table_schema: &TableSchema,
+        table: &TableInfo,
+        col_order: Option<Vec<usize>>,
         txn_id: TransactionId,
     ) -> Result<usize, FairyError> {
         let converted_result = mutator::convert_insert_vals(values)?; // This returns Vec<u8>
-        let validated_converted_result =
-            mutator::validate_tuples(table_id, table_schema, None, converted_result, &txn_id)?;
+        let mut validated_converted_result = mutator::validate_tuples(
+            &table.c_id,
+            &table.schema,
+            col_order,
+            converted_result,
+            &txn_id,
+        )?;
 
         if !validated_converted_result.unconverted.is_empty() {
-            return Err(FairyError::ValidationError(format!(
-                "Some records were not valid: {:?}",
-                validated_converted_result.unconverted
-            )));
+            return Err(mutator::invalid_records_error(
+                &table.schema,
+                &validated_converted_result.unconverted,
+            ));
         }
 
-        let insert_count = mutator::insert_validated_tuples(
-            *table_id,
-            &validated_converted_result.converted,
+        let inserted = mutator::insert_validated_tuples(
+            table,
+            &mut validated_converted_result.converted,
             txn_id,
             self.managers,
         )?;
 
-        Ok(insert_count)
+        Ok(inserted.len())
+    }
+
+    /// Deletes the records `scan` returns. They are all found before the first
+    /// is deleted, so the scan does not run into its own deletes.
+    pub fn delete_records(
+        &self,
+        mut scan: Box<dyn OpIterator>,
+        txn_id: TransactionId,
+    ) -> Result<usize, FairyError> {
+        let mut value_ids = Vec::new();
+        scan.configure(false);
+        scan.open()?;
+        while let Some(tuple) = scan.next()? {
+            value_ids.push(tuple.value_id.ok_or_else(|| {
+                FairyError::ExecutionError("No value id set for record. Cannot delete".to_string())
+            })?);
+        }
+        scan.close()?;
+        mutator::delete_records(&value_ids, txn_id, self.managers)
     }
 
-    /// Import database from csv file at path.
+    /// Deletes all the records of a table at once.
+    pub fn truncate_table(
+        &self,
+        table_id: &ContainerId,
+        table_schema: &TableSchema,
+        txn_id: TransactionId,
+    ) -> Result<usize, FairyError> {
+        mutator::truncate_table(*table_id, table_schema, txn_id, self.managers)
+    }
+
+    /// Import database from csv file at path. Either every record is
+    /// inserted, or, if one fails, none stay in the table.
     ///
     /// # Arguments
     ///
-    /// * `path` - Path of the csv file containing database.
-    /// * `table_name` - Destination table
+    /// * `rdr` - Reader of the records.
+    /// * `table` - Destination table
     /// * `txn_id` - Transaction Id of loading client
     pub fn import_records_from_reader(
         &self,
         rdr: &mut dyn DataReader,
-        table_id: &ContainerId,
+        table: &TableInfo,
         txn_id: TransactionId,
     ) -> Result<usize, FairyError> {
         // TODO: Magic number
         let max_records_in_mem = 100000;
-        let mut result_set = ConvertedResult::new();
-        result_set.converted.reserve(max_records_in_mem);
+        self.import_chunks(rdr, table, txn_id, max_records_in_mem)
+    }
 
-        let mut total_insert_count = 0;
+    /// Imports the records of `rdr` in chunks of `chunk_size`. Records of the
+    /// chunks before a failing one are deleted again.
+    fn import_chunks(
+        &self,
+        rdr: &mut dyn DataReader,
+        table: &TableInfo,
+        txn_id: TransactionId,
+        chunk_size: usize,
+    ) -> Result<usize, FairyError> {
+        let mut inserted = Vec::new();
+        let result = self.import_chunks_into(rdr, table, txn_id, chunk_size, &mut inserted);
+        if result.is_err() {
+            mutator::delete_records(&inserted, txn_id, self.managers)?;
+        }
+        result.map(|_| inserted.len())
+    }
+
+    fn import_chunks_into(
+        &self,
+        rdr: &mut dyn DataReader,
+        table: &TableInfo,
+        txn_id: TransactionId,
+        chunk_size: usize,
+        inserted: &mut Vec<ValueId>,
+    ) -> Result<(), FairyError> {
+        let mut result_set = ConvertedResult::new();
+        result_set.converted.reserve(chunk_size);
 
         loop {
             result_set.reset();
 
             // Read chunk of records
-            // If the number of records in memory exceeds max_records_in_mem, insert them into the table
+            // If the number of records in memory exceeds chunk_size, insert them into the table
             let mut count = 0;
             while let Some(tuple) = rdr.read_next()? {
                 result_set.converted.push(tuple);
 
                 count += 1;
-                if count >= max_records_in_mem {
+                if count >= chunk_size {
                     break;
                 }
             }
@@ -135,17 +226,16 @@ impl Executor {
                     result_set.unconverted
                 )));
             } else {
-                let insert_count = mutator::insert_validated_tuples(
-                    *table_id,
-                    &result_set.converted,
+                inserted.extend(mutator::insert_validated_tuples(
+                    table,
+                    &mut result_set.converted,
                     txn_id,
                     self.managers,
-                )?;
-                total_insert_count += insert_count;
+                )?);
             }
         }
 
-        Ok(total_insert_count)
+        Ok(())
     }
 }
 
@@ -169,10 +259,9 @@ mod test {
 
         let schema =
             TableSchema::from_vecs(vec!["a", "b"], vec![DataType::BigInt, DataType::BigInt]);
-        let c_id = catalog.get_table_id("test");
-        catalog
-            .add_table(TableInfo::new(c_id, "test".to_string(), schema.clone()))
-            .unwrap();
+        let c_id = catalog.get_table_id("test").unwrap();
+        let table = TableInfo::new(c_id, "test".to_string(), schema.clone());
+        catalog.add_table(table.clone()).unwrap();
         sm.create_container(c_id, None, StateType::BaseTable, None)
             .unwrap();
         test_setup
@@ -192,7 +281,7 @@ mod test {
         let exec = Executor::new_ref(test_setup.managers);
         exec.import_records_from_reader(
             &mut csv_reader as &mut dyn DataReader,
-            &c_id,
+            &table,
             TransactionId::new(),
         )
         .unwrap();
@@ -208,6 +297,41 @@ mod test {
         }
         assert_eq!(count, 5);
     }
+
+    #[test]
+    fn test_import_failing_chunk_inserts_nothing() {
+        let test_setup = TestSetup::new_with_content();
+        let sm = test_setup.get_storage_manager();
+        let catalog = test_setup.get_catalog();
+
+        let schema =
+            TableSchema::from_vecs(vec!["a", "b"], vec![DataType::BigInt, DataType::BigInt]);
+        let c_id = catalog.get_table_id("test").unwrap();
+        let mut table = TableInfo::new(c_id, "test".to_string(), schema.clone());
+        table.unique_keys = vec![vec![0]];
+        catalog.add_table(table.clone()).unwrap();
+        sm.create_container(c_id, None, StateType::BaseTable, None)
+            .unwrap();
+        test_setup
+            .managers
+            .stats
+            .register_table(c_id, schema.clone())
+            .unwrap();
+        // The third chunk repeats a key of the first.
+        let mut csv_reader = CsvReader::new(
+            std::io::Cursor::new("a,b\n1,2\n3,4\n5,6\n7,8\n1,10\n"),
+            &schema,
+            b',',
+            true,
+        )
+        .unwrap();
+
+        let exec = Executor::new_ref(test_setup.managers);
+        let result = exec.import_chunks(&mut csv_reader, &table, TransactionId::new(), 2);
+        assert!(matches!(result, Err(FairyError::DuplicateKey { .. })));
+        let iter = sm.get_iterator(c_id, TransactionId::new(), Permissions::ReadOnly);
+        assert_eq!(iter.count(), 0);
+    }
 }
 
 /* FIXME
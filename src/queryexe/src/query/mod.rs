@@ -1,5 +1,5 @@
 pub use executor::Executor;
-pub use translate_and_validate::get_attr;
+pub use translate_and_validate::{get_attr, get_default};
 pub use translate_and_validate::Translator;
 mod executor;
 // pub mod planner0;
@@ -1,20 +1,34 @@
 use crate::{
     opiterator::{
-        Aggregate, CrossJoin, Filter, HashEqJoin, NestedLoopJoin, OpIterator, Project, SeqScan,
+        Adaptation, AdaptivePolicy, Aggregate, Cancellation, CorruptRecordMode, CrossJoin,
+        Distinct, Filter, HashEqJoin, Limit, NestedLoopJoin, OpIterator, OperatorStats,
+        ParallelHashEqJoin, Project, RecordCheck, ScanPredicate, SchemaCheck, SeqScan, SharedSpool,
+        Sort, SortMergeJoin, Spool, SpoolBuffer, StreamAggregate, TopN, Traced, Union,
     },
+    stats::stats_view::StatsView,
+    temp_space::TempReservation,
     Managers,
 };
 use common::{
     catalog::{get_column_index_from_temp_col_id, CatalogRef},
-    error::c_err,
-    ids::{ColumnId, LogicalTimeStamp, TransactionId},
+    error::{c_err, ObjectKind},
+    ids::{ColumnId, ContainerId, LogicalTimeStamp, TransactionId},
     logical_expr::prelude::{Expression, JoinType},
-    physical_expr::physical_rel_expr::PhysicalRelExpr,
-    query::bytecode_expr::{ByteCodeExpr, ByteCodes},
+    physical::page_dictionary::PageFilter,
+    physical_expr::{
+        physical_rel_expr::{PhysicalRelExpr, PlanHashes},
+        plan_schema::{comparable, PlanSchema},
+    },
+    query::bytecode_expr::{ByteCodeExpr, ByteCodes, InList, SqlOrd},
     traits::plan::Plan,
-    BinaryOp, FairyError, TableSchema,
+    BinaryOp, DataType, FairyError, Field, TableSchema, Tuple,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+type ColIdToIdx = HashMap<ColumnId, ColumnId>;
 
 /// Convert a physical expression to a bytecode expression.
 /// This function may take in `col_id_to_idx` (mapping from the unique column ID to the
@@ -36,6 +50,16 @@ pub fn convert_expr_to_bytecode<P: Plan>(
 ) -> Result<ByteCodeExpr, FairyError> {
     let mut bound_expr = expr;
     if let Some(col_id_to_idx) = col_id_to_idx {
+        if let Some(id) = bound_expr
+            .free()
+            .into_iter()
+            .find(|id| !col_id_to_idx.contains_key(id))
+        {
+            return Err(FairyError::ExecutionError(format!(
+                "Column {} is not produced by the operator's input",
+                id
+            )));
+        }
         bound_expr = bound_expr.replace_variables(col_id_to_idx);
     }
     let mut bytecode_expr = ByteCodeExpr::new();
@@ -89,6 +113,8 @@ fn convert_expr_to_bytecode_inner<P: Plan>(
                 BinaryOp::Neq => bytecode_expr.add_code(ByteCodes::Neq as usize),
                 BinaryOp::Gt => bytecode_expr.add_code(ByteCodes::Gt as usize),
                 BinaryOp::Ge => bytecode_expr.add_code(ByteCodes::Gte as usize),
+                BinaryOp::Like => bytecode_expr.add_code(ByteCodes::Like as usize),
+                BinaryOp::NotLike => bytecode_expr.add_code(ByteCodes::NotLike as usize),
                 BinaryOp::Lt => bytecode_expr.add_code(ByteCodes::Lt as usize),
                 BinaryOp::Le => bytecode_expr.add_code(ByteCodes::Lte as usize),
                 BinaryOp::And => bytecode_expr.add_code(ByteCodes::And as usize),
@@ -99,7 +125,90 @@ fn convert_expr_to_bytecode_inner<P: Plan>(
             bytecode_expr.add_code(ByteCodes::PushField as usize);
             bytecode_expr.add_code(*i);
         }
-        // TODO: Currently does not support `Case` and `Subquery` physical expressions
+        Expression::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            convert_expr_to_bytecode_inner(expr, bytecode_expr)?;
+            let i = bytecode_expr.add_list(InList::new(list.clone()));
+            let code = if *negated {
+                ByteCodes::NotInList
+            } else {
+                ByteCodes::InList
+            };
+            bytecode_expr.add_code(code as usize);
+            bytecode_expr.add_code(i);
+        }
+        Expression::IsNull { expr, negated } => {
+            convert_expr_to_bytecode_inner(expr, bytecode_expr)?;
+            let code = if *negated {
+                ByteCodes::IsNotNull
+            } else {
+                ByteCodes::IsNull
+            };
+            bytecode_expr.add_code(code as usize);
+        }
+        Expression::Cast { expr, dtype } => {
+            convert_expr_to_bytecode_inner(expr, bytecode_expr)?;
+            let i = bytecode_expr.add_type(dtype.clone());
+            bytecode_expr.add_code(ByteCodes::Cast as usize);
+            bytecode_expr.add_code(i);
+        }
+        Expression::ScalarFunction { func, args } => {
+            for arg in args {
+                convert_expr_to_bytecode_inner(arg, bytecode_expr)?;
+            }
+            bytecode_expr.add_code(ByteCodes::Call as usize);
+            bytecode_expr.add_code(*func as usize);
+            bytecode_expr.add_code(args.len());
+        }
+        Expression::Case {
+            expr,
+            whens,
+            else_expr,
+        } => {
+            // CASE x WHEN a THEN r1 WHEN b THEN r2 ELSE r3 END
+            // [x] ([dup][a][=] | [cond]) [jump if not true L1] [pop]? [r1] [jump end]
+            // L1: ... [pop]? [r3]
+            // end:
+            // The operand stays on the stack until a branch is taken, and only
+            // the codes of that branch run.
+            if let Some(expr) = expr {
+                convert_expr_to_bytecode_inner(expr, bytecode_expr)?;
+            }
+            let mut ends = Vec::with_capacity(whens.len());
+            for (when, then) in whens {
+                if expr.is_some() {
+                    bytecode_expr.add_code(ByteCodes::Dup as usize);
+                    convert_expr_to_bytecode_inner(when, bytecode_expr)?;
+                    bytecode_expr.add_code(ByteCodes::Eq as usize);
+                } else {
+                    convert_expr_to_bytecode_inner(when, bytecode_expr)?;
+                }
+                let next = bytecode_expr.add_jump(ByteCodes::JumpIfNotTrue);
+                if expr.is_some() {
+                    bytecode_expr.add_code(ByteCodes::Pop as usize);
+                }
+                convert_expr_to_bytecode_inner(then, bytecode_expr)?;
+                ends.push(bytecode_expr.add_jump(ByteCodes::Jump));
+                bytecode_expr.patch_jump(next);
+            }
+            if expr.is_some() {
+                bytecode_expr.add_code(ByteCodes::Pop as usize);
+            }
+            convert_expr_to_bytecode_inner(else_expr, bytecode_expr)?;
+            for end in ends {
+                bytecode_expr.patch_jump(end);
+            }
+        }
+        Expression::Parameter { index, .. } => {
+            return Err(FairyError::ValidationError(format!(
+                "No value for parameter ${}, run the statement with EXECUTE",
+                index
+            )))
+        }
+        // TODO: Currently does not support `Subquery` physical expressions
         _ => return Err(c_err("Unsupported expression")),
     }
     Ok(())
@@ -107,6 +216,9 @@ fn convert_expr_to_bytecode_inner<P: Plan>(
 
 /// Convert a physical plan to an opiterator.
 ///
+/// Filtered scans (a chain of selections over a scan) that appear more than once
+/// in the plan are executed once into a shared spool that every occurrence reads.
+///
 /// # Arguments
 ///
 /// * `managers` - Managers struct (saved in ServerState)
@@ -129,13 +241,1011 @@ pub fn physical_plan_to_op_iterator(
     tid: TransactionId,
     timestamp: LogicalTimeStamp,
 ) -> Result<Box<dyn OpIterator>, FairyError> {
-    let (result, _) =
-        physical_plan_to_op_iterator_helper(managers, catalog, physical_plan, tid, timestamp);
-    result
+    physical_plan_to_op_iterator_with_schema(managers, catalog, physical_plan, tid, timestamp)
+        .map(|(iter, _)| iter)
+}
+
+/// Convert a physical plan to an opiterator, also returning the schema derived
+/// for the root of the plan.
+///
+/// The schema of every node is derived once before any opiterator is built, so
+/// type errors in the plan's expressions are reported here. The output of every
+/// node is then checked against its derived schema when the opiterator is opened.
+///
+/// # Returns
+///
+/// * `Result<(Box<dyn OpIterator>, PlanSchema), FairyError>` - The converted root
+///   opiterator and the derived schema of its output
+pub fn physical_plan_to_op_iterator_with_schema(
+    managers: &'static Managers,
+    catalog: &CatalogRef,
+    physical_plan: &PhysicalRelExpr,
+    tid: TransactionId,
+    timestamp: LogicalTimeStamp,
+) -> Result<(Box<dyn OpIterator>, PlanSchema), FairyError> {
+    physical_plan_to_analyzed_op_iterator(
+        managers,
+        catalog,
+        physical_plan,
+        tid,
+        timestamp,
+        CorruptRecordMode::default(),
+        &Cancellation::default(),
+        false,
+    )
+    .map(|(iter, schema, _)| (iter, schema))
+}
+
+/// Convert a physical plan to an opiterator like `physical_plan_to_op_iterator_with_schema`,
+/// also returning the plan as converted, which reports the runtime decisions of
+/// its adaptive joins and the corrupt records its scans skipped once the
+/// opiterator ran.
+///
+/// # Arguments
+///
+/// * `corrupt_records` - How the scans treat records that do not decode against
+///   the schema of their table
+/// * `cancellation` - Token of the query, which stops its scans, hash join builds
+///   and sorts once it is cancelled
+/// * `profile` - Whether to count the rows, loops and time of the operators, as
+///   `profile_operators` does for every query
+///
+/// # Returns
+///
+/// * `Result<(Box<dyn OpIterator>, PlanSchema, AnalyzedPlan), FairyError>` - The
+///   converted root opiterator, the derived schema of its output and the plan
+#[allow(clippy::too_many_arguments)]
+pub fn physical_plan_to_analyzed_op_iterator(
+    managers: &'static Managers,
+    catalog: &CatalogRef,
+    physical_plan: &PhysicalRelExpr,
+    tid: TransactionId,
+    timestamp: LogicalTimeStamp,
+    corrupt_records: CorruptRecordMode,
+    cancellation: &Cancellation,
+    profile: bool,
+) -> Result<(Box<dyn OpIterator>, PlanSchema, AnalyzedPlan), FairyError> {
+    // Boxed so that the node addresses the policies are keyed by stay valid.
+    let mut plan = Box::new(physical_plan.clone());
+    let mut subqueries = SubqueryResults::default();
+    bind_subqueries(&mut plan, &mut |subquery| {
+        subqueries.rows(subquery, || {
+            run_subquery(
+                managers,
+                catalog,
+                subquery,
+                tid,
+                timestamp,
+                corrupt_records,
+                cancellation,
+            )
+        })
+    })?;
+    let mut shared_scans = SharedScans::default();
+    if let Ok(hashes) = plan.compute_hashes() {
+        shared_scans.find_candidates(&plan, hashes);
+    }
+    let mut schemas = NodeSchemas::new();
+    let root_schema = plan.derive_schema_with(catalog, &mut |node, schema| {
+        schemas.insert(node_key(node), schema.clone());
+    })?;
+    let mut runtime = PlanRuntime {
+        records: RecordCheck::new(corrupt_records),
+        cancellation: cancellation.clone(),
+        profiling: profile || managers.config.profile_operators,
+        ..PlanRuntime::default()
+    };
+    let (result, _) = physical_plan_to_op_iterator_helper(
+        managers,
+        catalog,
+        &plan,
+        tid,
+        timestamp,
+        &mut shared_scans,
+        &schemas,
+        &mut runtime,
+    );
+    Ok((
+        result?,
+        root_schema,
+        AnalyzedPlan {
+            plan,
+            runtime,
+            subqueries_run: subqueries.runs,
+            subqueries_spilled: subqueries.spilled(),
+        },
+    ))
+}
+
+/// Results of the uncorrelated subqueries of a plan, keyed by the plan of the
+/// subquery. The translator gives identical subqueries the same plan, so a
+/// subquery the statement refers to several times is run once. Its rows are
+/// materialized in a spool that every reference reads.
+#[derive(Default)]
+struct SubqueryResults {
+    spools: HashMap<String, (SharedSpool, TableSchema)>,
+    runs: usize,
+}
+
+impl SubqueryResults {
+    /// Returns a reader of the rows of `subquery`, spooling them with `run`
+    /// unless it was run before.
+    fn rows(
+        &mut self,
+        subquery: &PhysicalRelExpr,
+        run: impl FnOnce() -> Result<(SharedSpool, TableSchema), FairyError>,
+    ) -> Result<Spool, FairyError> {
+        let key = format!("{:?}", subquery);
+        if let Some((buffer, schema)) = self.spools.get(&key) {
+            return Ok(Spool::new(schema.clone(), buffer.clone()));
+        }
+        let (buffer, schema) = run()?;
+        self.runs += 1;
+        self.spools.insert(key, (buffer.clone(), schema.clone()));
+        Ok(Spool::new(schema, buffer))
+    }
+
+    /// Number of subqueries whose rows did not fit in memory and were spilled.
+    fn spilled(&self) -> usize {
+        self.spools
+            .values()
+            .filter(|(buffer, _)| buffer.lock().unwrap().has_spilled())
+            .count()
+    }
+}
+
+/// Filter over the records of a table, stored in the order of its columns, that
+/// keeps the rows the plan of `SELECT * FROM t WHERE ..` returns. Returns None
+/// if the plan keeps every row, or an error for plans that do more than filter
+/// one table, e.g. with subqueries.
+pub fn table_row_filter(plan: &PhysicalRelExpr) -> Result<Option<ByteCodeExpr>, FairyError> {
+    let unsupported = || c_err("Only conditions on the columns of the table are supported");
+    let mut predicates: Vec<Expression<PhysicalRelExpr>> = Vec::new();
+    let mut node = plan;
+    loop {
+        match node {
+            PhysicalRelExpr::Select {
+                src, predicates: p, ..
+            } => {
+                predicates.extend(p.iter().cloned());
+                node = src;
+            }
+            PhysicalRelExpr::Project { src, .. } => node = src,
+            PhysicalRelExpr::Rename {
+                src, src_to_dest, ..
+            } => {
+                // Predicates above the rename refer to the renamed columns.
+                let dest_to_src: ColIdToIdx = src_to_dest
+                    .iter()
+                    .map(|(src, dest)| (*dest, *src))
+                    .collect();
+                predicates = predicates
+                    .into_iter()
+                    .map(|p| p.replace_variables(&dest_to_src))
+                    .collect();
+                node = src;
+            }
+            PhysicalRelExpr::Scan { column_names, .. } => {
+                let to_offset: ColIdToIdx = column_names
+                    .iter()
+                    .map(|id| (*id, get_column_index_from_temp_col_id(*id)))
+                    .collect();
+                if predicates.iter().any(|p| {
+                    !p.subqueries().is_empty()
+                        || p.free().iter().any(|id| !to_offset.contains_key(id))
+                }) {
+                    return Err(unsupported());
+                }
+                if predicates.is_empty() {
+                    return Ok(None);
+                }
+                let filter = convert_expr_to_bytecode(
+                    Expression::combine_preds(&predicates),
+                    Some(&to_offset),
+                )?;
+                return Ok(Some(filter));
+            }
+            _ => return Err(unsupported()),
+        }
+    }
+}
+
+/// Calls `visit` on every expression of `node` and its children, on the
+/// operands of an expression before the expression itself. The plans of the
+/// subqueries in the expressions are left to `visit`.
+fn visit_plan_exprs(
+    node: &mut PhysicalRelExpr,
+    visit: &mut dyn FnMut(&mut Expression<PhysicalRelExpr>) -> Result<(), FairyError>,
+) -> Result<(), FairyError> {
+    match node {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select {
+            src, predicates, ..
+        } => {
+            for pred in predicates {
+                visit_expr(pred, visit)?;
+            }
+            visit_plan_exprs(src, visit)?;
+        }
+        PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => visit_plan_exprs(src, visit)?,
+        PhysicalRelExpr::Map { input, exprs, .. } => {
+            for (_, expr) in exprs {
+                visit_expr(expr, visit)?;
+            }
+            visit_plan_exprs(input, visit)?;
+        }
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            visit_plan_exprs(input, visit)?;
+            visit_plan_exprs(func, visit)?;
+        }
+        PhysicalRelExpr::Union { left, right, .. } => {
+            visit_plan_exprs(left, visit)?;
+            visit_plan_exprs(right, visit)?;
+        }
+        PhysicalRelExpr::CrossJoin {
+            left,
+            right,
+            predicates,
+            ..
+        }
+        | PhysicalRelExpr::NestedLoopJoin {
+            left,
+            right,
+            predicates,
+            ..
+        }
+        | PhysicalRelExpr::HashJoin {
+            left,
+            right,
+            predicates,
+            ..
+        }
+        | PhysicalRelExpr::SortMergeJoin {
+            left,
+            right,
+            predicates,
+            ..
+        } => {
+            for pred in predicates {
+                visit_expr(pred, visit)?;
+            }
+            visit_plan_exprs(left, visit)?;
+            visit_plan_exprs(right, visit)?;
+        }
+    }
+    Ok(())
+}
+
+/// Calls `visit` on `expr` and its operands, operands first.
+fn visit_expr(
+    expr: &mut Expression<PhysicalRelExpr>,
+    visit: &mut dyn FnMut(&mut Expression<PhysicalRelExpr>) -> Result<(), FairyError>,
+) -> Result<(), FairyError> {
+    match expr {
+        Expression::ColRef { .. }
+        | Expression::Field { .. }
+        | Expression::Parameter { .. }
+        | Expression::Subquery { .. } => {}
+        Expression::Binary { left, right, .. } => {
+            visit_expr(left, visit)?;
+            visit_expr(right, visit)?;
+        }
+        Expression::Case {
+            expr,
+            whens,
+            else_expr,
+        } => {
+            if let Some(expr) = expr {
+                visit_expr(expr, visit)?;
+            }
+            for (when, then) in whens {
+                visit_expr(when, visit)?;
+                visit_expr(then, visit)?;
+            }
+            visit_expr(else_expr, visit)?;
+        }
+        Expression::InList { expr, .. }
+        | Expression::IsNull { expr, .. }
+        | Expression::Cast { expr, .. }
+        | Expression::Quantified { expr, .. } => visit_expr(expr, visit)?,
+        Expression::ScalarFunction { args, .. } => {
+            for arg in args {
+                visit_expr(arg, visit)?;
+            }
+        }
+    }
+    visit(expr)
+}
+
+/// Replaces the subqueries in the expressions of `node` and its children by
+/// values read from the rows `rows` returns for them.
+fn bind_subqueries(
+    node: &mut PhysicalRelExpr,
+    rows: &mut dyn FnMut(&PhysicalRelExpr) -> Result<Spool, FairyError>,
+) -> Result<(), FairyError> {
+    visit_plan_exprs(node, &mut |expr| {
+        match expr {
+            Expression::Subquery { expr: subquery } => {
+                let mut rows = rows(subquery)?;
+                rows.open()?;
+                // A subquery without rows is NULL.
+                let val = rows.next()?.map_or(Field::Null, first_field);
+                if rows.next()?.is_some() {
+                    return Err(FairyError::ExecutionError(
+                        "Subquery returned more than one row".to_string(),
+                    ));
+                }
+                rows.close()?;
+                *expr = Expression::Field { val };
+            }
+            Expression::Quantified {
+                expr: tested,
+                op,
+                all,
+                subquery,
+            } => {
+                let mut rows = rows(subquery)?;
+                let tested =
+                    std::mem::replace(tested.as_mut(), Expression::Field { val: Field::Null });
+                *expr = quantified_comparison(tested, *op, *all, &mut rows)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+/// Value of the only column of a subquery row.
+fn first_field(t: Tuple) -> Field {
+    t.get_field(0).cloned().unwrap_or(Field::Null)
+}
+
+/// Binds the placeholders `$1`, `$2`, ... of a prepared statement's plan, and of
+/// the plans of its subqueries, to `params`. The values are converted to the
+/// type of what their placeholder is compared with, and must be comparable
+/// with it. Fails unless the placeholders are numbered from `$1` without gaps
+/// and there is one value per placeholder.
+pub fn bind_parameters(plan: &mut PhysicalRelExpr, params: &[Field]) -> Result<(), FairyError> {
+    let mut used = BTreeSet::new();
+    bind_plan_parameters(plan, params, &mut used)?;
+    let count = used.last().copied().unwrap_or(0);
+    if let Some(gap) = (1..=count).find(|index| !used.contains(index)) {
+        return Err(FairyError::ValidationError(format!(
+            "The statement uses ${} but not ${}, parameters must be numbered from $1 without gaps",
+            count, gap
+        )));
+    }
+    if count != params.len() {
+        return Err(FairyError::ValidationError(format!(
+            "The statement has {} parameters, {} values were given",
+            count,
+            params.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Binds the placeholders of `plan` like `bind_parameters`, adding the index of
+/// each placeholder it has to `used`.
+fn bind_plan_parameters(
+    plan: &mut PhysicalRelExpr,
+    params: &[Field],
+    used: &mut BTreeSet<usize>,
+) -> Result<(), FairyError> {
+    visit_plan_exprs(plan, &mut |expr| {
+        match expr {
+            Expression::Parameter { index, dtype } => {
+                used.insert(*index);
+                let missing = || {
+                    FairyError::ValidationError(format!(
+                        "No value for parameter ${}, {} values were given",
+                        index,
+                        params.len()
+                    ))
+                };
+                let mut val = index
+                    .checked_sub(1)
+                    .and_then(|i| params.get(i))
+                    .cloned()
+                    .ok_or_else(missing)?;
+                if let Some(dtype) = dtype {
+                    let mismatch = || {
+                        FairyError::ValidationError(format!(
+                            "Parameter ${} expects a value of type {}, got {}",
+                            index, dtype, val
+                        ))
+                    };
+                    let coerced = val.clone().coerce_to(dtype).map_err(|_| mismatch())?;
+                    if !comparable(dtype, &DataType::from(&coerced)) {
+                        return Err(mismatch());
+                    }
+                    val = coerced;
+                }
+                *expr = Expression::Field { val };
+            }
+            Expression::Subquery { expr: subquery } | Expression::Quantified { subquery, .. } => {
+                bind_plan_parameters(subquery, params, used)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+/// Rewrites `expr op ANY (rows)`, or `expr op ALL (rows)` if `all` is set, into
+/// an expression without the subquery that has the same three-valued result:
+/// the comparison is unknown if it is not decided by the non-NULL values and
+/// there is a NULL among them. Only `= ANY` and `<> ALL` keep every value, as
+/// an IN list, other comparisons keep the smallest and the largest value.
+fn quantified_comparison(
+    expr: Expression<PhysicalRelExpr>,
+    op: BinaryOp,
+    all: bool,
+    rows: &mut dyn OpIterator,
+) -> Result<Expression<PhysicalRelExpr>, FairyError> {
+    let in_list = matches!((op, all), (BinaryOp::Eq, false) | (BinaryOp::Neq, true));
+    let mut vals = Vec::new();
+    let mut empty = true;
+    let mut has_null = false;
+    let mut bounds: Option<(Field, Field)> = None;
+    rows.open()?;
+    while let Some(t) = rows.next()? {
+        empty = false;
+        let val = first_field(t);
+        if in_list {
+            vals.push(val);
+        } else if val == Field::Null {
+            has_null = true;
+        } else {
+            bounds = Some(match bounds {
+                None => (val.clone(), val),
+                Some((min, max)) => (
+                    std::cmp::min_by(min, val.clone(), Field::sql_cmp),
+                    std::cmp::max_by(max, val, Field::sql_cmp),
+                ),
+            });
+        }
+    }
+    rows.close()?;
+    // Nothing to compare with: ANY is false and ALL is true.
+    let none = |all| Expression::Field {
+        val: Field::Bool(all),
+    };
+    if empty {
+        return Ok(none(all));
+    }
+    if in_list {
+        return Ok(Expression::in_list(expr, vals, all));
+    }
+    let compare =
+        |val: &Field| Expression::binary(op, expr.clone(), Expression::Field { val: val.clone() });
+    let decided = match &bounds {
+        None => none(all),
+        Some((min, max)) => match op {
+            // Compared with the bound that is easiest to satisfy for ANY and
+            // hardest to satisfy for ALL.
+            BinaryOp::Lt | BinaryOp::Le => compare(if all { min } else { max }),
+            BinaryOp::Gt | BinaryOp::Ge => compare(if all { max } else { min }),
+            // `<> ANY` holds unless the expression equals the only value, and
+            // `= ALL` only holds if the expression equals the only value.
+            _ if min == max => compare(min),
+            BinaryOp::Neq => Expression::binary(BinaryOp::Or, compare(min), compare(max)),
+            _ => Expression::binary(BinaryOp::And, compare(min), compare(max)),
+        },
+    };
+    if has_null {
+        let combine = if all { BinaryOp::And } else { BinaryOp::Or };
+        Ok(Expression::binary(
+            combine,
+            decided,
+            Expression::Field { val: Field::Null },
+        ))
+    } else {
+        Ok(decided)
+    }
+}
+
+/// Plans an uncorrelated subquery into a spool, which runs it when it is first
+/// read. The spool keeps `spool_memory_rows` rows in memory and spills the
+/// rest to the temporary space of the statement.
+fn run_subquery(
+    managers: &'static Managers,
+    catalog: &CatalogRef,
+    subquery: &PhysicalRelExpr,
+    tid: TransactionId,
+    timestamp: LogicalTimeStamp,
+    corrupt_records: CorruptRecordMode,
+    cancellation: &Cancellation,
+) -> Result<(SharedSpool, TableSchema), FairyError> {
+    let (iter, _, _) = physical_plan_to_analyzed_op_iterator(
+        managers,
+        catalog,
+        subquery,
+        tid,
+        timestamp,
+        corrupt_records,
+        cancellation,
+        false,
+    )?;
+    let schema = iter.get_schema().clone();
+    let temp = TempReservation::new(managers.temp.clone(), tid);
+    let buffer = SpoolBuffer::new_shared(iter, managers.config.spool_memory_rows, temp);
+    Ok((buffer, schema))
+}
+
+/// A physical plan converted to an opiterator, together with the state its
+/// operators report at runtime.
+pub struct AnalyzedPlan {
+    plan: Box<PhysicalRelExpr>,
+    runtime: PlanRuntime,
+    subqueries_run: usize,
+    subqueries_spilled: usize,
+}
+
+impl AnalyzedPlan {
+    /// Number of distinct subqueries that were run before the plan.
+    pub fn subqueries_run(&self) -> usize {
+        self.subqueries_run
+    }
+
+    /// Number of those subqueries whose rows were spilled to the temporary
+    /// space.
+    pub fn subqueries_spilled(&self) -> usize {
+        self.subqueries_spilled
+    }
+
+    /// Number of corrupt records the scans of the plan skipped.
+    pub fn corrupt_records(&self) -> usize {
+        self.runtime.records.skipped()
+    }
+
+    /// Whether an adaptive join changed strategy while the plan ran.
+    pub fn adapted(&self) -> bool {
+        self.runtime
+            .policies
+            .values()
+            .any(|policy| policy.decision().is_some())
+    }
+
+    /// The runtime decisions of the adaptive joins, in the order the joins are
+    /// printed.
+    pub fn adaptations(&self) -> Vec<Option<Adaptation>> {
+        let mut joins = Vec::new();
+        collect_joins(&self.plan, &mut joins);
+        joins
+            .into_iter()
+            .map(|join| {
+                self.runtime
+                    .policies
+                    .get(&node_key(join))
+                    .and_then(AdaptivePolicy::decision)
+            })
+            .collect()
+    }
+
+    /// Rows, next calls and time of the operators of the plan, or None if the
+    /// operators were not profiled.
+    pub fn profile(&self) -> Option<QueryProfile> {
+        if !self.runtime.profiling {
+            return None;
+        }
+        let mut operators = Vec::new();
+        collect_profile(&self.plan, &self.runtime.profiles, 0, &mut operators);
+        Some(QueryProfile { operators })
+    }
+
+    /// Prints the plan like `PhysicalRelExpr::pretty_string`, marking the joins
+    /// that changed strategy while the plan ran.
+    pub fn pretty_string(&self) -> String {
+        let mut adaptations = self.adaptations().into_iter();
+        let mut out = String::new();
+        for line in self.plan.pretty_string().lines() {
+            out.push_str(line);
+            let node = line.trim_start();
+            if node.starts_with("-> Nested loop ") || node.starts_with("-> Hash ") {
+                if let Some(adaptation) = adaptations.next().flatten() {
+                    out.push_str(&format!(" (adapted: {})", adaptation));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Counters of an operator of a profiled plan.
+#[derive(Debug, Clone)]
+pub struct OperatorProfile {
+    /// Number of profiled operators above it.
+    pub depth: usize,
+    /// Name of the operator, as recorded in its tracing span.
+    pub name: &'static str,
+    pub rows: u64,
+    pub next_calls: u64,
+    /// Passes over its input, see `OperatorStats::loops`.
+    pub loops: u64,
+    /// Time spent in the operator and the operators below it.
+    pub elapsed: Duration,
+    /// Time spent in the operator itself.
+    pub self_elapsed: Duration,
+}
+
+/// Counters of the operators of a profiled plan, from the root down.
+#[derive(Debug, Clone)]
+pub struct QueryProfile {
+    pub operators: Vec<OperatorProfile>,
+}
+
+impl fmt::Display for QueryProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.operators.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}-> {} rows={} next_calls={} loops={} time={:.3}ms self={:.3}ms",
+                "  ".repeat(op.depth),
+                op.name,
+                op.rows,
+                op.next_calls,
+                op.loops,
+                op.elapsed.as_secs_f64() * 1000.0,
+                op.self_elapsed.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the counters of `node` and of the nodes below it to `operators`, from
+/// the root down. Renames run no operator of their own and are left out.
+/// Returns the time spent in the operators of the subtree.
+fn collect_profile(
+    node: &PhysicalRelExpr,
+    profiles: &HashMap<usize, Arc<OperatorStats>>,
+    depth: usize,
+    operators: &mut Vec<OperatorProfile>,
+) -> Duration {
+    let Some(stats) = profiles.get(&node_key(node)) else {
+        return node
+            .children()
+            .into_iter()
+            .map(|child| collect_profile(child, profiles, depth, operators))
+            .sum();
+    };
+    let at = operators.len();
+    operators.push(OperatorProfile {
+        depth,
+        name: operator_name(node),
+        rows: stats.rows(),
+        next_calls: stats.next_calls(),
+        loops: stats.loops(),
+        elapsed: stats.elapsed(),
+        self_elapsed: stats.elapsed(),
+    });
+    let children: Duration = node
+        .children()
+        .into_iter()
+        .map(|child| collect_profile(child, profiles, depth + 1, operators))
+        .sum();
+    operators[at].self_elapsed = stats.elapsed().saturating_sub(children);
+    stats.elapsed()
+}
+
+/// Collects the nested loop and hash joins of a plan in the order
+/// `PhysicalRelExpr::pretty_string` prints them.
+fn collect_joins<'a>(node: &'a PhysicalRelExpr, joins: &mut Vec<&'a PhysicalRelExpr>) {
+    match node {
+        PhysicalRelExpr::Scan { .. } => {}
+        PhysicalRelExpr::Select { src, .. }
+        | PhysicalRelExpr::Project { src, .. }
+        | PhysicalRelExpr::Sort { src, .. }
+        | PhysicalRelExpr::HashDistinct { src, .. }
+        | PhysicalRelExpr::Limit { src, .. }
+        | PhysicalRelExpr::TopN { src, .. }
+        | PhysicalRelExpr::Rename { src, .. }
+        | PhysicalRelExpr::HashAggregate { src, .. }
+        | PhysicalRelExpr::StreamAggregate { src, .. } => collect_joins(src, joins),
+        PhysicalRelExpr::Map { input, .. } => collect_joins(input, joins),
+        PhysicalRelExpr::FlatMap { input, func, .. } => {
+            collect_joins(input, joins);
+            collect_joins(func, joins);
+        }
+        PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+        | PhysicalRelExpr::HashJoin { left, right, .. } => {
+            joins.push(node);
+            collect_joins(left, joins);
+            collect_joins(right, joins);
+        }
+        PhysicalRelExpr::CrossJoin { left, right, .. }
+        | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+        | PhysicalRelExpr::Union { left, right, .. } => {
+            collect_joins(left, joins);
+            collect_joins(right, joins);
+        }
+    }
+}
+
+/// Policy for an adaptive join whose materialized input is `materialized`, or
+/// None if adaptive joins are disabled or the input cannot be estimated.
+fn adaptive_policy(
+    managers: &'static Managers,
+    materialized: &PhysicalRelExpr,
+    other: &PhysicalRelExpr,
+) -> Option<AdaptivePolicy> {
+    let factor = managers.config.adaptive_join_factor;
+    if factor <= 0.0 {
+        return None;
+    }
+    // Runtime decisions always read the real statistics.
+    let stats = StatsView::new(managers.stats);
+    let estimate = stats.estimate_rows(materialized)?;
+    Some(AdaptivePolicy::new(
+        estimate,
+        stats.estimate_rows(other),
+        factor,
+    ))
+}
+
+/// Derived schemas of the nodes of a plan, keyed by node address.
+type NodeSchemas = HashMap<usize, PlanSchema>;
+
+/// State the operators of a plan share with the planner, reported once the plan ran.
+#[derive(Default)]
+struct PlanRuntime {
+    /// Policies of the adaptive joins, keyed by node address.
+    policies: HashMap<usize, AdaptivePolicy>,
+    /// Corrupt record handling of the scans.
+    records: RecordCheck,
+    /// Token of the query, checked by the scans, hash join builds and sorts.
+    cancellation: Cancellation,
+    /// Page filters of the scans right below a selection, keyed by table.
+    page_filters: HashMap<ContainerId, PageFilter>,
+    /// Predicates pushed into the scans right below a selection, keyed by table.
+    scan_predicates: HashMap<ContainerId, ScanPredicate>,
+    /// Whether the operators count their rows, next calls and time.
+    profiling: bool,
+    /// Counters of the operators when profiling, keyed by node address.
+    profiles: HashMap<usize, Arc<OperatorStats>>,
+}
+
+fn node_key(node: &PhysicalRelExpr) -> usize {
+    node as *const PhysicalRelExpr as usize
+}
+
+/// Name of the operator of a node, as recorded in its tracing span.
+fn operator_name(node: &PhysicalRelExpr) -> &'static str {
+    match node {
+        PhysicalRelExpr::Scan { .. } => "scan",
+        PhysicalRelExpr::Select { .. } => "select",
+        PhysicalRelExpr::CrossJoin { .. } => "cross_join",
+        PhysicalRelExpr::NestedLoopJoin { .. } => "nested_loop_join",
+        PhysicalRelExpr::HashJoin { .. } => "hash_join",
+        PhysicalRelExpr::SortMergeJoin { .. } => "sort_merge_join",
+        PhysicalRelExpr::Project { .. } => "project",
+        PhysicalRelExpr::Sort { .. } => "sort",
+        PhysicalRelExpr::HashDistinct { .. } => "hash_distinct",
+        PhysicalRelExpr::Limit { .. } => "limit",
+        PhysicalRelExpr::TopN { .. } => "top_n",
+        PhysicalRelExpr::HashAggregate { .. } => "hash_aggregate",
+        PhysicalRelExpr::StreamAggregate { .. } => "stream_aggregate",
+        PhysicalRelExpr::Map { .. } => "map",
+        PhysicalRelExpr::FlatMap { .. } => "flatmap",
+        PhysicalRelExpr::Rename { .. } => "rename",
+        PhysicalRelExpr::Union { .. } => "union",
+    }
+}
+
+/// The table read by `src`, the input of a selection, if it is a scan or a
+/// renamed scan, with the column ids seen by the selection mapped to the
+/// columns of the table.
+fn scanned_columns(src: &PhysicalRelExpr) -> Option<(ContainerId, ColIdToIdx)> {
+    let (scan, renames) = match src {
+        PhysicalRelExpr::Rename {
+            src, src_to_dest, ..
+        } => (&**src, Some(src_to_dest)),
+        _ => (src, None),
+    };
+    let PhysicalRelExpr::Scan {
+        cid, column_names, ..
+    } = scan
+    else {
+        return None;
+    };
+    let mut columns: ColIdToIdx = column_names
+        .iter()
+        .map(|id| (*id, get_column_index_from_temp_col_id(*id)))
+        .collect();
+    if let Some(renames) = renames {
+        columns = renames
+            .iter()
+            .filter_map(|(src, dest)| Some((*dest, *columns.get(src)?)))
+            .collect();
+    }
+    Some((*cid, columns))
+}
+
+/// Builds the page filter of the scan below a selection, if any, from the
+/// predicates that reference a single dictionary encoded column of its table.
+fn dictionary_page_filter(
+    catalog: &CatalogRef,
+    src: &PhysicalRelExpr,
+    predicates: &[Expression<PhysicalRelExpr>],
+) -> Option<(ContainerId, PageFilter)> {
+    let (cid, columns) = scanned_columns(src)?;
+    let table = catalog.get_table(cid)?;
+    let encoded = table.dictionary_columns();
+    if encoded.is_empty() {
+        return None;
+    }
+    let mut filter = PageFilter::default();
+    for pred in predicates {
+        let free = pred.free();
+        let [id] = free.iter().collect::<Vec<_>>()[..] else {
+            continue;
+        };
+        let Some(&column) = columns.get(id) else {
+            continue;
+        };
+        if !encoded.contains(&column) || pred.has_subquery() {
+            continue;
+        }
+        if let Ok(expr) = convert_expr_to_bytecode(pred.clone(), Some(&columns)) {
+            filter.add(column, table.schema.size(), expr);
+        }
+    }
+    (!filter.is_empty()).then_some((cid, filter))
+}
+
+/// Moves the predicates of a selection that compare a fixed-width column of
+/// the table scanned right below it with a constant into a predicate of the
+/// scan, which checks them before decoding the records. Returns the scan
+/// predicate, if any, and the predicates left to the selection.
+fn push_into_scan<'a>(
+    catalog: &CatalogRef,
+    src: &PhysicalRelExpr,
+    predicates: &'a [Expression<PhysicalRelExpr>],
+) -> (
+    Option<(ContainerId, ScanPredicate)>,
+    Vec<&'a Expression<PhysicalRelExpr>>,
+) {
+    let Some((cid, columns)) = scanned_columns(src) else {
+        return (None, predicates.iter().collect());
+    };
+    let Some(schema) = catalog.get_table_schema(cid) else {
+        return (None, predicates.iter().collect());
+    };
+    let mut pushed = ScanPredicate::default();
+    let mut remaining = Vec::new();
+    for pred in predicates {
+        let comparison = match pred {
+            Expression::Binary { op, left, right } => match (left.as_ref(), right.as_ref()) {
+                (Expression::ColRef { id }, Expression::Field { val }) => {
+                    flip_comparison(*op).map(|_| (*id, *op, val))
+                }
+                (Expression::Field { val }, Expression::ColRef { id }) => {
+                    flip_comparison(*op).map(|flipped| (*id, flipped, val))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let column = comparison.and_then(|(id, op, val)| {
+            let column = *columns.get(&id)?;
+            let fixed = schema.get_attribute(column)?.dtype().size().is_some();
+            fixed.then_some((column, op, val))
+        });
+        match column {
+            Some((column, op, val)) => pushed.add(column, op, val.clone()),
+            None => remaining.push(pred),
+        }
+    }
+    let pushed = (!pushed.is_empty()).then_some((cid, pushed));
+    (pushed, remaining)
+}
+
+/// Filtered scans that occur more than once in a single plan, keyed by tree hash,
+/// together with the spools built for them so far.
+#[derive(Default)]
+struct SharedScans {
+    /// Debug representation of the subtree for every shared tree hash. Used to
+    /// guard against hash collisions, since the spooled column layout is reused.
+    candidates: HashMap<u64, String>,
+    /// Tree hashes of the nodes of the plan.
+    hashes: Option<PlanHashes>,
+    spools: HashMap<u64, (SharedSpool, TableSchema, ColIdToIdx)>,
+}
+
+impl SharedScans {
+    /// Collects the filtered scans of the plan with the given hashes that occur more
+    /// than once.
+    fn find_candidates(&mut self, plan: &PhysicalRelExpr, hashes: PlanHashes) {
+        let mut seen: HashMap<u64, (String, usize)> = HashMap::new();
+        let mut conflicts = Vec::new();
+        let mut stack = vec![plan];
+        while let Some(node) = stack.pop() {
+            if is_filtered_scan(node) {
+                let Ok(hash) = hashes.get(node) else {
+                    continue;
+                };
+                let repr = format!("{:?}", node);
+                let (first, count) = seen.entry(hash).or_insert_with(|| (repr.clone(), 0));
+                if *first != repr {
+                    conflicts.push(hash);
+                }
+                *count += 1;
+                // Selections below this one are part of the same filtered scan.
+                continue;
+            }
+            match node {
+                PhysicalRelExpr::Scan { .. } => {}
+                PhysicalRelExpr::Select { src, .. }
+                | PhysicalRelExpr::Project { src, .. }
+                | PhysicalRelExpr::Sort { src, .. }
+                | PhysicalRelExpr::HashDistinct { src, .. }
+                | PhysicalRelExpr::Limit { src, .. }
+                | PhysicalRelExpr::TopN { src, .. }
+                | PhysicalRelExpr::Rename { src, .. }
+                | PhysicalRelExpr::HashAggregate { src, .. }
+                | PhysicalRelExpr::StreamAggregate { src, .. } => stack.push(src),
+                PhysicalRelExpr::Map { input, .. } => stack.push(input),
+                PhysicalRelExpr::FlatMap { input, func, .. } => {
+                    stack.push(input);
+                    stack.push(func);
+                }
+                PhysicalRelExpr::CrossJoin { left, right, .. }
+                | PhysicalRelExpr::NestedLoopJoin { left, right, .. }
+                | PhysicalRelExpr::HashJoin { left, right, .. }
+                | PhysicalRelExpr::SortMergeJoin { left, right, .. }
+                | PhysicalRelExpr::Union { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        for hash in conflicts {
+            seen.remove(&hash);
+        }
+        self.candidates = seen
+            .into_iter()
+            .filter(|(_, (_, count))| *count > 1)
+            .map(|(hash, (repr, _))| (hash, repr))
+            .collect();
+        self.hashes = Some(hashes);
+    }
+
+    /// Returns the tree hash of `node` if it is a filtered scan that should be spooled.
+    fn shared_hash(&self, node: &PhysicalRelExpr) -> Option<u64> {
+        if self.candidates.is_empty() || !is_filtered_scan(node) {
+            return None;
+        }
+        let hash = self.hashes.as_ref()?.get(node).ok()?;
+        let repr = self.candidates.get(&hash)?;
+        (*repr == format!("{:?}", node)).then_some(hash)
+    }
+}
+
+/// Returns true if the node is a chain of one or more selections over a scan.
+fn is_filtered_scan(node: &PhysicalRelExpr) -> bool {
+    match node {
+        PhysicalRelExpr::Select { src, .. } => {
+            matches!(src.as_ref(), PhysicalRelExpr::Scan { .. }) || is_filtered_scan(src)
+        }
+        _ => false,
+    }
 }
 
 /// Helper function called by `physical_plan_to_op_iterator` to recursively convert the
-/// physical plan to an opiterator.
+/// physical plan to an opiterator. The output of every node except renames, which
+/// reuse the opiterator of their child, is checked against the node's derived schema.
 ///
 /// # Arguments
 ///
@@ -154,16 +1264,308 @@ pub fn physical_plan_to_op_iterator(
 /// * `Result<(Box<dyn OpIterator>, HashMap<ColumnId, ColumnId>), FairyError>` -
 ///   The converted opiterator and a mapping from the unique column ID to the
 ///   index of the column in the schema
+#[allow(clippy::too_many_arguments)]
 fn physical_plan_to_op_iterator_helper(
+    managers: &'static Managers,
+    catalog: &CatalogRef,
+    physical_plan: &PhysicalRelExpr,
+    tid: TransactionId,
+    timestamp: LogicalTimeStamp,
+    shared_scans: &mut SharedScans,
+    schemas: &NodeSchemas,
+    runtime: &mut PlanRuntime,
+) -> (
+    Result<Box<dyn OpIterator>, FairyError>,
+    HashMap<ColumnId, ColumnId>,
+) {
+    let (iter, col_id_to_idx) = plan_node(
+        managers,
+        catalog,
+        physical_plan,
+        tid,
+        timestamp,
+        shared_scans,
+        schemas,
+        runtime,
+    );
+    if matches!(physical_plan, PhysicalRelExpr::Rename { .. }) {
+        return (iter, col_id_to_idx);
+    }
+    let iter = iter.map(|iter| match schemas.get(&node_key(physical_plan)) {
+        Some(schema) => Box::new(SchemaCheck::new(schema.clone(), iter)) as Box<dyn OpIterator>,
+        None => iter,
+    });
+    let table = match physical_plan {
+        PhysicalRelExpr::Scan { cid, .. } => Some(*cid),
+        _ => None,
+    };
+    let stats = runtime.profiling.then(|| {
+        runtime
+            .profiles
+            .entry(node_key(physical_plan))
+            .or_default()
+            .clone()
+    });
+    let iter = iter.map(|iter| {
+        Box::new(Traced::new(
+            operator_name(physical_plan),
+            table,
+            iter,
+            stats,
+        )) as Box<dyn OpIterator>
+    });
+    (iter, col_id_to_idx)
+}
+
+/// Converts a node of the physical plan, reusing the spool of a shared filtered scan.
+///
+/// # Arguments
+///
+/// * `managers` - Managers struct (saved in ServerState)
+///
+/// * `catalog` - Shared ownership of the catalog
+///
+/// * `physical_plan` - Root of the physical plan tree
+///
+/// * `tid` - Transaction ID
+///
+/// * `timestamp` - Logical timestamp
+///
+/// # Returns
+///
+/// * `Result<(Box<dyn OpIterator>, HashMap<ColumnId, ColumnId>), FairyError>` -
+///   The converted opiterator and a mapping from the unique column ID to the
+///   index of the column in the schema
+#[allow(clippy::too_many_arguments)]
+fn plan_node(
     managers: &'static Managers,
     catalog: &CatalogRef,
     physical_plan: &PhysicalRelExpr,
     tid: TransactionId,
     _timestamp: LogicalTimeStamp,
+    shared_scans: &mut SharedScans,
+    schemas: &NodeSchemas,
+    runtime: &mut PlanRuntime,
 ) -> (
     Result<Box<dyn OpIterator>, FairyError>,
     HashMap<ColumnId, ColumnId>,
 ) {
+    let hash = match shared_scans.shared_hash(physical_plan) {
+        Some(hash) => hash,
+        None => {
+            return split_result(build_op_iterator(
+                managers,
+                catalog,
+                physical_plan,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            ))
+        }
+    };
+    if let Some((buffer, schema, col_id_to_idx)) = shared_scans.spools.get(&hash) {
+        debug!("Reusing spool for shared filtered scan {}", hash);
+        let spool = Spool::new(schema.clone(), buffer.clone());
+        return (Ok(Box::new(spool)), col_id_to_idx.clone());
+    }
+    let (src_iter, col_id_to_idx) = split_result(build_op_iterator(
+        managers,
+        catalog,
+        physical_plan,
+        tid,
+        _timestamp,
+        shared_scans,
+        schemas,
+        runtime,
+    ));
+    let src_iter = match src_iter {
+        Ok(src_iter) => src_iter,
+        Err(e) => return (Err(e), col_id_to_idx),
+    };
+    debug!("Spooling shared filtered scan {}", hash);
+    let schema = src_iter.get_schema().clone();
+    let temp = TempReservation::new(managers.temp.clone(), tid);
+    let buffer = SpoolBuffer::new_shared(src_iter, managers.config.spool_memory_rows, temp);
+    shared_scans.spools.insert(
+        hash,
+        (buffer.clone(), schema.clone(), col_id_to_idx.clone()),
+    );
+    (Ok(Box::new(Spool::new(schema, buffer))), col_id_to_idx)
+}
+
+/// Splits the result of `build_op_iterator` into the form returned by
+/// `physical_plan_to_op_iterator_helper`.
+fn split_result(
+    result: Result<(Box<dyn OpIterator>, ColIdToIdx), FairyError>,
+) -> (Result<Box<dyn OpIterator>, FairyError>, ColIdToIdx) {
+    match result {
+        Ok((iter, col_id_to_idx)) => (Ok(iter), col_id_to_idx),
+        Err(e) => (Err(e), HashMap::new()),
+    }
+}
+
+/// Looks up the offset of column `id` in the output of an operator.
+fn column_index(col_id_to_idx: &ColIdToIdx, id: ColumnId) -> Result<ColumnId, FairyError> {
+    col_id_to_idx.get(&id).copied().ok_or_else(|| {
+        FairyError::ExecutionError(format!(
+            "Column {} is not produced by the operator's input",
+            id
+        ))
+    })
+}
+
+/// Returns an error for join types the join operators cannot execute.
+fn check_inner_join(join_type: &JoinType) -> Result<(), FairyError> {
+    match join_type {
+        JoinType::Inner | JoinType::CrossJoin => Ok(()),
+        other => Err(FairyError::ExecutionError(format!(
+            "{:?} join is not supported",
+            other
+        ))),
+    }
+}
+
+/// Whether a join of `join_type` outputs the matching pairs and nothing else.
+/// Outer, semi and anti joins also depend on which tuples match nothing, which
+/// only the serial joins track.
+fn is_inner(join_type: &JoinType) -> bool {
+    matches!(join_type, JoinType::Inner | JoinType::CrossJoin)
+}
+
+/// Output schema of a join of `left` and `right`. The columns an outer join
+/// pads with NULLs accept NULL, and semi and anti joins output `left` only.
+fn join_schema(join_type: &JoinType, left: &TableSchema, right: &TableSchema) -> TableSchema {
+    if join_type.outputs_left_only() {
+        return left.clone();
+    }
+    let left = match join_type {
+        JoinType::RightOuter | JoinType::FullOuter => left.nullable(),
+        _ => left.clone(),
+    };
+    let right = match join_type {
+        JoinType::LeftOuter | JoinType::FullOuter => right.nullable(),
+        _ => right.clone(),
+    };
+    left.merge(&right)
+}
+
+/// Column positions in the output of a join, given those in its `left` input
+/// and in the joined tuples.
+fn join_col_map(join_type: &JoinType, left: ColIdToIdx, joined: ColIdToIdx) -> ColIdToIdx {
+    if join_type.outputs_left_only() {
+        left
+    } else {
+        joined
+    }
+}
+
+/// Splits a join predicate `l op r` into a key over the left input and a key
+/// over the right input, flipping the comparison if the sides are swapped.
+/// Returns None if the predicate is not a comparison with one side per input.
+fn split_join_keys(
+    pred: &Expression<PhysicalRelExpr>,
+    left_col_id_to_idx: &ColIdToIdx,
+    right_col_id_to_idx: &ColIdToIdx,
+) -> Option<(
+    BinaryOp,
+    Expression<PhysicalRelExpr>,
+    Expression<PhysicalRelExpr>,
+)> {
+    let Expression::Binary { op, left, right } = pred else {
+        return None;
+    };
+    let flipped = flip_comparison(*op)?;
+    let binds = |e: &Expression<PhysicalRelExpr>, map: &ColIdToIdx| {
+        e.free().iter().all(|id| map.contains_key(id))
+    };
+    if binds(left, left_col_id_to_idx) && binds(right, right_col_id_to_idx) {
+        Some((*op, *left.clone(), *right.clone()))
+    } else if binds(right, left_col_id_to_idx) && binds(left, right_col_id_to_idx) {
+        Some((flipped, *right.clone(), *left.clone()))
+    } else {
+        None
+    }
+}
+
+/// The comparison that holds for `r op' l` when `l op r` does, or None if
+/// `op` is not a comparison.
+fn flip_comparison(op: BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::Eq | BinaryOp::Neq => Some(op),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::Le => Some(BinaryOp::Ge),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::Ge => Some(BinaryOp::Le),
+        _ => None,
+    }
+}
+
+/// Joins `left` and `right` with a cross join and filters the result with
+/// `predicates`, evaluated over the joined tuple.
+fn filtered_cross_join(
+    schema: TableSchema,
+    left: Box<dyn OpIterator>,
+    right: Box<dyn OpIterator>,
+    predicates: &[Expression<PhysicalRelExpr>],
+    col_id_to_idx: &ColIdToIdx,
+) -> Result<Box<dyn OpIterator>, FairyError> {
+    let mut child: Box<dyn OpIterator> = Box::new(CrossJoin::new(schema.clone(), left, right));
+    for pred in predicates {
+        let expr = convert_expr_to_bytecode(pred.clone(), Some(col_id_to_idx))?;
+        child = Box::new(Filter::new(expr, schema.clone(), child));
+    }
+    Ok(child)
+}
+
+/// Sorts `input`, the operator of `node`, ascending on `keys` for a sort-merge
+/// join, unless `node` already produces its rows in that order.
+fn sort_join_input(
+    managers: &'static Managers,
+    tid: TransactionId,
+    node: &PhysicalRelExpr,
+    keys: &[Expression<PhysicalRelExpr>],
+    input: Box<dyn OpIterator>,
+    col_id_to_idx: &ColIdToIdx,
+    cancellation: &Cancellation,
+) -> Result<Box<dyn OpIterator>, FairyError> {
+    let order = node.output_order();
+    let sorted = keys.len() <= order.len()
+        && keys.iter().zip(&order).all(|(key, (id, asc, _))| {
+            *asc && matches!(key, Expression::ColRef { id: key_id } if key_id == id)
+        });
+    if sorted {
+        return Ok(input);
+    }
+    let mut fields = Vec::with_capacity(keys.len());
+    for key in keys {
+        fields.push((
+            convert_expr_to_bytecode(key.clone(), Some(col_id_to_idx))?,
+            true,
+            false,
+        ));
+    }
+    let schema = input.get_schema().clone();
+    let temp = TempReservation::new(managers.temp.clone(), tid);
+    Ok(Box::new(
+        Sort::new(managers, fields, schema, input, temp).with_cancellation(cancellation.clone()),
+    ))
+}
+
+/// Converts a single node of the physical plan, calling
+/// `physical_plan_to_op_iterator_helper` for its children.
+#[allow(clippy::too_many_arguments)]
+fn build_op_iterator(
+    managers: &'static Managers,
+    catalog: &CatalogRef,
+    physical_plan: &PhysicalRelExpr,
+    tid: TransactionId,
+    _timestamp: LogicalTimeStamp,
+    shared_scans: &mut SharedScans,
+    schemas: &NodeSchemas,
+    runtime: &mut PlanRuntime,
+) -> Result<(Box<dyn OpIterator>, ColIdToIdx), FairyError> {
     let err = FairyError::ExecutionError(String::from("Malformed logical plan"));
 
     match physical_plan {
@@ -173,21 +1575,20 @@ fn physical_plan_to_op_iterator_helper(
             column_names,
             ..
         } => {
-            let in_schema = catalog.get_table_schema(*cid).unwrap();
+            let in_schema = catalog
+                .get_table_schema(*cid)
+                .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, cid.to_string()))?;
 
             let mut out_schema_att = Vec::new();
-            let mut fields = Vec::new();
+            let mut columns = Vec::new();
             for name in column_names {
                 // first locate the offset of the column in the base relation
                 let name = get_column_index_from_temp_col_id(*name);
-                out_schema_att.push(in_schema.get_attribute(name).unwrap().clone());
-                fields.push(
-                    convert_expr_to_bytecode(
-                        Expression::<PhysicalRelExpr>::ColRef { id: name },
-                        None,
-                    )
-                    .unwrap(),
-                );
+                let attr = in_schema.get_attribute(name).ok_or_else(|| {
+                    FairyError::NotFound(ObjectKind::Column, format!("{} of table {}", name, cid))
+                })?;
+                out_schema_att.push(attr.clone());
+                columns.push(name);
             }
             let out_schema = TableSchema::new(out_schema_att);
 
@@ -197,22 +1598,33 @@ fn physical_plan_to_op_iterator_helper(
                 .map(|(i, id)| (*id, i as ColumnId))
                 .collect::<HashMap<ColumnId, ColumnId>>();
 
-            let scan_iter = SeqScan::new(managers, &out_schema, cid, tid, None, Some(fields));
-            (Ok(Box::new(scan_iter)), col_id_to_idx)
+            let scan_iter = SeqScan::new(managers, &out_schema, cid, tid, None, None)
+                .with_columns(columns)
+                .with_record_check(&in_schema, runtime.records.clone())
+                .with_page_filter(runtime.page_filters.remove(cid).unwrap_or_default())
+                .with_predicate(runtime.scan_predicates.remove(cid).unwrap_or_default())
+                .with_cancellation(runtime.cancellation.clone());
+            Ok((Box::new(scan_iter), col_id_to_idx))
         }
 
         PhysicalRelExpr::Project { src, cols, .. } => {
-            let (src_iter, col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, src, tid, _timestamp);
-            let input_schema = src_iter.as_ref().unwrap().get_schema();
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+            let input_schema = src_iter.get_schema();
 
             let indexes = cols
                 .iter()
-                .map(|id| match col_id_to_idx.get(id) {
-                    Some(idx) => *idx,
-                    None => panic!("Column id not found in col_id_to_idx"),
-                })
-                .collect::<Vec<_>>();
+                .map(|id| column_index(&col_id_to_idx, *id))
+                .collect::<Result<Vec<_>, _>>()?;
             let attrs = indexes
                 .iter()
                 .map(|i| input_schema.get_attribute(*i).unwrap().clone())
@@ -223,55 +1635,219 @@ fn physical_plan_to_op_iterator_helper(
                 cols.iter()
                     .map(|i| Expression::<PhysicalRelExpr>::ColRef { id: *i })
                     .map(|e| convert_expr_to_bytecode(e, Some(&col_id_to_idx)))
-                    .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()
-                    .unwrap(),
+                    .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()?,
                 schema,
-                src_iter.unwrap(),
+                src_iter,
             );
-            (
-                Ok(Box::new(project_iter)),
+            Ok((
+                Box::new(project_iter),
                 cols.iter()
                     .enumerate()
                     .map(|(i, id)| (*id, i as ColumnId))
                     .collect(),
-            )
+            ))
         }
 
         PhysicalRelExpr::Rename {
             src, src_to_dest, ..
         } => {
-            let (src_iter, col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, src, tid, _timestamp);
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
             let new_col_id_to_index = col_id_to_idx
                 .iter()
-                .map(|(old_id, offset)| {
-                    let new_id = src_to_dest.get(old_id).unwrap();
-                    (*new_id, *offset)
+                .map(|(old_id, offset)| match src_to_dest.get(old_id) {
+                    Some(new_id) => Ok((*new_id, *offset)),
+                    None => Err(FairyError::ExecutionError(format!(
+                        "Rename has no target for column {}",
+                        old_id
+                    ))),
                 })
-                .collect::<HashMap<ColumnId, ColumnId>>();
-            (src_iter, new_col_id_to_index)
+                .collect::<Result<HashMap<ColumnId, ColumnId>, FairyError>>()?;
+            Ok((src_iter, new_col_id_to_index))
         }
 
         PhysicalRelExpr::Select {
             src, predicates, ..
         } => {
-            let (src_iter, col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, src, tid, _timestamp);
+            if let Some((cid, filter)) = dictionary_page_filter(catalog, src, predicates) {
+                runtime.page_filters.insert(cid, filter);
+            }
+            let (pushed, predicates) = push_into_scan(catalog, src, predicates);
+            if let Some((cid, predicate)) = pushed {
+                runtime.scan_predicates.insert(cid, predicate);
+            }
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
 
             let mut bytecode_exprs = Vec::new();
             for pred in predicates {
-                let bytecode_expr =
-                    convert_expr_to_bytecode(pred.clone(), Some(&col_id_to_idx)).unwrap();
+                let bytecode_expr = convert_expr_to_bytecode(pred.clone(), Some(&col_id_to_idx))?;
                 bytecode_exprs.push(bytecode_expr);
             }
 
-            let mut child = src_iter.unwrap();
+            let mut child = src_iter;
             let mut cur_filter;
             for expr in bytecode_exprs {
                 cur_filter = Filter::new(expr, child.get_schema().clone(), child);
                 child = Box::new(cur_filter);
             }
-            (Ok(child), col_id_to_idx)
+            Ok((child, col_id_to_idx))
+        }
+
+        PhysicalRelExpr::Sort { src, cols, .. } => {
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+
+            let mut fields = Vec::new();
+            for (id, asc, nulls_first) in cols {
+                let key = Expression::<PhysicalRelExpr>::ColRef { id: *id };
+                fields.push((
+                    convert_expr_to_bytecode(key, Some(&col_id_to_idx))?,
+                    *asc,
+                    *nulls_first,
+                ));
+            }
+            let schema = src_iter.get_schema().clone();
+            let temp = TempReservation::new(managers.temp.clone(), tid);
+            let sort_iter = Sort::new(managers, fields, schema, src_iter, temp)
+                .with_cancellation(runtime.cancellation.clone());
+            Ok((Box::new(sort_iter), col_id_to_idx))
+        }
+
+        PhysicalRelExpr::TopN {
+            src, cols, limit, ..
+        } => {
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+
+            let mut fields = Vec::new();
+            for (id, asc, nulls_first) in cols {
+                let key = Expression::<PhysicalRelExpr>::ColRef { id: *id };
+                fields.push((
+                    convert_expr_to_bytecode(key, Some(&col_id_to_idx))?,
+                    *asc,
+                    *nulls_first,
+                ));
+            }
+            let schema = src_iter.get_schema().clone();
+            let top_n_iter = TopN::new(fields, *limit, schema, src_iter);
+            Ok((Box::new(top_n_iter), col_id_to_idx))
+        }
+
+        PhysicalRelExpr::HashDistinct { src, .. } => {
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+            let schema = src_iter.get_schema().clone();
+            let distinct_iter = Distinct::new(schema, src_iter);
+            Ok((Box::new(distinct_iter), col_id_to_idx))
+        }
+
+        PhysicalRelExpr::Limit { src, limit, .. } => {
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+            let schema = src_iter.get_schema().clone();
+            let limit_iter = Limit::new(*limit, schema, src_iter);
+            Ok((Box::new(limit_iter), col_id_to_idx))
+        }
+
+        PhysicalRelExpr::Union { left, right, .. } => {
+            let (left_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                left,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let left_iter = left_iter?;
+            let (right_iter, _) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                right,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let right_iter = right_iter?;
+            let (left_size, right_size) = (
+                left_iter.get_schema().size(),
+                right_iter.get_schema().size(),
+            );
+            if left_size != right_size {
+                return Err(FairyError::ExecutionError(format!(
+                    "UNION inputs have {} and {} columns",
+                    left_size, right_size
+                )));
+            }
+
+            // The columns are named after those of the left input and typed to
+            // hold the values of both inputs.
+            let mut attrs = left_iter.get_schema().attributes.clone();
+            if let Some(schema) = schemas.get(&node_key(physical_plan)) {
+                for (attr, col) in attrs.iter_mut().zip(schema) {
+                    attr.dtype = col.dtype.clone();
+                }
+            }
+            let union_iter = Union::new(TableSchema::new(attrs), left_iter, right_iter);
+            Ok((Box::new(union_iter), col_id_to_idx))
         }
 
         PhysicalRelExpr::CrossJoin {
@@ -281,13 +1857,31 @@ fn physical_plan_to_op_iterator_helper(
             predicates,
             ..
         } => {
-            let (left_iter, left_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, left, tid, _timestamp);
-            let (right_iter, right_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, right, tid, _timestamp);
+            let (left_iter, left_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                left,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let left_iter = left_iter?;
+            let (right_iter, right_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                right,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let right_iter = right_iter?;
 
-            let left_schema = left_iter.as_ref().unwrap().get_schema();
-            let right_schema = right_iter.as_ref().unwrap().get_schema();
+            let left_schema = left_iter.get_schema();
+            let right_schema = right_iter.get_schema();
             let new_schema = left_schema.merge(right_schema);
 
             let mut new_col_id_to_idx = left_col_id_to_idx;
@@ -295,100 +1889,273 @@ fn physical_plan_to_op_iterator_helper(
                 new_col_id_to_idx.insert(old_id, offset + left_schema.size());
             }
 
-            let join_op = Box::new(CrossJoin::new(
-                new_schema.clone(),
-                left_iter.unwrap(),
-                right_iter.unwrap(),
-            ));
+            let join = filtered_cross_join(
+                new_schema,
+                left_iter,
+                right_iter,
+                predicates,
+                &new_col_id_to_idx,
+            )?;
+            Ok((join, new_col_id_to_idx))
+        }
 
-            if predicates.is_empty() {
-                return (Ok(join_op), new_col_id_to_idx);
-            }
+        PhysicalRelExpr::NestedLoopJoin {
+            join_type,
+            left,
+            right,
+            predicates,
+            ..
+        } => {
+            let (left_iter, left_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                left,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let left_iter = left_iter?;
+            let (right_iter, right_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                right,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let right_iter = right_iter?;
 
-            // Code below looks ugly, but this is to avoid ownership issues
-            let mut child_filter = Box::new(Filter::new(
-                convert_expr_to_bytecode(predicates[0].clone(), Some(&new_col_id_to_idx)).unwrap(),
-                new_schema.clone(),
-                join_op,
-            ));
-            for pred in predicates.iter().skip(1) {
-                let filter = Filter::new(
-                    convert_expr_to_bytecode(pred.clone(), Some(&new_col_id_to_idx)).unwrap(),
-                    new_schema.clone(),
-                    child_filter,
-                );
-                child_filter = Box::new(filter);
+            let left_schema = left_iter.get_schema();
+            let right_schema = right_iter.get_schema();
+            let new_schema = join_schema(join_type, left_schema, right_schema);
+
+            let mut new_col_id_to_idx = left_col_id_to_idx.clone();
+            for (old_id, offset) in right_col_id_to_idx.iter() {
+                new_col_id_to_idx.insert(*old_id, offset + left_schema.size());
             }
-            (Ok(child_filter), new_col_id_to_idx)
+
+            let keys = match predicates.as_slice() {
+                [pred] => split_join_keys(pred, &left_col_id_to_idx, &right_col_id_to_idx),
+                _ => None,
+            };
+            let join: Box<dyn OpIterator> = match keys {
+                Some((join_op, left_col, right_col)) => {
+                    let mut join = NestedLoopJoin::new(
+                        join_op,
+                        convert_expr_to_bytecode(left_col, Some(&left_col_id_to_idx))?,
+                        convert_expr_to_bytecode(right_col, Some(&right_col_id_to_idx))?,
+                        left_iter,
+                        right_iter,
+                        new_schema,
+                    )
+                    .with_join_type(*join_type);
+                    if join_op == BinaryOp::Eq && is_inner(join_type) {
+                        if let Some(policy) = adaptive_policy(managers, right, left) {
+                            runtime
+                                .policies
+                                .insert(node_key(physical_plan), policy.clone());
+                            join = join.with_adaptation(policy);
+                        }
+                    }
+                    Box::new(join)
+                }
+                // Predicates that do not split into one key per input are
+                // evaluated over the joined tuple instead, by the join itself
+                // if it pads unmatched tuples.
+                None if !is_inner(join_type) => {
+                    let residual = predicates
+                        .iter()
+                        .map(|pred| {
+                            convert_expr_to_bytecode(pred.clone(), Some(&new_col_id_to_idx))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Box::new(
+                        NestedLoopJoin::without_key(left_iter, right_iter, new_schema)
+                            .with_residual(residual)
+                            .with_join_type(*join_type),
+                    )
+                }
+                None => filtered_cross_join(
+                    new_schema,
+                    left_iter,
+                    right_iter,
+                    predicates,
+                    &new_col_id_to_idx,
+                )?,
+            };
+            let col_map = join_col_map(join_type, left_col_id_to_idx, new_col_id_to_idx);
+            Ok((join, col_map))
         }
 
-        PhysicalRelExpr::NestedLoopJoin {
+        PhysicalRelExpr::HashJoin {
             join_type,
             left,
             right,
             predicates,
             ..
         } => {
-            debug_assert_eq!(join_type, &JoinType::Inner);
-            let (left_iter, left_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, left, tid, _timestamp);
-            let (right_iter, right_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, right, tid, _timestamp);
-
-            let left_schema = left_iter.as_ref().unwrap().get_schema();
-            let right_schema = right_iter.as_ref().unwrap().get_schema();
-            let new_schema = left_schema.merge(right_schema);
+            let (left_iter, left_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                left,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let left_iter = left_iter?;
+            let (right_iter, right_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                right,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let right_iter = right_iter?;
+
+            let left_schema = left_iter.get_schema();
+            let right_schema = right_iter.get_schema();
+            let new_schema = join_schema(join_type, left_schema, right_schema);
 
             let mut new_col_id_to_idx = left_col_id_to_idx.clone();
             for (old_id, offset) in right_col_id_to_idx.iter() {
                 new_col_id_to_idx.insert(*old_id, offset + left_schema.size());
             }
 
-            debug_assert_eq!(predicates.len(), 1);
-
-            let (join_op, left_expr, right_expr) = match &predicates[0] {
-                Expression::Binary {
-                    op,
-                    left: left_expr,
-                    right: right_expr,
-                } => (*op, left_expr, right_expr),
-                _ => {
-                    panic!("Expected binary expression in nested loop join");
+            // Equalities with one side per input are the keys, the other
+            // predicates are evaluated over the joined tuples.
+            let mut left_keys = Vec::new();
+            let mut right_keys = Vec::new();
+            let mut residual = Vec::new();
+            let mut first_key = None;
+            for (i, pred) in predicates.iter().enumerate() {
+                match split_join_keys(pred, &left_col_id_to_idx, &right_col_id_to_idx) {
+                    Some((BinaryOp::Eq, left_col, right_col)) => {
+                        first_key.get_or_insert(i);
+                        left_keys.push(convert_expr_to_bytecode(
+                            left_col,
+                            Some(&left_col_id_to_idx),
+                        )?);
+                        right_keys.push(convert_expr_to_bytecode(
+                            right_col,
+                            Some(&right_col_id_to_idx),
+                        )?);
+                    }
+                    _ => residual.push(convert_expr_to_bytecode(
+                        pred.clone(),
+                        Some(&new_col_id_to_idx),
+                    )?),
+                }
+            }
+            let Some(first_key) = first_key else {
+                if !is_inner(join_type) {
+                    let join = NestedLoopJoin::without_key(left_iter, right_iter, new_schema)
+                        .with_residual(residual)
+                        .with_join_type(*join_type);
+                    let col_map = join_col_map(join_type, left_col_id_to_idx, new_col_id_to_idx);
+                    return Ok((Box::new(join), col_map));
                 }
+                let join = filtered_cross_join(
+                    new_schema,
+                    left_iter,
+                    right_iter,
+                    predicates,
+                    &new_col_id_to_idx,
+                )?;
+                return Ok((join, new_col_id_to_idx));
             };
 
-            let (left_col, right_col) = if left_expr.intersect_with(left) {
-                (left_expr, right_expr)
+            // Outer, semi and anti joins run on a single thread.
+            let parallel = managers.config.join_workers > 1 && is_inner(join_type);
+            let join: Box<dyn OpIterator> = if parallel {
+                // The parallel join has a single key, the other predicates are
+                // evaluated over its output.
+                let mut join: Box<dyn OpIterator> = Box::new(ParallelHashEqJoin::new(
+                    new_schema.clone(),
+                    left_keys.swap_remove(0),
+                    right_keys.swap_remove(0),
+                    left_iter,
+                    right_iter,
+                    managers.config.join_workers,
+                    managers.config.hash_join_distribution,
+                ));
+                for (i, pred) in predicates.iter().enumerate() {
+                    if i != first_key {
+                        let expr =
+                            convert_expr_to_bytecode(pred.clone(), Some(&new_col_id_to_idx))?;
+                        join = Box::new(Filter::new(expr, new_schema.clone(), join));
+                    }
+                }
+                join
             } else {
-                (right_expr, left_expr)
+                let mut join = HashEqJoin::new(
+                    managers, new_schema, left_keys, right_keys, left_iter, right_iter,
+                )
+                .with_residual(residual)
+                .with_join_type(*join_type)
+                .with_cancellation(runtime.cancellation.clone());
+                // The table is built over the input expected to be smaller.
+                // Semi and anti joins always build over their left input.
+                let stats = StatsView::new(managers.stats);
+                match (stats.estimate_rows(left), stats.estimate_rows(right)) {
+                    _ if join_type.outputs_left_only() => {}
+                    (Some(l), Some(r)) if r < l => join = join.with_build_right(),
+                    _ => {
+                        if let Some(policy) = adaptive_policy(managers, left, right) {
+                            runtime
+                                .policies
+                                .insert(node_key(physical_plan), policy.clone());
+                            join = join.with_adaptation(policy);
+                        }
+                    }
+                }
+                Box::new(join)
             };
-
-            let join = Box::new(NestedLoopJoin::new(
-                join_op,
-                convert_expr_to_bytecode(*left_col.clone(), Some(&left_col_id_to_idx)).unwrap(),
-                convert_expr_to_bytecode(*right_col.clone(), Some(&right_col_id_to_idx)).unwrap(),
-                left_iter.unwrap(),
-                right_iter.unwrap(),
-                new_schema,
-            ));
-            (Ok(join), new_col_id_to_idx)
+            let col_map = join_col_map(join_type, left_col_id_to_idx, new_col_id_to_idx);
+            Ok((join, col_map))
         }
 
-        PhysicalRelExpr::HashJoin {
+        PhysicalRelExpr::SortMergeJoin {
             join_type,
             left,
             right,
             predicates,
             ..
         } => {
-            debug_assert_eq!(join_type, &JoinType::Inner);
-            let (left_iter, left_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, left, tid, _timestamp);
-            let (right_iter, right_col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, right, tid, _timestamp);
-
-            let left_schema = left_iter.as_ref().unwrap().get_schema();
-            let right_schema = right_iter.as_ref().unwrap().get_schema();
+            check_inner_join(join_type)?;
+            let (left_iter, left_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                left,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let left_iter = left_iter?;
+            let (right_iter, right_col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                right,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let right_iter = right_iter?;
+
+            let left_schema = left_iter.get_schema();
+            let right_schema = right_iter.get_schema();
             let new_schema = left_schema.merge(right_schema);
 
             let mut new_col_id_to_idx = left_col_id_to_idx.clone();
@@ -396,34 +2163,67 @@ fn physical_plan_to_op_iterator_helper(
                 new_col_id_to_idx.insert(*old_id, offset + left_schema.size());
             }
 
-            debug_assert_eq!(predicates.len(), 1);
-
-            let (left_expr, right_expr) = match &predicates[0] {
-                Expression::Binary {
-                    op: BinaryOp::Eq,
-                    left: left_expr,
-                    right: right_expr,
-                } => (left_expr, right_expr),
-                _ => {
-                    panic!("Expected binary eq expression in hash eq join");
+            // Equalities with one side per input are merged on, the other
+            // predicates filter the merged tuples.
+            let mut left_keys = Vec::new();
+            let mut right_keys = Vec::new();
+            let mut residual = Vec::new();
+            for pred in predicates {
+                match split_join_keys(pred, &left_col_id_to_idx, &right_col_id_to_idx) {
+                    Some((BinaryOp::Eq, left_col, right_col)) => {
+                        left_keys.push(left_col);
+                        right_keys.push(right_col);
+                    }
+                    _ => residual.push(pred.clone()),
                 }
-            };
+            }
+            if left_keys.is_empty() {
+                let join = filtered_cross_join(
+                    new_schema,
+                    left_iter,
+                    right_iter,
+                    predicates,
+                    &new_col_id_to_idx,
+                )?;
+                return Ok((join, new_col_id_to_idx));
+            }
 
-            let (left_col, right_col) = if left_expr.intersect_with(left) {
-                (left_expr, right_expr)
-            } else {
-                (right_expr, left_expr)
+            let left_iter = sort_join_input(
+                managers,
+                tid,
+                left,
+                &left_keys,
+                left_iter,
+                &left_col_id_to_idx,
+                &runtime.cancellation,
+            )?;
+            let right_iter = sort_join_input(
+                managers,
+                tid,
+                right,
+                &right_keys,
+                right_iter,
+                &right_col_id_to_idx,
+                &runtime.cancellation,
+            )?;
+            let ascending = |keys: Vec<Expression<PhysicalRelExpr>>, col_id_to_idx| {
+                keys.into_iter()
+                    .map(|key| Ok((convert_expr_to_bytecode(key, Some(col_id_to_idx))?, true)))
+                    .collect::<Result<Vec<_>, FairyError>>()
             };
-
-            let join = Box::new(HashEqJoin::new(
+            let mut join: Box<dyn OpIterator> = Box::new(SortMergeJoin::new(
                 managers,
-                new_schema,
-                convert_expr_to_bytecode(*left_col.clone(), Some(&left_col_id_to_idx)).unwrap(),
-                convert_expr_to_bytecode(*right_col.clone(), Some(&right_col_id_to_idx)).unwrap(),
-                left_iter.unwrap(),
-                right_iter.unwrap(),
-            ));
-            (Ok(join), new_col_id_to_idx)
+                new_schema.clone(),
+                ascending(left_keys, &left_col_id_to_idx)?,
+                ascending(right_keys, &right_col_id_to_idx)?,
+                left_iter,
+                right_iter,
+            )?);
+            for pred in residual {
+                let expr = convert_expr_to_bytecode(pred, Some(&new_col_id_to_idx))?;
+                join = Box::new(Filter::new(expr, new_schema.clone(), join));
+            }
+            Ok((join, new_col_id_to_idx))
         }
 
         PhysicalRelExpr::HashAggregate {
@@ -431,23 +2231,38 @@ fn physical_plan_to_op_iterator_helper(
             group_by,
             aggrs,
             ..
+        }
+        | PhysicalRelExpr::StreamAggregate {
+            src,
+            group_by,
+            aggrs,
+            ..
         } => {
-            let (src_iter, col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, src, tid, _timestamp);
-            let in_schema = src_iter.as_ref().unwrap().get_schema();
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                src,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+            let in_schema = src_iter.get_schema();
 
             let mut out_schema_att = Vec::new();
 
             // First project the group by columns
             for id in group_by {
-                let offset = col_id_to_idx.get(id).unwrap();
-                out_schema_att.push(in_schema.get_attribute(*offset).unwrap().clone());
+                let offset = column_index(&col_id_to_idx, *id)?;
+                out_schema_att.push(in_schema.get_attribute(offset).unwrap().clone());
             }
 
             // Then project the aggregate columns
             for (_, (id, op)) in aggrs {
-                let offset = col_id_to_idx.get(id).unwrap();
-                let src_att = in_schema.get_attribute(*offset).unwrap();
+                let offset = column_index(&col_id_to_idx, *id)?;
+                let src_att = in_schema.get_attribute(offset).unwrap();
                 out_schema_att.push(op.to_attr(src_att));
             }
             let out_schema = TableSchema::new(out_schema_att);
@@ -456,70 +2271,455 @@ fn physical_plan_to_op_iterator_helper(
             let mut new_col_id_to_idx = HashMap::new();
 
             for (i, id) in group_by.iter().enumerate() {
-                group_by_exprs.push(
-                    convert_expr_to_bytecode(
-                        Expression::<PhysicalRelExpr>::ColRef { id: *id },
-                        Some(&col_id_to_idx),
-                    )
-                    .unwrap(),
-                );
+                group_by_exprs.push(convert_expr_to_bytecode(
+                    Expression::<PhysicalRelExpr>::ColRef { id: *id },
+                    Some(&col_id_to_idx),
+                )?);
                 new_col_id_to_idx.insert(*id, i as ColumnId);
             }
 
             let mut aggr_exprs = Vec::new();
             let mut ops = Vec::new();
             for (i, (dest_id, (src_id, op))) in aggrs.iter().enumerate() {
-                aggr_exprs.push(
-                    convert_expr_to_bytecode(
-                        Expression::<PhysicalRelExpr>::ColRef { id: *src_id },
-                        Some(&col_id_to_idx),
-                    )
-                    .unwrap(),
-                );
+                aggr_exprs.push(convert_expr_to_bytecode(
+                    Expression::<PhysicalRelExpr>::ColRef { id: *src_id },
+                    Some(&col_id_to_idx),
+                )?);
                 ops.push(*op);
                 new_col_id_to_idx.insert(*dest_id, i as ColumnId + group_by.len());
             }
 
-            let agg_iter = Aggregate::new(
-                managers,
-                group_by_exprs,
-                aggr_exprs,
-                ops,
-                out_schema,
-                src_iter.unwrap(),
-            );
-            (Ok(Box::new(agg_iter)), new_col_id_to_idx)
+            let agg_iter: Box<dyn OpIterator> = match physical_plan {
+                PhysicalRelExpr::StreamAggregate { .. } => Box::new(StreamAggregate::new(
+                    group_by_exprs,
+                    aggr_exprs,
+                    ops,
+                    out_schema,
+                    src_iter,
+                )),
+                _ => Box::new(Aggregate::new(
+                    managers,
+                    group_by_exprs,
+                    aggr_exprs,
+                    ops,
+                    out_schema,
+                    src_iter,
+                )),
+            };
+            Ok((agg_iter, new_col_id_to_idx))
         }
 
         PhysicalRelExpr::Map { input, exprs, .. } => {
-            let (src_iter, col_id_to_idx) =
-                physical_plan_to_op_iterator_helper(managers, catalog, input, tid, _timestamp);
-            let in_schema = src_iter.as_ref().unwrap().get_schema();
+            let (src_iter, col_id_to_idx) = physical_plan_to_op_iterator_helper(
+                managers,
+                catalog,
+                input,
+                tid,
+                _timestamp,
+                shared_scans,
+                schemas,
+                runtime,
+            );
+            let src_iter = src_iter?;
+            let in_schema = src_iter.get_schema();
 
             // Projecting all the columns
             let mut out_schema_att = in_schema.attributes.clone();
             let mut fields = (0..in_schema.size())
                 .map(|i| Expression::<PhysicalRelExpr>::ColRef { id: i })
                 .map(|e| convert_expr_to_bytecode(e, None))
-                .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()
-                .unwrap();
+                .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()?;
             let mut new_col_id_to_idx = col_id_to_idx.clone();
 
             // Projecting the additional new columns (generated by the map expressions)
             for (i, (id, expr)) in exprs.iter().enumerate() {
-                let bytecode_expr =
-                    convert_expr_to_bytecode(expr.clone(), Some(&col_id_to_idx)).unwrap();
+                let bytecode_expr = convert_expr_to_bytecode(expr.clone(), Some(&col_id_to_idx))?;
                 fields.push(bytecode_expr);
 
-                let new_att = expr.to_attr(in_schema, &col_id_to_idx);
+                let mut new_att = expr.to_attr(in_schema, &col_id_to_idx);
+                if let Some(schema) = schemas.get(&node_key(physical_plan)) {
+                    new_att.dtype = schema[in_schema.size() + i].dtype.clone();
+                }
                 out_schema_att.push(new_att);
 
                 new_col_id_to_idx.insert(*id, i as ColumnId + in_schema.size());
             }
             let out_schema = TableSchema::new(out_schema_att);
-            let project_iter = Project::new(fields, out_schema, src_iter.unwrap());
-            (Ok(Box::new(project_iter)), new_col_id_to_idx)
+            let project_iter = Project::new(fields, out_schema, src_iter);
+            Ok((Box::new(project_iter), new_col_id_to_idx))
+        }
+        _ => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::{execute_iter, TestSetup, TestTuples};
+    use common::{
+        ids::ValueId,
+        traits::{stat_manager_trait::StatManagerTrait, storage_trait::StorageTrait},
+        Field, Tuple,
+    };
+
+    /// `SELECT * FROM table0 WHERE col1 = 2`
+    fn filtered_scan(setup: &TestSetup) -> PhysicalRelExpr {
+        let cid = setup.catalog.get_table_id("table0").unwrap();
+        PhysicalRelExpr::Select {
+            src: Box::new(PhysicalRelExpr::Scan {
+                cid,
+                table_name: "table0".to_string(),
+                column_names: vec![0, 1, 2, 3],
+                tree_hash: None,
+            }),
+            predicates: vec![Expression::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(Expression::ColRef { id: 1 }),
+                right: Box::new(Expression::Field {
+                    val: Field::BigInt(2),
+                }),
+            }],
+            tree_hash: None,
         }
-        _ => (Err(err), HashMap::new()),
+    }
+
+    fn run(
+        setup: &TestSetup,
+        mut iter: Box<dyn OpIterator>,
+    ) -> Result<(Vec<Tuple>, usize), FairyError> {
+        let before = setup.managers.sm.mem_stats().bp_read_frame;
+        iter.configure(false);
+        let tuples = execute_iter(iter.as_mut(), true)?;
+        iter.close()?;
+        let reads = setup.managers.sm.mem_stats().bp_read_frame - before;
+        Ok((tuples, reads))
+    }
+
+    #[test]
+    fn test_case_expression_bytecode() {
+        type Expr = Expression<PhysicalRelExpr>;
+        let lit = |val| Expr::Field { val };
+        let gt = |id, val| Expr::binary(BinaryOp::Gt, Expr::col_ref(id), Expr::int(val));
+        let eval = |expr: &Expr, a: Field, b: Field| {
+            convert_expr_to_bytecode(expr.clone(), None)
+                .unwrap()
+                .try_eval(&Tuple::new(vec![a, b]))
+        };
+        let string = |s: &str| Field::String(s.into());
+
+        // CASE WHEN a > 8 THEN 'huge' WHEN a > 5 THEN 'big' ELSE 'small' END
+        let searched = Expr::case(
+            None,
+            vec![
+                (gt(0, 8), lit(string("huge"))),
+                (gt(0, 5), lit(string("big"))),
+            ],
+            lit(string("small")),
+        );
+        for (a, expected) in [(9, "huge"), (6, "big"), (1, "small")] {
+            assert_eq!(
+                eval(&searched, Field::BigInt(a), Field::Null),
+                Ok(string(expected))
+            );
+        }
+        // A NULL condition is not true.
+        assert_eq!(
+            eval(&searched, Field::Null, Field::Null),
+            Ok(string("small"))
+        );
+
+        // CASE a WHEN 1 THEN 'small' WHEN NULL THEN 'big' END
+        let simple = Expr::case(
+            Some(Expr::col_ref(0)),
+            vec![
+                (Expr::int(1), lit(string("small"))),
+                (lit(Field::Null), lit(string("big"))),
+            ],
+            lit(Field::Null),
+        );
+        assert_eq!(
+            eval(&simple, Field::BigInt(1), Field::Null),
+            Ok(string("small"))
+        );
+        assert_eq!(
+            eval(&simple, Field::BigInt(2), Field::Null),
+            Ok(Field::Null)
+        );
+        assert_eq!(eval(&simple, Field::Null, Field::Null), Ok(Field::Null));
+
+        // CASE WHEN b > 0 THEN a / b ELSE 0 END never divides by zero.
+        let guarded = Expr::case(
+            None,
+            vec![(
+                gt(1, 0),
+                Expr::binary(BinaryOp::Div, Expr::col_ref(0), Expr::col_ref(1)),
+            )],
+            Expr::int(0),
+        );
+        assert_eq!(
+            eval(&guarded, Field::BigInt(6), Field::BigInt(3)),
+            Ok(Field::BigInt(2))
+        );
+        assert_eq!(
+            eval(&guarded, Field::BigInt(6), Field::BigInt(0)),
+            Ok(Field::BigInt(0))
+        );
+    }
+
+    #[test]
+    fn test_ill_typed_map_rejected_at_plan_time() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        let plan = PhysicalRelExpr::Map {
+            input: Box::new(filtered_scan(&setup)),
+            exprs: vec![(
+                10,
+                Expression::Field {
+                    val: Field::Date(0),
+                }
+                .add(Expression::Field {
+                    val: Field::Bool(true),
+                }),
+            )],
+            tree_hash: None,
+        };
+        let err = physical_plan_to_op_iterator(setup.managers, &setup.catalog, &plan, tid, 0)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            FairyError::ValidationError("Cannot apply + to date and bool".to_string())
+        );
+    }
+
+    #[test]
+    fn test_map_output_uses_derived_type() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        let plan = PhysicalRelExpr::Map {
+            input: Box::new(filtered_scan(&setup)),
+            exprs: vec![(
+                10,
+                Expression::col_ref(0).add(Expression::Field {
+                    val: Field::Decimal(15, 1),
+                }),
+            )],
+            tree_hash: None,
+        };
+        let (iter, schema) =
+            physical_plan_to_op_iterator_with_schema(setup.managers, &setup.catalog, &plan, tid, 0)
+                .unwrap();
+        assert!(matches!(schema[4].dtype, common::DataType::Decimal(_, 1)));
+        assert!(matches!(
+            iter.get_schema().get_attribute(4).unwrap().dtype,
+            common::DataType::Decimal(_, 1)
+        ));
+        let (tuples, _) = run(&setup, iter).unwrap();
+        assert_eq!(tuples.len(), 3);
+    }
+
+    #[test]
+    fn test_shared_filtered_scan_is_spooled() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        let plan = PhysicalRelExpr::CrossJoin {
+            join_type: JoinType::CrossJoin,
+            left: Box::new(filtered_scan(&setup)),
+            right: Box::new(filtered_scan(&setup)),
+            predicates: vec![],
+            tree_hash: None,
+        };
+        let shared =
+            physical_plan_to_op_iterator(setup.managers, &setup.catalog, &plan, tid, 0).unwrap();
+        let (shared_tuples, shared_reads) = run(&setup, shared).unwrap();
+
+        // Unshared execution: each side is planned on its own.
+        let build = |p: &PhysicalRelExpr| {
+            physical_plan_to_op_iterator(setup.managers, &setup.catalog, p, tid, 0).unwrap()
+        };
+        let left = build(&filtered_scan(&setup));
+        let right = build(&filtered_scan(&setup));
+        let schema = left.get_schema().merge(right.get_schema());
+        let unshared = Box::new(CrossJoin::new(schema, left, right));
+        let (unshared_tuples, unshared_reads) = run(&setup, unshared).unwrap();
+
+        let (_, single_scan_reads) = run(&setup, build(&filtered_scan(&setup))).unwrap();
+
+        assert_eq!(shared_tuples.len(), 9);
+        assert_eq!(shared_tuples, unshared_tuples);
+        // The base table is scanned exactly once, even though the cross join
+        // rewinds its right side for every left tuple.
+        assert!(single_scan_reads > 0);
+        assert_eq!(shared_reads, single_scan_reads);
+        assert!(unshared_reads > shared_reads);
+    }
+
+    #[test]
+    fn test_comparisons_pushed_into_scan() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        let cid = setup.catalog.get_table_id("table0").unwrap();
+        let scan = PhysicalRelExpr::Scan {
+            cid,
+            table_name: "table0".to_string(),
+            column_names: vec![0, 1, 2, 3],
+            tree_hash: None,
+        };
+        let cmp = |op, left, right| Expression::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        let col = |id| Expression::ColRef { id };
+        let lit = |val| Expression::Field { val };
+        let predicates = vec![
+            // Flipped to col2 < 4.
+            cmp(BinaryOp::Gt, lit(Field::BigInt(4)), col(2)),
+            // Compares two columns.
+            cmp(BinaryOp::Neq, col(0), col(2)),
+            // Strings are not fixed-width.
+            cmp(BinaryOp::Eq, col(3), lit(Field::String("G".to_string()))),
+        ];
+        let (pushed, remaining) = push_into_scan(&setup.catalog, &scan, &predicates);
+        assert_eq!(pushed.map(|(c, _)| c), Some(cid));
+        assert_eq!(remaining.len(), 2);
+
+        let plan = PhysicalRelExpr::Select {
+            src: Box::new(scan),
+            predicates,
+            tree_hash: None,
+        };
+        let iter =
+            physical_plan_to_op_iterator(setup.managers, &setup.catalog, &plan, tid, 0).unwrap();
+        let (tuples, _) = run(&setup, iter).unwrap();
+        assert_eq!(
+            tuples,
+            vec![Tuple::new(vec![
+                Field::BigInt(2),
+                Field::BigInt(1),
+                Field::BigInt(3),
+                Field::String("G".to_string()),
+            ])]
+        );
+    }
+
+    /// Appends `copies` copies of the test tuples to `table` and feeds every
+    /// tuple of the table to the stats manager.
+    fn grow_table(setup: &TestSetup, table: &str, copies: usize) {
+        let cid = setup.catalog.get_table_id(table).unwrap();
+        let tuples = TestTuples::new(table).tuples;
+        for _ in 0..copies {
+            let values = tuples.iter().map(|t| t.to_bytes()).collect();
+            setup
+                .managers
+                .sm
+                .insert_values(cid, values, TransactionId::new());
+        }
+        for tuple in tuples.iter().cycle().take(tuples.len() * (copies + 1)) {
+            setup
+                .managers
+                .stats
+                .new_record(tuple, ValueId::new(cid))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_hash_join_adapts_to_bad_estimate() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        grow_table(&setup, "table0", 9);
+        grow_table(&setup, "table1", 0);
+        let scan = |table: &str, first_col: ColumnId| PhysicalRelExpr::Scan {
+            cid: setup.catalog.get_table_id(table).unwrap(),
+            table_name: table.to_string(),
+            column_names: (first_col..first_col + 4).collect(),
+            tree_hash: None,
+        };
+        let plan = PhysicalRelExpr::HashJoin {
+            join_type: JoinType::Inner,
+            left: Box::new(scan("table0", 0)),
+            right: Box::new(scan("table1", 100)),
+            predicates: vec![Expression::col_ref(1).eq(Expression::col_ref(101))],
+            tree_hash: None,
+        };
+
+        let (iter, _) =
+            physical_plan_to_op_iterator_with_schema(setup.managers, &setup.catalog, &plan, tid, 0)
+                .unwrap();
+        let (expected, _) = run(&setup, iter).unwrap();
+
+        // Wrong statistics claim the 60 row build side holds a single row.
+        setup
+            .managers
+            .stats
+            .import_record_count(setup.catalog.get_table_id("table0").unwrap(), 1)
+            .unwrap();
+        let (iter, _, analyzed) = physical_plan_to_analyzed_op_iterator(
+            setup.managers,
+            &setup.catalog,
+            &plan,
+            tid,
+            0,
+            CorruptRecordMode::Strict,
+            &Cancellation::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(analyzed.adaptations(), vec![None]);
+        let (tuples, _) = run(&setup, iter).unwrap();
+        assert_eq!(tuples, expected);
+        assert_eq!(tuples.len(), 180);
+        assert_eq!(
+            analyzed.adaptations(),
+            vec![Some(Adaptation::SwappedBuildSide)]
+        );
+        assert!(analyzed
+            .pretty_string()
+            .contains("(adapted: swapped build side)"));
+    }
+
+    #[test]
+    fn test_cancelled_query_fails() {
+        let setup = TestSetup::new_with_content();
+        let tid = TransactionId::new();
+        let scan = |table: &str, first_col: ColumnId| PhysicalRelExpr::Scan {
+            cid: setup.catalog.get_table_id(table).unwrap(),
+            table_name: table.to_string(),
+            column_names: (first_col..first_col + 4).collect(),
+            tree_hash: None,
+        };
+        let plan = PhysicalRelExpr::Sort {
+            src: Box::new(PhysicalRelExpr::HashJoin {
+                join_type: JoinType::Inner,
+                left: Box::new(scan("table0", 0)),
+                right: Box::new(scan("table1", 100)),
+                predicates: vec![Expression::col_ref(1).eq(Expression::col_ref(101))],
+                tree_hash: None,
+            }),
+            cols: vec![(0, true, false)],
+            tree_hash: None,
+        };
+        let cancellation = Cancellation::new();
+        let build = || {
+            physical_plan_to_analyzed_op_iterator(
+                setup.managers,
+                &setup.catalog,
+                &plan,
+                tid,
+                0,
+                CorruptRecordMode::Strict,
+                &cancellation,
+                false,
+            )
+            .map(|(iter, _, _)| iter)
+            .unwrap()
+        };
+        assert!(run(&setup, build()).is_ok());
+        cancellation.cancel();
+        assert!(matches!(
+            run(&setup, build()),
+            Err(FairyError::QueryCancelled)
+        ));
     }
 }
@@ -1,7 +1,7 @@
 // Reference: https://github.com/rotaki/decorrelator
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
 };
 
@@ -11,16 +11,19 @@ use common::{
 };
 use common::{
     catalog::CatalogRef,
-    datatypes::{default_decimal_precision, default_decimal_scale},
+    datatypes::{default_decimal_precision, default_decimal_scale, MICROS_PER_DAY},
     ids::ColumnId,
     logical_expr::prelude::{Expression, JoinType},
     physical::col_id_generator::ColIdGeneratorRef,
+    physical_expr::plan_schema::{common_dtype, field_matches_dtype, PlanSchema},
     traits::plan::Plan,
-    AggOp, BinaryOp,
+    AggOp, BinaryOp, ScalarFunc,
 };
+use common::query::like::{with_escape, LIKE_ESCAPE};
 use common::{logical_expr::prelude::LogicalRelExpr, Field};
+use common::util::suggest::closest_match;
 use common::{FairyError, DataType};
-use sqlparser::ast::{self, ExactNumberInfo};
+use sqlparser::ast::{self, ExactNumberInfo, TimezoneInfo};
 
 /// Retrieve the name from the command parser object.
 ///
@@ -45,10 +48,23 @@ pub fn get_name(name: &ast::ObjectName) -> Result<String, FairyError> {
 /// * `dtype` - Name object from the command parser.
 pub fn get_attr(dtype: &ast::DataType) -> Result<DataType, FairyError> {
     match dtype {
-        ast::DataType::Int(_) | ast::DataType::Integer(_) => Ok(DataType::BigInt),
+        ast::DataType::Int(_) | ast::DataType::Integer(_) | ast::DataType::BigInt(_) => {
+            Ok(DataType::BigInt)
+        }
         ast::DataType::Varchar(_) => Ok(DataType::String),
         ast::DataType::Char(_) => Ok(DataType::String),
         ast::DataType::Date => Ok(DataType::Date),
+        ast::DataType::Timestamp(_, TimezoneInfo::None | TimezoneInfo::WithoutTimeZone) => {
+            Ok(DataType::Timestamp)
+        }
+        ast::DataType::Bool | ast::DataType::Boolean => Ok(DataType::Bool),
+        ast::DataType::Float(_)
+        | ast::DataType::Float4
+        | ast::DataType::Float8
+        | ast::DataType::Float64
+        | ast::DataType::Real
+        | ast::DataType::Double
+        | ast::DataType::DoublePrecision => Ok(DataType::Float64),
         ast::DataType::Decimal(exact_num_info) => match exact_num_info {
             ExactNumberInfo::PrecisionAndScale(p, s) => Ok(DataType::Decimal(*p as u32, *s as u32)),
             ExactNumberInfo::Precision(p) => {
@@ -66,8 +82,53 @@ pub fn get_attr(dtype: &ast::DataType) -> Result<DataType, FairyError> {
     }
 }
 
+/// Converts the DEFAULT clause of a column of type `dtype` to its value, which
+/// must be a literal of that type.
+///
+/// # Argument
+///
+/// * `expr` - Expression of the DEFAULT clause.
+/// * `name` - Name of the column.
+/// * `dtype` - Dtype of the column.
+pub fn get_default(expr: &ast::Expr, name: &str, dtype: &DataType) -> Result<Field, FairyError> {
+    let field = match crate::mutator::convert_literal(expr) {
+        Ok(Ok(field)) => field.coerce_to(dtype).ok(),
+        _ => None,
+    };
+    match field {
+        Some(field) if field_matches_dtype(&field, dtype) => Ok(field),
+        _ => Err(FairyError::ValidationError(format!(
+            "Default {} of column {} is not a value of type {}",
+            expr, name, dtype
+        ))),
+    }
+}
+
+/// Converts a value given to `EXECUTE` for a placeholder, which must be a
+/// literal or a negative number.
+pub fn get_parameter_value(expr: &ast::Expr) -> Result<Field, FairyError> {
+    let not_a_value = || FairyError::ValidationError(format!("Parameter {} is not a value", expr));
+    match expr {
+        ast::Expr::UnaryOp {
+            op: ast::UnaryOperator::Minus,
+            expr: number,
+        } => match get_parameter_value(number)? {
+            Field::BigInt(i) => Ok(Field::BigInt(-i)),
+            Field::Decimal(w, s) => Ok(Field::Decimal(-w, s)),
+            _ => Err(not_a_value()),
+        },
+        expr => match crate::mutator::convert_literal(expr) {
+            Ok(Ok(field)) => Ok(field),
+            _ => Err(not_a_value()),
+        },
+    }
+}
+
 pub type EnvironmentRef = Arc<Environment>;
 
+/// Names and ids of the columns of a table of the FROM clause.
+type TableColumns = Vec<(String, ColumnId)>;
+
 #[derive(Debug, Clone)]
 pub struct Environment {
     outer: Option<EnvironmentRef>,
@@ -77,6 +138,14 @@ pub struct Environment {
     /// original column index and container id
     id_to_origin: Arc<RwLock<HashMap<ColumnId, OriginExpression>>>,
 
+    /// Maps a table name or alias of the FROM clause to the names and ids of
+    /// its columns, in catalog order. Used to expand `table.*`.
+    tables: Arc<RwLock<HashMap<String, TableColumns>>>,
+
+    /// Columns compared by USING and NATURAL joins of the FROM clause: the one
+    /// the unqualified name refers to and the other one, which `*` leaves out.
+    join_columns: Arc<RwLock<Vec<(ColumnId, ColumnId)>>>,
+
     /// Catalog: used to find where the column is coming from
     catalog: CatalogRef,
 }
@@ -87,6 +156,8 @@ impl Environment {
             outer: None,
             columns: Arc::new(RwLock::new(HashMap::new())),
             id_to_origin: Arc::new(RwLock::new(HashMap::new())),
+            tables: Arc::new(RwLock::new(HashMap::new())),
+            join_columns: Arc::new(RwLock::new(Vec::new())),
             catalog,
         }
     }
@@ -96,6 +167,8 @@ impl Environment {
             outer: Some(outer),
             columns: Arc::new(RwLock::new(HashMap::new())),
             id_to_origin: Arc::new(RwLock::new(HashMap::new())),
+            tables: Arc::new(RwLock::new(HashMap::new())),
+            join_columns: Arc::new(RwLock::new(Vec::new())),
             catalog,
         }
     }
@@ -128,6 +201,26 @@ impl Environment {
         None
     }
 
+    /// Returns the name in scope, here or in an outer scope, that is closest
+    /// to `name` if one is close enough to be what was meant.
+    fn closest_name(&self, name: &str) -> Option<String> {
+        let mut names: Vec<String> = self.columns.read().unwrap().keys().cloned().collect();
+        let mut outer = self.outer.clone();
+        while let Some(env) = outer {
+            names.extend(env.columns.read().unwrap().keys().cloned());
+            outer = env.outer.clone();
+        }
+        // Qualified names are only suggested for qualified references.
+        let qualified = name.contains('.');
+        closest_match(
+            name,
+            names
+                .iter()
+                .map(String::as_str)
+                .filter(|n| n.contains('.') == qualified),
+        )
+    }
+
     fn set(&self, name: &str, index: usize) {
         self.columns
             .write()
@@ -135,10 +228,52 @@ impl Environment {
             .insert(name.to_string(), index);
     }
 
+    /// Points the names of column `old` at column `new`.
+    fn rename_id(&self, old: ColumnId, new: ColumnId) {
+        for index in self.columns.write().unwrap().values_mut() {
+            if *index == old {
+                *index = new;
+            }
+        }
+    }
+
+    fn set_table(&self, name: &str, cols: TableColumns) {
+        self.tables.write().unwrap().insert(name.to_string(), cols);
+    }
+
+    /// Columns of the table or alias `name` of the current FROM clause.
+    fn get_table(&self, name: &str) -> Option<TableColumns> {
+        self.tables.read().unwrap().get(name).cloned()
+    }
+
+    fn add_join_column(&self, kept: ColumnId, dropped: ColumnId) {
+        self.join_columns.write().unwrap().push((kept, dropped));
+    }
+
+    fn join_columns(&self) -> Vec<(ColumnId, ColumnId)> {
+        self.join_columns.read().unwrap().clone()
+    }
+
     fn add_to_origin_map(&self, col_id: ColumnId, origin: OriginExpression) {
         self.id_to_origin.write().unwrap().insert(col_id, origin);
     }
 
+    /// Type of column `col_id` if it is a column of a table, None for computed
+    /// columns.
+    fn column_type(&self, col_id: ColumnId) -> Option<DataType> {
+        let origin = self.id_to_origin.read().unwrap().get(&col_id).cloned();
+        match origin {
+            Some(OriginExpression::BaseCidAndIndex { cid, index }) => {
+                let table = self.catalog.get_table(cid)?;
+                let attr = table.schema.get_attribute(index)?;
+                Some(attr.dtype().clone())
+            }
+            Some(OriginExpression::DerivedColRef { col_id }) => self.column_type(col_id),
+            Some(_) => None,
+            None => self.outer.as_ref()?.column_type(col_id),
+        }
+    }
+
     fn get_names(&self, col_id: usize) -> Vec<String> {
         let mut names = Vec::new();
         for (name, index) in self.columns.read().unwrap().iter() {
@@ -183,7 +318,9 @@ impl Environment {
                     }
                 }
             }
-            OriginExpression::Field { .. } => expr.clone(),
+            OriginExpression::Field { .. }
+            | OriginExpression::Subquery
+            | OriginExpression::Parameter => expr.clone(),
             OriginExpression::Binary { op, left, right } => OriginExpression::Binary {
                 op: *op,
                 left: Box::new(self.get_origin(left)),
@@ -194,22 +331,51 @@ impl Environment {
                 whens,
                 else_expr,
             } => OriginExpression::Case {
-                expr: Box::new(self.get_origin(expr)),
+                expr: expr.as_ref().map(|expr| Box::new(self.get_origin(expr))),
                 whens: whens
                     .iter()
                     .map(|(when, then)| (self.get_origin(when), self.get_origin(then)))
                     .collect(),
                 else_expr: Box::new(self.get_origin(else_expr)),
             },
+            OriginExpression::InList {
+                expr,
+                list,
+                negated,
+            } => OriginExpression::InList {
+                expr: Box::new(self.get_origin(expr)),
+                list: list.clone(),
+                negated: *negated,
+            },
+            OriginExpression::IsNull { expr, negated } => OriginExpression::IsNull {
+                expr: Box::new(self.get_origin(expr)),
+                negated: *negated,
+            },
+            OriginExpression::Cast { expr, dtype } => OriginExpression::Cast {
+                expr: Box::new(self.get_origin(expr)),
+                dtype: dtype.clone(),
+            },
+            OriginExpression::ScalarFunction { func, args } => OriginExpression::ScalarFunction {
+                func: *func,
+                args: args.iter().map(|arg| self.get_origin(arg)).collect(),
+            },
         }
     }
 }
 
+/// Uncorrelated scalar subqueries of a statement, keyed by their SQL. Identical
+/// subqueries share one plan so that the planner runs them once.
+type SubqueriesRef = Arc<RwLock<HashMap<sqlparser::ast::Query, LogicalRelExpr>>>;
+
+/// Type, right input and predicates of a join a subquery condition becomes.
+type SubqueryJoin = (JoinType, LogicalRelExpr, Vec<Expression<LogicalRelExpr>>);
+
 pub struct Translator {
     catalog_ref: CatalogRef,
     enabled_rules: RulesRef,
     col_id_gen: ColIdGeneratorRef,
     env: EnvironmentRef, // Variables in the current scope
+    subqueries: SubqueriesRef,
 }
 
 #[derive(Debug, Clone)]
@@ -230,7 +396,10 @@ impl Query {
 
 #[derive(Debug)]
 pub enum TranslatorError {
-    ColumnNotFound(String),
+    ColumnNotFound {
+        name: String,
+        suggestion: Option<String>,
+    },
     TableNotFound(String),
     InvalidSQL(String),
     UnsupportedSQL(String),
@@ -239,7 +408,11 @@ pub enum TranslatorError {
 impl std::fmt::Display for TranslatorError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            TranslatorError::ColumnNotFound(s) => write!(f, "Column not found: {}", s),
+            TranslatorError::ColumnNotFound {
+                name,
+                suggestion: Some(s),
+            } => write!(f, "Column not found: {}, did you mean {}?", name, s),
+            TranslatorError::ColumnNotFound { name, .. } => write!(f, "Column not found: {}", name),
             TranslatorError::TableNotFound(s) => write!(f, "Table not found: {}", s),
             TranslatorError::InvalidSQL(s) => write!(f, "Invalid SQL: {}", s),
             TranslatorError::UnsupportedSQL(s) => write!(f, "Unsupported SQL: {}", s),
@@ -248,9 +421,6 @@ impl std::fmt::Display for TranslatorError {
 }
 
 macro_rules! translation_err {
-    (ColumnNotFound, $($arg:tt)*) => {
-        TranslatorError::ColumnNotFound(format!($($arg)*))
-    };
     (TableNotFound, $($arg:tt)*) => {
         TranslatorError::TableNotFound(format!($($arg)*))
     };
@@ -273,6 +443,7 @@ impl Translator {
             enabled_rules: enabled_rules.clone(),
             col_id_gen: col_id_gen.clone(),
             env: Arc::new(Environment::new(catalog.clone())),
+            subqueries: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -281,12 +452,14 @@ impl Translator {
         enabled_rules: &RulesRef,
         col_id_gen: &ColIdGeneratorRef,
         outer: &EnvironmentRef,
+        subqueries: &SubqueriesRef,
     ) -> Translator {
         Translator {
             col_id_gen: col_id_gen.clone(),
             enabled_rules: enabled_rules.clone(),
             catalog_ref: catalog.clone(),
             env: Arc::new(Environment::new_with_outer(outer.clone(), catalog.clone())),
+            subqueries: subqueries.clone(),
         }
     }
 
@@ -304,8 +477,14 @@ impl Translator {
         &mut self,
         query: &sqlparser::ast::Query,
     ) -> Result<Query, TranslatorError> {
-        let select = match query.body.as_ref() {
-            sqlparser::ast::SetExpr::Select(select) => select,
+        let plan = match query.body.as_ref() {
+            sqlparser::ast::SetExpr::Select(select) => {
+                self.process_select(select, &query.order_by)?
+            }
+            body @ sqlparser::ast::SetExpr::SetOperation { .. } => {
+                let plan = self.process_set_expr(body)?;
+                self.process_set_order_by(plan, &query.order_by)?
+            }
             _ => {
                 return Err(translation_err!(
                     UnsupportedSQL,
@@ -313,24 +492,216 @@ impl Translator {
                 ))
             }
         };
+        let plan = self.process_limit(plan, query)?;
+
+        Ok(Query {
+            env: self.env.clone(),
+            plan,
+        })
+    }
+
+    /// Keeps the first rows of the result of `query`, as many as its LIMIT,
+    /// which must be a non-negative integer.
+    fn process_limit(
+        &self,
+        plan: LogicalRelExpr,
+        query: &sqlparser::ast::Query,
+    ) -> Result<LogicalRelExpr, TranslatorError> {
+        if query.offset.is_some() || query.fetch.is_some() {
+            return Err(translation_err!(
+                UnsupportedSQL,
+                "OFFSET and FETCH are not supported"
+            ));
+        }
+        match &query.limit {
+            None => Ok(plan),
+            Some(sqlparser::ast::Expr::Value(sqlparser::ast::Value::Number(n, _))) => {
+                match n.parse::<usize>() {
+                    Ok(limit) => Ok(plan.limit(limit)),
+                    Err(_) => Err(translation_err!(
+                        InvalidSQL,
+                        "LIMIT must be a non-negative integer, not {}",
+                        n
+                    )),
+                }
+            }
+            Some(expr) => Err(translation_err!(
+                UnsupportedSQL,
+                "LIMIT must be a non-negative integer, not {}",
+                expr
+            )),
+        }
+    }
 
+    fn process_select(
+        &mut self,
+        select: &sqlparser::ast::Select,
+        order_by: &[sqlparser::ast::OrderByExpr],
+    ) -> Result<LogicalRelExpr, TranslatorError> {
         let plan = self.process_from(&select.from)?;
         let plan = self.process_where(plan, &select.selection)?;
-        let plan = self.process_projection(
+        self.process_projection(
             plan,
             &select.projection,
             &select.from,
-            &query.order_by,
-            &query.limit,
+            order_by,
             &select.group_by,
             &select.having,
             &select.distinct,
-        )?;
+        )
+    }
 
-        Ok(Query {
-            env: self.env.clone(),
-            plan,
-        })
+    /// Translates an input of a set operation. The names of the columns of the
+    /// leftmost SELECT are added to the current scope, they name the result.
+    fn process_set_expr(
+        &mut self,
+        body: &sqlparser::ast::SetExpr,
+    ) -> Result<LogicalRelExpr, TranslatorError> {
+        use sqlparser::ast::{SetExpr, SetOperator, SetQuantifier};
+        match body {
+            SetExpr::Select(select) => self.process_select(select, &[]),
+            SetExpr::Query(query) => Ok(self.process_query(query)?.plan),
+            SetExpr::SetOperation {
+                op: SetOperator::Union,
+                set_quantifier,
+                left,
+                right,
+            } => {
+                let all = match set_quantifier {
+                    SetQuantifier::All => true,
+                    SetQuantifier::Distinct | SetQuantifier::None => false,
+                    _ => {
+                        return Err(translation_err!(
+                            UnsupportedSQL,
+                            "UNION {} is not supported",
+                            set_quantifier
+                        ))
+                    }
+                };
+                let left = self.process_set_expr(left)?;
+                // The right input sees the outer scopes but not the columns of
+                // the left one.
+                let mut translator = self.new_sibling();
+                let right = translator.process_set_expr(right)?;
+                self.check_union_inputs(&left, &right)?;
+                let plan = left.union(right);
+                Ok(if all { plan } else { plan.distinct() })
+            }
+            SetExpr::SetOperation { op, .. } => {
+                Err(translation_err!(UnsupportedSQL, "{} is not supported", op))
+            }
+            _ => Err(translation_err!(
+                UnsupportedSQL,
+                "Only SELECT queries are supported"
+            )),
+        }
+    }
+
+    /// Translator for another input of the set operation this translator
+    /// translates an input of. It shares the outer scopes of this translator.
+    fn new_sibling(&self) -> Translator {
+        let env = match &self.env.outer {
+            Some(outer) => Environment::new_with_outer(outer.clone(), self.catalog_ref.clone()),
+            None => Environment::new(self.catalog_ref.clone()),
+        };
+        Translator {
+            catalog_ref: self.catalog_ref.clone(),
+            enabled_rules: self.enabled_rules.clone(),
+            col_id_gen: self.col_id_gen.clone(),
+            env: Arc::new(env),
+            subqueries: self.subqueries.clone(),
+        }
+    }
+
+    /// Output columns of `plan` in order, if their types can be derived before
+    /// the subqueries of the plan run.
+    fn output_schema(&self, plan: &LogicalRelExpr) -> Option<PlanSchema> {
+        plan.to_physical_plan()
+            .derive_schema(&self.catalog_ref)
+            .ok()
+    }
+
+    /// Checks that the inputs of a UNION have the same number of columns and
+    /// that the columns at the same position may hold the values of both.
+    fn check_union_inputs(
+        &self,
+        left: &LogicalRelExpr,
+        right: &LogicalRelExpr,
+    ) -> Result<(), TranslatorError> {
+        let (Some(left), Some(right)) = (self.output_schema(left), self.output_schema(right))
+        else {
+            // Checked when the plan is built.
+            return Ok(());
+        };
+        if left.len() != right.len() {
+            return Err(translation_err!(
+                InvalidSQL,
+                "Each UNION input must have the same number of columns, found {} and {}",
+                left.len(),
+                right.len()
+            ));
+        }
+        for (i, (l, r)) in left.iter().zip(&right).enumerate() {
+            if common_dtype(&l.dtype, &r.dtype).is_none() {
+                return Err(translation_err!(
+                    InvalidSQL,
+                    "UNION column {} has incompatible types {} and {}",
+                    i + 1,
+                    l.dtype,
+                    r.dtype
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sorts the result of a set operation. A key is a column of the result,
+    /// named after the leftmost SELECT, or its position.
+    fn process_set_order_by(
+        &mut self,
+        plan: LogicalRelExpr,
+        order_by: &[sqlparser::ast::OrderByExpr],
+    ) -> Result<LogicalRelExpr, TranslatorError> {
+        if order_by.is_empty() {
+            return Ok(plan);
+        }
+        let att = plan.att();
+        let mut cols = Vec::new();
+        for order in order_by {
+            let col_id = match &order.expr {
+                sqlparser::ast::Expr::Value(sqlparser::ast::Value::Number(position, _)) => {
+                    let columns = self.output_schema(&plan).ok_or(translation_err!(
+                        UnsupportedSQL,
+                        "ORDER BY position {} needs the columns of the UNION to be known",
+                        position
+                    ))?;
+                    position
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| i.checked_sub(1))
+                        .and_then(|i| columns.get(i))
+                        .map(|c| c.id)
+                        .ok_or(translation_err!(
+                            InvalidSQL,
+                            "ORDER BY position {} is not in select list",
+                            position
+                        ))?
+                }
+                expr => match self.process_expr(expr, Some(0))? {
+                    Expression::ColRef { id } if att.contains(&id) => id,
+                    _ => {
+                        return Err(translation_err!(
+                            InvalidSQL,
+                            "ORDER BY {} must be a column of the UNION result",
+                            expr
+                        ))
+                    }
+                },
+            };
+            let asc = order.asc.unwrap_or(true);
+            cols.push((col_id, asc, order.nulls_first.unwrap_or(!asc)));
+        }
+        Ok(plan.order_by(cols))
     }
 
     fn process_from(
@@ -373,7 +744,8 @@ impl Translator {
             let (right, is_subquery) = self.process_table_factor(&join.relation)?;
             // If it is a subquery, we use flat_map + condition
             // Other wise we use a join
-            let (join_type, condition) = self.process_join_operator(&join.join_operator)?;
+            let (join_type, condition) =
+                self.process_join_operator(&join.join_operator, &plan, &right)?;
             plan = if is_subquery {
                 if matches!(
                     join_type,
@@ -405,31 +777,119 @@ impl Translator {
         Ok((plan, is_sbqry))
     }
 
+    /// Translates the type and condition of a join of `left` and `right`.
+    ///
+    /// USING and NATURAL joins compare the columns of the same name of both
+    /// inputs. The unqualified name refers to the column of the left input, or
+    /// of the right input for a RIGHT JOIN, which are the ones that are never
+    /// NULL. `*` returns only that one, before the other columns.
     fn process_join_operator(
         &self,
         join_operator: &sqlparser::ast::JoinOperator,
+        left: &LogicalRelExpr,
+        right: &LogicalRelExpr,
     ) -> Result<(JoinType, Option<Expression<LogicalRelExpr>>), TranslatorError> {
         use sqlparser::ast::{JoinConstraint, JoinOperator::*};
-        match join_operator {
-            Inner(JoinConstraint::On(cond)) => {
-                Ok((JoinType::Inner, Some(self.process_expr(cond, None)?)))
-            }
-            LeftOuter(JoinConstraint::On(cond)) => {
-                Ok((JoinType::LeftOuter, Some(self.process_expr(cond, None)?)))
+        let (join_type, constraint) = match join_operator {
+            Inner(constraint) => (JoinType::Inner, constraint),
+            LeftOuter(constraint) => (JoinType::LeftOuter, constraint),
+            RightOuter(constraint) => (JoinType::RightOuter, constraint),
+            FullOuter(constraint) => (JoinType::FullOuter, constraint),
+            CrossJoin => return Ok((JoinType::CrossJoin, None)),
+            _ => {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "Unsupported join operator: {:?}",
+                    join_operator
+                ))
             }
-            RightOuter(JoinConstraint::On(cond)) => {
-                Ok((JoinType::RightOuter, Some(self.process_expr(cond, None)?)))
+        };
+        match constraint {
+            JoinConstraint::On(cond) => {
+                return Ok((join_type, Some(self.process_expr(cond, None)?)))
             }
-            FullOuter(JoinConstraint::On(cond)) => {
-                Ok((JoinType::FullOuter, Some(self.process_expr(cond, None)?)))
+            JoinConstraint::None => {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "Unsupported join operator: {:?}",
+                    join_operator
+                ))
             }
-            CrossJoin => Ok((JoinType::CrossJoin, None)),
-            _ => Err(translation_err!(
+            JoinConstraint::Using(_) | JoinConstraint::Natural => {}
+        }
+        if join_type == JoinType::FullOuter {
+            return Err(translation_err!(
                 UnsupportedSQL,
-                "Unsupported join operator: {:?}",
-                join_operator
-            )),
+                "FULL OUTER JOIN with USING or NATURAL is not supported"
+            ));
+        }
+
+        let left_cols = self.columns_by_name(left);
+        let right_cols = self.columns_by_name(right);
+        let names: Vec<String> = match constraint {
+            JoinConstraint::Using(idents) => idents.iter().map(|i| i.value.clone()).collect(),
+            _ => {
+                // The columns both inputs have, in the order of the left input.
+                let mut common: Vec<(ColumnId, String)> = left_cols
+                    .iter()
+                    .filter(|(name, _)| right_cols.contains_key(*name))
+                    .map(|(name, ids)| (ids[0], name.clone()))
+                    .collect();
+                common.sort();
+                common.into_iter().map(|(_, name)| name).collect()
+            }
+        };
+        if names.is_empty() {
+            let join_type = match join_type {
+                JoinType::Inner => JoinType::CrossJoin,
+                join_type => join_type,
+            };
+            return Ok((join_type, None));
+        }
+
+        let mut condition: Option<Expression<LogicalRelExpr>> = None;
+        for name in &names {
+            let l = join_column(name, &left_cols, "left")?;
+            let r = join_column(name, &right_cols, "right")?;
+            let eq = Expression::col_ref(l).eq(Expression::col_ref(r));
+            condition = Some(match condition {
+                Some(condition) => Expression::binary(BinaryOp::And, condition, eq),
+                None => eq,
+            });
+            let (kept, dropped) = if join_type == JoinType::RightOuter {
+                (r, l)
+            } else {
+                (l, r)
+            };
+            self.env.set(name, kept);
+            self.env.add_join_column(kept, dropped);
+        }
+        Ok((join_type, condition))
+    }
+
+    /// Columns of `plan` by their unqualified names in the environment.
+    fn columns_by_name(&self, plan: &LogicalRelExpr) -> HashMap<String, Vec<ColumnId>> {
+        let mut att: Vec<ColumnId> = plan.att().into_iter().collect();
+        att.sort();
+        let mut cols: HashMap<String, Vec<ColumnId>> = HashMap::new();
+        for id in att {
+            let mut names: Vec<String> = self
+                .env
+                .get_names(id)
+                .iter()
+                .map(|name| {
+                    name.rsplit_once('.')
+                        .map_or(name.as_str(), |(_, col)| col)
+                        .to_string()
+                })
+                .collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                cols.entry(name).or_default().push(id);
+            }
         }
+        cols
     }
 
     // Out: (RelExpr, is_subquery: bool)
@@ -453,6 +913,10 @@ impl Translator {
                     let att = plan.att();
                     let (plan, mut new_col_ids) =
                         plan.rename(&self.enabled_rules, &self.col_id_gen);
+                    let table_cols: TableColumns = cols
+                        .iter()
+                        .map(|(name, id)| (name.clone(), new_col_ids[id]))
+                        .collect();
 
                     for i in att {
                         let new_col_id = new_col_ids.remove(&i).unwrap();
@@ -482,6 +946,10 @@ impl Translator {
                             }
                         }
                     }
+                    if let Some(alias) = alias {
+                        self.env.set_table(&alias.name.value, table_cols.clone());
+                    }
+                    self.env.set_table(&table_name, table_cols);
 
                     Ok((plan, false))
                 } else {
@@ -496,10 +964,36 @@ impl Translator {
                     &self.enabled_rules,
                     &self.col_id_gen,
                     &self.env,
+                    &self.subqueries,
                 );
                 let subquery = translator.process_query(subquery)?;
                 let plan = subquery.plan;
                 let att = plan.att();
+                if let Some(alias) = alias {
+                    // The columns of the subquery in the order it outputs them.
+                    let ids = match self.output_schema(&plan) {
+                        Some(schema) => schema.iter().map(|c| c.id).collect(),
+                        None => {
+                            let mut ids: Vec<ColumnId> = att.iter().copied().collect();
+                            ids.sort();
+                            ids
+                        }
+                    };
+                    let cols = ids
+                        .into_iter()
+                        .map(|id| {
+                            let names = subquery.env.get_names(id);
+                            let name = names
+                                .iter()
+                                .find(|n| !n.contains('.'))
+                                .or(names.first())
+                                .cloned()
+                                .unwrap_or_else(|| format!("@{}", id));
+                            (name, id)
+                        })
+                        .collect();
+                    self.env.set_table(&alias.name.value, cols);
+                }
 
                 for i in att {
                     // get the name of the column from env
@@ -533,13 +1027,50 @@ impl Translator {
         plan: LogicalRelExpr,
         where_clause: &Option<sqlparser::ast::Expr>,
     ) -> Result<LogicalRelExpr, TranslatorError> {
-        if let Some(expr) = where_clause {
+        let Some(expr) = where_clause else {
+            return Ok(plan);
+        };
+        // Conditions on correlated IN and ANY subqueries become semi-joins, and
+        // EXISTS and NOT EXISTS correlated by an equality become semi and anti
+        // joins. The rest of the conditions are translated as a whole.
+        let mut plan = plan;
+        let mut rest: Option<sqlparser::ast::Expr> = None;
+        for conjunct in split_conjunction(expr) {
+            if let Some((expr, op, subquery)) = self.correlated_semi_join(conjunct)? {
+                plan = self.process_semi_join(plan, expr, op, subquery);
+            } else if let Some((join_type, subquery, predicates)) =
+                self.correlated_exists(&plan, conjunct)?
+            {
+                plan = plan.join(
+                    true,
+                    &self.enabled_rules,
+                    &self.col_id_gen,
+                    join_type,
+                    subquery,
+                    predicates,
+                );
+            } else {
+                rest = Some(match rest {
+                    Some(rest) => sqlparser::ast::Expr::BinaryOp {
+                        left: Box::new(rest),
+                        op: sqlparser::ast::BinaryOperator::And,
+                        right: Box::new(conjunct.clone()),
+                    },
+                    None => conjunct.clone(),
+                });
+            }
+        }
+        let where_clause = rest;
+        if let Some(expr) = &where_clause {
             match self.process_expr(expr, Some(0)) {
                 Ok(expr) => {
                     match expr {
                         Expression::Subquery { expr } => {
                             if expr.att().len() != 1 {
-                                panic!("Subquery in WHERE clause returns more than one column")
+                                return Err(translation_err!(
+                                    InvalidSQL,
+                                    "Subquery in WHERE clause returns more than one column"
+                                ));
                             }
                             // Add map first
                             let col_id = self.col_id_gen.next();
@@ -567,7 +1098,7 @@ impl Translator {
                         )),
                     }
                 }
-                Err(TranslatorError::ColumnNotFound(_)) => {
+                Err(TranslatorError::ColumnNotFound { .. }) => {
                     // Search globally.
                     let expr = self.process_expr(expr, None)?;
                     let col_id = self.col_id_gen.next();
@@ -599,31 +1130,65 @@ impl Translator {
         mut plan: LogicalRelExpr,
         projection: &Vec<sqlparser::ast::SelectItem>,
         _from: &[sqlparser::ast::TableWithJoins],
-        _order_by: &[sqlparser::ast::OrderByExpr],
-        _limit: &Option<sqlparser::ast::Expr>,
+        order_by: &[sqlparser::ast::OrderByExpr],
         group_by: &sqlparser::ast::GroupByExpr,
         having: &Option<sqlparser::ast::Expr>,
-        _distinct: &Option<sqlparser::ast::Distinct>,
+        distinct: &Option<sqlparser::ast::Distinct>,
     ) -> Result<LogicalRelExpr, TranslatorError> {
+        let distinct = match distinct {
+            None => false,
+            Some(sqlparser::ast::Distinct::Distinct) => true,
+            Some(sqlparser::ast::Distinct::On(_)) => {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "DISTINCT ON is not supported"
+                ))
+            }
+        };
         let mut projected_cols = Vec::new();
         let mut aggregations = Vec::new();
         let mut maps = Vec::new();
+        // Expressions computed before the aggregation, so that GROUP BY can use them.
+        let mut mapped_exprs = Vec::new();
         let mut is_wildcard = false;
+        // Items selected outside an aggregation function, with their names,
+        // positions in `projected_cols` and, if they are not a column, their
+        // expressions. With aggregation they must be grouped.
+        let mut plain_items = Vec::new();
         for item in projection {
             match item {
                 sqlparser::ast::SelectItem::Wildcard(_) => {
                     is_wildcard = true;
                     break;
                 }
+                sqlparser::ast::SelectItem::QualifiedWildcard(name, _) => {
+                    let table_name = get_table_name(name);
+                    let Some(cols) = self.env.get_table(&table_name) else {
+                        return Err(translation_err!(TableNotFound, "{}", table_name));
+                    };
+                    for (col_name, col_id) in cols {
+                        let name = format!("{}.{}", table_name, col_name);
+                        plain_items.push((name, projected_cols.len(), None));
+                        projected_cols.push(col_id);
+                    }
+                }
                 sqlparser::ast::SelectItem::UnnamedExpr(expr) => {
-                    if !has_agg(expr) {
+                    if !has_agg(expr)? {
+                        let name = expr.to_string();
                         match self.process_expr(expr, Some(0)) {
                             Ok(expr) => {
                                 let col_id = if let Expression::ColRef { id } = expr {
+                                    plain_items.push((name, projected_cols.len(), None));
                                     id
                                 } else {
+                                    plain_items.push((
+                                        name,
+                                        projected_cols.len(),
+                                        Some(expr.clone()),
+                                    ));
                                     // create a new col_id for the expression
                                     let col_id = self.col_id_gen.next();
+                                    mapped_exprs.push((col_id, format!("{:?}", expr)));
                                     plan = plan.map(
                                         true,
                                         &self.enabled_rules,
@@ -635,11 +1200,13 @@ impl Translator {
                                 };
                                 projected_cols.push(col_id);
                             }
-                            Err(TranslatorError::ColumnNotFound(_)) => {
+                            Err(TranslatorError::ColumnNotFound { .. }) => {
                                 // Search globally.
                                 let expr = self.process_expr(expr, None)?;
+                                plain_items.push((name, projected_cols.len(), Some(expr.clone())));
                                 // Add a map to the plan
                                 let col_id = self.col_id_gen.next();
+                                mapped_exprs.push((col_id, format!("{:?}", expr)));
                                 self.env.add_to_origin_map(col_id, expr.clone().into());
                                 plan = plan.map(
                                     true,
@@ -664,9 +1231,9 @@ impl Translator {
                         // Level3: |map col_id3 + col_id4 + 4 to col_id5
 
                         let mut aggs = Vec::new();
-                        let res = self.process_aggregation_arguments(plan, expr, &mut aggs);
-                        plan = res.0;
-                        let expr = res.1;
+                        let (new_plan, expr) =
+                            self.process_aggregation_arguments(plan, expr, &mut aggs)?;
+                        plan = new_plan;
                         let col_id = if let Expression::ColRef { id } = expr {
                             id
                         } else {
@@ -682,13 +1249,21 @@ impl Translator {
                 }
                 sqlparser::ast::SelectItem::ExprWithAlias { expr, alias } => {
                     // create a new col_id for the expression
-                    let col_id = if !has_agg(expr) {
+                    let col_id = if !has_agg(expr)? {
+                        let name = expr.to_string();
                         let col_id = match self.process_expr(expr, Some(0)) {
                             Ok(expr) => {
                                 if let Expression::ColRef { id } = expr {
+                                    plain_items.push((name, projected_cols.len(), None));
                                     id
                                 } else {
+                                    plain_items.push((
+                                        name,
+                                        projected_cols.len(),
+                                        Some(expr.clone()),
+                                    ));
                                     let col_id = self.col_id_gen.next();
+                                    mapped_exprs.push((col_id, format!("{:?}", expr)));
                                     self.env.add_to_origin_map(col_id, expr.clone().into());
                                     plan = plan.map(
                                         true,
@@ -699,10 +1274,12 @@ impl Translator {
                                     col_id
                                 }
                             }
-                            Err(TranslatorError::ColumnNotFound(_)) => {
+                            Err(TranslatorError::ColumnNotFound { .. }) => {
                                 // Search globally.
                                 let expr = self.process_expr(expr, None)?;
+                                plain_items.push((name, projected_cols.len(), Some(expr.clone())));
                                 let col_id = self.col_id_gen.next();
+                                mapped_exprs.push((col_id, format!("{:?}", expr)));
                                 self.env.add_to_origin_map(col_id, expr.clone().into());
                                 plan = plan.map(
                                     true,
@@ -729,9 +1306,9 @@ impl Translator {
                         // Level3: |map col_id3 + col_id4 + 4 to col_id5
 
                         let mut aggs = Vec::new();
-                        let res = self.process_aggregation_arguments(plan, expr, &mut aggs);
-                        plan = res.0;
-                        let expr = res.1;
+                        let (new_plan, expr) =
+                            self.process_aggregation_arguments(plan, expr, &mut aggs)?;
+                        plan = new_plan;
                         let col_id = if let Expression::ColRef { id } = expr {
                             id
                         } else {
@@ -758,13 +1335,6 @@ impl Translator {
                         ));
                     }
                 }
-                _ => {
-                    return Err(translation_err!(
-                        UnsupportedSQL,
-                        "Unsupported select item: {:?}",
-                        item
-                    ))
-                }
             }
         }
 
@@ -778,8 +1348,13 @@ impl Translator {
                     let mut group_by = Vec::new();
                     for expr in exprs {
                         let expr = self.process_expr(expr, None)?;
+                        let key = format!("{:?}", expr);
+                        let selected = mapped_exprs.iter().find(|(_, e)| *e == key);
                         let col_id = if let Expression::ColRef { id } = expr {
                             id
+                        } else if let Some((col_id, _)) = selected {
+                            // The expression is also selected, group by its column.
+                            *col_id
                         } else {
                             // create a new col_id for the expression
                             let col_id = self.col_id_gen.next();
@@ -797,10 +1372,53 @@ impl Translator {
                     group_by
                 }
             };
+            for (name, i, expr) in plain_items {
+                if group_by.contains(&projected_cols[i]) {
+                    continue;
+                }
+                // An expression of grouped columns, or of none, is computed
+                // after the aggregation.
+                match expr {
+                    Some(expr) if expr.free().iter().all(|id| group_by.contains(id)) => {
+                        let col_id = self.col_id_gen.next();
+                        self.env.add_to_origin_map(col_id, expr.clone().into());
+                        self.env.rename_id(projected_cols[i], col_id);
+                        maps.push((col_id, expr));
+                        projected_cols[i] = col_id;
+                    }
+                    _ => {
+                        return Err(translation_err!(
+                            InvalidSQL,
+                            "Column {} must appear in the GROUP BY clause or be used in an aggregation function",
+                            name
+                        ))
+                    }
+                }
+            }
             plan = plan.aggregate(group_by, aggregations);
             plan = self.process_where(plan, having)?;
         }
         plan = plan.map(true, &self.enabled_rules, &self.col_id_gen, maps); // This map corresponds to the Level3 in the comment above
+        let join_columns = self.env.join_columns();
+        if is_wildcard && !join_columns.is_empty() {
+            // `*` lists each column of a USING or NATURAL join once, first.
+            if let Some(schema) = self.output_schema(&plan) {
+                let kept: Vec<ColumnId> = join_columns.iter().map(|(kept, _)| *kept).collect();
+                let others = schema.iter().map(|col| col.id).filter(|id| {
+                    !kept.contains(id) && !join_columns.iter().any(|(_, dropped)| dropped == id)
+                });
+                projected_cols = kept.iter().copied().chain(others).collect();
+                is_wildcard = false;
+            }
+        }
+        plan = self.process_order_by(
+            plan,
+            order_by,
+            &projected_cols,
+            &mapped_exprs,
+            is_wildcard,
+            distinct,
+        )?;
         plan = plan.project(
             true,
             &self.enabled_rules,
@@ -808,9 +1426,86 @@ impl Translator {
             projected_cols,
             is_wildcard,
         );
+        if distinct {
+            // Duplicates are removed as the rows stream, so the order is kept.
+            plan = plan.distinct();
+        }
         Ok(plan)
     }
 
+    /// Sorts the plan by the ORDER BY keys. A key is a column of the input, an alias
+    /// or position of the select list, or an expression, which is mapped to a new
+    /// column unless it is also selected. Without NULLS FIRST or NULLS LAST, NULLs
+    /// sort as if they were larger than any value. With DISTINCT, the keys must be
+    /// selected.
+    fn process_order_by(
+        &mut self,
+        mut plan: LogicalRelExpr,
+        order_by: &[sqlparser::ast::OrderByExpr],
+        projected_cols: &[ColumnId],
+        mapped_exprs: &[(ColumnId, String)],
+        is_wildcard: bool,
+        distinct: bool,
+    ) -> Result<LogicalRelExpr, TranslatorError> {
+        let mut cols = Vec::new();
+        for order in order_by {
+            let col_id = match &order.expr {
+                sqlparser::ast::Expr::Value(sqlparser::ast::Value::Number(position, _))
+                    if !is_wildcard =>
+                {
+                    position
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| i.checked_sub(1))
+                        .and_then(|i| projected_cols.get(i))
+                        .copied()
+                        .ok_or(translation_err!(
+                            InvalidSQL,
+                            "ORDER BY position {} is not in select list",
+                            position
+                        ))?
+                }
+                expr => match self.process_expr(expr, Some(0))? {
+                    Expression::ColRef { id } => id,
+                    _ if is_wildcard => {
+                        return Err(translation_err!(
+                            UnsupportedSQL,
+                            "ORDER BY {} needs an explicit select list",
+                            expr
+                        ))
+                    }
+                    expr => {
+                        let key = format!("{:?}", expr);
+                        match mapped_exprs.iter().find(|(_, e)| *e == key) {
+                            Some((col_id, _)) => *col_id,
+                            None => {
+                                let col_id = self.col_id_gen.next();
+                                self.env.add_to_origin_map(col_id, expr.clone().into());
+                                plan = plan.map(
+                                    true,
+                                    &self.enabled_rules,
+                                    &self.col_id_gen,
+                                    [(col_id, expr)],
+                                );
+                                col_id
+                            }
+                        }
+                    }
+                },
+            };
+            if distinct && !is_wildcard && !projected_cols.contains(&col_id) {
+                return Err(translation_err!(
+                    InvalidSQL,
+                    "For SELECT DISTINCT, ORDER BY {} must appear in select list",
+                    order.expr
+                ));
+            }
+            let asc = order.asc.unwrap_or(true);
+            cols.push((col_id, asc, order.nulls_first.unwrap_or(!asc)));
+        }
+        Ok(plan.order_by(cols))
+    }
+
     // DFS until we find an aggregation function
     // If we find an aggregation function, then add the aggregation argument to the plan
     // and put the aggregation function in the aggregation list, return the modified plan with the expression.
@@ -823,20 +1518,24 @@ impl Translator {
         mut plan: LogicalRelExpr,
         expr: &sqlparser::ast::Expr,
         aggs: &mut Vec<(usize, (usize, AggOp))>,
-    ) -> (LogicalRelExpr, Expression<LogicalRelExpr>) {
+    ) -> Result<(LogicalRelExpr, Expression<LogicalRelExpr>), TranslatorError> {
         match expr {
             sqlparser::ast::Expr::Identifier(_) | sqlparser::ast::Expr::CompoundIdentifier(_) => {
-                unreachable!(
-                    "Identifier and compound identifier should be processed in the Function branch"
-                )
+                Err(translation_err!(
+                    UnsupportedSQL,
+                    "Column {} must appear inside an aggregation function",
+                    expr
+                ))
             }
-            sqlparser::ast::Expr::Value(_) | sqlparser::ast::Expr::TypedString { .. } => {
-                let expr = self.process_expr(expr, Some(0)).unwrap();
-                (plan, expr)
+            sqlparser::ast::Expr::Value(_)
+            | sqlparser::ast::Expr::TypedString { .. }
+            | sqlparser::ast::Expr::Interval(_) => {
+                let expr = self.process_expr(expr, Some(0))?;
+                Ok((plan, expr))
             }
             sqlparser::ast::Expr::BinaryOp { left, op, right } => {
-                let (plan, left) = self.process_aggregation_arguments(plan, left, aggs);
-                let (plan, right) = self.process_aggregation_arguments(plan, right, aggs);
+                let (plan, left) = self.process_aggregation_arguments(plan, left, aggs)?;
+                let (plan, right) = self.process_aggregation_arguments(plan, right, aggs)?;
                 let bin_op = match op {
                     sqlparser::ast::BinaryOperator::And => BinaryOp::And,
                     sqlparser::ast::BinaryOperator::Or => BinaryOp::Or,
@@ -850,22 +1549,57 @@ impl Translator {
                     sqlparser::ast::BinaryOperator::Gt => BinaryOp::Gt,
                     sqlparser::ast::BinaryOperator::LtEq => BinaryOp::Le,
                     sqlparser::ast::BinaryOperator::GtEq => BinaryOp::Ge,
-                    _ => unimplemented!("Unsupported binary operator: {:?}", op),
+                    _ => {
+                        return Err(translation_err!(
+                            UnsupportedSQL,
+                            "Unsupported binary operator: {:?}",
+                            op
+                        ))
+                    }
+                };
+                Ok((plan, Expression::binary(bin_op, left, right)))
+            }
+            sqlparser::ast::Expr::UnaryOp { op, expr } => {
+                let (plan, expr) = self.process_aggregation_arguments(plan, expr, aggs)?;
+                Ok((plan, apply_unary_op(op, expr)?))
+            }
+            sqlparser::ast::Expr::Substring { .. }
+            | sqlparser::ast::Expr::Trim { .. }
+            | sqlparser::ast::Expr::Extract { .. } => {
+                let Some((func, args)) = scalar_call(expr)? else {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "Unsupported function: {}",
+                        expr
+                    ));
                 };
-                (plan, Expression::binary(bin_op, left, right))
+                self.process_scalar_call_arguments(plan, func, &args, aggs)
             }
             sqlparser::ast::Expr::Function(function) => {
+                if let Some((func, args)) = scalar_call(expr)? {
+                    return self.process_scalar_call_arguments(plan, func, &args, aggs);
+                }
                 let name = get_table_name(&function.name).to_uppercase();
-                let agg_op = match name.as_str() {
-                    "COUNT" => AggOp::Count,
-                    "SUM" => AggOp::Sum,
-                    "AVG" => AggOp::Avg,
-                    "MIN" => AggOp::Min,
-                    "MAX" => AggOp::Max,
-                    _ => unimplemented!("Unsupported aggregation function: {:?}", function),
+                let Some(agg_op) = AggOp::from_name(&name) else {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "Unsupported aggregation function: {}",
+                        name
+                    ));
                 };
                 if function.args.len() != 1 {
-                    unimplemented!("Unsupported aggregation function: {:?}", function);
+                    return Err(translation_err!(
+                        InvalidSQL,
+                        "{} takes exactly one argument",
+                        name
+                    ));
+                }
+                if function.distinct {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "{}(DISTINCT ...) is not supported",
+                        name
+                    ));
                 }
                 let function_arg_expr = match &function.args[0] {
                     sqlparser::ast::FunctionArg::Named { arg, .. } => arg,
@@ -881,7 +1615,7 @@ impl Translator {
 
                                 if let Expression::ColRef { id } = expr {
                                     aggs.push((agg_col_id, (id, agg_op)));
-                                    (plan, Expression::col_ref(agg_col_id))
+                                    Ok((plan, Expression::col_ref(agg_col_id)))
                                 } else {
                                     plan = plan.map(
                                         true,
@@ -890,12 +1624,12 @@ impl Translator {
                                         [(agg_col_id, expr)],
                                     );
                                     aggs.push((agg_col_id, (agg_col_id, agg_op)));
-                                    (plan, Expression::col_ref(agg_col_id))
+                                    Ok((plan, Expression::col_ref(agg_col_id)))
                                 }
                             }
-                            Err(TranslatorError::ColumnNotFound(_)) => {
+                            Err(TranslatorError::ColumnNotFound { .. }) => {
                                 // Search globally.
-                                let expr = self.process_expr(expr, None).unwrap();
+                                let expr = self.process_expr(expr, None)?;
                                 let col_id = self.col_id_gen.next();
                                 self.env.add_to_origin_map(col_id, expr.clone().into());
                                 plan = plan.map(
@@ -905,17 +1639,18 @@ impl Translator {
                                     [(col_id, expr)],
                                 );
                                 aggs.push((agg_col_id, (col_id, agg_op)));
-                                (plan, Expression::col_ref(agg_col_id))
+                                Ok((plan, Expression::col_ref(agg_col_id)))
                             }
-                            _ => unimplemented!("Unsupported expression: {:?}", expr),
+                            Err(e) => Err(e),
                         }
                     }
-                    sqlparser::ast::FunctionArgExpr::QualifiedWildcard(_) => {
-                        unimplemented!("QualifiedWildcard is not supported yet")
-                    }
+                    sqlparser::ast::FunctionArgExpr::QualifiedWildcard(_) => Err(
+                        translation_err!(UnsupportedSQL, "QualifiedWildcard is not supported yet"),
+                    ),
                     sqlparser::ast::FunctionArgExpr::Wildcard => {
                         // Wildcard is only supported for COUNT
-                        // If wildcard, just need to return Int(1) as it returns the count of rows
+                        // COUNT(*) counts every row, so it counts Int(1), which is never
+                        // NULL, where COUNT(col) skips the rows whose col is NULL
                         if matches!(agg_op, AggOp::Count) {
                             let col_id = self.col_id_gen.next();
                             let count_expr = Expression::int(1);
@@ -928,18 +1663,322 @@ impl Translator {
                                 [(col_id, count_expr)],
                             );
                             aggs.push((agg_col_id, (col_id, agg_op)));
-                            (plan, Expression::col_ref(agg_col_id))
+                            Ok((plan, Expression::col_ref(agg_col_id)))
                         } else {
-                            panic!("Wildcard is only supported for COUNT");
+                            Err(translation_err!(
+                                UnsupportedSQL,
+                                "Wildcard is only supported for COUNT"
+                            ))
                         }
                     }
                 }
             }
+            sqlparser::ast::Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let (mut plan, operand) = match operand {
+                    Some(operand) => {
+                        let (plan, operand) =
+                            self.process_aggregation_arguments(plan, operand, aggs)?;
+                        (plan, Some(operand))
+                    }
+                    None => (plan, None),
+                };
+                let mut whens = Vec::with_capacity(conditions.len());
+                for (when, then) in conditions.iter().zip(results) {
+                    let (when_plan, when) = self.process_aggregation_arguments(plan, when, aggs)?;
+                    let (then_plan, then) =
+                        self.process_aggregation_arguments(when_plan, then, aggs)?;
+                    plan = then_plan;
+                    whens.push((when, then));
+                }
+                let (plan, else_expr) = match else_result {
+                    Some(else_result) => {
+                        self.process_aggregation_arguments(plan, else_result, aggs)?
+                    }
+                    None => (plan, Expression::Field { val: Field::Null }),
+                };
+                Ok((plan, Expression::case(operand, whens, else_expr)))
+            }
             sqlparser::ast::Expr::Nested(expr) => {
                 self.process_aggregation_arguments(plan, expr, aggs)
             }
-            _ => unimplemented!("Unsupported expression: {:?}", expr),
+            sqlparser::ast::Expr::Cast {
+                expr,
+                data_type,
+                format,
+            } => {
+                let dtype = cast_type(data_type, format)?;
+                let (plan, expr) = self.process_aggregation_arguments(plan, expr, aggs)?;
+                Ok((plan, Expression::cast(expr, dtype)))
+            }
+            _ => Err(translation_err!(
+                UnsupportedSQL,
+                "Unsupported expression: {:?}",
+                expr
+            )),
+        }
+    }
+
+    /// Processes the arguments of a call of a scalar function in an aggregation
+    /// like `process_aggregation_arguments`, e.g. UPPER(MAX(name)).
+    fn process_scalar_call_arguments(
+        &self,
+        mut plan: LogicalRelExpr,
+        func: ScalarFunc,
+        args: &[sqlparser::ast::Expr],
+        aggs: &mut Vec<(usize, (usize, AggOp))>,
+    ) -> Result<(LogicalRelExpr, Expression<LogicalRelExpr>), TranslatorError> {
+        let mut exprs = Vec::with_capacity(args.len());
+        for arg in args {
+            let (arg_plan, arg) = self.process_aggregation_arguments(plan, arg, aggs)?;
+            plan = arg_plan;
+            exprs.push(arg);
+        }
+        Ok((plan, scalar_function(func, exprs)?))
+    }
+
+    fn column_not_found(&self, name: &str) -> TranslatorError {
+        TranslatorError::ColumnNotFound {
+            name: name.to_string(),
+            suggestion: self.env.closest_name(name),
+        }
+    }
+
+    /// Translates a subquery used as an expression, which must return a single
+    /// column. Uncorrelated subqueries are translated once per statement.
+    fn process_subquery(
+        &self,
+        query: &sqlparser::ast::Query,
+    ) -> Result<LogicalRelExpr, TranslatorError> {
+        if let Some(plan) = self.subqueries.read().unwrap().get(query) {
+            return Ok(plan.clone());
+        }
+        let mut translator = Translator::new_with_outer(
+            &self.catalog_ref,
+            &self.enabled_rules,
+            &self.col_id_gen,
+            &self.env,
+            &self.subqueries,
+        );
+        let plan = translator.process_query(query)?.plan;
+        if plan.att().len() != 1 {
+            return Err(translation_err!(
+                InvalidSQL,
+                "Subquery returns more than one column"
+            ));
+        }
+        if plan.free().is_empty() {
+            self.subqueries
+                .write()
+                .unwrap()
+                .insert(query.clone(), plan.clone());
+        }
+        Ok(plan)
+    }
+
+    /// Translates `expr op ANY (query)`, or `expr op ALL (query)` if `all` is
+    /// set, for an uncorrelated subquery. Correlated ones are only supported as
+    /// semi-joins, see `process_semi_join`.
+    fn process_quantified(
+        &self,
+        expr: &sqlparser::ast::Expr,
+        op: BinaryOp,
+        all: bool,
+        query: &sqlparser::ast::Query,
+        distance: Option<usize>,
+    ) -> Result<Expression<LogicalRelExpr>, TranslatorError> {
+        let expr = self.process_expr(expr, distance)?;
+        let plan = self.process_subquery(query)?;
+        if !plan.free().is_empty() {
+            return Err(translation_err!(
+                UnsupportedSQL,
+                "Correlated subqueries are only supported in IN and ANY conditions of the WHERE clause"
+            ));
         }
+        Ok(Expression::quantified(expr, op, all, plan))
+    }
+
+    /// Returns the tested expression, the comparison and the plan of `conjunct`
+    /// of a WHERE clause if it is `expr IN (query)` or `expr op ANY (query)` for
+    /// a correlated subquery.
+    fn correlated_semi_join(
+        &self,
+        conjunct: &sqlparser::ast::Expr,
+    ) -> Result<Option<(Expression<LogicalRelExpr>, BinaryOp, LogicalRelExpr)>, TranslatorError>
+    {
+        let (expr, op, query) = match conjunct {
+            sqlparser::ast::Expr::InSubquery {
+                expr,
+                subquery,
+                negated: false,
+            } => (expr, BinaryOp::Eq, subquery),
+            sqlparser::ast::Expr::AnyOp {
+                left,
+                compare_op,
+                right,
+            } => match right.as_ref() {
+                sqlparser::ast::Expr::Subquery(subquery) => {
+                    (left, comparison_op(compare_op)?, subquery)
+                }
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let plan = self.process_subquery(query)?;
+        if plan.free().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((self.process_expr(expr, None)?, op, plan)))
+    }
+
+    /// Returns the join type, the rows and the join predicates of `conjunct` of
+    /// the WHERE clause over `plan` if it is `[NOT] EXISTS (query)` for a query
+    /// correlated with `plan` by at least one equality. Its rows are those of
+    /// the FROM clause of the query, and its conditions are the predicates.
+    /// Other EXISTS conditions are translated by `process_expr`.
+    fn correlated_exists(
+        &self,
+        plan: &LogicalRelExpr,
+        conjunct: &sqlparser::ast::Expr,
+    ) -> Result<Option<SubqueryJoin>, TranslatorError> {
+        let sqlparser::ast::Expr::Exists { subquery, negated } = conjunct else {
+            return Ok(None);
+        };
+        let sqlparser::ast::SetExpr::Select(select) = subquery.body.as_ref() else {
+            return Ok(None);
+        };
+        // Whether the query returns a row must only depend on its FROM and
+        // WHERE clauses.
+        let no_groups = matches!(
+            &select.group_by,
+            sqlparser::ast::GroupByExpr::Expressions(exprs) if exprs.is_empty()
+        );
+        if subquery.with.is_some()
+            || subquery.limit.is_some()
+            || subquery.offset.is_some()
+            || subquery.fetch.is_some()
+            || select.from.is_empty()
+            || !no_groups
+            || select.having.is_some()
+        {
+            return Ok(None);
+        }
+        for item in &select.projection {
+            if let sqlparser::ast::SelectItem::UnnamedExpr(expr)
+            | sqlparser::ast::SelectItem::ExprWithAlias { expr, .. } = item
+            {
+                if has_agg(expr)? {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let mut translator = Translator::new_with_outer(
+            &self.catalog_ref,
+            &self.enabled_rules,
+            &self.col_id_gen,
+            &self.env,
+            &self.subqueries,
+        );
+        let rows = translator.process_from(&select.from)?;
+        if !rows.free().is_empty() {
+            return Ok(None);
+        }
+        let (rows_att, plan_att) = (rows.att(), plan.att());
+        let scope: HashSet<ColumnId> = rows_att.union(&plan_att).copied().collect();
+        let over = |e: &Expression<LogicalRelExpr>, att: &HashSet<ColumnId>| {
+            !e.free().is_empty() && e.free().is_subset(att)
+        };
+        let mut predicates = Vec::new();
+        let mut correlated_by_eq = false;
+        for conjunct in select.selection.iter().flat_map(split_conjunction) {
+            let pred = match translator.process_expr(conjunct, Some(0)) {
+                Ok(pred) => pred,
+                Err(TranslatorError::ColumnNotFound { .. }) => {
+                    translator.process_expr(conjunct, None)?
+                }
+                Err(e) => return Err(e),
+            };
+            if matches!(pred, Expression::Subquery { .. }) || !pred.free().is_subset(&scope) {
+                return Ok(None);
+            }
+            if let Expression::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } = &pred
+            {
+                correlated_by_eq |= (over(left, &rows_att) && over(right, &plan_att))
+                    || (over(left, &plan_att) && over(right, &rows_att));
+            }
+            predicates.push(pred);
+        }
+        if !correlated_by_eq {
+            return Ok(None);
+        }
+        let join_type = if *negated {
+            JoinType::Anti
+        } else {
+            JoinType::Semi
+        };
+        Ok(Some((join_type, rows, predicates)))
+    }
+
+    /// Keeps the rows of `plan` for which `expr op ANY (subquery)` holds. The
+    /// subquery is reduced to the values the comparison needs, one row per row
+    /// of `plan` at most, so that the flatmap decorrelates into a join.
+    fn process_semi_join(
+        &self,
+        plan: LogicalRelExpr,
+        expr: Expression<LogicalRelExpr>,
+        op: BinaryOp,
+        subquery: LogicalRelExpr,
+    ) -> LogicalRelExpr {
+        // Keeps the columns of `plan` in the order they were created.
+        let mut att: Vec<ColumnId> = plan.att().into_iter().collect();
+        att.sort();
+        let col_id = *subquery.att().iter().next().unwrap();
+        let bound = |agg_op| {
+            let bound_id = self.col_id_gen.next();
+            self.env.add_to_origin_map(
+                bound_id,
+                Expression::<LogicalRelExpr>::col_ref(col_id).into(),
+            );
+            (bound_id, (col_id, agg_op))
+        };
+        let compare = |(id, _): (ColumnId, (ColumnId, AggOp))| {
+            Expression::binary(op, expr.clone(), Expression::col_ref(id))
+        };
+        let (subquery, pred) = match op {
+            BinaryOp::Eq => (
+                subquery.aggregate(vec![col_id], vec![]),
+                Expression::binary(op, expr.clone(), Expression::col_ref(col_id)),
+            ),
+            BinaryOp::Lt | BinaryOp::Le => {
+                let max = bound(AggOp::Max);
+                (subquery.aggregate(vec![], vec![max]), compare(max))
+            }
+            BinaryOp::Gt | BinaryOp::Ge => {
+                let min = bound(AggOp::Min);
+                (subquery.aggregate(vec![], vec![min]), compare(min))
+            }
+            // `<> ANY` holds unless the expression equals both the least and
+            // the greatest value.
+            _ => {
+                let (min, max) = (bound(AggOp::Min), bound(AggOp::Max));
+                (
+                    subquery.aggregate(vec![], vec![min, max]),
+                    Expression::binary(BinaryOp::Or, compare(min), compare(max)),
+                )
+            }
+        };
+        plan.flatmap(true, &self.enabled_rules, &self.col_id_gen, subquery)
+            .select(true, &self.enabled_rules, &self.col_id_gen, vec![pred])
+            .project(true, &self.enabled_rules, &self.col_id_gen, att, false)
     }
 
     fn process_expr(
@@ -954,19 +1993,7 @@ impl Translator {
                 } else {
                     self.env.get(&ident.value)
                 };
-                let id = id.ok_or(translation_err!(
-                    ColumnNotFound,
-                    "{}, env: {}",
-                    ident.value,
-                    self.env
-                        .columns
-                        .read()
-                        .unwrap()
-                        .iter()
-                        .map(|(k, v)| format!("{}:{}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ))?;
+                let id = id.ok_or_else(|| self.column_not_found(&ident.value))?;
                 Ok(Expression::col_ref(id))
             }
             sqlparser::ast::Expr::CompoundIdentifier(idents) => {
@@ -980,23 +2007,13 @@ impl Translator {
                 } else {
                     self.env.get(&name)
                 };
-                let id = id.ok_or(translation_err!(
-                    ColumnNotFound,
-                    "{}, env: {}",
-                    name,
-                    self.env
-                        .columns
-                        .read()
-                        .unwrap()
-                        .iter()
-                        .map(|(k, v)| format!("{}:{}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ))?;
+                let id = id.ok_or_else(|| self.column_not_found(&name))?;
                 Ok(Expression::col_ref(id))
             }
             sqlparser::ast::Expr::BinaryOp { left, op, right } => {
                 use sqlparser::ast::BinaryOperator::*;
+                let left_is_interval = matches!(**left, sqlparser::ast::Expr::Interval(_));
+                let right_is_interval = matches!(**right, sqlparser::ast::Expr::Interval(_));
                 let left = self.process_expr(left, distance)?;
                 let right = self.process_expr(right, distance)?;
                 let bin_op = match op {
@@ -1020,10 +2037,167 @@ impl Translator {
                         ));
                     }
                 };
+                let (left, right) = match bin_op {
+                    BinaryOp::Eq
+                    | BinaryOp::Neq
+                    | BinaryOp::Lt
+                    | BinaryOp::Gt
+                    | BinaryOp::Le
+                    | BinaryOp::Ge => {
+                        let left = self.coerce_literal(&right, left)?;
+                        let right = self.coerce_literal(&left, right)?;
+                        (left, right)
+                    }
+                    BinaryOp::Add | BinaryOp::Sub if right_is_interval && self.is_date(&left) => {
+                        date_and_interval(left, right)
+                    }
+                    BinaryOp::Add if left_is_interval && self.is_date(&right) => {
+                        let (right, left) = date_and_interval(right, left);
+                        (left, right)
+                    }
+                    _ => (left, right),
+                };
                 Ok(Expression::binary(bin_op, left, right))
             }
+            sqlparser::ast::Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let expr = self.process_expr(expr, distance)?;
+                let low = self.coerce_literal(&expr, self.process_expr(low, distance)?)?;
+                let high = self.coerce_literal(&expr, self.process_expr(high, distance)?)?;
+                Ok(if *negated {
+                    Expression::binary(
+                        BinaryOp::Or,
+                        Expression::binary(BinaryOp::Lt, expr.clone(), low),
+                        Expression::binary(BinaryOp::Gt, expr, high),
+                    )
+                } else {
+                    Expression::binary(
+                        BinaryOp::And,
+                        Expression::binary(BinaryOp::Ge, expr.clone(), low),
+                        Expression::binary(BinaryOp::Le, expr, high),
+                    )
+                })
+            }
+            sqlparser::ast::Expr::IsNull(expr) => Ok(Expression::is_null(
+                self.process_expr(expr, distance)?,
+                false,
+            )),
+            sqlparser::ast::Expr::Cast {
+                expr,
+                data_type,
+                format,
+            } => {
+                let dtype = cast_type(data_type, format)?;
+                match self.process_expr(expr, distance)? {
+                    // Literals are converted right away.
+                    Expression::Field { val } => Ok(Expression::Field {
+                        val: val
+                            .cast_to(&dtype)
+                            .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+                    }),
+                    expr => Ok(Expression::cast(expr, dtype)),
+                }
+            }
+            sqlparser::ast::Expr::IsNotNull(expr) => Ok(Expression::is_null(
+                self.process_expr(expr, distance)?,
+                true,
+            )),
+            sqlparser::ast::Expr::Function(_)
+            | sqlparser::ast::Expr::Substring { .. }
+            | sqlparser::ast::Expr::Trim { .. }
+            | sqlparser::ast::Expr::Extract { .. } => {
+                let Some((func, args)) = scalar_call(expr)? else {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "Unsupported function: {}",
+                        expr
+                    ));
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| self.process_expr(arg, distance))
+                    .collect::<Result<Vec<_>, _>>()?;
+                scalar_function(func, args)
+            }
+            sqlparser::ast::Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let expr = self.process_expr(expr, distance)?;
+                let items = list
+                    .iter()
+                    .map(|item| self.coerce_literal(&expr, self.process_expr(item, distance)?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // A list of literals is looked up as a whole.
+                let values: Option<Vec<Field>> = items
+                    .iter()
+                    .map(|item| match item {
+                        Expression::Field { val } => Some(val.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                if let Some(values) = values.filter(|values| !values.is_empty()) {
+                    return Ok(Expression::in_list(expr, values, *negated));
+                }
+                // Otherwise `a IN (x, y)` is `a = x OR a = y`, `a NOT IN (x, y)` is
+                // `a <> x AND a <> y`.
+                let (compare, combine) = if *negated {
+                    (BinaryOp::Neq, BinaryOp::And)
+                } else {
+                    (BinaryOp::Eq, BinaryOp::Or)
+                };
+                let mut result: Option<Expression<LogicalRelExpr>> = None;
+                for item in items {
+                    let test = Expression::binary(compare, expr.clone(), item);
+                    result = Some(match result {
+                        Some(result) => Expression::binary(combine, result, test),
+                        None => test,
+                    });
+                }
+                result.ok_or(translation_err!(InvalidSQL, "IN list must not be empty"))
+            }
+            sqlparser::ast::Expr::Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            } => {
+                let expr = self.process_expr(expr, distance)?;
+                let pattern = match (self.process_expr(pattern, distance)?, escape_char) {
+                    // Patterns are matched with `\` as the escape character.
+                    (
+                        Expression::Field {
+                            val: Field::String(pattern),
+                        },
+                        Some(escape),
+                    ) => Expression::Field {
+                        val: Field::String(with_escape(&pattern, *escape)),
+                    },
+                    (_, Some(escape)) if *escape != LIKE_ESCAPE => {
+                        return Err(translation_err!(
+                            UnsupportedSQL,
+                            "ESCAPE '{}' is only supported with a literal pattern",
+                            escape
+                        ));
+                    }
+                    (pattern, _) => pattern,
+                };
+                let op = if *negated {
+                    BinaryOp::NotLike
+                } else {
+                    BinaryOp::Like
+                };
+                Ok(Expression::binary(op, expr, pattern))
+            }
             sqlparser::ast::Expr::Value(value) => match value {
-                sqlparser::ast::Value::Number(num, _) => Ok(Expression::int(num.parse().unwrap())),
+                sqlparser::ast::Value::Number(num, _) => Ok(Expression::Field {
+                    val: number_literal(num).map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+                }),
                 sqlparser::ast::Value::SingleQuotedString(s)
                 | sqlparser::ast::Value::DoubleQuotedString(s) => Ok(Expression::Field {
                     val: Field::String(s.clone()),
@@ -1032,6 +2206,18 @@ impl Translator {
                     val: Field::Bool(*b),
                 }),
                 sqlparser::ast::Value::Null => Ok(Expression::Field { val: Field::Null }),
+                sqlparser::ast::Value::Placeholder(placeholder) => {
+                    match placeholder.strip_prefix('$').map(str::parse::<usize>) {
+                        Some(Ok(index)) if index > 0 => {
+                            Ok(Expression::Parameter { index, dtype: None })
+                        }
+                        _ => Err(translation_err!(
+                            UnsupportedSQL,
+                            "Unsupported placeholder {}, expected $1, $2, ...",
+                            placeholder
+                        )),
+                    }
+                }
                 _ => Err(translation_err!(
                     UnsupportedSQL,
                     "Unsupported value: {:?}",
@@ -1044,6 +2230,7 @@ impl Translator {
                     &self.enabled_rules,
                     &self.col_id_gen,
                     &self.env,
+                    &self.subqueries,
                 );
                 let subquery = translator.process_query(subquery)?;
                 let mut plan = subquery.plan;
@@ -1094,8 +2281,52 @@ impl Translator {
                 );
                 Ok(Expression::subquery(plan))
             }
+            sqlparser::ast::Expr::Subquery(query) => {
+                let plan = self.process_subquery(query)?;
+                if !plan.free().is_empty() {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "Correlated subqueries are not supported"
+                    ));
+                }
+                Ok(Expression::subquery(plan))
+            }
+            sqlparser::ast::Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                // `a IN (...)` is `a = ANY (...)`, `a NOT IN (...)` is `a <> ALL (...)`.
+                let (op, all) = if *negated {
+                    (BinaryOp::Neq, true)
+                } else {
+                    (BinaryOp::Eq, false)
+                };
+                self.process_quantified(expr, op, all, subquery, distance)
+            }
+            sqlparser::ast::Expr::AnyOp {
+                left,
+                compare_op,
+                right,
+            }
+            | sqlparser::ast::Expr::AllOp {
+                left,
+                compare_op,
+                right,
+            } => {
+                let sqlparser::ast::Expr::Subquery(subquery) = right.as_ref() else {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "ANY and ALL are only supported with a subquery"
+                    ));
+                };
+                let all = matches!(expr, sqlparser::ast::Expr::AllOp { .. });
+                let op = comparison_op(compare_op)?;
+                self.process_quantified(left, op, all, subquery, distance)
+            }
             sqlparser::ast::Expr::TypedString { data_type, value } => {
-                let dtype = get_attr(data_type).unwrap();
+                let dtype =
+                    get_attr(data_type).map_err(|e| translation_err!(UnsupportedSQL, "{}", e))?;
                 let expr = match dtype {
                     DataType::BigInt | DataType::SmallInt | DataType::Int => Expression::int(
                         value
@@ -1123,10 +2354,61 @@ impl Translator {
                         val: Field::from_str_to_decimal(value, precision, scale)
                             .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
                     },
+                    DataType::Float64 => Expression::Field {
+                        val: Field::from_str_to_float(value)
+                            .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+                    },
+                    DataType::Timestamp => Expression::Field {
+                        val: Field::from_str_to_timestamp(value)
+                            .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+                    },
                 };
                 Ok(expr)
             }
+            sqlparser::ast::Expr::Interval(interval) => {
+                Ok(Expression::int(interval_micros(interval)?))
+            }
+            sqlparser::ast::Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let operand = operand
+                    .as_ref()
+                    .map(|operand| self.process_expr(operand, distance))
+                    .transpose()?;
+                let whens = conditions
+                    .iter()
+                    .zip(results)
+                    .map(|(when, then)| {
+                        let when = self.process_expr(when, distance)?;
+                        // The values of `CASE x WHEN ..` are compared with x.
+                        let when = match &operand {
+                            Some(operand) => self.coerce_literal(operand, when)?,
+                            None => when,
+                        };
+                        Ok((when, self.process_expr(then, distance)?))
+                    })
+                    .collect::<Result<Vec<_>, TranslatorError>>()?;
+                // Without ELSE a CASE that matches no branch is NULL.
+                let else_expr = match else_result {
+                    Some(else_result) => self.process_expr(else_result, distance)?,
+                    None => Expression::Field { val: Field::Null },
+                };
+                let case = Expression::case(operand, whens, else_expr);
+                if case.has_correlated_subquery() {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "Correlated subqueries are not supported in CASE"
+                    ));
+                }
+                Ok(case)
+            }
             sqlparser::ast::Expr::Nested(expr) => self.process_expr(expr, distance),
+            sqlparser::ast::Expr::UnaryOp { op, expr } => {
+                apply_unary_op(op, self.process_expr(expr, distance)?)
+            }
             _ => Err(translation_err!(
                 UnsupportedSQL,
                 "Unsupported expression: {:?}",
@@ -1136,7 +2418,279 @@ impl Translator {
     }
 }
 
+impl Translator {
+    /// Converts `literal`, compared with `other`, to the type of `other` if that
+    /// is known. Reports literals that do not parse as that type. A placeholder
+    /// takes the type, so that the value bound to it is converted when the
+    /// statement is executed.
+    fn coerce_literal(
+        &self,
+        other: &Expression<LogicalRelExpr>,
+        literal: Expression<LogicalRelExpr>,
+    ) -> Result<Expression<LogicalRelExpr>, TranslatorError> {
+        match (self.expr_type(other), literal) {
+            (Some(dtype), Expression::Field { val }) => Ok(Expression::Field {
+                val: val
+                    .coerce_to(&dtype)
+                    .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+            }),
+            (Some(dtype), Expression::Parameter { index, .. }) => Ok(Expression::Parameter {
+                index,
+                dtype: Some(dtype),
+            }),
+            (_, literal) => Ok(literal),
+        }
+    }
+
+    /// Type of `expr` if it is a table column, a cast, a scalar function or a
+    /// date moved by a number of days. None for other expressions.
+    fn expr_type(&self, expr: &Expression<LogicalRelExpr>) -> Option<DataType> {
+        match expr {
+            Expression::ColRef { id } => self.env.column_type(*id),
+            Expression::Cast { dtype, .. } => Some(dtype.clone()),
+            Expression::ScalarFunction { func, .. } => Some(func.return_type()),
+            Expression::Binary {
+                op: BinaryOp::Add | BinaryOp::Sub,
+                left,
+                right,
+            } => match (self.expr_type(left), self.expr_type(right)) {
+                (Some(DataType::Date), Some(DataType::Date)) => Some(DataType::BigInt),
+                (Some(DataType::Date), _) | (_, Some(DataType::Date)) => Some(DataType::Date),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn is_date(&self, expr: &Expression<LogicalRelExpr>) -> bool {
+        self.expr_type(expr) == Some(DataType::Date)
+    }
+}
+
 // Helper functions
+
+/// Converts a numeric literal. Integers become ints and numbers with a
+/// fractional part decimals. Numbers written with an exponent, and those with
+/// too many digits for a decimal, become floats.
+pub(crate) fn number_literal(num: &str) -> Result<Field, FairyError> {
+    if let Ok(i) = num.parse() {
+        return Ok(Field::BigInt(i));
+    }
+    if !num.contains(['e', 'E']) {
+        let decimal =
+            Field::from_str_to_decimal(num, default_decimal_precision(), default_decimal_scale());
+        if decimal.is_ok() {
+            return decimal;
+        }
+    }
+    Field::from_str_to_float(num)
+}
+
+/// Target type of a `CAST`.
+fn cast_type(
+    data_type: &ast::DataType,
+    format: &Option<ast::CastFormat>,
+) -> Result<DataType, TranslatorError> {
+    if format.is_some() {
+        return Err(translation_err!(
+            UnsupportedSQL,
+            "CAST with a FORMAT is not supported"
+        ));
+    }
+    get_attr(data_type).map_err(|e| translation_err!(UnsupportedSQL, "{}", e))
+}
+
+/// The scalar function that `expr` calls and its arguments, or None if `expr`
+/// calls another function, e.g. an aggregate.
+fn scalar_call(expr: &ast::Expr) -> Result<Option<(ScalarFunc, Vec<ast::Expr>)>, TranslatorError> {
+    let (func, args) = match expr {
+        ast::Expr::Function(function) => {
+            let Some(func) = ScalarFunc::from_name(&get_table_name(&function.name)) else {
+                return Ok(None);
+            };
+            if function.distinct
+                || function.filter.is_some()
+                || function.over.is_some()
+                || !function.order_by.is_empty()
+            {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "{} does not take DISTINCT, FILTER, ORDER BY or OVER",
+                    func
+                ));
+            }
+            let args = function
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(arg)) => Ok(arg.clone()),
+                    _ => Err(translation_err!(
+                        UnsupportedSQL,
+                        "Unsupported argument of {}: {}",
+                        func,
+                        arg
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            (func, args)
+        }
+        ast::Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+            ..
+        } => {
+            // Without FROM the substring starts at the first character.
+            let from = substring_from
+                .as_deref()
+                .cloned()
+                .unwrap_or_else(|| ast::Expr::Value(ast::Value::Number("1".to_string(), false)));
+            let mut args = vec![expr.as_ref().clone(), from];
+            args.extend(substring_for.as_deref().cloned());
+            (ScalarFunc::Substr, args)
+        }
+        ast::Expr::Trim {
+            expr,
+            trim_where,
+            trim_what,
+            trim_characters,
+        } => {
+            if trim_characters.is_some() {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "TRIM with a list of characters is not supported, use TRIM(chars FROM expr)"
+                ));
+            }
+            let func = match trim_where {
+                Some(ast::TrimWhereField::Leading) => ScalarFunc::LTrim,
+                Some(ast::TrimWhereField::Trailing) => ScalarFunc::RTrim,
+                Some(ast::TrimWhereField::Both) | None => ScalarFunc::Trim,
+            };
+            let mut args = vec![expr.as_ref().clone()];
+            args.extend(trim_what.as_deref().cloned());
+            (func, args)
+        }
+        ast::Expr::Extract { field, expr } => {
+            let func = match field {
+                ast::DateTimeField::Year => ScalarFunc::Year,
+                ast::DateTimeField::Month => ScalarFunc::Month,
+                ast::DateTimeField::Day => ScalarFunc::Day,
+                _ => {
+                    return Err(translation_err!(
+                        UnsupportedSQL,
+                        "EXTRACT of {} is not supported, use YEAR, MONTH or DAY",
+                        field
+                    ))
+                }
+            };
+            (func, vec![expr.as_ref().clone()])
+        }
+        _ => return Ok(None),
+    };
+    func.check_arity(args.len())
+        .map_err(|e| translation_err!(InvalidSQL, "{}", e))?;
+    Ok(Some((func, args)))
+}
+
+/// Operands for adding `interval` to or subtracting it from `date`. Whole days
+/// become a number of days, which keeps the result a date. Other intervals are
+/// applied to the date as a timestamp.
+fn date_and_interval(
+    date: Expression<LogicalRelExpr>,
+    interval: Expression<LogicalRelExpr>,
+) -> (Expression<LogicalRelExpr>, Expression<LogicalRelExpr>) {
+    match interval {
+        Expression::Field {
+            val: Field::BigInt(micros),
+        } if micros % MICROS_PER_DAY == 0 => (date, Expression::int(micros / MICROS_PER_DAY)),
+        interval => (Expression::cast(date, DataType::Timestamp), interval),
+    }
+}
+
+/// Calls `func` on `args`. Calls on literals are evaluated right away.
+fn scalar_function(
+    func: ScalarFunc,
+    args: Vec<Expression<LogicalRelExpr>>,
+) -> Result<Expression<LogicalRelExpr>, TranslatorError> {
+    let vals: Option<Vec<Field>> = args
+        .iter()
+        .map(|arg| match arg {
+            Expression::Field { val } => Some(val.clone()),
+            _ => None,
+        })
+        .collect();
+    match vals {
+        Some(vals) => Ok(Expression::Field {
+            val: func
+                .eval(&vals)
+                .map_err(|e| translation_err!(InvalidSQL, "{}", e))?,
+        }),
+        None => Ok(Expression::scalar_function(func, args)),
+    }
+}
+
+/// Units of `INTERVAL` literals and their length in microseconds. Months and
+/// years vary in length, so they are not supported.
+const INTERVAL_UNITS: &[(&[&str], i64)] = &[
+    (&["microsecond", "microseconds", "us"], 1),
+    (&["millisecond", "milliseconds", "ms"], 1_000),
+    (&["second", "seconds", "sec", "secs", "s"], 1_000_000),
+    (&["minute", "minutes", "min", "mins"], 60_000_000),
+    (&["hour", "hours", "h"], 3_600_000_000),
+    (&["day", "days", "d"], 86_400_000_000),
+    (&["week", "weeks", "w"], 604_800_000_000),
+];
+
+/// Converts an interval, written as `INTERVAL '90' MINUTE` or as
+/// `INTERVAL '1 day 2 hours'`, to microseconds, which are added to or
+/// subtracted from timestamps.
+fn interval_micros(interval: &sqlparser::ast::Interval) -> Result<i64, TranslatorError> {
+    if interval.last_field.is_some() {
+        return Err(translation_err!(
+            UnsupportedSQL,
+            "Intervals with a range of units are not supported: {}",
+            interval
+        ));
+    }
+    let value = match interval.value.as_ref() {
+        sqlparser::ast::Expr::Value(
+            sqlparser::ast::Value::SingleQuotedString(s) | sqlparser::ast::Value::Number(s, _),
+        ) => s.clone(),
+        _ => {
+            return Err(translation_err!(
+                UnsupportedSQL,
+                "Only literal intervals are supported: {}",
+                interval
+            ))
+        }
+    };
+    let text = match &interval.leading_field {
+        Some(field) => format!("{} {}", value, field),
+        None => value,
+    };
+    let invalid = || translation_err!(InvalidSQL, "Invalid interval '{}'", text);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() || !words.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    let mut micros: i64 = 0;
+    for pair in words.chunks(2) {
+        let count: i64 = pair[0].parse().map_err(|_| invalid())?;
+        let unit = pair[1].to_lowercase();
+        let (_, length) = INTERVAL_UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit.as_str()))
+            .ok_or_else(|| {
+                translation_err!(UnsupportedSQL, "Unsupported interval unit {}", pair[1])
+            })?;
+        micros = count
+            .checked_mul(*length)
+            .and_then(|m| micros.checked_add(m))
+            .ok_or_else(invalid)?;
+    }
+    Ok(micros)
+}
+
 fn get_table_name(name: &sqlparser::ast::ObjectName) -> String {
     name.0
         .iter()
@@ -1145,25 +2699,196 @@ fn get_table_name(name: &sqlparser::ast::ObjectName) -> String {
         .join(".")
 }
 
+/// The column `name` of one input of a USING or NATURAL join.
+fn join_column(
+    name: &str,
+    cols: &HashMap<String, Vec<ColumnId>>,
+    side: &str,
+) -> Result<ColumnId, TranslatorError> {
+    match cols.get(name).map(Vec::as_slice) {
+        Some([id]) => Ok(*id),
+        Some(_) => Err(TranslatorError::ColumnNotFound {
+            name: format!("{} (ambiguous in the {} input of the join)", name, side),
+            suggestion: None,
+        }),
+        None => Err(TranslatorError::ColumnNotFound {
+            name: name.to_string(),
+            suggestion: closest_match(name, cols.keys().map(String::as_str)),
+        }),
+    }
+}
+
 fn is_valid_alias(alias: &str) -> bool {
     alias.chars().all(|c| c.is_alphanumeric() || c == '_')
 }
 
-fn has_agg(expr: &sqlparser::ast::Expr) -> bool {
+/// Splits a condition into the conditions that are combined with AND.
+fn split_conjunction(expr: &sqlparser::ast::Expr) -> Vec<&sqlparser::ast::Expr> {
+    match expr {
+        sqlparser::ast::Expr::BinaryOp {
+            left,
+            op: sqlparser::ast::BinaryOperator::And,
+            right,
+        } => {
+            let mut conjuncts = split_conjunction(left);
+            conjuncts.extend(split_conjunction(right));
+            conjuncts
+        }
+        sqlparser::ast::Expr::Nested(expr) => match expr.as_ref() {
+            sqlparser::ast::Expr::BinaryOp {
+                op: sqlparser::ast::BinaryOperator::And,
+                ..
+            } => split_conjunction(expr),
+            expr => vec![expr],
+        },
+        _ => vec![expr],
+    }
+}
+
+/// Converts the comparison of an ANY or ALL subquery.
+fn comparison_op(op: &sqlparser::ast::BinaryOperator) -> Result<BinaryOp, TranslatorError> {
+    use sqlparser::ast::BinaryOperator::*;
+    match op {
+        Eq => Ok(BinaryOp::Eq),
+        NotEq => Ok(BinaryOp::Neq),
+        Lt => Ok(BinaryOp::Lt),
+        Gt => Ok(BinaryOp::Gt),
+        LtEq => Ok(BinaryOp::Le),
+        GtEq => Ok(BinaryOp::Ge),
+        _ => Err(translation_err!(
+            UnsupportedSQL,
+            "Unsupported comparison for ANY or ALL: {:?}",
+            op
+        )),
+    }
+}
+
+/// Returns true if `expr` calls an aggregation function. Fails if an
+/// aggregation is nested in the argument of another one, e.g. SUM(MAX(a)).
+fn has_agg(expr: &sqlparser::ast::Expr) -> Result<bool, TranslatorError> {
     use sqlparser::ast::Expr::*;
     match expr {
-        Identifier(_) => false,
-        CompoundIdentifier(_) => false,
-        Value(_) => false,
-        TypedString { .. } => false,
-
-        BinaryOp { left, op: _, right } => has_agg(left) || has_agg(right),
-        Function(function) => matches!(
-            get_table_name(&function.name).to_uppercase().as_str(),
-            "COUNT" | "SUM" | "AVG" | "MIN" | "MAX"
+        Identifier(_) => Ok(false),
+        CompoundIdentifier(_) => Ok(false),
+        Value(_) => Ok(false),
+        TypedString { .. } => Ok(false),
+        Interval(_) => Ok(false),
+
+        BinaryOp { left, op: _, right } => any_agg([left.as_ref(), right]),
+        Between {
+            expr, low, high, ..
+        } => any_agg([expr.as_ref(), low, high]),
+        InList { expr, list, .. } => any_agg(std::iter::once(expr.as_ref()).chain(list)),
+        Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => any_agg(
+            operand
+                .as_deref()
+                .into_iter()
+                .chain(conditions)
+                .chain(results)
+                .chain(else_result.as_deref()),
+        ),
+        IsNull(expr) | IsNotNull(expr) => has_agg(expr),
+        Cast { expr, .. } => has_agg(expr),
+        InSubquery { expr, .. } => has_agg(expr),
+        AnyOp { left, .. } | AllOp { left, .. } => has_agg(left),
+        Function(function) => {
+            let args = function.args.iter().filter_map(|arg| match arg {
+                ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(arg))
+                | ast::FunctionArg::Named {
+                    arg: ast::FunctionArgExpr::Expr(arg),
+                    ..
+                } => Some(arg),
+                _ => None,
+            });
+            let name = get_table_name(&function.name).to_uppercase();
+            if AggOp::from_name(&name).is_none() {
+                return any_agg(args);
+            }
+            if any_agg(args)? {
+                return Err(translation_err!(
+                    UnsupportedSQL,
+                    "Aggregation functions cannot be nested: {}",
+                    expr
+                ));
+            }
+            Ok(true)
+        }
+        Substring {
+            expr,
+            substring_from,
+            substring_for,
+            ..
+        } => any_agg(
+            std::iter::once(expr.as_ref())
+                .chain(substring_from.as_deref())
+                .chain(substring_for.as_deref()),
         ),
+        Trim {
+            expr, trim_what, ..
+        } => any_agg(std::iter::once(expr.as_ref()).chain(trim_what.as_deref())),
+        Extract { expr, .. } => has_agg(expr),
         Nested(expr) => has_agg(expr),
-        _ => unimplemented!("Unsupported expression: {:?}", expr),
+        UnaryOp { expr, .. } => has_agg(expr),
+        // Anything else is rejected when the expression is translated.
+        _ => Ok(false),
+    }
+}
+
+/// Returns true if any of `exprs` calls an aggregation function, see `has_agg`.
+fn any_agg<'a>(
+    exprs: impl IntoIterator<Item = &'a sqlparser::ast::Expr>,
+) -> Result<bool, TranslatorError> {
+    let mut res = false;
+    for expr in exprs {
+        res |= has_agg(expr)?;
+    }
+    Ok(res)
+}
+
+/// Applies a unary plus, minus or NOT to a translated expression. Minus folds
+/// into numeric literals and is `0 - expr` otherwise. NOT folds into bool
+/// literals and is `expr = false` otherwise.
+fn apply_unary_op(
+    op: &sqlparser::ast::UnaryOperator,
+    expr: Expression<LogicalRelExpr>,
+) -> Result<Expression<LogicalRelExpr>, TranslatorError> {
+    match op {
+        sqlparser::ast::UnaryOperator::Plus => Ok(expr),
+        sqlparser::ast::UnaryOperator::Minus => match expr {
+            Expression::Field {
+                val: Field::BigInt(i),
+            } => Ok(Expression::int(-i)),
+            Expression::Field {
+                val: Field::Decimal(w, s),
+            } => Ok(Expression::Field {
+                val: Field::Decimal(-w, s),
+            }),
+            expr => Ok(Expression::binary(BinaryOp::Sub, Expression::int(0), expr)),
+        },
+        sqlparser::ast::UnaryOperator::Not => match expr {
+            Expression::Field {
+                val: Field::Bool(b),
+            } => Ok(Expression::Field {
+                val: Field::Bool(!b),
+            }),
+            expr => Ok(Expression::binary(
+                BinaryOp::Eq,
+                expr,
+                Expression::Field {
+                    val: Field::Bool(false),
+                },
+            )),
+        },
+        _ => Err(translation_err!(
+            UnsupportedSQL,
+            "Unsupported unary operator: {:?}",
+            op
+        )),
     }
 }
 
@@ -1184,7 +2909,7 @@ mod tests {
         let t1_names = vec!["a", "b", "p", "q", "r"];
         let t1_dtypes = (0..5).map(|_| DataType::BigInt).collect::<Vec<_>>();
         let t1_schema = TableSchema::from_vecs(t1_names, t1_dtypes);
-        let t1_cid = catalog.get_table_id(&t1_table_name);
+        let t1_cid = catalog.get_table_id(&t1_table_name).unwrap();
         let t1_table = TableInfo::new(t1_cid, t1_table_name, t1_schema);
         catalog.add_table(t1_table);
 
@@ -1192,7 +2917,7 @@ mod tests {
         let t2_names = vec!["c", "d"];
         let t2_dtypes = (0..2).map(|_| DataType::BigInt).collect::<Vec<_>>();
         let t2_schema = TableSchema::from_vecs(t2_names, t2_dtypes);
-        let t2_cid = catalog.get_table_id(&t2_table_name);
+        let t2_cid = catalog.get_table_id(&t2_table_name).unwrap();
         let t2_table = TableInfo::new(t2_cid, t2_table_name, t2_schema);
         catalog.add_table(t2_table);
 
@@ -1200,7 +2925,7 @@ mod tests {
         let t3_names = vec!["e", "f"];
         let t3_dtypes = (0..2).map(|_| DataType::BigInt).collect::<Vec<_>>();
         let t3_schema = TableSchema::from_vecs(t3_names, t3_dtypes);
-        let t3_cid = catalog.get_table_id(&t3_table_name);
+        let t3_cid = catalog.get_table_id(&t3_table_name).unwrap();
         let t3_table = TableInfo::new(t3_cid, t3_table_name, t3_schema);
         catalog.add_table(t3_table);
 
@@ -1245,6 +2970,75 @@ mod tests {
         query.plan.pretty_string()
     }
 
+    /// Checks that the physical plan of `sql` has `joins` joins, hash joins for
+    /// equi-joins and nested loop joins otherwise, and no cross product.
+    fn assert_key_joins(sql: &str, joins: usize) {
+        let query = parse_sql(sql);
+        let plan = get_translator()
+            .process_query(&query)
+            .unwrap()
+            .plan
+            .to_physical_plan();
+        let printed = plan.pretty_string();
+        let count = |prefix: &str| {
+            printed
+                .lines()
+                .filter(|line| line.trim().starts_with(prefix))
+                .count()
+        };
+        let hash_joins = count("-> Hash inner_join(");
+        let nested_loop_joins = count("-> Nested loop inner_join(");
+        assert_eq!(hash_joins + nested_loop_joins, joins, "{}", printed);
+        assert_eq!(sql.contains('='), hash_joins > 0, "{}", printed);
+        assert!(!plan.has_cross_product(), "{}", printed);
+    }
+
+    #[test]
+    fn has_agg_looks_into_scalar_functions() {
+        use sqlparser::dialect::GenericDialect;
+        use sqlparser::parser::Parser;
+
+        let has_agg = |sql: &str| {
+            let expr = Parser::new(&GenericDialect {})
+                .try_with_sql(sql)
+                .unwrap()
+                .parse_expr()
+                .unwrap();
+            super::has_agg(&expr).unwrap()
+        };
+        assert!(!has_agg("UPPER(a)"));
+        assert!(!has_agg("TRIM(LEADING 'x' FROM a)"));
+        assert!(has_agg("UPPER(MAX(a))"));
+        assert!(has_agg("LENGTH(a) + COUNT(*)"));
+        assert!(has_agg("SUBSTRING(a FROM 1 FOR MIN(b))"));
+    }
+
+    #[test]
+    fn in_list_of_non_literals_is_compared_item_by_item() {
+        let select = |sql: &str| {
+            let plan = get_plan(sql);
+            plan.lines()
+                .find_map(|line| line.trim().strip_prefix("-> select("))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            select("SELECT a FROM t1 WHERE a IN (1, 2, 3, 4, 5, 6, 7, 8, 9)"),
+            "@10000 IN (1, 2, 3, 4, 5, 6, 7, 8, 9))"
+        );
+        // One column among the members, even in a list long enough for a hash
+        // set, turns the whole list into a chain of comparisons.
+        assert_eq!(
+            select("SELECT a FROM t1 WHERE a IN (b, 1, 2, 3, 4, 5, 6, 7, 8, 9)"),
+            "@10000=@10001||@10000=1||@10000=2||@10000=3||@10000=4||@10000=5||\
+             @10000=6||@10000=7||@10000=8||@10000=9)"
+        );
+        assert_eq!(
+            select("SELECT a FROM t1 WHERE a NOT IN (b, 1 + 1)"),
+            "@10000!=@10001 && @10000!=1+1)"
+        );
+    }
+
     #[test]
     fn parse_simple_select() {
         let sql = "SELECT a, b, p, q, r FROM t1";
@@ -1279,20 +3073,36 @@ mod tests {
     fn parse_cross_join() {
         let sql = "SELECT * FROM t1, t2, t3 WHERE a = c AND b = d AND r = e";
         println!("{}", get_plan(sql));
-
-        // Should become inner join
+        assert_key_joins(sql, 2);
     }
 
     #[test]
     fn parse_cross_join2() {
         let sql = "SELECT * FROM t1, t2, t3 WHERE a = c AND b = d AND r = e AND a = 1 AND c = 2 AND e = 3";
         println!("{}", get_plan(sql));
+        assert_key_joins(sql, 2);
     }
 
     #[test]
     fn parse_cross_join3() {
         let sql = "SELECT * FROM t1, t2 WHERE a = c AND b = 2";
         println!("{}", get_plan(sql));
+        assert_key_joins(sql, 1);
+    }
+
+    #[test]
+    fn parse_cross_product() {
+        let sql = "SELECT * FROM t1, t2 WHERE a = 1";
+        let query = parse_sql(sql);
+        let plan = get_translator()
+            .process_query(&query)
+            .unwrap()
+            .plan
+            .to_physical_plan();
+        assert!(plan.has_cross_product(), "{}", plan.pretty_string());
+        assert!(plan.pretty_string().starts_with("-> Cross cross_join()"));
+        // A predicate relating the inputs is a join, if not an equi-join.
+        assert_key_joins("SELECT * FROM t1, t2 WHERE a < c", 1);
     }
 
     #[test]
@@ -1328,6 +3138,67 @@ mod tests {
         println!("{}", get_plan(sql));
     }
 
+    #[test]
+    fn join_using_and_natural() {
+        // The unqualified join column is the one of the left input.
+        let plan = get_plan("SELECT a, y.b FROM t1 x JOIN t1 y USING (a, b)");
+        assert!(plan.starts_with("-> project(@10000, @10006)"), "{}", plan);
+        assert!(
+            plan.contains("inner_join(@10000=@10005 && @10001=@10006)"),
+            "{}",
+            plan
+        );
+
+        let plan = get_plan("SELECT a FROM t1 x NATURAL JOIN t1 y WHERE p > 1");
+        assert!(
+            plan.contains(
+                "inner_join(@10000=@10005 && @10001=@10006 && @10002=@10007 && @10003=@10008 && @10004=@10009)"
+            ),
+            "{}",
+            plan
+        );
+        assert!(plan.contains("select(@10002>1)"), "{}", plan);
+
+        // `*` lists the join column once, first.
+        let plan = get_plan("SELECT * FROM t1 x JOIN t1 y USING (b)");
+        assert!(
+            plan.starts_with(
+                "-> project(@10001, @10000, @10002, @10003, @10004, @10005, @10007, @10008, @10009)"
+            ),
+            "{}",
+            plan
+        );
+
+        // Only the right input has all the values of a RIGHT JOIN.
+        let plan = get_plan("SELECT a FROM t1 x RIGHT JOIN t1 y USING (a)");
+        assert!(plan.starts_with("-> project(@10005)"), "{}", plan);
+        assert!(plan.contains("right_outer_join(@10000=@10005)"), "{}", plan);
+
+        // Tables without common columns are not compared.
+        let plan = get_plan("SELECT * FROM t1 NATURAL JOIN t2");
+        assert!(plan.starts_with("-> cross_join()"), "{}", plan);
+
+        for (sql, msg) in [
+            ("SELECT * FROM t1 JOIN t2 USING (a)", "Column not found: a"),
+            (
+                "SELECT * FROM t1 x JOIN t1 y USING (bb)",
+                "Column not found: bb, did you mean b?",
+            ),
+            (
+                "SELECT * FROM t1 x JOIN t1 y ON x.a = y.a JOIN t1 z USING (a)",
+                "Column not found: a (ambiguous in the left input of the join)",
+            ),
+            (
+                "SELECT * FROM t1 x FULL JOIN t1 y USING (a)",
+                "FULL OUTER JOIN with USING or NATURAL is not supported",
+            ),
+        ] {
+            let query = parse_sql(sql);
+            let err = get_translator().process_query(&query).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", sql, err);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn parse_from_with_subquery_joins() {
@@ -1347,6 +3218,69 @@ mod tests {
         println!("{}", get_plan(sql));
     }
 
+    #[test]
+    fn parse_where_in_list() {
+        let sql = "SELECT a FROM t1 WHERE a IN (1, 2, 3)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains(" IN (1, 2, 3)"));
+    }
+
+    #[test]
+    fn parse_where_placeholders() {
+        let plan = get_plan("SELECT a FROM t1 WHERE a = $1 AND b < $2");
+        assert!(plan.contains("$1") && plan.contains("$2"), "{}", plan);
+        for sql in [
+            "SELECT a FROM t1 WHERE a = ?",
+            "SELECT a FROM t1 WHERE a = $0",
+        ] {
+            let query = parse_sql(sql);
+            assert!(get_translator().process_query(&query).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn parse_where_not_in_list_with_null() {
+        let sql = "SELECT a FROM t1 WHERE a NOT IN (1, NULL)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains(" NOT IN (1, NULL)"));
+    }
+
+    #[test]
+    fn parse_where_in_list_of_columns() {
+        // Lists with expressions other than literals are compared item by item.
+        let sql = "SELECT a FROM t1 WHERE a IN (b, 1)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(!plan.contains(" IN ("));
+    }
+
+    #[test]
+    fn parse_case() {
+        let sql = "SELECT CASE WHEN a > 5 THEN 1 WHEN b > 5 THEN 2 ELSE 0 END FROM t1";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains("case when @"));
+        assert!(plan.contains(" then 2 else 0 end"));
+
+        // The simple form keeps its operand, and a missing ELSE is NULL.
+        let sql = "SELECT CASE a WHEN 1 THEN b END FROM t1";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains("case @"));
+        assert!(plan.contains(" when 1 then @"));
+        assert!(plan.contains(" else NULL end"));
+    }
+
+    #[test]
+    fn parse_case_with_aggregate() {
+        let sql = "SELECT CASE WHEN COUNT(*) > 1 THEN SUM(a) ELSE 0 END FROM t1";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains("case when @"));
+    }
+
     #[test]
     fn parse_subquery() {
         let sql = "SELECT a, x, y FROM t1, (SELECT COUNT(*) AS x, SUM(c) as y FROM t2 WHERE c = a)";
@@ -1379,25 +3313,197 @@ mod tests {
         println!("{}", get_plan(sql));
     }
 
+    #[test]
+    fn parse_subquery_in() {
+        let sql = "SELECT a FROM t1 WHERE a IN (SELECT c FROM t2)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains("=ANY λ"));
+    }
+
+    #[test]
+    fn parse_subquery_not_in() {
+        let sql = "SELECT a FROM t1 WHERE a NOT IN (SELECT c FROM t2 WHERE d > 1)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains("!=ALL λ"));
+    }
+
+    #[test]
+    fn parse_subquery_any_all() {
+        let sql =
+            "SELECT a FROM t1 WHERE b > ANY((SELECT d FROM t2)) AND b <= ALL((SELECT e FROM t3))";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.contains(">ANY λ") && plan.contains("<=ALL λ"));
+    }
+
+    #[test]
+    fn parse_subquery_in_correlated() {
+        // Becomes a join with the subquery, grouped by the columns of t1.
+        let sql = "SELECT a FROM t1 WHERE a IN (SELECT c FROM t2 WHERE d = b)";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(!plan.contains("ANY λ"));
+    }
+
+    #[test]
+    fn parse_subquery_any_correlated() {
+        let sql = "SELECT a FROM t1 WHERE b < ANY((SELECT d FROM t2 WHERE c = a)) AND p = 1";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(!plan.contains("ANY λ"));
+    }
+
+    #[test]
+    fn parse_subquery_not_in_correlated() {
+        let sql = "SELECT a FROM t1 WHERE a NOT IN (SELECT c FROM t2 WHERE d = b)";
+        let query = parse_sql(sql);
+        assert!(get_translator().process_query(&query).is_err());
+    }
+
     #[test]
     fn parse_joins_with_same_name() {
         let sql = "SELECT * FROM t1 as a, t1 as b";
         println!("{}", get_plan(sql));
     }
 
+    #[test]
+    fn parse_union() {
+        let sql = "SELECT a, b FROM t1 UNION ALL SELECT c, d FROM t2";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.starts_with("-> union"));
+
+        // Without ALL the duplicates are removed from the whole union.
+        let sql = "SELECT a FROM t1 UNION SELECT c FROM t2 UNION SELECT e FROM t3 ORDER BY a";
+        let plan = get_plan(sql);
+        println!("{}", plan);
+        assert!(plan.starts_with("-> order_by"));
+        assert_eq!(plan.matches("-> distinct").count(), 2);
+        assert_eq!(plan.matches("-> union").count(), 2);
+    }
+
+    #[test]
+    fn parse_union_mismatched_inputs() {
+        let sql = "SELECT a, b FROM t1 UNION SELECT c FROM t2";
+        let query = parse_sql(sql);
+        let err = get_translator().process_query(&query).unwrap_err();
+        assert!(err.to_string().contains("same number of columns"));
+
+        // The right input does not see the columns of the left one.
+        let sql = "SELECT a FROM t1 UNION SELECT b FROM t2";
+        let query = parse_sql(sql);
+        assert!(get_translator().process_query(&query).is_err());
+    }
+
     #[test]
     fn parser_aggregate() {
         let sql = "SELECT COUNT(a), SUM(b) FROM t1";
         println!("{}", get_plan(sql));
     }
 
-    // #[test]
-    // fn parse_subquery_where() {
-    //     let sql = "SELECT a FROM t1 WHERE exists (SELECT * FROM t2 WHERE c = a)";
-    //     println!("{}", get_plan(sql));
+    #[test]
+    fn malformed_aggregates_are_errors() {
+        for (sql, msg) in [
+            (
+                "SELECT SUM(MAX(a)) FROM t1",
+                "Aggregation functions cannot be nested: SUM(MAX(a))",
+            ),
+            (
+                "SELECT b, UPPER(COUNT(COUNT(*))) FROM t1 GROUP BY b",
+                "Aggregation functions cannot be nested: COUNT(COUNT(*))",
+            ),
+            (
+                "SELECT AVG(*) FROM t1",
+                "Wildcard is only supported for COUNT",
+            ),
+            ("SELECT MODE(a) FROM t1", "Unsupported function: MODE(a)"),
+            (
+                "SELECT SUM(a) + MODE(b) FROM t1",
+                "Unsupported aggregation function: MODE",
+            ),
+            (
+                "SELECT STDDEV(MEDIAN(a)) FROM t1",
+                "Aggregation functions cannot be nested: STDDEV(MEDIAN(a))",
+            ),
+            ("SELECT SUM(a, b) FROM t1", "SUM takes exactly one argument"),
+        ] {
+            let query = parse_sql(sql);
+            let err = get_translator().process_query(&query).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", sql, err);
+        }
+
+        // CASE inside and around aggregations is fine.
+        get_plan("SELECT SUM(CASE WHEN a > 1 THEN b ELSE 0 END) FROM t1");
+        get_plan("SELECT CASE WHEN SUM(a) > 1 THEN MAX(b) END FROM t1");
+    }
 
-    //     // left_outer_join not supported yet
-    // }
+    #[test]
+    fn qualified_wildcard_expands_in_catalog_order() {
+        let sql = "SELECT a.*, b.b FROM t1 a JOIN t1 b ON a.a = b.a";
+        let query = parse_sql(sql);
+        let mut translator = get_translator();
+        let query = translator.process_query(&query).unwrap();
+        let schema = translator.output_schema(&query.plan).unwrap();
+        let expected = ["a.a", "a.b", "a.p", "a.q", "a.r", "b.b"];
+        assert_eq!(schema.len(), expected.len());
+        for (col, name) in schema.iter().zip(expected) {
+            let names = query.env.get_names(col.id);
+            assert!(names.iter().any(|n| n == name), "{}: {:?}", name, names);
+        }
+
+        // Derived tables expand to the columns their query outputs.
+        let sql = "SELECT s.* FROM (SELECT q, a FROM t1) s";
+        let query = parse_sql(sql);
+        let mut translator = get_translator();
+        let query = translator.process_query(&query).unwrap();
+        let schema = translator.output_schema(&query.plan).unwrap();
+        let mut ids: Vec<_> = schema.iter().map(|c| c.id).collect();
+        ids.sort();
+        let mut expected = vec![query.env.get("s.q").unwrap(), query.env.get("s.a").unwrap()];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        // Aggregates next to a wildcard need its columns to be grouped.
+        get_plan("SELECT t2.*, COUNT(*) FROM t2 GROUP BY c, d");
+        for (sql, msg) in [
+            ("SELECT x.* FROM t1 a", "x"),
+            (
+                "SELECT a.*, COUNT(*) FROM t1 a GROUP BY a.a",
+                "Column a.b must appear in the GROUP BY clause",
+            ),
+        ] {
+            let query = parse_sql(sql);
+            let err = get_translator().process_query(&query).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", sql, err);
+        }
+    }
+
+    #[test]
+    fn parse_exists_as_semi_join() {
+        let physical = |sql| {
+            let query = parse_sql(sql);
+            let plan = get_translator().process_query(&query).unwrap().plan;
+            plan.to_physical_plan().pretty_string()
+        };
+        let plan = physical("SELECT a FROM t1 WHERE EXISTS (SELECT * FROM t2 WHERE c = a)");
+        assert!(plan.contains("Hash semi_join"), "{}", plan);
+        let plan =
+            physical("SELECT a FROM t1 WHERE NOT EXISTS (SELECT d FROM t2 WHERE c = a AND d > b)");
+        assert!(plan.contains("Hash anti_join"), "{}", plan);
+        // Without an equality with the outer query, or with a count that
+        // depends on more than the rows, EXISTS counts the rows of the query.
+        for sql in [
+            "SELECT a FROM t1 WHERE EXISTS (SELECT * FROM t2 WHERE c > 4)",
+            "SELECT a FROM t1 WHERE EXISTS (SELECT * FROM t2 WHERE c < a)",
+            "SELECT a FROM t1 WHERE EXISTS (SELECT * FROM t2 WHERE c = a LIMIT 0)",
+            "SELECT a FROM t1 WHERE EXISTS (SELECT COUNT(*) FROM t2 WHERE c = a)",
+        ] {
+            let plan = get_plan(sql);
+            assert!(!plan.contains("semi_join"), "{}: {}", sql, plan);
+        }
+    }
 }
 
 // Subquery types
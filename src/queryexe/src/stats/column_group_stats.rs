@@ -0,0 +1,165 @@
+use common::datatypes::compare_fields;
+use common::logical_expr::prelude::Expression;
+use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use common::{BinaryOp, Field, Tuple};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Number of the most frequent value combinations kept for a column group.
+pub const COLUMN_GROUP_TOP_K: usize = 100;
+
+/// Fraction of its records by which a table may grow or shrink after a column
+/// group was analyzed before the statistics of the group count as stale.
+pub const STALE_COLUMN_GROUP_CHANGE: f64 = 0.2;
+
+/// Joint distribution of a group of columns, computed by `ANALYZE t (a, b)`
+/// over every record of the table. Unlike the sample, it knows how often the
+/// most frequent combinations of values occur, even those the sample misses.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ColumnGroupStats {
+    /// Indexes of the columns in the schema, in the order of the group.
+    pub columns: Vec<usize>,
+    /// Number of records when the group was analyzed.
+    pub record_count: usize,
+    /// Number of distinct combinations of values.
+    pub distinct_count: usize,
+    /// The most frequent combinations and their number of records, most
+    /// frequent first.
+    pub top: Vec<(Vec<Field>, usize)>,
+}
+
+/// Counts the combinations of values of a column group one record at a time,
+/// so that ANALYZE does not hold the records of the table.
+pub struct ColumnGroupCounter {
+    columns: Vec<usize>,
+    counts: HashMap<Vec<Field>, usize>,
+    record_count: usize,
+}
+
+impl ColumnGroupCounter {
+    pub fn new(columns: Vec<usize>) -> Self {
+        ColumnGroupCounter {
+            columns,
+            counts: HashMap::new(),
+            record_count: 0,
+        }
+    }
+
+    pub fn add(&mut self, tuple: &Tuple) {
+        let key = self
+            .columns
+            .iter()
+            .map(|i| tuple.field_vals[*i].clone())
+            .collect();
+        *self.counts.entry(key).or_default() += 1;
+        self.record_count += 1;
+    }
+
+    /// The statistics of the group over the records added.
+    pub fn finish(self) -> ColumnGroupStats {
+        let distinct_count = self.counts.len();
+        let mut top: Vec<(Vec<Field>, usize)> = self.counts.into_iter().collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(COLUMN_GROUP_TOP_K);
+        ColumnGroupStats {
+            columns: self.columns,
+            record_count: self.record_count,
+            distinct_count,
+            top,
+        }
+    }
+}
+
+impl ColumnGroupStats {
+    /// Counts the combinations of values of `columns` in `tuples`.
+    pub fn analyze(columns: Vec<usize>, tuples: &[Tuple]) -> Self {
+        let mut counter = ColumnGroupCounter::new(columns);
+        for tuple in tuples {
+            counter.add(tuple);
+        }
+        counter.finish()
+    }
+
+    /// Whether the table changed too much since the group was analyzed, now
+    /// that it holds `record_count` records.
+    pub fn is_stale(&self, record_count: usize) -> bool {
+        record_count.abs_diff(self.record_count) as f64
+            > self.record_count.max(1) as f64 * STALE_COLUMN_GROUP_CHANGE
+    }
+
+    /// Fraction of the records whose columns equal `values`, given in the order
+    /// of the group. A combination outside the most frequent ones gets an even
+    /// share of the remaining records.
+    pub fn selectivity(&self, values: &[&Field]) -> f64 {
+        if self.record_count == 0 || values.iter().any(|v| matches!(v, Field::Null)) {
+            return 0.0;
+        }
+        let matches = |key: &[Field]| {
+            key.iter()
+                .zip(values)
+                .all(|(k, v)| compare_fields(BinaryOp::Eq, k, v))
+        };
+        if let Some((_, count)) = self.top.iter().find(|(key, _)| matches(key)) {
+            return *count as f64 / self.record_count as f64;
+        }
+        let top_records: usize = self.top.iter().map(|(_, count)| count).sum();
+        let rest = self.distinct_count - self.top.len();
+        if rest == 0 {
+            return 0.0;
+        }
+        (self.record_count - top_records) as f64 / rest as f64 / self.record_count as f64
+    }
+}
+
+/// Selectivity of the equalities between a column and a literal in
+/// `predicates` that fix the values of one of `groups`, the one with the most
+/// columns, along with the predicates left. Returns None if no group is
+/// covered.
+pub fn covered_group_selectivity(
+    groups: &[ColumnGroupStats],
+    predicates: &[Expression<PhysicalRelExpr>],
+) -> Option<(f64, Vec<Expression<PhysicalRelExpr>>)> {
+    let equalities: Vec<Option<(usize, &Field)>> =
+        predicates.iter().map(column_equals_literal).collect();
+    let position = |column: usize| {
+        equalities
+            .iter()
+            .position(|eq| eq.is_some_and(|(c, _)| c == column))
+    };
+    let (group, positions) = groups
+        .iter()
+        .filter_map(|group| {
+            let positions: Option<Vec<usize>> =
+                group.columns.iter().map(|c| position(*c)).collect();
+            positions.map(|positions| (group, positions))
+        })
+        .max_by_key(|(group, _)| group.columns.len())?;
+    let values: Vec<&Field> = positions
+        .iter()
+        .map(|i| equalities[*i].unwrap().1)
+        .collect();
+    let rest = predicates
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !positions.contains(i))
+        .map(|(_, p)| p.clone())
+        .collect();
+    Some((group.selectivity(&values), rest))
+}
+
+/// The column and the literal of a `column = literal` predicate.
+fn column_equals_literal(predicate: &Expression<PhysicalRelExpr>) -> Option<(usize, &Field)> {
+    let Expression::Binary {
+        op: BinaryOp::Eq,
+        left,
+        right,
+    } = predicate
+    else {
+        return None;
+    };
+    match (left.as_ref(), right.as_ref()) {
+        (Expression::ColRef { id }, Expression::Field { val })
+        | (Expression::Field { val }, Expression::ColRef { id }) => Some((*id, val)),
+        _ => None,
+    }
+}
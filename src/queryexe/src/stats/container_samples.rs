@@ -1,4 +1,4 @@
-use super::{per_attr_stats::PerAttrStats, SAMPLE_SIZE};
+use super::{column_group_stats::ColumnGroupStats, per_attr_stats::PerAttrStats, SAMPLE_SIZE};
 use common::{ids::ValueId, TableSchema, Tuple};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -13,6 +13,8 @@ pub struct ContainerSamples {
 
     // Attr Stats same order as the attributes in the schema
     pub per_attr_stats: Vec<PerAttrStats>,
+    /// Joint distributions of the column groups analyzed with `ANALYZE t (a, b)`.
+    pub column_groups: Vec<ColumnGroupStats>,
     // A key map should be added, but this needs a catalog
 }
 
@@ -24,6 +26,8 @@ pub struct SerlializedContainerSamples {
     pub schema: TableSchema,
     pub id_to_sample: HashMap<String, usize>,
     pub per_attr_stats: Vec<PerAttrStats>,
+    #[serde(default)]
+    pub column_groups: Vec<ColumnGroupStats>,
 }
 
 impl ContainerSamples {
@@ -41,6 +45,7 @@ impl ContainerSamples {
             schema,
             id_to_sample: HashMap::with_capacity(SAMPLE_SIZE),
             per_attr_stats,
+            column_groups: Vec::new(),
         }
     }
 
@@ -50,9 +55,13 @@ impl ContainerSamples {
     /// Finally, don't forget to update record_count
     /// Note: idx should only be supplied if the record count is equal to SAMPLE_SIZE
     /// (i.e., the vector is full and we are replacing a sample)
-    pub fn add_sample(&mut self, tuple: Tuple, value_id: ValueId, idx: Option<usize>) {
+    pub fn add_sample(&mut self, mut tuple: Tuple, value_id: ValueId, idx: Option<usize>) {
+        tuple.value_id = Some(value_id);
         match idx {
             Some(i) => {
+                if let Some(replaced) = self.samples[i].value_id {
+                    self.id_to_sample.remove(&replaced);
+                }
                 self.samples[i] = tuple;
                 self.id_to_sample.insert(value_id, i);
             }
@@ -63,6 +72,23 @@ impl ContainerSamples {
         }
     }
 
+    /// Forgets a deleted record, and its sample if it has one. The last sample
+    /// takes the place of the removed one.
+    pub fn remove_record(&mut self, value_id: &ValueId) {
+        self.record_count = self.record_count.saturating_sub(1);
+        let Some(i) = self.id_to_sample.remove(value_id) else {
+            return;
+        };
+        if self.samples.get(i).and_then(|t| t.value_id) != Some(*value_id) {
+            // The mapping outlived its sample, e.g. across a restart.
+            return;
+        }
+        self.samples.swap_remove(i);
+        if let Some(moved) = self.samples.get(i).and_then(|t| t.value_id) {
+            self.id_to_sample.insert(moved, i);
+        }
+    }
+
     /// Increment the record count
     pub fn increment_record_count(&mut self) {
         self.record_count += 1;
@@ -112,6 +138,13 @@ impl ContainerSamples {
             .map(|d| d as f64 / self.get_record_count() as f64)
     }
 
+    /// Replaces the statistics of the column group over the same columns, if
+    /// any, with `stats`.
+    pub fn set_column_group(&mut self, stats: ColumnGroupStats) {
+        self.column_groups.retain(|g| g.columns != stats.columns);
+        self.column_groups.push(stats);
+    }
+
     pub fn get_serializable_container_sample(&self) -> SerlializedContainerSamples {
         let id_to_sample: HashMap<String, usize> = self
             .id_to_sample
@@ -133,6 +166,7 @@ impl ContainerSamples {
             schema: self.schema.clone(),
             id_to_sample,
             per_attr_stats: self.per_attr_stats.clone(),
+            column_groups: self.column_groups.clone(),
         }
     }
 }
@@ -163,6 +197,7 @@ impl SerlializedContainerSamples {
             schema: self.schema.clone(),
             id_to_sample,
             per_attr_stats: self.per_attr_stats.clone(),
+            column_groups: self.column_groups.clone(),
         }
     }
 }
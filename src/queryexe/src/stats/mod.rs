@@ -1,5 +1,7 @@
+pub mod column_group_stats;
 pub mod container_samples;
 pub mod per_attr_stats;
 pub mod reservoir_stat_manager;
+pub mod stats_view;
 
 const SAMPLE_SIZE: usize = 1000;
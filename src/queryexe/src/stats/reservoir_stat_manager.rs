@@ -21,6 +21,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::query::planner::convert_expr_to_bytecode;
 
+use super::column_group_stats::{covered_group_selectivity, ColumnGroupStats};
 use super::container_samples::{ContainerSamples, SerlializedContainerSamples};
 use super::SAMPLE_SIZE;
 
@@ -126,16 +127,16 @@ impl StatManagerTrait for ReservoirStatManager {
 
         // e2e tests lack context for parent dir to exist, so we create parent (not needed here, but just in case)
         if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).expect("Failed to create directory");
+            fs::create_dir_all(parent)?;
         }
 
         serde_json::to_writer(
-            fs::File::create(path.clone()).expect("error creating file"),
+            fs::File::create(path.clone())?,
             &self.get_serializable_stat_manager(),
         )
         .map_err(|e| c_err(&format!("failed to serialize: {}", e)))?;
 
-        info!("Stat manager saved to {}", path.to_str().unwrap());
+        info!("Stat manager saved to {}", path.display());
         Ok(())
     }
 
@@ -152,8 +153,21 @@ impl StatManagerTrait for ReservoirStatManager {
         Ok(())
     }
 
-    fn deleted_record(&self, _value_id: &ValueId) -> Result<(), FairyError> {
-        todo!()
+    fn remove_table(&self, c_id: ContainerId) -> Result<(), FairyError> {
+        let mut samples = self.samples.write().unwrap();
+        let mut states = self.states.write().unwrap();
+        samples.remove(&c_id);
+        states.remove(&c_id);
+        Ok(())
+    }
+
+    fn deleted_record(&self, value_id: &ValueId) -> Result<(), FairyError> {
+        let mut samples = self.samples.write().unwrap();
+        let container_samples = samples
+            .get_mut(&value_id.container_id)
+            .ok_or_else(|| FairyError::FairyError("Container not found/registered".to_string()))?;
+        container_samples.remove_record(value_id);
+        Ok(())
     }
 
     fn updated_record(
@@ -194,17 +208,13 @@ impl StatManagerTrait for ReservoirStatManager {
 
     /// Given a container and a predicate, estimate the selectivity of the predicate.
     /// This is done by evaluating the predicate on the sample and then dividing the result by the
-    /// number of samples.
+    /// number of samples. Equalities that fix the values of an analyzed column group
+    /// are estimated from its joint distribution instead.
     fn estimate_count_and_sel(
         &self,
         c_id: ContainerId,
         predicate: &[Expression<PhysicalRelExpr>],
     ) -> Result<(usize, f64), FairyError> {
-        let predicate = predicate
-            .iter()
-            .map(|p| convert_expr_to_bytecode(p.clone(), None))
-            .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()?;
-
         let samples = self.samples.read().unwrap();
         let container_samples = samples
             .get(&c_id)
@@ -214,15 +224,34 @@ impl StatManagerTrait for ReservoirStatManager {
             return Ok((0, 0.0));
         }
 
-        let matching_count = container_samples
-            .samples
-            .iter()
-            .filter(|tuple| predicate.iter().all(|p| p.eval(tuple) == Field::Bool(true)))
-            .count();
+        let (group_sel, predicate) =
+            covered_group_selectivity(&container_samples.column_groups, predicate)
+                .unwrap_or_else(|| (1.0, predicate.to_vec()));
+        let predicate = predicate
+            .into_iter()
+            .map(|p| convert_expr_to_bytecode(p, None))
+            .collect::<Result<Vec<ByteCodeExpr>, FairyError>>()?;
+
+        // A predicate that fails on a sample, e.g. on the wrong types, fails the
+        // estimate rather than the planner.
+        let matches = |tuple: &Tuple| -> Result<bool, FairyError> {
+            for p in &predicate {
+                if p.try_eval(tuple)? != Field::Bool(true) {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        };
+        let mut matching_count = 0;
+        for tuple in &container_samples.samples {
+            if matches(tuple)? {
+                matching_count += 1;
+            }
+        }
 
         let sample_size = container_samples.samples.len();
 
-        let selectivity = matching_count as f64 / sample_size as f64;
+        let selectivity = group_sel * matching_count as f64 / sample_size as f64;
         let count = (selectivity * container_samples.record_count as f64) as usize;
         Ok((count, selectivity))
     }
@@ -323,6 +352,60 @@ impl ReservoirStatManager {
         ReservoirStatManager::new(Box::leak(Box::new(ServerConfig::temporary())), 1000)
     }
 
+    /// Overrides the record count of a registered container, e.g. with statistics
+    /// imported from another database. The samples are kept, so selectivity
+    /// estimates are unchanged.
+    pub fn import_record_count(
+        &self,
+        c_id: ContainerId,
+        record_count: usize,
+    ) -> Result<(), FairyError> {
+        let mut samples = self.samples.write().unwrap();
+        let container_samples = samples
+            .get_mut(&c_id)
+            .ok_or(FairyError::FairyError("Container not found".to_string()))?;
+        container_samples.record_count = record_count;
+        Ok(())
+    }
+
+    /// Computes the joint distribution of the columns at `columns` over all the
+    /// records of a registered container, replacing the one computed before.
+    pub fn analyze_column_group(
+        &self,
+        c_id: ContainerId,
+        columns: Vec<usize>,
+        tuples: &[Tuple],
+    ) -> Result<(), FairyError> {
+        self.set_column_group(c_id, ColumnGroupStats::analyze(columns, tuples))
+    }
+
+    /// Replaces the joint distribution of a column group of a registered
+    /// container by `stats`, counted over all its records.
+    pub fn set_column_group(
+        &self,
+        c_id: ContainerId,
+        stats: ColumnGroupStats,
+    ) -> Result<(), FairyError> {
+        let mut samples = self.samples.write().unwrap();
+        let container_samples = samples
+            .get_mut(&c_id)
+            .ok_or(FairyError::FairyError("Container not found".to_string()))?;
+        container_samples.set_column_group(stats);
+        Ok(())
+    }
+
+    /// Whether a column group of the container was analyzed when it held a much
+    /// different number of records.
+    pub fn has_stale_column_groups(&self, c_id: ContainerId) -> bool {
+        let samples = self.samples.read().unwrap();
+        samples.get(&c_id).is_some_and(|container_samples| {
+            container_samples
+                .column_groups
+                .iter()
+                .any(|group| group.is_stale(container_samples.get_record_count()))
+        })
+    }
+
     fn get_serializable_stat_manager(&self) -> SerlializedReservoirStatManager {
         let r = self.samples.read().unwrap();
         let samples = r
@@ -361,6 +444,19 @@ mod tests {
         ReservoirStatManager::new(config, mem_budget)
     }
 
+    /// `n` rows of a key `id` and columns `ia1`, `ia2` and `ia3` uniform over
+    /// 10, 100 and 1000 values.
+    fn gen_table(n: usize, seed: u64) -> (TableSchema, Vec<Tuple>) {
+        let schema =
+            TableSchema::from_vecs(vec!["id", "ia1", "ia2", "ia3"], vec![DataType::BigInt; 4]);
+        let gen = TableGen::new(schema, seed)
+            .column("id", ColumnDist::Sequential { start: 0 })
+            .column("ia1", ColumnDist::Uniform { min: 0, max: 9 })
+            .column("ia2", ColumnDist::Uniform { min: 0, max: 99 })
+            .column("ia3", ColumnDist::Uniform { min: 0, max: 999 });
+        (gen.schema().clone(), gen.generate(n))
+    }
+
     #[test]
     fn test_reservoir_stat_manager() {
         let stat_manager = gen_test_stat_manager();
@@ -386,10 +482,9 @@ mod tests {
         let c_id = 1;
         let value_id = ValueId::new(c_id);
         let tuple_count = 1000;
-        let mut rng = get_rng();
-        let (table, mut tuples) = gen_test_table_and_tuples(&mut rng, c_id, tuple_count);
+        let (schema, mut tuples) = gen_table(tuple_count, 1);
         let mut count = 0;
-        stat_manager.register_table(c_id, table.schema).unwrap();
+        stat_manager.register_table(c_id, schema).unwrap();
         // Check that the first 1000 records fill the reservoir
         for tuple in &tuples {
             let result = stat_manager.new_record(tuple, value_id);
@@ -404,7 +499,7 @@ mod tests {
         }
 
         // Add new records and check size
-        tuples = gen_test_tuples(&mut rng, tuple_count);
+        tuples = gen_table(tuple_count, 2).1;
         for tuple in &tuples {
             let result = stat_manager.new_record(tuple, value_id);
             count += 1;
@@ -422,10 +517,8 @@ mod tests {
     fn test_estimated_record_count_simple() {
         let stat_manager = gen_test_stat_manager();
         let c_id = 1;
-        let tuple_count = 1;
-        let mut rng = get_rng();
-        let (table, tuples) = gen_test_table_and_tuples(&mut rng, c_id, tuple_count);
-        stat_manager.register_table(c_id, table.schema).unwrap();
+        let (schema, tuples) = gen_table(1, 3);
+        stat_manager.register_table(c_id, schema).unwrap();
         let tuple = tuples.first().unwrap();
         let f1 = tuple.get_field(0).unwrap();
 
@@ -434,13 +527,13 @@ mod tests {
             left: Box::new(Expression::<PhysicalRelExpr>::ColRef { id: 1 }), // Referring to "ia1"
             right: Box::new(Expression::<PhysicalRelExpr>::Field { val: f1.to_owned() }),
         };
-        let estimated_count_res = stat_manager.estimate_count_and_sel(2, &[predicate.clone()]);
+        let estimated_count_res = stat_manager.estimate_count_and_sel(2, std::slice::from_ref(&predicate));
         assert_eq!(
             estimated_count_res,
             Err(FairyError::FairyError("Container not found".to_string()))
         );
 
-        let estimated_count_res = stat_manager.estimate_count_and_sel(c_id, &[predicate.clone()]);
+        let estimated_count_res = stat_manager.estimate_count_and_sel(c_id, std::slice::from_ref(&predicate));
         assert_eq!(estimated_count_res, Ok((0, 0.0)));
         stat_manager.new_record(tuple, ValueId::new(c_id)).unwrap();
         let (estimated_count, _) = stat_manager
@@ -454,10 +547,8 @@ mod tests {
         let stat_manager = gen_test_stat_manager();
         let c_id = 1;
         let value_id = ValueId::new(c_id);
-        let tuple_count = 10000;
-        let mut rng = get_rng();
-        let (table, tuples) = gen_test_table_and_tuples(&mut rng, c_id, tuple_count);
-        stat_manager.register_table(c_id, table.schema).unwrap();
+        let (schema, tuples) = gen_table(10000, 4);
+        stat_manager.register_table(c_id, schema).unwrap();
 
         // ia1 should be uniformly distributed 0-9
         let predicate = Expression::<PhysicalRelExpr>::Binary {
@@ -504,9 +595,8 @@ mod tests {
         let c_id = 1;
         let value_id = ValueId::new(c_id);
         let tuple_count = 1000;
-        let mut rng = get_rng();
-        let (table, tuples) = gen_test_table_and_tuples(&mut rng, c_id, tuple_count);
-        stat_manager.register_table(c_id, table.schema).unwrap();
+        let (schema, tuples) = gen_table(tuple_count, 5);
+        stat_manager.register_table(c_id, schema).unwrap();
 
         for tuple in &tuples {
             stat_manager.new_record(tuple, value_id).unwrap();
@@ -523,7 +613,7 @@ mod tests {
         let (estimated_count, _) = stat_manager
             .estimate_count_and_sel(c_id, &[predicate])
             .unwrap();
-        assert!(estimated_count <= tuple_count.try_into().unwrap());
+        assert!(estimated_count <= tuple_count);
     }
 
     #[test]
@@ -531,35 +621,25 @@ mod tests {
         let stat_manager = gen_test_stat_manager();
 
         let left_c_id = 1;
-        let left_tuple_count = 10000;
-
         let right_c_id = 2;
-        let right_tuple_count = 10000;
-
-        let mut rng = get_rng();
-        let (table, left_tuples) = gen_test_table_and_tuples(&mut rng, left_c_id, left_tuple_count);
 
-        stat_manager
-            .register_table(left_c_id, table.schema)
-            .unwrap();
+        let (schema, left_tuples) = gen_table(10000, 6);
+        stat_manager.register_table(left_c_id, schema).unwrap();
         for tuple in &left_tuples {
             stat_manager
                 .new_record(tuple, ValueId::new(left_c_id))
                 .unwrap();
         }
 
-        let (table, right_tuples) =
-            gen_test_table_and_tuples(&mut rng, right_c_id, right_tuple_count);
-        stat_manager
-            .register_table(right_c_id, table.schema)
-            .unwrap();
+        let (schema, right_tuples) = gen_table(10000, 7);
+        stat_manager.register_table(right_c_id, schema).unwrap();
         for tuple in &right_tuples {
             stat_manager
                 .new_record(tuple, ValueId::new(right_c_id))
                 .unwrap();
         }
 
-        // ia2: 0~999, ia3: 0~99, so the prob for eqs should be 0.001
+        // ia3: 0~999, ia2: 0~99, so the prob for eqs should be 0.001
         let ops = vec![BinaryOp::Eq];
         let left_expr = Expression::<PhysicalRelExpr>::ColRef { id: 3 }; // Referring to "ia3"
         let right_expr = Expression::<PhysicalRelExpr>::ColRef { id: 2 }; // Referring to "ia2"
@@ -576,4 +656,88 @@ mod tests {
         assert!((70000..=130000).contains(&estimated_count));
         assert!((0.0007..=0.0013).contains(&est_sel));
     }
+
+    #[test]
+    fn test_column_group_estimates() {
+        let stat_manager = gen_test_stat_manager();
+        let c_id = 1;
+        let schema = TableSchema::from_vecs(
+            vec!["id", "city", "state"],
+            vec![DataType::BigInt, DataType::String, DataType::String],
+        );
+        stat_manager.register_table(c_id, schema).unwrap();
+        let row = |id: usize, city: &str, state: &str| {
+            Tuple::new(vec![
+                Field::BigInt(id as i64),
+                Field::String(city.to_string()),
+                Field::String(state.to_string()),
+            ])
+        };
+        // A full reservoir replaces a random sample with every new record, so
+        // the first rows are all but certainly gone from it.
+        let places = [("Albany", "NY"), ("Austin", "TX"), ("Dallas", "TX")];
+        let mut tuples: Vec<Tuple> = (0..600).map(|i| row(i, "NYC", "NY")).collect();
+        tuples.extend((600..20600).map(|i| {
+            let (city, state) = places[i % places.len()];
+            row(i, city, state)
+        }));
+        for tuple in &tuples {
+            stat_manager.new_record(tuple, ValueId::new(c_id)).unwrap();
+        }
+
+        let eq = |id: usize, val: &str| Expression::<PhysicalRelExpr>::Binary {
+            op: BinaryOp::Eq,
+            left: Box::new(Expression::ColRef { id }),
+            right: Box::new(Expression::Field {
+                val: Field::String(val.to_string()),
+            }),
+        };
+        let nyc = [eq(1, "NYC"), eq(2, "NY")];
+        let actual = tuples
+            .iter()
+            .filter(|t| t.field_vals[1] == Field::String("NYC".to_string()))
+            .count();
+        let (without, _) = stat_manager.estimate_count_and_sel(c_id, &nyc).unwrap();
+        assert_eq!((without, actual), (0, 600));
+        let ny_before = stat_manager.estimate_count_and_sel(c_id, &[eq(2, "NY")]);
+
+        stat_manager
+            .analyze_column_group(c_id, vec![1, 2], &tuples)
+            .unwrap();
+        let (with, sel) = stat_manager.estimate_count_and_sel(c_id, &nyc).unwrap();
+        assert_eq!(with, actual);
+        assert!((sel - 600.0 / 20600.0).abs() < 1e-9);
+
+        // Predicates outside the group are estimated from the sample.
+        let id_below = Expression::<PhysicalRelExpr>::Binary {
+            op: BinaryOp::Lt,
+            left: Box::new(Expression::ColRef { id: 0 }),
+            right: Box::new(Expression::Field {
+                val: Field::BigInt(0),
+            }),
+        };
+        let mut predicates = nyc.to_vec();
+        predicates.push(id_below);
+        let (count, _) = stat_manager
+            .estimate_count_and_sel(c_id, &predicates)
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // A combination that never occurs selects nothing, and one column alone
+        // does not cover the group.
+        let (count, _) = stat_manager
+            .estimate_count_and_sel(c_id, &[eq(1, "Austin"), eq(2, "NY")])
+            .unwrap();
+        assert_eq!(count, 0);
+        let state_only = stat_manager.estimate_count_and_sel(c_id, &[eq(2, "NY")]);
+        assert_eq!(state_only, ny_before);
+
+        // The group goes stale once the table grew by a fifth.
+        assert!(!stat_manager.has_stale_column_groups(c_id));
+        for i in 0..5000 {
+            let tuple = row(20600 + i, "Austin", "TX");
+            stat_manager.new_record(&tuple, ValueId::new(c_id)).unwrap();
+        }
+        assert!(stat_manager.has_stale_column_groups(c_id));
+    }
 }
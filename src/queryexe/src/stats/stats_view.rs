@@ -0,0 +1,234 @@
+use super::reservoir_stat_manager::ReservoirStatManager;
+use common::{
+    catalog::{get_column_index_from_temp_col_id, CatalogRef},
+    ids::{ColumnId, ContainerId},
+    logical_expr::prelude::Expression,
+    physical_expr::physical_rel_expr::PhysicalRelExpr,
+    traits::stat_manager_trait::StatManagerTrait,
+    BinaryOp, FairyError,
+};
+use std::collections::HashMap;
+
+/// Statistics a session assumes for a table in place of the real ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStatsOverlay {
+    /// Number of records of the table.
+    pub rows: usize,
+    /// Number of distinct values, keyed by the offset of the column in the table.
+    pub distinct: HashMap<usize, usize>,
+}
+
+/// Statistics a session set with `SET hypothetical_stats` to see the plans the
+/// optimizer would pick for them. They are only read while planning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HypotheticalStats {
+    text: String,
+    tables: HashMap<ContainerId, TableStatsOverlay>,
+}
+
+impl HypotheticalStats {
+    /// Parses statistics written as `{"t1": {"rows": 10000000, "distinct": {"a": 500}}}`.
+    /// Tables and columns must exist in `catalog`.
+    pub fn parse(text: &str, catalog: &CatalogRef) -> Result<Self, FairyError> {
+        let invalid = |msg: String| {
+            FairyError::ValidationError(format!("Invalid hypothetical_stats: {}", msg))
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let tables = value
+            .as_object()
+            .ok_or_else(|| invalid("expected an object keyed by table".to_string()))?;
+        let mut overlays = HashMap::new();
+        for (table, stats) in tables {
+            let c_id = catalog
+                .get_table_id_if_exists(table)
+                .ok_or_else(|| invalid(format!("unknown table {}", table)))?;
+            let schema = catalog.get_table_schema(c_id).unwrap();
+            let rows = stats
+                .get("rows")
+                .and_then(|rows| rows.as_u64())
+                .ok_or_else(|| invalid(format!("expected a row count for {}", table)))?;
+            let mut distinct = HashMap::new();
+            if let Some(columns) = stats.get("distinct") {
+                let columns = columns.as_object().ok_or_else(|| {
+                    invalid(format!("expected distinct counts by column for {}", table))
+                })?;
+                for (column, count) in columns {
+                    let offset = schema
+                        .get_field_index(column)
+                        .ok_or_else(|| invalid(format!("unknown column {}.{}", table, column)))?;
+                    let count = count.as_u64().filter(|count| *count > 0).ok_or_else(|| {
+                        invalid(format!(
+                            "expected a distinct count for {}.{}",
+                            table, column
+                        ))
+                    })?;
+                    distinct.insert(offset, count as usize);
+                }
+            }
+            overlays.insert(
+                c_id,
+                TableStatsOverlay {
+                    rows: rows as usize,
+                    distinct,
+                },
+            );
+        }
+        Ok(Self {
+            text: text.to_string(),
+            tables: overlays,
+        })
+    }
+
+    /// The statistics as the session set them.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn table(&self, c_id: ContainerId) -> Option<&TableStatsOverlay> {
+        self.tables.get(&c_id)
+    }
+}
+
+/// The statistics the planner reads: the real ones, with the tables of the
+/// session's hypothetical statistics, if any, replaced by them.
+#[derive(Clone, Copy)]
+pub struct StatsView<'a> {
+    stats: &'a ReservoirStatManager,
+    overlay: Option<&'a HypotheticalStats>,
+}
+
+impl<'a> StatsView<'a> {
+    /// View of the real statistics.
+    pub fn new(stats: &'a ReservoirStatManager) -> Self {
+        Self {
+            stats,
+            overlay: None,
+        }
+    }
+
+    pub fn with_overlay(mut self, overlay: Option<&'a HypotheticalStats>) -> Self {
+        self.overlay = overlay;
+        self
+    }
+
+    /// Returns true if the view differs from the real statistics.
+    pub fn is_hypothetical(&self) -> bool {
+        self.overlay.is_some()
+    }
+
+    /// Like `StatManagerTrait::estimate_count_and_sel`. For a table with
+    /// hypothetical statistics, an equality between a column with a distinct
+    /// count and a literal selects one of its values, and other predicates keep
+    /// the selectivity of the real samples, if there are any.
+    pub fn estimate_count_and_sel(
+        &self,
+        c_id: ContainerId,
+        predicates: &[Expression<PhysicalRelExpr>],
+    ) -> Result<(usize, f64), FairyError> {
+        let Some(table) = self.overlay.and_then(|overlay| overlay.table(c_id)) else {
+            return self.stats.estimate_count_and_sel(c_id, predicates);
+        };
+        let sampled = self.stats.get_container_record_count(c_id).unwrap_or(0) > 0;
+        let mut sel = 1.0;
+        for predicate in predicates {
+            sel *= match distinct_of_equality(predicate, table) {
+                Some(distinct) => 1.0 / distinct as f64,
+                None if sampled => self
+                    .stats
+                    .estimate_count_and_sel(c_id, std::slice::from_ref(predicate))
+                    .map_or(1.0, |(_, sel)| sel),
+                None => 1.0,
+            };
+        }
+        Ok(((table.rows as f64 * sel) as usize, sel))
+    }
+
+    /// Estimated number of rows a node produces. Only filtered scans are
+    /// estimated, projections, sorts and renames keep the estimate of their
+    /// input.
+    /// Returns None for anything else.
+    pub fn estimate_rows(&self, node: &PhysicalRelExpr) -> Option<usize> {
+        let mut predicates = Vec::new();
+        let mut src = node;
+        loop {
+            match src {
+                PhysicalRelExpr::Select {
+                    src: inner,
+                    predicates: preds,
+                    ..
+                } => {
+                    predicates.extend(preds.iter().cloned());
+                    src = inner;
+                }
+                PhysicalRelExpr::Project { src: inner, .. }
+                | PhysicalRelExpr::Sort { src: inner, .. }
+                    if predicates.is_empty() =>
+                {
+                    src = inner
+                }
+                PhysicalRelExpr::Rename {
+                    src: inner,
+                    src_to_dest,
+                    ..
+                } => {
+                    // Predicates above the rename refer to the renamed columns.
+                    let dest_to_src: HashMap<ColumnId, ColumnId> = src_to_dest
+                        .iter()
+                        .map(|(src, dest)| (*dest, *src))
+                        .collect();
+                    predicates = predicates
+                        .into_iter()
+                        .map(|p| p.replace_variables(&dest_to_src))
+                        .collect();
+                    src = inner;
+                }
+                PhysicalRelExpr::Scan {
+                    cid, column_names, ..
+                } => {
+                    // The statistics refer to columns by their offset in the table.
+                    let to_offset: HashMap<ColumnId, ColumnId> = column_names
+                        .iter()
+                        .map(|id| (*id, get_column_index_from_temp_col_id(*id)))
+                        .collect();
+                    if predicates
+                        .iter()
+                        .any(|p| p.free().iter().any(|id| !to_offset.contains_key(id)))
+                    {
+                        predicates.clear();
+                    }
+                    let predicates: Vec<_> = predicates
+                        .into_iter()
+                        .map(|p| p.replace_variables(&to_offset))
+                        .collect();
+                    return self
+                        .estimate_count_and_sel(*cid, &predicates)
+                        .ok()
+                        .map(|(count, sel)| (count as f64 * sel).round() as usize);
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Distinct count of the column a `column = literal` predicate compares, if the
+/// table has one.
+fn distinct_of_equality(
+    predicate: &Expression<PhysicalRelExpr>,
+    table: &TableStatsOverlay,
+) -> Option<usize> {
+    let Expression::Binary {
+        op: BinaryOp::Eq,
+        left,
+        right,
+    } = predicate
+    else {
+        return None;
+    };
+    match (left.as_ref(), right.as_ref()) {
+        (Expression::ColRef { id }, Expression::Field { .. })
+        | (Expression::Field { .. }, Expression::ColRef { id }) => table.distinct.get(id).copied(),
+        _ => None,
+    }
+}
@@ -0,0 +1,175 @@
+//! Temporary files that operators spill to, and the budget they share.
+
+use common::ids::TransactionId;
+use common::physical::config::ServerConfig;
+use common::FairyError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::NamedTempFile;
+
+/// Prefix of the names of spill files.
+const SPILL_FILE_PREFIX: &str = "spill-";
+
+#[derive(Default)]
+struct TempUsage {
+    /// Bytes reserved by all statements.
+    total: u64,
+    /// Bytes reserved by the statements of each transaction.
+    by_txn: HashMap<TransactionId, u64>,
+}
+
+/// Directory of the temporary files of the server and the space their data may
+/// take. Operators reserve the bytes they are about to spill, and a reservation
+/// that would take the usage of all statements over the budget fails, so that
+/// the statement fails instead of filling the volume.
+pub struct TempSpace {
+    dir: PathBuf,
+    /// Bytes all statements may reserve at once, 0 for no limit.
+    budget: u64,
+    usage: Mutex<TempUsage>,
+    /// Reservations refused because of the budget.
+    pub rejected: AtomicU64,
+}
+
+pub type TempSpaceRef = Arc<TempSpace>;
+
+impl TempSpace {
+    pub fn new(dir: PathBuf, budget: u64) -> Self {
+        TempSpace {
+            dir,
+            budget,
+            usage: Mutex::new(TempUsage::default()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        TempSpace::new(config.temp_dir(), config.temp_space_budget_bytes)
+    }
+
+    /// Bytes reserved by all statements.
+    pub fn used(&self) -> u64 {
+        self.usage.lock().unwrap().total
+    }
+
+    /// Bytes reserved by the statements of transaction `tid`.
+    pub fn used_by(&self, tid: TransactionId) -> u64 {
+        let usage = self.usage.lock().unwrap();
+        usage.by_txn.get(&tid).copied().unwrap_or(0)
+    }
+
+    /// Creates a temporary file, which is removed when it is dropped. Its data
+    /// must be reserved with a `TempReservation`.
+    pub fn create_file(&self) -> Result<NamedTempFile, FairyError> {
+        fs::create_dir_all(&self.dir)?;
+        Ok(tempfile::Builder::new()
+            .prefix(SPILL_FILE_PREFIX)
+            .tempfile_in(&self.dir)?)
+    }
+
+    fn reserve(&self, tid: TransactionId, bytes: u64) -> Result<(), FairyError> {
+        let mut usage = self.usage.lock().unwrap();
+        if self.budget > 0 && usage.total + bytes > self.budget {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(FairyError::ExecutionError(format!(
+                "Temporary space exhausted: {} more bytes do not fit in the {} bytes budget, {} are in use",
+                bytes, self.budget, usage.total
+            )));
+        }
+        usage.total += bytes;
+        *usage.by_txn.entry(tid).or_default() += bytes;
+        Ok(())
+    }
+
+    fn release(&self, tid: TransactionId, bytes: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.total -= bytes;
+        if let Some(used) = usage.by_txn.get_mut(&tid) {
+            *used -= bytes;
+            if *used == 0 {
+                usage.by_txn.remove(&tid);
+            }
+        }
+    }
+}
+
+/// Temporary space held by one operator of a statement, released when it is
+/// dropped.
+pub struct TempReservation {
+    space: TempSpaceRef,
+    tid: TransactionId,
+    bytes: u64,
+}
+
+impl TempReservation {
+    pub fn new(space: TempSpaceRef, tid: TransactionId) -> Self {
+        TempReservation {
+            space,
+            tid,
+            bytes: 0,
+        }
+    }
+
+    /// Reserves `bytes` more, or fails if they do not fit in the budget.
+    pub fn grow(&mut self, bytes: u64) -> Result<(), FairyError> {
+        self.space.reserve(self.tid, bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    /// Releases all the space reserved so far, once the files it was reserved
+    /// for are removed.
+    pub fn release(&mut self) {
+        if self.bytes > 0 {
+            self.space.release(self.tid, self.bytes);
+            self.bytes = 0;
+        }
+    }
+
+    /// Creates a temporary file in the directory of the space.
+    pub fn create_file(&self) -> Result<NamedTempFile, FairyError> {
+        self.space.create_file()
+    }
+}
+
+impl Drop for TempReservation {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_budget_is_shared() {
+        let dir = tempfile::tempdir().unwrap();
+        let space = Arc::new(TempSpace::new(dir.path().to_path_buf(), 100));
+        let (t1, t2) = (TransactionId::new(), TransactionId::new());
+        let mut first = TempReservation::new(space.clone(), t1);
+        let mut second = TempReservation::new(space.clone(), t2);
+        first.grow(60).unwrap();
+        second.grow(30).unwrap();
+        let err = second.grow(20).unwrap_err();
+        assert!(
+            err.to_string().contains("Temporary space exhausted"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            (space.used(), space.used_by(t1), space.used_by(t2)),
+            (90, 60, 30)
+        );
+        assert_eq!(space.rejected.load(Ordering::Relaxed), 1);
+
+        drop(first);
+        second.grow(20).unwrap();
+        assert_eq!((space.used(), space.used_by(t1)), (50, 0));
+        drop(second);
+        assert_eq!(space.used(), 0);
+    }
+}
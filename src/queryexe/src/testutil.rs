@@ -27,6 +27,19 @@ pub fn execute_iter(iter: &mut dyn OpIterator, sorted: bool) -> Result<Vec<Tuple
     Ok(tuples)
 }
 
+/// Like `execute_iter`, but reads the tuples with next_batch(), `max` at a time.
+pub fn execute_batches(iter: &mut dyn OpIterator, max: usize) -> Result<Vec<Tuple>, FairyError> {
+    let mut tuples = Vec::new();
+    iter.open()?;
+    loop {
+        let before = tuples.len();
+        iter.next_batch(&mut tuples, max)?;
+        if tuples.len() == before {
+            return Ok(tuples);
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub struct TestTuples {
     pub schema: TableSchema,
@@ -138,7 +151,7 @@ impl TestSetup {
         for i in 0..3 {
             let name = format!("table{}", i);
             let test_tuples = TestTuples::new(&name);
-            let c_id = catalog.get_table_id(&name);
+            let c_id = catalog.get_table_id(&name).unwrap();
             let table = TableInfo::new(c_id, name.clone(), test_tuples.schema.clone());
             catalog.add_table(table.clone()).unwrap();
             managers.sm.create_table(c_id).unwrap();
@@ -0,0 +1,272 @@
+use common::metrics::LatencyHistogram;
+use common::physical::config::ServerConfig;
+use common::FairyError;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Counters of the admission of statements, shown in `system.metrics`.
+#[derive(Default)]
+pub struct AdmissionMetrics {
+    /// How long the statements that had to queue waited for their turn.
+    pub queue_wait: LatencyHistogram,
+    /// Statements rejected because the queue was full.
+    pub rejected_queue_full: AtomicU64,
+    /// Statements rejected because they waited in the queue too long.
+    pub rejected_timeout: AtomicU64,
+}
+
+impl AdmissionMetrics {
+    pub fn reset(&self) {
+        self.queue_wait.reset();
+        self.rejected_queue_full.store(0, Ordering::Relaxed);
+        self.rejected_timeout.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A statement that runs or waits for its turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub client_id: u64,
+    pub statement: String,
+    /// When the statement started running, or was queued.
+    pub since: Instant,
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    next_ticket: u64,
+    running: HashMap<u64, Activity>,
+    /// Waiting statements in arrival order.
+    queued: VecDeque<(u64, Activity)>,
+}
+
+/// Limits how many statements of all databases run at once. Statements beyond
+/// `max_running` wait in a FIFO queue of at most `max_queued` statements for
+/// up to `queue_timeout`, and fail with a server busy error if the queue is
+/// full or their wait times out. A `max_running` of 0 admits every statement.
+pub struct Admission {
+    max_running: usize,
+    max_queued: usize,
+    queue_timeout: Duration,
+    state: Mutex<AdmissionState>,
+    turn: Condvar,
+    pub metrics: AdmissionMetrics,
+}
+
+/// Admission of a running statement. Dropping it lets the next one run.
+pub struct AdmissionPermit<'a> {
+    admission: &'a Admission,
+    ticket: u64,
+}
+
+impl Admission {
+    pub fn new(max_running: usize, max_queued: usize, queue_timeout: Duration) -> Self {
+        Admission {
+            max_running,
+            max_queued,
+            queue_timeout,
+            state: Mutex::new(AdmissionState::default()),
+            turn: Condvar::new(),
+            metrics: AdmissionMetrics::default(),
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Admission::new(
+            config.max_running_statements,
+            config.max_queued_statements,
+            Duration::from_millis(config.admission_timeout_ms),
+        )
+    }
+
+    /// Waits until `statement` of client `client_id` may run.
+    pub fn admit(
+        &self,
+        client_id: u64,
+        statement: String,
+    ) -> Result<AdmissionPermit<'_>, FairyError> {
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let activity = Activity {
+            client_id,
+            statement,
+            since: Instant::now(),
+        };
+        if self.max_running == 0
+            || (state.queued.is_empty() && state.running.len() < self.max_running)
+        {
+            state.running.insert(ticket, activity);
+            return Ok(AdmissionPermit {
+                admission: self,
+                ticket,
+            });
+        }
+        if state.queued.len() >= self.max_queued {
+            self.metrics
+                .rejected_queue_full
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(FairyError::ServerBusy(format!(
+                "{} statements are already waiting to run, try again later",
+                state.queued.len()
+            )));
+        }
+        let queued_at = activity.since;
+        state.queued.push_back((ticket, activity));
+        loop {
+            let first = state.queued.front().map(|(first, _)| *first);
+            if first == Some(ticket) && state.running.len() < self.max_running {
+                let (_, mut activity) = state.queued.pop_front().unwrap();
+                let now = Instant::now();
+                self.metrics.queue_wait.record(now - queued_at);
+                activity.since = now;
+                state.running.insert(ticket, activity);
+                // The next statement in line may run as well.
+                self.turn.notify_all();
+                return Ok(AdmissionPermit {
+                    admission: self,
+                    ticket,
+                });
+            }
+            let waited = queued_at.elapsed();
+            if waited >= self.queue_timeout {
+                state.queued.retain(|(queued, _)| *queued != ticket);
+                self.metrics
+                    .rejected_timeout
+                    .fetch_add(1, Ordering::Relaxed);
+                self.turn.notify_all();
+                return Err(FairyError::ServerBusy(format!(
+                    "the statement waited {} ms for other statements to finish, try again later",
+                    waited.as_millis()
+                )));
+            }
+            state = self
+                .turn
+                .wait_timeout(state, self.queue_timeout - waited)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// The running statements, oldest first, and the queued ones in the order
+    /// they will run.
+    pub fn activity(&self) -> (Vec<Activity>, Vec<Activity>) {
+        let state = self.state.lock().unwrap();
+        let mut running: Vec<Activity> = state.running.values().cloned().collect();
+        running.sort_by_key(|activity| activity.since);
+        let queued = state
+            .queued
+            .iter()
+            .map(|(_, activity)| activity.clone())
+            .collect();
+        (running, queued)
+    }
+
+    fn release(&self, ticket: u64) {
+        self.state.lock().unwrap().running.remove(&ticket);
+        self.turn.notify_all();
+    }
+}
+
+impl Drop for AdmissionPermit<'_> {
+    fn drop(&mut self) {
+        self.admission.release(self.ticket);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_concurrency_ceiling() {
+        let admission = Arc::new(Admission::new(3, 100, Duration::from_secs(30)));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..12)
+            .map(|i| {
+                let (admission, running, peak) = (admission.clone(), running.clone(), peak.clone());
+                thread::spawn(move || {
+                    let _permit = admission.admit(i, format!("SELECT {}", i)).unwrap();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        // Every statement ran, some after waiting in the queue.
+        assert!(admission.metrics.queue_wait.count() > 0);
+        assert_eq!(admission.activity(), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_fifo_order() {
+        let admission = Admission::new(1, 10, Duration::from_secs(30));
+        let permit = admission.admit(0, "first".to_string()).unwrap();
+        let order = Mutex::new(Vec::new());
+        thread::scope(|s| {
+            for i in 1..=4 {
+                let (admission, order) = (&admission, &order);
+                s.spawn(move || {
+                    let _permit = admission.admit(i, format!("statement {}", i)).unwrap();
+                    order.lock().unwrap().push(i);
+                });
+                // Queue the statements one after another.
+                while admission.activity().1.len() < i as usize {
+                    thread::yield_now();
+                }
+            }
+            let (running, queued) = admission.activity();
+            assert_eq!(running.len(), 1);
+            let clients: Vec<u64> = queued.iter().map(|a| a.client_id).collect();
+            assert_eq!(clients, vec![1, 2, 3, 4]);
+            drop(permit);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_rejections() {
+        let admission = Admission::new(1, 1, Duration::from_millis(50));
+        let _permit = admission.admit(0, "running".to_string()).unwrap();
+        thread::scope(|s| {
+            let queued = s.spawn(|| admission.admit(1, "queued".to_string()).map(|_| ()));
+            while admission.activity().1.is_empty() {
+                thread::yield_now();
+            }
+            // The queue holds one statement.
+            let full = admission.admit(2, "rejected".to_string());
+            assert!(matches!(full, Err(FairyError::ServerBusy(_))));
+            // Nothing finishes, so the queued statement times out.
+            assert!(matches!(
+                queued.join().unwrap(),
+                Err(FairyError::ServerBusy(_))
+            ));
+        });
+        let metrics = &admission.metrics;
+        assert_eq!(metrics.rejected_queue_full.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.rejected_timeout.load(Ordering::Relaxed), 1);
+        assert!(admission.activity().1.is_empty());
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let admission = Admission::new(0, 0, Duration::ZERO);
+        let permits: Vec<_> = (0..10)
+            .map(|i| admission.admit(i, String::new()).unwrap())
+            .collect();
+        assert_eq!(admission.activity().0.len(), 10);
+        drop(permits);
+        assert!(admission.activity().0.is_empty());
+    }
+}
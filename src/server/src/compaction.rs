@@ -0,0 +1,225 @@
+use crate::database_state::DatabaseState;
+use crate::StorageTrait;
+use common::ids::{ContainerId, PageId};
+use common::metrics::StatementKind;
+use common::physical::config::ServerConfig;
+use common::table_lock::TableLockMode;
+use common::traits::storage_trait::StorageReport;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Name of the setting that turns automatic compaction of a database on or off.
+pub const AUTO_COMPACTION: &str = "auto_compaction";
+
+/// Tables with fewer pages holding values are never compacted automatically.
+const MIN_PAGES: usize = 4;
+/// A table is compacted once this percent of its pages holding values are less
+/// than 25% utilized...
+const SPARSE_PAGES_PCT: usize = 20;
+/// ...or this percent of its slots are dead.
+const DEAD_SLOTS_PCT: usize = 20;
+
+/// Whether the storage report of a table shows enough fragmentation to compact it.
+fn needs_compaction(report: &StorageReport) -> bool {
+    let used_pages = report.num_pages.saturating_sub(report.empty_pages);
+    if used_pages < MIN_PAGES {
+        return false;
+    }
+    let sparse_pages = report.pages_under_25.saturating_sub(report.empty_pages);
+    let slots = report.num_records + report.dead_slots;
+    sparse_pages * 100 >= used_pages * SPARSE_PAGES_PCT
+        || (slots > 0 && report.dead_slots * 100 >= slots * DEAD_SLOTS_PCT)
+}
+
+/// What a maintenance tick did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickOutcome {
+    /// Automatic compaction is turned off.
+    Disabled,
+    /// The database ran too many statements since the last tick.
+    Paused,
+    /// Compacted `pages` pages of `tables` tables.
+    Ran { tables: usize, pages: usize },
+}
+
+/// Counters of the work done by automatic compaction, shown in `system.metrics`.
+#[derive(Default)]
+pub struct CompactionMetrics {
+    pub pages_visited: AtomicU64,
+    pub pages_compacted: AtomicU64,
+    pub values_relocated: AtomicU64,
+    pub pages_emptied: AtomicU64,
+    /// Ticks skipped because the database was busy.
+    pub paused_ticks: AtomicU64,
+}
+
+/// Where compaction of a table resumes and whether it still has work to do.
+#[derive(Default)]
+struct TableProgress {
+    next_page: PageId,
+    /// Pages changed since compaction last started over at the first page.
+    changes_in_pass: usize,
+    /// Report of the table when a whole pass changed nothing. The table is
+    /// skipped until its report differs.
+    settled: Option<StorageReport>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    last_tick: Option<Instant>,
+    /// Statements the database had run at the last tick.
+    last_statements: u64,
+    /// Pages that may still be visited, refilled as time passes.
+    budget: f64,
+    tables: HashMap<ContainerId, TableProgress>,
+}
+
+/// Automatic compaction of the tables of one database. Each tick of the
+/// maintenance thread picks the tables whose storage report shows too many
+/// sparse pages or dead slots and compacts some of their pages, within a
+/// budget of pages per minute. Ticks are skipped while the database is busy,
+/// and a table is only touched when its lock can be taken exclusively at once
+/// and no open cursor reads it. Full rewrites of a table remain manual.
+pub struct AutoCompaction {
+    pages_per_minute: usize,
+    max_queries_per_sec: f64,
+    enabled: AtomicBool,
+    state: Mutex<SchedulerState>,
+    pub metrics: CompactionMetrics,
+}
+
+impl AutoCompaction {
+    pub fn new(config: &ServerConfig) -> Self {
+        AutoCompaction {
+            pages_per_minute: config.compaction_pages_per_minute,
+            max_queries_per_sec: config.compaction_max_queries_per_sec,
+            enabled: AtomicBool::new(config.compaction_pages_per_minute > 0),
+            state: Mutex::default(),
+            metrics: CompactionMetrics::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turns automatic compaction of the database on or off. It cannot be
+    /// turned on if the server allows no pages per minute.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled
+            .store(enabled && self.pages_per_minute > 0, Ordering::Relaxed);
+    }
+
+    /// Forgets the progress on a dropped table.
+    pub fn forget_table(&self, c_id: ContainerId) {
+        self.state.lock().unwrap().tables.remove(&c_id);
+    }
+
+    /// Runs one round of automatic compaction of `db` at time `now`. The budget
+    /// grows with the time since the previous tick, the first tick only starts
+    /// the clock.
+    pub fn tick(&self, db: &DatabaseState, now: Instant) -> TickOutcome {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let statements: u64 = StatementKind::ALL
+            .iter()
+            .map(|kind| db.query_metrics.get(*kind).count())
+            .sum();
+        let elapsed = state
+            .last_tick
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        // Resetting the metrics makes the count go down.
+        let recent_statements = statements.saturating_sub(state.last_statements);
+        state.last_tick = Some(now);
+        state.last_statements = statements;
+        if !self.enabled() {
+            return TickOutcome::Disabled;
+        }
+
+        let per_minute = self.pages_per_minute as f64;
+        state.budget = (state.budget + per_minute * elapsed.as_secs_f64() / 60.0).min(per_minute);
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 && recent_statements as f64 / secs > self.max_queries_per_sec {
+            self.metrics.paused_ticks.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                "Compaction of {} paused: {} statements in {:?}",
+                db.name, recent_statements, elapsed
+            );
+            return TickOutcome::Paused;
+        }
+
+        let sm = db.managers.sm;
+        let busy = db.cursors.open_tables();
+        let mut candidates: Vec<(ContainerId, String, StorageReport)> = db
+            .catalog
+            .get_tables()
+            .into_iter()
+            .filter(|table| !busy.contains(&table.c_id))
+            .filter_map(|table| {
+                let report = sm.storage_report(table.c_id).ok()?;
+                let settled = state
+                    .tables
+                    .get(&table.c_id)
+                    .and_then(|progress| progress.settled.as_ref());
+                (settled != Some(&report) && needs_compaction(&report))
+                    .then_some((table.c_id, table.name, report))
+            })
+            .collect();
+        candidates.sort_by_key(|(_, _, report)| std::cmp::Reverse(report.wasted_bytes));
+
+        let (mut tables, mut pages) = (0, 0);
+        for (c_id, name, report) in candidates {
+            let max_pages = state.budget as usize;
+            if max_pages == 0 {
+                break;
+            }
+            // Statements using the table go first.
+            let Ok(lock) =
+                db.catalog
+                    .lock_tables(&[c_id], TableLockMode::Exclusive, Duration::ZERO)
+            else {
+                continue;
+            };
+            let progress = state.tables.entry(c_id).or_default();
+            let round = match sm.compact(c_id, progress.next_page, max_pages) {
+                Ok(round) => round,
+                Err(e) => {
+                    warn!("Could not compact table {}: {}", name, e);
+                    continue;
+                }
+            };
+            drop(lock);
+
+            state.budget -= round.pages_visited as f64;
+            progress.next_page = round.next_page;
+            progress.changes_in_pass += round.pages_compacted + round.values_relocated;
+            if round.next_page <= 1 {
+                // A pass that found nothing to do settles the table.
+                if progress.changes_in_pass == 0 {
+                    progress.settled = Some(report);
+                }
+                progress.changes_in_pass = 0;
+            }
+            let metrics = &self.metrics;
+            let add =
+                |counter: &AtomicU64, n: usize| counter.fetch_add(n as u64, Ordering::Relaxed);
+            add(&metrics.pages_visited, round.pages_visited);
+            add(&metrics.pages_compacted, round.pages_compacted);
+            add(&metrics.values_relocated, round.values_relocated);
+            add(&metrics.pages_emptied, round.pages_emptied);
+            info!(
+                "Compacted table {}: visited {} pages, compacted {}, relocated {} values, emptied {} pages",
+                name,
+                round.pages_visited,
+                round.pages_compacted,
+                round.values_relocated,
+                round.pages_emptied
+            );
+            tables += 1;
+            pages += round.pages_visited;
+        }
+        TickOutcome::Ran { tables, pages }
+    }
+}
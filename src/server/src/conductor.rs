@@ -1,42 +1,78 @@
 use std::path::Path;
 use std::sync::Arc;
 
+use crate::compaction::AUTO_COMPACTION;
+use crate::cursors::Cursor;
 use crate::database_state::DatabaseState;
+use crate::prepared_statements::PreparedStatement;
+use crate::session_settings::HYPOTHETICAL_STATS;
 
-use crate::sql_parser::{ParserResponse, SQLParser};
-use crate::Executor;
+use crate::sql_parser::{Analyze, CopyFrom, CopyTo, ParserResponse, SQLParser};
+use crate::statement_retry::{is_reexecutable, StatementRetryPolicy};
+use crate::system_tables;
+use crate::{Executor, StorageTrait};
 
-use common::error::c_err;
-use common::ids::TransactionId;
-use common::util::data_reader::CsvReader;
+use common::error::{c_err, ObjectKind, SourcePosition};
+use common::ids::{ContainerId, TransactionId};
+use common::keywords::{is_reserved_keyword, quote_identifier};
+use common::util::data_reader::{CsvReader, Utf8Policy};
+use common::util::suggest::closest_match;
 
 use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use common::physical_expr::plan_schema::PlanSchema;
+use common::query::query_registrar::SchemaStamp;
+use common::query::query_result::{csv_line, Notice};
 use common::query::rules::Rules;
-use common::{FairyError, QueryResult};
+use common::table_lock::TableLockMode;
+use common::{Attribute, FairyError, QueryResult, TableSchema};
 
-use queryexe::query::planner::physical_plan_to_op_iterator;
-use queryexe::query::translate_and_validate::{get_name, Query};
+use queryexe::opiterator::{Cancellation, OpIterator, RecordCheck, SeqScan};
+use queryexe::query::planner::{
+    bind_parameters, physical_plan_to_analyzed_op_iterator, table_row_filter, AnalyzedPlan,
+};
+use queryexe::query::translate_and_validate::{
+    get_name, get_parameter_value, Query, TranslatorError,
+};
 use queryexe::query::Translator;
+use queryexe::stats::column_group_stats::ColumnGroupCounter;
+use queryexe::stats::stats_view::{HypotheticalStats, StatsView};
 use queryexe::Managers;
-use sqlparser::ast::{SetExpr, Statement};
-use std::fs::OpenOptions;
+use sqlparser::ast::{
+    CloseCursor, CopySource, Expr, FetchDirection, GroupByExpr, Ident, ObjectType,
+    Query as SqlQuery, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use txn_manager::transactions::Transaction;
 
 #[allow(unused_imports)]
 use optimizer::cost::dummy_cost_model::{DummyCost, DummyCostModel};
+use optimizer::deadline::PlanningDeadline;
 #[allow(unused_imports)]
 use optimizer::mock_optimizer::MockOptimizer;
 
 type ConductorCostModel = DummyCostModel;
 type Optimizer = MockOptimizer<ConductorCostModel>;
 
+/// Source of the ids of the `query` spans of statements, unique in the process.
+static QUERY_IDS: AtomicU64 = AtomicU64::new(1);
+
 /// Conductor runs query to the database
 pub struct Conductor {
     pub parser: SQLParser,
     pub optimizer: Optimizer,
     pub executor: Executor,
     pub active_txn: Transaction,
+    /// Session the statements are run for.
+    pub client_id: u64,
+    /// Token that stops the statement when the session is cancelled.
+    pub cancellation: Cancellation,
 }
 
 impl Conductor {
@@ -49,6 +85,8 @@ impl Conductor {
             optimizer,
             executor,
             active_txn: Transaction::new(),
+            client_id: 0,
+            cancellation: Cancellation::new(),
         };
         Ok(conductor)
     }
@@ -57,6 +95,7 @@ impl Conductor {
     pub fn new_from_tid(
         managers: &'static Managers,
         tid: TransactionId,
+        client_id: u64,
     ) -> Result<Self, FairyError> {
         let parser = SQLParser::new();
         let optimizer = Optimizer::new(ConductorCostModel::new(managers.stats), managers);
@@ -66,6 +105,8 @@ impl Conductor {
             optimizer,
             executor,
             active_txn: Transaction::new_from_tid(tid),
+            client_id,
+            cancellation: Cancellation::new(),
         };
         Ok(conductor)
     }
@@ -75,10 +116,45 @@ impl Conductor {
         sql: String,
         db_state: &'static DatabaseState,
     ) -> Result<QueryResult, FairyError> {
+        let span = tracing::info_span!(
+            "query",
+            query_id = QUERY_IDS.fetch_add(1, Ordering::Relaxed),
+            session_id = self.client_id
+        );
+        let _entered = span.enter();
+        // Every statement gets a new token. `\cancel` from another connection
+        // cancels it, and so does the timeout of the session once it ran out.
+        let timeout = db_state.session_settings.get(self.client_id).timeout;
+        self.cancellation = if timeout.is_zero() {
+            Cancellation::new()
+        } else {
+            Cancellation::new().with_timeout(timeout)
+        };
+        let _running = db_state
+            .running_queries
+            .start(self.client_id, &self.cancellation);
         debug!("Parsing SQL: {:?}", &sql);
-        match SQLParser::parse_sql(sql) {
-            ParserResponse::SQL(ast) => self.run_sql(ast, db_state),
-            ParserResponse::SQLError(e) => Err(c_err(format!("SQL error: {}", e).as_str())),
+        SQLParser::check_literal_sizes(&sql, db_state.managers.config.max_field_size)?;
+        match SQLParser::parse_sql(sql.clone()) {
+            ParserResponse::SQL(ast) => self
+                .run_sql_with_retry(ast, db_state)
+                .map_err(|e| locate_error(&sql, e)),
+            ParserResponse::AlterColumnOptions(alter) => {
+                debug!("Processing ALTER COLUMN {}.{}", alter.table, alter.column);
+                db_state
+                    .set_column_options(&alter.table, &alter.column, &alter.options)
+                    .map_err(|e| locate_error(&sql, e))
+            }
+            ParserResponse::CopyTo(copy) => self
+                .copy_to(&copy, db_state)
+                .map_err(|e| locate_error(&sql, e)),
+            ParserResponse::CopyFrom(copy) => self
+                .copy_from(&copy, db_state)
+                .map_err(|e| locate_error(&sql, e)),
+            ParserResponse::Analyze(analyze) => self
+                .analyze(&analyze, db_state)
+                .map_err(|e| locate_error(&sql, e)),
+            ParserResponse::SQLError(e) => Err(SQLParser::syntax_error(&sql, &e)),
             ParserResponse::SQLConstraintError(msg) => {
                 Err(c_err(format!("SQL constraint error: {}", msg).as_str()))
             }
@@ -86,6 +162,43 @@ impl Conductor {
         }
     }
 
+    /// Runs a statement and, if its transaction is aborted and running it again
+    /// is safe, retries it in a new transaction. There are no explicit
+    /// transactions yet, so every statement runs in an implicit transaction of
+    /// its own; once there are, statements inside one must report the abort to
+    /// the client instead.
+    fn run_sql_with_retry(
+        &mut self,
+        ast: Vec<Statement>,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        if !ast.first().is_some_and(is_reexecutable) {
+            return self.run_sql(ast, db_state);
+        }
+        let policy = StatementRetryPolicy::from_config(db_state.managers.config);
+        let (res, retries) = policy.retry(|attempt| {
+            if attempt > 0 {
+                // The aborted transaction was rolled back, run again in a new one.
+                let tid = db_state.assign_new_tid(self.client_id);
+                self.active_txn = Transaction::new_from_tid(tid);
+            }
+            self.run_sql(ast.clone(), db_state)
+        });
+        if retries == 0 {
+            return res;
+        }
+        db_state
+            .query_metrics
+            .statement_retries
+            .fetch_add(retries as u64, Ordering::Relaxed);
+        res.map(|qr| {
+            qr.with_notice(format!(
+                "NOTICE: statement retried {} time(s) after its transaction was aborted",
+                retries
+            ))
+        })
+    }
+
     pub fn to_logical_plan(
         &self,
         sql: &str,
@@ -104,12 +217,17 @@ impl Conductor {
                             &enabled_rules,
                             &db_state.col_id_gen,
                         )
-                        .map_err(|e| c_err(format!("{}", e).as_str()))
+                        .map_err(translator_error)
+                        .map_err(|e| locate_error(sql, e))
                     }
                     _ => Err(c_err("Not a query")),
                 }
             }
-            ParserResponse::SQLError(e) => Err(c_err(format!("SQL error: {}", e).as_str())),
+            ParserResponse::AlterColumnOptions(_)
+            | ParserResponse::CopyTo(_)
+            | ParserResponse::CopyFrom(_)
+            | ParserResponse::Analyze(_) => Err(c_err("Not a query")),
+            ParserResponse::SQLError(e) => Err(SQLParser::syntax_error(sql, &e)),
             ParserResponse::SQLConstraintError(msg) => {
                 Err(c_err(format!("SQL constraint error: {}", msg).as_str()))
             }
@@ -122,9 +240,51 @@ impl Conductor {
         logical_plan: Query,
         db_state: &'static DatabaseState,
     ) -> Result<PhysicalRelExpr, FairyError> {
-        Ok(self
-            .optimizer
-            .optimize(&logical_plan, Some(&db_state.query_registrar)))
+        let deadline = self.planning_deadline(db_state);
+        Ok(self.optimize(&logical_plan, db_state, &deadline))
+    }
+
+    /// Starts the planning budget of the session.
+    fn planning_deadline(&self, db_state: &'static DatabaseState) -> PlanningDeadline {
+        PlanningDeadline::new(
+            db_state
+                .session_settings
+                .get(self.client_id)
+                .planning_timeout,
+        )
+    }
+
+    /// Optimizes a logical plan with the statistics of the session, which may
+    /// be hypothetical.
+    fn optimize(
+        &self,
+        logical_plan: &Query,
+        db_state: &'static DatabaseState,
+        deadline: &PlanningDeadline,
+    ) -> PhysicalRelExpr {
+        let overlay = db_state.session_settings.hypothetical_stats(self.client_id);
+        let stats = StatsView::new(db_state.managers.stats).with_overlay(overlay.as_deref());
+        self.optimizer.optimize_with_stats(
+            logical_plan,
+            Some(&db_state.query_registrar),
+            &stats,
+            deadline,
+        )
+    }
+
+    /// Note telling the client that the last plan skipped optimizations
+    /// because planning ran over the session's budget.
+    fn truncated_planning_notice(&self, db_state: &'static DatabaseState) -> Option<String> {
+        self.optimizer.last_planning_truncated().then(|| {
+            format!(
+                "Planning exceeded its budget of {} ms, some optimizations were skipped",
+                db_state
+                    .session_settings
+                    .get(self.client_id)
+                    .planning_timeout
+                    .as_millis()
+            )
+        })
     }
 
     pub fn run_physical_plan(
@@ -132,18 +292,50 @@ impl Conductor {
         physical_plan: PhysicalRelExpr,
         db_state: &'static DatabaseState,
     ) -> Result<QueryResult, FairyError> {
-        let op_iterator = physical_plan_to_op_iterator(
+        let _span = tracing::info_span!("execute").entered();
+        let (op_iterator, plan_schema, analyzed) =
+            self.build_plan(&physical_plan, db_state, false)?;
+        // We populate the executor with the state: physical plan, and storage manager ref
+        self.executor
+            .configure_query_with_schema(op_iterator, plan_schema);
+
+        // Finally, execute the query
+        let result = self
+            .executor
+            .execute()?
+            .with_notices(analyzed.adapted().then_some(Notice::AdaptiveJoinSwap));
+        if let Some(profile) = analyzed.profile() {
+            db_state.session_profiles.record(self.client_id, profile);
+        }
+        match analyzed.corrupt_records() {
+            0 => Ok(result),
+            skipped => Ok(result.with_footer(format!("Skipped {} corrupt records", skipped))),
+        }
+    }
+
+    /// Converts a physical plan to an opiterator, with the scans treating corrupt
+    /// records as the session's settings say and stopping once the statement is
+    /// cancelled. With `profile` the operators are profiled whatever
+    /// `profile_operators` says.
+    fn build_plan(
+        &self,
+        physical_plan: &PhysicalRelExpr,
+        db_state: &'static DatabaseState,
+        profile: bool,
+    ) -> Result<(Box<dyn OpIterator>, PlanSchema, AnalyzedPlan), FairyError> {
+        physical_plan_to_analyzed_op_iterator(
             db_state.managers,
             &db_state.catalog,
-            &physical_plan,
+            physical_plan,
             self.active_txn.tid()?,
             db_state.get_current_time(),
-        )?;
-        // We populate the executor with the state: physical plan, and storage manager ref
-        self.executor.configure_query(op_iterator);
-
-        // Finally, execute the query
-        self.executor.execute()
+            db_state
+                .session_settings
+                .get(self.client_id)
+                .corrupt_records,
+            &self.cancellation,
+            profile,
+        )
     }
 
     pub fn run_opiterator(
@@ -170,37 +362,270 @@ impl Conductor {
                 name: table_name,
                 columns,
                 constraints,
+                with_options,
+                if_not_exists,
                 ..
             } => {
                 debug!("Processing CREATE table: {:?}", table_name);
                 debug!("Columns: {:?}", columns);
-                db_state.create_table(&get_name(table_name)?, columns, constraints)
+                for ident in &table_name.0 {
+                    check_not_reserved(ident, ObjectKind::Table)?;
+                }
+                for column in columns {
+                    check_not_reserved(&column.name, ObjectKind::Column)?;
+                }
+                db_state.create_table(
+                    &get_name(table_name)?,
+                    columns,
+                    constraints,
+                    with_options,
+                    *if_not_exists,
+                )
             }
             Statement::Query(qbox) => {
                 debug!("Processing SQL Query");
-                let enabled_rules = Arc::new(Rules::default());
-                let lp = Translator::from_sql(
-                    qbox,
-                    &db_state.catalog,
-                    &enabled_rules,
-                    &db_state.col_id_gen,
-                )
-                .unwrap();
-
-                // println!("Optimize start time: {:?}", std::time::Instant::now());
-
-                // inside here, see if any parts of the plan already exist (use hash)
-                // we pass the optional query registrar to replace subplans (TODO)
-                let pp = self
-                    .optimizer
-                    .optimize(&lp, Some(&db_state.query_registrar));
-
-                debug!("Optimized plan: {:?}", pp);
-
-                // TESTING - optimizer above will return subset stub for now if it exists
-                //   so that we can see what running a physical subplan looks like
-
-                self.run_physical_plan(pp, db_state)
+                if let Some(res) = system_tables::run_system_query(qbox, db_state) {
+                    return res;
+                }
+                self.run_query(qbox, db_state)
+            }
+            Statement::Explain {
+                analyze, statement, ..
+            } => {
+                if !analyze {
+                    if let Some(plan) = self.explain_write(statement, db_state)? {
+                        return Ok(QueryResult::MessageOnly(plan));
+                    }
+                }
+                let Statement::Query(qbox) = statement.as_ref() else {
+                    return Err(c_err(if *analyze {
+                        "EXPLAIN ANALYZE is only supported for queries"
+                    } else {
+                        "EXPLAIN is only supported for queries, INSERT with VALUES and DELETE"
+                    }));
+                };
+                debug!("Processing EXPLAIN (analyze: {})", analyze);
+                let pp = self.plan_query(qbox, db_state)?;
+                // Only the plan is chosen with the hypothetical statistics, running
+                // it reads the real ones.
+                let hypothetical =
+                    match db_state.session_settings.hypothetical_stats(self.client_id) {
+                        Some(_) => "\nUsing hypothetical stats",
+                        None => "",
+                    };
+                let mut notes = self
+                    .truncated_planning_notice(db_state)
+                    .map_or(String::new(), |notice| format!("\n{}", notice));
+                for notice in plan_notices(&pp) {
+                    notes.push_str(&format!("\nWarning: {}", notice));
+                }
+                if !analyze {
+                    return Ok(QueryResult::MessageOnly(format!(
+                        "{}{}{}",
+                        pp.pretty_string(),
+                        hypothetical,
+                        notes
+                    )));
+                }
+                let mut table_ids = Vec::new();
+                pp.get_tables_involved(&mut table_ids);
+                let _lock = db_state.lock_tables(&table_ids, TableLockMode::Shared)?;
+                // Subqueries run while the plan is built, so their pages count.
+                let pages = db_state.managers.sm.mem_stats();
+                let (op_iterator, plan_schema, analyzed) = self.build_plan(&pp, db_state, true)?;
+                self.executor
+                    .configure_query_with_schema(op_iterator, plan_schema);
+                let rows = self
+                    .executor
+                    .execute()?
+                    .get_tuples()
+                    .map_or(0, |tuples| tuples.len());
+                let io = db_state.managers.sm.mem_stats().io_since(&pages);
+                let mut msg = format!("{}Rows: {}", analyzed.pretty_string(), rows);
+                if analyzed.corrupt_records() > 0 {
+                    msg.push_str(&format!(
+                        "\nSkipped {} corrupt records",
+                        analyzed.corrupt_records()
+                    ));
+                }
+                if analyzed.subqueries_run() > 0 {
+                    msg.push_str(&format!("\nSubqueries run: {}", analyzed.subqueries_run()));
+                }
+                if analyzed.subqueries_spilled() > 0 {
+                    msg.push_str(&format!(
+                        "\nSubqueries spilled: {}",
+                        analyzed.subqueries_spilled()
+                    ));
+                }
+                msg.push_str(hypothetical);
+                msg.push_str(&notes);
+                if let Some(profile) = analyzed.profile() {
+                    msg.push_str(&format!("\nProfile:\n{}", profile));
+                    db_state.session_profiles.record(self.client_id, profile);
+                }
+                msg.push_str(&format!("\n{}", io));
+                Ok(QueryResult::MessageOnly(msg))
+            }
+            Statement::SetVariable {
+                variable, value, ..
+            } => {
+                let value = match value.as_slice() {
+                    [Expr::Identifier(ident)] => ident.value.clone(),
+                    [Expr::Value(Value::SingleQuotedString(s))] => s.clone(),
+                    [Expr::Value(Value::Number(n, _))] => n.clone(),
+                    _ => return Err(c_err("SET expects a single name, number or string value")),
+                };
+                let name = get_name(variable)?;
+                if name.eq_ignore_ascii_case(AUTO_COMPACTION) {
+                    let enabled = match value.to_ascii_lowercase().as_str() {
+                        "on" | "true" => true,
+                        "off" | "false" => false,
+                        _ => {
+                            return Err(FairyError::ValidationError(format!(
+                                "Invalid value {} for {}, expected on or off",
+                                value, AUTO_COMPACTION
+                            )))
+                        }
+                    };
+                    db_state.compaction.set_enabled(enabled);
+                } else if name.eq_ignore_ascii_case(HYPOTHETICAL_STATS) {
+                    let stats = match value.to_ascii_lowercase().as_str() {
+                        "" | "none" | "default" => None,
+                        _ => Some(HypotheticalStats::parse(&value, &db_state.catalog)?),
+                    };
+                    db_state
+                        .session_settings
+                        .set_hypothetical_stats(self.client_id, stats);
+                } else {
+                    db_state
+                        .session_settings
+                        .set(self.client_id, &name, &value)?;
+                }
+                Ok(QueryResult::MessageOnly(format!(
+                    "SET {} = {}",
+                    name, value
+                )))
+            }
+            Statement::ShowVariable { variable } => {
+                let name = variable
+                    .iter()
+                    .map(|ident| ident.value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                if let Some(value) = db_state.session_settings.show(self.client_id, &name) {
+                    return Ok(system_tables::show_setting(
+                        &name.to_ascii_lowercase(),
+                        value,
+                    ));
+                }
+                system_tables::show_server_setting(db_state, &name)
+                    .ok_or_else(|| FairyError::ValidationError(format!("Unknown setting {}", name)))
+            }
+            Statement::Declare { name, query, .. } => {
+                debug!("Processing DECLARE CURSOR {}", name);
+                let pp = self.plan_query(query, db_state)?;
+                let mut table_ids = Vec::new();
+                pp.get_tables_involved(&mut table_ids);
+                let _lock = db_state.lock_tables(&table_ids, TableLockMode::Shared)?;
+                let (op_iterator, plan_schema, analyzed) = self.build_plan(&pp, db_state, false)?;
+                let stamps = SchemaStamp::from_plan(&pp, &db_state.catalog);
+                let mut notices = plan_notices(&pp);
+                notices.extend(stale_stats_notice(&table_ids, db_state));
+                let cursor = Cursor::open(op_iterator, plan_schema, stamps, analyzed, notices)?;
+                db_state.cursors.declare(
+                    self.client_id,
+                    &name.value,
+                    cursor,
+                    db_state.managers.config.max_cursors_per_session,
+                )?;
+                Ok(QueryResult::MessageOnly(format!(
+                    "Cursor {} declared",
+                    name
+                )))
+            }
+            Statement::Fetch {
+                name,
+                direction,
+                into: None,
+            } => {
+                debug!("Processing FETCH {} FROM {}", direction, name);
+                let count = fetch_count(direction)?;
+                // Hold off DDL on the cursor's tables while it runs
+                let table_ids = db_state.cursors.tables(self.client_id, &name.value)?;
+                let _lock = db_state.lock_tables(&table_ids, TableLockMode::Shared)?;
+                db_state
+                    .cursors
+                    .fetch(self.client_id, &name.value, count, &db_state.catalog)
+            }
+            Statement::Fetch { .. } => Err(c_err("FETCH INTO is not supported")),
+            Statement::Close { cursor } => match cursor {
+                CloseCursor::Specific { name } => {
+                    debug!("Processing CLOSE {}", name);
+                    db_state.cursors.close(self.client_id, &name.value)?;
+                    Ok(QueryResult::MessageOnly(format!("Cursor {} closed", name)))
+                }
+                CloseCursor::All => {
+                    db_state.cursors.close_session(self.client_id);
+                    Ok(QueryResult::MessageOnly("All cursors closed".to_string()))
+                }
+            },
+            Statement::Prepare {
+                name,
+                data_types,
+                statement,
+            } => {
+                debug!("Processing PREPARE {}", name);
+                if !data_types.is_empty() {
+                    return Err(c_err(
+                        "PREPARE with parameter types is not supported, a parameter takes the \
+                         type of what it is compared with",
+                    ));
+                }
+                let Statement::Query(query) = statement.as_ref() else {
+                    return Err(c_err("PREPARE is only supported for queries"));
+                };
+                let pp = self.plan_query(query, db_state)?;
+                db_state.prepared_statements.prepare(
+                    self.client_id,
+                    &name.value,
+                    PreparedStatement::new(pp, &db_state.catalog),
+                )?;
+                Ok(QueryResult::MessageOnly(format!(
+                    "Statement {} prepared",
+                    name
+                )))
+            }
+            Statement::Execute { name, parameters } => {
+                debug!("Processing EXECUTE {}", name);
+                let params = parameters
+                    .iter()
+                    .map(get_parameter_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let statement = db_state
+                    .prepared_statements
+                    .get(self.client_id, &name.value)?;
+                // Checked before locking as well, a dropped table cannot be
+                // locked.
+                statement.check(&name.value, &db_state.catalog)?;
+                let table_ids = statement.tables();
+                let _lock = db_state.lock_tables(&table_ids, TableLockMode::Shared)?;
+                statement.check(&name.value, &db_state.catalog)?;
+                // The plan is bound as prepared, without planning the query again.
+                let mut pp = statement.plan;
+                bind_parameters(&mut pp, &params)?;
+                let mut notices = plan_notices(&pp);
+                notices.extend(stale_stats_notice(&table_ids, db_state));
+                Ok(self.run_physical_plan(pp, db_state)?.with_notices(notices))
+            }
+            Statement::Deallocate { name, .. } => {
+                debug!("Processing DEALLOCATE {}", name);
+                db_state
+                    .prepared_statements
+                    .deallocate(self.client_id, &name.value)?;
+                Ok(QueryResult::MessageOnly(format!(
+                    "Statement {} deallocated",
+                    name
+                )))
             }
             Statement::Insert {
                 table_name,
@@ -218,58 +643,732 @@ impl Conductor {
                     return Err(c_err("No source for insert"));
                 };
                 match source.body.as_ref() {
-                    SetExpr::Values(values) if columns.is_empty() => {
+                    SetExpr::Values(values) => {
                         // identify the table id and schema of the table via catalog
                         let table_name = get_name(table_name)?;
-                        let table_id = db_state.catalog.get_table_id(&table_name);
-                        let table_schema = db_state.catalog.get_table_schema(table_id).unwrap();
-                        let count = self.executor.import_tuples(
-                            values,
-                            &table_name,
-                            &table_id,
-                            &table_schema,
-                            self.active_txn.tid()?,
-                        )?;
+                        let table_id = get_table_id(&table_name, db_state)?;
+                        let _lock = db_state.lock_tables(&[table_id], TableLockMode::Shared)?;
+                        let table = db_state.catalog.get_table(table_id).unwrap();
+                        let col_order = insert_columns(&table.schema, columns)?;
+                        let tid = self.active_txn.tid()?;
+                        let write = db_state.begin_write(&table, tid);
+                        let count = self
+                            .executor
+                            .import_tuples(values, &table, col_order, tid)?;
+                        self.executor.managers.sm.commit(tid)?;
+                        if let Some(write) = write {
+                            write.commit();
+                        }
+                        db_state
+                            .session_writes
+                            .record_write(self.client_id, table_id);
                         let qr = QueryResult::new_insert_result(count, table_name);
                         Ok(qr)
                     }
-                    SetExpr::Values(_) => {
-                        Err(c_err(
-                            "Inserts with columns specified are not currently supported. Must supply values for the entire table",
-                        ))
-                    }
-                    _ => {
-                        Err(c_err(
-                            "Inserts via query not currently supported. Must supply values",
-                        ))
-                    }
+                    _ => Err(c_err(
+                        "Inserts via query not currently supported. Must supply values",
+                    )),
+                }
+            }
+            statement @ Statement::Delete { from, .. } => {
+                debug!("Processing DELETE FROM {:?}", from);
+                let (target, table_name, selection) = delete_target(statement)?;
+                self.delete(target, &table_name, selection, db_state)
+            }
+            Statement::Drop {
+                object_type: ObjectType::Table,
+                if_exists,
+                names,
+                ..
+            } => {
+                debug!("Processing DROP TABLE {:?}", names);
+                let mut res = Err(c_err("No table to drop"));
+                for name in names {
+                    res = Ok(db_state.drop_table(&get_name(name)?, *if_exists)?);
                 }
+                res
+            }
+            Statement::Comment {
+                object_type,
+                object_name,
+                comment,
+                if_exists,
+            } => {
+                debug!("Processing COMMENT on {:?} {}", object_type, object_name);
+                db_state.set_comment(object_type, object_name, comment.clone(), *if_exists)
             }
-            _ => {
-                unimplemented!()
+            Statement::ShowTables { filter: None, .. } => {
+                Ok(system_tables::show_tables(&db_state.catalog))
+            }
+            Statement::ShowColumns {
+                table_name,
+                filter: None,
+                ..
+            }
+            | Statement::ExplainTable { table_name, .. } => {
+                system_tables::show_columns(&db_state.catalog, &get_name(table_name)?)
+            }
+            Statement::ShowTables { .. } | Statement::ShowColumns { .. } => Err(c_err(
+                "LIKE and WHERE filters are not supported on SHOW statements",
+            )),
+            // Unique keys are only checked on inserts, an UPDATE would need
+            // the same check.
+            Statement::Update { .. } => Err(c_err(
+                "UPDATE is not supported, delete the rows and insert them again",
+            )),
+            _ => Err(c_err("This statement is not supported")),
+        }
+    }
+
+    /// Deletes the rows of `target` that satisfy `selection`, found as the query
+    /// `SELECT * FROM target WHERE selection` would find them. Without a
+    /// selection the table is emptied at once.
+    fn delete(
+        &mut self,
+        target: &TableWithJoins,
+        table_name: &str,
+        selection: Option<&Expr>,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let table_id = get_table_id(table_name, db_state)?;
+        let _lock = db_state.lock_tables(&[table_id], TableLockMode::Shared)?;
+        let table = db_state.catalog.get_table(table_id).unwrap();
+        let tid = self.active_txn.tid()?;
+        let filter = match self.delete_plan(target, selection, db_state)? {
+            Some(plan) => Some(table_row_filter(&plan)?),
+            None => None,
+        };
+        let write = db_state.begin_write(&table, tid);
+        let deleted = match filter {
+            Some(filter) => {
+                let records = RecordCheck::new(
+                    db_state
+                        .session_settings
+                        .get(self.client_id)
+                        .corrupt_records,
+                );
+                let scan = SeqScan::new(
+                    db_state.managers,
+                    &table.schema,
+                    &table_id,
+                    tid,
+                    filter,
+                    None,
+                )
+                .with_record_check(&table.schema, records)
+                .with_cancellation(self.cancellation.clone());
+                self.executor.delete_records(Box::new(scan), tid)?
+            }
+            None => self
+                .executor
+                .truncate_table(&table_id, &table.schema, tid)?,
+        };
+        self.executor.managers.sm.commit(tid)?;
+        if let Some(write) = write {
+            write.commit();
+        }
+        db_state
+            .session_writes
+            .record_write(self.client_id, table_id);
+        Ok(QueryResult::MessageOnly(format!("DELETE {}", deleted)))
+    }
+
+    /// Plans `SELECT * FROM target WHERE selection`, the query that finds the
+    /// rows a DELETE deletes. Returns None without a selection.
+    fn delete_plan(
+        &self,
+        target: &TableWithJoins,
+        selection: Option<&Expr>,
+        db_state: &'static DatabaseState,
+    ) -> Result<Option<PhysicalRelExpr>, FairyError> {
+        let Some(selection) = selection else {
+            return Ok(None);
+        };
+        let sql = format!("SELECT * FROM {} WHERE {}", target, selection);
+        let query = Parser::new(&GenericDialect {})
+            .try_with_sql(&sql)
+            .and_then(|mut parser| parser.parse_query())
+            .map_err(|e| SQLParser::syntax_error(&sql, &e))?;
+        self.plan_query(&query, db_state).map(Some)
+    }
+
+    /// Describes how a DELETE or an INSERT with VALUES would run, without
+    /// running it: the plan of the query that finds the rows to delete, or the
+    /// number of rows to insert. Returns None for other statements.
+    fn explain_write(
+        &self,
+        statement: &Statement,
+        db_state: &'static DatabaseState,
+    ) -> Result<Option<String>, FairyError> {
+        match statement {
+            Statement::Delete { .. } => {
+                let (target, table_name, selection) = delete_target(statement)?;
+                get_table_id(&table_name, db_state)?;
+                let explained = match self.delete_plan(target, selection, db_state)? {
+                    Some(plan) => {
+                        // Fails as the DELETE would for conditions it cannot run.
+                        table_row_filter(&plan)?;
+                        format!("Delete from {}\n{}", table_name, plan.pretty_string())
+                    }
+                    None => format!("Delete all rows of {}, emptying its pages", table_name),
+                };
+                Ok(Some(explained))
+            }
+            Statement::Insert {
+                table_name,
+                columns,
+                source: Some(source),
+                ..
+            } => {
+                let SetExpr::Values(values) = source.body.as_ref() else {
+                    return Ok(None);
+                };
+                let table_name = get_name(table_name)?;
+                let table_id = get_table_id(&table_name, db_state)?;
+                let table = db_state
+                    .catalog
+                    .get_table(table_id)
+                    .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.clone()))?;
+                insert_columns(&table.schema, columns)?;
+                Ok(Some(format!(
+                    "Insert {} rows into {}",
+                    values.rows.len(),
+                    table_name
+                )))
             }
+            _ => Ok(None),
         }
     }
 
+    /// Plans and runs a query, keeping the tables it reads from being dropped
+    /// until it is done.
+    fn run_query(
+        &mut self,
+        query: &SqlQuery,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let pp = self.plan_query(query, db_state)?;
+        let notice = self.truncated_planning_notice(db_state);
+
+        // TESTING - optimizer above will return subset stub for now if it exists
+        //   so that we can see what running a physical subplan looks like
+
+        // Keep the scanned tables from being dropped until the query is done
+        let mut table_ids = Vec::new();
+        pp.get_tables_involved(&mut table_ids);
+        let _lock = db_state.lock_tables(&table_ids, TableLockMode::Shared)?;
+        let mut notices = plan_notices(&pp);
+        notices.extend(stale_stats_notice(&table_ids, db_state));
+        let result = self.run_physical_plan(pp, db_state)?.with_notices(notices);
+        Ok(match notice {
+            Some(notice) => result.with_notice(notice),
+            None => result,
+        })
+    }
+
+    /// Translates and optimizes a query.
+    fn plan_query(
+        &self,
+        query: &SqlQuery,
+        db_state: &'static DatabaseState,
+    ) -> Result<PhysicalRelExpr, FairyError> {
+        let _span = tracing::info_span!("plan").entered();
+        // The budget covers translating the query too.
+        let deadline = self.planning_deadline(db_state);
+        let enabled_rules = Arc::new(Rules::default());
+        let lp = Translator::from_sql(
+            query,
+            &db_state.catalog,
+            &enabled_rules,
+            &db_state.col_id_gen,
+        )
+        .map_err(translator_error)?;
+
+        // println!("Optimize start time: {:?}", std::time::Instant::now());
+
+        // inside here, see if any parts of the plan already exist (use hash)
+        // we pass the optional query registrar to replace subplans (TODO)
+        let pp = self.optimize(&lp, db_state, &deadline);
+
+        debug!("Optimized plan: {:?}", pp);
+        let settings = db_state.session_settings.get(self.client_id);
+        if settings.forbid_cross_products && pp.has_cross_product() {
+            return Err(FairyError::ExecutionError(
+                "The plan has a cross product, a join without a predicate, which \
+                 forbid_cross_products rejects"
+                    .to_string(),
+            ));
+        }
+        Ok(pp)
+    }
+
     pub fn import_csv(
         &mut self,
         table_name: &str,
         file_path: &Path,
         db_state: &'static DatabaseState,
     ) -> Result<QueryResult, FairyError> {
-        let table_id = db_state.catalog.get_table_id(table_name);
-        let table_schema = db_state.catalog.get_table_schema(table_id).unwrap();
-        let file = OpenOptions::new().read(true).open(file_path).unwrap();
-        let mut csv_reader = CsvReader::new(file, &table_schema, b',', false).unwrap();
+        self.import_file(table_name, file_path, false, Utf8Policy::Strict, db_state)
+    }
+
+    /// Inserts the records of a CSV file into a table. Invalid UTF-8 fails the
+    /// import or is replaced, depending on `utf8`.
+    fn import_file(
+        &mut self,
+        table_name: &str,
+        file_path: &Path,
+        has_header: bool,
+        utf8: Utf8Policy,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let table_id = get_table_id(table_name, db_state)?;
+        let _lock = db_state.lock_tables(&[table_id], TableLockMode::Shared)?;
+        let table = db_state.catalog.get_table(table_id).unwrap();
+        let file = OpenOptions::new().read(true).open(file_path).map_err(|e| {
+            FairyError::IOError(format!("Could not read {}: {}", file_path.display(), e))
+        })?;
+        let mut csv_reader = CsvReader::new(file, &table.schema, b',', has_header)?
+            .with_utf8_policy(utf8)
+            .with_max_field_size(db_state.managers.config.max_field_size);
+        let tid = self.active_txn.tid()?;
+        let write = db_state.begin_write(&table, tid);
         let num_inserts = self
             .executor
-            .import_records_from_reader(&mut csv_reader, &table_id, self.active_txn.tid()?)
-            .unwrap();
+            .import_records_from_reader(&mut csv_reader, &table, tid)?;
+        self.executor.managers.sm.commit(tid)?;
+        if let Some(write) = write {
+            write.commit();
+        }
+        db_state
+            .session_writes
+            .record_write(self.client_id, table_id);
         Ok(QueryResult::new_insert_result(
             num_inserts,
             table_name.to_string(),
         ))
     }
+
+    /// Writes the rows of a table or query to a CSV file on the server. With the
+    /// SNAPSHOT option a table is exported as it was when the export started,
+    /// while writers continue.
+    fn copy_to(
+        &mut self,
+        copy: &CopyTo,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let mut snapshot = false;
+        let mut header = false;
+        for (name, value) in &copy.options {
+            match name.value.to_ascii_lowercase().as_str() {
+                "snapshot" => snapshot = copy_switch(name, value)?,
+                "header" => header = copy_switch(name, value)?,
+                _ => copy_format_option(name, value)?,
+            }
+        }
+        let result =
+            match (whole_table(&copy.source)?, &copy.source) {
+                (Some(table_name), _) => self.scan_table(&table_name, snapshot, db_state)?,
+                (None, _) if snapshot => return Err(c_err(
+                    "COPY with SNAPSHOT only exports whole tables, as in COPY (SELECT * FROM t)",
+                )),
+                (None, CopySource::Query(query)) => self.run_query(query, db_state)?,
+                (None, CopySource::Table { .. }) => unreachable!("tables are copied whole"),
+            };
+
+        let tuples = result.get_tuples().map_or(&[][..], |t| t.as_slice());
+        let io_err = |e: std::io::Error| {
+            FairyError::IOError(format!("Could not write {}: {}", copy.filename, e))
+        };
+        let mut file = BufWriter::new(File::create(&copy.filename).map_err(io_err)?);
+        if let (true, Some(schema)) = (header, result.get_schema()) {
+            let names = schema.attributes().map(|a| Cow::from(a.name()));
+            writeln!(file, "{}", csv_line(names)).map_err(io_err)?;
+        }
+        for tuple in tuples {
+            writeln!(file, "{}", tuple.to_csv()).map_err(io_err)?;
+        }
+        file.flush().map_err(io_err)?;
+        Ok(QueryResult::MessageOnly(format!("COPY {}", tuples.len())))
+    }
+
+    /// Imports a CSV file on the server into a table. Strings must be valid
+    /// UTF-8 unless the `utf8 lossy` option replaces invalid sequences with
+    /// U+FFFD.
+    fn copy_from(
+        &mut self,
+        copy: &CopyFrom,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let mut header = false;
+        let mut utf8 = Utf8Policy::Strict;
+        for (name, value) in &copy.options {
+            match name.value.to_ascii_lowercase().as_str() {
+                "header" => header = copy_switch(name, value)?,
+                "utf8" => utf8 = value.value.parse()?,
+                _ => copy_format_option(name, value)?,
+            }
+        }
+        let table_name = copy.table.to_string();
+        let path = Path::new(&copy.filename);
+        match self.import_file(&table_name, path, header, utf8, db_state)? {
+            QueryResult::Insert { inserted, .. } => {
+                Ok(QueryResult::MessageOnly(format!("COPY {}", inserted)))
+            }
+            result => Ok(result),
+        }
+    }
+
+    /// Declares the column group an ANALYZE names, if any, and computes the
+    /// joint distributions of all the column groups of the table from its rows.
+    /// The scan reports the pages it read to `\progress` and stops, keeping
+    /// the distributions computed before, once the statement is cancelled or
+    /// runs longer than the analyze timeout.
+    fn analyze(
+        &mut self,
+        analyze: &Analyze,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let start = Instant::now();
+        let table_name = get_name(&analyze.table)?;
+        let table_id = get_table_id(&table_name, db_state)?;
+        if !analyze.columns.is_empty() {
+            let columns: Vec<String> = analyze.columns.iter().map(|c| c.value.clone()).collect();
+            db_state.catalog.add_column_group(table_id, &columns)?;
+        }
+        let table = db_state
+            .catalog
+            .get_table(table_id)
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.clone()))?;
+        if table.column_groups.is_empty() {
+            return Ok(QueryResult::MessageOnly("ANALYZE".to_string()));
+        }
+        let mut counters = table
+            .column_groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|column| {
+                        table.schema.get_field_index(column).ok_or_else(|| {
+                            FairyError::NotFound(
+                                ObjectKind::Column,
+                                format!("{}.{}", table_name, column),
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<usize>, FairyError>>()
+                    .map(ColumnGroupCounter::new)
+            })
+            .collect::<Result<Vec<_>, FairyError>>()?;
+
+        // ANALYZE reads the whole table, so the query timeout of the session
+        // does not apply to it.
+        let cancellation = match db_state.managers.config.analyze_timeout_ms {
+            0 => self.cancellation.clone().without_timeout(),
+            ms => self
+                .cancellation
+                .clone()
+                .with_timeout(Duration::from_millis(ms)),
+        };
+        let running = &db_state.running_queries;
+        running.report_pages(self.client_id, 0);
+        let _lock = db_state.lock_tables(&[table_id], TableLockMode::Shared)?;
+        let records = RecordCheck::new(
+            db_state
+                .session_settings
+                .get(self.client_id)
+                .corrupt_records,
+        );
+        let mut scan = SeqScan::new(
+            db_state.managers,
+            &table.schema,
+            &table_id,
+            self.active_txn.tid()?,
+            None,
+            None,
+        )
+        .with_record_check(&table.schema, records)
+        .with_cancellation(cancellation.clone());
+        let mut pages = 0;
+        let mut page = None;
+        scan.configure(false);
+        scan.open()?;
+        while let Some(tuple) = scan.next()? {
+            let tuple_page = tuple.value_id.and_then(|id| id.page_id);
+            if pages == 0 || tuple_page != page {
+                page = tuple_page;
+                pages += 1;
+                running.report_pages(self.client_id, pages);
+                cancellation.check("analyze")?;
+            }
+            for counter in counters.iter_mut() {
+                counter.add(&tuple);
+            }
+        }
+        scan.close()?;
+        for counter in counters {
+            db_state
+                .managers
+                .stats
+                .set_column_group(table_id, counter.finish())?;
+        }
+        Ok(QueryResult::MessageOnly(format!(
+            "ANALYZE: {} pages scanned in {} ms",
+            pages,
+            start.elapsed().as_millis()
+        )))
+    }
+
+    /// Reads every row of a table. With `snapshot`, only the rows committed when
+    /// the scan starts are read, which requires the table to stamp its rows.
+    fn scan_table(
+        &mut self,
+        table_name: &str,
+        snapshot: bool,
+        db_state: &'static DatabaseState,
+    ) -> Result<QueryResult, FairyError> {
+        let table_id = get_table_id(table_name, db_state)?;
+        // Writers take shared locks too, so they are not held up by the scan.
+        let _lock = db_state.lock_tables(&[table_id], TableLockMode::Shared)?;
+        let table = db_state
+            .catalog
+            .get_table(table_id)
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.to_string()))?;
+        let records = RecordCheck::new(
+            db_state
+                .session_settings
+                .get(self.client_id)
+                .corrupt_records,
+        );
+        let mut scan = SeqScan::new(
+            db_state.managers,
+            &table.schema,
+            &table_id,
+            self.active_txn.tid()?,
+            None,
+            None,
+        )
+        .with_record_check(&table.schema, records)
+        .with_cancellation(self.cancellation.clone());
+        if snapshot {
+            if !table.txn_stamps {
+                return Err(c_err(&format!(
+                    "COPY with SNAPSHOT requires table {} to be created WITH (txn_stamps = on)",
+                    table_name
+                )));
+            }
+            let sm = db_state.managers.sm;
+            let snapshot = db_state.stamped_writes.snapshot(|| sm.num_pages(table_id));
+            scan = scan.with_snapshot(Arc::new(snapshot));
+        }
+        self.run_opiterator(Box::new(scan))
+    }
+}
+
+/// Value of an on/off COPY option.
+fn copy_switch(name: &Ident, value: &Ident) -> Result<bool, FairyError> {
+    SQLParser::parse_switch(&value.value).ok_or_else(|| {
+        FairyError::ValidationError(format!(
+            "Invalid value {} for COPY option {}, expected on or off",
+            value.value, name.value
+        ))
+    })
+}
+
+/// Checks a COPY option that both directions accept: only the csv format.
+fn copy_format_option(name: &Ident, value: &Ident) -> Result<(), FairyError> {
+    if !name.value.eq_ignore_ascii_case("format") {
+        return Err(FairyError::ValidationError(format!(
+            "Unknown COPY option {}",
+            name.value
+        )));
+    }
+    if !value.value.eq_ignore_ascii_case("csv") {
+        return Err(FairyError::ValidationError(format!(
+            "COPY format {} is not supported, only csv",
+            value.value
+        )));
+    }
+    Ok(())
+}
+
+/// Name of the table a COPY exports whole, if it does, as in `COPY t TO ..` or
+/// `COPY (SELECT * FROM t) TO ..`.
+fn whole_table(source: &CopySource) -> Result<Option<String>, FairyError> {
+    let query = match source {
+        CopySource::Table { table_name, .. } => return get_name(table_name).map(Some),
+        CopySource::Query(query) => query,
+    };
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return Ok(None);
+    };
+    let plain = query.with.is_none()
+        && query.order_by.is_empty()
+        && query.limit.is_none()
+        && query.offset.is_none()
+        && select.distinct.is_none()
+        && select.selection.is_none()
+        && select.having.is_none()
+        && matches!(select.projection.as_slice(), [SelectItem::Wildcard(_)])
+        && matches!(&select.group_by, GroupByExpr::Expressions(exprs) if exprs.is_empty());
+    match select.from.as_slice() {
+        [TableWithJoins {
+            relation: TableFactor::Table {
+                name, args: None, ..
+            },
+            joins,
+        }] if plain && joins.is_empty() => get_name(name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Notices about the shape of a plan, known before it runs.
+fn plan_notices(plan: &PhysicalRelExpr) -> Vec<Notice> {
+    plan.has_cross_product()
+        .then_some(Notice::CrossProduct)
+        .into_iter()
+        .collect()
+}
+
+/// Number of rows a FETCH asks for. Cursors only move forward, so only counts,
+/// NEXT and ALL are accepted.
+/// Notice that one of the tables a plan reads has stale column group
+/// statistics, which the plan may have been chosen with.
+fn stale_stats_notice(
+    table_ids: &[ContainerId],
+    db_state: &'static DatabaseState,
+) -> Option<Notice> {
+    table_ids
+        .iter()
+        .any(|id| db_state.managers.stats.has_stale_column_groups(*id))
+        .then_some(Notice::StaleStats)
+}
+
+fn fetch_count(direction: &FetchDirection) -> Result<usize, FairyError> {
+    let limit = match direction {
+        FetchDirection::Next | FetchDirection::Forward { limit: None } => return Ok(1),
+        FetchDirection::All | FetchDirection::ForwardAll => return Ok(usize::MAX),
+        FetchDirection::Count { limit } | FetchDirection::Forward { limit: Some(limit) } => limit,
+        _ => {
+            return Err(c_err(&format!(
+                "FETCH {} is not supported, cursors only move forward",
+                direction
+            )))
+        }
+    };
+    match limit {
+        Value::Number(n, _) => match n.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(c_err(&format!("Invalid FETCH count {}", n))),
+        },
+        _ => Err(c_err(&format!("Invalid FETCH count {}", limit))),
+    }
+}
+
+/// Fails if an unquoted table or column name is a reserved keyword. Such names
+/// are ambiguous in later statements, so they have to be quoted.
+fn check_not_reserved(ident: &Ident, kind: ObjectKind) -> Result<(), FairyError> {
+    if ident.quote_style.is_none() && is_reserved_keyword(&ident.value) {
+        return Err(FairyError::ValidationError(format!(
+            "{} name {} is a reserved keyword, quote it as {} to use it",
+            kind,
+            ident.value,
+            quote_identifier(&ident.value)
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves a table name, failing with `NotFound` if the table does not exist.
+fn get_table_id(table_name: &str, db_state: &DatabaseState) -> Result<ContainerId, FairyError> {
+    db_state
+        .catalog
+        .get_table_id_if_exists(table_name)
+        .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.to_string()))
+}
+
+/// Indexes in `schema` of the columns an insert lists, or None if it lists
+/// none and gives the values of every column.
+/// The table a DELETE deletes from, its name and the condition on its rows,
+/// for the forms of DELETE that are supported.
+fn delete_target(
+    statement: &Statement,
+) -> Result<(&TableWithJoins, String, Option<&Expr>), FairyError> {
+    let Statement::Delete {
+        tables,
+        from,
+        using,
+        selection,
+        returning,
+        order_by,
+        limit,
+    } = statement
+    else {
+        return Err(c_err("Expected a DELETE"));
+    };
+    if !tables.is_empty()
+        || using.is_some()
+        || returning.is_some()
+        || !order_by.is_empty()
+        || limit.is_some()
+    {
+        return Err(c_err("DELETE only supports FROM and WHERE"));
+    }
+    match from.as_slice() {
+        [target @ TableWithJoins {
+            relation: TableFactor::Table { name, .. },
+            joins,
+        }] if joins.is_empty() => Ok((target, get_name(name)?, selection.as_ref())),
+        _ => Err(c_err("DELETE only deletes from one table")),
+    }
+}
+
+fn insert_columns(
+    schema: &TableSchema,
+    columns: &[Ident],
+) -> Result<Option<Vec<usize>>, FairyError> {
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    let mut order = Vec::with_capacity(columns.len());
+    for column in columns {
+        let name = column.value.as_str();
+        let i = schema
+            .get_field_index(name)
+            .ok_or_else(|| FairyError::UnknownColumn {
+                name: name.to_string(),
+                suggestion: closest_match(name, schema.attributes().map(Attribute::name)),
+            })?;
+        if order.contains(&i) {
+            return Err(c_err(&format!("Column {} is listed more than once", name)));
+        }
+        order.push(i);
+    }
+    Ok(Some(order))
+}
+
+/// Converts a translation error into the error reported to the client.
+fn translator_error(e: TranslatorError) -> FairyError {
+    match e {
+        TranslatorError::TableNotFound(name) => FairyError::NotFound(ObjectKind::Table, name),
+        TranslatorError::ColumnNotFound { name, suggestion } => {
+            FairyError::UnknownColumn { name, suggestion }
+        }
+        e => c_err(format!("{}", e).as_str()),
+    }
+}
+
+/// Positions an error that names part of the statement at the first place
+/// `sql` mentions it.
+fn locate_error(sql: &str, e: FairyError) -> FairyError {
+    let offset = match &e {
+        FairyError::UnknownColumn { name, .. } => SQLParser::find_identifier(sql, name),
+        _ => None,
+    };
+    match offset {
+        Some(offset) => e.at(SourcePosition::from_offset(sql, offset)),
+        None => e,
+    }
 }
 
 // pub struct Conductor {
@@ -0,0 +1,303 @@
+use common::catalog::Catalog;
+use common::error::ObjectKind;
+use common::ids::ContainerId;
+use common::physical_expr::plan_schema::PlanSchema;
+use common::query::query_registrar::{SchemaStamp, StampCheck};
+use common::query::query_result::{Notice, PagingInfo};
+use common::{FairyError, QueryResult, TableSchema, Tuple};
+use queryexe::opiterator::{check_schema, OpIterator};
+use queryexe::query::planner::AnalyzedPlan;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// A query opened by `DECLARE name CURSOR FOR ...`. The opiterator tree stays
+/// open between fetches, each `FETCH` resumes it for the requested number of
+/// rows. The notices of the query come with the last batch, once it is known
+/// how the query ran.
+pub struct Cursor {
+    iter: Option<Box<dyn OpIterator>>,
+    /// Runtime decisions of the plan of `iter`.
+    analyzed: AnalyzedPlan,
+    /// Notices known when the query was planned.
+    notices: Vec<Notice>,
+    schema: TableSchema,
+    /// Tables the query reads, stamped with their schema when it was declared.
+    stamps: Vec<SchemaStamp>,
+    /// Row read ahead of the last batch, to tell whether the cursor is exhausted.
+    lookahead: Option<Tuple>,
+    /// Number of batches fetched so far.
+    batches: u32,
+    /// Why the cursor can no longer be fetched from, set by DDL on a table it reads.
+    invalidated: Option<String>,
+}
+
+impl Cursor {
+    /// Opens `iter` after checking its output against `plan_schema`, the schema
+    /// derived from its plan. `stamps` stamp the tables the query reads, see
+    /// `SchemaStamp::from_plan`, and `notices` are those known from planning.
+    pub fn open(
+        mut iter: Box<dyn OpIterator>,
+        plan_schema: PlanSchema,
+        stamps: Vec<SchemaStamp>,
+        analyzed: AnalyzedPlan,
+        notices: Vec<Notice>,
+    ) -> Result<Self, FairyError> {
+        let schema = iter.get_schema().clone();
+        check_schema(&plan_schema, &schema)?;
+        iter.configure(false);
+        iter.open()?;
+        Ok(Cursor {
+            iter: Some(iter),
+            analyzed,
+            notices,
+            schema,
+            stamps,
+            lookahead: None,
+            batches: 0,
+            invalidated: None,
+        })
+    }
+
+    /// Tables the query reads.
+    pub fn tables(&self) -> Vec<ContainerId> {
+        self.stamps.iter().map(|stamp| stamp.c_id).collect()
+    }
+
+    /// Returns up to `count` rows, `count` is at least 1. The paging info of the result tells whether
+    /// rows remain: `current_page` is the number of this batch, `has_next_page`
+    /// is false once the query is exhausted.
+    fn fetch(
+        &mut self,
+        name: &str,
+        count: usize,
+        catalog: &Catalog,
+    ) -> Result<QueryResult, FairyError> {
+        if let Some(reason) = &self.invalidated {
+            return Err(invalidated(name, reason));
+        }
+        for stamp in &self.stamps {
+            let reason = match stamp.check(catalog) {
+                StampCheck::Valid => continue,
+                StampCheck::Stale => {
+                    let name = catalog.get_table(stamp.c_id).map(|t| t.name.clone());
+                    format!("table {} was altered", name.unwrap_or_default())
+                }
+                StampCheck::Invalid(reason) => reason,
+            };
+            self.invalidate(reason.clone());
+            return Err(invalidated(name, &reason));
+        }
+
+        let mut rows = Vec::new();
+        let was_open = self.iter.is_some();
+        if let Some(iter) = self.iter.as_mut() {
+            rows.extend(self.lookahead.take());
+            while rows.len() < count {
+                match iter.next()? {
+                    Some(t) => rows.push(t),
+                    None => break,
+                }
+            }
+            if rows.len() == count {
+                self.lookahead = iter.next()?;
+            }
+            if self.lookahead.is_none() {
+                // Exhausted: release the operators now rather than on CLOSE.
+                self.release()?;
+            }
+        }
+        self.batches += 1;
+        let exhausted = self.iter.is_none();
+        let paging_info = PagingInfo {
+            current_page: self.batches,
+            total_pages: self.batches + u32::from(!exhausted),
+            has_next_page: !exhausted,
+            page_size: count.try_into().unwrap_or(u32::MAX),
+        };
+        let result = QueryResult::new_select_result(&self.schema, rows, Some(paging_info));
+        // Only the batch that exhausted the query carries its notices.
+        if !(was_open && exhausted) {
+            return Ok(result);
+        }
+        let adapted = self.analyzed.adapted().then_some(Notice::AdaptiveJoinSwap);
+        Ok(result.with_notices(self.notices.iter().copied().chain(adapted)))
+    }
+
+    /// Closes the opiterator tree, releasing the operators and the temporary
+    /// files they spilled to.
+    fn release(&mut self) -> Result<(), FairyError> {
+        self.lookahead = None;
+        match self.iter.take() {
+            Some(mut iter) => iter.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn invalidate(&mut self, reason: String) {
+        if let Err(e) = self.release() {
+            warn!("Failed to close invalidated cursor: {}", e);
+        }
+        self.invalidated = Some(reason);
+    }
+}
+
+fn invalidated(name: &str, reason: &str) -> FairyError {
+    FairyError::ExecutionError(format!("Cursor {} is no longer valid: {}", name, reason))
+}
+
+/// Open cursors of every session, by session and cursor name. A session sees
+/// only its own cursors.
+#[derive(Default)]
+pub struct SessionCursors {
+    sessions: Mutex<HashMap<u64, HashMap<String, Cursor>>>,
+}
+
+impl SessionCursors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a cursor to `session`. Fails, closing `cursor`, if the session
+    /// already has a cursor with the same name or `max_cursors` cursors.
+    pub fn declare(
+        &self,
+        session: u64,
+        name: &str,
+        mut cursor: Cursor,
+        max_cursors: usize,
+    ) -> Result<(), FairyError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let cursors = sessions.entry(session).or_default();
+        let err = if cursors.contains_key(name) {
+            FairyError::AlreadyExists(ObjectKind::Cursor, name.to_string())
+        } else if cursors.len() >= max_cursors {
+            FairyError::ExecutionError(format!(
+                "A session can have at most {} open cursors",
+                max_cursors
+            ))
+        } else {
+            cursors.insert(name.to_string(), cursor);
+            return Ok(());
+        };
+        drop(sessions);
+        if let Err(e) = cursor.release() {
+            warn!("Failed to close cursor {}: {}", name, e);
+        }
+        Err(err)
+    }
+
+    /// Tables read by the cursor `name` of `session`. Fails if the cursor was
+    /// invalidated.
+    pub fn tables(&self, session: u64, name: &str) -> Result<Vec<ContainerId>, FairyError> {
+        let sessions = self.sessions.lock().unwrap();
+        let cursor = sessions
+            .get(&session)
+            .and_then(|cursors| cursors.get(name))
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::Cursor, name.to_string()))?;
+        match &cursor.invalidated {
+            Some(reason) => Err(invalidated(name, reason)),
+            None => Ok(cursor.tables()),
+        }
+    }
+
+    /// Tables read by the open cursors of every session. Their scans are
+    /// paused between fetches, so the values of these tables must stay put.
+    pub fn open_tables(&self) -> HashSet<ContainerId> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .values()
+            .flat_map(HashMap::values)
+            .filter(|cursor| cursor.iter.is_some())
+            .flat_map(Cursor::tables)
+            .collect()
+    }
+
+    /// Fetches up to `count` rows from the cursor `name` of `session`. The caller
+    /// holds shared locks on the tables the cursor reads.
+    pub fn fetch(
+        &self,
+        session: u64,
+        name: &str,
+        count: usize,
+        catalog: &Catalog,
+    ) -> Result<QueryResult, FairyError> {
+        // The cursor is taken out of the map while it runs, so that fetches of
+        // other sessions do not wait for it. Its tables are locked, so no DDL
+        // can look for it in the meantime.
+        let mut cursor = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&session)
+            .and_then(|cursors| cursors.remove(name))
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::Cursor, name.to_string()))?;
+        let result = cursor.fetch(name, count, catalog);
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(session)
+            .or_default()
+            .insert(name.to_string(), cursor);
+        result
+    }
+
+    /// Closes the cursor `name` of `session`.
+    pub fn close(&self, session: u64, name: &str) -> Result<(), FairyError> {
+        let cursor = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&session)
+            .and_then(|cursors| cursors.remove(name));
+        match cursor {
+            Some(mut cursor) => cursor.release(),
+            None => Err(FairyError::NotFound(ObjectKind::Cursor, name.to_string())),
+        }
+    }
+
+    /// Closes every cursor of `session`, e.g. when it disconnects.
+    pub fn close_session(&self, session: u64) {
+        let cursors = self.sessions.lock().unwrap().remove(&session);
+        for (name, mut cursor) in cursors.into_iter().flatten() {
+            if let Err(e) = cursor.release() {
+                warn!("Failed to close cursor {}: {}", name, e);
+            }
+        }
+    }
+
+    /// Closes the cursors of every session, e.g. when the database is reset.
+    pub fn close_all(&self) {
+        let sessions: Vec<u64> = self.sessions.lock().unwrap().keys().copied().collect();
+        for session in sessions {
+            self.close_session(session);
+        }
+    }
+
+    /// Invalidates the cursors of all sessions that read table `c_id`, releasing
+    /// their operators. Fetching from them fails with `reason`.
+    pub fn invalidate_table(&self, c_id: ContainerId, reason: &str) {
+        let mut sessions = self.sessions.lock().unwrap();
+        for cursor in sessions
+            .values_mut()
+            .flat_map(|cursors| cursors.values_mut())
+        {
+            if cursor.invalidated.is_none() && cursor.tables().contains(&c_id) {
+                cursor.invalidate(reason.to_string());
+            }
+        }
+    }
+
+    /// Number of cursors `session` has open, including invalidated ones.
+    pub fn open_count(&self, session: u64) -> usize {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&session).map_or(0, HashMap::len)
+    }
+
+    /// Number of cursors of `session` whose operators are still open.
+    pub fn active_count(&self, session: u64) -> usize {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(&session).map_or(0, |cursors| {
+            cursors.values().filter(|c| c.iter.is_some()).count()
+        })
+    }
+}
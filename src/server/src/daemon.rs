@@ -1,26 +1,50 @@
 use crate::server_state::ServerState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-#[allow(dead_code)]
+/// Longest the daemon sleeps before checking for shutdown again.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+/// Background maintenance thread. Every interval it runs a tick of the
+/// automatic compaction of each database, until the server shuts down.
 pub(crate) struct Daemon {
-    _server_state: &'static ServerState,
-    pub(crate) _thread: Option<thread::JoinHandle<()>>,
+    thread: Option<thread::JoinHandle<()>>,
 }
 
-#[allow(dead_code)]
 impl Daemon {
-    pub(crate) fn new(server_state: &'static ServerState, sleep_sec: u64) -> Self {
-        // This should be async or moved into the workers
-        let thread = std::thread::spawn(move || loop {
-            debug!("Daemon doing stuff");
-
-            thread::sleep(Duration::new(sleep_sec, 0));
+    pub(crate) fn new(
+        server_state: &'static ServerState,
+        interval: Duration,
+        shutdown_signal: Arc<AtomicBool>,
+    ) -> Self {
+        let thread = thread::spawn(move || {
+            let mut next_tick = Instant::now() + interval;
+            while !shutdown_signal.load(Ordering::Acquire) {
+                let now = Instant::now();
+                if now < next_tick {
+                    thread::sleep((next_tick - now).min(SHUTDOWN_POLL));
+                    continue;
+                }
+                for db in server_state.get_dbs() {
+                    let outcome = db.compaction.tick(db, now);
+                    trace!("Maintenance of {}: {:?}", db.name, outcome);
+                }
+                next_tick = now + interval;
+            }
         });
-
         Daemon {
-            _server_state: server_state,
-            _thread: Some(thread),
+            thread: Some(thread),
+        }
+    }
+
+    /// Waits for the thread to notice the shutdown signal and stop.
+    pub(crate) fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Maintenance thread panicked");
+            }
         }
     }
 }
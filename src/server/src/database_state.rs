@@ -6,23 +6,40 @@ use std::hash::{Hash, Hasher};
 use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use crate::{StorageManager, StorageTrait};
 use common::catalog::{Catalog, CatalogRef};
+use common::error::ObjectKind;
 use common::ids::{AtomicTimeStamp, StateMeta};
+use common::metrics::QueryMetrics;
 use common::physical::col_id_generator::{ColIdGenerator, ColIdGeneratorRef};
+use common::physical::page_dictionary::ColumnEncoding;
 use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
-use common::query::query_registrar::QueryStateRegistrar;
-use common::table::TableInfo;
+use common::query::query_registrar::{QueryStateRegistrar, SchemaStamp, StampCheck};
+use common::query::query_result::Notice;
+use common::table::{validate_identifier, TableInfo};
+use common::table_lock::{TableLockGuard, TableLockMode};
 use common::traits::stat_manager_trait::StatManagerTrait;
 use common::traits::state_tracker_trait::StateTrackerTrait;
+use common::txn_stamps::{StampedWrite, StampedWrites};
+use common::util::suggest::closest_match;
 use common::{prelude::*, QUERY_CACHES_DIR_NAME};
 use common::{Attribute, QueryResult};
-use queryexe::query::get_attr;
+use queryexe::query::{get_attr, get_default};
 use queryexe::Managers;
-use sqlparser::ast::ColumnDef;
 use sqlparser::ast::TableConstraint;
-
+use sqlparser::ast::{ColumnDef, ColumnOption};
+use sqlparser::ast::{CommentObject, Ident, ObjectName, SqlOption, Value};
+
+use crate::admission::Admission;
+use crate::compaction::AutoCompaction;
+use crate::cursors::SessionCursors;
+use crate::prepared_statements::SessionPreparedStatements;
+use crate::running_queries::RunningQueries;
+use crate::session_profiles::SessionProfiles;
+use crate::session_settings::SessionSettings;
+use crate::session_writes::SessionWrites;
 use crate::sql_parser::{ParserResponse, SQLParser};
 
 #[derive(Serialize)]
@@ -47,6 +64,47 @@ pub struct DatabaseState {
     // TODO: query registrar state should be persisted (work around physical plan stuff)
     pub query_registrar: QueryStateRegistrar,
 
+    #[serde(skip)]
+    // End-to-end latency of the SQL statements run against this database
+    pub query_metrics: QueryMetrics,
+
+    #[serde(skip)]
+    // Per-session write generations, used to keep cached results from hiding a
+    // session's own writes
+    pub session_writes: SessionWrites,
+
+    #[serde(skip)]
+    // Cursors opened by DECLARE, per session
+    pub cursors: SessionCursors,
+
+    #[serde(skip)]
+    // Statements prepared by PREPARE, per session
+    pub prepared_statements: SessionPreparedStatements,
+
+    #[serde(skip)]
+    // Settings changed with SET, per session
+    pub session_settings: SessionSettings,
+
+    #[serde(skip)]
+    // Operator profile of the last query of each session
+    pub session_profiles: SessionProfiles,
+
+    #[serde(skip)]
+    // Cancellation tokens of the statements each session is running
+    pub running_queries: RunningQueries,
+
+    #[serde(skip)]
+    // Automatic compaction of the tables, run by the maintenance thread
+    pub compaction: AutoCompaction,
+
+    #[serde(skip)]
+    // Writers of the tables stamped with transaction ids, for snapshot exports
+    pub stamped_writes: StampedWrites,
+
+    #[serde(skip)]
+    // Admission of statements to run, shared by all databases of the server
+    pub admission: Arc<Admission>,
+
     client_tids: RwLock<HashMap<u64, TransactionId>>,
 }
 
@@ -84,18 +142,23 @@ impl DatabaseState {
         base_dir: &Path,
         db_name: &str,
         managers: &'static Managers,
+        admission: Arc<Admission>,
     ) -> Result<Self, FairyError> {
         let db_path = base_dir.join(db_name);
         if db_path.exists() {
             // this will no longer ever flag because db states are stored in base_dir/server_state.
             // won't change because going to `else` here is completely safe and we don't expect existence anyways (would've been created on db spin-up)
-            DatabaseState::load(db_path, managers)
+            DatabaseState::load(db_path, managers, admission)
         } else {
-            DatabaseState::new_from_name(db_name, managers)
+            DatabaseState::new_from_name(db_name, managers, admission)
         }
     }
 
-    pub fn new_from_name(db_name: &str, managers: &'static Managers) -> Result<Self, FairyError> {
+    pub fn new_from_name(
+        db_name: &str,
+        managers: &'static Managers,
+        admission: Arc<Admission>,
+    ) -> Result<Self, FairyError> {
         let db_name: String = String::from(db_name);
         let db_id = DatabaseState::get_database_id(&db_name);
         debug!(
@@ -111,12 +174,26 @@ impl DatabaseState {
             container_vec: Arc::new(RwLock::new(HashMap::new())),
             atomic_time: common::ids::AtomicTimeStamp::new(0),
             query_registrar: QueryStateRegistrar::new(),
+            query_metrics: QueryMetrics::new(),
+            session_writes: SessionWrites::new(),
+            cursors: SessionCursors::new(),
+            prepared_statements: SessionPreparedStatements::new(),
+            session_settings: SessionSettings::new(managers.config),
+            session_profiles: SessionProfiles::new(),
+            running_queries: RunningQueries::new(),
+            compaction: AutoCompaction::new(managers.config),
+            stamped_writes: StampedWrites::new(),
+            admission,
             client_tids: RwLock::new(HashMap::new()),
         };
         Ok(db_state)
     }
 
-    pub fn load(filename: PathBuf, managers: &'static Managers) -> Result<Self, FairyError> {
+    pub fn load(
+        filename: PathBuf,
+        managers: &'static Managers,
+        admission: Arc<Admission>,
+    ) -> Result<Self, FairyError> {
         let reader = fs::File::open(filename).expect("error opening db state file");
         let partial_db_state_info: SerializedDatabaseState =
             serde_json::from_reader(reader).expect("error reading from json");
@@ -131,7 +208,24 @@ impl DatabaseState {
             atomic_time: common::ids::AtomicTimeStamp::new(0), // I thihk it's fine to reset this?
             client_tids: RwLock::new(HashMap::new()),
             query_registrar: QueryStateRegistrar::default(), // TODO: persist query_registrar state and inherit from partial
+            query_metrics: QueryMetrics::new(),
+            session_writes: SessionWrites::new(),
+            cursors: SessionCursors::new(),
+            prepared_statements: SessionPreparedStatements::new(),
+            session_settings: SessionSettings::new(managers.config),
+            session_profiles: SessionProfiles::new(),
+            running_queries: RunningQueries::new(),
+            compaction: AutoCompaction::new(managers.config),
+            stamped_writes: StampedWrites::new(),
+            admission,
         };
+        // The storage manager does not persist the encodings of columns.
+        for table in db_state.catalog.get_tables() {
+            let columns = table.dictionary_columns();
+            if !columns.is_empty() {
+                managers.sm.set_dictionary_columns(table.c_id, columns)?;
+            }
+        }
         Ok(db_state)
     }
 
@@ -167,15 +261,15 @@ impl DatabaseState {
     ///
     /// * `name` - Name of the new table.
     /// * `cols` - Table columns.
+    /// * `if_not_exists` - Succeed without changes if the table already exists.
     pub fn create_table(
         &self,
         table_name: &str,
         columns: &[ColumnDef],
         constraints: &[TableConstraint],
+        options: &[SqlOption],
+        if_not_exists: bool,
     ) -> Result<QueryResult, FairyError> {
-        // Constraints aren't implemented yet
-
-        let table_id = self.catalog.get_table_id(table_name);
         let pks = match SQLParser::get_pks(columns, constraints) {
             Ok(pks) => pks,
             Err(ParserResponse::SQLConstraintError(s)) => return Err(FairyError::FairyError(s)),
@@ -184,35 +278,116 @@ impl DatabaseState {
 
         let mut attributes: Vec<Attribute> = Vec::new();
         for col in columns {
-            let constraint = if pks.contains(&col.name) {
-                common::Constraint::PrimaryKey
-            } else {
-                common::Constraint::None
+            let name = col.name.value.clone().to_string();
+            let dtype = get_attr(&col.data_type)?;
+            let (mut not_null, mut unique) = (false, false);
+            let mut default = None;
+            for option in &col.options {
+                match &option.option {
+                    ColumnOption::NotNull => not_null = true,
+                    ColumnOption::Unique { is_primary: false } => unique = true,
+                    ColumnOption::Default(expr) => {
+                        default = Some(get_default(expr, &name, &dtype)?)
+                    }
+                    _ => {}
+                }
+            }
+            let constraint = match (pks.contains(&col.name), unique, not_null) {
+                (true, _, _) => common::Constraint::PrimaryKey,
+                (false, true, true) => common::Constraint::UniqueNotNull,
+                (false, true, false) => common::Constraint::Unique,
+                (false, false, true) => common::Constraint::NotNull,
+                (false, false, false) => common::Constraint::None,
             };
             let attr = Attribute {
-                name: col.name.value.clone().to_string(),
-                dtype: get_attr(&col.data_type)?,
+                name,
+                dtype,
                 constraint,
+                default,
             };
             attributes.push(attr);
         }
         let schema = TableSchema::new(attributes);
+        let unique_keys = constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                TableConstraint::Unique {
+                    is_primary: false,
+                    columns,
+                    ..
+                } => Some(unique_key(&schema, columns)),
+                _ => None,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let txn_stamps = txn_stamps_option(options)?;
+        self.add_table(table_name, schema, unique_keys, txn_stamps, if_not_exists)
+    }
+
+    /// Creates a new table with the given schema. Fails if the schema exceeds the
+    /// column, identifier or row width limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Name of the new table.
+    /// * `schema` - Schema of the new table.
+    /// * `if_not_exists` - Succeed without changes if the table already exists.
+    pub fn create_table_with_schema(
+        &self,
+        table_name: &str,
+        schema: TableSchema,
+        if_not_exists: bool,
+    ) -> Result<QueryResult, FairyError> {
+        self.add_table(table_name, schema, Vec::new(), false, if_not_exists)
+    }
+
+    /// Creates a new table whose rows are stamped with the id of the transaction
+    /// that wrote them if `txn_stamps` is set. `unique_keys` are the keys of the
+    /// table constraints, on top of those the constraints of the columns declare.
+    fn add_table(
+        &self,
+        table_name: &str,
+        schema: TableSchema,
+        unique_keys: Vec<Vec<usize>>,
+        txn_stamps: bool,
+        if_not_exists: bool,
+    ) -> Result<QueryResult, FairyError> {
+        validate_identifier(table_name)?;
+        schema.validate()?;
         debug!("Creating table with schema: {:?}", schema);
 
-        let table_info = TableInfo::new(table_id, table_name.to_string(), schema.clone());
-        self.managers.sm.create_container(
+        // The catalog insert is the point where concurrent creates of the same
+        // name are decided, so it happens before the container is created.
+        let table_id = self.catalog.get_new_table_id(table_name, |c_id| {
+            self.managers.sm.container_id_in_use(c_id)
+        })?;
+        let mut table_info = TableInfo::new(table_id, table_name.to_string(), schema.clone());
+        table_info.txn_stamps = txn_stamps;
+        for key in unique_keys {
+            if !table_info.unique_keys.contains(&key) {
+                table_info.unique_keys.push(key);
+            }
+        }
+        if self.catalog.add_table(table_info.clone()).is_none() {
+            if if_not_exists {
+                return Ok(QueryResult::MessageOnly(format!(
+                    "Table {} already exists, skipping",
+                    table_name
+                )));
+            }
+            return Err(FairyError::AlreadyExists(
+                ObjectKind::Table,
+                table_name.to_string(),
+            ));
+        }
+        if let Err(e) = self.managers.sm.create_container(
             table_id,
             Some(table_name.to_string()),
             common::ids::StateType::BaseTable,
             None,
-        )?;
-        let res = self.catalog.add_table(table_info);
-        if res.is_none() {
-            // TODO: This check should be done in the sm.
-            return Err(FairyError::FairyError(format!(
-                "Table {} already exists",
-                table_name
-            )));
+        ) {
+            self.catalog.remove_table(table_id);
+            self.catalog.release_table_id(table_id);
+            return Err(e);
         }
         self.managers.stats.register_table(table_id, schema)?;
 
@@ -221,8 +396,180 @@ impl DatabaseState {
         Ok(qr)
     }
 
+    /// Drops a table. Waits for the statements using the table to finish, up to
+    /// the configured DDL lock timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - Name of the table to drop.
+    /// * `if_exists` - Skip instead of failing if the table does not exist.
+    pub fn drop_table(&self, table_name: &str, if_exists: bool) -> Result<QueryResult, FairyError> {
+        let table_id = match self.catalog.get_table_id_if_exists(table_name) {
+            Some(table_id) => table_id,
+            None if if_exists => {
+                return Ok(QueryResult::MessageOnly(format!(
+                    "Table {} does not exist, skipping",
+                    table_name
+                )))
+            }
+            None => {
+                return Err(FairyError::NotFound(
+                    ObjectKind::Table,
+                    table_name.to_string(),
+                ))
+            }
+        };
+        let _lock = self.lock_tables(&[table_id], TableLockMode::Exclusive)?;
+        if self.catalog.remove_table(table_id).is_none() {
+            // Dropped by a concurrent statement while waiting for the lock.
+            return Err(FairyError::NotFound(
+                ObjectKind::Table,
+                table_name.to_string(),
+            ));
+        }
+        self.cursors
+            .invalidate_table(table_id, &format!("table {} was dropped", table_name));
+        // A table created later may take the id, the cached results must not
+        // outlive this one.
+        self.query_registrar.purge_queries_on_table(table_id)?;
+        self.container_vec.write().unwrap().remove(&table_id);
+        self.managers.sm.remove_container(table_id)?;
+        // This also frees the containers of earlier drops that were still read.
+        self.managers.sm.release_removed_containers()?;
+        self.managers.stats.remove_table(table_id)?;
+        self.managers.im.remove_table(table_id);
+        self.compaction.forget_table(table_id);
+        // New tables may take the id once the container is released.
+        self.catalog.release_table_id(table_id);
+        Ok(QueryResult::MessageOnly(format!(
+            "Table {} dropped",
+            table_name
+        )))
+    }
+
+    /// Locks the given tables for the duration of a statement, waiting up to the
+    /// configured DDL lock timeout. A shared lock also checks that the tables were
+    /// not dropped while the statement waited for it.
+    pub fn lock_tables(
+        &self,
+        table_ids: &[ContainerId],
+        mode: TableLockMode,
+    ) -> Result<TableLockGuard<'_>, FairyError> {
+        let timeout = Duration::from_millis(self.managers.config.ddl_lock_timeout_ms);
+        let guard = self.catalog.lock_tables(table_ids, mode, timeout)?;
+        if mode == TableLockMode::Shared {
+            if let Some(c_id) = table_ids
+                .iter()
+                .find(|c_id| !self.catalog.is_valid_table(**c_id))
+            {
+                return Err(FairyError::NotFound(
+                    ObjectKind::Table,
+                    format!("with id {}", c_id),
+                ));
+            }
+        }
+        Ok(guard)
+    }
+
+    /// Sets the comment of a table or column. A `None` comment clears it.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_type` - Whether the comment is on a table or a column.
+    /// * `object_name` - `table` or `table.column`.
+    /// * `comment` - New comment.
+    /// * `if_exists` - Skip instead of failing if the table does not exist.
+    pub fn set_comment(
+        &self,
+        object_type: &CommentObject,
+        object_name: &ObjectName,
+        comment: Option<String>,
+        if_exists: bool,
+    ) -> Result<QueryResult, FairyError> {
+        let idents = &object_name.0;
+        let (table_name, column_name) = match (object_type, idents.len()) {
+            (CommentObject::Table, n) if n >= 1 => (&idents[n - 1].value, None),
+            (CommentObject::Column, n) if n >= 2 => {
+                (&idents[n - 2].value, Some(&idents[n - 1].value))
+            }
+            _ => {
+                return Err(FairyError::ValidationError(format!(
+                    "Invalid comment target {}",
+                    object_name
+                )))
+            }
+        };
+        let c_id = match self.catalog.get_table_id_if_exists(table_name) {
+            Some(c_id) => c_id,
+            None if if_exists => {
+                return Ok(QueryResult::MessageOnly(format!(
+                    "Table {} does not exist, skipping",
+                    table_name
+                )))
+            }
+            None => return Err(FairyError::NotFound(ObjectKind::Table, table_name.clone())),
+        };
+        match column_name {
+            Some(column_name) => self
+                .catalog
+                .set_column_comment(c_id, column_name, comment)?,
+            None => self.catalog.set_table_comment(c_id, comment)?,
+        }
+        Ok(QueryResult::MessageOnly(format!(
+            "Comment on {} updated",
+            object_name
+        )))
+    }
+
+    /// Sets options of a column. The only option is `encoding`, `plain` or
+    /// `dictionary`, which applies to the rows written from now on.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Name of the table.
+    /// * `column` - Name of the column.
+    /// * `options` - Names and values of the options.
+    pub fn set_column_options(
+        &self,
+        table: &ObjectName,
+        column: &Ident,
+        options: &[(Ident, Ident)],
+    ) -> Result<QueryResult, FairyError> {
+        let table_name = &table.0.last().ok_or(FairyError::InvalidOperation)?.value;
+        let c_id = self
+            .catalog
+            .get_table_id_if_exists(table_name)
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.clone()))?;
+        for (name, value) in options {
+            if !name.value.eq_ignore_ascii_case("encoding") {
+                return Err(FairyError::ValidationError(format!(
+                    "Unknown column option {}",
+                    name.value
+                )));
+            }
+            let encoding = value.value.parse::<ColumnEncoding>()?;
+            let columns = self
+                .catalog
+                .set_column_encoding(c_id, &column.value, encoding)?;
+            self.managers.sm.set_dictionary_columns(c_id, columns)?;
+        }
+        Ok(QueryResult::MessageOnly(format!(
+            "Table {} altered",
+            table_name
+        )))
+    }
+
+    /// Registers a write of transaction `tid` to `table` if the table stamps
+    /// its rows, which must happen before the first row is written. Snapshots
+    /// count the rows as committed once the returned write is committed.
+    pub fn begin_write(&self, table: &TableInfo, tid: TransactionId) -> Option<StampedWrite<'_>> {
+        table.txn_stamps.then(|| self.stamped_writes.begin(tid))
+    }
+
     pub fn reset(&self) -> Result<(), FairyError> {
         self.query_registrar.reset()?;
+        self.cursors.close_all();
+        self.prepared_statements.close_all();
         // get rid of persisted query registrar info and reset
         let mut query_registrar_info_path = PathBuf::new();
         query_registrar_info_path.push(&self.managers.config.db_path);
@@ -259,7 +606,9 @@ impl DatabaseState {
     ///
     /// * `query_name` - Query name to register.
     /// * `query_plan` - Query plan to register.
+    /// * `write_generation` - Write generation taken before the result was computed.
     // NOTE FOR ME: this isn't even actually used anywhere right now
+    #[allow(clippy::too_many_arguments)]
     pub fn register_query_with_result(
         &self,
         query_name: String,
@@ -268,7 +617,11 @@ impl DatabaseState {
         query_plan: Arc<PhysicalRelExpr>,
         query_result_path: String,
         query_tid: TransactionId,
+        write_generation: u64,
     ) -> Result<(), FairyError> {
+        let schema_stamps = SchemaStamp::from_plan(&query_plan, &self.catalog);
+        self.session_writes
+            .record_cached(&query_name, write_generation);
         self.query_registrar.register_query_with_result(
             query_name,
             sql,
@@ -276,6 +629,7 @@ impl DatabaseState {
             query_plan,
             query_result_path,
             query_tid,
+            schema_stamps,
         )
     }
 
@@ -309,10 +663,24 @@ impl DatabaseState {
     /// See if query has been executed in the past from physical plan.
     /// If it has, return the old result. Else, return None.
     ///
+    /// The cached plan is validated against the schema generations it was stamped
+    /// with. If a table it reads changed but every referenced column is intact, the
+    /// cache entry is purged and None is returned so that the query is re-planned.
+    /// If a referenced column is gone or was retyped, an error is returned.
+    ///
+    /// A result is never served to a session that wrote to one of the tables it
+    /// reads after the result was computed. A served result carries a
+    /// `CacheHit` notice.
+    ///
     /// # Arguments
     ///
     /// * `sql` - sql string inputted by the user.
-    pub fn query_result_from_sql(&self, sql: &String) -> Result<Option<QueryResult>, FairyError> {
+    /// * `client_id` - Session the result would be served to.
+    pub fn query_result_from_sql(
+        &self,
+        sql: &String,
+        client_id: u64,
+    ) -> Result<Option<QueryResult>, FairyError> {
         // get name from sql query
         let query_name = match self.query_registrar.get_query_name_from_sql(sql)? {
             Some(n) => n,
@@ -321,12 +689,41 @@ impl DatabaseState {
             }
         };
 
+        // check that the cached plan still matches the schema of the tables it reads
+        match self
+            .query_registrar
+            .check_schema_stamps(&query_name, &self.catalog)?
+        {
+            Some(StampCheck::Valid) => {}
+            Some(StampCheck::Stale) => {
+                self.query_registrar.purge_query_with_name(&query_name)?;
+                return Ok(None);
+            }
+            Some(StampCheck::Invalid(reason)) => {
+                self.query_registrar.purge_query_with_name(&query_name)?;
+                return Err(FairyError::ExecutionError(format!(
+                    "cached plan is invalid: {}",
+                    reason
+                )));
+            }
+            None => {
+                return Ok(None);
+            }
+        }
+
         // check that query result is still up-to-date and purge + return if not
         match self
             .query_registrar
             .get_touched_tables_from_name(&query_name)?
         {
             Some(v) => {
+                if self
+                    .session_writes
+                    .wrote_since_cached(client_id, &v, &query_name)
+                {
+                    self.query_registrar.purge_query_with_name(&query_name)?;
+                    return Ok(None);
+                }
                 // get last modified table's tid and compare to tid of stored query result
                 // NOTE only looking at the high valid for tables both are same
                 let latest_tid_val = v
@@ -367,7 +764,12 @@ impl DatabaseState {
         let mut contents = String::new();
         buf_reader.read_to_string(&mut contents)?;
         let qr: QueryResult = serde_json::from_str(&contents).unwrap();
-        Ok(Some(qr))
+        self.query_registrar.record_hit(&query_name);
+        let hit = self
+            .query_registrar
+            .get_plan_hash_from_name(&query_name)
+            .map(|hash| Notice::CacheHit { hash });
+        Ok(Some(qr.with_notices(hit)))
     }
 
     // If the client already has a TID, return it; otherwise, assign a new TID.
@@ -392,3 +794,48 @@ impl DatabaseState {
         map.get(&client_id).copied()
     }
 }
+
+/// Positions in `schema` of the columns of a `UNIQUE (..)` table constraint.
+fn unique_key(schema: &TableSchema, columns: &[Ident]) -> Result<Vec<usize>, FairyError> {
+    let mut key = Vec::with_capacity(columns.len());
+    for column in columns {
+        let name = column.value.as_str();
+        let i = schema
+            .get_field_index(name)
+            .ok_or_else(|| FairyError::UnknownColumn {
+                name: name.to_string(),
+                suggestion: closest_match(name, schema.attributes().map(Attribute::name)),
+            })?;
+        if !key.contains(&i) {
+            key.push(i);
+        }
+    }
+    Ok(key)
+}
+
+/// Reads the options of `CREATE TABLE .. WITH (..)`. The only option is
+/// `txn_stamps`, which stamps rows with the id of the transaction that wrote
+/// them so that the table can be exported as of a snapshot.
+fn txn_stamps_option(options: &[SqlOption]) -> Result<bool, FairyError> {
+    let mut txn_stamps = false;
+    for option in options {
+        if !option.name.value.eq_ignore_ascii_case("txn_stamps") {
+            return Err(FairyError::ValidationError(format!(
+                "Unknown table option {}",
+                option.name.value
+            )));
+        }
+        txn_stamps = match &option.value {
+            Value::Boolean(b) => Some(*b),
+            Value::SingleQuotedString(s) | Value::UnQuotedString(s) => SQLParser::parse_switch(s),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            FairyError::ValidationError(format!(
+                "Invalid value {} for table option txn_stamps, expected on or off",
+                option.value
+            ))
+        })?;
+    }
+    Ok(txn_stamps)
+}
@@ -1,10 +1,16 @@
 use crate::conductor::Conductor;
 use crate::database_state::DatabaseState;
 use crate::server_state::ServerState;
+use crate::system_tables;
+use crate::StorageTrait;
 
-use common::commands::{self, Command, CommandWithArgs, DBCommand, Response, SystemCommand};
+use common::commands::{
+    self, Command, CommandWithArgs, DBCommand, ErrorCode, Response, SystemCommand,
+};
 
 use common::error::c_err;
+use common::keywords::unquote_identifier;
+use common::metrics::{latch_metrics, storage_metrics, StatementKind};
 use common::QUERY_CACHES_DIR_NAME;
 use common::{ids::TransactionId, FairyError, QueryResult};
 use std::fs::{self, File};
@@ -12,6 +18,7 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub fn handle_command(
     shutdown_signal: Arc<AtomicBool>,
@@ -37,21 +44,50 @@ pub fn handle_command(
             system_command,
             &command.args,
         ),
-        Command::DB(database_command) => {
+        Command::DB(ref database_command) => {
             if let Ok(db) = server_state.get_connected_db(client_id) {
+                let _permit = if needs_admission(database_command, &command.args) {
+                    match db.admission.admit(client_id, command.to_string()) {
+                        Ok(permit) => Some(permit),
+                        Err(e) => {
+                            let code = e.error_code().unwrap_or(ErrorCode::QueryExecution);
+                            return (false, Response::QueryExecutionError(code, e.to_string()));
+                        }
+                    }
+                } else {
+                    None
+                };
                 let tid = db.get_or_assign_tid(client_id);
-                handle_database_command(db, database_command, &command.args, tid, client_id)
+                handle_database_command(db, database_command.clone(), &command.args, tid, client_id)
             } else {
                 error!("Client {} is not connected to a database", client_id);
                 (
                     false,
-                    Response::SystemErr("Not connected to a database".to_string()),
+                    Response::SystemErr(
+                        ErrorCode::System,
+                        "Not connected to a database".to_string(),
+                    ),
                 )
             }
         }
     }
 }
 
+/// Whether the command waits for its turn to run when the server is busy.
+/// Statements that only read or change settings, and the meta commands
+/// reporting on the database, run right away.
+fn needs_admission(command: &DBCommand, args: &[String]) -> bool {
+    match command {
+        DBCommand::ExecuteSQL => {
+            let sql = args.first().map(String::as_str).unwrap_or_default();
+            let keyword = sql.split_whitespace().next().unwrap_or_default();
+            !(keyword.eq_ignore_ascii_case("set") || keyword.eq_ignore_ascii_case("show"))
+        }
+        DBCommand::Import | DBCommand::RegisterQuery => true,
+        _ => false,
+    }
+}
+
 pub fn handle_system_command(
     shutdown_signal: Arc<AtomicBool>,
     quiet_mode: &mut bool,
@@ -71,7 +107,10 @@ pub fn handle_system_command(
 
     match response {
         Ok(response) => response,
-        Err(e) => (false, Response::SystemErr(e.to_string())),
+        Err(e) => {
+            let code = e.error_code().unwrap_or(ErrorCode::System);
+            (false, Response::SystemErr(code, e.to_string()))
+        }
     }
 }
 
@@ -85,9 +124,9 @@ fn run_system_command(
 ) -> Result<(bool, Response), FairyError> {
     match system_command {
         SystemCommand::ShowDatabases => {
-            let db_names = server_state.get_db_names();
-            let response = Response::SystemMsg(format!("Databases: {}", db_names.join(", ")));
-            Ok((false, response))
+            let result =
+                system_tables::show_databases(server_state.get_dbs(), server_state.managers.sm);
+            Ok((false, Response::QueryResult(result)))
         }
         SystemCommand::Reset => {
             server_state.reset()?;
@@ -102,7 +141,7 @@ fn run_system_command(
         }
         SystemCommand::QuietMode => {
             *quiet_mode = true;
-            Ok((false, Response::QuietOk))
+            Ok((false, Response::Ack))
         }
         SystemCommand::Test => {
             unimplemented!()
@@ -128,6 +167,28 @@ fn run_system_command(
             let response = Response::SystemMsg(commands::gen_help_string());
             Ok((false, response))
         }
+        SystemCommand::Progress => {
+            let result = system_tables::progress(server_state);
+            Ok((false, Response::QueryResult(result)))
+        }
+        SystemCommand::Cancel => {
+            let target = command_args.first().map(|s| s.trim()).unwrap_or_default();
+            let target: u64 = target
+                .parse()
+                .map_err(|_| c_err(&format!("Invalid client id: {:?}", target)))?;
+            let cancelled = server_state
+                .get_connected_db(target)
+                .is_ok_and(|db| db.running_queries.cancel(target));
+            if !cancelled {
+                return Err(c_err(&format!(
+                    "Client {} is not running a statement",
+                    target
+                )));
+            }
+            let response =
+                Response::SystemMsg(format!("Cancelled the statement of client {}", target));
+            Ok((false, response))
+        }
     }
 }
 
@@ -140,7 +201,18 @@ pub fn handle_database_command(
 ) -> (bool, Response) {
     match run_database_command(db, database_command, command_args, tid, client_id) {
         Ok(response) => response,
-        Err(e) => (false, Response::QueryExecutionError(e.to_string())),
+        Err(e) => {
+            let code = e.error_code().unwrap_or(ErrorCode::QueryExecution);
+            let response = match e.position() {
+                Some(position) => Response::QueryErrorAt {
+                    code,
+                    message: e.to_string(),
+                    position,
+                },
+                None => Response::QueryExecutionError(code, e.to_string()),
+            };
+            (false, response)
+        }
     }
 }
 
@@ -154,13 +226,23 @@ pub fn run_database_command(
     match database_command {
         DBCommand::ExecuteSQL => {
             let sql = command_args.first().expect("SQL not provided").to_string();
-            let mut conductor = Conductor::new_from_tid(db.managers, tid)?;
-            let qr = if let Some(query_result) = db.query_result_from_sql(&sql)? {
-                info!("Fetched registered query result");
-                query_result
-            } else {
-                conductor.run_sql_from_string(sql, db)?
-            };
+            let mut conductor = Conductor::new_from_tid(db.managers, tid, client_id)?;
+            let start = Instant::now();
+            let kind = StatementKind::from_sql(&sql);
+            let result =
+                db.query_result_from_sql(&sql, client_id)
+                    .and_then(|cached| match cached {
+                        Some(query_result) => {
+                            info!("Fetched registered query result");
+                            Ok(query_result)
+                        }
+                        None => conductor.run_sql_from_string(sql, db),
+                    });
+            db.query_metrics.record(kind, start.elapsed());
+            let settings = db.session_settings.get(client_id);
+            let qr = result?
+                .with_format(settings.display)
+                .with_max_rows(settings.max_result_rows);
 
             // HACK: until committing is properly implemented, we will manually increment the working tid so that query
             // execution is isolated into one txn (i.e. every user command is one transaction).
@@ -174,12 +256,65 @@ pub fn run_database_command(
             let result = QueryResult::MessageOnly(format!("Tables: {}", tables.join(", ")));
             Ok((false, Response::QueryResult(result)))
         }
+        DBCommand::DescribeTable => {
+            let table_name = command_args.first().map(|s| s.trim()).unwrap_or_default();
+            if table_name.is_empty() {
+                return Err(c_err("Table name not provided"));
+            }
+            let result =
+                system_tables::describe_table(&db.catalog, &unquote_identifier(table_name))?;
+            Ok((false, Response::QueryResult(result)))
+        }
+        DBCommand::StorageReport => {
+            let result = system_tables::storage_report(&db.catalog, db.managers.sm)?;
+            Ok((false, Response::QueryResult(result)))
+        }
+        DBCommand::ResetMetrics => {
+            db.query_metrics.reset();
+            db.admission.metrics.reset();
+            storage_metrics().reset();
+            latch_metrics().reset();
+            Ok((false, Response::SystemMsg("Metrics reset".to_string())))
+        }
+        DBCommand::Set => {
+            let (Some(name), Some(value)) = (command_args.first(), command_args.get(1)) else {
+                return Err(c_err("Setting name and value not provided"));
+            };
+            let (name, value) = (name.trim(), value.trim());
+            db.session_settings.set(client_id, name, value)?;
+            let response = Response::SystemMsg(format!("SET {} = {}", name, value));
+            Ok((false, response))
+        }
+        DBCommand::LastProfile => match db.session_profiles.last(client_id) {
+            Some(profile) => Ok((false, Response::SystemMsg(profile.to_string()))),
+            None if db.managers.config.profile_operators => {
+                Err(c_err("No query has been profiled in this session"))
+            }
+            None => Err(c_err(
+                "Operators are not profiled, start the server with --profile_operators",
+            )),
+        },
+        DBCommand::CacheEvict => {
+            let hash = command_args.first().map(|s| s.trim()).unwrap_or_default();
+            let hash = u64::from_str_radix(hash, 16)
+                .map_err(|_| c_err(&format!("Invalid plan hash {}", hash)))?;
+            match db.query_registrar.evict_plan(hash)? {
+                Some(query_name) => Ok((
+                    false,
+                    Response::SystemMsg(format!(
+                        "Evicted cached plan {:016x} of query {}",
+                        hash, query_name
+                    )),
+                )),
+                None => Err(c_err(&format!("No cached plan with hash {:016x}", hash))),
+            }
+        }
         DBCommand::Import => {
             let table_name = command_args.get(1).expect("table_name not provided");
             let file_path_str = command_args.first().expect("file_path not provided");
             let file_path = Path::new(file_path_str);
 
-            let mut conductor = Conductor::new_from_tid(db.managers, tid)?;
+            let mut conductor = Conductor::new_from_tid(db.managers, tid, client_id)?;
             let qr = conductor.import_csv(table_name, file_path, db)?;
 
             // HACK: until committing is properly implemented, we will manually increment the working tid so that insertion is isolated into one txn
@@ -189,6 +324,7 @@ pub fn run_database_command(
             if let QueryResult::Insert {
                 inserted,
                 table_name,
+                ..
             } = qr
             {
                 let response = Response::SystemMsg(format!(
@@ -206,9 +342,10 @@ pub fn run_database_command(
         DBCommand::RegisterQuery => {
             let query_name = command_args.first().expect("Query name not provided");
             let query = command_args.get(1).expect("Query not provided");
-            let mut conductor = Conductor::new_from_tid(db.managers, tid)?;
+            let mut conductor = Conductor::new_from_tid(db.managers, tid, client_id)?;
 
-            let maybe_cached = db.query_result_from_sql(query)?;
+            let maybe_cached = db.query_result_from_sql(query, client_id)?;
+            let write_generation;
             let qr = match maybe_cached {
                 Some(_) => {
                     return Ok((
@@ -219,7 +356,10 @@ pub fn run_database_command(
                         ),
                     ));
                 }
-                None => conductor.run_sql_from_string(query.clone(), db)?,
+                None => {
+                    write_generation = db.session_writes.current();
+                    conductor.run_sql_from_string(query.clone(), db)?
+                }
             };
 
             let lp = conductor.to_logical_plan(query, db)?;
@@ -245,6 +385,7 @@ pub fn run_database_command(
                         pp.into(),
                         file_path,
                         tid, // should be equal to conductor.active_txn.tid()? now
+                        write_generation,
                     )?;
                     let json = serde_json::to_string(&qr).unwrap();
                     f.write_all(json.as_bytes())?;
@@ -281,6 +422,7 @@ pub fn run_database_command(
         }
         DBCommand::Commit => {
             // db.managers.tm.commit_txn(tid)?; xtx not implemented
+            db.managers.sm.commit(tid)?;
             let new_tid = db.assign_new_tid(client_id);
             let response = Response::SystemMsg(format!(
                 "Committed old TID {tid:?}. New TID is {new_tid:?}."
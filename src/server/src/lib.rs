@@ -3,16 +3,29 @@ extern crate log;
 #[macro_use]
 extern crate serde;
 
+mod admission;
+mod compaction;
 mod conductor;
+mod cursors;
 mod daemon;
 mod database_state;
 mod handler;
+mod logging;
+mod prepared_statements;
+mod running_queries;
 mod server;
 mod server_state;
+mod session_profiles;
+mod session_settings;
+mod session_writes;
 mod sql_parser;
+pub mod sqllogictest;
+mod statement_retry;
+mod system_tables;
 mod worker;
 
 pub use common::traits::storage_trait::StorageTrait;
+pub use logging::init_logging;
 pub use queryexe;
 pub use queryexe::query::Executor;
 pub use queryexe::stats::reservoir_stat_manager::ReservoirStatManager as StatManager;
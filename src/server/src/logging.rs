@@ -0,0 +1,63 @@
+use common::physical::config::ServerConfig;
+use env_logger::Env;
+use std::fs::File;
+use std::io;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::{filter_fn, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+
+/// Starts the log of the process. Log lines go to `config.log_file`, or to
+/// stderr if it is empty, filtered by `config.log_level` or `RUST_LOG`.
+///
+/// Without a trace file the log is written by env_logger. With one, it is
+/// written by a tracing subscriber that also writes every span to the trace
+/// file as a JSON line when the span closes, followed by the log lines written
+/// inside spans, so the lines of concurrent statements can be told apart by the
+/// `query_id` of their `query` span.
+///
+/// Does nothing if the log was already started.
+pub fn init_logging(config: &ServerConfig) -> io::Result<()> {
+    let filter = format!("{},sqlparser::parser=warn", config.log_level);
+    let Some(trace_file) = &config.trace_file else {
+        let mut builder = env_logger::Builder::from_env(Env::default().default_filter_or(filter));
+        if !config.log_file.is_empty() {
+            let log_file = File::create(config.log_file.as_str())?;
+            builder.target(env_logger::Target::Pipe(Box::new(log_file)));
+        }
+        let _ = builder.try_init();
+        return Ok(());
+    };
+
+    let filter = std::env::var("RUST_LOG").unwrap_or(filter);
+    let targets: Targets = filter
+        .parse()
+        .unwrap_or_else(|_| Targets::new().with_default(LevelFilter::WARN));
+    let text = if config.log_file.is_empty() {
+        tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_filter(targets.clone())
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(File::create(config.log_file.as_str())?))
+            .with_filter(targets.clone())
+            .boxed()
+    };
+    // Spans are always recorded, log lines only as the log level says.
+    let json = tracing_subscriber::fmt::layer()
+        .json()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_span_list(true)
+        .with_writer(Mutex::new(File::create(trace_file)?))
+        .with_filter(filter_fn(move |meta| {
+            meta.is_span() || targets.would_enable(meta.target(), meta.level())
+        }));
+    let _ = tracing_subscriber::registry()
+        .with(text)
+        .with(json)
+        .try_init();
+    Ok(())
+}
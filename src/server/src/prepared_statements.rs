@@ -0,0 +1,115 @@
+use common::catalog::Catalog;
+use common::error::ObjectKind;
+use common::ids::ContainerId;
+use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
+use common::query::query_registrar::{SchemaStamp, StampCheck};
+use common::FairyError;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A query prepared by `PREPARE name AS query`. It is planned once, `EXECUTE`
+/// binds the values of its placeholders into the plan and runs it.
+#[derive(Clone)]
+pub struct PreparedStatement {
+    pub plan: PhysicalRelExpr,
+    /// Tables the query reads, stamped with their schema when it was prepared.
+    stamps: Vec<SchemaStamp>,
+}
+
+impl PreparedStatement {
+    pub fn new(plan: PhysicalRelExpr, catalog: &Catalog) -> Self {
+        let stamps = SchemaStamp::from_plan(&plan, catalog);
+        PreparedStatement { plan, stamps }
+    }
+
+    /// Tables the query reads.
+    pub fn tables(&self) -> Vec<ContainerId> {
+        self.stamps.iter().map(|stamp| stamp.c_id).collect()
+    }
+
+    /// Fails if a table the query reads was dropped or altered since it was
+    /// prepared, the plan may no longer fit it. The caller holds shared locks
+    /// on the tables.
+    pub fn check(&self, name: &str, catalog: &Catalog) -> Result<(), FairyError> {
+        for stamp in &self.stamps {
+            let reason = match stamp.check(catalog) {
+                StampCheck::Valid => continue,
+                StampCheck::Stale => {
+                    let table = catalog.get_table(stamp.c_id).map(|t| t.name.clone());
+                    format!("table {} was altered", table.unwrap_or_default())
+                }
+                StampCheck::Invalid(reason) => reason,
+            };
+            return Err(FairyError::ExecutionError(format!(
+                "Prepared statement {} is no longer valid: {}, prepare it again",
+                name, reason
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Prepared statements of every session, by session and statement name. A
+/// session sees only its own statements.
+#[derive(Default)]
+pub struct SessionPreparedStatements {
+    sessions: RwLock<HashMap<u64, HashMap<String, PreparedStatement>>>,
+}
+
+impl SessionPreparedStatements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a statement to `session`. Fails if the session already has a
+    /// statement with the same name.
+    pub fn prepare(
+        &self,
+        session: u64,
+        name: &str,
+        statement: PreparedStatement,
+    ) -> Result<(), FairyError> {
+        let mut sessions = self.sessions.write().unwrap();
+        let statements = sessions.entry(session).or_default();
+        if statements.contains_key(name) {
+            return Err(FairyError::AlreadyExists(
+                ObjectKind::PreparedStatement,
+                name.to_string(),
+            ));
+        }
+        statements.insert(name.to_string(), statement);
+        Ok(())
+    }
+
+    /// The statement `name` of `session`.
+    pub fn get(&self, session: u64, name: &str) -> Result<PreparedStatement, FairyError> {
+        self.sessions
+            .read()
+            .unwrap()
+            .get(&session)
+            .and_then(|statements| statements.get(name))
+            .cloned()
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::PreparedStatement, name.to_string()))
+    }
+
+    /// Forgets the statement `name` of `session`.
+    pub fn deallocate(&self, session: u64, name: &str) -> Result<(), FairyError> {
+        self.sessions
+            .write()
+            .unwrap()
+            .get_mut(&session)
+            .and_then(|statements| statements.remove(name))
+            .map(|_| ())
+            .ok_or_else(|| FairyError::NotFound(ObjectKind::PreparedStatement, name.to_string()))
+    }
+
+    /// Forgets the statements of a session that ended.
+    pub fn close_session(&self, session: u64) {
+        self.sessions.write().unwrap().remove(&session);
+    }
+
+    /// Forgets the statements of every session, e.g. when the database is reset.
+    pub fn close_all(&self) {
+        self.sessions.write().unwrap().clear();
+    }
+}
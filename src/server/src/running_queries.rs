@@ -0,0 +1,70 @@
+use queryexe::opiterator::Cancellation;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Cancellation tokens of the statements the sessions are running, so that
+/// `\cancel` from another connection can stop them.
+#[derive(Default)]
+pub struct RunningQueries {
+    sessions: RwLock<HashMap<u64, Cancellation>>,
+    /// Pages scanned so far by the statements that report them, by session.
+    pages: RwLock<HashMap<u64, usize>>,
+}
+
+impl RunningQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `cancellation` as the token of the statement `session` runs,
+    /// until the returned guard is dropped.
+    pub fn start(&self, session: u64, cancellation: &Cancellation) -> RunningQuery<'_> {
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(session, cancellation.clone());
+        RunningQuery {
+            queries: self,
+            session,
+        }
+    }
+
+    /// Cancels the statement `session` runs. Returns false if it runs none.
+    pub fn cancel(&self, session: u64) -> bool {
+        match self.sessions.read().unwrap().get(&session) {
+            Some(cancellation) => {
+                cancellation.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records that the statement `session` runs scanned `pages` pages so far.
+    pub fn report_pages(&self, session: u64, pages: usize) {
+        self.pages.write().unwrap().insert(session, pages);
+    }
+
+    /// Pages the statement `session` runs scanned so far, if it reports them.
+    pub fn pages(&self, session: u64) -> Option<usize> {
+        self.pages.read().unwrap().get(&session).copied()
+    }
+
+    /// Forgets the statement of a session that ended.
+    pub fn close_session(&self, session: u64) {
+        self.sessions.write().unwrap().remove(&session);
+        self.pages.write().unwrap().remove(&session);
+    }
+}
+
+/// Statement registered with `RunningQueries::start`, unregistered on drop.
+pub struct RunningQuery<'a> {
+    queries: &'a RunningQueries,
+    session: u64,
+}
+
+impl Drop for RunningQuery<'_> {
+    fn drop(&mut self) {
+        self.queries.close_session(self.session);
+    }
+}
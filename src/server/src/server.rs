@@ -1,11 +1,14 @@
+use crate::admission::Admission;
 use crate::conductor::Conductor;
+use crate::daemon::Daemon;
 use crate::database_state::DatabaseState;
 use crate::handler::handle_command;
+use crate::logging::init_logging;
 use crate::server_state::ServerState;
 use crate::StatManager;
 use common::catalog::CatalogRef;
-use common::commands::CommandWithArgs;
 use common::commands::Response;
+use common::commands::{CommandWithArgs, ErrorCode};
 use common::physical::config::ServerConfig;
 use common::physical::small_string::StringManager;
 use common::physical_expr::physical_rel_expr::PhysicalRelExpr;
@@ -13,16 +16,15 @@ use common::prelude::TransactionId;
 use common::traits::stat_manager_trait::StatManagerTrait;
 use common::traits::storage_trait::StorageTrait;
 use common::util::data_reader::{CsvReader, DataReader};
-use common::{FairyError, QueryResult};
-use env_logger::Env;
+use common::workload::WorkloadCapture;
+use common::{FairyError, QueryResult, Tuple};
 use index::IndexManager;
 use queryexe::opiterator::OpIterator;
 use queryexe::query::translate_and_validate::Query;
 use queryexe::Managers;
 use queryexe::{StorageManager, STORAGE_DIR};
 use std::fs;
-use std::io::Read;
-use std::io::Write;
+use std::io::{self, ErrorKind, Read, Write};
 use std::net::TcpListener;
 use std::net::{Shutdown, TcpStream};
 use std::path::{Path, PathBuf};
@@ -30,9 +32,13 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 use txn_manager::mock_tm::MockTransactionManager as TransactionManager;
 
 const MAX_STAT_BUDGET_MB: usize = 100;
+/// Bytes a request may take beyond a value of max_field_size, for the
+/// statement around it.
+const REQUEST_OVERHEAD: usize = 1024 * 1024;
 
 fn create_storage_manager(config: &'static ServerConfig) -> &'static StorageManager {
     let storage_manager = Box::new(StorageManager::new(config));
@@ -102,56 +108,70 @@ pub struct Server {
     shutdown_signal: Arc<AtomicBool>,
     config: &'static ServerConfig,
     server_state: &'static ServerState,
+    capture: Option<Arc<WorkloadCapture>>,
     thread_handles: Vec<thread::JoinHandle<()>>,
 }
 
 impl Server {
     pub fn new(config: &'static ServerConfig) -> Self {
-        // Configure log environment
-        let base_log_level = config.log_level.as_str();
-        let adjusted_log_level = format!("{},sqlparser::parser=warn", base_log_level);
-
-        if config.log_file.is_empty() {
-            let _ =
-                env_logger::Builder::from_env(Env::default().default_filter_or(adjusted_log_level))
-                    .try_init();
-        } else {
-            let log_file = fs::File::create(config.log_file.as_str()).unwrap();
-            let target = Box::new(log_file);
-            let _ =
-                env_logger::Builder::from_env(Env::default().default_filter_or(adjusted_log_level))
-                    .target(env_logger::Target::Pipe(target))
-                    .try_init();
-        }
+        init_logging(config).unwrap();
 
         let server_state = create_server_state(config);
+        let capture = config.capture_file.as_ref().map(|path| {
+            info!("Capturing executed statements to {:?}", path);
+            Arc::new(WorkloadCapture::create(path).unwrap())
+        });
 
         Server {
             cliend_id: AtomicU64::new(1), // 0 is reserved.
             shutdown_signal: Arc::new(AtomicBool::new(false)),
             config,
             server_state,
+            capture,
             thread_handles: vec![],
         }
     }
 
     pub fn run_server(&mut self) {
         trace!("Starting server... {:?}", self.config);
+        let listener = self.bind().unwrap();
+        self.serve(listener);
+    }
 
-        //Start listening to requests by spawning a handler per request.
+    /// Binds the address of the server. With port 0 the system picks a free
+    /// port, which the listener reports.
+    pub fn bind(&self) -> std::io::Result<TcpListener> {
         let mut bind_addr = self.config.host.clone();
         bind_addr.push(':');
         bind_addr.push_str(&self.config.port);
-        let listener = TcpListener::bind(bind_addr).unwrap();
+        TcpListener::bind(bind_addr)
+    }
+
+    /// Flag that stops the server once it is set and the server accepts one
+    /// more connection.
+    pub fn shutdown_signal(&self) -> Arc<AtomicBool> {
+        self.shutdown_signal.clone()
+    }
 
+    /// Serves the connections of `listener` until the server is shut down.
+    pub fn serve(&mut self, listener: TcpListener) {
         // Accept connections and process them on independent threads.
+        let port = listener
+            .local_addr()
+            .map_or_else(|_| self.config.port.clone(), |addr| addr.port().to_string());
         info!(
             "Server listening on with host {} on port {} with storage dir {:?}.",
-            self.config.host, self.config.port, self.config.db_path
+            self.config.host, port, self.config.db_path
         );
         println!(
             "Server listening on with host {} on port {} with storage dir {:?}.",
-            self.config.host, self.config.port, self.config.db_path
+            self.config.host, port, self.config.db_path
+        );
+
+        let daemon = Daemon::new(
+            self.server_state,
+            Duration::from_millis(self.config.maintenance_interval_ms),
+            self.shutdown_signal.clone(),
         );
 
         // (TODO) Here, we spawn a new thread for each client connection. This is not ideal.
@@ -174,8 +194,15 @@ impl Server {
                             .fetch_add(1, std::sync::atomic::Ordering::AcqRel);
                         let shutdown_signal = self.shutdown_signal.clone();
                         let server_state = self.server_state;
+                        let capture = self.capture.clone();
                         move || {
-                            handle_client_request(client_id, shutdown_signal, stream, server_state);
+                            handle_client_request(
+                                client_id,
+                                shutdown_signal,
+                                stream,
+                                server_state,
+                                capture,
+                            );
                         }
                     });
                     self.thread_handles.push(handle);
@@ -195,6 +222,7 @@ impl Server {
         for handler in self.thread_handles.drain(..) {
             handler.join().unwrap();
         }
+        daemon.join();
 
         info!("Server shutting down...");
     }
@@ -205,10 +233,27 @@ pub fn handle_client_request(
     shutdown_signal: Arc<AtomicBool>,
     mut stream: TcpStream,
     server_state: &'static ServerState,
+    capture: Option<Arc<WorkloadCapture>>,
 ) {
     let mut quiet_mode = false;
+    let max_field_size = server_state.managers.config.max_field_size;
+    let chunk_rows = server_state.managers.config.result_chunk_rows;
 
-    while let Some(request_command) = read_command(&mut stream) {
+    while let Some(request) = read_command(&mut stream, max_field_size) {
+        let request_command = match request {
+            Ok(command) => command,
+            Err(e) => {
+                let code = e.error_code().unwrap_or(ErrorCode::System);
+                let response = Response::QueryExecutionError(code, e.to_string());
+                if let Err(e) = send_response(&mut stream, response, quiet_mode, chunk_rows) {
+                    error!("Failed to send response: {:?}", e);
+                    break;
+                }
+                continue;
+            }
+        };
+        let statement = capture.as_ref().map(|_| request_command.to_string());
+        let started = Instant::now();
         let (should_break, response) = handle_command(
             shutdown_signal.clone(),
             &mut quiet_mode,
@@ -216,8 +261,13 @@ pub fn handle_client_request(
             server_state,
             client_id,
         );
+        if let (Some(capture), Some(statement)) = (&capture, statement) {
+            if let Err(e) = capture.record(client_id, &statement, started, &response) {
+                error!("Failed to capture statement: {:?}", e);
+            }
+        }
 
-        match send_response(&mut stream, response, quiet_mode) {
+        match send_response(&mut stream, response, quiet_mode, chunk_rows) {
             Ok(_) => {}
             Err(e) => {
                 error!("Failed to send response: {:?}", e);
@@ -232,6 +282,7 @@ pub fn handle_client_request(
     }
 
     info!("Closing connection with client {}", client_id);
+    server_state.close_connection(client_id);
     // finally close the stream
     let shutdown = stream.shutdown(Shutdown::Both);
     if let Err(e) = shutdown {
@@ -240,26 +291,68 @@ pub fn handle_client_request(
     info!("Connection closed with client {}", client_id);
 }
 
+/// Sends a response, its length as 8 big-endian bytes then the response in
+/// CBOR. A select result of more than `chunk_rows` rows is sent in chunks: a
+/// `ResultHeader` with the result without its rows, `ResultRows` frames of at
+/// most `chunk_rows` rows, then `ResultEnd`, so that the client can print the
+/// first rows before the rest arrive. 0 sends every result in one message.
 pub fn send_response(
     stream: &mut TcpStream,
     response: Response,
     quiet_mode: bool,
+    chunk_rows: usize,
 ) -> Result<(), FairyError> {
     let response = if !quiet_mode {
         response
     } else {
         info!("Quiet mode enabled");
         match response {
-            Response::SystemErr(_) | Response::QueryExecutionError(_) | Response::QuietErr => {
-                Response::QuietErr
-            }
+            Response::SystemErr(code, message)
+            | Response::QueryExecutionError(code, message)
+            | Response::QueryErrorAt { code, message, .. }
+            | Response::QuietErr { code, message } => Response::QuietErr { code, message },
             Response::Shutdown(from_client) => Response::Shutdown(from_client),
+            Response::Ack => Response::Ack,
             _ => Response::QuietOk,
         }
     };
+    match response {
+        Response::QueryResult(QueryResult::Select {
+            schema,
+            result,
+            paging_info,
+            footer,
+            format,
+            notices,
+        }) if chunk_rows > 0 && result.len() > chunk_rows => {
+            let header = QueryResult::Select {
+                schema,
+                result: Vec::new(),
+                paging_info,
+                footer,
+                format,
+                notices,
+            };
+            send_frame(stream, &Response::ResultHeader(header))?;
+            let mut rows = result.into_iter();
+            loop {
+                let chunk: Vec<Tuple> = rows.by_ref().take(chunk_rows).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                send_frame(stream, &Response::ResultRows(chunk))?;
+            }
+            send_frame(stream, &Response::ResultEnd)
+        }
+        response => send_frame(stream, &response),
+    }
+}
+
+/// Sends one message of a response, its length then the message in CBOR.
+fn send_frame(stream: &mut TcpStream, response: &Response) -> Result<(), FairyError> {
     // xtx maybe update here so that I can have a variable number of queries coming in that can be configured but not sure how to handle cancel and restart
-    let response_bytes = serde_cbor::to_vec(&response)
-        .map_err(|e| FairyError::SerializationError(e.to_string()))?;
+    let response_bytes =
+        serde_cbor::to_vec(response).map_err(|e| FairyError::SerializationError(e.to_string()))?;
     // TODO magic number - I  guess there is a potential issue of if the lenght is biggerthan u64 not sure if I need to deal with this
     let response_length = response_bytes.len() as u64;
     let response_length_bytes = response_length.to_be_bytes();
@@ -277,19 +370,42 @@ pub fn send_response(
     Ok(())
 }
 
-pub fn read_command(stream: &mut TcpStream) -> Option<CommandWithArgs> {
-    let mut buffer = [0; 1024];
-    match stream.read(&mut buffer) {
-        Ok(size) if size > 0 => serde_cbor::from_slice(&buffer[0..size]).ok(),
-        Ok(_) => {
+/// Reads a request: its length as 8 big-endian bytes, then the command in
+/// CBOR. A request too long to hold a value of `max_field_size` bytes and the
+/// statement around it is skipped and reported as too large. Returns None once
+/// the client closes the connection or sends a malformed request.
+pub fn read_command(
+    stream: &mut TcpStream,
+    max_field_size: usize,
+) -> Option<Result<CommandWithArgs, FairyError>> {
+    let mut length_bytes = [0u8; 8];
+    if let Err(e) = stream.read_exact(&mut length_bytes) {
+        if e.kind() == ErrorKind::UnexpectedEof {
             info!("Received empty request, closing connection");
-            None
+        } else {
+            error!("Error reading from stream: {:?}", e);
         }
-        Err(e) => {
+        return None;
+    }
+    let length = u64::from_be_bytes(length_bytes);
+    if length > max_field_size.saturating_add(REQUEST_OVERHEAD) as u64 {
+        // Skip the request so that the next one can be read.
+        if let Err(e) = io::copy(&mut Read::by_ref(stream).take(length), &mut io::sink()) {
             error!("Error reading from stream: {:?}", e);
-            None
+            return None;
         }
+        return Some(Err(FairyError::FieldTooLarge {
+            size: length as usize,
+            limit: max_field_size,
+            line: None,
+        }));
     }
+    let mut request = vec![0; length as usize];
+    if let Err(e) = stream.read_exact(&mut request) {
+        error!("Error reading from stream: {:?}", e);
+        return None;
+    }
+    serde_cbor::from_slice(&request).ok().map(Ok)
 }
 
 /// Available for testing without a running server
@@ -319,8 +435,9 @@ impl QueryEngine {
         config.db_path = storage_dir;
 
         let managers = create_managers(Box::leak(Box::new(config)));
+        let admission = Arc::new(Admission::from_config(managers.config));
         let database_state =
-            Box::new(DatabaseState::create_db(base_dir, "db_name", managers).unwrap());
+            Box::new(DatabaseState::create_db(base_dir, "db_name", managers, admission).unwrap());
         let database_state: &'static DatabaseState = Box::leak(database_state);
         let conductor = Conductor::new(managers).unwrap();
         QueryEngine {
@@ -351,7 +468,7 @@ impl QueryEngine {
         self.database_state.managers.sm
     }
 
-    pub fn get_table_id(&mut self, table_name: &str) -> u16 {
+    pub fn get_table_id(&mut self, table_name: &str) -> Result<u16, FairyError> {
         self.database_state.catalog.get_table_id(table_name)
     }
 
@@ -394,18 +511,20 @@ impl QueryEngine {
     where
         R: Read,
     {
-        let table_id = self.database_state.catalog.get_table_id(table_name);
-        let table_schema = self
-            .database_state
-            .catalog
-            .get_table_schema(table_id)
-            .unwrap();
-        let mut csv_reader = CsvReader::new(reader, &table_schema, delimiter, has_header)?;
-        self.conductor.executor.import_records_from_reader(
+        let table_id = self.database_state.catalog.get_table_id(table_name)?;
+        let table = self.database_state.catalog.get_table(table_id).unwrap();
+        let mut csv_reader = CsvReader::new(reader, &table.schema, delimiter, has_header)?;
+        let tid = TransactionId::new();
+        let write = self.database_state.begin_write(&table, tid);
+        let inserted = self.conductor.executor.import_records_from_reader(
             &mut csv_reader as &mut dyn DataReader,
-            &table_id,
-            TransactionId::new(),
-        )
+            &table,
+            tid,
+        )?;
+        if let Some(write) = write {
+            write.commit();
+        }
+        Ok(inserted)
     }
 }
 
@@ -433,13 +552,24 @@ impl Drop for QueryEngine {
 mod test {
     // Test query engine
     use super::*;
+    use common::catalog::MAX_COMMENT_LENGTH;
+    use common::commands::ErrorCode;
+    use common::error::ObjectKind;
+    use common::physical::config::Durability;
+    use common::physical_expr::plan_schema::field_matches_dtype;
+    use common::table_lock::TableLockMode;
+    use common::{Attribute, DataType, Field, TableSchema, Tuple};
+    use common::{MAX_COLUMNS, MAX_IDENTIFIER_LEN, MAX_TUPLE_SIZE};
+    use std::time::Duration;
 
     mod query_engine {
         use super::*;
+        use optimizer::deadline::{PlanningDeadline, DEFAULT_PLANNING_TIMEOUT};
+        use queryexe::stats::stats_view::StatsView;
 
         #[test]
         fn test_run_sql() {
-            let base_dir = tempfile::tempdir().unwrap().into_path();
+            let base_dir = tempfile::tempdir().unwrap().keep();
             let mut query_engine = QueryEngine::new(&base_dir);
             // create table with primary key
             let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10));";
@@ -461,7 +591,7 @@ mod test {
 
         #[test]
         fn test_load_csv_and_run_sql() {
-            let base_dir = tempfile::tempdir().unwrap().into_path();
+            let base_dir = tempfile::tempdir().unwrap().keep();
             let mut query_engine = QueryEngine::new(&base_dir);
             // create table with primary key
             let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10));";
@@ -481,5 +611,3150 @@ mod test {
             };
             assert_eq!(t.len(), 5);
         }
+
+        #[test]
+        fn test_comment_on_table_and_column() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10));";
+            query_engine.run_sql(sql).unwrap();
+            query_engine
+                .run_sql("COMMENT ON TABLE foo IS 'all the foos'")
+                .unwrap();
+            query_engine
+                .run_sql("COMMENT ON COLUMN foo.name IS 'name of the foo'")
+                .unwrap();
+
+            let c_id = query_engine.get_table_id("foo").unwrap();
+            let table = query_engine.get_catalog().get_table(c_id).unwrap();
+            assert_eq!(table.comment.as_deref(), Some("all the foos"));
+            assert_eq!(
+                table.get_column_comment("name").map(String::as_str),
+                Some("name of the foo")
+            );
+
+            let result = query_engine.run_sql("SELECT * FROM system.tables").unwrap();
+            let tuples = result.get_tuples().unwrap();
+            assert_eq!(tuples.len(), 1);
+            assert_eq!(
+                tuples[0].get_field(2),
+                Some(&Field::String("all the foos".to_string()))
+            );
+
+            // Clearing with IS NULL
+            query_engine
+                .run_sql("COMMENT ON COLUMN foo.name IS NULL")
+                .unwrap();
+            let result = query_engine
+                .run_sql("SELECT * FROM system.columns")
+                .unwrap();
+            let tuples = result.get_tuples().unwrap();
+            assert_eq!(tuples.len(), 2);
+            assert!(tuples.iter().all(|t| t.get_field(4) == Some(&Field::Null)));
+
+            // Unknown columns and overly long comments are rejected
+            assert!(query_engine
+                .run_sql("COMMENT ON COLUMN foo.bar IS 'x'")
+                .is_err());
+            let long = "x".repeat(MAX_COMMENT_LENGTH + 1);
+            assert!(query_engine
+                .run_sql(&format!("COMMENT ON TABLE foo IS '{}'", long))
+                .is_err());
+            assert!(query_engine
+                .run_sql("COMMENT IF EXISTS ON TABLE missing IS 'x'")
+                .is_ok());
+        }
+        #[test]
+        fn test_create_table_name_collision() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10));";
+            query_engine.run_sql(sql).unwrap();
+            let err = query_engine.run_sql(sql).unwrap_err();
+            assert_eq!(
+                err,
+                FairyError::AlreadyExists(ObjectKind::Table, "foo".to_string())
+            );
+            assert_eq!(err.error_code(), Some(ErrorCode::AlreadyExists));
+
+            let sql = "CREATE TABLE IF NOT EXISTS foo (id INT PRIMARY KEY);";
+            assert!(query_engine.run_sql(sql).is_ok());
+            // The original schema is kept.
+            let c_id = query_engine.get_table_id("foo").unwrap();
+            let table = query_engine.get_catalog().get_table(c_id).unwrap();
+            assert_eq!(table.schema.size(), 2);
+
+            let err = query_engine
+                .run_sql("COMMENT ON TABLE missing IS 'x'")
+                .unwrap_err();
+            assert_eq!(err.error_code(), Some(ErrorCode::NotFound));
+            let err = query_engine
+                .run_sql("COMMENT ON COLUMN foo.missing IS 'x'")
+                .unwrap_err();
+            assert_eq!(
+                err,
+                FairyError::NotFound(ObjectKind::Column, "foo.missing".to_string())
+            );
+        }
+
+        #[test]
+        fn test_show_and_describe_statements() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            let result = query_engine.run_sql("SHOW TABLES").unwrap();
+            assert!(result.get_tuples().unwrap().is_empty());
+
+            for sql in [
+                "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10))",
+                "CREATE TABLE bar (a INT PRIMARY KEY)",
+                "COMMENT ON COLUMN foo.name IS 'name of the foo'",
+            ] {
+                query_engine.run_sql(sql).unwrap();
+            }
+            let result = query_engine.run_sql("SHOW TABLES").unwrap();
+            let names: Vec<_> = result
+                .get_tuples()
+                .unwrap()
+                .iter()
+                .map(|t| t.get_field(0).unwrap().to_string())
+                .collect();
+            assert_eq!(names, vec!["foo", "bar"]);
+
+            let expected = vec![
+                Tuple::new(vec![
+                    Field::String("id".to_string()),
+                    Field::String("bigint".to_string()),
+                    Field::String("NO".to_string()),
+                    Field::String("primary key".to_string()),
+                    Field::Null,
+                ]),
+                Tuple::new(vec![
+                    Field::String("name".to_string()),
+                    Field::String("string".to_string()),
+                    Field::String("YES".to_string()),
+                    Field::String(String::new()),
+                    Field::String("name of the foo".to_string()),
+                ]),
+            ];
+            for sql in ["SHOW COLUMNS FROM foo", "DESCRIBE foo"] {
+                let result = query_engine.run_sql(sql).unwrap();
+                let schema = result.get_schema().unwrap();
+                let names: Vec<_> = schema.attributes().map(|a| a.name.as_str()).collect();
+                assert_eq!(
+                    names,
+                    vec![
+                        "column_name",
+                        "data_type",
+                        "is_nullable",
+                        "constraint",
+                        "comment"
+                    ]
+                );
+                assert_eq!(result.get_tuples().unwrap(), &expected);
+            }
+
+            for sql in ["SHOW COLUMNS FROM missing", "DESCRIBE missing"] {
+                let err = query_engine.run_sql(sql).unwrap_err();
+                assert_eq!(
+                    err,
+                    FairyError::NotFound(ObjectKind::Table, "missing".to_string())
+                );
+            }
+            assert!(query_engine.run_sql("SHOW TABLES LIKE 'f%'").is_err());
+        }
+
+        #[test]
+        fn test_concurrent_create_table() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let query_engine = QueryEngine::new(&base_dir);
+            let db_state = query_engine.database_state;
+            let num_threads = 8;
+            let barrier = Arc::new(std::sync::Barrier::new(num_threads));
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        let mut conductor = Conductor::new(db_state.managers).unwrap();
+                        barrier.wait();
+                        conductor.run_sql_from_string(
+                            "CREATE TABLE foo (id INT PRIMARY KEY)".to_string(),
+                            db_state,
+                        )
+                    })
+                })
+                .collect();
+            let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+            assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+            for err in results.into_iter().filter_map(Result::err) {
+                assert_eq!(
+                    err,
+                    FairyError::AlreadyExists(ObjectKind::Table, "foo".to_string())
+                );
+            }
+            assert_eq!(db_state.catalog.get_tables().len(), 1);
+        }
+
+        #[test]
+        fn test_planning_budget_on_wide_join() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            let num_tables = 12;
+            for i in 0..num_tables {
+                let sql = format!("CREATE TABLE t{} (id INT PRIMARY KEY, v INT);", i);
+                query_engine.run_sql(&sql).unwrap();
+                let sql = format!("INSERT INTO t{} VALUES (1, {}), (2, {});", i, i, i + 1);
+                query_engine.run_sql(&sql).unwrap();
+            }
+            let tables: Vec<String> = (0..num_tables).map(|i| format!("t{}", i)).collect();
+            let predicates: Vec<String> = (1..num_tables)
+                .map(|i| format!("t{}.id = t{}.id", i - 1, i))
+                .collect();
+            let sql = format!(
+                "SELECT * FROM {} WHERE {};",
+                tables.join(", "),
+                predicates.join(" AND ")
+            );
+
+            let budget = DEFAULT_PLANNING_TIMEOUT;
+            let start = std::time::Instant::now();
+            let plan = query_engine.to_physical_plan(&sql).unwrap();
+            assert!(start.elapsed() < budget * 5);
+            assert!(!query_engine.conductor.optimizer.last_planning_truncated());
+
+            let result = query_engine.run_physical_plan(plan).unwrap();
+            assert_eq!(result.get_tuples().unwrap().len(), 2);
+        }
+
+        #[test]
+        fn test_planning_deadline_skips_join_reordering() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            for (table, n) in [("small", 2), ("large", 20)] {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY, k INT);", table);
+                query_engine.run_sql(&sql).unwrap();
+                let values: Vec<String> = (0..n).map(|i| format!("({}, {})", i, i % 2)).collect();
+                let sql = format!("INSERT INTO {} VALUES {};", table, values.join(", "));
+                query_engine.run_sql(&sql).unwrap();
+            }
+            let sql = "SELECT small.a, large.a FROM large JOIN small ON small.k = large.k;";
+            let optimize = |query_engine: &mut QueryEngine, deadline: &PlanningDeadline| {
+                let logical_plan = query_engine.to_logical_plan(sql).unwrap();
+                let stats = StatsView::new(query_engine.database_state.managers.stats);
+                let optimizer = &query_engine.conductor.optimizer;
+                let plan = optimizer.optimize_with_stats(&logical_plan, None, &stats, deadline);
+                (plan.pretty_string(), optimizer.last_planning_truncated())
+            };
+            let small_first =
+                |plan: &str| plan.find("small").unwrap() < plan.find("large").unwrap();
+
+            let (plan, truncated) =
+                optimize(&mut query_engine, &PlanningDeadline::new(Duration::ZERO));
+            assert!(small_first(&plan), "{}", plan);
+            assert!(!truncated);
+
+            // Once the budget is spent the joins keep the translator's order.
+            let deadline = PlanningDeadline::new(Duration::from_nanos(1));
+            std::thread::sleep(Duration::from_millis(1));
+            let (plan, truncated) = optimize(&mut query_engine, &deadline);
+            assert!(!small_first(&plan), "{}", plan);
+            assert!(truncated);
+        }
+
+        #[test]
+        fn test_derived_schema_matches_results() {
+            let base_dir = tempfile::tempdir().unwrap().keep();
+            let mut query_engine = QueryEngine::new(&base_dir);
+            for sql in [
+                "CREATE TABLE r (id INT PRIMARY KEY, v INT, name VARCHAR(10));",
+                "CREATE TABLE s (id INT PRIMARY KEY, r_id INT);",
+                "INSERT INTO r VALUES (1, 10, 'a'), (2, 20, 'b'), (3, 30, 'c');",
+                "INSERT INTO s VALUES (1, 1), (2, 1), (3, 2);",
+            ] {
+                query_engine.run_sql(sql).unwrap();
+            }
+            let corpus = [
+                "SELECT * FROM r;",
+                "SELECT name, id FROM r WHERE v > 15;",
+                "SELECT id, v + 1, v * 2 FROM r;",
+                "SELECT r.name, s.id FROM r, s WHERE r.id = s.r_id;",
+                "SELECT r.name, s.id FROM r LEFT JOIN s ON r.id = s.r_id;",
+                "SELECT r_id, COUNT(*), SUM(id), AVG(id) FROM s GROUP BY r_id;",
+                "SELECT MAX(v), MIN(name) FROM r;",
+            ];
+            for sql in corpus {
+                let plan = query_engine.to_physical_plan(sql).unwrap();
+                let schema = plan.derive_schema(query_engine.get_catalog()).unwrap();
+                let result = query_engine.run_physical_plan(plan).unwrap();
+                let tuples = result.get_tuples().unwrap();
+                assert!(!tuples.is_empty(), "{}", sql);
+                for tuple in tuples {
+                    assert_eq!(tuple.len(), schema.len(), "{}", sql);
+                    for (field, col) in tuple.field_vals().zip(&schema) {
+                        assert!(field_matches_dtype(field, &col.dtype), "{}", sql);
+                        assert!(col.nullable || *field != Field::Null, "{}", sql);
+                    }
+                }
+            }
+        }
+    }
+
+    mod server_state {
+        use super::*;
+        use crate::compaction::{AutoCompaction, TickOutcome};
+        use common::commands::parse_command;
+        use common::ids::TransactionId;
+        use common::query::query_result::Notice;
+        use common::testutil::{ColumnDist, TableGen};
+        use std::collections::{HashMap, HashSet};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+        use std::time::Instant;
+
+        fn run_command(server_state: &'static ServerState, cmd: &str) -> Response {
+            run_command_as(server_state, 0, cmd)
+        }
+
+        fn run_command_as(
+            server_state: &'static ServerState,
+            client_id: u64,
+            cmd: &str,
+        ) -> Response {
+            let mut quiet_mode = false;
+            let (_, response) = handle_command(
+                Arc::new(AtomicBool::new(false)),
+                &mut quiet_mode,
+                parse_command(cmd.to_string()).unwrap(),
+                server_state,
+                client_id,
+            );
+            response
+        }
+
+        /// A generator of rows for `table` of the database of client 0.
+        fn table_gen(server_state: &'static ServerState, table: &str, seed: u64) -> TableGen {
+            let db = server_state.get_connected_db(0).unwrap();
+            let table = db
+                .catalog
+                .get_table(db.catalog.get_table_id(table).unwrap())
+                .unwrap();
+            TableGen::new(table.schema, seed)
+        }
+
+        /// Imports `rows` into `table` through a CSV file.
+        fn import_rows(server_state: &'static ServerState, table: &str, rows: &[Tuple]) {
+            let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+            let csv: Vec<String> = rows.iter().map(|t| format!("{}\n", t.to_csv())).collect();
+            std::fs::write(&path, csv.concat()).unwrap();
+            let cmd = format!("\\i {} {}", path.display(), table);
+            assert!(run_command(server_state, &cmd).is_ok());
+        }
+
+        /// Number of rows of the join of `left` and `right` on the columns
+        /// `left_key` and `right_key`.
+        fn join_size(left: &[Tuple], left_key: usize, right: &[Tuple], right_key: usize) -> usize {
+            let mut keys: HashMap<&Field, usize> = HashMap::new();
+            for tuple in left {
+                *keys.entry(&tuple.field_vals[left_key]).or_default() += 1;
+            }
+            right
+                .iter()
+                .filter_map(|tuple| keys.get(&tuple.field_vals[right_key]))
+                .sum()
+        }
+
+        #[test]
+        fn test_comments_survive_restart() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "COMMENT ON COLUMN foo.name IS 'first line\nsecond line'";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "COMMENT ON TABLE foo IS 'table of foos'";
+            assert!(run_command(server_state, sql).is_ok());
+            server_state.shutdown().unwrap();
+
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\c db");
+            let result = match run_command(server_state, "\\d foo") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let tuples = result.get_tuples().unwrap();
+            assert_eq!(
+                tuples[1].get_field(3),
+                Some(&Field::String("first line\nsecond line".to_string()))
+            );
+
+            // The comment column is aligned and the newline is escaped.
+            let rendered = result.to_string();
+            let lines: Vec<&str> = rendered.trim().lines().collect();
+            assert_eq!(lines.len(), 4);
+            let comment_offset = lines[0].find("comment").unwrap();
+            assert_eq!(
+                lines[3].find("first line\\nsecond line"),
+                Some(comment_offset)
+            );
+
+            let result = match run_command(server_state, "SELECT * FROM system.tables") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            assert_eq!(
+                result.get_tuples().unwrap()[0].get_field(2),
+                Some(&Field::String("table of foos".to_string()))
+            );
+        }
+
+        #[test]
+        fn test_reserved_keyword_identifiers() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            match run_command(
+                server_state,
+                "CREATE TABLE order (select BIGINT PRIMARY KEY)",
+            ) {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::QueryExecution);
+                    assert_eq!(
+                        msg,
+                        "Validation Error: Table name order is a reserved keyword, \
+                         quote it as \"order\" to use it"
+                    );
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            let sql = "CREATE TABLE \"order\" (\"select\" BIGINT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO \"order\" VALUES (1), (2)";
+            assert!(run_command(server_state, sql).is_ok());
+            match run_command(
+                server_state,
+                "SELECT \"select\" FROM \"order\" WHERE \"select\" = 2",
+            ) {
+                Response::QueryResult(result) => {
+                    assert_eq!(result.get_tuples().unwrap().len(), 1)
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            // The table name is quoted again wherever it is printed as SQL.
+            match run_command(server_state, "EXPLAIN SELECT * FROM \"order\"") {
+                Response::QueryResult(QueryResult::MessageOnly(plan)) => {
+                    assert!(plan.contains("scan(\"order\", "), "{}", plan)
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+            match run_command(server_state, "\\d \"order\"") {
+                Response::QueryResult(result) => assert_eq!(
+                    result.get_tuples().unwrap()[0].get_field(0),
+                    Some(&Field::String("select".to_string()))
+                ),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_corrupt_record_modes() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE damaged (a INT PRIMARY KEY, b VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO damaged VALUES (1, 'one'), (2, 'two')";
+            assert!(run_command(server_state, sql).is_ok());
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id("damaged").unwrap();
+            // A record with the wrong field types, written directly to a page.
+            let bad = Tuple::new(vec![Field::String("x".to_string()), Field::BigInt(3)]);
+            let vid = db
+                .managers
+                .sm
+                .insert_value(c_id, bad.to_bytes(), TransactionId::new());
+
+            match run_command(server_state, "SELECT * FROM damaged") {
+                Response::QueryExecutionError(_, msg) => assert_eq!(
+                    msg,
+                    format!(
+                        "Execution Error: Corrupt record in container {} page {} slot {}: \
+                         field a is String(\"x\"), column has type bigint",
+                        c_id,
+                        vid.page_id.unwrap(),
+                        vid.slot_id.unwrap()
+                    )
+                ),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // Tolerant mode is per session.
+            let sql = "SET corrupt_records = tolerant";
+            assert!(run_command(server_state, sql).is_ok());
+            match run_command(server_state, "SELECT * FROM damaged") {
+                Response::QueryResult(result) => {
+                    assert_eq!(result.get_tuples().unwrap().len(), 2);
+                    assert_eq!(result.get_footer(), Some("Skipped 1 corrupt records"));
+                    assert!(result.to_string().ends_with("Skipped 1 corrupt records\n"));
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            run_command_as(server_state, 1, "\\c db");
+            assert!(!run_command_as(server_state, 1, "SELECT * FROM damaged").is_ok());
+            match run_command(server_state, "SELECT * FROM system.metrics") {
+                Response::QueryResult(result) => {
+                    let row = result
+                        .get_tuples()
+                        .unwrap()
+                        .iter()
+                        .find(|t| {
+                            t.get_field(0) == Some(&Field::String("scan.corrupt_records".into()))
+                        })
+                        .cloned()
+                        .unwrap();
+                    assert!(matches!(row.get_field(1), Some(Field::BigInt(n)) if *n >= 1));
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            let sql = "SET corrupt_records = 'lenient'";
+            assert!(!run_command(server_state, sql).is_ok());
+            assert!(!run_command(server_state, "SET missing_setting = 1").is_ok());
+        }
+
+        #[test]
+        fn test_durability_setting() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                durability: Durability::Strict,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            assert!(run_command(server_state, "CREATE TABLE t (a INT PRIMARY KEY)").is_ok());
+            assert!(run_command(server_state, "INSERT INTO t VALUES (1), (2)").is_ok());
+            match run_command(server_state, "SHOW durability") {
+                Response::QueryResult(result) => {
+                    assert_eq!(
+                        result.get_tuples().unwrap()[0].get_field(0),
+                        Some(&Field::String("strict".into()))
+                    );
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            match run_command(server_state, "SELECT * FROM system.settings") {
+                Response::QueryResult(result) => {
+                    let rows: Vec<_> = result
+                        .get_tuples()
+                        .unwrap()
+                        .iter()
+                        .map(|t| t.field_vals.clone())
+                        .collect();
+                    assert!(rows.contains(&vec![
+                        Field::String("durability".into()),
+                        Field::String("strict".into())
+                    ]));
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            match run_command(server_state, "SHOW corrupt_records") {
+                Response::QueryResult(result) => {
+                    assert_eq!(
+                        result.get_tuples().unwrap()[0].get_field(0),
+                        Some(&Field::String("strict".into()))
+                    );
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            assert!(!run_command(server_state, "SHOW missing_setting").is_ok());
+        }
+
+        #[test]
+        fn test_display_settings() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                null_text: "-".to_string(),
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, d DATE, s VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO t VALUES (1, '2024-03-05', NULL)";
+            assert!(run_command(server_state, sql).is_ok());
+            let select =
+                |client_id| match run_command_as(server_state, client_id, "SELECT d, s FROM t") {
+                    Response::QueryResult(result) => result.to_string(),
+                    r => panic!("Expected query result, got {:?}", r),
+                };
+            assert_eq!(
+                select(0),
+                "\nd             s\n---------------\n2024-03-05    -\n"
+            );
+
+            assert!(run_command(server_state, "SET date_format = '%d/%m/%Y'").is_ok());
+            assert!(run_command(server_state, "SET null_text = '(null)'").is_ok());
+            assert_eq!(
+                select(0),
+                "\nd             s     \n--------------------\n05/03/2024    (null)\n"
+            );
+            match run_command(server_state, "SHOW date_format") {
+                Response::QueryResult(result) => assert_eq!(
+                    result.get_tuples().unwrap()[0].get_field(0),
+                    Some(&Field::String("%d/%m/%Y".into()))
+                ),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            // Other sessions keep the server defaults.
+            run_command_as(server_state, 1, "\\c db");
+            assert_eq!(
+                select(1),
+                "\nd             s\n---------------\n2024-03-05    -\n"
+            );
+            assert!(!run_command(server_state, "SET date_format = '%Q'").is_ok());
+            assert!(!run_command(server_state, "SET decimal_separator = '::'").is_ok());
+        }
+
+        #[test]
+        fn test_planning_timeout_setting() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let show = |client_id| match run_command_as(
+                server_state,
+                client_id,
+                "SHOW planning_timeout",
+            ) {
+                Response::QueryResult(result) => {
+                    result.get_tuples().unwrap()[0].get_field(0).cloned()
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            assert_eq!(show(0), Some(Field::String("100".into())));
+            assert!(run_command(server_state, "SET planning_timeout = 0").is_ok());
+            assert_eq!(show(0), Some(Field::String("0".into())));
+            assert!(run_command(server_state, "SET planning_timeout = 250").is_ok());
+            assert_eq!(show(0), Some(Field::String("250".into())));
+            for value in ["-1", "1.5", "'fast'"] {
+                let sql = format!("SET planning_timeout = {}", value);
+                assert!(!run_command(server_state, &sql).is_ok(), "{}", sql);
+            }
+            assert_eq!(show(0), Some(Field::String("250".into())));
+            run_command_as(server_state, 1, "\\c db");
+            assert_eq!(show(1), Some(Field::String("100".into())));
+        }
+
+        #[test]
+        fn test_query_timeout() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                query_timeout_ms: 1,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            setup_long_scan(server_state);
+            let cross_join = "SELECT * FROM t, u";
+            match run_command(server_state, cross_join) {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::QueryTimeout);
+                    assert!(msg.starts_with("Query timed out after "), "{}", msg);
+                    assert!(msg.ends_with(" ms while running scan"), "{}", msg);
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // The session lifts the timeout of the server, other sessions keep it.
+            match run_command(server_state, "\\set timeout 0") {
+                Response::SystemMsg(msg) => assert_eq!(msg, "SET timeout = 0"),
+                r => panic!("Expected system message, got {:?}", r),
+            }
+            match run_command(server_state, cross_join) {
+                Response::QueryResult(result) => {
+                    assert_eq!(result.get_tuples().unwrap().len(), 200 * 200)
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            run_command_as(server_state, 1, "\\c db");
+            match run_command_as(server_state, 1, "SHOW timeout") {
+                Response::QueryResult(result) => assert_eq!(
+                    result.get_tuples().unwrap()[0].get_field(0).cloned(),
+                    Some(Field::String("1".into()))
+                ),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            let response = run_command_as(server_state, 1, cross_join);
+            assert!(
+                matches!(
+                    response,
+                    Response::QueryExecutionError(ErrorCode::QueryTimeout, _)
+                ),
+                "{:?}",
+                response
+            );
+            for cmd in ["\\set timeout fast", "\\set timeout", "SET timeout = -1"] {
+                assert!(!run_command(server_state, cmd).is_ok(), "{}", cmd);
+            }
+        }
+
+        #[test]
+        fn test_error_codes() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            assert!(run_command(server_state, "\\r db").is_ok());
+            match run_command(server_state, "\\r db") {
+                Response::SystemErr(code, _) => assert_eq!(code, ErrorCode::AlreadyExists),
+                r => panic!("Expected system error, got {:?}", r),
+            }
+            match run_command(server_state, "\\c missing") {
+                Response::SystemErr(code, _) => assert_eq!(code, ErrorCode::NotFound),
+                r => panic!("Expected system error, got {:?}", r),
+            }
+            run_command(server_state, "\\c db");
+            match run_command(server_state, "\\d missing") {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::NotFound);
+                    assert_eq!(msg, "Table missing does not exist");
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_error_positions() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE orders (order_id INT PRIMARY KEY, amount INT)";
+            assert!(run_command(server_state, sql).is_ok());
+
+            match run_command(server_state, "SELECT order_id FORM orders") {
+                Response::QueryErrorAt { code, position, .. } => {
+                    assert_eq!(code, ErrorCode::Syntax);
+                    assert_eq!((position.line, position.column), (1, 22));
+                }
+                r => panic!("Expected positioned error, got {:?}", r),
+            }
+            match run_command(
+                server_state,
+                "SELECT amount\nFROM orders\nWHERE ordr_id = 1",
+            ) {
+                Response::QueryErrorAt {
+                    code,
+                    message,
+                    position,
+                } => {
+                    assert_eq!(code, ErrorCode::NotFound);
+                    assert_eq!(
+                        message,
+                        "Column ordr_id does not exist, did you mean order_id?"
+                    );
+                    assert_eq!((position.line, position.column), (3, 7));
+                    assert_eq!(position.offset, 32);
+                }
+                r => panic!("Expected positioned error, got {:?}", r),
+            }
+            match run_command(server_state, "SELECT nothing_like_it FROM orders") {
+                Response::QueryErrorAt { message, .. } => {
+                    assert_eq!(message, "Column nothing_like_it does not exist")
+                }
+                r => panic!("Expected positioned error, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_show_statements_over_protocol() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE foo (id INT PRIMARY KEY, name VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            match run_command(server_state, "SHOW TABLES") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 1),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            match run_command(server_state, "DESCRIBE foo") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 2),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            match run_command(server_state, "SHOW COLUMNS FROM missing") {
+                Response::QueryExecutionError(code, _) => assert_eq!(code, ErrorCode::NotFound),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_read_your_writes_with_result_cache() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            run_command_as(server_state, 1, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+
+            let sql = "SELECT * FROM t";
+            let register = format!("\\register q {}", sql);
+            for i in 0..50 {
+                // Session 0 caches the result under a newer transaction id than the
+                // one session 1 is about to insert with.
+                run_command(server_state, sql);
+                assert!(run_command(server_state, &register).is_ok());
+
+                let insert = format!("INSERT INTO t VALUES ({})", i);
+                assert!(run_command_as(server_state, 1, &insert).is_ok());
+                match run_command_as(server_state, 1, sql) {
+                    Response::QueryResult(result) => {
+                        assert_eq!(result.get_tuples().unwrap().len(), i + 1)
+                    }
+                    r => panic!("Expected query result, got {:?}", r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_plan_cache_introspection() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            for sql in [
+                "CREATE TABLE foo (a INT PRIMARY KEY, b INT)",
+                "INSERT INTO foo VALUES (1, 10), (2, 20)",
+                "\\register q1 SELECT a FROM foo",
+                "\\register q2 SELECT * FROM foo",
+                "SELECT a FROM foo",
+                "SELECT a FROM foo",
+                "SELECT * FROM foo",
+            ] {
+                assert!(run_command(server_state, sql).is_ok(), "{}", sql);
+            }
+            let select = |sql: &str| match run_command(server_state, sql) {
+                Response::QueryResult(result) => result.get_tuples().unwrap().clone(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+
+            let statements = select("SELECT * FROM system.prepared_statements");
+            assert_eq!(statements.len(), 2);
+            assert_eq!(
+                statements[0].field_vals[..4],
+                [
+                    Field::String("q1".to_string()),
+                    Field::String("SELECT a FROM foo".to_string()),
+                    Field::String(String::new()),
+                    Field::BigInt(2),
+                ]
+            );
+            assert_eq!(statements[1].get_field(3), Some(&Field::BigInt(1)));
+
+            let cache = select("SELECT * FROM system.plan_cache");
+            assert_eq!(cache.len(), 2);
+            let q1 = &cache[0];
+            assert_eq!(q1.get_field(1), Some(&Field::String("foo".to_string())));
+            assert_eq!(q1.get_field(2), Some(&Field::String("foo@0".to_string())));
+            assert_eq!(q1.get_field(3), Some(&Field::BigInt(2)));
+            assert!(matches!(q1.get_field(4), Some(Field::BigInt(n)) if *n > 0));
+            let hash = q1.get_field(0).unwrap().to_string();
+
+            let response = run_command(server_state, &format!("\\cache_evict {}", hash));
+            assert!(response.is_ok(), "{:?}", response);
+            match run_command(server_state, &format!("\\cache_evict {}", hash)) {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("No cached plan"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // The evicted query is planned and run again and no longer counts hits.
+            assert_eq!(select("SELECT a FROM foo").len(), 2);
+            select("SELECT * FROM foo");
+            let cache = select("SELECT * FROM system.plan_cache");
+            assert_eq!(cache.len(), 1);
+            assert_ne!(cache[0].get_field(0).unwrap().to_string(), hash);
+            assert_eq!(cache[0].get_field(3), Some(&Field::BigInt(2)));
+            assert_eq!(select("SELECT * FROM system.prepared_statements").len(), 1);
+        }
+
+        #[test]
+        fn test_cached_plan_schema_change() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            for sql in [
+                "CREATE TABLE foo (a INT PRIMARY KEY, b INT)",
+                "INSERT INTO foo VALUES (1, 10), (2, 20)",
+            ] {
+                assert!(run_command(server_state, sql).is_ok());
+            }
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id_if_exists("foo").unwrap();
+            let sql = "SELECT a FROM foo";
+            let register = format!("\\register q {}", sql);
+
+            // Adding a column leaves the referenced column intact, so the query is re-planned.
+            assert!(run_command(server_state, &register).is_ok());
+            assert!(db
+                .query_result_from_sql(&sql.to_string(), 0)
+                .unwrap()
+                .is_some());
+            let mut schema = db.catalog.get_table_schema(c_id).unwrap();
+            schema
+                .attributes
+                .push(Attribute::new("c".to_string(), DataType::Int));
+            assert_eq!(db.catalog.update_table_schema(c_id, schema).unwrap(), 1);
+            match run_command(server_state, sql) {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 2),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            assert_eq!(
+                db.get_registered_query_names().unwrap(),
+                "No registered queries"
+            );
+
+            // Dropping the referenced column invalidates the cached plan.
+            assert!(run_command(server_state, &register).is_ok());
+            assert!(db
+                .query_result_from_sql(&sql.to_string(), 0)
+                .unwrap()
+                .is_some());
+            let mut schema = db.catalog.get_table_schema(c_id).unwrap();
+            schema.attributes.remove(0);
+            db.catalog.update_table_schema(c_id, schema).unwrap();
+            match run_command(server_state, sql) {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("cached plan is invalid"), "{}", msg);
+                    assert!(msg.contains("foo.a"), "{}", msg);
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_query_latency_metrics() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let values: Vec<String> = (0..200).map(|i| format!("({})", i)).collect();
+            for table in ["small", "big_a", "big_b"] {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY)", table);
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+            let inserts = [
+                "INSERT INTO small VALUES (1)".to_string(),
+                format!("INSERT INTO big_a VALUES {}", values.join(", ")),
+                format!("INSERT INTO big_b VALUES {}", values.join(", ")),
+            ];
+            for sql in &inserts {
+                assert!(run_command(server_state, sql).is_ok());
+            }
+            assert!(run_command(server_state, "\\reset_metrics").is_ok());
+
+            // Every 20th query is a large nested loop join, so 5% of the queries are slow.
+            let mut slowest_fast = Duration::ZERO;
+            let mut fastest_slow = Duration::MAX;
+            for i in 0..100 {
+                let slow = i % 20 == 19;
+                let sql = if slow {
+                    "SELECT * FROM big_a JOIN big_b ON big_a.a < big_b.a"
+                } else {
+                    "SELECT a FROM small"
+                };
+                let start = Instant::now();
+                assert!(run_command(server_state, sql).is_ok());
+                let elapsed = start.elapsed();
+                if slow {
+                    fastest_slow = fastest_slow.min(elapsed);
+                } else {
+                    slowest_fast = slowest_fast.max(elapsed);
+                }
+            }
+            assert!(fastest_slow > slowest_fast * 4);
+
+            let result = match run_command(server_state, "SELECT * FROM system.metrics") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let row = |name: &str| -> Vec<i64> {
+                let tuple = result
+                    .get_tuples()
+                    .unwrap()
+                    .iter()
+                    .find(|t| t.get_field(0) == Some(&Field::String(name.to_string())))
+                    .unwrap()
+                    .clone();
+                (1..6)
+                    .map(|i| match tuple.get_field(i) {
+                        Some(Field::BigInt(n)) => *n,
+                        f => panic!("Expected bigint, got {:?}", f),
+                    })
+                    .collect()
+            };
+            let (count, p50, p90, p99, max) = match row("query.select")[..] {
+                [count, p50, p90, p99, max] => (count, p50, p90, p99, max),
+                _ => unreachable!(),
+            };
+            assert_eq!(count, 100);
+            assert_eq!(row("query.insert")[0], 0);
+            assert_eq!(row("query.ddl")[0], 0);
+            assert_eq!(row("query.retries")[0], 0);
+            // p50 and p90 only see fast queries, p99 lands among the slow ones.
+            assert!(p50 <= p90);
+            assert!(p90 as u128 <= slowest_fast.as_micros() * 9 / 8);
+            assert!(p99 as u128 >= fastest_slow.as_micros() / 2);
+            assert!(p99 > p90 * 2);
+            assert!(p99 <= max);
+
+            assert!(run_command(server_state, "\\reset_metrics").is_ok());
+            match run_command(server_state, "SELECT * FROM system.metrics") {
+                Response::QueryResult(result) => {
+                    let select = &result.get_tuples().unwrap()[0];
+                    assert_eq!(select.get_field(1), Some(&Field::BigInt(0)));
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_create_table_row_width_limit() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let db = server_state.get_connected_db(0).unwrap();
+
+            // 15 CHAR(255) columns and a last CHAR column sized so that the minimum
+            // row is exactly as large as a page can hold.
+            let schema = |last: u8| {
+                let mut attrs: Vec<Attribute> = (0..15)
+                    .map(|i| Attribute::new(format!("c{}", i), DataType::Char(255)))
+                    .collect();
+                attrs.push(Attribute::new("last".to_string(), DataType::Char(last)));
+                TableSchema::new(attrs)
+            };
+            let last = (0..255)
+                .find(|&n| schema(n).min_tuple_size() == MAX_TUPLE_SIZE)
+                .unwrap();
+
+            match db.create_table_with_schema("wide", schema(last + 1), false) {
+                Err(FairyError::ValidationError(msg)) => assert_eq!(
+                    msg,
+                    format!(
+                        "minimum row size of {} bytes exceeds the page limit of {} bytes",
+                        MAX_TUPLE_SIZE + 1,
+                        MAX_TUPLE_SIZE
+                    )
+                ),
+                r => panic!("Expected validation error, got {:?}", r),
+            }
+            assert!(db.catalog.get_table_id_if_exists("wide").is_none());
+
+            // Just under the limit the table is created and full-width rows fit.
+            assert!(db
+                .create_table_with_schema("wide", schema(last), false)
+                .is_ok());
+            let c_id = db.catalog.get_table_id_if_exists("wide").unwrap();
+            let mut fields = vec![Field::Char(255, "x".repeat(255)); 15];
+            fields.push(Field::Char(last, "x".repeat(last as usize)));
+            let mut tuple = Tuple::new(fields);
+            tuple.tid = u64::MAX;
+            let bytes = tuple.to_bytes();
+            assert_eq!(bytes.len(), MAX_TUPLE_SIZE);
+            db.managers
+                .sm
+                .insert_value(c_id, bytes, TransactionId::new());
+            match run_command(server_state, "SELECT * FROM wide") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 1),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            let columns: Vec<String> = (1..=MAX_COLUMNS).map(|i| format!("c{} INT", i)).collect();
+            let sql = format!(
+                "CREATE TABLE many (c0 INT PRIMARY KEY, {})",
+                columns.join(", ")
+            );
+            match run_command(server_state, &sql) {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("101 columns, the limit is 100"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            let sql = format!(
+                "CREATE TABLE {} (a INT PRIMARY KEY)",
+                "t".repeat(MAX_IDENTIFIER_LEN + 1)
+            );
+            match run_command(server_state, &sql) {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("the limit is 63"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+        }
+
+        /// Creates tables `t` and `u` with 200 rows each in database `db` and
+        /// returns a join over them that takes a while to run.
+        fn setup_long_scan(server_state: &'static ServerState) -> &'static str {
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let values: Vec<String> = (0..200).map(|i| format!("({})", i)).collect();
+            for table in ["t", "u"] {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY)", table);
+                assert!(run_command(server_state, &sql).is_ok());
+                let sql = format!("INSERT INTO {} VALUES {}", table, values.join(", "));
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+            "SELECT * FROM t JOIN u ON t.a < u.a"
+        }
+
+        #[test]
+        fn test_drop_table_waits_for_scan() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            let long_scan = setup_long_scan(server_state);
+            let db = server_state.get_connected_db(0).unwrap();
+            let t_id = db.catalog.get_table_id_if_exists("t").unwrap();
+
+            run_command_as(server_state, 1, "\\c db");
+            let scan = thread::spawn(move || run_command_as(server_state, 1, long_scan));
+            while !db.catalog.is_table_locked(t_id) {
+                thread::yield_now();
+            }
+            // The scan holds its lock until it has produced its result, so the drop
+            // can only return after the scan is done.
+            let response = run_command(server_state, "DROP TABLE t");
+            assert!(response.is_ok(), "{:?}", response);
+            match scan.join().unwrap() {
+                Response::QueryResult(result) => {
+                    assert_eq!(result.get_tuples().unwrap().len(), 200 * 199 / 2)
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            match run_command(server_state, "SELECT * FROM t") {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::NotFound);
+                    assert_eq!(msg, "Table t does not exist");
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            match run_command(server_state, "DROP TABLE t") {
+                Response::QueryExecutionError(code, _) => assert_eq!(code, ErrorCode::NotFound),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert!(run_command(server_state, "DROP TABLE IF EXISTS t").is_ok());
+            // The name can be reused after the drop.
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert!(run_command(server_state, "INSERT INTO t VALUES (1)").is_ok());
+            match run_command(server_state, "SELECT * FROM t") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 1),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_drop_table_purges_cached_results() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let create = "CREATE TABLE t (a INT PRIMARY KEY)";
+            let sql = "SELECT * FROM t";
+            for cmd in [
+                create,
+                "INSERT INTO t VALUES (1), (2)",
+                "\\register q SELECT * FROM t",
+            ] {
+                assert!(run_command(server_state, cmd).is_ok(), "{}", cmd);
+            }
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id_if_exists("t").unwrap();
+            let result_path = db
+                .query_registrar
+                .get_query_result_path_from_name(&"q".to_string())
+                .unwrap()
+                .unwrap();
+            assert!(Path::new(&result_path).exists());
+
+            assert!(run_command(server_state, "DROP TABLE t").is_ok());
+            assert_eq!(
+                db.get_registered_query_names().unwrap(),
+                "No registered queries"
+            );
+            assert!(!Path::new(&result_path).exists());
+            // The new table takes the id of the dropped one, not its rows.
+            assert!(run_command(server_state, create).is_ok());
+            assert_eq!(db.catalog.get_table_id_if_exists("t"), Some(c_id));
+            match run_command(server_state, sql) {
+                Response::QueryResult(result) => assert!(result.get_tuples().unwrap().is_empty()),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_container_ids_recycled() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE kept (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert!(run_command(server_state, "INSERT INTO kept VALUES (1), (2)").is_ok());
+            let db = server_state.get_connected_db(0).unwrap();
+            let kept = db.catalog.get_table_id_if_exists("kept").unwrap();
+
+            let mut ids = HashSet::new();
+            for i in 0..2000 {
+                let sql = format!("CREATE TABLE temp_{} (a INT PRIMARY KEY, b INT)", i);
+                assert!(run_command(server_state, &sql).is_ok());
+                let c_id = db
+                    .catalog
+                    .get_table_id_if_exists(&format!("temp_{}", i))
+                    .unwrap();
+                assert_ne!(c_id, kept);
+                ids.insert(c_id);
+                let sql = format!("INSERT INTO temp_{} VALUES ({}, 1), ({}, 2)", i, i, i + 1);
+                assert!(run_command(server_state, &sql).is_ok());
+                // The rows of the tables that had the id before are gone.
+                match run_command(server_state, &format!("SELECT a FROM temp_{}", i)) {
+                    Response::QueryResult(result) => {
+                        assert_eq!(result.get_tuples().unwrap().len(), 2, "table temp_{}", i)
+                    }
+                    r => panic!("Expected query result, got {:?}", r),
+                }
+                let sql = format!("DROP TABLE temp_{}", i);
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+            assert_eq!(ids.len(), 1, "ids were not recycled: {:?}", ids);
+            match run_command(server_state, "SELECT a FROM kept") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 2),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            server_state.shutdown().unwrap();
+
+            // The free ids survive a restart.
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE after_restart (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id_if_exists("after_restart");
+            assert_eq!(c_id.as_ref(), ids.iter().next());
+            match run_command(server_state, "SELECT a FROM after_restart") {
+                Response::QueryResult(result) => assert!(result.get_tuples().unwrap().is_empty()),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_ddl_lock_timeouts() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                ddl_lock_timeout_ms: 20,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            let long_scan = setup_long_scan(server_state);
+            let db = server_state.get_connected_db(0).unwrap();
+            let t_id = db.catalog.get_table_id_if_exists("t").unwrap();
+
+            // A query on a table held by DDL gives up after the timeout.
+            let lock = db.lock_tables(&[t_id], TableLockMode::Exclusive).unwrap();
+            match run_command(server_state, "SELECT * FROM t") {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("table t is locked by DDL"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            drop(lock);
+
+            // DDL waiting for a long scan gives up after the timeout.
+            run_command_as(server_state, 1, "\\c db");
+            let scan = thread::spawn(move || run_command_as(server_state, 1, long_scan));
+            while !db.catalog.is_table_locked(t_id) {
+                thread::yield_now();
+            }
+            match run_command(server_state, "DROP TABLE t") {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(
+                        msg.contains("waiting for statements using table t"),
+                        "{}",
+                        msg
+                    )
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert!(scan.join().unwrap().is_ok());
+            assert!(run_command(server_state, "SELECT * FROM t").is_ok());
+        }
+
+        #[test]
+        fn test_cancel() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            let long_scan = setup_long_scan(server_state);
+            let db = server_state.get_connected_db(0).unwrap();
+            let t_id = db.catalog.get_table_id_if_exists("t").unwrap();
+            match run_command(server_state, "\\cancel 1") {
+                Response::SystemErr(_, msg) => {
+                    assert_eq!(msg, "Fairy Error: Client 1 is not running a statement")
+                }
+                r => panic!("Expected system error, got {:?}", r),
+            }
+
+            // The scan waits for the lock, so it is cancelled before it reads a row.
+            let lock = db.lock_tables(&[t_id], TableLockMode::Exclusive).unwrap();
+            run_command_as(server_state, 1, "\\c db");
+            let scan = thread::spawn(move || run_command_as(server_state, 1, long_scan));
+            while !matches!(
+                run_command(server_state, "\\cancel 1"),
+                Response::SystemMsg(_)
+            ) {
+                thread::yield_now();
+            }
+            drop(lock);
+            match scan.join().unwrap() {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::QueryCancelled);
+                    assert_eq!(msg, "cancelled");
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // The connection runs the next statement as usual.
+            assert!(!matches!(
+                run_command(server_state, "\\cancel 1"),
+                Response::SystemMsg(_)
+            ));
+            match run_command_as(server_state, 1, long_scan) {
+                Response::QueryResult(result) => {
+                    assert_eq!(result.get_tuples().unwrap().len(), 200 * 199 / 2)
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_analyze_progress_and_cancel() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT, c VARCHAR(40))";
+            assert!(run_command(server_state, sql).is_ok());
+            for chunk in 0..4 {
+                let values: Vec<String> = (chunk * 500..(chunk + 1) * 500)
+                    .map(|i| format!("({}, {}, 'a string of some length {}')", i, i % 7, i))
+                    .collect();
+                let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+            let db = server_state.get_connected_db(0).unwrap();
+            let t_id = db.catalog.get_table_id_if_exists("t").unwrap();
+            let pages = db.managers.sm.storage_report(t_id).unwrap().num_pages;
+            assert!(pages > 1);
+
+            // The query timeout of the session does not apply to ANALYZE.
+            assert!(run_command(server_state, "SET timeout = 1").is_ok());
+            match run_command(server_state, "ANALYZE t (b, c)") {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                    let prefix = format!("ANALYZE: {} pages scanned in ", pages);
+                    assert!(msg.starts_with(&prefix), "{}", msg);
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+
+            // The scan waits for the lock, \progress shows it has read no page
+            // yet, and it is cancelled before it reads one.
+            let lock = db.lock_tables(&[t_id], TableLockMode::Exclusive).unwrap();
+            run_command_as(server_state, 1, "\\c db");
+            let analyze = thread::spawn(move || run_command_as(server_state, 1, "ANALYZE t"));
+            let pages_scanned = || match run_command(server_state, "\\progress") {
+                Response::QueryResult(result) => result
+                    .get_tuples()
+                    .unwrap()
+                    .iter()
+                    .find(|t| t.get_field(0) == Some(&Field::BigInt(1)))
+                    .and_then(|t| t.get_field(6).cloned()),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            while pages_scanned() != Some(Field::BigInt(0)) {
+                thread::yield_now();
+            }
+            assert!(matches!(
+                run_command(server_state, "\\cancel 1"),
+                Response::SystemMsg(_)
+            ));
+            drop(lock);
+            match analyze.join().unwrap() {
+                Response::QueryExecutionError(code, _) => {
+                    assert_eq!(code, ErrorCode::QueryCancelled)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert_eq!(db.running_queries.pages(1), None);
+        }
+
+        #[test]
+        fn test_cursor_fetch_in_batches() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                max_cursors_per_session: 2,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE big (a INT PRIMARY KEY, b VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = (0..1000).map(|i| format!("({}, 'b{}')", i, i)).collect();
+            let sql = format!("INSERT INTO big VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+            let db = server_state.get_connected_db(0).unwrap();
+
+            let query = "SELECT * FROM big WHERE a >= 100";
+            let expected = match run_command(server_state, query) {
+                Response::QueryResult(result) => result.get_tuples().unwrap().clone(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            assert_eq!(expected.len(), 900);
+
+            let sql = format!("DECLARE c CURSOR FOR {}", query);
+            assert!(run_command(server_state, &sql).is_ok());
+            assert_eq!(db.cursors.active_count(0), 1);
+            let mut fetched = Vec::new();
+            for (batch, (len, has_next)) in [(400, true), (400, true), (100, false)]
+                .into_iter()
+                .enumerate()
+            {
+                match run_command(server_state, "FETCH 400 FROM c") {
+                    Response::QueryResult(QueryResult::Select {
+                        result,
+                        paging_info: Some(paging_info),
+                        ..
+                    }) => {
+                        assert_eq!(result.len(), len);
+                        assert_eq!(paging_info.current_page, batch as u32 + 1);
+                        assert_eq!(paging_info.has_next_page, has_next);
+                        fetched.extend(result);
+                    }
+                    r => panic!("Expected select result, got {:?}", r),
+                }
+            }
+            assert_eq!(fetched, expected);
+            // The operators are released as soon as the cursor is exhausted.
+            assert_eq!(db.cursors.active_count(0), 0);
+            match run_command(server_state, "FETCH NEXT FROM c") {
+                Response::QueryResult(result) => assert!(result.get_tuples().unwrap().is_empty()),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            // Cursors are scoped to their session and bounded per session.
+            run_command_as(server_state, 1, "\\c db");
+            match run_command_as(server_state, 1, "FETCH 1 FROM c") {
+                Response::QueryExecutionError(code, _) => assert_eq!(code, ErrorCode::NotFound),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            let sql = "DECLARE d CURSOR FOR SELECT * FROM big";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "DECLARE e CURSOR FOR SELECT * FROM big";
+            match run_command(server_state, sql) {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("at most 2 open cursors"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert!(run_command(server_state, "CLOSE c").is_ok());
+            assert_eq!(db.cursors.open_count(0), 1);
+            match run_command(server_state, "CLOSE c") {
+                Response::QueryExecutionError(code, _) => assert_eq!(code, ErrorCode::NotFound),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // DDL on a table a cursor reads invalidates it.
+            assert!(run_command(server_state, "FETCH 10 FROM d").is_ok());
+            assert_eq!(db.cursors.active_count(0), 1);
+            assert!(run_command(server_state, "DROP TABLE big").is_ok());
+            assert_eq!(db.cursors.active_count(0), 0);
+            match run_command(server_state, "FETCH 10 FROM d") {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(msg.contains("table big was dropped"), "{}", msg)
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+
+            // Ending the session closes its cursors.
+            let sql = "CREATE TABLE small (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "DECLARE s CURSOR FOR SELECT * FROM small";
+            assert!(run_command_as(server_state, 1, sql).is_ok());
+            assert_eq!(db.cursors.open_count(1), 1);
+            run_command_as(server_state, 1, "\\close");
+            assert_eq!(db.cursors.open_count(1), 0);
+        }
+
+        #[test]
+        fn test_explain_analyze_adaptive_join() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let mut rows = Vec::new();
+            for (seed, (table, n)) in [("outer_t", 200), ("inner_t", 20)].into_iter().enumerate() {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY, k INT)", table);
+                assert!(run_command(server_state, &sql).is_ok());
+                let gen = table_gen(server_state, table, seed as u64).column(
+                    "k",
+                    ColumnDist::Zipf {
+                        min: 0,
+                        cardinality: 10,
+                        skew: 1.0,
+                    },
+                );
+                rows.push(gen.generate(n));
+                import_rows(server_state, table, &rows[seed]);
+            }
+            let query = "SELECT * FROM outer_t JOIN inner_t ON outer_t.k = inner_t.k";
+            let sorted_result = || match run_command(server_state, query) {
+                Response::QueryResult(result) => {
+                    let mut tuples = result.get_tuples().unwrap().clone();
+                    tuples.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+                    tuples
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let expected = sorted_result();
+            assert_eq!(expected.len(), join_size(&rows[0], 1, &rows[1], 1));
+
+            // Wrong statistics claim the outer side of the join, which the hash
+            // table is built over, holds one row.
+            let db = server_state.get_connected_db(0).unwrap();
+            let outer_id = db.catalog.get_table_id("outer_t").unwrap();
+            db.managers.stats.import_record_count(outer_id, 1).unwrap();
+            assert_eq!(sorted_result(), expected);
+            assert_eq!(
+                run_command(server_state, query).notices(),
+                [Notice::AdaptiveJoinSwap]
+            );
+
+            match run_command(server_state, &format!("EXPLAIN ANALYZE {}", query)) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                    assert!(msg.contains("(adapted: swapped build side)"), "{}", msg);
+                    let rows = format!("\nRows: {}\n", expected.len());
+                    assert!(msg.contains(&rows), "{}", msg);
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+            match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                    assert!(msg.contains("Hash inner_join"), "{}", msg);
+                    assert!(!msg.contains("adapted"), "{}", msg);
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_last_profile() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                profile_operators: true,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            assert!(matches!(
+                run_command(server_state, "\\last_profile"),
+                Response::QueryExecutionError(_, msg) if msg.contains("No query has been profiled")
+            ));
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO t VALUES (1, 10), (2, 20), (3, 30), (4, 40)";
+            assert!(run_command(server_state, sql).is_ok());
+
+            assert!(run_command(server_state, "SELECT a FROM t WHERE b > 15").is_ok());
+            let profile = match run_command(server_state, "\\last_profile") {
+                Response::SystemMsg(msg) => msg,
+                r => panic!("Expected message, got {:?}", r),
+            };
+            let lines: Vec<&str> = profile.lines().collect();
+            // The plan runs in batches: the rows in one, then an empty one.
+            assert!(
+                lines[0].starts_with("-> project rows=3 next_calls=2 "),
+                "{}",
+                profile
+            );
+            assert!(
+                lines[1].starts_with("  -> select rows=3 next_calls=2 "),
+                "{}",
+                profile
+            );
+            // The comparison is pushed into the scan.
+            assert!(
+                lines[2].starts_with("    -> scan rows=3 next_calls=2 "),
+                "{}",
+                profile
+            );
+            assert_eq!(lines.len(), 3, "{}", profile);
+
+            match run_command(server_state, "EXPLAIN ANALYZE SELECT a FROM t") {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                    assert!(msg.contains("Profile:\n-> scan rows=4 "), "{}", msg);
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+            match run_command(server_state, "\\last_profile") {
+                Response::SystemMsg(msg) => assert!(msg.starts_with("-> scan rows=4 "), "{}", msg),
+                r => panic!("Expected message, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_last_profile_needs_profiling() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            assert!(run_command(server_state, "CREATE TABLE t (a INT PRIMARY KEY)").is_ok());
+            assert!(run_command(server_state, "SELECT a FROM t").is_ok());
+            assert!(matches!(
+                run_command(server_state, "\\last_profile"),
+                Response::QueryExecutionError(_, msg) if msg.contains("--profile_operators")
+            ));
+        }
+
+        #[test]
+        fn test_explain_analyze() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            for sql in [
+                "CREATE TABLE t (a INT PRIMARY KEY)",
+                "CREATE TABLE u (b INT PRIMARY KEY)",
+                "INSERT INTO t VALUES (1), (2), (3)",
+                "INSERT INTO u VALUES (1), (2), (3), (4)",
+            ] {
+                assert!(run_command(server_state, sql).is_ok(), "{}", sql);
+            }
+            // Profiled without profile_operators.
+            let query = "EXPLAIN ANALYZE SELECT * FROM t, u WHERE a < b";
+            let msg = match run_command(server_state, query) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => msg,
+                r => panic!("Expected message, got {:?}", r),
+            };
+            assert!(msg.starts_with("-> Nested loop inner_join"), "{}", msg);
+            assert!(msg.contains("\nRows: 6\nProfile:\n"), "{}", msg);
+            assert!(
+                msg.contains("\n-> nested_loop_join rows=6 next_calls=7 loops=1 "),
+                "{}",
+                msg
+            );
+            // The inner side is scanned again for every row of the outer one.
+            assert!(
+                msg.contains("\n  -> scan rows=3 next_calls=4 loops=1 "),
+                "{}",
+                msg
+            );
+            assert!(
+                msg.contains("\n  -> scan rows=12 next_calls=15 loops=3 "),
+                "{}",
+                msg
+            );
+            // Every pass over a table reads its page from the buffer pool.
+            let reads: usize = msg
+                .split("\nBuffer pool: reads=")
+                .nth(1)
+                .and_then(|rest| rest.split(' ').next())
+                .and_then(|reads| reads.parse().ok())
+                .unwrap_or_else(|| panic!("No buffer pool reads in {}", msg));
+            assert!(reads >= 4, "{}", msg);
+            assert!(msg.contains("\nDisk: reads="), "{}", msg);
+        }
+
+        #[test]
+        fn test_column_group_flips_join_order() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            for sql in [
+                "CREATE TABLE places (id INT PRIMARY KEY, city VARCHAR(10), state VARCHAR(2))",
+                "CREATE TABLE visits (id INT PRIMARY KEY, place_id INT)",
+            ] {
+                assert!(run_command(server_state, sql).is_ok());
+            }
+            // The sample of the statistics keeps the latest rows, so it misses
+            // the rows of New York City, which come first.
+            let row = |id: usize, city: &str, state: &str| {
+                Tuple::new(vec![
+                    Field::Int(id as i32),
+                    Field::String(city.to_string()),
+                    Field::String(state.to_string()),
+                ])
+            };
+            let others = [("Albany", "NY"), ("Austin", "TX"), ("Dallas", "TX")];
+            let mut rows: Vec<Tuple> = (0..600).map(|i| row(i, "NYC", "NY")).collect();
+            rows.extend((600..20600).map(|i| {
+                let (city, state) = others[i % others.len()];
+                row(i, city, state)
+            }));
+            import_rows(server_state, "places", &rows);
+            let values: Vec<String> = (0..10).map(|i| format!("({}, {})", i, i * 7)).collect();
+            let sql = format!("INSERT INTO visits VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            let query = "SELECT places.id, visits.id FROM places JOIN visits \
+                         ON places.id = visits.place_id \
+                         WHERE places.city = 'NYC' AND places.state = 'NY'";
+            let explain = || match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                Response::QueryResult(QueryResult::MessageOnly(plan)) => plan,
+                r => panic!("Expected message, got {:?}", r),
+            };
+            // A hash join builds over its left input, the one estimated to be
+            // smaller.
+            let places_first =
+                |plan: &str| plan.find("places").unwrap() < plan.find("visits").unwrap();
+            let plan = explain();
+            assert!(places_first(&plan), "{}", plan);
+
+            assert!(run_command(server_state, "ANALYZE places (city, state)").is_ok());
+            let plan = explain();
+            assert!(!places_first(&plan), "{}", plan);
+            match run_command(server_state, query) {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 10),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            match run_command(server_state, "\\d places") {
+                Response::QueryResult(result) => {
+                    let groups: Vec<&Field> = result
+                        .get_tuples()
+                        .unwrap()
+                        .iter()
+                        .map(|t| t.get_field(4).unwrap())
+                        .collect();
+                    let group = Field::String("(city, state)".to_string());
+                    assert_eq!(groups, [&Field::String(String::new()), &group, &group]);
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            for (sql, expected) in [
+                ("ANALYZE places (city, zip)", "zip"),
+                ("ANALYZE places (city)", "at least two columns"),
+                ("ANALYZE places (city, city)", "appears twice"),
+            ] {
+                match run_command(server_state, sql) {
+                    Response::QueryExecutionError(_, msg) => {
+                        assert!(msg.contains(expected), "{}", msg)
+                    }
+                    r => panic!("Expected query execution error, got {:?}", r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_shared_subquery_runs_once() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE big (a INT PRIMARY KEY, x INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = (0..100).map(|i| format!("({}, {})", i, i % 7)).collect();
+            let sql = format!("INSERT INTO big VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            let shared = "SELECT a FROM big WHERE a > (SELECT max(x) FROM big) \
+                          AND x < (SELECT max(x) FROM big)";
+            // The second subquery differs in text, so it is not shared.
+            let naive = "SELECT a FROM big WHERE a > (SELECT max(x) FROM big) \
+                         AND x < (SELECT max(x) FROM big WHERE a >= 0)";
+            let sorted_result = |query: &str| match run_command(server_state, query) {
+                Response::QueryResult(result) => {
+                    let mut tuples = result.get_tuples().unwrap().clone();
+                    tuples.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+                    tuples
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let expected = sorted_result(naive);
+            assert_eq!(expected.len(), (7..100).filter(|i| i % 7 < 6).count());
+            assert_eq!(sorted_result(shared), expected);
+
+            for (query, runs) in [(shared, 1), (naive, 2)] {
+                match run_command(server_state, &format!("EXPLAIN ANALYZE {}", query)) {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                        assert!(
+                            msg.contains(&format!("\nSubqueries run: {}\n", runs)),
+                            "{}",
+                            msg
+                        );
+                    }
+                    r => panic!("Expected message, got {:?}", r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_subquery_rows_spill() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                spool_memory_rows: 2,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE big (a INT PRIMARY KEY, x INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = (0..20).map(|i| format!("({}, {})", i, i % 7)).collect();
+            let sql = format!("INSERT INTO big VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            // The subquery has more rows than the spool keeps in memory, and
+            // both references read them back from the spill.
+            let query = "SELECT a FROM big WHERE a IN (SELECT x FROM big WHERE a > 9) \
+                         AND a > ANY (SELECT x FROM big WHERE a > 9)";
+            match run_command(server_state, query) {
+                Response::QueryResult(result) => {
+                    let mut tuples = result.get_tuples().unwrap().clone();
+                    tuples.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+                    let expected: Vec<Tuple> =
+                        (1..7).map(|a| Tuple::new(vec![Field::BigInt(a)])).collect();
+                    assert_eq!(tuples, expected);
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            match run_command(server_state, &format!("EXPLAIN ANALYZE {}", query)) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                    assert!(msg.contains("\nSubqueries run: 1\n"), "{}", msg);
+                    assert!(msg.contains("\nSubqueries spilled: 1\n"), "{}", msg);
+                }
+                r => panic!("Expected message, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_order_by() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT, c VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = [7, 2, 9, 4, 0, 5, 8, 1, 6, 3]
+                .iter()
+                .map(|i| format!("({}, {}, 'c{}')", i, i % 3, i))
+                .collect();
+            let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            let query = "SELECT a AS x, c FROM t ORDER BY b DESC, x";
+            match run_command(server_state, query) {
+                Response::QueryResult(QueryResult::Select { result, .. }) => {
+                    let order: Vec<Field> =
+                        result.iter().map(|t| t.field_vals[0].clone()).collect();
+                    let expected = [2, 5, 8, 1, 4, 7, 0, 3, 6, 9].map(Field::BigInt);
+                    assert_eq!(order, expected);
+                }
+                r => panic!("Expected select result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_stream_aggregate() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, g INT, v INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let rows = table_gen(server_state, "t", 3)
+                .column(
+                    "g",
+                    ColumnDist::Zipf {
+                        min: 0,
+                        cardinality: 50,
+                        skew: 0.8,
+                    },
+                )
+                .column("v", ColumnDist::Uniform { min: 0, max: 12 })
+                .nulls("v", 0.1)
+                .generate(500);
+            import_rows(server_state, "t", &rows);
+            let mut groups: Vec<&Field> = rows.iter().map(|t| &t.field_vals[1]).collect();
+            groups.sort();
+            groups.dedup();
+
+            let hashed = "SELECT g, SUM(v), COUNT(v) FROM t GROUP BY g";
+            let streamed = "SELECT s.g, SUM(s.v), COUNT(s.v) \
+                            FROM (SELECT g, v FROM t ORDER BY g) AS s GROUP BY s.g";
+            for (query, strategy) in [(hashed, "hash_aggregate"), (streamed, "stream_aggregate")] {
+                match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                        assert!(msg.contains(strategy), "{}", msg)
+                    }
+                    r => panic!("Expected message, got {:?}", r),
+                }
+            }
+            let result = |query: &str| match run_command(server_state, query) {
+                Response::QueryResult(result) => result.get_tuples().unwrap().clone(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let mut expected = result(hashed);
+            expected.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+            assert_eq!(expected.len(), groups.len());
+            // The groups come out in the order of the input.
+            assert_eq!(result(streamed), expected);
+        }
+
+        #[test]
+        fn test_redundant_sort_removed() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+
+            for (query, sorts) in [
+                ("SELECT a FROM t ORDER BY a", 1),
+                (
+                    "SELECT s.a FROM (SELECT a, b FROM t ORDER BY a, b) AS s ORDER BY s.a",
+                    1,
+                ),
+                (
+                    "SELECT s.a FROM (SELECT a FROM t ORDER BY a) AS s ORDER BY s.a DESC",
+                    2,
+                ),
+                (
+                    "SELECT s.b, COUNT(s.a) FROM (SELECT a, b FROM t ORDER BY b) AS s \
+                     GROUP BY s.b ORDER BY s.b",
+                    1,
+                ),
+            ] {
+                match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                        assert_eq!(msg.matches("order_by(").count(), sorts, "{}", msg)
+                    }
+                    r => panic!("Expected message, got {:?}", r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_top_n_below_limit() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                top_n_max_rows: 5,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO t VALUES (1, 10), (2, 40), (3, 30), (4, 20), (5, 30), (6, 50)";
+            assert!(run_command(server_state, sql).is_ok());
+
+            for (query, top_n, expected) in [
+                (
+                    "SELECT a FROM t ORDER BY b DESC, a LIMIT 3",
+                    true,
+                    vec![6, 2, 3],
+                ),
+                (
+                    "SELECT a FROM t ORDER BY b, a LIMIT 5",
+                    true,
+                    vec![1, 4, 3, 5, 2],
+                ),
+                // Above the maximum the input is sorted.
+                (
+                    "SELECT a FROM t ORDER BY b, a LIMIT 6",
+                    false,
+                    vec![1, 4, 3, 5, 2, 6],
+                ),
+            ] {
+                match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                        assert_eq!(msg.contains("top_n("), top_n, "{}", msg);
+                        assert_eq!(msg.contains("order_by("), !top_n, "{}", msg);
+                    }
+                    r => panic!("Expected message, got {:?}", r),
+                }
+                match run_command(server_state, query) {
+                    Response::QueryResult(result) => {
+                        let expected: Vec<Tuple> = expected
+                            .into_iter()
+                            .map(|a| Tuple::new(vec![Field::BigInt(a)]))
+                            .collect();
+                        assert_eq!(result.get_tuples().unwrap(), &expected, "{}", query);
+                    }
+                    r => panic!("Expected query result, got {:?}", r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_merge_join_for_large_inputs() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                hash_join_max_build_rows: 100,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let mut rows = Vec::new();
+            for (seed, (table, n)) in [("orders", 400), ("items", 300)].into_iter().enumerate() {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY, k INT)", table);
+                assert!(run_command(server_state, &sql).is_ok());
+                let gen = table_gen(server_state, table, seed as u64)
+                    .column("k", ColumnDist::Uniform { min: 0, max: 49 });
+                rows.push(gen.generate(n));
+                import_rows(server_state, table, &rows[seed]);
+            }
+            let explain =
+                |query: &str| match run_command(server_state, &format!("EXPLAIN {}", query)) {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => msg,
+                    r => panic!("Expected message, got {:?}", r),
+                };
+            let count = |query: &str| match run_command(server_state, query) {
+                Response::QueryResult(result) => result.get_tuples().unwrap().len(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+
+            // Both inputs are too large to build a hash table over.
+            let query = "SELECT orders.a, items.a FROM orders JOIN items ON orders.k = items.k";
+            let plan = explain(query);
+            assert!(plan.contains("Sort merge inner_join"), "{}", plan);
+            assert_eq!(count(query), join_size(&rows[0], 1, &rows[1], 1));
+
+            // A small input keeps the hash join.
+            let query = "SELECT orders.a, items.a FROM orders JOIN items ON orders.k = items.k \
+                         WHERE items.a < 10";
+            let plan = explain(query);
+            assert!(plan.contains("Hash inner_join"), "{}", plan);
+            // The keys `a` of the rows are sequential from 0.
+            assert_eq!(count(query), join_size(&rows[0], 1, &rows[1][..10], 1));
+        }
+
+        #[test]
+        fn test_hypothetical_stats() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            for client in [0, 1] {
+                run_command_as(server_state, client, "\\r db");
+                run_command_as(server_state, client, "\\c db");
+            }
+            let mut rows = Vec::new();
+            for (seed, (table, n)) in [("small", 10), ("large", 50)].into_iter().enumerate() {
+                let sql = format!("CREATE TABLE {} (a INT PRIMARY KEY, k INT)", table);
+                assert!(run_command(server_state, &sql).is_ok());
+                let gen = table_gen(server_state, table, seed as u64)
+                    .column("k", ColumnDist::Uniform { min: 0, max: 4 });
+                rows.push(gen.generate(n));
+                import_rows(server_state, table, &rows[seed]);
+            }
+            let query = "SELECT small.a, large.a FROM small JOIN large ON small.k = large.k";
+            let explain = |client: u64| match run_command_as(
+                server_state,
+                client,
+                &format!("EXPLAIN {}", query),
+            ) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => msg,
+                r => panic!("Expected message, got {:?}", r),
+            };
+            let sorted_result = |client: u64| match run_command_as(server_state, client, query) {
+                Response::QueryResult(result) => {
+                    let mut tuples = result.get_tuples().unwrap().clone();
+                    tuples.sort_by(|a, b| a.field_vals.cmp(&b.field_vals));
+                    tuples
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            // The hash join builds over its first input, the smaller table.
+            let small_first =
+                |plan: &str| plan.find("small").unwrap() < plan.find("large").unwrap();
+            let plan = explain(0);
+            assert!(small_first(&plan), "{}", plan);
+            assert!(!plan.contains("hypothetical"), "{}", plan);
+            let expected = sorted_result(0);
+            assert_eq!(expected.len(), join_size(&rows[0], 1, &rows[1], 1));
+
+            let sql =
+                r#"SET hypothetical_stats = '{"small": {"rows": 10000000, "distinct": {"k": 5}}}'"#;
+            assert!(run_command_as(server_state, 0, sql).is_ok());
+            let plan = explain(0);
+            assert!(!small_first(&plan), "{}", plan);
+            assert!(plan.ends_with("Using hypothetical stats"), "{}", plan);
+            assert_eq!(sorted_result(0), expected);
+            // Other sessions keep planning with the real statistics.
+            let plan = explain(1);
+            assert!(small_first(&plan), "{}", plan);
+            assert!(!plan.contains("hypothetical"), "{}", plan);
+            assert_eq!(sorted_result(1), expected);
+
+            for sql in [
+                r#"SET hypothetical_stats = '{"missing": {"rows": 1}}'"#,
+                r#"SET hypothetical_stats = '{"small": {"rows": 1, "distinct": {"b": 1}}}'"#,
+                r#"SET hypothetical_stats = '{"small": {}}'"#,
+                "SET hypothetical_stats = 'rows'",
+            ] {
+                assert!(!run_command_as(server_state, 0, sql).is_ok(), "{}", sql);
+            }
+            assert!(run_command_as(server_state, 0, "SET hypothetical_stats = none").is_ok());
+            let plan = explain(0);
+            assert!(small_first(&plan), "{}", plan);
+            assert!(!plan.contains("hypothetical"), "{}", plan);
+        }
+
+        #[test]
+        fn test_storage_report() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let values: Vec<String> = (0..300).map(|i| format!("({}, 'name{}')", i, i)).collect();
+            for table in ["packed", "fragmented"] {
+                let sql = format!(
+                    "CREATE TABLE {} (id INT PRIMARY KEY, name VARCHAR(10))",
+                    table
+                );
+                assert!(run_command(server_state, &sql).is_ok());
+                let sql = format!("INSERT INTO {} VALUES {}", table, values.join(", "));
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+
+            // There is no DELETE statement yet, so delete through the storage manager.
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id_if_exists("fragmented").unwrap();
+            let sm = server_state.managers.sm;
+            let tid = TransactionId::new();
+            let ids: Vec<_> = sm
+                .get_iterator(c_id, tid, common::ids::Permissions::ReadOnly)
+                .map(|(_, id)| id)
+                .collect();
+            for id in ids
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .chain(ids.iter().skip(2).step_by(4))
+            {
+                sm.delete_value(*id, tid).unwrap();
+            }
+
+            let result = match run_command(server_state, "\\storage_report") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let tuples = result.get_tuples().unwrap();
+            assert_eq!(tuples.len(), 2);
+            // Sorted by wasted bytes, so the fragmented table comes first.
+            assert_eq!(
+                tuples[0].get_field(0),
+                Some(&Field::String("fragmented".to_string()))
+            );
+            let pages = tuples[0].get_field(1).unwrap().clone();
+            assert_eq!(tuples[0].get_field(4), Some(&pages));
+            assert_eq!(tuples[0].get_field(7), Some(&Field::BigInt(225)));
+            assert_eq!(tuples[1].get_field(7), Some(&Field::BigInt(0)));
+            assert!(matches!(tuples[1].get_field(5), Some(Field::BigInt(n)) if *n <= 1));
+        }
+
+        /// Ids of the rows of table t, sorted.
+        fn scan_ids(server_state: &'static ServerState, client_id: u64) -> Vec<Field> {
+            match run_command_as(server_state, client_id, "SELECT id FROM t") {
+                Response::QueryResult(result) => {
+                    let mut ids: Vec<Field> = result
+                        .get_tuples()
+                        .unwrap()
+                        .iter()
+                        .map(|t| t.get_field(0).unwrap().clone())
+                        .collect();
+                    ids.sort();
+                    ids
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_auto_compaction() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                compaction_pages_per_minute: 60,
+                compaction_max_queries_per_sec: 1e9,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            run_command_as(server_state, 1, "\\c db");
+            let sql = "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(100))";
+            assert!(run_command(server_state, sql).is_ok());
+            let name = "x".repeat(90);
+            let values: Vec<String> = (0..800).map(|i| format!("({}, '{}')", i, name)).collect();
+            let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            // There is no DELETE statement yet, so delete through the storage manager.
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id("t").unwrap();
+            let sm = server_state.managers.sm;
+            let tid = TransactionId::new();
+            let ids: Vec<_> = sm
+                .get_iterator(c_id, tid, common::ids::Permissions::ReadOnly)
+                .map(|(_, id)| id)
+                .collect();
+            for (i, id) in ids.iter().enumerate() {
+                if i % 8 != 0 {
+                    sm.delete_value(*id, tid).unwrap();
+                }
+            }
+            let expected = scan_ids(server_state, 0);
+            assert_eq!(expected.len(), 100);
+            let before = sm.storage_report(c_id).unwrap();
+            assert_eq!(before.pages_under_25, before.num_pages);
+
+            // The first tick starts the clock, the budget then grows with time.
+            let start = Instant::now();
+            let at = |secs| start + Duration::from_secs(secs);
+            let ran = |tables, pages| TickOutcome::Ran { tables, pages };
+            assert_eq!(db.compaction.tick(db, at(0)), ran(0, 0));
+            assert_eq!(db.compaction.tick(db, at(2)), ran(1, 2));
+
+            // Statements and open cursors using the table hold it off.
+            let lock = db.lock_tables(&[c_id], TableLockMode::Shared).unwrap();
+            assert_eq!(db.compaction.tick(db, at(4)), ran(0, 0));
+            drop(lock);
+            assert!(run_command(server_state, "DECLARE c CURSOR FOR SELECT id FROM t").is_ok());
+            assert!(run_command(server_state, "FETCH 1 FROM c").is_ok());
+            assert_eq!(db.compaction.tick(db, at(6)), ran(0, 0));
+            assert!(run_command(server_state, "CLOSE c").is_ok());
+
+            // A busy database pauses compaction.
+            let strict = ServerConfig {
+                compaction_max_queries_per_sec: 1.0,
+                ..config.clone()
+            };
+            let busy = AutoCompaction::new(&strict);
+            assert_eq!(busy.tick(db, at(0)), ran(0, 0));
+            for _ in 0..5 {
+                scan_ids(server_state, 0);
+            }
+            assert_eq!(busy.tick(db, at(1)), TickOutcome::Paused);
+            assert_eq!(busy.metrics.paused_ticks.load(Ordering::Relaxed), 1);
+
+            // Scans running during compaction see every row exactly once.
+            let scanner = thread::spawn(move || {
+                for _ in 0..20 {
+                    assert_eq!(scan_ids(server_state, 1), expected);
+                }
+            });
+            for minute in 1..=5 {
+                db.compaction.tick(db, at(60 * minute));
+            }
+            scanner.join().unwrap();
+            // Once a whole pass finds nothing to do, the table is not visited again.
+            let settled =
+                (6..20).any(|minute| db.compaction.tick(db, at(60 * minute)) == ran(0, 0));
+            assert!(settled);
+            assert_eq!(db.compaction.tick(db, at(1200)), ran(0, 0));
+
+            let after = sm.storage_report(c_id).unwrap();
+            assert!(after.empty_pages > 0);
+            assert!(after.dead_slots < before.dead_slots);
+            assert!(after.pages_under_25 - after.empty_pages < before.pages_under_25);
+            assert_eq!(scan_ids(server_state, 0).len(), 100);
+            match run_command(server_state, "SELECT * FROM system.metrics") {
+                Response::QueryResult(result) => {
+                    let relocated = result
+                        .get_tuples()
+                        .unwrap()
+                        .iter()
+                        .find(|t| {
+                            t.get_field(0)
+                                == Some(&Field::String("compaction.values_relocated".into()))
+                        })
+                        .and_then(|t| t.get_field(1).cloned());
+                    assert!(matches!(relocated, Some(Field::BigInt(n)) if n > 0));
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            assert!(run_command(server_state, "SET auto_compaction = off").is_ok());
+            assert_eq!(db.compaction.tick(db, at(1260)), TickOutcome::Disabled);
+            match run_command(server_state, "SHOW auto_compaction") {
+                Response::QueryResult(result) => assert_eq!(
+                    result.get_tuples().unwrap()[0].get_field(0),
+                    Some(&Field::String("off".into()))
+                ),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+            assert!(!run_command(server_state, "SET auto_compaction = maybe").is_ok());
+        }
+
+        #[test]
+        fn test_snapshot_export() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            run_command_as(server_state, 1, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(40)) WITH (txn_stamps = on)";
+            assert!(run_command(server_state, sql).is_ok());
+            let insert = |client_id, keys: std::ops::Range<usize>| {
+                let values: Vec<String> = keys.map(|i| format!("({}, 'row {}')", i, i)).collect();
+                let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+                let response = run_command_as(server_state, client_id, &sql);
+                assert!(response.is_ok(), "{:?}", response);
+            };
+            insert(0, 0..100);
+            let path = config.db_path.join("t.csv");
+            let export = || -> Vec<usize> {
+                let sql = format!(
+                    "COPY (SELECT * FROM t) TO '{}' WITH (SNAPSHOT true)",
+                    path.display()
+                );
+                let response = run_command(server_state, &sql);
+                let mut keys: Vec<usize> = fs::read_to_string(&path)
+                    .unwrap()
+                    .lines()
+                    .map(|line| line.split(',').next().unwrap().parse().unwrap())
+                    .collect();
+                keys.sort_unstable();
+                match response {
+                    Response::QueryResult(QueryResult::MessageOnly(msg)) => {
+                        assert_eq!(msg, format!("COPY {}", keys.len()))
+                    }
+                    r => panic!("Expected message, got {:?}", r),
+                }
+                keys
+            };
+            assert_eq!(export(), (0..100).collect::<Vec<_>>());
+
+            // A write that has not committed when the export starts is left out,
+            // even if it commits before the export is done.
+            let db = server_state.get_connected_db(0).unwrap();
+            let table = db
+                .catalog
+                .get_table(db.catalog.get_table_id("t").unwrap())
+                .unwrap();
+            let tid = TransactionId::new();
+            let write = db.begin_write(&table, tid).unwrap();
+            let mut tuple = Tuple::new(vec![Field::BigInt(100), Field::String("row".into())]);
+            tuple.tid = tid.id();
+            server_state
+                .managers
+                .sm
+                .insert_value(table.c_id, tuple.to_bytes(), tid);
+            assert_eq!(export(), (0..100).collect::<Vec<_>>());
+            write.commit();
+            assert_eq!(export(), (0..101).collect::<Vec<_>>());
+
+            // Export while another session inserts blocks of rows, one block per
+            // statement, in key order. Every export must hold the blocks
+            // committed before it started and none of a block still being
+            // written.
+            const BLOCK: usize = 50;
+            let started = Arc::new(AtomicUsize::new(101));
+            let committed = Arc::new(AtomicUsize::new(101));
+            let done = Arc::new(AtomicBool::new(false));
+            let writer = thread::spawn({
+                let (started, committed, done) = (started.clone(), committed.clone(), done.clone());
+                move || {
+                    for key in (101..20_000).step_by(BLOCK) {
+                        if done.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        started.store(key + BLOCK, Ordering::SeqCst);
+                        insert(1, key..key + BLOCK);
+                        committed.store(key + BLOCK, Ordering::SeqCst);
+                    }
+                }
+            });
+            let mut last = 0;
+            for _ in 0..5 {
+                while committed.load(Ordering::SeqCst) < last + 2 * BLOCK {
+                    thread::yield_now();
+                }
+                let before = committed.load(Ordering::SeqCst);
+                let keys = export();
+                let after = started.load(Ordering::SeqCst);
+                assert!(
+                    before <= keys.len() && keys.len() <= after,
+                    "exported {} rows, {} committed before and {} started after",
+                    keys.len(),
+                    before,
+                    after
+                );
+                assert_eq!((keys.len() - 101) % BLOCK, 0, "partial block exported");
+                assert_eq!(keys, (0..keys.len()).collect::<Vec<_>>());
+                last = keys.len();
+            }
+            done.store(true, Ordering::SeqCst);
+            writer.join().unwrap();
+
+            let sql = "CREATE TABLE u (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = format!("COPY u TO '{}' (SNAPSHOT)", path.display());
+            match run_command(server_state, &sql) {
+                Response::QueryExecutionError(_, msg) => assert!(
+                    msg.ends_with(
+                        "COPY with SNAPSHOT requires table u to be created WITH (txn_stamps = on)"
+                    ),
+                    "{}",
+                    msg
+                ),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_delete_during_scans() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            run_command_as(server_state, 1, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let rows: Vec<Tuple> = (0..2000)
+                .map(|i| Tuple::new(vec![Field::BigInt(i), Field::BigInt(i % 7)]))
+                .collect();
+            import_rows(server_state, "t", &rows);
+            let db = server_state.get_connected_db(0).unwrap();
+            let c_id = db.catalog.get_table_id("t").unwrap();
+            let delete = |sql: &str| match run_command(server_state, sql) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => msg,
+                r => panic!("Expected message, got {:?}", r),
+            };
+
+            assert_eq!(delete("DELETE FROM t WHERE a >= 1500"), "DELETE 500");
+            let stats = server_state.managers.stats;
+            assert_eq!(stats.get_container_record_count(c_id).unwrap(), 1500);
+
+            // Another session scans while rows are deleted. Every scan returns
+            // rows that were there when it started, each once.
+            let done = Arc::new(AtomicBool::new(false));
+            let scanner = thread::spawn({
+                let done = done.clone();
+                move || {
+                    let mut scans = 0;
+                    while scans == 0 || !done.load(Ordering::SeqCst) {
+                        let keys: Vec<i64> =
+                            match run_command_as(server_state, 1, "SELECT a FROM t") {
+                                Response::QueryResult(result) => result
+                                    .get_tuples()
+                                    .unwrap()
+                                    .iter()
+                                    .map(|t| match t.get_field(0) {
+                                        Some(Field::BigInt(a)) => *a,
+                                        f => panic!("Expected int, got {:?}", f),
+                                    })
+                                    .collect(),
+                                r => panic!("Expected query result, got {:?}", r),
+                            };
+                        let distinct: HashSet<i64> = keys.iter().copied().collect();
+                        assert_eq!(distinct.len(), keys.len(), "row returned twice");
+                        assert!(keys.iter().all(|a| (0..1500).contains(a)));
+                        scans += 1;
+                    }
+                }
+            });
+            assert_eq!(delete("DELETE FROM t WHERE a < 500 AND b = 0"), "DELETE 72");
+            assert_eq!(delete("DELETE FROM t WHERE a < 500"), "DELETE 428");
+            assert_eq!(delete("DELETE FROM t"), "DELETE 1000");
+            done.store(true, Ordering::SeqCst);
+            scanner.join().unwrap();
+
+            assert_eq!(stats.get_container_record_count(c_id).unwrap(), 0);
+            match run_command(server_state, "SELECT a FROM t") {
+                Response::QueryResult(result) => assert!(result.get_tuples().unwrap().is_empty()),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+        }
+
+        #[test]
+        fn test_explain_writes() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert!(run_command(server_state, "INSERT INTO t VALUES (1, 2), (3, 4)").is_ok());
+            let explain = |sql: &str| match run_command(server_state, &format!("EXPLAIN {}", sql)) {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => msg,
+                r => panic!("Expected message, got {:?}", r),
+            };
+
+            let plan = explain("DELETE FROM t WHERE a < 2");
+            assert!(plan.starts_with("Delete from t\n"), "{}", plan);
+            assert!(plan.contains("scan(t, "), "{}", plan);
+            assert_eq!(
+                explain("DELETE FROM t"),
+                "Delete all rows of t, emptying its pages"
+            );
+            assert_eq!(
+                explain("INSERT INTO t (b, a) VALUES (6, 5), (8, 7)"),
+                "Insert 2 rows into t"
+            );
+            // Nothing was deleted or inserted.
+            match run_command(server_state, "SELECT * FROM t") {
+                Response::QueryResult(result) => assert_eq!(result.get_tuples().unwrap().len(), 2),
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            for (sql, expected) in [
+                ("EXPLAIN DELETE FROM missing", "missing"),
+                ("EXPLAIN INSERT INTO t (c) VALUES (1)", "c"),
+                (
+                    "EXPLAIN ANALYZE DELETE FROM t",
+                    "EXPLAIN ANALYZE is only supported for queries",
+                ),
+            ] {
+                match run_command(server_state, sql) {
+                    Response::QueryExecutionError(_, msg)
+                    | Response::QueryErrorAt { message: msg, .. } => {
+                        assert!(msg.contains(expected), "{}: {}", sql, msg)
+                    }
+                    r => panic!("Expected error for {}, got {:?}", sql, r),
+                }
+            }
+        }
+
+        #[test]
+        fn test_prepared_statements() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(10))";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = (0..100).map(|i| format!("({}, 'b{}')", i, i)).collect();
+            let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+            let column = |sql: &str, i: usize| match run_command(server_state, sql) {
+                Response::QueryResult(result) => result
+                    .get_tuples()
+                    .unwrap()
+                    .iter()
+                    .map(|row| row.get_field(i).unwrap().to_string())
+                    .collect::<Vec<_>>(),
+                r => panic!("Expected query result for {}, got {:?}", sql, r),
+            };
+            let error = |client: u64, sql: &str| match run_command_as(server_state, client, sql) {
+                Response::QueryExecutionError(_, msg)
+                | Response::QueryErrorAt { message: msg, .. } => msg,
+                r => panic!("Expected error for {}, got {:?}", sql, r),
+            };
+
+            let sql = "PREPARE q AS SELECT a, b FROM t WHERE a = $1";
+            assert!(run_command(server_state, sql).is_ok());
+            assert_eq!(column("EXECUTE q (5)", 1), ["b5"]);
+            assert_eq!(column("EXECUTE q (42)", 1), ["b42"]);
+            assert!(column("EXECUTE q (1000)", 1).is_empty());
+            // The plan is kept with its placeholder, each EXECUTE binds a copy.
+            let db = server_state.get_connected_db(0).unwrap();
+            let prepared = db.prepared_statements.get(0, "q").unwrap();
+            assert!(prepared.plan.pretty_string().contains("$1"));
+
+            let sql = "PREPARE r AS SELECT a FROM t WHERE a >= $1 AND a < $2 AND b <> $3";
+            assert!(run_command(server_state, sql).is_ok());
+            assert_eq!(column("EXECUTE r (10, 13, 'b11')", 0), ["10", "12"]);
+            assert_eq!(column("EXECUTE r (-5, 2, 'x')", 0), ["0", "1"]);
+            // Placeholders in subqueries are bound too.
+            let sql = "PREPARE s AS SELECT a FROM t WHERE a = (SELECT MAX(a) FROM t WHERE a < $1)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert_eq!(column("EXECUTE s (50)", 0), ["49"]);
+            assert_eq!(column("EXECUTE s (7)", 0), ["6"]);
+            // Placeholders may repeat and come in any order, but not skip one.
+            let sql = "PREPARE u AS SELECT a FROM t WHERE a > $2 AND a < $1 AND a <> $2 + 1";
+            assert!(run_command(server_state, sql).is_ok());
+            assert_eq!(column("EXECUTE u (14, 10)", 0), ["12", "13"]);
+            let sql = "PREPARE g AS SELECT a FROM t WHERE a > $1 AND a < $3";
+            assert!(run_command(server_state, sql).is_ok());
+
+            for (sql, expected) in [
+                ("EXECUTE q ('x')", "Parameter $1 expects a value of type"),
+                (
+                    "EXECUTE r (1, 2, 3)",
+                    "Parameter $3 expects a value of type",
+                ),
+                ("EXECUTE q", "No value for parameter $1"),
+                (
+                    "EXECUTE q (1, 2)",
+                    "The statement has 1 parameters, 2 values were given",
+                ),
+                (
+                    "EXECUTE g (1, 2, 3)",
+                    "The statement uses $3 but not $2, parameters must be numbered from $1 without gaps",
+                ),
+                ("EXECUTE g (1, 2)", "No value for parameter $3"),
+                ("EXECUTE q (a)", "Parameter a is not a value"),
+                ("EXECUTE missing (1)", "missing"),
+                ("PREPARE q AS SELECT a FROM t", "already exists"),
+                (
+                    "PREPARE d AS DELETE FROM t",
+                    "PREPARE is only supported for queries",
+                ),
+                (
+                    "PREPARE p (INT) AS SELECT a FROM t WHERE a = $1",
+                    "parameter types",
+                ),
+                ("SELECT a FROM t WHERE a = $1", "No value for parameter $1"),
+                ("SELECT a FROM t WHERE a = ?", "Unsupported placeholder ?"),
+            ] {
+                assert!(
+                    error(0, sql).contains(expected),
+                    "{}: {}",
+                    sql,
+                    error(0, sql)
+                );
+            }
+
+            // Prepared statements are scoped to their session.
+            run_command_as(server_state, 1, "\\c db");
+            assert!(error(1, "EXECUTE q (5)").contains("does not exist"));
+            assert!(run_command(server_state, "DEALLOCATE q").is_ok());
+            assert!(error(0, "EXECUTE q (5)").contains("does not exist"));
+
+            // Dropping a table the plan reads invalidates the statement.
+            assert!(run_command(server_state, "DROP TABLE t").is_ok());
+            let msg = error(0, "EXECUTE r (1, 2, 'x')");
+            assert!(
+                msg.contains("Prepared statement r is no longer valid"),
+                "{}",
+                msg
+            );
+        }
+
+        #[test]
+        fn test_copy_from_invalid_utf8() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(20))";
+            assert!(run_command(server_state, sql).is_ok());
+            let path = config.db_path.join("t.csv");
+            fs::write(&path, b"a,b\n1,caf\xc3\xa9\n2,bad\xff\xfebyte\n3,ok\n").unwrap();
+
+            let copy = |options: &str| {
+                let sql = format!(
+                    "COPY t FROM '{}' WITH (header, {})",
+                    path.display(),
+                    options
+                );
+                run_command(server_state, &sql)
+            };
+            match copy("utf8 strict") {
+                Response::QueryExecutionError(_, msg) => {
+                    assert!(
+                        msg.contains("Invalid UTF-8 on line 3 in field 2"),
+                        "{}",
+                        msg
+                    )
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert!(!copy("utf8 maybe").is_ok());
+            match copy("utf8 lossy") {
+                Response::QueryResult(QueryResult::MessageOnly(msg)) => assert_eq!(msg, "COPY 3"),
+                r => panic!("Expected message, got {:?}", r),
+            }
+
+            let result = match run_command(server_state, "SELECT b FROM t ORDER BY a") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let values: Vec<String> = result
+                .get_tuples()
+                .unwrap()
+                .iter()
+                .map(|t| t.get_field(0).unwrap().to_string())
+                .collect();
+            assert_eq!(values, ["caf\u{e9}", "bad\u{fffd}\u{fffd}byte", "ok"]);
+            let json: serde_json::Value =
+                serde_json::from_str(&result.render_json(&Default::default())).unwrap();
+            assert_eq!(json[1]["b"], "bad\u{fffd}\u{fffd}byte");
+        }
+
+        #[test]
+        fn test_not_null_and_defaults() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (id INT PRIMARY KEY, n INT NOT NULL, i INT DEFAULT 7, \
+                       s VARCHAR(10) DEFAULT 'none', d DATE DEFAULT '2024-01-02', \
+                       ts TIMESTAMP DEFAULT '2024-01-02 03:04:05', b BOOL DEFAULT true, \
+                       f FLOAT DEFAULT 1.5, m DECIMAL(10, 2) DEFAULT 2.25)";
+            assert!(run_command(server_state, sql).is_ok());
+            let error = |sql: &str| match run_command(server_state, sql) {
+                Response::QueryExecutionError(_, msg) => msg,
+                r => panic!("Expected query execution error, got {:?}", r),
+            };
+
+            // Omitted columns take their default, or NULL without one.
+            let sql = "INSERT INTO t (n, id) VALUES (10, 1)";
+            assert!(run_command(server_state, sql).is_ok());
+            let sql = "INSERT INTO t (id, n, i, s) VALUES (2, 20, NULL, 'set')";
+            assert!(run_command(server_state, sql).is_ok());
+            let result = match run_command(server_state, "SELECT * FROM t ORDER BY id") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let rows: Vec<String> = result
+                .get_tuples()
+                .unwrap()
+                .iter()
+                .map(|t| t.to_csv())
+                .collect();
+            assert_eq!(
+                rows,
+                [
+                    "1,10,7,none,2024-01-02,2024-01-02 03:04:05,true,1.5,2.2500",
+                    "2,20,NULL,set,2024-01-02,2024-01-02 03:04:05,true,1.5,2.2500",
+                ]
+            );
+
+            assert_eq!(
+                error("INSERT INTO t (id) VALUES (3)"),
+                "Execution Error: Null value in column n of row 1 violates the not-null constraint"
+            );
+            assert_eq!(
+                error("INSERT INTO t (id, n) VALUES (3, 30), (4, NULL)"),
+                "Execution Error: Null value in column n of row 2 violates the not-null constraint"
+            );
+            match run_command(server_state, "INSERT INTO t (id, nn) VALUES (3, 30)") {
+                Response::QueryErrorAt {
+                    message, position, ..
+                } => {
+                    assert_eq!(message, "Column nn does not exist, did you mean n?");
+                    assert_eq!(position.column, 20);
+                }
+                r => panic!("Expected error at the column, got {:?}", r),
+            }
+            assert!(error("INSERT INTO t (id, n, id) VALUES (3, 30, 3)").contains("more than once"));
+            assert!(error("INSERT INTO t (id, n) VALUES (3)").contains("expected 2 values"));
+
+            let sql = "CREATE TABLE u (a INT PRIMARY KEY, b INT DEFAULT 'x')";
+            assert!(error(sql).contains("Default 'x' of column b is not a value of type"));
+            match run_command(server_state, "SHOW COLUMNS FROM t") {
+                Response::QueryResult(result) => {
+                    let nullable: Vec<String> = result.get_tuples().unwrap()[..3]
+                        .iter()
+                        .map(|t| t.get_field(2).unwrap().to_string())
+                        .collect();
+                    assert_eq!(nullable, ["NO", "NO", "YES"]);
+                }
+                r => panic!("Expected query result, got {:?}", r),
+            }
+
+            // Imports follow the same rule, reporting the line of the file.
+            let path = config.db_path.join("t.csv");
+            fs::write(&path, "5,50,1,a,2024-01-01,2024-01-01 00:00:00,false,1,1\n6,NULL,1,a,2024-01-01,2024-01-01 00:00:00,false,1,1\n").unwrap();
+            assert_eq!(
+                error(&format!("\\i {} t", path.display())),
+                "Execution Error: Null value in column n on line 2 violates the not-null constraint"
+            );
+        }
+
+        #[test]
+        fn test_unique_keys() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (id INT PRIMARY KEY, u INT UNIQUE, a INT, b INT, \
+                       UNIQUE (a, b))";
+            assert!(run_command(server_state, sql).is_ok());
+            let error = |sql: &str| match run_command(server_state, sql) {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::DuplicateKey);
+                    msg
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            };
+            let rows = || match run_command(server_state, "SELECT * FROM t ORDER BY id") {
+                Response::QueryResult(result) => result
+                    .get_tuples()
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.to_csv())
+                    .collect::<Vec<_>>(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+
+            let sql = "INSERT INTO t VALUES (1, 10, 1, 1), (2, NULL, 1, 2), (3, NULL, 1, NULL)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert_eq!(
+                error("INSERT INTO t VALUES (4, 40, 2, 2), (1, 50, 2, 3)"),
+                "Duplicate key (id)=(1) violates a unique constraint"
+            );
+            assert_eq!(
+                error("INSERT INTO t VALUES (4, 40, 2, 2), (5, 40, 2, 3)"),
+                "Duplicate key (u)=(40) violates a unique constraint"
+            );
+            assert_eq!(
+                error("INSERT INTO t VALUES (4, 40, 1, 2)"),
+                "Duplicate key (a, b)=(1, 2) violates a unique constraint"
+            );
+            // NULLs never repeat a key.
+            assert!(run_command(server_state, "INSERT INTO t VALUES (4, NULL, 1, NULL)").is_ok());
+            assert_eq!(
+                rows(),
+                ["1,10,1,1", "2,NULL,1,2", "3,NULL,1,NULL", "4,NULL,1,NULL"]
+            );
+
+            // Deleted rows free their keys, the others still conflict.
+            assert!(run_command(server_state, "DELETE FROM t WHERE id <= 2").is_ok());
+            assert!(run_command(server_state, "INSERT INTO t VALUES (1, 10, 1, 1)").is_ok());
+            assert_eq!(
+                error("INSERT INTO t VALUES (2, 10, 1, 2)"),
+                "Duplicate key (u)=(10) violates a unique constraint"
+            );
+            assert!(run_command(server_state, "INSERT INTO t VALUES (2, NULL, 1, 2)").is_ok());
+            assert_eq!(
+                error("INSERT INTO t VALUES (5, NULL, 1, 2)"),
+                "Duplicate key (a, b)=(1, 2) violates a unique constraint"
+            );
+
+            // Imports are checked too, within the file and against the table.
+            let path = config.db_path.join("t.csv");
+            fs::write(&path, "5,50,5,5\n6,60,6,6\n5,70,7,7\n").unwrap();
+            assert_eq!(
+                error(&format!("\\i {} t", path.display())),
+                "Duplicate key (id)=(5) violates a unique constraint"
+            );
+            fs::write(&path, "5,50,5,5\n6,10,6,6\n").unwrap();
+            assert_eq!(
+                error(&format!("\\i {} t", path.display())),
+                "Duplicate key (u)=(10) violates a unique constraint"
+            );
+            assert_eq!(
+                rows(),
+                ["1,10,1,1", "2,NULL,1,2", "3,NULL,1,NULL", "4,NULL,1,NULL"]
+            );
+
+            // UPDATE is not supported, so it cannot repeat a key either.
+            match run_command(server_state, "UPDATE t SET id = 2 WHERE id = 1") {
+                Response::QueryExecutionError(_, msg) => assert_eq!(
+                    msg,
+                    "Fairy Error: UPDATE is not supported, delete the rows and insert them again"
+                ),
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            assert_eq!(
+                rows(),
+                ["1,10,1,1", "2,NULL,1,2", "3,NULL,1,NULL", "4,NULL,1,NULL"]
+            );
+        }
+
+        #[test]
+        fn test_import_field_too_large() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                max_field_size: 8,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR(20))";
+            assert!(run_command(server_state, sql).is_ok());
+            // The path of COPY is a literal over the limit, so import the file.
+            let path = config.db_path.join("t.csv");
+            fs::write(&path, "1,12345678\n2,123456789\n").unwrap();
+
+            let cmd = format!("\\i {} t", path.display());
+            match run_command(server_state, &cmd) {
+                Response::QueryExecutionError(code, msg) => {
+                    assert_eq!(code, ErrorCode::FieldTooLarge);
+                    assert_eq!(
+                        msg,
+                        "Value of 9 bytes on line 2 exceeds max_field_size of 8 bytes"
+                    );
+                }
+                r => panic!("Expected query execution error, got {:?}", r),
+            }
+            fs::write(&path, "1,12345678\n").unwrap();
+            assert!(run_command(server_state, &cmd).is_ok());
+        }
+
+        #[test]
+        fn test_admission_control() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                max_running_statements: 1,
+                max_queued_statements: 1,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            for client in 0..4 {
+                run_command_as(server_state, client, "\\r db");
+                run_command_as(server_state, client, "\\c db");
+            }
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY)";
+            assert!(run_command(server_state, sql).is_ok());
+            assert!(run_command(server_state, "INSERT INTO t VALUES (1), (2)").is_ok());
+
+            // Take the only slot, so that the next statement has to queue.
+            let admission = &server_state.admission;
+            let permit = admission.admit(9, "held".to_string()).unwrap();
+            thread::scope(|s| {
+                let queued = s.spawn(|| run_command_as(server_state, 1, "SELECT * FROM t"));
+                while admission.activity().1.is_empty() {
+                    thread::yield_now();
+                }
+                let progress = match run_command(server_state, "\\progress") {
+                    Response::QueryResult(result) => result.get_tuples().unwrap().clone(),
+                    r => panic!("Expected query result, got {:?}", r),
+                };
+                let rows: Vec<_> = progress
+                    .iter()
+                    .map(|t| {
+                        (
+                            t.get_field(0),
+                            t.get_field(1),
+                            t.get_field(2),
+                            t.get_field(5),
+                        )
+                    })
+                    .collect();
+                let text = |s: &str| Field::String(s.to_string());
+                assert_eq!(
+                    rows,
+                    vec![
+                        (
+                            Some(&Field::BigInt(9)),
+                            Some(&text("running")),
+                            Some(&Field::Null),
+                            Some(&text("held"))
+                        ),
+                        (
+                            Some(&Field::BigInt(1)),
+                            Some(&text("queued")),
+                            Some(&Field::BigInt(1)),
+                            Some(&text("SELECT * FROM t"))
+                        ),
+                    ]
+                );
+
+                // Settings and meta commands do not wait for their turn.
+                assert!(run_command_as(server_state, 2, "SHOW durability").is_ok());
+                assert!(run_command_as(server_state, 2, "\\dt").is_ok());
+                // The queue is full.
+                match run_command_as(server_state, 3, "SELECT * FROM t") {
+                    Response::QueryExecutionError(code, message) => {
+                        assert_eq!(code, ErrorCode::ServerBusy);
+                        assert!(message.starts_with("Server busy"), "{}", message);
+                    }
+                    r => panic!("Expected server busy, got {:?}", r),
+                }
+
+                drop(permit);
+                match queued.join().unwrap() {
+                    Response::QueryResult(result) => {
+                        assert_eq!(result.get_tuples().unwrap().len(), 2)
+                    }
+                    r => panic!("Expected query result, got {:?}", r),
+                }
+            });
+
+            let result = match run_command(server_state, "SELECT * FROM system.metrics") {
+                Response::QueryResult(result) => result,
+                r => panic!("Expected query result, got {:?}", r),
+            };
+            let count = |name: &str| {
+                result
+                    .get_tuples()
+                    .unwrap()
+                    .iter()
+                    .find(|t| t.get_field(0) == Some(&Field::String(name.to_string())))
+                    .map(|t| t.get_field(1).cloned())
+                    .unwrap()
+            };
+            assert_eq!(
+                count("admission.rejected_queue_full"),
+                Some(Field::BigInt(1))
+            );
+            assert_eq!(count("admission.rejected_timeout"), Some(Field::BigInt(0)));
+            assert_eq!(count("admission.queue_wait"), Some(Field::BigInt(1)));
+            assert_eq!(count("admission.queued"), Some(Field::BigInt(0)));
+            assert_eq!(count("temp.used_bytes"), Some(Field::BigInt(0)));
+            // The query reading the metrics is the one running.
+            assert_eq!(count("admission.running"), Some(Field::BigInt(1)));
+        }
+
+        #[test]
+        fn test_admission_ceiling_under_load() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+                max_running_statements: 2,
+                ..ServerConfig::temporary()
+            }));
+            let server_state = create_server_state(config);
+            for client in 0..8 {
+                run_command_as(server_state, client, "\\r db");
+                run_command_as(server_state, client, "\\c db");
+            }
+            let sql = "CREATE TABLE t (a INT PRIMARY KEY, b INT)";
+            assert!(run_command(server_state, sql).is_ok());
+            let values: Vec<String> = (0..200).map(|i| format!("({}, {})", i, i % 7)).collect();
+            let sql = format!("INSERT INTO t VALUES {}", values.join(", "));
+            assert!(run_command(server_state, &sql).is_ok());
+
+            // Start more joins than may run at once and watch how many run.
+            let admission = &server_state.admission;
+            let done = AtomicBool::new(false);
+            let peak = AtomicUsize::new(0);
+            thread::scope(|s| {
+                s.spawn(|| {
+                    while !done.load(Ordering::SeqCst) {
+                        peak.fetch_max(admission.activity().0.len(), Ordering::SeqCst);
+                        thread::yield_now();
+                    }
+                });
+                let clients: Vec<_> = (0..8)
+                    .map(|client| {
+                        s.spawn(move || {
+                            let sql = "SELECT t1.a FROM t t1, t t2 WHERE t1.b = t2.b";
+                            run_command_as(server_state, client, sql)
+                        })
+                    })
+                    .collect();
+                for client in clients {
+                    assert!(client.join().unwrap().is_ok());
+                }
+                done.store(true, Ordering::SeqCst);
+            });
+            assert_eq!(peak.load(Ordering::SeqCst), 2);
+            assert!(admission.metrics.queue_wait.count() > 0);
+        }
+
+        #[test]
+        fn test_dictionary_encoding() {
+            let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig::temporary()));
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\r db");
+            run_command(server_state, "\\c db");
+            let db = server_state.get_connected_db(0).unwrap();
+            let sm = server_state.managers.sm;
+            let count = |server_state, sql: &str| match run_command(server_state, sql) {
+                Response::QueryResult(result) => result.get_tuples().unwrap()[0].clone(),
+                r => panic!("Expected query result, got {:?}", r),
+            };
+
+            // A skewed column: three common values, and a rare one in the last rows.
+            let color = |i: usize| match i {
+                0..=2699 => format!("a common color {} {}", i % 3, "of the palette ".repeat(5)),
+                _ => "a rare color of the palette".to_string(),
+            };
+            let values: Vec<String> = (0..3000)
+                .map(|i| format!("({}, '{}')", i, color(i)))
+                .collect();
+            for table in ["plain", "dict"] {
+                let sql = format!(
+                    "CREATE TABLE {} (id INT PRIMARY KEY, color VARCHAR(100))",
+                    table
+                );
+                assert!(run_command(server_state, &sql).is_ok());
+            }
+            let sql = "ALTER TABLE dict ALTER COLUMN color SET (encoding = dictionary)";
+            assert!(run_command(server_state, sql).is_ok());
+            for table in ["plain", "dict"] {
+                for chunk in values.chunks(500) {
+                    let sql = format!("INSERT INTO {} VALUES {}", table, chunk.join(", "));
+                    assert!(run_command(server_state, &sql).is_ok());
+                }
+            }
+            let plain = db.catalog.get_table_id("plain").unwrap();
+            let dict = db.catalog.get_table_id("dict").unwrap();
+            let plain_pages = sm.storage_report(plain).unwrap().num_pages;
+            let dict_pages = sm.storage_report(dict).unwrap().num_pages;
+            assert!(
+                dict_pages * 2 < plain_pages,
+                "{} {}",
+                dict_pages,
+                plain_pages
+            );
+
+            // Pages whose dictionary lacks the rare color are not read.
+            let reads = |table: &str| {
+                let before = sm.mem_stats().bp_read_frame;
+                let sql = format!(
+                    "SELECT COUNT(*) FROM {} WHERE color = 'a rare color of the palette'",
+                    table
+                );
+                assert_eq!(
+                    count(server_state, &sql),
+                    Tuple::new(vec![Field::BigInt(300)])
+                );
+                sm.mem_stats().bp_read_frame - before
+            };
+            let plain_reads = reads("plain");
+            let dict_reads = reads("dict");
+            assert!(plain_reads >= plain_pages, "{}", plain_reads);
+            assert!(dict_reads < dict_pages / 2, "{} {}", dict_reads, dict_pages);
+            for sql in [
+                "SELECT COUNT(*) FROM dict WHERE color <> 'a rare color of the palette'",
+                "SELECT COUNT(*) FROM dict WHERE color LIKE '%common%'",
+            ] {
+                assert_eq!(
+                    count(server_state, sql),
+                    Tuple::new(vec![Field::BigInt(2700)])
+                );
+            }
+
+            // Updates past the capacity of the page dictionary are stored inline.
+            let tid = TransactionId::new();
+            let rows: Vec<_> = sm
+                .get_iterator(dict, tid, common::ids::Permissions::ReadOnly)
+                .take(100)
+                .collect();
+            for (i, (bytes, id)) in rows.into_iter().enumerate() {
+                let mut tuple = Tuple::from_bytes(&bytes);
+                tuple.field_vals[1] = Field::String(format!("new {}", i));
+                sm.update_value(tuple.to_bytes(), id, tid).unwrap();
+            }
+            for i in [0, 99] {
+                let sql = format!("SELECT COUNT(*) FROM dict WHERE color = 'new {}'", i);
+                assert_eq!(
+                    count(server_state, &sql),
+                    Tuple::new(vec![Field::BigInt(1)])
+                );
+            }
+            let sql = "SELECT COUNT(*) FROM dict WHERE color LIKE 'a %'";
+            assert_eq!(
+                count(server_state, sql),
+                Tuple::new(vec![Field::BigInt(2900)])
+            );
+
+            // The encoding of the column survives a restart.
+            server_state.shutdown().unwrap();
+            let server_state = create_server_state(config);
+            run_command(server_state, "\\c db");
+            let sm = server_state.managers.sm;
+            let values: Vec<String> = (3000..4000)
+                .map(|i| format!("({}, '{}')", i, color(i % 2700)))
+                .collect();
+            let mut added = Vec::new();
+            for (table, c_id) in [("plain", plain), ("dict", dict)] {
+                let before = sm.storage_report(c_id).unwrap().num_pages;
+                for chunk in values.chunks(500) {
+                    let sql = format!("INSERT INTO {} VALUES {}", table, chunk.join(", "));
+                    assert!(run_command(server_state, &sql).is_ok());
+                }
+                added.push(sm.storage_report(c_id).unwrap().num_pages - before);
+            }
+            assert!(added[1] * 2 < added[0], "{:?}", added);
+            let sql = "SELECT COUNT(*) FROM dict WHERE color = 'a rare color of the palette'";
+            assert_eq!(
+                count(server_state, sql),
+                Tuple::new(vec![Field::BigInt(300)])
+            );
+        }
     }
 }
@@ -1,11 +1,12 @@
 use std::collections::{hash_map::Entry, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
+use crate::admission::Admission;
 use crate::database_state::DatabaseState;
 
-use common::error::c_err;
+use common::error::{c_err, ObjectKind};
 use common::{FairyError, QUERY_CACHES_DIR_NAME};
 
 use queryexe::Managers;
@@ -22,6 +23,8 @@ pub struct ServerState {
     /// active connections indicates what client_id is connected to what db_id
     pub active_connections: RwLock<HashMap<u64, u64>>, //xtx type these chnage to a struct  and update the tids
     pub managers: &'static Managers,
+    /// Admission of the statements of all databases.
+    pub admission: Arc<Admission>,
 }
 
 impl ServerState {
@@ -29,6 +32,7 @@ impl ServerState {
         // Create databases
         let server_state_dir = base_dir.join(SERVER_STATE_DIR);
         debug!("Looking for databases in {:?}", server_state_dir);
+        let admission = Arc::new(Admission::from_config(managers.config));
 
         let mut db_map = HashMap::new();
         if server_state_dir.exists() {
@@ -41,7 +45,8 @@ impl ServerState {
                     let db = db.unwrap();
                     let db_path = db.path();
                     info!("Found persisted database {:?}", db_path);
-                    let db_box = Box::new(DatabaseState::load(db_path, managers)?);
+                    let db_box =
+                        Box::new(DatabaseState::load(db_path, managers, admission.clone())?);
                     let db_state: &'static DatabaseState = Box::leak(db_box);
                     db_map.insert(db_state.id, db_state);
                 }
@@ -58,6 +63,7 @@ impl ServerState {
             active_connections: RwLock::new(HashMap::new()),
             server_state_dir,
             managers,
+            admission,
         };
 
         Ok(server_state)
@@ -83,9 +89,9 @@ impl ServerState {
         }
     }
 
-    pub fn get_db_names(&self) -> Vec<String> {
+    pub fn get_dbs(&self) -> Vec<&'static DatabaseState> {
         let id_to_db = self.id_to_db.read().unwrap();
-        id_to_db.values().map(|db| db.name.clone()).collect()
+        id_to_db.values().copied().collect()
     }
 
     /// Reset the server
@@ -105,13 +111,9 @@ impl ServerState {
         fs::remove_dir_all(self.server_state_dir.clone())?;
         fs::create_dir_all(self.server_state_dir.clone())?;
 
-        // clear the manager states
+        // clear the manager states and the data they persisted
         self.managers.reset()?;
 
-        // remove manager persisted data
-        fs::remove_dir_all(self.managers.path.clone()).ok(); // try to remove but will fail if base_dir is empty - this is ok
-        fs::create_dir_all(self.managers.path.clone())?;
-
         Ok(())
     }
 
@@ -154,7 +156,7 @@ impl ServerState {
         }
 
         // call shutdown on SM to ensure stateful shutdown
-        self.managers.shutdown();
+        self.managers.shutdown()?;
         Ok(())
     }
 
@@ -164,14 +166,19 @@ impl ServerState {
         let mut id_to_db = self.id_to_db.write().unwrap();
 
         match id_to_db.entry(db_id) {
-            Entry::Occupied(_) => Err(FairyError::FairyError(format!(
-                "database with name {:?} already exists",
-                name
-            ))),
+            Entry::Occupied(_) => Err(FairyError::AlreadyExists(
+                ObjectKind::Database,
+                name.to_string(),
+            )),
             Entry::Vacant(entry) => {
-                let db_state = DatabaseState::new_from_name(name, self.managers).map_err(|e| {
-                    FairyError::FairyError(format!("Failed to create database state: {}", e))
-                })?;
+                let db_state =
+                    DatabaseState::new_from_name(name, self.managers, self.admission.clone())
+                        .map_err(|e| {
+                            FairyError::FairyError(format!(
+                                "Failed to create database state: {}",
+                                e
+                            ))
+                        })?;
                 entry.insert(Box::leak(Box::new(db_state)));
                 Ok(())
             }
@@ -181,13 +188,31 @@ impl ServerState {
     pub fn connect_to_db(&self, db_name: &str, client_id: u64) -> Result<(), FairyError> {
         let db_id = self.get_db_id_from_name(db_name)?;
         let mut active_connections = self.active_connections.write().unwrap();
-        active_connections.insert(client_id, db_id);
+        if let Some(previous) = active_connections.insert(client_id, db_id) {
+            if previous != db_id {
+                self.close_session(previous, client_id);
+            }
+        }
         Ok(())
     }
 
     pub fn close_connection(&self, client_id: u64) {
         let mut active_connections = self.active_connections.write().unwrap();
-        active_connections.remove(&client_id);
+        if let Some(db_id) = active_connections.remove(&client_id) {
+            self.close_session(db_id, client_id);
+        }
+    }
+
+    /// Releases the cursors the client declared in database `db_id` and forgets
+    /// its prepared statements, settings, last profile and running statement.
+    fn close_session(&self, db_id: u64, client_id: u64) {
+        if let Some(db) = self.id_to_db.read().unwrap().get(&db_id) {
+            db.cursors.close_session(client_id);
+            db.prepared_statements.close_session(client_id);
+            db.session_settings.close_session(client_id);
+            db.session_profiles.close_session(client_id);
+            db.running_queries.close_session(client_id);
+        }
     }
 
     fn get_db_id_from_name(&self, db_name: &str) -> Result<u64, FairyError> {
@@ -197,6 +222,9 @@ impl ServerState {
                 return Ok(*db_id);
             }
         }
-        Err(FairyError::FairyError(String::from("db_name not found!")))
+        Err(FairyError::NotFound(
+            ObjectKind::Database,
+            db_name.to_string(),
+        ))
     }
 }
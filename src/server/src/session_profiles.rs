@@ -0,0 +1,31 @@
+use queryexe::query::planner::QueryProfile;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Operator profile of the last query each session ran, kept while the server
+/// profiles operators so that `\last_profile` can show it.
+#[derive(Default)]
+pub struct SessionProfiles {
+    sessions: RwLock<HashMap<u64, QueryProfile>>,
+}
+
+impl SessionProfiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `profile` as the last one of `session`.
+    pub fn record(&self, session: u64, profile: QueryProfile) {
+        self.sessions.write().unwrap().insert(session, profile);
+    }
+
+    /// Profile of the last query `session` ran, if any was profiled.
+    pub fn last(&self, session: u64) -> Option<QueryProfile> {
+        self.sessions.read().unwrap().get(&session).cloned()
+    }
+
+    /// Forgets the profile of a session that ended.
+    pub fn close_session(&self, session: u64) {
+        self.sessions.write().unwrap().remove(&session);
+    }
+}
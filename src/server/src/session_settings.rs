@@ -0,0 +1,223 @@
+use common::datatypes::DisplayFormat;
+use common::physical::config::ServerConfig;
+use common::FairyError;
+use optimizer::deadline::DEFAULT_PLANNING_TIMEOUT;
+use queryexe::opiterator::CorruptRecordMode;
+use queryexe::stats::stats_view::HypotheticalStats;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Name of the setting that overlays the statistics the planner reads.
+pub const HYPOTHETICAL_STATS: &str = "hypothetical_stats";
+
+/// Settings a session can change with SET or `\set`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// How scans treat records that do not decode against their table's schema.
+    pub corrupt_records: CorruptRecordMode,
+    /// How results render NULL, dates and decimals.
+    pub display: DisplayFormat,
+    /// Time budget for planning a query. Zero disables the budget.
+    pub planning_timeout: Duration,
+    /// Rows a query returns at most, the rest are dropped. Zero disables the
+    /// limit.
+    pub max_result_rows: usize,
+    /// Whether queries whose plan has a cross product are rejected.
+    pub forbid_cross_products: bool,
+    /// Time a query may run before it is cancelled. Zero disables the timeout.
+    pub timeout: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            corrupt_records: CorruptRecordMode::default(),
+            display: DisplayFormat::default(),
+            planning_timeout: DEFAULT_PLANNING_TIMEOUT,
+            max_result_rows: 0,
+            forbid_cross_products: false,
+            timeout: Duration::ZERO,
+        }
+    }
+}
+
+/// Settings of each session. Sessions that never ran SET use the server
+/// defaults.
+#[derive(Default)]
+pub struct SessionSettings {
+    defaults: Settings,
+    sessions: RwLock<HashMap<u64, Settings>>,
+    hypothetical_stats: RwLock<HashMap<u64, Arc<HypotheticalStats>>>,
+}
+
+impl SessionSettings {
+    /// Settings whose sessions render results in the display format and
+    /// time out queries after the query timeout of `config` until they change
+    /// them.
+    pub fn new(config: &ServerConfig) -> Self {
+        SessionSettings {
+            defaults: Settings {
+                display: config.display_format(),
+                timeout: Duration::from_millis(config.query_timeout_ms),
+                ..Settings::default()
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&self, session: u64) -> Settings {
+        self.sessions
+            .read()
+            .unwrap()
+            .get(&session)
+            .cloned()
+            .unwrap_or_else(|| self.defaults.clone())
+    }
+
+    /// Changes setting `name` of `session` to `value`. Names and values are
+    /// case-insensitive.
+    pub fn set(&self, session: u64, name: &str, value: &str) -> Result<(), FairyError> {
+        let mut sessions = self.sessions.write().unwrap();
+        let settings = sessions
+            .entry(session)
+            .or_insert_with(|| self.defaults.clone());
+        match name.to_ascii_lowercase().as_str() {
+            "corrupt_records" => {
+                settings.corrupt_records = match value.to_ascii_lowercase().as_str() {
+                    "strict" => CorruptRecordMode::Strict,
+                    "tolerant" => CorruptRecordMode::Tolerant,
+                    _ => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for corrupt_records, expected strict or tolerant",
+                            value
+                        )))
+                    }
+                }
+            }
+            "null_text" => settings.display.null_text = value.to_string().into(),
+            "date_format" => {
+                DisplayFormat::validate_date_format(value)?;
+                settings.display.date_format = value.to_string().into();
+            }
+            "decimal_separator" => {
+                let mut chars = value.chars();
+                settings.display.decimal_separator = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for decimal_separator, expected a single character",
+                            value
+                        )))
+                    }
+                }
+            }
+            "planning_timeout" => {
+                settings.planning_timeout = match value.parse() {
+                    Ok(millis) => Duration::from_millis(millis),
+                    Err(_) => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for planning_timeout, expected milliseconds or 0 for no limit",
+                            value
+                        )))
+                    }
+                }
+            }
+            "timeout" => {
+                settings.timeout = match value.parse() {
+                    Ok(millis) => Duration::from_millis(millis),
+                    Err(_) => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for timeout, expected milliseconds or 0 for no limit",
+                            value
+                        )))
+                    }
+                }
+            }
+            "max_result_rows" => {
+                settings.max_result_rows = match value.parse() {
+                    Ok(rows) => rows,
+                    Err(_) => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for max_result_rows, expected a number of rows or 0 for no limit",
+                            value
+                        )))
+                    }
+                }
+            }
+            "forbid_cross_products" => {
+                settings.forbid_cross_products = match value.to_ascii_lowercase().as_str() {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => {
+                        return Err(FairyError::ValidationError(format!(
+                            "Invalid value {} for forbid_cross_products, expected on or off",
+                            value
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(FairyError::ValidationError(format!(
+                    "Unknown setting {}",
+                    name
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Statistics the planner of `session` reads in place of the real ones, if
+    /// the session set any.
+    pub fn hypothetical_stats(&self, session: u64) -> Option<Arc<HypotheticalStats>> {
+        self.hypothetical_stats
+            .read()
+            .unwrap()
+            .get(&session)
+            .cloned()
+    }
+
+    /// Overlays the statistics the planner of `session` reads, or goes back to
+    /// the real statistics if `stats` is None.
+    pub fn set_hypothetical_stats(&self, session: u64, stats: Option<HypotheticalStats>) {
+        let mut sessions = self.hypothetical_stats.write().unwrap();
+        match stats {
+            Some(stats) => sessions.insert(session, Arc::new(stats)),
+            None => sessions.remove(&session),
+        };
+    }
+
+    /// Value of setting `name` of `session` as SHOW prints it, or None if there is
+    /// no such session setting.
+    pub fn show(&self, session: u64, name: &str) -> Option<String> {
+        let settings = self.get(session);
+        match name.to_ascii_lowercase().as_str() {
+            "corrupt_records" => Some(settings.corrupt_records.to_string()),
+            "null_text" => Some(settings.display.null_text.into_owned()),
+            "date_format" => Some(settings.display.date_format.into_owned()),
+            "decimal_separator" => Some(settings.display.decimal_separator.to_string()),
+            "planning_timeout" => Some(settings.planning_timeout.as_millis().to_string()),
+            "timeout" => Some(settings.timeout.as_millis().to_string()),
+            "max_result_rows" => Some(settings.max_result_rows.to_string()),
+            "forbid_cross_products" => Some(
+                if settings.forbid_cross_products {
+                    "on"
+                } else {
+                    "off"
+                }
+                .to_string(),
+            ),
+            HYPOTHETICAL_STATS => Some(
+                self.hypothetical_stats(session)
+                    .map_or("none".to_string(), |stats| stats.text().to_string()),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Forgets the settings of a session that ended.
+    pub fn close_session(&self, session: u64) {
+        self.sessions.write().unwrap().remove(&session);
+        self.hypothetical_stats.write().unwrap().remove(&session);
+    }
+}
@@ -0,0 +1,101 @@
+use common::ids::ContainerId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Tracks which tables each session wrote to, so that a session always sees its
+/// own writes even when a shortcut such as the registered query result cache
+/// could answer a statement.
+///
+/// Writes and cache fills are ordered by a per-database generation counter rather
+/// than by transaction id: every client keeps its transaction id for a whole
+/// command, so another session can fill the cache with a newer id than the write
+/// it is supposed to cover.
+#[derive(Default)]
+pub struct SessionWrites {
+    generation: AtomicU64,
+    /// Generation of the last write of each session, per table.
+    sessions: RwLock<HashMap<u64, HashMap<ContainerId, u64>>>,
+    /// Generation at which each cached query result was computed.
+    cached: RwLock<HashMap<String, u64>>,
+}
+
+impl SessionWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current generation. A result computed after this call
+    /// reflects every write recorded before it.
+    pub fn current(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Records a write by `session` to table `c_id`.
+    pub fn record_write(&self, session: u64, c_id: ContainerId) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.sessions
+            .write()
+            .unwrap()
+            .entry(session)
+            .or_default()
+            .insert(c_id, generation);
+    }
+
+    /// Records that the result of `query_name` was computed as of `generation`.
+    pub fn record_cached(&self, query_name: &str, generation: u64) {
+        self.cached
+            .write()
+            .unwrap()
+            .insert(query_name.to_string(), generation);
+    }
+
+    /// Returns true if `session` wrote to any of `tables` after the cached result
+    /// of `query_name` was computed, in which case the result must not be served
+    /// to it.
+    pub fn wrote_since_cached(
+        &self,
+        session: u64,
+        tables: &[ContainerId],
+        query_name: &str,
+    ) -> bool {
+        let cached = self
+            .cached
+            .read()
+            .unwrap()
+            .get(query_name)
+            .copied()
+            .unwrap_or(0);
+        let sessions = self.sessions.read().unwrap();
+        let writes = match sessions.get(&session) {
+            Some(writes) => writes,
+            None => return false,
+        };
+        tables
+            .iter()
+            .any(|c_id| writes.get(c_id).is_some_and(|&g| g > cached))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrote_since_cached() {
+        let writes = SessionWrites::new();
+        writes.record_write(1, 7);
+        writes.record_cached("q", writes.current());
+        assert!(!writes.wrote_since_cached(1, &[7], "q"));
+
+        writes.record_write(2, 7);
+        writes.record_write(1, 8);
+        // Only the session's own writes to the tables the query reads count.
+        assert!(!writes.wrote_since_cached(1, &[7], "q"));
+        assert!(writes.wrote_since_cached(2, &[7], "q"));
+        assert!(writes.wrote_since_cached(1, &[7, 8], "q"));
+        assert!(!writes.wrote_since_cached(3, &[7, 8], "q"));
+        // A result that was never recorded predates every write.
+        assert!(writes.wrote_since_cached(1, &[7], "other"));
+    }
+}
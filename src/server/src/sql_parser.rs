@@ -1,9 +1,15 @@
+use sqlparser::dialect::GenericDialect;
+use sqlparser::keywords::Keyword;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer};
 
 use sqlparser::ast::TableConstraint;
-use sqlparser::ast::{ColumnDef, ColumnOption, Ident, Statement};
+use sqlparser::ast::{ColumnDef, ColumnOption, CopySource, Ident, ObjectName, Statement};
 use sqlparser::parser::ParserError;
 
+use common::error::SourcePosition;
+use common::FairyError;
+
 pub struct SQLParser {}
 
 #[allow(clippy::upper_case_acronyms)]
@@ -14,6 +20,48 @@ pub enum ParserResponse {
     SQLError(ParserError),
     SQL(Vec<Statement>),
     SQLConstraintError(String),
+    AlterColumnOptions(AlterColumnOptions),
+    CopyTo(CopyTo),
+    CopyFrom(CopyFrom),
+    Analyze(Analyze),
+}
+
+/// `ALTER TABLE t ALTER [COLUMN] c SET (name = value, ...)`, which sqlparser
+/// does not support.
+#[derive(Debug)]
+pub struct AlterColumnOptions {
+    pub table: ObjectName,
+    pub column: Ident,
+    pub options: Vec<(Ident, Ident)>,
+}
+
+/// `COPY { table | (query) } TO 'file' [WITH] [(name [value], ...)]`. sqlparser
+/// only accepts the options of PostgreSQL, which do not include SNAPSHOT.
+#[derive(Debug)]
+pub struct CopyTo {
+    pub source: CopySource,
+    pub filename: String,
+    /// Names and values of the options. Options given without a value are on.
+    pub options: Vec<(Ident, Ident)>,
+}
+
+/// `COPY table FROM 'file' [WITH] [(name [value], ...)]`, which imports a CSV
+/// file on the server like `\i`.
+#[derive(Debug)]
+pub struct CopyFrom {
+    pub table: ObjectName,
+    pub filename: String,
+    /// Names and values of the options. Options given without a value are on.
+    pub options: Vec<(Ident, Ident)>,
+}
+
+/// `ANALYZE [TABLE] t [(a, b, ...)]`. With columns, it declares a group of
+/// columns whose joint distribution the statistics keep. The declared groups
+/// of the table are then computed from all its rows.
+#[derive(Debug)]
+pub struct Analyze {
+    pub table: ObjectName,
+    pub columns: Vec<Ident>,
 }
 
 impl Default for SQLParser {
@@ -29,6 +77,21 @@ impl SQLParser {
 
     /// Validates sql string, first if it is sql itself, then if it has a primary key
     pub fn parse_sql(sql: String) -> ParserResponse {
+        match SQLParser::parse_alter_column_options(&sql) {
+            Some(Ok(alter)) => return ParserResponse::AlterColumnOptions(alter),
+            Some(Err(e)) => return ParserResponse::SQLError(e),
+            None => {}
+        }
+        match SQLParser::parse_copy(&sql) {
+            Some(Ok(copy)) => return copy,
+            Some(Err(e)) => return ParserResponse::SQLError(e),
+            None => {}
+        }
+        match SQLParser::parse_analyze(&sql) {
+            Some(Ok(analyze)) => return ParserResponse::Analyze(analyze),
+            Some(Err(e)) => return ParserResponse::SQLError(e),
+            None => {}
+        }
         // Allows for multiple checks and different errors for each fail
         let request = SQLParser::validate_sql(sql);
         match request {
@@ -63,13 +126,326 @@ impl SQLParser {
 
     /// Returns Request::SQL if given string is valid sql, else returns Request::SQLError
     fn validate_sql(sql: String) -> ParserResponse {
-        let dialect = sqlparser::dialect::GenericDialect {};
-        match Parser::parse_sql(&dialect, &sql) {
+        // The generic dialect does not support COMMENT ON, so those statements
+        // are handed to the PostgreSQL dialect which does.
+        let parsed = if SQLParser::is_comment_statement(&sql) {
+            Parser::parse_sql(&sqlparser::dialect::PostgreSqlDialect {}, &sql)
+        } else {
+            SQLParser::parse_generic(&sql)
+        };
+        match parsed {
             Ok(a) => ParserResponse::SQL(a),
             Err(e) => ParserResponse::SQLError(e),
         }
     }
 
+    /// Parses `sql` if it sets options of a column. Returns None for other
+    /// statements, including other forms of ALTER TABLE.
+    fn parse_alter_column_options(sql: &str) -> Option<Result<AlterColumnOptions, ParserError>> {
+        let dialect = GenericDialect {};
+        let mut parser = Parser::new(&dialect).try_with_sql(sql).ok()?;
+        if !parser.parse_keywords(&[Keyword::ALTER, Keyword::TABLE]) {
+            return None;
+        }
+        let table = parser.parse_object_name().ok()?;
+        if !parser.parse_keyword(Keyword::ALTER) {
+            return None;
+        }
+        let _ = parser.parse_keyword(Keyword::COLUMN);
+        let column = parser.parse_identifier().ok()?;
+        if !parser.parse_keyword(Keyword::SET) || !parser.consume_token(&Token::LParen) {
+            return None;
+        }
+        let parse_options = || {
+            let options = parser.parse_comma_separated(|p| {
+                let name = p.parse_identifier()?;
+                p.expect_token(&Token::Eq)?;
+                Ok((name, p.parse_identifier()?))
+            })?;
+            parser.expect_token(&Token::RParen)?;
+            let _ = parser.consume_token(&Token::SemiColon);
+            parser.expect_token(&Token::EOF)?;
+            Ok(AlterColumnOptions {
+                table,
+                column,
+                options,
+            })
+        };
+        Some(parse_options())
+    }
+
+    /// Parses `sql` if it is a COPY statement, into a `CopyTo` or a `CopyFrom`.
+    fn parse_copy(sql: &str) -> Option<Result<ParserResponse, ParserError>> {
+        let dialect = GenericDialect {};
+        let mut parser = Parser::new(&dialect).try_with_sql(sql).ok()?;
+        if !parser.parse_keyword(Keyword::COPY) {
+            return None;
+        }
+        let mut parse_copy = || {
+            let source = if parser.consume_token(&Token::LParen) {
+                let query = parser.parse_query()?;
+                parser.expect_token(&Token::RParen)?;
+                CopySource::Query(Box::new(query))
+            } else {
+                CopySource::Table {
+                    table_name: parser.parse_object_name()?,
+                    columns: vec![],
+                }
+            };
+            let from = parser.parse_keyword(Keyword::FROM);
+            if !from {
+                parser.expect_keyword(Keyword::TO)?;
+            }
+            let filename = parser.parse_literal_string()?;
+            let with = parser.parse_keyword(Keyword::WITH);
+            let mut options = vec![];
+            if parser.consume_token(&Token::LParen) {
+                options = parser.parse_comma_separated(|p| {
+                    let name = p.parse_identifier()?;
+                    let _ = p.consume_token(&Token::Eq);
+                    let value = match p.peek_token().token {
+                        Token::Comma | Token::RParen => Ident::new("true"),
+                        _ => p.parse_identifier()?,
+                    };
+                    Ok((name, value))
+                })?;
+                parser.expect_token(&Token::RParen)?;
+            } else if with {
+                parser.expect_token(&Token::LParen)?;
+            }
+            let _ = parser.consume_token(&Token::SemiColon);
+            parser.expect_token(&Token::EOF)?;
+            match (source, from) {
+                (CopySource::Table { table_name, .. }, true) => {
+                    Ok(ParserResponse::CopyFrom(CopyFrom {
+                        table: table_name,
+                        filename,
+                        options,
+                    }))
+                }
+                (CopySource::Query(_), true) => Err(ParserError::ParserError(
+                    "COPY FROM imports into a table, not a query".to_string(),
+                )),
+                (source, false) => Ok(ParserResponse::CopyTo(CopyTo {
+                    source,
+                    filename,
+                    options,
+                })),
+            }
+        };
+        Some(parse_copy())
+    }
+
+    /// Parses `sql` if it is an ANALYZE statement. sqlparser only accepts the
+    /// form of Hive, which names no column group.
+    fn parse_analyze(sql: &str) -> Option<Result<Analyze, ParserError>> {
+        let dialect = GenericDialect {};
+        let mut parser = Parser::new(&dialect).try_with_sql(sql).ok()?;
+        if !parser.parse_keyword(Keyword::ANALYZE) {
+            return None;
+        }
+        let mut parse_analyze = || {
+            let _ = parser.parse_keyword(Keyword::TABLE);
+            let table = parser.parse_object_name()?;
+            let mut columns = vec![];
+            if parser.consume_token(&Token::LParen) {
+                columns = parser.parse_comma_separated(Parser::parse_identifier)?;
+                parser.expect_token(&Token::RParen)?;
+            }
+            let _ = parser.consume_token(&Token::SemiColon);
+            parser.expect_token(&Token::EOF)?;
+            Ok(Analyze { table, columns })
+        };
+        Some(parse_analyze())
+    }
+
+    /// Reads an on/off option value, accepting on, off, true and false.
+    pub fn parse_switch(value: &str) -> Option<bool> {
+        match value.to_ascii_lowercase().as_str() {
+            "on" | "true" => Some(true),
+            "off" | "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Parses `sql` with the generic dialect, which only accepts a subquery as
+    /// the operand of ANY and ALL if it has its own parentheses, as in
+    /// `a = ANY((SELECT ...))`. These are added for the standard form.
+    fn parse_generic(sql: &str) -> Result<Vec<Statement>, ParserError> {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, sql).tokenize_with_location()?;
+        Parser::new(&dialect)
+            .with_tokens_with_locations(SQLParser::quote_on_values(
+                SQLParser::wrap_quantified_subqueries(tokens),
+            ))
+            .parse_statements()
+    }
+
+    /// Quotes `on` where it is the value of an option, as in `WITH (txn_stamps
+    /// = on)`, which sqlparser would read as a keyword. The keyword cannot
+    /// follow `=` anywhere else.
+    fn quote_on_values(mut tokens: Vec<TokenWithLocation>) -> Vec<TokenWithLocation> {
+        let mut after_eq = false;
+        for t in tokens.iter_mut() {
+            if matches!(t.token, Token::Whitespace(_)) {
+                continue;
+            }
+            if let Token::Word(w) = &t.token {
+                if after_eq && w.keyword == Keyword::ON {
+                    t.token = Token::SingleQuotedString(w.value.clone());
+                }
+            }
+            after_eq = t.token == Token::Eq;
+        }
+        tokens
+    }
+
+    /// Wraps the subqueries of `ANY (SELECT ...)` and `ALL (SELECT ...)` in an
+    /// extra pair of parentheses.
+    fn wrap_quantified_subqueries(tokens: Vec<TokenWithLocation>) -> Vec<TokenWithLocation> {
+        let is_quantifier = |t: &Token| matches!(t, Token::Word(w) if matches!(w.keyword, Keyword::ANY | Keyword::ALL));
+        let is_query = |t: &Token| matches!(t, Token::Word(w) if matches!(w.keyword, Keyword::SELECT | Keyword::WITH));
+        let significant: Vec<usize> = (0..tokens.len())
+            .filter(|i| !matches!(tokens[*i].token, Token::Whitespace(_)))
+            .collect();
+        // Indexes of the opening parentheses to wrap.
+        let mut wrapped = Vec::new();
+        for w in significant.windows(3) {
+            if is_quantifier(&tokens[w[0]].token)
+                && tokens[w[1]].token == Token::LParen
+                && is_query(&tokens[w[2]].token)
+            {
+                wrapped.push(w[1]);
+            }
+        }
+        if wrapped.is_empty() {
+            return tokens;
+        }
+        let mut result = Vec::with_capacity(tokens.len() + 2 * wrapped.len());
+        // Depths at which a wrapping parenthesis is still to be closed.
+        let mut open = Vec::new();
+        let mut depth = 0;
+        for (i, t) in tokens.into_iter().enumerate() {
+            match t.token {
+                Token::LParen => {
+                    depth += 1;
+                    if wrapped.contains(&i) {
+                        open.push(depth);
+                        result.push(t.clone());
+                    }
+                }
+                Token::RParen => {
+                    if open.last() == Some(&depth) {
+                        open.pop();
+                        result.push(t.clone());
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            result.push(t);
+        }
+        result
+    }
+
+    /// Fails on the first string literal of `sql` larger than `limit` bytes,
+    /// pointing at it. Text that does not tokenize is left for the parser to
+    /// report.
+    pub fn check_literal_sizes(sql: &str, limit: usize) -> Result<(), FairyError> {
+        // No literal can be larger than the text holding it.
+        if sql.len() <= limit {
+            return Ok(());
+        }
+        let Ok(tokens) = Tokenizer::new(&GenericDialect {}, sql).tokenize_with_location() else {
+            return Ok(());
+        };
+        for t in tokens {
+            let size = match &t.token {
+                Token::SingleQuotedString(s)
+                | Token::DoubleQuotedString(s)
+                | Token::SingleQuotedByteStringLiteral(s)
+                | Token::DoubleQuotedByteStringLiteral(s)
+                | Token::RawStringLiteral(s)
+                | Token::NationalStringLiteral(s)
+                | Token::EscapedStringLiteral(s)
+                | Token::HexStringLiteral(s) => s.len(),
+                Token::DollarQuotedString(s) => s.value.len(),
+                _ => continue,
+            };
+            if size > limit {
+                let position = SourcePosition::from_line_column(
+                    sql,
+                    t.location.line as usize,
+                    t.location.column as usize,
+                );
+                let error = FairyError::FieldTooLarge {
+                    size,
+                    limit,
+                    line: None,
+                };
+                return Err(error.at(position));
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts an error parsing `sql` into a syntax error positioned at the
+    /// token it was reported for. sqlparser only reports the position as a
+    /// suffix of the message, and not at all when the statement ended early,
+    /// in which case the end of the statement is the offending position.
+    pub fn syntax_error(sql: &str, e: &ParserError) -> FairyError {
+        let message = e.to_string();
+        if let Some((message, location)) = message.rsplit_once(" at Line: ") {
+            let position = location.split_once(", Column ").and_then(|(line, column)| {
+                Some(SourcePosition::from_line_column(
+                    sql,
+                    line.parse().ok()?,
+                    column.parse().ok()?,
+                ))
+            });
+            if let Some(position) = position {
+                return FairyError::SyntaxError(message.to_string()).at(position);
+            }
+        }
+        let error = FairyError::SyntaxError(message.clone());
+        if message.ends_with("found: EOF") {
+            error.at(SourcePosition::from_offset(sql, sql.trim_end().len()))
+        } else {
+            error
+        }
+    }
+
+    /// Returns the byte offset of the first mention of identifier `name` in
+    /// `sql` outside of string literals, ignoring case.
+    pub fn find_identifier(sql: &str, name: &str) -> Option<usize> {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let mut in_string = false;
+        let mut prev = None;
+        for (i, c) in sql.char_indices() {
+            if c == '\'' {
+                in_string = !in_string;
+            } else if !in_string
+                && !prev.is_some_and(is_ident)
+                && sql[i..]
+                    .get(..name.len())
+                    .is_some_and(|s| s.eq_ignore_ascii_case(name))
+                && !sql[i + name.len()..].starts_with(is_ident)
+            {
+                return Some(i);
+            }
+            prev = Some(c);
+        }
+        None
+    }
+
+    /// Returns true if the sql string starts with the COMMENT keyword
+    fn is_comment_statement(sql: &str) -> bool {
+        let mut words = sql.split_whitespace();
+        words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("comment"))
+    }
+
     /// Returns a vector of the Idents of tables that are primary keys if valid
     /// Returns an error (as request) if there is a problem
     ///
@@ -199,6 +575,7 @@ impl SQLParser {
 #[cfg(test)]
 mod libtests {
     use super::*;
+    use common::keywords::is_reserved_keyword;
     /*
     #[test]
     fn test_validate_non_createtable_sql() {
@@ -249,6 +626,243 @@ mod libtests {
     }
     */
 
+    #[test]
+    fn test_syntax_error_position() {
+        let sql = "SELECT a\nFROM t WHERE a = = 1";
+        let e = match SQLParser::parse_sql(sql.to_string()) {
+            ParserResponse::SQLError(e) => SQLParser::syntax_error(sql, &e),
+            r => panic!("Expected a parse error, got {:?}", r),
+        };
+        let position = e.position().unwrap();
+        assert_eq!((position.line, position.column), (2, 18));
+        assert_eq!(&sql[position.offset..], "= 1");
+        assert!(!e.to_string().contains("Line:"));
+
+        // Statements that end early are positioned at their end.
+        let sql = "SELECT a FROM  ";
+        let e = match SQLParser::parse_sql(sql.to_string()) {
+            ParserResponse::SQLError(e) => SQLParser::syntax_error(sql, &e),
+            r => panic!("Expected a parse error, got {:?}", r),
+        };
+        assert_eq!(e.position().unwrap().offset, "SELECT a FROM".len());
+    }
+
+    #[test]
+    fn test_find_identifier() {
+        let sql = "SELECT 'ab', xab, ab FROM t WHERE t.AB = 1";
+        assert_eq!(
+            SQLParser::find_identifier(sql, "ab"),
+            sql.find(", ab").map(|i| i + 2)
+        );
+        assert_eq!(SQLParser::find_identifier(sql, "t.ab"), sql.find("t.AB"));
+        assert_eq!(SQLParser::find_identifier(sql, "missing"), None);
+    }
+
+    #[test]
+    fn test_parse_quantified_subquery() {
+        let sql = "SELECT a FROM t WHERE a > ALL (SELECT b FROM u WHERE b = ANY(SELECT c FROM v)) AND a = ANY((SELECT d FROM w))";
+        let ast = match SQLParser::parse_sql(sql.to_string()) {
+            ParserResponse::SQL(ast) => ast,
+            r => panic!("Expected SQL, got {:?}", r),
+        };
+        assert_eq!(
+            ast.first().unwrap().to_string(),
+            "SELECT a FROM t WHERE a > ALL((SELECT b FROM u WHERE b = ANY((SELECT c FROM v)))) AND a = ANY((SELECT d FROM w))"
+        );
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        let sql = String::from("COMMENT ON TABLE foo IS 'hello'");
+        let ast = match SQLParser::parse_sql(sql) {
+            ParserResponse::SQL(ast) => ast,
+            r => panic!("Expected SQL, got {:?}", r),
+        };
+        assert!(matches!(
+            ast.first().unwrap(),
+            Statement::Comment { comment: Some(c), .. } if c == "hello"
+        ));
+
+        let sql = String::from("comment on column foo.a is null");
+        let ast = match SQLParser::parse_sql(sql) {
+            ParserResponse::SQL(ast) => ast,
+            r => panic!("Expected SQL, got {:?}", r),
+        };
+        assert!(matches!(
+            ast.first().unwrap(),
+            Statement::Comment { comment: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_alter_column_options() {
+        let sql = String::from("ALTER TABLE foo ALTER COLUMN b SET (encoding = dictionary);");
+        let alter = match SQLParser::parse_sql(sql) {
+            ParserResponse::AlterColumnOptions(alter) => alter,
+            r => panic!("Expected column options, got {:?}", r),
+        };
+        assert_eq!(alter.table.to_string(), "foo");
+        assert_eq!(alter.column.value, "b");
+        assert_eq!(alter.options.len(), 1);
+        assert_eq!(alter.options[0].0.value, "encoding");
+        assert_eq!(alter.options[0].1.value, "dictionary");
+
+        let sql = String::from("alter table foo alter b set (encoding = 'plain', x = y)");
+        assert!(matches!(
+            SQLParser::parse_sql(sql),
+            ParserResponse::AlterColumnOptions(alter) if alter.options.len() == 2
+        ));
+        let sql = String::from("ALTER TABLE foo ALTER COLUMN b SET (encoding dictionary)");
+        assert!(matches!(
+            SQLParser::parse_sql(sql),
+            ParserResponse::SQLError(_)
+        ));
+        // Other forms are left to sqlparser.
+        let sql = String::from("ALTER TABLE foo ALTER COLUMN b SET NOT NULL");
+        assert!(matches!(SQLParser::parse_sql(sql), ParserResponse::SQL(_)));
+    }
+
+    #[test]
+    fn test_parse_copy_to() {
+        let sql = String::from("COPY (SELECT * FROM foo) TO '/tmp/foo.csv' WITH (SNAPSHOT true);");
+        let copy = match SQLParser::parse_sql(sql) {
+            ParserResponse::CopyTo(copy) => copy,
+            r => panic!("Expected COPY, got {:?}", r),
+        };
+        assert!(matches!(copy.source, CopySource::Query(_)));
+        assert_eq!(copy.filename, "/tmp/foo.csv");
+        assert_eq!(copy.options.len(), 1);
+        assert_eq!(copy.options[0].0.value, "SNAPSHOT");
+        assert_eq!(copy.options[0].1.value, "true");
+
+        let sql = String::from("copy foo to 'foo.csv' (format csv, header, snapshot = on)");
+        let copy = match SQLParser::parse_sql(sql) {
+            ParserResponse::CopyTo(copy) => copy,
+            r => panic!("Expected COPY, got {:?}", r),
+        };
+        assert!(matches!(copy.source, CopySource::Table { .. }));
+        let values: Vec<&str> = copy.options.iter().map(|(_, v)| v.value.as_str()).collect();
+        assert_eq!(values, ["csv", "true", "on"]);
+
+        let sql = String::from("COPY foo FROM '/tmp/foo.csv' WITH (utf8 lossy, header)");
+        let copy = match SQLParser::parse_sql(sql) {
+            ParserResponse::CopyFrom(copy) => copy,
+            r => panic!("Expected COPY FROM, got {:?}", r),
+        };
+        assert_eq!(copy.table.to_string(), "foo");
+        assert_eq!(copy.filename, "/tmp/foo.csv");
+        let options: Vec<(&str, &str)> = copy
+            .options
+            .iter()
+            .map(|(n, v)| (n.value.as_str(), v.value.as_str()))
+            .collect();
+        assert_eq!(options, [("utf8", "lossy"), ("header", "true")]);
+
+        for sql in [
+            "COPY (SELECT * FROM foo) FROM 'foo.csv'",
+            "COPY foo TO 'foo.csv' WITH",
+            "COPY foo TO 'foo.csv' (snapshot true",
+            "COPY (SELECT * FROM foo TO 'foo.csv'",
+        ] {
+            assert!(
+                matches!(
+                    SQLParser::parse_sql(sql.to_string()),
+                    ParserResponse::SQLError(_)
+                ),
+                "{}",
+                sql
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_analyze() {
+        let analyze = match SQLParser::parse_sql("ANALYZE t (city, state);".to_string()) {
+            ParserResponse::Analyze(analyze) => analyze,
+            r => panic!("Expected ANALYZE, got {:?}", r),
+        };
+        assert_eq!(analyze.table.to_string(), "t");
+        let columns: Vec<&str> = analyze.columns.iter().map(|c| c.value.as_str()).collect();
+        assert_eq!(columns, ["city", "state"]);
+
+        match SQLParser::parse_sql("analyze table t".to_string()) {
+            ParserResponse::Analyze(analyze) => assert!(analyze.columns.is_empty()),
+            r => panic!("Expected ANALYZE, got {:?}", r),
+        }
+        for sql in ["ANALYZE t (city", "ANALYZE TABLE t COMPUTE STATISTICS"] {
+            assert!(
+                matches!(
+                    SQLParser::parse_sql(sql.to_string()),
+                    ParserResponse::SQLError(_)
+                ),
+                "{}",
+                sql
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_on_option_value() {
+        let sql = String::from("CREATE TABLE foo (a INT PRIMARY KEY) WITH (txn_stamps = on)");
+        match SQLParser::parse_sql(sql) {
+            ParserResponse::SQL(ast) => match &ast[0] {
+                Statement::CreateTable { with_options, .. } => {
+                    assert_eq!(with_options[0].to_string(), "txn_stamps = 'on'")
+                }
+                s => panic!("Expected CREATE TABLE, got {:?}", s),
+            },
+            r => panic!("Expected SQL, got {:?}", r),
+        }
+        assert_eq!(SQLParser::parse_switch("OFF"), Some(false));
+        assert_eq!(SQLParser::parse_switch("True"), Some(true));
+        assert_eq!(SQLParser::parse_switch("yes"), None);
+    }
+
+    #[test]
+    fn test_check_literal_sizes() {
+        let sql = "SELECT * FROM t\nWHERE a = 'abc' OR b = 'abcdef'";
+        assert!(SQLParser::check_literal_sizes(sql, 6).is_ok());
+        let e = SQLParser::check_literal_sizes(sql, 5).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Value of 6 bytes exceeds max_field_size of 5 bytes"
+        );
+        let position = e.position().unwrap();
+        assert_eq!((position.line, position.column), (2, 24));
+        // Text that does not tokenize is left for the parser.
+        assert!(SQLParser::check_literal_sizes("SELECT 'abcdef", 5).is_ok());
+    }
+
+    #[test]
+    fn test_reserved_keywords_cover_parser() {
+        // Every keyword that does not survive as an unquoted table or column
+        // name must be rejected at CREATE TABLE.
+        for keyword in sqlparser::keywords::ALL_KEYWORDS {
+            let name = keyword.to_lowercase();
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                continue;
+            }
+            let statements = [
+                format!("SELECT a FROM {} WHERE a = 1", name),
+                format!("SELECT t.a FROM {} JOIN t ON {}.a = t.a", name, name),
+                format!("SELECT a, {} FROM t WHERE {} = 1", name, name),
+                format!("SELECT a FROM t GROUP BY {} ORDER BY {}", name, name),
+                format!("INSERT INTO {} VALUES (1)", name),
+            ];
+            let unusable = statements.iter().any(|sql| {
+                match Parser::parse_sql(&sqlparser::dialect::GenericDialect {}, sql) {
+                    Ok(ast) => !ast[0].to_string().eq_ignore_ascii_case(sql),
+                    Err(_) => true,
+                }
+            });
+            assert!(
+                !unusable || is_reserved_keyword(&name),
+                "{} must be quoted",
+                name
+            );
+        }
+    }
+
     #[test]
     fn test_get_pks() {
         // fail cases
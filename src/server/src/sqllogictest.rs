@@ -0,0 +1,566 @@
+//! Runner for SQL logic test (`.slt`) scripts.
+//!
+//! The format follows sqllogictest: a script is a sequence of records separated
+//! by blank lines, `#` starts a comment.
+//!
+//! ```text
+//! statement ok
+//! CREATE TABLE t (a INT PRIMARY KEY, b INT)
+//!
+//! statement error does not exist
+//! SELECT * FROM missing
+//!
+//! query II rowsort
+//! SELECT a, b FROM t
+//! ----
+//! 1 10
+//! 2 20
+//!
+//! query I nosort
+//! SELECT a FROM t ORDER BY a
+//! ----
+//! 2 values hashing to 6ddb4095eb719e2a9f0a3f95677d24e0
+//! ```
+//!
+//! The text after `statement error` is optional and must be a substring of the
+//! error message. Query column types are `I` (integer), `R` (real, printed with
+//! three decimals) and `T` (text); NULL is printed as `NULL` and an empty
+//! string as `(empty)`. Results are compared in the order given by the sort
+//! mode: `nosort` (default), `rowsort` or `valuesort`. Expected results are
+//! either one row per line with values separated by spaces, one value per line,
+//! or `N values hashing to H` where H is the MD5 of every value followed by a
+//! newline. `skipif fairydb` and `onlyif <engine>` conditions, `hash-threshold`
+//! and `halt` are understood as well.
+
+use crate::QueryEngine;
+use common::{FairyError, Field, QueryResult};
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+/// Engine name matched by `skipif` and `onlyif` conditions.
+pub const ENGINE_NAME: &str = "fairydb";
+
+/// How query results are ordered before they are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    NoSort,
+    RowSort,
+    ValueSort,
+}
+
+/// Expected result of a query record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// Result lines as written in the script.
+    Lines(Vec<String>),
+    /// Number of values and MD5 hash of the result.
+    Hash { values: usize, hash: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordKind {
+    /// `statement ok` has no expected error, `statement error` expects an error
+    /// whose message contains the given text, which may be empty.
+    Statement {
+        sql: String,
+        error: Option<String>,
+    },
+    Query {
+        sql: String,
+        types: Vec<char>,
+        sort: SortMode,
+        expected: Expected,
+    },
+    HashThreshold(usize),
+    Halt,
+}
+
+/// A record of a script with the line on which it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub line: usize,
+    pub kind: RecordKind,
+}
+
+/// A record whose outcome did not match the script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SltFailure {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SltFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn parse_error(line: usize, msg: &str) -> FairyError {
+    FairyError::ValidationError(format!("line {}: {}", line, msg))
+}
+
+/// Parses a script into records, skipping the ones excluded by `skipif` or
+/// `onlyif` conditions.
+pub fn parse_script(script: &str) -> Result<Vec<Record>, FairyError> {
+    let lines: Vec<&str> = script.lines().collect();
+    let mut records = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let header = lines[i].trim();
+        if header.is_empty() || header.starts_with('#') {
+            i += 1;
+            continue;
+        }
+        let mut skip = false;
+        let mut i_header = i;
+        let mut words: Vec<&str>;
+        loop {
+            words = lines[i_header].split_whitespace().collect();
+            match words.as_slice() {
+                ["skipif", engine, ..] => skip |= *engine == ENGINE_NAME,
+                ["onlyif", engine, ..] => skip |= *engine != ENGINE_NAME,
+                _ => break,
+            }
+            i_header += 1;
+            if i_header == lines.len() {
+                return Err(parse_error(i_header, "condition without a record"));
+            }
+        }
+        let line = i_header + 1;
+
+        // The body runs until the next blank line.
+        let mut end = i_header + 1;
+        while end < lines.len() && !lines[end].trim().is_empty() {
+            end += 1;
+        }
+        let body = &lines[i_header + 1..end];
+        i = end;
+
+        let kind = match words.as_slice() {
+            ["statement", "ok"] => RecordKind::Statement {
+                sql: body.join("\n"),
+                error: None,
+            },
+            ["statement", "error", ..] => {
+                let header = lines[i_header].trim();
+                let message = header["statement error".len()..].trim().to_string();
+                RecordKind::Statement {
+                    sql: body.join("\n"),
+                    error: Some(message),
+                }
+            }
+            ["query", types, options @ ..] => parse_query(line, types, options, body)?,
+            ["hash-threshold", n] => RecordKind::HashThreshold(
+                n.parse()
+                    .map_err(|_| parse_error(line, "invalid hash-threshold"))?,
+            ),
+            ["halt"] => RecordKind::Halt,
+            _ => {
+                return Err(parse_error(
+                    line,
+                    &format!("unknown record '{}'", lines[i_header].trim()),
+                ))
+            }
+        };
+        if let RecordKind::Statement { sql, .. } | RecordKind::Query { sql, .. } = &kind {
+            if sql.trim().is_empty() {
+                return Err(parse_error(line, "record without SQL"));
+            }
+        }
+        if !skip {
+            records.push(Record { line, kind });
+        }
+    }
+    Ok(records)
+}
+
+fn parse_query(
+    line: usize,
+    types: &str,
+    options: &[&str],
+    body: &[&str],
+) -> Result<RecordKind, FairyError> {
+    let types: Vec<char> = types.chars().collect();
+    if let Some(t) = types.iter().find(|t| !matches!(t, 'I' | 'R' | 'T')) {
+        return Err(parse_error(line, &format!("unknown column type '{}'", t)));
+    }
+    let sort = match options.first() {
+        None | Some(&"nosort") => SortMode::NoSort,
+        Some(&"rowsort") => SortMode::RowSort,
+        Some(&"valuesort") => SortMode::ValueSort,
+        // Anything else is a label, which is only used to compare results
+        // across queries.
+        Some(_) => SortMode::NoSort,
+    };
+    let separator = body.iter().position(|l| l.trim() == "----");
+    let (sql, results) = match separator {
+        Some(pos) => (&body[..pos], &body[pos + 1..]),
+        None => (body, &[][..]),
+    };
+    let expected = match results {
+        [result] if result.contains("values hashing to") => {
+            let words: Vec<&str> = result.split_whitespace().collect();
+            match words.as_slice() {
+                [values, "values", "hashing", "to", hash] => Expected::Hash {
+                    values: values
+                        .parse()
+                        .map_err(|_| parse_error(line, "invalid value count"))?,
+                    hash: hash.to_lowercase(),
+                },
+                _ => return Err(parse_error(line, "invalid hash result")),
+            }
+        }
+        _ => Expected::Lines(results.iter().map(|l| l.trim().to_string()).collect()),
+    };
+    Ok(RecordKind::Query {
+        sql: sql.join("\n"),
+        types,
+        sort,
+        expected,
+    })
+}
+
+/// Renders a field the way sqllogictest expects for the column type.
+pub fn format_field(field: &Field, column_type: char) -> String {
+    let int = match field {
+        Field::Bool(b) => Some(*b as i64 as f64),
+        _ => field.to_f64(),
+    };
+    match (field, column_type, int) {
+        (Field::Null, _, _) => "NULL".to_string(),
+        (_, 'I', Some(v)) => (v.trunc() as i64).to_string(),
+        (_, 'R', Some(v)) => format!("{:.3}", v),
+        _ => {
+            let s = field.to_string();
+            if s.is_empty() {
+                "(empty)".to_string()
+            } else {
+                s
+            }
+        }
+    }
+}
+
+/// Runs every record of `script` against `engine` and returns the number of
+/// records that were run, or every record that failed. A failed statement does
+/// not stop the script.
+pub fn run_script(engine: &mut QueryEngine, script: &str) -> Result<usize, Vec<SltFailure>> {
+    let records = parse_script(script).map_err(|e| {
+        vec![SltFailure {
+            line: 0,
+            message: e.to_string(),
+        }]
+    })?;
+    let mut failures = Vec::new();
+    let mut hash_threshold = 0;
+    let mut run = 0;
+    for record in records {
+        let outcome = match &record.kind {
+            RecordKind::Statement { sql, error } => {
+                run_sql(engine, sql).and_then(|res| check_statement(res, error))
+            }
+            RecordKind::Query {
+                sql,
+                types,
+                sort,
+                expected,
+            } => run_sql(engine, sql)
+                .and_then(|res| check_query(res, types, *sort, expected, hash_threshold)),
+            RecordKind::HashThreshold(n) => {
+                hash_threshold = *n;
+                Ok(())
+            }
+            RecordKind::Halt => break,
+        };
+        run += 1;
+        if let Err(message) = outcome {
+            failures.push(SltFailure {
+                line: record.line,
+                message,
+            });
+        }
+    }
+    if failures.is_empty() {
+        Ok(run)
+    } else {
+        Err(failures)
+    }
+}
+
+/// Runs a script file against a fresh database in a temporary directory.
+pub fn run_file(path: &Path) -> Result<usize, Vec<SltFailure>> {
+    let script = fs::read_to_string(path).map_err(|e| {
+        vec![SltFailure {
+            line: 0,
+            message: format!("cannot read {}: {}", path.display(), e),
+        }]
+    })?;
+    let dir = tempfile::tempdir().unwrap();
+    let mut engine = QueryEngine::new(dir.path());
+    let res = run_script(&mut engine, &script);
+    drop(engine);
+    res
+}
+
+/// Runs a statement, turning a panic into a failure of the record so that one
+/// bad statement does not hide the results of the rest of the suite.
+fn run_sql(engine: &mut QueryEngine, sql: &str) -> Result<Result<QueryResult, FairyError>, String> {
+    panic::catch_unwind(AssertUnwindSafe(|| engine.run_sql(sql))).map_err(|e| {
+        let message = e
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| e.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        format!("statement panicked: {}", message)
+    })
+}
+
+fn check_statement(
+    result: Result<QueryResult, FairyError>,
+    expected_error: &Option<String>,
+) -> Result<(), String> {
+    match (result, expected_error) {
+        (Ok(_), None) => Ok(()),
+        (Ok(_), Some(_)) => Err("statement succeeded but an error was expected".to_string()),
+        (Err(e), None) => Err(format!("statement failed: {}", e)),
+        (Err(e), Some(expected)) => {
+            let message = e.to_string();
+            if message.contains(expected.as_str()) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected an error containing '{}', got '{}'",
+                    expected, message
+                ))
+            }
+        }
+    }
+}
+
+fn check_query(
+    result: Result<QueryResult, FairyError>,
+    types: &[char],
+    sort: SortMode,
+    expected: &Expected,
+    hash_threshold: usize,
+) -> Result<(), String> {
+    let result = result.map_err(|e| format!("query failed: {}", e))?;
+    let tuples = result
+        .get_tuples()
+        .ok_or_else(|| "statement did not return rows".to_string())?;
+    let mut rows: Vec<Vec<String>> = Vec::with_capacity(tuples.len());
+    for t in tuples {
+        if t.len() != types.len() {
+            return Err(format!(
+                "query returned {} columns, {} types were given",
+                t.len(),
+                types.len()
+            ));
+        }
+        rows.push(
+            t.field_vals
+                .iter()
+                .zip(types)
+                .map(|(f, &c)| format_field(f, c))
+                .collect(),
+        );
+    }
+    let values: Vec<String> = match sort {
+        SortMode::NoSort => rows.into_iter().flatten().collect(),
+        SortMode::RowSort => {
+            rows.sort();
+            rows.into_iter().flatten().collect()
+        }
+        SortMode::ValueSort => {
+            let mut values: Vec<String> = rows.into_iter().flatten().collect();
+            values.sort();
+            values
+        }
+    };
+    let actual_hash = || {
+        let mut input = String::new();
+        for v in &values {
+            input.push_str(v);
+            input.push('\n');
+        }
+        md5_hex(input.as_bytes())
+    };
+
+    match expected {
+        Expected::Hash {
+            values: count,
+            hash,
+        } => {
+            let actual = actual_hash();
+            if *count == values.len() && *hash == actual {
+                Ok(())
+            } else {
+                Err(format!(
+                    "expected {} values hashing to {}, got {} values hashing to {}",
+                    count,
+                    hash,
+                    values.len(),
+                    actual
+                ))
+            }
+        }
+        Expected::Lines(lines) => {
+            // One value per line, or one row per line with the values
+            // separated by spaces.
+            let width = types.len().max(1);
+            let as_rows: Vec<String> = values.chunks(width).map(|row| row.join(" ")).collect();
+            if *lines == values || *lines == as_rows {
+                return Ok(());
+            }
+            let got = if hash_threshold > 0 && values.len() > hash_threshold {
+                format!("{} values hashing to {}", values.len(), actual_hash())
+            } else {
+                as_rows.join("\n")
+            };
+            Err(format!(
+                "query result mismatch\nexpected:\n{}\ngot:\n{}",
+                lines.join("\n"),
+                got
+            ))
+        }
+    }
+}
+
+/// MD5 digest of `input` as lowercase hex, as used by result hashes.
+fn md5_hex(input: &[u8]) -> String {
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let k: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((input.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks(64) {
+        let m: Vec<u32> = block
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(k[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(S[i]));
+        }
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+    state
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_md5() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            md5_hex(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        assert_eq!(md5_hex(&[b'a'; 100]), "36a92cc94a9e0fa21f625f8bfb007adf");
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = "# comment\n\
+            statement ok\n\
+            CREATE TABLE t\n  (a INT PRIMARY KEY)\n\
+            \n\
+            skipif fairydb\n\
+            statement ok\n\
+            VACUUM\n\
+            \n\
+            onlyif fairydb\n\
+            statement error does not exist\n\
+            SELECT * FROM missing\n\
+            \n\
+            query IT rowsort\n\
+            SELECT a, b FROM t\n\
+            ----\n\
+            1 x\n\
+            \n\
+            query R\n\
+            SELECT a FROM t\n\
+            ----\n\
+            3 values hashing to ABC\n";
+        let records = parse_script(script).unwrap();
+        let kinds: Vec<RecordKind> = records.iter().map(|r| r.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RecordKind::Statement {
+                    sql: "CREATE TABLE t\n  (a INT PRIMARY KEY)".to_string(),
+                    error: None,
+                },
+                RecordKind::Statement {
+                    sql: "SELECT * FROM missing".to_string(),
+                    error: Some("does not exist".to_string()),
+                },
+                RecordKind::Query {
+                    sql: "SELECT a, b FROM t".to_string(),
+                    types: vec!['I', 'T'],
+                    sort: SortMode::RowSort,
+                    expected: Expected::Lines(vec!["1 x".to_string()]),
+                },
+                RecordKind::Query {
+                    sql: "SELECT a FROM t".to_string(),
+                    types: vec!['R'],
+                    sort: SortMode::NoSort,
+                    expected: Expected::Hash {
+                        values: 3,
+                        hash: "abc".to_string(),
+                    },
+                },
+            ]
+        );
+        let lines: Vec<usize> = records.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![2, 11, 14, 19]);
+
+        assert!(parse_script("statement maybe\nSELECT 1").is_err());
+        assert!(parse_script("query X\nSELECT 1").is_err());
+    }
+
+    #[test]
+    fn test_format_field() {
+        assert_eq!(format_field(&Field::BigInt(-3), 'I'), "-3");
+        assert_eq!(format_field(&Field::BigInt(2), 'R'), "2.000");
+        assert_eq!(format_field(&Field::Decimal(12345, 2), 'R'), "123.450");
+        assert_eq!(format_field(&Field::Decimal(12345, 2), 'I'), "123");
+        assert_eq!(format_field(&Field::Bool(true), 'I'), "1");
+        assert_eq!(format_field(&Field::Null, 'T'), "NULL");
+        assert_eq!(format_field(&Field::String(String::new()), 'T'), "(empty)");
+        assert_eq!(format_field(&Field::String("a b".to_string()), 'T'), "a b");
+    }
+}
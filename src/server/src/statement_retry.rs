@@ -0,0 +1,166 @@
+use common::physical::config::ServerConfig;
+use common::util::backoff::Backoff;
+use common::FairyError;
+use sqlparser::ast::{Expr, SetExpr, Statement};
+use std::time::Duration;
+
+/// Delay before the first retry of an aborted statement.
+const INITIAL_DELAY: Duration = Duration::from_millis(5);
+/// Upper bound for the delay between two retries.
+const MAX_DELAY: Duration = Duration::from_millis(200);
+
+/// How the conductor retries a statement running in its own implicit
+/// transaction when the transaction is aborted, e.g. as the victim of a
+/// deadlock or because it failed to serialize.
+///
+/// A statement runs at most `max_attempts` times. Retries back off
+/// exponentially, from `initial_delay` up to `max_delay`, with random jitter so
+/// that the statements of a deadlock do not collide again. Other failures are
+/// not retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementRetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for StatementRetryPolicy {
+    fn default() -> Self {
+        StatementRetryPolicy {
+            max_attempts: 3,
+            initial_delay: INITIAL_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+}
+
+impl StatementRetryPolicy {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        StatementRetryPolicy {
+            max_attempts: config.statement_retry_attempts.max(1),
+            ..StatementRetryPolicy::default()
+        }
+    }
+
+    /// Calls `attempt` with the number of the attempt, starting at 0, until it
+    /// succeeds, fails with an error other than an abort, or runs out of
+    /// attempts. Returns the last result and the number of retries it took.
+    pub fn retry<T>(
+        &self,
+        attempt: impl FnMut(u32) -> Result<T, FairyError>,
+    ) -> (Result<T, FairyError>, u32) {
+        Backoff::sleeping(self.initial_delay, self.max_delay).retry(attempt, |e, retries| {
+            let retry = is_abort(e) && retries + 1 < self.max_attempts;
+            if retry {
+                debug!("Retrying statement aborted with {:?}", e);
+            }
+            retry
+        })
+    }
+}
+
+/// Returns true if the error means the transaction of the statement was
+/// aborted and rolled back, so that running the statement again may succeed.
+pub fn is_abort(e: &FairyError) -> bool {
+    matches!(e, FairyError::TransactionRollback(_))
+}
+
+/// Returns true if running `statement` again after its transaction was aborted
+/// has the same effect as running it once: queries, which return nothing
+/// before they finish, executions of prepared queries, and inserts of literal
+/// values. An insert computing its values could insert different ones the
+/// second time.
+pub fn is_reexecutable(statement: &Statement) -> bool {
+    match statement {
+        Statement::Query(_) | Statement::Execute { .. } => true,
+        Statement::Insert {
+            source: Some(source),
+            ..
+        } => match source.body.as_ref() {
+            SetExpr::Values(values) => values.rows.iter().flatten().all(is_literal),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(_) => true,
+        Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => is_literal(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_parser::{ParserResponse, SQLParser};
+    use common::ids::TransactionId;
+
+    fn policy(max_attempts: u32) -> StatementRetryPolicy {
+        StatementRetryPolicy {
+            max_attempts,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        }
+    }
+
+    fn parse(sql: &str) -> Statement {
+        match SQLParser::parse_sql(sql.to_string()) {
+            ParserResponse::SQL(mut ast) => ast.remove(0),
+            _ => panic!("failed to parse {}", sql),
+        }
+    }
+
+    #[test]
+    fn test_retry_until_success() {
+        let (res, retries) = policy(3).retry(|attempt| match attempt {
+            0 | 1 => Err(FairyError::TransactionRollback(TransactionId::new())),
+            _ => Ok(attempt),
+        });
+        assert_eq!(res, Ok(2));
+        assert_eq!(retries, 2);
+    }
+
+    #[test]
+    fn test_retry_attempts_run_out() {
+        let mut attempts = 0;
+        let (res, retries): (Result<(), _>, _) = policy(3).retry(|_| {
+            attempts += 1;
+            Err(FairyError::TransactionRollback(TransactionId::new()))
+        });
+        assert!(matches!(res, Err(FairyError::TransactionRollback(_))));
+        assert_eq!(attempts, 3);
+        assert_eq!(retries, 2);
+    }
+
+    #[test]
+    fn test_other_failures_not_retried() {
+        let mut attempts = 0;
+        let (res, retries): (Result<(), _>, _) = policy(3).retry(|_| {
+            attempts += 1;
+            Err(FairyError::ExecutionError(
+                "table t is locked by DDL".to_string(),
+            ))
+        });
+        assert!(matches!(res, Err(FairyError::ExecutionError(_))));
+        assert_eq!(attempts, 1);
+        assert_eq!(retries, 0);
+    }
+
+    #[test]
+    fn test_is_reexecutable() {
+        assert!(is_reexecutable(&parse("SELECT a FROM t WHERE b = 1")));
+        assert!(is_reexecutable(&parse("EXECUTE q (1, 'a')")));
+        assert!(is_reexecutable(&parse(
+            "INSERT INTO t VALUES (1, -2, 'a'), (2, NULL, 'b')"
+        )));
+        assert!(!is_reexecutable(&parse(
+            "INSERT INTO t VALUES (1, random())"
+        )));
+        assert!(!is_reexecutable(&parse("INSERT INTO t SELECT * FROM u")));
+        assert!(!is_reexecutable(&parse("CREATE TABLE t (a INT PRIMARY KEY)")));
+        assert!(!is_reexecutable(&parse("DROP TABLE t")));
+    }
+}
@@ -0,0 +1,634 @@
+use crate::compaction::AUTO_COMPACTION;
+use crate::database_state::DatabaseState;
+use crate::server_state::ServerState;
+use crate::{StorageManager, StorageTrait};
+use common::catalog::CatalogRef;
+use common::error::ObjectKind;
+use common::metrics::{storage_metrics, HistogramSnapshot, StatementKind};
+use common::prelude::*;
+use common::{Attribute, Constraint, QueryResult, PAGE_SIZE};
+use sqlparser::ast::{Query, SelectItem, SetExpr, TableFactor};
+use std::fs;
+use std::sync::atomic::Ordering;
+
+/// Schema name under which the virtual system tables are exposed.
+const SYSTEM_SCHEMA: &str = "system";
+
+/// Returns the virtual table name if the query reads from `system.<name>`.
+fn system_table_name(query: &Query) -> Option<String> {
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        _ => return None,
+    };
+    let table = select.from.first()?;
+    match &table.relation {
+        TableFactor::Table { name, .. }
+            if name.0.len() == 2 && name.0[0].value.eq_ignore_ascii_case(SYSTEM_SCHEMA) =>
+        {
+            Some(name.0[1].value.to_lowercase())
+        }
+        _ => None,
+    }
+}
+
+/// Runs a query against the system tables (`system.tables`, `system.columns`,
+/// `system.metrics`, `system.prepared_statements`, `system.plan_cache` and
+/// `system.settings`). Returns None if the query does not reference a system table
+/// so that the caller can fall back to regular planning. Only
+/// `SELECT * FROM system.<name>` is supported.
+pub fn run_system_query(
+    query: &Query,
+    db: &DatabaseState,
+) -> Option<Result<QueryResult, FairyError>> {
+    let name = system_table_name(query)?;
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        _ => unreachable!(),
+    };
+    let is_plain_scan = select.from.len() == 1
+        && select.from[0].joins.is_empty()
+        && select.selection.is_none()
+        && matches!(select.projection.as_slice(), [SelectItem::Wildcard(_)]);
+    if !is_plain_scan {
+        return Some(Err(FairyError::ValidationError(
+            "Only SELECT * is supported on system tables".to_string(),
+        )));
+    }
+    match name.as_str() {
+        "tables" => Some(Ok(system_tables(&db.catalog))),
+        "columns" => Some(Ok(system_columns(&db.catalog))),
+        "metrics" => Some(Ok(system_metrics(db))),
+        "prepared_statements" => Some(Ok(system_prepared_statements(db))),
+        "plan_cache" => Some(Ok(system_plan_cache(db))),
+        "settings" => Some(Ok(system_settings(db))),
+        _ => Some(Err(FairyError::ValidationError(format!(
+            "System table {}.{} does not exist",
+            SYSTEM_SCHEMA, name
+        )))),
+    }
+}
+
+/// Describes a single table: one row per column with its type, constraint and comment.
+/// Server settings that can be read with SHOW and from `system.settings`.
+fn server_settings(db: &DatabaseState) -> Vec<(&'static str, String)> {
+    let config = db.managers.config;
+    vec![
+        ("durability", config.durability.to_string()),
+        (
+            "durability_batch_delay_ms",
+            config.durability_batch_delay_ms.to_string(),
+        ),
+        (
+            AUTO_COMPACTION,
+            if db.compaction.enabled() { "on" } else { "off" }.to_string(),
+        ),
+    ]
+}
+
+/// Result of `SHOW name` for a server setting, or None if there is no such setting.
+pub fn show_server_setting(db: &DatabaseState, name: &str) -> Option<QueryResult> {
+    let (name, value) = server_settings(db)
+        .into_iter()
+        .find(|(setting, _)| setting.eq_ignore_ascii_case(name))?;
+    Some(show_setting(name, value))
+}
+
+/// One column named after the setting holding its value.
+pub fn show_setting(name: &str, value: String) -> QueryResult {
+    let schema = TableSchema::from_vecs(vec![name], vec![DataType::String]);
+    QueryResult::new_select_result(&schema, vec![Tuple::new(vec![Field::String(value)])], None)
+}
+
+/// Result of `\d t`: one row per column with its type, constraint, comment
+/// and the column groups declared with `ANALYZE t (a, b)` that include it.
+pub fn describe_table(catalog: &CatalogRef, table_name: &str) -> Result<QueryResult, FairyError> {
+    let table = get_table(catalog, table_name)?;
+    let schema = TableSchema::from_vecs(
+        vec!["column", "type", "constraint", "comment", "column_groups"],
+        vec![DataType::String; 5],
+    );
+    let rows = table
+        .schema
+        .attributes()
+        .map(|attr| {
+            let comment = table.get_column_comment(&attr.name).cloned();
+            Tuple::new(vec![
+                Field::String(attr.name.clone()),
+                Field::String(attr.dtype.to_string()),
+                Field::String(constraint_name(attr)),
+                Field::String(comment.unwrap_or_default()),
+                Field::String(column_groups(&table.column_groups, &attr.name)),
+            ])
+        })
+        .collect();
+    Ok(QueryResult::new_select_result(&schema, rows, None))
+}
+
+/// Result of `SHOW TABLES`: one `table_name` row per table, ordered by creation.
+pub fn show_tables(catalog: &CatalogRef) -> QueryResult {
+    let schema = TableSchema::from_vecs(vec!["table_name"], vec![DataType::String]);
+    let rows = catalog
+        .get_tables()
+        .into_iter()
+        .map(|table| Tuple::new(vec![Field::String(table.name)]))
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// Result of `SHOW COLUMNS FROM t` and `DESCRIBE t`. One row per column with the
+/// fixed columns `column_name`, `data_type`, `is_nullable` (`YES` or `NO`),
+/// `constraint` and `comment` (NULL if not set).
+pub fn show_columns(catalog: &CatalogRef, table_name: &str) -> Result<QueryResult, FairyError> {
+    let table = get_table(catalog, table_name)?;
+    let schema = TableSchema::from_vecs(
+        vec![
+            "column_name",
+            "data_type",
+            "is_nullable",
+            "constraint",
+            "comment",
+        ],
+        vec![DataType::String; 5],
+    );
+    let rows = table
+        .schema
+        .attributes()
+        .map(|attr| {
+            let is_nullable = if attr.is_nullable() { "YES" } else { "NO" };
+            Tuple::new(vec![
+                Field::String(attr.name.clone()),
+                Field::String(attr.dtype.to_string()),
+                Field::String(is_nullable.to_string()),
+                Field::String(constraint_name(attr)),
+                comment_field(table.get_column_comment(&attr.name)),
+            ])
+        })
+        .collect();
+    Ok(QueryResult::new_select_result(&schema, rows, None))
+}
+
+/// Result of `SHOW DATABASES` and `\l`: one row per database, ordered by name,
+/// with its number of tables and the bytes of the data pages of its tables.
+pub fn show_databases(mut databases: Vec<&DatabaseState>, sm: &StorageManager) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec!["database_name", "tables", "size_bytes"],
+        vec![DataType::String, DataType::BigInt, DataType::BigInt],
+    );
+    databases.sort_by(|a, b| a.name.cmp(&b.name));
+    let rows = databases
+        .into_iter()
+        .map(|db| {
+            let tables = db.catalog.get_tables();
+            // Storage managers that do not organize values in pages report no size.
+            let size: usize = tables
+                .iter()
+                .filter_map(|table| sm.storage_report(table.c_id).ok())
+                .map(|report| report.num_pages * PAGE_SIZE)
+                .sum();
+            Tuple::new(vec![
+                Field::String(db.name.clone()),
+                Field::BigInt(tables.len() as i64),
+                Field::BigInt(size as i64),
+            ])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// One row per statement of the server that runs or waits for its turn, the
+/// running ones first. `position` is the place of a queued statement in the
+/// queue, `elapsed_ms` how long it has been running or waiting, `temp_bytes`
+/// the temporary space its operators hold and `pages_scanned` the pages an
+/// ANALYZE scanned so far.
+pub fn progress(server_state: &ServerState) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec![
+            "client",
+            "state",
+            "position",
+            "elapsed_ms",
+            "temp_bytes",
+            "statement",
+            "pages_scanned",
+        ],
+        vec![
+            DataType::BigInt,
+            DataType::String,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::String,
+            DataType::BigInt,
+        ],
+    );
+    let temp_bytes = |client_id: u64| {
+        let tid = server_state
+            .get_connected_db(client_id)
+            .ok()
+            .and_then(|db| db.get_tid(client_id));
+        tid.map_or(0, |tid| server_state.managers.temp.used_by(tid))
+    };
+    let pages_scanned = |client_id: u64| {
+        server_state
+            .get_connected_db(client_id)
+            .ok()
+            .and_then(|db| db.running_queries.pages(client_id))
+            .map_or(Field::Null, |pages| Field::BigInt(pages as i64))
+    };
+    let (running, queued) = server_state.admission.activity();
+    let running = running.into_iter().map(|activity| {
+        let temp = Field::BigInt(temp_bytes(activity.client_id) as i64);
+        ("running", None, temp, activity)
+    });
+    let queued = queued
+        .into_iter()
+        .enumerate()
+        .map(|(i, activity)| ("queued", Some(i as i64 + 1), Field::Null, activity));
+    let rows = running
+        .chain(queued)
+        .map(|(state, position, temp, activity)| {
+            Tuple::new(vec![
+                Field::BigInt(activity.client_id as i64),
+                Field::String(state.to_string()),
+                position.map_or(Field::Null, Field::BigInt),
+                Field::BigInt(activity.since.elapsed().as_millis() as i64),
+                temp,
+                Field::String(activity.statement),
+                pages_scanned(activity.client_id),
+            ])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// Reports the space usage of every table, sorted by wasted bytes (descending).
+pub fn storage_report(
+    catalog: &CatalogRef,
+    sm: &StorageManager,
+) -> Result<QueryResult, FairyError> {
+    let schema = TableSchema::from_vecs(
+        vec![
+            "table_name",
+            "pages",
+            "sampled_pages",
+            "pages_under_25",
+            "pages_under_50",
+            "pages_under_75",
+            "avg_record_size",
+            "dead_slots",
+            "wasted_bytes",
+            "empty_pages",
+        ],
+        vec![
+            DataType::String,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+        ],
+    );
+    let mut reports = Vec::new();
+    for table in catalog.get_tables() {
+        reports.push((table.name, sm.storage_report(table.c_id)?));
+    }
+    reports.sort_by_key(|(_, r)| std::cmp::Reverse(r.wasted_bytes));
+    let rows = reports
+        .into_iter()
+        .map(|(name, r)| {
+            let mut fields = vec![Field::String(name)];
+            fields.extend(
+                [
+                    r.num_pages,
+                    r.sampled_pages,
+                    r.pages_under_25,
+                    r.pages_under_50,
+                    r.pages_under_75,
+                    r.avg_record_size,
+                    r.dead_slots,
+                    r.wasted_bytes,
+                    r.empty_pages,
+                ]
+                .into_iter()
+                .map(|n| Field::BigInt(n as i64)),
+            );
+            Tuple::new(fields)
+        })
+        .collect();
+    Ok(QueryResult::new_select_result(&schema, rows, None))
+}
+
+fn system_tables(catalog: &CatalogRef) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec!["table_name", "num_columns", "comment"],
+        vec![DataType::String, DataType::Int, DataType::String],
+    );
+    let rows = catalog
+        .get_tables()
+        .into_iter()
+        .map(|table| {
+            Tuple::new(vec![
+                Field::String(table.name.clone()),
+                Field::Int(table.schema.size() as i32),
+                comment_field(table.comment.as_ref()),
+            ])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+fn system_columns(catalog: &CatalogRef) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec!["table_name", "column_name", "type", "constraint", "comment"],
+        vec![DataType::String; 5],
+    );
+    let mut rows = Vec::new();
+    for table in catalog.get_tables() {
+        for attr in table.schema.attributes() {
+            rows.push(Tuple::new(vec![
+                Field::String(table.name.clone()),
+                Field::String(attr.name.clone()),
+                Field::String(attr.dtype.to_string()),
+                Field::String(constraint_name(attr)),
+                comment_field(table.get_column_comment(&attr.name)),
+            ]));
+        }
+    }
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// One row per latency histogram. Query latencies are tagged by statement type,
+/// storage latencies are process-wide. Latencies are in microseconds. Builds with
+/// the `latch_stats` feature also report latch acquisitions and their waits.
+fn system_metrics(db: &DatabaseState) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec!["metric", "count", "p50_us", "p90_us", "p99_us", "max_us"],
+        vec![
+            DataType::String,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::BigInt,
+        ],
+    );
+    let mut metrics: Vec<(String, HistogramSnapshot)> = StatementKind::ALL
+        .iter()
+        .map(|kind| {
+            let name = format!("query.{}", kind.name());
+            (name, db.query_metrics.get(*kind).snapshot())
+        })
+        .collect();
+    metrics.push((
+        "query.retries".to_string(),
+        HistogramSnapshot {
+            count: db.query_metrics.statement_retries.load(Ordering::Relaxed),
+            ..HistogramSnapshot::default()
+        },
+    ));
+    let storage = storage_metrics();
+    metrics.push(("bp.page_fault".to_string(), storage.page_fault.snapshot()));
+    metrics.push(("disk.fsync".to_string(), storage.fsync.snapshot()));
+    metrics.push(("bp.retry".to_string(), storage.bp_retry.snapshot()));
+    metrics.push((
+        "bp.retry_exhausted".to_string(),
+        storage.bp_retry_exhausted.snapshot(),
+    ));
+    // A counter rather than a latency, so only the count is reported.
+    metrics.push((
+        "scan.corrupt_records".to_string(),
+        HistogramSnapshot {
+            count: storage.corrupt_records.load(Ordering::Relaxed),
+            ..HistogramSnapshot::default()
+        },
+    ));
+    // Work done by automatic compaction, also counters.
+    let compaction = &db.compaction.metrics;
+    for (name, counter) in [
+        ("compaction.pages_visited", &compaction.pages_visited),
+        ("compaction.pages_compacted", &compaction.pages_compacted),
+        ("compaction.values_relocated", &compaction.values_relocated),
+        ("compaction.pages_emptied", &compaction.pages_emptied),
+        ("compaction.paused_ticks", &compaction.paused_ticks),
+    ] {
+        metrics.push((
+            name.to_string(),
+            HistogramSnapshot {
+                count: counter.load(Ordering::Relaxed),
+                ..HistogramSnapshot::default()
+            },
+        ));
+    }
+    // Temporary space of the operators of all databases: the bytes in use at
+    // the moment and the reservations refused because of the budget.
+    let temp = &db.managers.temp;
+    for (name, count) in [
+        ("temp.used_bytes", temp.used()),
+        ("temp.rejected", temp.rejected.load(Ordering::Relaxed)),
+    ] {
+        metrics.push((
+            name.to_string(),
+            HistogramSnapshot {
+                count,
+                ..HistogramSnapshot::default()
+            },
+        ));
+    }
+    // Admission of statements to run, shared by all databases. The running and
+    // queued statements are reported as counts of the moment.
+    let admission = &db.admission;
+    let (running, queued) = admission.activity();
+    for (name, count) in [
+        ("admission.running", running.len() as u64),
+        ("admission.queued", queued.len() as u64),
+        (
+            "admission.rejected_queue_full",
+            admission
+                .metrics
+                .rejected_queue_full
+                .load(Ordering::Relaxed),
+        ),
+        (
+            "admission.rejected_timeout",
+            admission.metrics.rejected_timeout.load(Ordering::Relaxed),
+        ),
+    ] {
+        metrics.push((
+            name.to_string(),
+            HistogramSnapshot {
+                count,
+                ..HistogramSnapshot::default()
+            },
+        ));
+    }
+    metrics.push((
+        "admission.queue_wait".to_string(),
+        admission.metrics.queue_wait.snapshot(),
+    ));
+    #[cfg(feature = "latch_stats")]
+    {
+        let latches = common::metrics::latch_metrics();
+        metrics.push(("latch.shared".to_string(), latches.shared.snapshot()));
+        metrics.push(("latch.exclusive".to_string(), latches.exclusive.snapshot()));
+    }
+    let rows = metrics
+        .into_iter()
+        .map(|(name, s)| {
+            let mut fields = vec![Field::String(name)];
+            fields.extend(
+                [s.count, s.p50_us, s.p90_us, s.p99_us, s.max_us]
+                    .into_iter()
+                    .map(|n| Field::BigInt(n as i64)),
+            );
+            Tuple::new(fields)
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// One row per registered query. Registered queries are named statements without
+/// parameters, so `param_types` is always empty.
+fn system_prepared_statements(db: &DatabaseState) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec![
+            "name",
+            "sql",
+            "param_types",
+            "times_executed",
+            "last_planned_at",
+        ],
+        vec![
+            DataType::String,
+            DataType::String,
+            DataType::String,
+            DataType::BigInt,
+            DataType::String,
+        ],
+    );
+    let rows = db
+        .query_registrar
+        .snapshot()
+        .into_iter()
+        .map(|query| {
+            Tuple::new(vec![
+                Field::String(query.name),
+                optional_string(query.sql),
+                Field::String(String::new()),
+                Field::BigInt(query.usage.as_ref().map_or(0, |u| u.hits as i64)),
+                optional_string(query.usage.map(|u| u.registered_at.to_rfc3339())),
+            ])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// One row per server setting with its value.
+fn system_settings(db: &DatabaseState) -> QueryResult {
+    let schema = TableSchema::from_vecs(vec!["name", "value"], vec![DataType::String; 2]);
+    let rows = server_settings(db)
+        .into_iter()
+        .map(|(name, value)| {
+            Tuple::new(vec![Field::String(name.to_string()), Field::String(value)])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+/// One row per cached plan, keyed by the hash of its plan tree. The hash is the
+/// argument of `\cache_evict`.
+fn system_plan_cache(db: &DatabaseState) -> QueryResult {
+    let schema = TableSchema::from_vecs(
+        vec![
+            "tree_hash",
+            "source_tables",
+            "generation_stamps",
+            "hit_count",
+            "result_bytes",
+            "created_at",
+        ],
+        vec![
+            DataType::String,
+            DataType::String,
+            DataType::String,
+            DataType::BigInt,
+            DataType::BigInt,
+            DataType::String,
+        ],
+    );
+    // Names and file sizes are looked up after the snapshot, outside its locks.
+    let table_name = |c_id: ContainerId| {
+        db.catalog
+            .get_table(c_id)
+            .map_or_else(|| c_id.to_string(), |table| table.name)
+    };
+    let rows = db
+        .query_registrar
+        .snapshot()
+        .into_iter()
+        .map(|query| {
+            let tables: Vec<String> = query.tables.iter().map(|c| table_name(*c)).collect();
+            let stamps: Vec<String> = query
+                .schema_stamps
+                .iter()
+                .map(|stamp| format!("{}@{}", table_name(stamp.c_id), stamp.generation))
+                .collect();
+            let result_bytes = query
+                .result_path
+                .and_then(|path| fs::metadata(path).ok())
+                .map_or(0, |m| m.len());
+            Tuple::new(vec![
+                Field::String(format!("{:016x}", query.plan_hash)),
+                Field::String(tables.join(", ")),
+                Field::String(stamps.join(", ")),
+                Field::BigInt(query.usage.as_ref().map_or(0, |u| u.hits as i64)),
+                Field::BigInt(result_bytes as i64),
+                optional_string(query.usage.map(|u| u.registered_at.to_rfc3339())),
+            ])
+        })
+        .collect();
+    QueryResult::new_select_result(&schema, rows, None)
+}
+
+fn optional_string(s: Option<String>) -> Field {
+    s.map_or(Field::Null, Field::String)
+}
+
+fn get_table(catalog: &CatalogRef, table_name: &str) -> Result<TableInfo, FairyError> {
+    catalog
+        .get_table_id_if_exists(table_name)
+        .and_then(|c_id| catalog.get_table(c_id))
+        .ok_or_else(|| FairyError::NotFound(ObjectKind::Table, table_name.to_string()))
+}
+
+fn comment_field(comment: Option<&String>) -> Field {
+    match comment {
+        Some(c) => Field::String(c.clone()),
+        None => Field::Null,
+    }
+}
+
+/// The column groups that include `column`, as in `(city, state)`.
+fn column_groups(groups: &[Vec<String>], column: &str) -> String {
+    groups
+        .iter()
+        .filter(|group| group.iter().any(|c| c == column))
+        .map(|group| format!("({})", group.join(", ")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn constraint_name(attr: &Attribute) -> String {
+    match attr.constraint {
+        Constraint::None => String::new(),
+        Constraint::PrimaryKey => "primary key".to_string(),
+        Constraint::Unique => "unique".to_string(),
+        Constraint::NotNull => "not null".to_string(),
+        Constraint::UniqueNotNull => "unique not null".to_string(),
+        Constraint::ForeignKey(c_id) => format!("foreign key ({})", c_id),
+        Constraint::NotNullFKey(c_id) => format!("not null foreign key ({})", c_id),
+    }
+}
@@ -0,0 +1,229 @@
+//! Tests that run a server and talk to it through clients.
+
+use cli_fairy::Client;
+use common::commands::Response;
+use common::{Field, QueryResult};
+use test_support::TestServer;
+
+fn run(client: &mut Client, statements: &[&str]) {
+    for statement in statements {
+        client.execute(statement).unwrap();
+    }
+}
+
+fn rows(client: &mut Client, sql: &str) -> Vec<Vec<Field>> {
+    match client.execute(sql).unwrap() {
+        Response::QueryResult(QueryResult::Select { result, .. }) => {
+            result.into_iter().map(|t| t.field_vals).collect()
+        }
+        response => panic!("Expected select result, got {:?}", response),
+    }
+}
+
+mod replay {
+    use super::*;
+    use cli_fairy::replay::{replay_workload, Pacing, ReplayOptions};
+    use common::physical::config::ServerConfig;
+    use common::workload::{is_mutating, read_workload};
+    use std::thread;
+
+    const LOAD: [&str; 5] = [
+        "\\r db",
+        "\\c db",
+        "CREATE TABLE foo (id INT PRIMARY KEY, v INT)",
+        "INSERT INTO foo VALUES (1, 10), (2, 20), (3, 30)",
+        "INSERT INTO foo VALUES (4, 40)",
+    ];
+
+    #[test]
+    fn test_capture_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture_file = dir.path().join("capture.jsonl");
+        let server = TestServer::with_config(ServerConfig {
+            capture_file: Some(capture_file.clone()),
+            ..ServerConfig::default()
+        });
+
+        // Scripted mixed workload: one session loads the data, then two
+        // sessions read it, including a statement that fails.
+        let mut first = server.connect().unwrap();
+        run(&mut first, &LOAD);
+        thread::sleep(std::time::Duration::from_millis(100));
+        let mut second = server.connect().unwrap();
+        run(&mut second, &["\\c db", "SELECT * FROM foo WHERE v > 15"]);
+        run(&mut first, &["SELECT * FROM foo", "SHOW TABLES"]);
+        run(&mut second, &["\\d missing", "DESCRIBE foo", "\\dt"]);
+        drop(first);
+        drop(second);
+
+        let workload = read_workload(&capture_file).unwrap();
+        assert_eq!(workload.len(), 12);
+        assert_eq!(
+            workload.iter().filter(|s| !s.ok).count(),
+            1,
+            "only \\d missing fails"
+        );
+        let select = workload
+            .iter()
+            .find(|s| s.statement == "SELECT * FROM foo WHERE v > 15")
+            .unwrap();
+        assert_eq!(select.result_count, Some(3));
+
+        // Replay the reads against a fresh server loaded with the same data.
+        let server = TestServer::start();
+        run(&mut server.connect().unwrap(), &LOAD);
+        let options = ReplayOptions {
+            pacing: Pacing::AsFastAsPossible,
+            include_mutations: false,
+        };
+        let report = replay_workload(&server.client_config(), &workload, &options).unwrap();
+        assert_eq!(
+            report.statements.len(),
+            workload
+                .iter()
+                .filter(|s| !is_mutating(&s.statement))
+                .count()
+        );
+        assert!(report.mismatches().is_empty(), "{}", report);
+
+        // Replay everything, with the original pacing, against an empty server.
+        let server = TestServer::start();
+        let options = ReplayOptions {
+            pacing: Pacing::Original,
+            include_mutations: true,
+        };
+        let started = std::time::Instant::now();
+        let report = replay_workload(&server.client_config(), &workload, &options).unwrap();
+        assert_eq!(report.statements.len(), workload.len());
+        assert!(report.mismatches().is_empty(), "{}", report);
+        let last_offset = workload.iter().map(|s| s.offset_us).max().unwrap();
+        assert!(started.elapsed().as_micros() as u64 >= last_offset);
+    }
+}
+
+mod databases {
+    use super::*;
+    use common::physical::config::ClientConfig;
+
+    #[test]
+    fn test_connect_with_url_and_switch() {
+        let server = TestServer::start();
+        let config = server.client_config();
+        let mut admin = server.connect().unwrap();
+        assert_eq!(admin.database(), None);
+        assert_eq!(admin.prompt(), "[fairydb]>>");
+        admin.execute("\\r first").unwrap();
+        admin.execute("\\r second").unwrap();
+
+        let url = format!("fairydb://{}:{}/first", config.host, config.port);
+        let mut client = Client::connect(ClientConfig::from_url(&url).unwrap()).unwrap();
+        assert_eq!(client.database(), Some("first"));
+        assert_eq!(client.prompt(), "[fairydb:first]>>");
+        client
+            .execute("CREATE TABLE t (a INT PRIMARY KEY)")
+            .unwrap();
+        client.execute("INSERT INTO t VALUES (1)").unwrap();
+
+        // The same table name in another database is a different table.
+        client.use_database("second").unwrap();
+        assert_eq!(client.prompt(), "[fairydb:second]>>");
+        client
+            .execute("CREATE TABLE t (a INT PRIMARY KEY)")
+            .unwrap();
+        client.execute("INSERT INTO t VALUES (2), (3)").unwrap();
+        assert_eq!(
+            rows(&mut client, "SELECT a FROM t"),
+            vec![vec![Field::BigInt(2)], vec![Field::BigInt(3)]]
+        );
+
+        // Switching to a missing database keeps the current one.
+        assert!(client.use_database("missing").is_err());
+        assert_eq!(client.database(), Some("second"));
+        assert_eq!(rows(&mut client, "SELECT a FROM t").len(), 2);
+
+        client.execute("\\c first").unwrap();
+        assert_eq!(client.database(), Some("first"));
+        assert_eq!(
+            rows(&mut client, "SELECT a FROM t"),
+            vec![vec![Field::BigInt(1)]]
+        );
+
+        let databases = rows(&mut client, "SHOW DATABASES");
+        assert_eq!(databases, rows(&mut admin, "\\l"));
+        let names: Vec<Field> = databases.iter().map(|row| row[0].clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Field::String("first".to_string()),
+                Field::String("second".to_string())
+            ]
+        );
+        for row in &databases {
+            assert_eq!(row[1], Field::BigInt(1));
+            assert!(matches!(row[2], Field::BigInt(size) if size > 0));
+        }
+
+        client.execute("\\close").unwrap();
+        assert_eq!(client.database(), None);
+    }
+
+    #[test]
+    fn test_connect_to_missing_database() {
+        let server = TestServer::start();
+        let config = ClientConfig {
+            database: "missing".to_string(),
+            ..server.client_config()
+        };
+        assert!(Client::connect(config).is_err());
+    }
+}
+
+mod aggregation_errors {
+    use super::*;
+
+    #[test]
+    fn test_malformed_aggregates_keep_the_connection() {
+        let server = TestServer::start();
+        let mut client = server.connect().unwrap();
+        run(
+            &mut client,
+            &[
+                "\\r db",
+                "\\c db",
+                "CREATE TABLE t (a INT PRIMARY KEY, b INT)",
+                "INSERT INTO t VALUES (1, 10), (2, 20)",
+            ],
+        );
+
+        for (sql, msg) in [
+            (
+                "SELECT SUM(MAX(a)) FROM t",
+                "Aggregation functions cannot be nested",
+            ),
+            (
+                "SELECT AVG(*) FROM t",
+                "Wildcard is only supported for COUNT",
+            ),
+            (
+                "SELECT SUM(a) + MODE(b) FROM t",
+                "Unsupported aggregation function: MODE",
+            ),
+        ] {
+            match client.execute(sql).unwrap() {
+                Response::QueryExecutionError(_, error) => {
+                    assert!(error.contains(msg), "{}: {}", sql, error)
+                }
+                response => panic!("Expected query execution error, got {:?}", response),
+            }
+        }
+
+        // The same connection still runs queries.
+        assert_eq!(
+            rows(
+                &mut client,
+                "SELECT SUM(CASE WHEN a > 1 THEN b ELSE 0 END) FROM t"
+            ),
+            vec![vec![Field::BigInt(20)]]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Runs EXISTS over a large table with the JSON span exporter on and checks
+//! from the row counts of the operators that the semi join stops reading the
+//! table once every row has matched. The exporter is set up once per process,
+//! so this is a test binary of its own.
+
+use common::physical::config::ServerConfig;
+use common::QueryResult;
+use serde_json::Value;
+use server::{init_logging, QueryEngine};
+use std::fs;
+
+const INNER_ROWS: usize = 2000;
+
+#[test]
+fn semi_join_stops_probing_once_every_row_matched() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_file = dir.path().join("trace.json");
+    let config = ServerConfig {
+        log_file: dir.path().join("log.txt").to_string_lossy().into_owned(),
+        log_level: "debug".to_owned(),
+        trace_file: Some(trace_file.clone()),
+        ..ServerConfig::default()
+    };
+    init_logging(&config).unwrap();
+
+    let mut engine = QueryEngine::new(&dir.path().join("db"));
+    engine
+        .run_sql("CREATE TABLE t (a INT PRIMARY KEY, b INT)")
+        .unwrap();
+    engine
+        .run_sql("CREATE TABLE u (id INT PRIMARY KEY, name VARCHAR(10))")
+        .unwrap();
+    engine
+        .run_sql("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)")
+        .unwrap();
+    let ids: Vec<usize> = (1..=INNER_ROWS).collect();
+    for chunk in ids.chunks(500) {
+        let values: Vec<String> = chunk
+            .iter()
+            .map(|id| format!("({}, 'u{}')", id, id))
+            .collect();
+        engine
+            .run_sql(&format!("INSERT INTO u VALUES {}", values.join(", ")))
+            .unwrap();
+    }
+    let u = engine.get_table_id("u").unwrap() as u64;
+    let result = engine
+        .run_sql("SELECT t.b FROM t WHERE EXISTS (SELECT * FROM u WHERE u.id = t.a)")
+        .unwrap();
+    let QueryResult::Select { result, .. } = result else {
+        panic!("not a SELECT result: {:?}", result);
+    };
+    assert_eq!(result.len(), 3);
+
+    let events: Vec<Value> = fs::read_to_string(&trace_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let closed = |name: &str| {
+        events
+            .iter()
+            .filter(|e| e["fields"]["message"] == "close" && e["span"]["name"] == name)
+            .collect::<Vec<_>>()
+    };
+    let query_id = &closed("query").last().unwrap()["span"]["query_id"];
+    let operators: Vec<&Value> = closed("operator")
+        .into_iter()
+        .filter(|e| e["spans"][0]["query_id"] == *query_id)
+        .collect();
+
+    let join = operators
+        .iter()
+        .find(|e| e["span"]["operator"].as_str().unwrap().ends_with("join"))
+        .expect("no join span");
+    assert_eq!(join["span"]["rows"], 3);
+    let scan = operators
+        .iter()
+        .find(|e| e["span"]["operator"] == "scan" && e["span"]["table"] == u)
+        .expect("no scan of u");
+    let rows = scan["span"]["rows"].as_u64().unwrap();
+    assert!(
+        rows < INNER_ROWS as u64,
+        "the semi join read all {} rows of u",
+        rows
+    );
+}
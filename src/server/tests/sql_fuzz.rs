@@ -0,0 +1,309 @@
+//! Randomized harness for the SQL front end.
+//!
+//! Generated and mutated queries, inserts, deletes, updates, DDL and
+//! transaction statements are parsed, translated and executed against a small
+//! fixed schema. Every statement may fail, but only by
+//! returning an error: a panic anywhere in the pipeline fails the test.
+//! Set `FAIRY_FUZZ_STEPS` for a longer session and `FAIRY_SEED` to replay the
+//! seed a failure reports.
+
+use common::testutil::get_fuzz_config;
+use rand::rngs::SmallRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use server::QueryEngine;
+use std::panic::{self, AssertUnwindSafe};
+use std::cell::Cell;
+use std::sync::Once;
+
+static QUIET_PANICS: Once = Once::new();
+
+thread_local! {
+    /// Set while a statement runs, so its panic is reported by the harness
+    /// instead of being printed by the panic hook.
+    static IN_STATEMENT: Cell<bool> = const { Cell::new(false) };
+}
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE t (a INT PRIMARY KEY, b INT, c VARCHAR(10));",
+    "CREATE TABLE u (a INT PRIMARY KEY, d INT);",
+    "INSERT INTO t VALUES (1, 10, 'x'), (2, 20, 'y'), (3, 30, 'z');",
+    "INSERT INTO u VALUES (1, 100), (3, 300);",
+];
+
+/// Statements that panicked before, replayed on every run.
+const REGRESSIONS: &[&str] = &[
+    "SELECT SUM(b) FROM t WHERE a > 10;",
+    "INSERT INTO t VALUES (-1, 0, 'n');",
+    "SELECT a FROM t HAVING COUNT(*) > 1;",
+    "SELECT x.a FROM t AS x, t AS y WHERE x.a = y.a;",
+    "SELECT a + c FROM t;",
+    "SELECT * FROM t WHERE c;",
+    "SELECT * FROM t WHERE a = 'x';",
+    "SELECT missing FROM t;",
+    "SELECT * FROM missing;",
+    "INSERT INTO t VALUES (1);",
+    "INSERT INTO t VALUES ('a', 'b', 'c');",
+    "SELECT;",
+    "SELECT * FROM t, t;",
+    "SELECT TRUE, c, b FROM t, u;",
+    "SELECT *, COUNT(missing) FROM u, t WHERE b / u.a <> (a);",
+    "SELECT x.a FROM t AS x, u WHERE u.a < x.a;",
+    "INSERT INTO t VALUES ('', TRUE, 0, 2.5);",
+    "SELECT 2.5 - b FROM t;",
+    "SELECT -1 FROM t;",
+    "SELECT + a FROM t;",
+    "SELECT AVG(*) FROM t;",
+    "SELECT (t.a) AND SUM(a) FROM t;",
+    "SELECT SUM(t.a), MAX(b) FROM u;",
+    "INSERT INTO u VALUES (5, NULL);",
+    "SELECT SUM(d) FROM u, t AS x GROUP BY a;",
+    "SELECT t.a / NULL = b, *, b FROM t AS x, u;",
+    "SELECT b / 0 FROM t;",
+    "SELECT * FROM t WHERE NULL AND TRUE;",
+    "UPDATE t SET b = 1 WHERE a = 2;",
+    "BEGIN;",
+    "COMMIT;",
+    "",
+];
+
+const COLUMNS: &[&str] = &["a", "b", "c", "d", "t.a", "u.a", "x.a", "*", "missing"];
+const LITERALS: &[&str] = &["1", "0", "-1", "2.5", "'x'", "''", "NULL", "TRUE"];
+const OPS: &[&str] = &["+", "-", "*", "/", "=", "<>", "<", ">=", "AND", "OR"];
+const AGGS: &[&str] = &[
+    "COUNT", "SUM", "AVG", "MIN", "MAX", "STDDEV", "VARIANCE", "MEDIAN",
+];
+const TABLES: &[&str] = &["t", "u", "t AS x", "u AS y"];
+const TARGETS: &[&str] = &["t", "u", "missing"];
+/// Tables of DDL statements, mostly `v` so that `t` and `u` are rarely dropped.
+const DDL_TABLES: &[&str] = &["v", "v", "v", "t", "missing"];
+const TYPES: &[&str] = &[
+    "INT",
+    "BIGINT",
+    "VARCHAR(10)",
+    "DOUBLE",
+    "DECIMAL(10,2)",
+    "DATE",
+    "BOOLEAN",
+];
+const CONSTRAINTS: &[&str] = &["", "", " PRIMARY KEY", " NOT NULL", " UNIQUE", " DEFAULT 1"];
+const TRANSACTIONS: &[&str] = &[
+    "BEGIN;",
+    "START TRANSACTION;",
+    "COMMIT;",
+    "ROLLBACK;",
+    "SAVEPOINT s;",
+    "ROLLBACK TO SAVEPOINT s;",
+];
+
+fn pick<'a>(rng: &mut SmallRng, items: &[&'a str]) -> &'a str {
+    items.choose(rng).unwrap()
+}
+
+fn expr(rng: &mut SmallRng, depth: usize) -> String {
+    match rng.random_range(0..6) {
+        0 if depth < 3 => format!(
+            "{} {} {}",
+            expr(rng, depth + 1),
+            pick(rng, OPS),
+            expr(rng, depth + 1)
+        ),
+        1 if depth < 3 => format!("({})", expr(rng, depth + 1)),
+        2 => pick(rng, LITERALS).to_string(),
+        _ => pick(rng, COLUMNS).to_string(),
+    }
+}
+
+fn select(rng: &mut SmallRng) -> String {
+    let items = (0..rng.random_range(1..4))
+        .map(|_| match rng.random_range(0..4) {
+            0 => format!("{}({})", pick(rng, AGGS), pick(rng, COLUMNS)),
+            _ => expr(rng, 0),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let tables = (0..rng.random_range(1..3))
+        .map(|_| pick(rng, TABLES))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut sql = format!("SELECT {} FROM {}", items, tables);
+    if rng.random_bool(0.6) {
+        sql += &format!(" WHERE {}", expr(rng, 0));
+    }
+    if rng.random_bool(0.3) {
+        sql += &format!(" GROUP BY {}", pick(rng, COLUMNS));
+    }
+    if rng.random_bool(0.2) {
+        sql += &format!(" ORDER BY {}", pick(rng, COLUMNS));
+    }
+    if rng.random_bool(0.1) {
+        sql += &format!(" LIMIT {}", pick(rng, LITERALS));
+    }
+    sql + ";"
+}
+
+fn insert(rng: &mut SmallRng) -> String {
+    let rows = (0..rng.random_range(1..3))
+        .map(|_| {
+            let values = (0..rng.random_range(0..5))
+                .map(|_| pick(rng, LITERALS))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("({})", values)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("INSERT INTO {} VALUES {};", pick(rng, TARGETS), rows)
+}
+
+fn delete(rng: &mut SmallRng) -> String {
+    let mut sql = format!("DELETE FROM {}", pick(rng, TARGETS));
+    if rng.random_bool(0.8) {
+        sql += &format!(" WHERE {}", expr(rng, 0));
+    }
+    sql + ";"
+}
+
+fn update(rng: &mut SmallRng) -> String {
+    let assignments = (0..rng.random_range(1..3))
+        .map(|_| format!("{} = {}", pick(rng, COLUMNS), expr(rng, 1)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut sql = format!("UPDATE {} SET {}", pick(rng, TARGETS), assignments);
+    if rng.random_bool(0.7) {
+        sql += &format!(" WHERE {}", expr(rng, 0));
+    }
+    sql + ";"
+}
+
+fn ddl(rng: &mut SmallRng) -> String {
+    let table = pick(rng, DDL_TABLES);
+    match rng.random_range(0..5) {
+        0 | 1 => {
+            let columns = (0..rng.random_range(0..4))
+                .map(|i| format!("c{} {}{}", i, pick(rng, TYPES), pick(rng, CONSTRAINTS)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("CREATE TABLE {} ({});", table, columns)
+        }
+        2 => {
+            let if_exists = if rng.random_bool(0.5) {
+                "IF EXISTS "
+            } else {
+                ""
+            };
+            format!("DROP TABLE {}{};", if_exists, table)
+        }
+        3 => format!("TRUNCATE TABLE {};", table),
+        _ => format!("ALTER TABLE {} ADD COLUMN e {};", table, pick(rng, TYPES)),
+    }
+}
+
+/// Deletes, duplicates or swaps whitespace separated tokens of `sql`.
+fn mutate(rng: &mut SmallRng, sql: &str) -> String {
+    let mut tokens: Vec<&str> = sql.split_whitespace().collect();
+    for _ in 0..rng.random_range(1..4) {
+        if tokens.is_empty() {
+            break;
+        }
+        let i = rng.random_range(0..tokens.len());
+        match rng.random_range(0..3) {
+            0 => {
+                tokens.remove(i);
+            }
+            1 => tokens.insert(i, tokens[i]),
+            _ => {
+                let j = rng.random_range(0..tokens.len());
+                tokens.swap(i, j);
+            }
+        }
+    }
+    tokens.join(" ")
+}
+
+fn statement(rng: &mut SmallRng) -> String {
+    let sql = match rng.random_range(0..10) {
+        0 => insert(rng),
+        1 => delete(rng),
+        2 => update(rng),
+        3 => ddl(rng),
+        4 => pick(rng, TRANSACTIONS).to_string(),
+        5 => {
+            let seed = pick(rng, REGRESSIONS);
+            mutate(rng, seed)
+        }
+        _ => select(rng),
+    };
+    if rng.random_bool(0.3) {
+        mutate(rng, &sql)
+    } else {
+        sql
+    }
+}
+
+fn engine() -> QueryEngine {
+    let base_dir = tempfile::tempdir().unwrap().keep();
+    let mut engine = QueryEngine::new(&base_dir);
+    for sql in SCHEMA {
+        engine.run_sql(sql).unwrap();
+    }
+    engine
+}
+
+/// Runs `sql`, returning the panic message if it panicked.
+fn run(engine: &mut QueryEngine, sql: &str) -> Option<String> {
+    IN_STATEMENT.with(|s| s.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _ = engine.run_sql(sql);
+    }));
+    IN_STATEMENT.with(|s| s.set(false));
+    result.err().map(|e| {
+        e.downcast_ref::<String>()
+            .cloned()
+            .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default()
+    })
+}
+
+/// Runs every statement on a fresh engine, collecting the ones that panicked.
+/// A panic may leave the engine in an unknown state, so it is replaced after one.
+fn run_all(statements: impl IntoIterator<Item = String>) -> Vec<String> {
+    QUIET_PANICS.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !IN_STATEMENT.with(|s| s.get()) {
+                default(info)
+            }
+        }));
+    });
+    let mut engine = engine();
+    let mut failures = Vec::new();
+    for sql in statements {
+        if let Some(msg) = run(&mut engine, &sql) {
+            failures.push(format!("{:?}: {}", sql, msg));
+            engine = self::engine();
+        }
+    }
+    failures
+}
+
+#[test]
+fn fuzz_sql_regressions() {
+    let failures = run_all(REGRESSIONS.iter().map(|s| s.to_string()));
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn fuzz_sql_front_end() {
+    let (seed, steps) = get_fuzz_config(300);
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let statements: Vec<String> = (0..steps).map(|_| statement(&mut rng)).collect();
+    let failures = run_all(statements);
+    assert!(
+        failures.is_empty(),
+        "seed {}: {} statement(s) panicked:\n{}",
+        seed,
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,31 @@
+//! Runs every `.slt` script under `tests/slt`, each against a fresh database.
+
+use server::sqllogictest::run_file;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn sqllogictest() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/slt");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "slt"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "no scripts in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for script in &scripts {
+        let name = script.file_name().unwrap().to_string_lossy().to_string();
+        if let Err(errors) = run_file(script) {
+            failures.extend(errors.iter().map(|e| format!("{}:{}", name, e)));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} sqllogictest failure(s):\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,120 @@
+//! Runs a join with the JSON span exporter on and checks the spans it wrote.
+//! The exporter is set up once per process, so this is a test binary of its own.
+
+use common::physical::config::ServerConfig;
+use serde_json::Value;
+use server::{init_logging, QueryEngine};
+use std::fs;
+
+/// Names of the spans an event was written in, outermost first. The close
+/// event of a span is written in the spans around it.
+fn span_names(event: &Value) -> Vec<String> {
+    event["spans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|span| match span["operator"].as_str() {
+            Some(operator) => operator.to_string(),
+            None => span["name"].as_str().unwrap().to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn join_spans_nest_under_their_query() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_file = dir.path().join("trace.json");
+    let config = ServerConfig {
+        log_file: dir.path().join("log.txt").to_string_lossy().into_owned(),
+        log_level: "debug".to_owned(),
+        trace_file: Some(trace_file.clone()),
+        ..ServerConfig::default()
+    };
+    init_logging(&config).unwrap();
+
+    let mut engine = QueryEngine::new(&dir.path().join("db"));
+    engine
+        .run_sql("CREATE TABLE t (a INT PRIMARY KEY, b INT)")
+        .unwrap();
+    engine
+        .run_sql("CREATE TABLE u (id INT PRIMARY KEY, name VARCHAR(10))")
+        .unwrap();
+    engine
+        .run_sql("INSERT INTO t VALUES (1, 10), (2, 20), (3, 30)")
+        .unwrap();
+    engine
+        .run_sql("INSERT INTO u VALUES (1, 'one'), (3, 'three')")
+        .unwrap();
+    let t = engine.get_table_id("t").unwrap();
+    let u = engine.get_table_id("u").unwrap();
+    engine
+        .run_sql("SELECT t.b, u.name FROM t JOIN u ON t.a = u.id")
+        .unwrap();
+
+    let events: Vec<Value> = fs::read_to_string(&trace_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let closed = |name: &str| {
+        events
+            .iter()
+            .filter(|e| e["fields"]["message"] == "close" && e["span"]["name"] == name)
+            .collect::<Vec<_>>()
+    };
+
+    // Every statement has a span of its own, the join is the last one.
+    let queries = closed("query");
+    assert_eq!(queries.len(), 5);
+    let query_id = &queries[4]["span"]["query_id"];
+    let ids = queries
+        .iter()
+        .filter(|q| q["span"]["query_id"] == *query_id);
+    assert_eq!(ids.count(), 1);
+    assert_eq!(queries[4]["span"]["session_id"], 0);
+
+    let operators: Vec<&Value> = closed("operator")
+        .into_iter()
+        .filter(|e| e["spans"][0]["query_id"] == *query_id)
+        .collect();
+    let join = operators
+        .iter()
+        .find(|e| e["span"]["operator"].as_str().unwrap().ends_with("join"))
+        .expect("no join span");
+    let join_name = join["span"]["operator"].as_str().unwrap();
+    assert_eq!(join["span"]["rows"], 2);
+    let mut join_path = span_names(join);
+    assert_eq!(join_path[..2], ["query", "execute"]);
+
+    // The scans run inside the join, each with its table and row count.
+    join_path.push(join_name.to_string());
+    let mut scans: Vec<(u64, u64)> = Vec::new();
+    for scan in operators.iter().filter(|e| e["span"]["operator"] == "scan") {
+        assert_eq!(span_names(scan), join_path);
+        scans.push((
+            scan["span"]["table"].as_u64().unwrap(),
+            scan["span"]["rows"].as_u64().unwrap(),
+        ));
+    }
+    // A scan that is rewound by its join produces its table more than once.
+    scans.sort();
+    assert_eq!(scans.len(), 2);
+    assert_eq!((scans[0].0, scans[1].0), (t.min(u) as u64, t.max(u) as u64));
+    for (table, rows) in scans {
+        let size = if table == t as u64 { 3 } else { 2 };
+        assert!(
+            rows > 0 && rows.is_multiple_of(size),
+            "{} rows from table {}",
+            rows,
+            table
+        );
+    }
+
+    // Log lines are written inside the span of their statement.
+    assert!(events.iter().any(|e| {
+        e["fields"]["message"]
+            .as_str()
+            .is_some_and(|m| m.starts_with("Parsing SQL: \"SELECT t.b"))
+            && e["spans"][0]["query_id"] == *query_id
+    }));
+}
@@ -1,13 +1,14 @@
+use crate::durability::{FileSync, Fsync};
 use crate::file_stats::FileStats;
 use crate::page::Page;
 use common::ids::{ContainerId, PageId};
 use common::PAGE_SIZE;
-use libc::fsync;
 use std::fs::{File, OpenOptions};
 use std::mem::MaybeUninit;
 use std::os::raw::c_void;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// BaseFileTrait is a trait that defines the methods that are required to manage the file that is used to store the pages.
 pub trait BaseFileTrait: Send + Sync {
@@ -19,7 +20,7 @@ pub trait BaseFileTrait: Send + Sync {
     fn read_page(&self, page_id: PageId, page: &mut Page) -> Result<(), std::io::Error>;
     /// Write a page to the file at the given page_id.
     fn write_page(&self, page_id: PageId, page: &Page) -> Result<(), std::io::Error>;
-    /// Flush the file to disk if necessary.
+    /// Force the pages written to the file to disk.
     fn flush(&self) -> Result<(), std::io::Error>;
 }
 
@@ -31,6 +32,7 @@ pub struct BaseFile {
     stats: FileStats,
     file_no: i32,
     direct: bool,
+    sync: Arc<dyn FileSync>,
 }
 
 impl BaseFile {
@@ -53,8 +55,15 @@ impl BaseFile {
             stats: FileStats::new(),
             file_no,
             direct: true,
+            sync: Arc::new(Fsync),
         })
     }
+
+    /// Replaces how the file is synced by `flush`.
+    pub fn with_sync(mut self, sync: Arc<dyn FileSync>) -> Self {
+        self.sync = sync;
+        self
+    }
 }
 
 impl BaseFileTrait for BaseFile {
@@ -119,8 +128,7 @@ impl BaseFileTrait for BaseFile {
         }
 
         if ret as usize != to_read {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            return Err(std::io::Error::other(
                 "Failed to read the expected amount of data",
             ));
         }
@@ -160,18 +168,8 @@ impl BaseFileTrait for BaseFile {
         Ok(())
     }
 
-    // With psync_direct, we don't need to flush.
+    // Whether to flush at all is decided by the container file catalog's durability level.
     fn flush(&self) -> Result<(), std::io::Error> {
-        if self.direct {
-            Ok(())
-        } else {
-            unsafe {
-                let ret = fsync(self.file_no);
-                if ret != 0 {
-                    return Err(std::io::Error::last_os_error());
-                }
-            }
-            Ok(())
-        }
+        self.sync.sync(&self._file)
     }
 }
@@ -35,6 +35,12 @@ impl BaseFileMock {
             direct: true,
         })
     }
+
+    /// The mock never syncs, so the sync is ignored.
+    #[allow(dead_code)]
+    pub fn with_sync(self, _sync: std::sync::Arc<dyn crate::durability::FileSync>) -> Self {
+        self
+    }
 }
 
 impl BaseFileTrait for BaseFileMock {
@@ -2,7 +2,9 @@ use crate::buffer_pool::buffer_pool_stats::BPStats;
 use crate::buffer_pool::eviction_policy::SmallThreadRng;
 use crate::container_file_catalog::ContainerFileCatalog;
 use common::ids::{ContainerId, ContainerPageId, PageId};
+use common::metrics::storage_metrics;
 use common::rwlatch::RwLatch;
+use common::util::backoff::Backoff;
 use rand::RngCore;
 
 use super::{
@@ -16,15 +18,44 @@ use std::{
     cell::UnsafeCell,
     collections::{BTreeMap, HashMap},
     sync::{atomic::Ordering, Arc},
+    time::Instant,
 };
 
 use concurrent_queue::ConcurrentQueue;
 
+/// Number of frames visited per acquisition of the pool latch in flush_all and
+/// clear_dirty_flags. The pool latch is released between batches.
+const FRAME_BATCH_SIZE: usize = 64;
+/// Number of backoff rounds spent waiting for a frame latch before the frame is
+/// skipped and retried in a later pass. This is kept within the spinning phase of
+/// the backoff so that the thread never yields while it holds the pool latch.
+const FRAME_LATCH_BUDGET: u32 = 6;
+
+/// Call `try_latch` until it succeeds or `FRAME_LATCH_BUDGET` rounds of backoff
+/// are spent. Returns None if the latch could not be obtained within the budget.
+fn latch_frame<G>(mut try_latch: impl FnMut() -> Option<G>) -> Option<G> {
+    let (res, _) = Backoff::spinning().retry(
+        |_| try_latch().ok_or(()),
+        |_, retries| retries < FRAME_LATCH_BUDGET,
+    );
+    res.ok()
+}
+
+/// Span of reading page `key` from disk into the buffer pool.
+fn page_fault_span(key: PageFrameId) -> tracing::Span {
+    tracing::debug_span!(
+        "page_fault",
+        container_id = key.p_key().c_id,
+        page_id = key.p_key().page_id
+    )
+}
+
 pub struct PageToFrame {
     map: HashMap<ContainerId, HashMap<PageId, usize>>, // (c_key, page_id) -> frame_index
 }
 
 impl PageToFrame {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         PageToFrame {
             map: HashMap::new(),
@@ -307,6 +338,121 @@ impl BufferPool {
 
         Ok(())
     }
+
+    /// Call `f` on every frame with the frame's read latch held.
+    /// Frames are taken in batches under the shared pool latch, which is released
+    /// between batches. A frame whose latch cannot be obtained within the budget is
+    /// skipped and retried in a later pass, so every frame is visited before this returns.
+    fn for_each_frame_shared<F>(&self, mut f: F) -> Result<(), MemPoolStatus>
+    where
+        F: FnMut(&FrameReadGuard) -> Result<(), MemPoolStatus>,
+    {
+        let frames = unsafe { &*self.frames.get() };
+        let mut pending: Vec<usize> = (0..frames.len()).collect();
+        while !pending.is_empty() {
+            let mut skipped = Vec::new();
+            for batch in pending.chunks(FRAME_BATCH_SIZE) {
+                self.shared();
+                for &index in batch {
+                    match latch_frame(|| frames[index].try_read()) {
+                        Some(frame) => f(&frame).inspect_err(|_| {
+                            self.release_shared();
+                        })?,
+                        None => skipped.push(index),
+                    }
+                }
+                self.release_shared();
+            }
+            if !skipped.is_empty() {
+                // Give the threads holding the skipped frames a chance to finish.
+                std::thread::yield_now();
+            }
+            pending = skipped;
+        }
+        Ok(())
+    }
+
+    /// Call `f` on every frame with the frame's write latch held.
+    /// Same batching and retry behavior as `for_each_frame_shared`, but the
+    /// exclusive pool latch is held while a batch is processed.
+    fn for_each_frame_exclusive<F>(&self, mut f: F) -> Result<(), MemPoolStatus>
+    where
+        F: FnMut(&mut FrameWriteGuard) -> Result<(), MemPoolStatus>,
+    {
+        let frames = unsafe { &*self.frames.get() };
+        let mut pending: Vec<usize> = (0..frames.len()).collect();
+        while !pending.is_empty() {
+            let mut skipped = Vec::new();
+            for batch in pending.chunks(FRAME_BATCH_SIZE) {
+                self.exclusive();
+                for &index in batch {
+                    match latch_frame(|| frames[index].try_write(false)) {
+                        Some(mut frame) => f(&mut frame).inspect_err(|_| {
+                            self.release_exclusive();
+                        })?,
+                        None => skipped.push(index),
+                    }
+                }
+                self.release_exclusive();
+            }
+            if !skipped.is_empty() {
+                // Give the threads holding the skipped frames a chance to finish.
+                std::thread::yield_now();
+            }
+            pending = skipped;
+        }
+        Ok(())
+    }
+
+    /// Clear every frame and make it available for eviction, writing dirty pages
+    /// to disk first if `write_back` is set.
+    /// Unlike flush_all, the exclusive pool latch is held until every frame is
+    /// cleared, so no page can be loaded into a frame that was already cleared.
+    /// A frame whose latch is busy is retried in a later pass; its holder does not
+    /// need the pool latch to release it.
+    fn clear_frames(&self, write_back: bool) -> Result<(), MemPoolStatus> {
+        self.exclusive();
+        let res = self.clear_frames_exclusive(write_back);
+        self.release_exclusive();
+        res
+    }
+
+    /// Body of clear_frames. The caller holds the exclusive pool latch.
+    fn clear_frames_exclusive(&self, write_back: bool) -> Result<(), MemPoolStatus> {
+        let frames = unsafe { &*self.frames.get() };
+        let page_to_frame = unsafe { &mut *self.page_to_frame.get() };
+
+        let mut pending: Vec<usize> = (0..frames.len()).collect();
+        while !pending.is_empty() {
+            let mut skipped = Vec::new();
+            for &index in &pending {
+                match latch_frame(|| frames[index].try_write(false)) {
+                    Some(mut frame) => {
+                        if write_back {
+                            self.write_victim_to_disk_if_dirty_w(&frame)?;
+                        }
+                        frame.clear();
+                    }
+                    None => skipped.push(index),
+                }
+            }
+            if !skipped.is_empty() {
+                // Give the threads holding the skipped frames a chance to finish.
+                std::thread::yield_now();
+            }
+            pending = skipped;
+        }
+
+        self.cfc.flush_all()?;
+
+        page_to_frame.clear();
+
+        while self.eviction_hints.pop().is_ok() {}
+        for i in 0..frames.len() {
+            self.eviction_hints.push(i).unwrap();
+        }
+        Ok(())
+    }
 }
 
 impl MemPool for BufferPool {
@@ -326,6 +472,34 @@ impl MemPool for BufferPool {
         Ok(())
     }
 
+    fn release_container(&self, c_key: ContainerId) -> Result<bool, MemPoolStatus> {
+        let frames = unsafe { &*self.frames.get() };
+        // The exclusive latch keeps pages of the container from being loaded meanwhile.
+        self.exclusive();
+        let page_to_frame = unsafe { &mut *self.page_to_frame.get() };
+        let mut latched = Vec::new();
+        for (_, frame_index) in page_to_frame.iter_container(c_key) {
+            match frames[*frame_index].try_write(false) {
+                Some(frame) => latched.push(frame),
+                None => {
+                    self.release_exclusive();
+                    return Ok(false);
+                }
+            }
+        }
+        for mut frame in latched {
+            if let Some(key) = *frame.page_id() {
+                page_to_frame.remove(&key);
+            }
+            frame.clear();
+            self.eviction_hints.push(frame.frame_id() as usize).unwrap();
+        }
+        let removed = self.cfc.remove_container(c_key);
+        self.release_exclusive();
+        removed?;
+        Ok(true)
+    }
+
     /// Create a new page for write in memory.
     /// NOTE: This function does not write the page to disk.
     /// See more at `handle_page_fault(key, new_page=true)`
@@ -565,7 +739,9 @@ impl MemPool for BufferPool {
                     self.release_exclusive();
 
                     // Read the wanted page from disk.
+                    let _span = page_fault_span(key).entered();
                     let container = self.cfc.get_container(key.p_key().c_id);
+                    let start = Instant::now();
                     container
                         .read_page(key.p_key().page_id, &mut victim)
                         .map(|()| {
@@ -573,6 +749,7 @@ impl MemPool for BufferPool {
                             victim.evict_info().reset();
                             victim.evict_info().update();
                         })?;
+                    storage_metrics().page_fault.record(start.elapsed());
                     victim.dirty().store(true, Ordering::Release); // Prepare the page for writing.
                     Ok(victim)
                 }
@@ -674,7 +851,9 @@ impl MemPool for BufferPool {
 
                     self.release_exclusive();
 
+                    let _span = page_fault_span(key).entered();
                     let container = self.cfc.get_container(key.p_key().c_id);
+                    let start = Instant::now();
                     container
                         .read_page(key.p_key().page_id, &mut victim)
                         .map(|()| {
@@ -682,6 +861,7 @@ impl MemPool for BufferPool {
                             victim.evict_info().reset();
                             victim.evict_info().update();
                         })?;
+                    storage_metrics().page_fault.record(start.elapsed());
                     Ok(victim.downgrade())
                 }
             }
@@ -692,31 +872,15 @@ impl MemPool for BufferPool {
         Ok(())
     }
 
+    /// Write every page that is dirty at call time to disk.
+    /// A frame that is reassigned to another page before it is visited had its
+    /// old page written back by the eviction path, so skipping busy frames and
+    /// coming back to them later does not lose any writes.
     fn flush_all(&self) -> Result<(), MemPoolStatus> {
-        self.shared();
-
-        let frames = unsafe { &*self.frames.get() };
-        for frame in frames.iter() {
-            let frame = loop {
-                if let Some(guard) = frame.try_read() {
-                    break guard;
-                }
-                // spin
-                std::hint::spin_loop();
-            };
-            self.write_victim_to_disk_if_dirty_r(&frame)
-                .inspect_err(|_| {
-                    self.release_shared();
-                })?;
-        }
+        self.for_each_frame_shared(|frame| self.write_victim_to_disk_if_dirty_r(frame))?;
 
         // Call fsync on all the files
-        self.cfc.flush_all().inspect_err(|_| {
-            self.release_shared();
-        })?;
-
-        self.release_shared();
-        Ok(())
+        self.cfc.flush_all()
     }
 
     fn fast_evict(&self, _frame_id: u32) -> Result<(), MemPoolStatus> {
@@ -768,35 +932,7 @@ impl MemPool for BufferPool {
     }
 
     fn reset(&self) -> Result<(), MemPoolStatus> {
-        self.exclusive();
-
-        let frames = unsafe { &*self.frames.get() };
-        let page_to_frame = unsafe { &mut *self.page_to_frame.get() };
-
-        for frame in frames.iter() {
-            let mut frame = loop {
-                if let Some(guard) = frame.try_write(false) {
-                    break guard;
-                }
-                // spin
-                std::hint::spin_loop();
-            };
-            frame.clear();
-        }
-
-        self.cfc.flush_all().inspect_err(|_| {
-            self.release_exclusive();
-        })?;
-
-        page_to_frame.clear();
-
-        while self.eviction_hints.pop().is_ok() {}
-        for i in 0..frames.len() {
-            self.eviction_hints.push(i).unwrap();
-        }
-
-        self.release_exclusive();
-        Ok(())
+        self.clear_frames(false)
     }
 
     // Reset the runtime stats
@@ -808,65 +944,19 @@ impl MemPool for BufferPool {
     /// This will write all the dirty pages to disk and flush the files.
     /// After this operation, the buffer pool will have all the frames cleared.
     fn flush_all_and_reset(&self) -> Result<(), MemPoolStatus> {
-        self.exclusive();
-
-        let frames = unsafe { &*self.frames.get() };
-        let page_to_frame = unsafe { &mut *self.page_to_frame.get() };
-
-        for frame in frames.iter() {
-            let mut frame = loop {
-                if let Some(guard) = frame.try_write(false) {
-                    break guard;
-                }
-                // spin
-                std::hint::spin_loop();
-            };
-            self.write_victim_to_disk_if_dirty_w(&frame)
-                .inspect_err(|_| {
-                    self.release_exclusive();
-                })?;
-            frame.clear();
-        }
-
-        self.cfc.flush_all().inspect_err(|_| {
-            self.release_exclusive();
-        })?;
-
-        page_to_frame.clear();
-
-        while self.eviction_hints.pop().is_ok() {}
-        for i in 0..frames.len() {
-            self.eviction_hints.push(i).unwrap();
-        }
-
-        self.release_exclusive();
-        Ok(())
+        self.clear_frames(true)
     }
 
     fn clear_dirty_flags(&self) -> Result<(), MemPoolStatus> {
-        self.exclusive();
-
-        let frames = unsafe { &*self.frames.get() };
-
-        for frame in frames.iter() {
-            let frame = loop {
-                if let Some(guard) = frame.try_write(false) {
-                    break guard;
-                }
-                // spin
-                std::hint::spin_loop();
-            };
+        self.for_each_frame_exclusive(|frame| {
             frame.dirty().store(false, Ordering::Release);
-        }
-
-        self.cfc.flush_all().inspect_err(|_| {
-            self.release_exclusive();
+            Ok(())
         })?;
 
+        self.cfc.flush_all()?;
+
         // container_to_file.clear();
         self.stats.clear();
-
-        self.release_exclusive();
         Ok(())
     }
 }
@@ -960,7 +1050,9 @@ pub fn get_test_bp(num_frames: usize) -> Arc<BufferPool> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::AtomicBool;
     use std::thread::{self};
+    use std::time::{Duration, Instant};
     use tempfile::TempDir;
 
     #[test]
@@ -1216,4 +1308,94 @@ mod tests {
         let stats = bp.eviction_stats();
         println!("{}", stats);
     }
+
+    #[test]
+    fn test_bp_flush_all_with_concurrent_writers() {
+        let num_frames = 4;
+        let bp = get_test_bp(num_frames);
+        let c_id = 0;
+
+        let keys: Vec<PageFrameId> = (0..num_frames * 4)
+            .map(|i| {
+                let mut guard = bp.create_new_page_for_write(c_id).unwrap();
+                guard[64] = i as u8;
+                guard.page_frame_id().unwrap()
+            })
+            .collect();
+
+        let done = AtomicBool::new(false);
+        let start = Instant::now();
+        thread::scope(|s| {
+            for t in 0..3 {
+                let (bp, keys, done) = (&bp, &keys, &done);
+                s.spawn(move || {
+                    let mut i = t;
+                    while !done.load(Ordering::Acquire) {
+                        if let Ok(mut guard) = bp.get_page_for_write(keys[i % keys.len()]) {
+                            guard[65] = guard[65].wrapping_add(1);
+                        }
+                        i += 1;
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..100 {
+                    bp.flush_all().unwrap();
+                }
+                done.store(true, Ordering::Release);
+            });
+        });
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "flush_all took {:?} under a concurrent write workload",
+            start.elapsed()
+        );
+
+        bp.run_checks();
+        bp.flush_all_and_reset().unwrap();
+        bp.run_checks();
+        for (i, key) in keys.iter().enumerate() {
+            let guard = bp.get_page_for_read(*key).unwrap();
+            assert_eq!(guard[64], i as u8);
+        }
+    }
+
+    #[test]
+    fn test_bp_reset_waits_for_latched_frames() {
+        let num_frames = 4;
+        let bp = get_test_bp(num_frames);
+        let c_id = 0;
+
+        let keys: Vec<PageFrameId> = (0..num_frames)
+            .map(|i| {
+                let mut guard = bp.create_new_page_for_write(c_id).unwrap();
+                guard[64] = i as u8;
+                guard.page_frame_id().unwrap()
+            })
+            .collect();
+
+        let latched = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let guard = bp.get_page_for_read(keys[0]).unwrap();
+                latched.store(true, Ordering::Release);
+                thread::sleep(Duration::from_millis(20));
+                drop(guard);
+            });
+            while !latched.load(Ordering::Acquire) {
+                std::hint::spin_loop();
+            }
+            bp.flush_all_and_reset().unwrap();
+        });
+
+        // The frame latched during the reset was cleared too.
+        for key in &keys {
+            assert!(!bp.is_in_mem(*key));
+        }
+        bp.run_checks();
+        for (i, key) in keys.iter().enumerate() {
+            let guard = bp.get_page_for_read(*key).unwrap();
+            assert_eq!(guard[64], i as u8);
+        }
+    }
 }
@@ -39,6 +39,12 @@ pub trait MemPool: Sync + Send {
     /// is virtually free.
     fn drop_container(&self, c_id: ContainerId) -> Result<(), MemPoolStatus>;
 
+    /// Release a dropped container so that its id can be reused.
+    /// The frames holding pages of the container are cleared without writing them
+    /// back and the container file is removed. Nothing is released, and false is
+    /// returned, if one of those frames is latched, e.g. by an open iterator.
+    fn release_container(&self, c_id: ContainerId) -> Result<bool, MemPoolStatus>;
+
     /// Create a new page for write.
     /// This function will allocate a new page in memory and return a FrameWriteGuard.
     /// In general, this function does not need to write the page to disk.
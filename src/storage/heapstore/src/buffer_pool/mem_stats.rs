@@ -88,6 +88,20 @@ impl MemoryStats {
                 .collect(),
         }
     }
+
+    /// Summarizes the pages requested from the buffer pool and read from or
+    /// written to disk since `previous`, e.g. around a query. The counters are
+    /// shared by every user of the pool.
+    pub fn io_since(&self, previous: &MemoryStats) -> String {
+        format!(
+            "Buffer pool: reads={} writes={} new_pages={}\nDisk: reads={} writes={}",
+            self.bp_read_frame.saturating_sub(previous.bp_read_frame),
+            self.bp_write_frame.saturating_sub(previous.bp_write_frame),
+            self.bp_new_page.saturating_sub(previous.bp_new_page),
+            self.disk_read.saturating_sub(previous.disk_read),
+            self.disk_write.saturating_sub(previous.disk_write)
+        )
+    }
 }
 
 impl std::fmt::Display for MemoryStats {
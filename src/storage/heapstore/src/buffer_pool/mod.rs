@@ -4,4 +4,4 @@ pub mod buffer_pool;
 pub mod buffer_pool_stats;
 pub mod eviction_policy;
 pub mod mem_pool_trait;
-mod mem_stats;
+pub mod mem_stats;
@@ -4,9 +4,11 @@ use crate::base_file::BaseFileTrait;
 #[cfg(feature = "mock")]
 use crate::base_file_mock::BaseFileMock as BaseFile;
 use crate::buffer_pool::mem_pool_trait::MemPoolStatus;
+use crate::durability::{FileSync, Fsync};
 use crate::file_stats::FileStats;
 use crate::page::Page;
 use common::ids::{AtomicPageId, ContainerId, PageId};
+use common::physical::config::Durability;
 use dashmap::DashMap;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
@@ -79,7 +81,12 @@ impl Container {
     }
 
     pub fn flush(&self) -> Result<(), std::io::Error> {
-        self.base_file.flush()
+        if !self.is_temp() {
+            // Nothing of a temporary container is written to the file.
+            self.base_file.flush()
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -91,6 +98,8 @@ pub struct ContainerFileCatalog {
     base_dir: PathBuf,
     /// A concurrent/thread-safe map of container ids to their corresponding containers.
     containers: DashMap<ContainerId, Arc<Container>>, // c_id -> Container
+    durability: Durability,
+    sync: Arc<dyn FileSync>,
 }
 
 impl ContainerFileCatalog {
@@ -105,6 +114,21 @@ impl ContainerFileCatalog {
     pub fn new<P: AsRef<Path>>(
         base_dir: P,
         remove_dir_on_drop: bool,
+    ) -> Result<Self, std::io::Error> {
+        Self::new_with_durability(
+            base_dir,
+            remove_dir_on_drop,
+            Durability::default(),
+            Arc::new(Fsync),
+        )
+    }
+
+    /// Like `new`, but `flush_all` syncs as `durability` requires, using `sync`.
+    pub fn new_with_durability<P: AsRef<Path>>(
+        base_dir: P,
+        remove_dir_on_drop: bool,
+        durability: Durability,
+        sync: Arc<dyn FileSync>,
     ) -> Result<Self, std::io::Error> {
         trace!("Creating/Reading containers in {:?}", &base_dir.as_ref());
         // Identify all the directories. A directory corresponds to a database.
@@ -125,7 +149,9 @@ impl ContainerFileCatalog {
                     .unwrap()
                     .parse()
                     .unwrap();
-                let fm = BaseFile::new(&base_dir, c_id).unwrap();
+                let fm = BaseFile::new(&base_dir, c_id)
+                    .unwrap()
+                    .with_sync(sync.clone());
                 containers.insert(c_id, Arc::new(Container::new(fm)));
             }
         }
@@ -134,9 +160,15 @@ impl ContainerFileCatalog {
             remove_dir_on_drop,
             base_dir: base_dir.as_ref().to_path_buf(),
             containers,
+            durability,
+            sync,
         })
     }
 
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
     pub fn container_ids(&self) -> Vec<ContainerId> {
         self.containers.iter().map(|c| *c.key()).collect()
     }
@@ -149,7 +181,9 @@ impl ContainerFileCatalog {
     pub fn get_container(&self, c_id: ContainerId) -> Arc<Container> {
         let container = self.containers.entry(c_id).or_insert_with(|| {
             let db_path = &self.base_dir;
-            let fm = BaseFile::new(db_path, c_id).unwrap();
+            let fm = BaseFile::new(db_path, c_id)
+                .unwrap()
+                .with_sync(self.sync.clone());
             Arc::new(Container::new(fm))
         });
         container.value().clone()
@@ -162,7 +196,9 @@ impl ContainerFileCatalog {
     pub fn register_container(&self, c_id: ContainerId, is_temp: bool) {
         self.containers.entry(c_id).or_insert_with(|| {
             let db_path = &self.base_dir;
-            let fm = BaseFile::new(db_path, c_id).unwrap();
+            let fm = BaseFile::new(db_path, c_id)
+                .unwrap()
+                .with_sync(self.sync.clone());
             if is_temp {
                 Arc::new(Container::new_temp(fm))
             } else {
@@ -171,6 +207,19 @@ impl ContainerFileCatalog {
         });
     }
 
+    pub fn contains(&self, c_id: ContainerId) -> bool {
+        self.containers.contains_key(&c_id)
+    }
+
+    /// Removes the container and deletes its file, if it has one.
+    pub fn remove_container(&self, c_id: ContainerId) -> Result<(), std::io::Error> {
+        self.containers.remove(&c_id);
+        match std::fs::remove_file(self.base_dir.join(c_id.to_string())) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_stats(&self) -> Vec<(ContainerId, (PageId, FileStats))> {
         let mut vec = Vec::new();
         for container in self.containers.iter() {
@@ -181,7 +230,11 @@ impl ContainerFileCatalog {
         vec
     }
 
+    /// Syncs every container, unless the durability level is none.
     pub fn flush_all(&self) -> Result<(), MemPoolStatus> {
+        if self.durability == Durability::None {
+            return Ok(());
+        }
         for container in self.containers.iter() {
             container.flush()?;
         }
@@ -0,0 +1,131 @@
+use common::metrics::storage_metrics;
+use libc::fsync;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Forces the pages written to a file to stable storage. Base files sync through
+/// this trait so that tests can count or fail syncs.
+pub trait FileSync: Send + Sync {
+    fn sync(&self, file: &File) -> Result<(), std::io::Error>;
+}
+
+/// Syncs with fsync.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fsync;
+
+impl FileSync for Fsync {
+    fn sync(&self, file: &File) -> Result<(), std::io::Error> {
+        let start = Instant::now();
+        let ret = unsafe { fsync(file.as_raw_fd()) };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        storage_metrics().fsync.record(start.elapsed());
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct GroupState {
+    /// Number of commits that asked for a sync.
+    requested: u64,
+    /// Every commit up to this number is synced.
+    synced: u64,
+    /// Whether a leader is collecting or syncing a group.
+    syncing: bool,
+}
+
+/// Coalesces concurrent commits onto one sync.
+///
+/// The first commit to arrive while no sync is running becomes the leader of a
+/// group. It waits `delay` for other commits to join, then syncs once for the
+/// whole group. Commits arriving while the leader syncs wait for it to finish and
+/// form the next group. If the sync fails the leader returns the error and the
+/// members of its group elect a new leader to try again.
+pub struct GroupCommit {
+    delay: Duration,
+    state: Mutex<GroupState>,
+    done: Condvar,
+}
+
+impl GroupCommit {
+    pub fn new(delay: Duration) -> Self {
+        GroupCommit {
+            delay,
+            state: Mutex::new(GroupState::default()),
+            done: Condvar::new(),
+        }
+    }
+
+    /// Returns once a call of `sync` that started after this call returned Ok.
+    pub fn commit<E>(&self, sync: impl Fn() -> Result<(), E>) -> Result<(), E> {
+        let mut state = self.state.lock().unwrap();
+        state.requested += 1;
+        let ticket = state.requested;
+        loop {
+            if state.synced >= ticket {
+                return Ok(());
+            }
+            if !state.syncing {
+                state.syncing = true;
+                drop(state);
+                std::thread::sleep(self.delay);
+                let group = self.state.lock().unwrap().requested;
+                let result = sync();
+                let mut state = self.state.lock().unwrap();
+                state.syncing = false;
+                if result.is_ok() {
+                    state.synced = state.synced.max(group);
+                }
+                self.done.notify_all();
+                return result;
+            }
+            state = self.done.wait(state).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_group_commit_coalesces() {
+        let group = Arc::new(GroupCommit::new(Duration::from_millis(50)));
+        let syncs = Arc::new(AtomicUsize::new(0));
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let group = group.clone();
+                let syncs = syncs.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    group
+                        .commit(|| {
+                            syncs.fetch_add(1, Ordering::SeqCst);
+                            Ok::<(), ()>(())
+                        })
+                        .unwrap();
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let syncs = syncs.load(Ordering::SeqCst);
+        assert!(syncs >= 1 && syncs < threads, "{} syncs", syncs);
+    }
+
+    #[test]
+    fn test_group_commit_retries_failed_sync() {
+        let group = GroupCommit::new(Duration::ZERO);
+        assert_eq!(group.commit(|| Err("disk full")), Err("disk full"));
+        assert_eq!(group.commit(|| Ok::<(), &str>(())), Ok(()));
+    }
+}
@@ -3,35 +3,102 @@ use crate::buffer_pool::buffer_frame::FrameWriteGuard;
 use crate::buffer_pool::mem_pool_trait::MemPool;
 use crate::buffer_pool::mem_pool_trait::PageFrameId;
 use crate::heap_page;
-use crate::heap_page::HeapPage;
+use crate::heap_page::{HeapPage, UpdateOutcome};
+use crate::page::Page;
+use crate::retry_policy::{pool_error, RetryPolicy};
 #[allow(unused_imports)]
 use common::ids::AtomicPageId;
+use common::physical::config::FitPolicy;
+use common::physical::page_dictionary::{is_encoded, PageDictionary, PageFilter};
 use common::prelude::*;
+use common::traits::storage_trait::{CompactionOutcome, StorageReport};
+use common::PAGE_SIZE;
+use std::collections::HashMap;
 #[allow(unused_imports)]
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+/// Maximum number of data pages read to build a storage report. Larger files
+/// are sampled at an even stride and the page counts are scaled up.
+pub(crate) const STORAGE_REPORT_MAX_SAMPLED_PAGES: usize = 256;
+
+/// Compaction moves the values of pages that are less than this percent
+/// utilized to other pages.
+pub(crate) const RELOCATE_BELOW_UTILIZATION: usize = 25;
 
 /// The struct for a heap file.
 pub(crate) struct HeapFile<T: MemPool> {
     c_id: ContainerId,
     bp: Arc<T>,
     last_insert_page: AtomicPageId,
+    retry: RetryPolicy,
+    fit: FitPolicy,
+    /// Columns of the stored tuples that new rows keep in the dictionary of
+    /// their page.
+    dictionary_columns: RwLock<Vec<usize>>,
+    /// Dictionaries of the pages read or written so far, `None` for pages
+    /// without one. Scans use them to skip pages without reading them.
+    dictionaries: RwLock<HashMap<PageId, Option<Arc<PageDictionary>>>>,
+}
+
+/// Reads the dictionary of a page, if it has one.
+fn read_dictionary(page: &Page) -> Result<Option<PageDictionary>, FairyError> {
+    let Some(slot_id) = page.get_dictionary_slot() else {
+        return Ok(None);
+    };
+    let bytes = page.get_value(slot_id).ok_or(FairyError::StorageError)?;
+    PageDictionary::from_bytes(bytes).map(Some)
+}
+
+/// Writes `dict` as the dictionary of a page. Returns false if the page has no
+/// room for it.
+fn write_dictionary(page: &mut Page, dict: &PageDictionary, fit: FitPolicy) -> bool {
+    let bytes = dict.to_bytes();
+    match page.get_dictionary_slot() {
+        Some(slot_id) => matches!(
+            page.update_value_with(slot_id, &bytes, fit),
+            Some(UpdateOutcome::Updated | UpdateOutcome::UpdatedMoved)
+        ),
+        None => match page.add_value_with(&bytes, fit) {
+            Some(slot_id) => {
+                page.set_dictionary_slot(Some(slot_id));
+                true
+            }
+            None => false,
+        },
+    }
 }
 
 /// HeapFile required functions
 impl<T: MemPool> HeapFile<T> {
     /// Helper function to fetch a page for read from the buffer pool.
-    fn get_page_for_read(&self, page_id: PageId) -> FrameReadGuard {
-        self.bp
-            .get_page_for_read(PageFrameId::new(self.c_id, page_id))
-            .unwrap()
+    /// Transient failures are retried according to the retry policy.
+    fn get_page_for_read(&self, page_id: PageId) -> Result<FrameReadGuard, FairyError> {
+        let key = PageFrameId::new(self.c_id, page_id);
+        self.retry
+            .retry(|| self.bp.get_page_for_read(key))
+            .map_err(|e| pool_error(e, || self.bp.stats()))
     }
 
     /// Helper function to fetch a page for write from the buffer pool.
-    fn get_page_for_write(&self, page_id: PageId) -> FrameWriteGuard {
-        self.bp
-            .get_page_for_write(PageFrameId::new(self.c_id, page_id))
-            .unwrap()
+    /// Transient failures are retried according to the retry policy.
+    fn get_page_for_write(&self, page_id: PageId) -> Result<FrameWriteGuard, FairyError> {
+        let key = PageFrameId::new(self.c_id, page_id);
+        self.retry
+            .retry(|| self.bp.get_page_for_write(key))
+            .map_err(|e| pool_error(e, || self.bp.stats()))
+    }
+
+    /// Use `retry` to retry transient buffer pool failures.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use `fit` to place values in pages.
+    pub fn with_fit_policy(mut self, fit: FitPolicy) -> Self {
+        self.fit = fit;
+        self
     }
 
     /// Create a brand-new heap file for container `c_id`.
@@ -40,9 +107,10 @@ impl<T: MemPool> HeapFile<T> {
         // You may not end up using the header page, but some tests will assume this.
 
         // Add any extra initialization code in this function.
-        let mut header = mem_pool
-            .create_new_page_for_write(c_id)
-            .map_err(|_| FairyError::StorageError)?;
+        let retry = RetryPolicy::default();
+        let mut header = retry
+            .retry(|| mem_pool.create_new_page_for_write(c_id))
+            .map_err(|e| pool_error(e, || mem_pool.stats()))?;
 
         header.init_heap_page();
 
@@ -50,6 +118,10 @@ impl<T: MemPool> HeapFile<T> {
             c_id,
             bp: mem_pool.clone(),
             last_insert_page: AtomicPageId::new(0),
+            retry,
+            fit: FitPolicy::default(),
+            dictionary_columns: RwLock::default(),
+            dictionaries: RwLock::default(),
         };
         Ok(heap_file)
     }
@@ -63,7 +135,12 @@ impl<T: MemPool> HeapFile<T> {
         let hf = HeapFile {
             c_id,
             bp: mem_pool.clone(),
-            last_insert_page: AtomicPageId::new(max_page),
+            // The page count is one past the last allocated page id.
+            last_insert_page: AtomicPageId::new(max_page.saturating_sub(1)),
+            retry: RetryPolicy::default(),
+            fit: FitPolicy::default(),
+            dictionary_columns: RwLock::default(),
+            dictionaries: RwLock::default(),
         };
 
         Ok(hf)
@@ -78,10 +155,119 @@ impl<T: MemPool> HeapFile<T> {
 
     /// Read a value at (page_id, slot_id) from the heap file.
     pub fn get_val(&self, page_id: PageId, slot_id: SlotId) -> Result<Vec<u8>, FairyError> {
-        let page = self.get_page_for_read(page_id);
-        page.get_value(slot_id)
-            .map(|s| s.to_vec())
-            .ok_or(FairyError::StorageError)
+        let page = self.get_page_for_read(page_id)?;
+        let bytes = page
+            .get_value(slot_id)
+            .filter(|_| page.get_dictionary_slot() != Some(slot_id))
+            .ok_or(FairyError::StorageError)?;
+        match self.page_dictionary(page_id, &page)? {
+            Some(dict) => dict.decode(bytes),
+            None => Ok(bytes.to_vec()),
+        }
+    }
+
+    /// Keeps `columns` of the tuples added from now on in the dictionary of
+    /// their page.
+    pub fn set_dictionary_columns(&self, columns: Vec<usize>) {
+        *self.dictionary_columns.write().unwrap() = columns;
+    }
+
+    /// Returns the dictionary of a page, reading it from `page` unless it is
+    /// cached. The caller must hold a latch on the page.
+    fn page_dictionary(
+        &self,
+        page_id: PageId,
+        page: &Page,
+    ) -> Result<Option<Arc<PageDictionary>>, FairyError> {
+        if let Some(dict) = self.dictionaries.read().unwrap().get(&page_id) {
+            return Ok(dict.clone());
+        }
+        let dict = read_dictionary(page)?.map(Arc::new);
+        self.dictionaries
+            .write()
+            .unwrap()
+            .insert(page_id, dict.clone());
+        Ok(dict)
+    }
+
+    /// Encodes `val` with the dictionary of a page. Returns None if the value is
+    /// stored as it is, or the stored form and, if the dictionary changes, the
+    /// new dictionary.
+    #[allow(clippy::type_complexity)]
+    fn encode_for_page(
+        &self,
+        page_id: PageId,
+        page: &Page,
+        val: &[u8],
+    ) -> Result<Option<(Vec<u8>, Option<PageDictionary>)>, FairyError> {
+        let columns = self.dictionary_columns.read().unwrap();
+        let current = self.page_dictionary(page_id, page)?;
+        if columns.is_empty() && current.is_none() {
+            return Ok(None);
+        }
+        let mut dict = current.as_deref().cloned().unwrap_or_default();
+        // Rows written before the dictionary hold their values inline.
+        dict.add_columns(&columns, page.iter().next().is_some());
+        let row = dict.encode(val);
+        let changed = current.as_deref() != Some(&dict);
+        Ok(Some((row, changed.then_some(dict))))
+    }
+
+    /// Writes the new dictionary `dict` of a page, if any, and then a row with
+    /// `write`, which returns None if the row does not fit. The old dictionary
+    /// is restored if the row does not fit.
+    fn write_with_dictionary<R>(
+        &self,
+        page_id: PageId,
+        page: &mut Page,
+        dict: Option<PageDictionary>,
+        write: impl FnOnce(&mut Page) -> Option<R>,
+    ) -> Option<R> {
+        let Some(dict) = dict else {
+            return write(page);
+        };
+        let old_slot = page.get_dictionary_slot();
+        let old = old_slot.and_then(|slot_id| page.get_value(slot_id).map(<[u8]>::to_vec));
+        if !write_dictionary(page, &dict, self.fit) {
+            return None;
+        }
+        let res = write(page);
+        if res.is_some() {
+            self.dictionaries
+                .write()
+                .unwrap()
+                .insert(page_id, Some(Arc::new(dict)));
+            return res;
+        }
+        match (old_slot, old) {
+            // The old dictionary is smaller, so it fits back.
+            (Some(slot_id), Some(old)) => {
+                page.update_value_with(slot_id, &old, self.fit);
+            }
+            _ => {
+                if let Some(slot_id) = page.get_dictionary_slot() {
+                    page.delete_value(slot_id);
+                }
+                page.set_dictionary_slot(None);
+            }
+        }
+        None
+    }
+
+    /// Adds `val` to a page, encoded with the dictionary of the page. Returns
+    /// None if the page has no room for it.
+    fn add_to_page(
+        &self,
+        page_id: PageId,
+        page: &mut Page,
+        val: &[u8],
+    ) -> Result<Option<SlotId>, FairyError> {
+        let Some((row, dict)) = self.encode_for_page(page_id, page, val)? else {
+            return Ok(page.add_value_with(val, self.fit));
+        };
+        Ok(self.write_with_dictionary(page_id, page, dict, |page| {
+            page.add_value_with(&row, self.fit)
+        }))
     }
 
     // Delete a value at (page_id, slot_id) from the heap file.
@@ -89,22 +275,72 @@ impl<T: MemPool> HeapFile<T> {
         if page_id == 0 || page_id > self.num_pages() {
             return Err(FairyError::StorageError);
         }
-        let mut frame = self.get_page_for_write(page_id);
+        let mut frame = self.get_page_for_write(page_id)?;
+        if frame.get_dictionary_slot() == Some(slot_id) {
+            return Err(FairyError::StorageError);
+        }
         frame
             .delete_value(slot_id)
             .ok_or(FairyError::StorageError)?;
         Ok(())
     }
 
+    /// Deletes all the values of the heap file and returns how many there were.
+    /// Its pages are reset and kept, so that iterators over them see empty
+    /// pages, and inserts start over from the first data page.
+    pub fn truncate(&self) -> Result<usize, FairyError> {
+        let mut deleted = 0;
+        for page_id in 1..self.num_pages() {
+            let mut page = self.get_page_for_write(page_id)?;
+            deleted += page.iter().count();
+            page.init_heap_page();
+            self.dictionaries.write().unwrap().insert(page_id, None);
+        }
+        self.last_insert_page.store(1, Ordering::Relaxed);
+        Ok(deleted)
+    }
+
+    /// Update the value at (page_id, slot_id). The value stays on its page when
+    /// the page has room for it, otherwise it is deleted and reinserted on
+    /// another page. Returns the ValueId of the updated value.
     pub fn update_val(
         &self,
         page_id: PageId,
         slot_id: SlotId,
         val: &[u8],
     ) -> Result<ValueId, FairyError> {
-        self.delete_val(page_id, slot_id)?;
-        let new_vid = self.add_val(val)?;
-        Ok(new_vid)
+        if page_id == 0 || page_id > self.num_pages() {
+            return Err(FairyError::StorageError);
+        }
+        let mut frame = self.get_page_for_write(page_id)?;
+        if frame.get_value(slot_id).is_none() || frame.get_dictionary_slot() == Some(slot_id) {
+            return Err(FairyError::StorageError);
+        }
+        let (row, dict) = self
+            .encode_for_page(page_id, &frame, val)?
+            .unwrap_or_else(|| (val.to_vec(), None));
+        let updated = self.write_with_dictionary(page_id, &mut frame, dict, |page| {
+            match page.update_value_with(slot_id, &row, self.fit) {
+                Some(UpdateOutcome::Updated | UpdateOutcome::UpdatedMoved) => Some(()),
+                _ => None,
+            }
+        });
+        drop(frame);
+        match updated {
+            Some(()) => Ok(ValueId {
+                container_id: self.c_id,
+                page_id: Some(page_id),
+                slot_id: Some(slot_id),
+                segment_id: Some(0),
+            }),
+            None => {
+                // Insert the new value before deleting the old one, so a failed
+                // insert leaves the old value in place.
+                let new_vid = self.add_val(val)?;
+                self.delete_val(page_id, slot_id)?;
+                Ok(new_vid)
+            }
+        }
     }
 
     // This function is not implemented in a thread-safe way. Can cause deadlocks when used in a multi-threaded environment.
@@ -112,9 +348,9 @@ impl<T: MemPool> HeapFile<T> {
     pub fn add_val(&self, val: &[u8]) -> Result<ValueId, FairyError> {
         let max_pid = self.num_pages();
         let last = self.last_insert_page.load(Ordering::Relaxed);
-        if last > 0 && last <= max_pid {
-            let mut frame = self.get_page_for_write(last);
-            if let Some(slot) = frame.add_value(val) {
+        if last > 0 && last < max_pid {
+            let mut frame = self.get_page_for_write(last)?;
+            if let Some(slot) = self.add_to_page(last, &mut frame, val)? {
                 // still fits on same page
                 self.last_insert_page.store(last, Ordering::Relaxed);
                 return Ok(ValueId {
@@ -128,12 +364,19 @@ impl<T: MemPool> HeapFile<T> {
 
         // 2) Nope, allocate a brand‐new page at the end
         let mut new_frame = self
-            .bp
-            .create_new_page_for_write(self.c_id)
-            .map_err(|_| FairyError::StorageError)?;
+            .retry
+            .retry(|| self.bp.create_new_page_for_write(self.c_id))
+            .map_err(|e| pool_error(e, || self.bp.stats()))?;
         new_frame.init_heap_page();
-        let slot = new_frame.add_value(val).ok_or(FairyError::StorageError)?;
         let pid = new_frame.page_id().unwrap().page_id;
+        self.dictionaries.write().unwrap().remove(&pid);
+        // A value that only fits without a dictionary is stored as it is.
+        let slot = match self.add_to_page(pid, &mut new_frame, val)? {
+            Some(slot) => slot,
+            None => new_frame
+                .add_value_with(val, self.fit)
+                .ok_or(FairyError::StorageError)?,
+        };
 
         // remember for next time
         self.last_insert_page.store(pid, Ordering::Relaxed);
@@ -159,6 +402,167 @@ impl<T: MemPool> HeapFile<T> {
         Ok(val_ids)
     }
 
+    /// Summarize the space usage of the data pages of this heap file.
+    /// Pages are only read with shared latches, and at most
+    /// `STORAGE_REPORT_MAX_SAMPLED_PAGES` pages are read.
+    pub fn storage_report(&self) -> Result<StorageReport, FairyError> {
+        // Page 0 is the header page.
+        let num_pages = (self.num_pages() as usize).saturating_sub(1);
+        let stride = num_pages.div_ceil(STORAGE_REPORT_MAX_SAMPLED_PAGES).max(1);
+
+        let mut report = StorageReport {
+            container_id: self.c_id,
+            num_pages,
+            ..Default::default()
+        };
+        let mut record_bytes = 0;
+        for page_id in (1..=num_pages).step_by(stride) {
+            let page = self.get_page_for_read(page_id as PageId)?;
+            let usable = PAGE_SIZE - page.get_header_size();
+            let free = page.remaining_size().min(usable);
+            let utilization = (usable - free) * 100 / usable;
+            report.sampled_pages += 1;
+            report.pages_under_25 += (utilization < 25) as usize;
+            report.pages_under_50 += (utilization < 50) as usize;
+            report.pages_under_75 += (utilization < 75) as usize;
+            report.wasted_bytes += free;
+
+            let mut live = 0;
+            for (bytes, _) in page.iter() {
+                live += 1;
+                record_bytes += bytes.len();
+            }
+            report.num_records += live;
+            report.dead_slots += page.slot_count() - live;
+            report.empty_pages += (live == 0) as usize;
+        }
+
+        report.avg_record_size = record_bytes.checked_div(report.num_records).unwrap_or(0);
+        if report.sampled_pages > 0 && report.sampled_pages < num_pages {
+            let scale = |n: usize| n * num_pages / report.sampled_pages;
+            report.pages_under_25 = scale(report.pages_under_25);
+            report.pages_under_50 = scale(report.pages_under_50);
+            report.pages_under_75 = scale(report.pages_under_75);
+            report.num_records = scale(report.num_records);
+            report.dead_slots = scale(report.dead_slots);
+            report.wasted_bytes = scale(report.wasted_bytes);
+            report.empty_pages = scale(report.empty_pages);
+        }
+        Ok(report)
+    }
+
+    /// Utilization in percent and number of values of a data page.
+    fn page_usage(&self, page_id: PageId) -> Result<(usize, usize), FairyError> {
+        let page = self.get_page_for_read(page_id)?;
+        let usable = PAGE_SIZE - page.get_header_size();
+        let free = page.remaining_size().min(usable);
+        Ok(((usable - free) * 100 / usable, page.iter().count()))
+    }
+
+    /// Compacts the data pages `start_page..start_page + max_pages` (starting
+    /// over at the first page if `start_page` is past the end). The values of
+    /// pages less than `RELOCATE_BELOW_UTILIZATION` percent utilized are moved
+    /// to the fullest pages of the round that hold them, least utilized pages
+    /// first, and pages left empty are reset. Then the holes of every page of
+    /// the round are closed and trailing free slots are dropped.
+    ///
+    /// Relocated values get new value ids: the caller must make sure that
+    /// nobody holds value ids of this file.
+    pub fn compact(
+        &self,
+        start_page: PageId,
+        max_pages: usize,
+    ) -> Result<CompactionOutcome, FairyError> {
+        // Page 0 is the header page.
+        let num_pages = self.num_pages().saturating_sub(1);
+        let mut outcome = CompactionOutcome::default();
+        if num_pages == 0 || max_pages == 0 {
+            return Ok(outcome);
+        }
+        let first = if (1..=num_pages).contains(&start_page) {
+            start_page
+        } else {
+            1
+        };
+        let last = num_pages.min(first.saturating_add(max_pages as PageId - 1));
+        let round: Vec<PageId> = (first..=last).collect();
+        outcome.pages_visited = round.len();
+        outcome.next_page = if last == num_pages { 1 } else { last + 1 };
+
+        let mut usage = Vec::with_capacity(round.len());
+        for &page_id in &round {
+            usage.push((page_id, self.page_usage(page_id)?));
+        }
+        usage.sort_by_key(|&(_, (utilization, _))| utilization);
+        let mut emptied = Vec::new();
+        for &(donor, _) in &usage {
+            // Earlier donors may have filled this page up since the round began.
+            let (utilization, live) = self.page_usage(donor)?;
+            if live == 0 || utilization >= RELOCATE_BELOW_UTILIZATION {
+                continue;
+            }
+            // Values move as plain tuples and are encoded for their new page.
+            let values: Vec<(SlotId, Vec<u8>)> = {
+                let page = self.get_page_for_read(donor)?;
+                let dict = self.page_dictionary(donor, &page)?;
+                page.iter()
+                    .map(|(bytes, slot)| match &dict {
+                        Some(dict) => Ok((slot, dict.decode(bytes)?)),
+                        None => Ok((slot, bytes.to_vec())),
+                    })
+                    .collect::<Result<_, FairyError>>()?
+            };
+            let mut free = Vec::new();
+            for &page_id in &round {
+                if page_id != donor && !emptied.contains(&page_id) {
+                    let page = self.get_page_for_read(page_id)?;
+                    free.push((page_id, page.remaining_size()));
+                }
+            }
+            // Fill the fullest pages first.
+            free.sort_by_key(|&(_, remaining)| remaining);
+            let mut moved = 0;
+            for (slot, bytes) in &values {
+                let needed = bytes.len() + heap_page::SLOT_METADATA_SIZE;
+                let Some(target) = free.iter_mut().find(|(_, remaining)| *remaining >= needed)
+                else {
+                    break;
+                };
+                let mut page = self.get_page_for_write(target.0)?;
+                if self.add_to_page(target.0, &mut page, bytes)?.is_none() {
+                    target.1 = 0;
+                    continue;
+                }
+                target.1 = page.remaining_size();
+                drop(page);
+                self.get_page_for_write(donor)?
+                    .delete_value(*slot)
+                    .ok_or(FairyError::StorageError)?;
+                moved += 1;
+            }
+            outcome.values_relocated += moved;
+            if moved == values.len() {
+                self.get_page_for_write(donor)?.init_heap_page();
+                self.dictionaries.write().unwrap().insert(donor, None);
+                emptied.push(donor);
+                outcome.pages_emptied += 1;
+            }
+        }
+
+        for &page_id in &round {
+            if emptied.contains(&page_id) {
+                continue;
+            }
+            let mut page = self.get_page_for_write(page_id)?;
+            let trimmed = page.trim_free_slots();
+            if trimmed > 0 || page.free_regions().len() > 1 {
+                page.compact_page();
+                outcome.pages_compacted += 1;
+            }
+        }
+        Ok(outcome)
+    }
+
     pub fn iter(self: &Arc<Self>) -> HeapFileIter<T> {
         // Create the HeapFileIter
         HeapFileIter::new_from(self.clone(), 0, 0)
@@ -168,6 +572,14 @@ impl<T: MemPool> HeapFile<T> {
         // Create the HeapFileIter
         HeapFileIter::new_from(self.clone(), page_id, slot_id)
     }
+
+    /// Like `iter`, but skips the pages whose dictionary shows that none of
+    /// their rows satisfies `filter`.
+    pub fn iter_filtered(self: &Arc<Self>, filter: PageFilter) -> HeapFileIter<T> {
+        let mut iter = HeapFileIter::new_from(self.clone(), 0, 0);
+        iter.filter = (!filter.is_empty()).then_some(filter);
+        iter
+    }
 }
 
 pub struct HeapFileIter<T: MemPool> {
@@ -183,6 +595,9 @@ pub struct HeapFileIter<T: MemPool> {
     current_frame: Option<FrameReadGuard<'static>>,
     current_iter: Option<heap_page::HeapPageIter<'static>>,
     value_buffer: Vec<u8>,
+    /// Dictionary of the current page.
+    dictionary: Option<Arc<PageDictionary>>,
+    filter: Option<PageFilter>,
 }
 
 impl<T: MemPool> HeapFileIter<T> {
@@ -199,15 +614,22 @@ impl<T: MemPool> HeapFileIter<T> {
             current_iter: None,
             // Pre-allocate with a reasonable capacity to avoid reallocations
             value_buffer: Vec::with_capacity(4096),
+            dictionary: None,
+            filter: None,
         }
     }
 
     // Helper function to get a page for read from the buffer pool.
+    // The iterator cannot return an error, so it panics with the error once the
+    // retry budget is spent.
     fn get_page(&self, page_id: PageId) -> FrameReadGuard<'static> {
         // Safety: self.heapfile object has a reference to the buffer pool
         // which makes sure that the frame is not deallocated while this
         // (self) object is alive.
-        let page = self.heapfile.get_page_for_read(page_id);
+        let page = self
+            .heapfile
+            .get_page_for_read(page_id)
+            .unwrap_or_else(|e| panic!("{}", e));
         unsafe { std::mem::transmute::<FrameReadGuard, FrameReadGuard<'static>>(page) }
     }
 
@@ -218,15 +640,43 @@ impl<T: MemPool> HeapFileIter<T> {
         }
     }
 
+    /// Whether the filter rules out a page by its cached dictionary.
+    fn skip_page(&self, page_id: PageId) -> bool {
+        let Some(filter) = &self.filter else {
+            return false;
+        };
+        matches!(
+            self.heapfile.dictionaries.read().unwrap().get(&page_id),
+            Some(Some(dict)) if !filter.may_match(dict)
+        )
+    }
+
     fn load_page(&mut self) -> bool {
         self.current_frame = None;
         self.current_iter = None;
+        self.dictionary = None;
 
-        if self.page_id > self.max_page {
-            return false;
-        }
-
-        let frame = self.get_page(self.page_id);
+        let frame = loop {
+            while self.page_id < self.max_page && self.skip_page(self.page_id) {
+                self.page_id += 1;
+                self.slot_id = 0;
+            }
+            // Pages are numbered 0..num_pages. Reading past the end would map a page
+            // that does not exist yet into the buffer pool under the id the next new
+            // page gets.
+            if self.page_id >= self.max_page {
+                return false;
+            }
+            let frame = self.get_page(self.page_id);
+            self.dictionary = self
+                .heapfile
+                .page_dictionary(self.page_id, &frame)
+                .unwrap_or_else(|e| panic!("{}", e));
+            // Reading the page cached its dictionary.
+            if !self.skip_page(self.page_id) {
+                break frame;
+            }
+        };
 
         let iter = if self.slot_id > 0 {
             frame.iter_from(self.slot_id)
@@ -270,7 +720,15 @@ impl<T: MemPool> Iterator for HeapFileIter<T> {
             if let Some(iter) = &mut self.current_iter {
                 if let Some((bytes, slot)) = iter.next() {
                     self.value_buffer.clear();
-                    self.value_buffer.extend_from_slice(bytes);
+                    match &self.dictionary {
+                        // A row that does not decode is returned as it is
+                        // stored, for the reader to report.
+                        Some(dict) if is_encoded(bytes) => match dict.decode(bytes) {
+                            Ok(val) => self.value_buffer = val,
+                            Err(_) => self.value_buffer.extend_from_slice(bytes),
+                        },
+                        _ => self.value_buffer.extend_from_slice(bytes),
+                    }
 
                     let value_id = ValueId {
                         container_id: self.heapfile.c_id,
@@ -108,4 +108,101 @@ mod test {
         #[cfg(feature = "hs_33500")]
         assert!(bp.disk_size() > 0, "Eviction not working");
     }
+
+    #[test]
+    fn hs_hf_update_grows_until_moved() {
+        let cid = 0;
+        let bp = get_test_bp(BP_FRAMES);
+        let hf = Arc::new(HeapFile::new(cid, bp.clone()).unwrap());
+
+        // Fill the first data page with 30 values of 100 bytes
+        let to_insert = gen_values(30);
+        let val_ids = hf.add_vals(to_insert.clone().into_iter()).unwrap();
+        let first = val_ids[0];
+        assert!(val_ids.iter().all(|id| id.page_id == first.page_id));
+
+        // Grow the first value until it no longer fits on its page
+        let mut val_id = first;
+        let mut size = 100;
+        while val_id.page_id == first.page_id {
+            size += 100;
+            let bigger = gen_bytes(0, size);
+            val_id = hf
+                .update_val(val_id.page_id.unwrap(), val_id.slot_id.unwrap(), &bigger)
+                .unwrap();
+            let val = hf
+                .get_val(val_id.page_id.unwrap(), val_id.slot_id.unwrap())
+                .unwrap();
+            assert_eq!(val, bigger);
+        }
+
+        // The old slot is freed and the other values are untouched
+        assert!(hf
+            .get_val(first.page_id.unwrap(), first.slot_id.unwrap())
+            .is_err());
+        for (id, expected) in val_ids.iter().zip(to_insert.iter()).skip(1) {
+            let val = hf
+                .get_val(id.page_id.unwrap(), id.slot_id.unwrap())
+                .unwrap();
+            assert_eq!(&val, expected);
+        }
+        let values = hf.iter().map(|(val, _)| val).collect::<Vec<_>>();
+        assert_eq!(values.len(), to_insert.len());
+        assert!(values.contains(&gen_bytes(0, size)));
+    }
+
+    #[test]
+    fn hs_hfiter_concurrent_scans_small_pool() {
+        // Eight scans share a pool of two frames, so most page requests first
+        // find every frame latched and have to wait for another scan.
+        let cid = 0;
+        let bp = get_test_bp(2);
+        let hf = Arc::new(HeapFile::new(cid, bp.clone()).unwrap());
+
+        let to_insert = gen_values(500);
+        hf.add_vals(to_insert.clone().into_iter()).unwrap();
+        assert!(hf.num_pages() > 4);
+
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..3 {
+                        let values = hf.iter().map(|(val, _)| val).collect::<Vec<_>>();
+                        assert_eq!(values, to_insert);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn hs_hf_truncate_during_scan() {
+        let cid = 0;
+        let bp = get_test_bp(BP_FRAMES);
+        let hf = Arc::new(HeapFile::new(cid, bp.clone()).unwrap());
+
+        let to_insert = gen_values(2000);
+        hf.add_vals(to_insert.clone().into_iter()).unwrap();
+        let num_pages = hf.num_pages();
+        assert!(num_pages > 4);
+
+        // The scan sees some of the values, each once, and does not fail.
+        let scanned = std::thread::scope(|s| {
+            let scan = s.spawn(|| hf.iter().map(|(val, _)| val).collect::<Vec<_>>());
+            assert_eq!(hf.truncate().unwrap(), to_insert.len());
+            scan.join().unwrap()
+        });
+        assert!(scanned.len() <= to_insert.len());
+        assert!(scanned.iter().all(|val| to_insert.contains(val)));
+        assert_eq!(hf.iter().count(), 0);
+
+        // Inserts reuse the emptied pages.
+        let val_id = hf.add_val(&to_insert[0]).unwrap();
+        assert_eq!(val_id.page_id, Some(1));
+        assert_eq!(hf.num_pages(), num_pages);
+        assert_eq!(
+            hf.iter().map(|(val, _)| val).collect::<Vec<_>>(),
+            [to_insert[0].clone()]
+        );
+    }
 }
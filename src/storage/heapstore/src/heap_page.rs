@@ -1,3 +1,4 @@
+use common::physical::config::FitPolicy;
 use common::prelude::*;
 #[allow(unused_imports)]
 use common::PAGE_SIZE;
@@ -25,6 +26,18 @@ pub(crate) const LOWEST_AVAIL_OFFSET: usize = NEXT_FREE_SLOT_OFFSET + 2;
 pub(crate) const REMAINING_SIZE_OFFSET: usize = LOWEST_AVAIL_OFFSET + 2;
 
 pub(crate) const OFFSET_SIZE: usize = mem::size_of::<Offset>();
+
+/// The result of updating a value in a heap page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// The new value was written over the old one.
+    Updated,
+    /// The new value was written at a new offset in the page. The SlotId is unchanged.
+    UpdatedMoved,
+    /// The new value does not fit in the page, which is `required` bytes short.
+    /// The old value is left in place.
+    NeedsSpace { required: usize },
+}
 /// This is trait of a HeapPage for the Page struct.
 ///
 /// The page header size is fixed to `PAGE_FIXED_HEADER_LEN` bytes and you will use
@@ -69,6 +82,26 @@ pub trait HeapPage {
 
     /// Compact all live records to the end of the page.
     fn compact_page(&mut self);
+
+    /// Drop the free slots at the end of the slot directory, returning their
+    /// space to the page. Returns the number of slots dropped.
+    fn trim_free_slots(&mut self) -> usize;
+
+    /// Free regions of the data area as (offset, length), lowest first: the space
+    /// between the slot directory and the lowest value, and the holes between
+    /// values.
+    fn free_regions(&self) -> Vec<(usize, usize)>;
+
+    /// Like `add_value`, placing the bytes as `fit` says.
+    fn add_value_with(&mut self, bytes: &[u8], fit: FitPolicy) -> Option<SlotId>;
+
+    /// Like `update_value`, placing a value that grows as `fit` says.
+    fn update_value_with(
+        &mut self,
+        slot_id: SlotId,
+        bytes: &[u8],
+        fit: FitPolicy,
+    ) -> Option<UpdateOutcome>;
     // Do not change these functions signatures (only the function bodies)
 
     /// Initialize the page struct as a heap page.
@@ -82,6 +115,7 @@ pub trait HeapPage {
     /// bytes in the page may not follow the slot order.
     /// If a slot is deleted you should reuse the slotId in the future.
     /// The page should always assign the lowest available slot_id to an insertion.
+    /// The bytes are placed with `FitPolicy::FirstFit`.
     ///
     /// HINT: You can copy/clone bytes into a slice using the following function.
     /// They must have the same size.
@@ -100,10 +134,12 @@ pub trait HeapPage {
     #[allow(dead_code)]
     fn delete_value(&mut self, slot_id: SlotId) -> Option<()>;
 
-    /// Update the value for the slotId. If the slotId is not valid or there is not
-    /// space on the page return None and leave the old value/slot. If there is space, update the value and return Some(())
+    /// Update the value for the slotId. If the slotId is not valid or the value is empty
+    /// return None. A value that does not grow is rewritten in place; one that grows is
+    /// relocated within the page (compacting it if needed) as long as the page has
+    /// enough free space in total. Otherwise NeedsSpace is returned and the old value/slot is left.
     #[allow(dead_code)]
-    fn update_value(&mut self, slot_id: SlotId, bytes: &[u8]) -> Option<()>;
+    fn update_value(&mut self, slot_id: SlotId, bytes: &[u8]) -> Option<UpdateOutcome>;
 
     /// A utility function to determine the current size of the header for this page
     /// Will be used by tests. Optional for you to use in your code
@@ -129,6 +165,7 @@ impl HeapPage for Page {
         self.data[PAGE_FIXED_HEADER_LEN..PAGE_SIZE].fill(0); // Zero out everything just in case.
                                                              // Basically, I want to store four informations in the metadata:
                                                              // How many slots and The next free slot, the remaining size, lowest avil slot id. All are two bytes.
+        self.set_dictionary_slot(None);
 
         //slot_count = 0
         self.write_u16_at(SLOT_NUMBER_OFFSET, 0);
@@ -235,13 +272,68 @@ impl HeapPage for Page {
         self.set_next_free(write_ptr);
     }
 
+    fn trim_free_slots(&mut self) -> usize {
+        let entry_base = PAGE_FIXED_HEADER_LEN + HEAP_PAGE_FIXED_METADATA_SIZE;
+        let mut count = self.slot_count();
+        while count > 0
+            && self.read_u16_at(entry_base + (count - 1) * SLOT_METADATA_SIZE + OFFSET_SIZE) == 0
+        {
+            count -= 1;
+        }
+        let dropped = self.slot_count() - count;
+        if dropped > 0 {
+            self.data[entry_base + count * SLOT_METADATA_SIZE
+                ..entry_base + (count + dropped) * SLOT_METADATA_SIZE]
+                .fill(0);
+            self.set_slot_count(count);
+            self.set_remaining_size(self.remaining_size() + dropped * SLOT_METADATA_SIZE);
+            self.set_lowest_avail(self.lowest_avail().min(count));
+        }
+        dropped
+    }
+
+    fn free_regions(&self) -> Vec<(usize, usize)> {
+        let slot_start = PAGE_FIXED_HEADER_LEN + HEAP_PAGE_FIXED_METADATA_SIZE;
+        let slot_count = self.slot_count();
+        let mut live: Vec<(usize, usize)> = (0..slot_count)
+            .map(|slot| {
+                let meta_off = slot_start + slot * SLOT_METADATA_SIZE;
+                (
+                    self.read_u16_at(meta_off) as usize,
+                    self.read_u16_at(meta_off + OFFSET_SIZE) as usize,
+                )
+            })
+            .filter(|&(_, len)| len > 0)
+            .collect();
+        live.sort_unstable();
+
+        let mut regions = Vec::new();
+        let mut start = slot_start + slot_count * SLOT_METADATA_SIZE;
+        for (off, len) in live.into_iter().chain(std::iter::once((PAGE_SIZE, 0))) {
+            if off > start {
+                regions.push((start, off - start));
+            }
+            start = start.max(off + len);
+        }
+        regions
+    }
+
     /// Attempts to add a new value to this page if there is space available.
     /// Returns Some(SlotId) if inserted or None if there was not enough space.
     fn add_value(&mut self, bytes: &[u8]) -> Option<SlotId> {
+        self.add_value_with(bytes, FitPolicy::FirstFit)
+    }
+
+    fn add_value_with(&mut self, bytes: &[u8], fit: FitPolicy) -> Option<SlotId> {
         const ENTRY_SZ: usize = SLOT_METADATA_SIZE;
         // where the slot directory ends
         let hdr_end = PAGE_FIXED_HEADER_LEN + HEAP_PAGE_FIXED_METADATA_SIZE;
 
+        // a zero length marks a free slot, so empty values cannot be stored
+        if bytes.is_empty() {
+            return None;
+        }
+
         // gather info
         let mut remaining = self.remaining_size();
         let old_count = self.slot_count();
@@ -255,25 +347,54 @@ impl HeapPage for Page {
         }
 
         let dir_end = hdr_end + (old_count + needs_slot as usize) * ENTRY_SZ;
-        let mut next_free = self.next_free();
 
-        // if not enough contiguous body space, compact once
-        if bytes.len() > next_free.saturating_sub(dir_end) {
-            self.compact_page();
-            next_free = self.next_free();
-        }
+        // Best fit takes the smallest region that holds the value. The region
+        // below the lowest value must also leave room for a new slot entry.
+        let best = match fit {
+            FitPolicy::FirstFit => None,
+            FitPolicy::BestFit => {
+                let regions = self.free_regions();
+                let old_dir_end = hdr_end + old_count * ENTRY_SZ;
+                let dir_fits = !needs_slot
+                    || regions
+                        .first()
+                        .is_some_and(|&(off, len)| off == old_dir_end && len >= ENTRY_SZ);
+                if dir_fits {
+                    regions
+                        .into_iter()
+                        .map(|(off, len)| {
+                            let start = off.max(dir_end);
+                            (start, (off + len).saturating_sub(start))
+                        })
+                        .filter(|&(_, len)| len >= bytes.len())
+                        .min_by_key(|&(_, len)| len)
+                        .map(|(start, len)| start + len)
+                } else {
+                    None
+                }
+            }
+        };
+        let write_end = match best {
+            Some(end) => end,
+            None => {
+                // if not enough contiguous body space, compact once
+                if bytes.len() > self.next_free().saturating_sub(dir_end) {
+                    self.compact_page();
+                }
+                self.next_free()
+            }
+        };
 
         // if we’re appending a new slot, bump slot_count
         if needs_slot {
             self.set_slot_count(old_count + 1);
         }
 
-        // allocate at tail
-        let write_end = next_free;
         let write_start = write_end - bytes.len();
         self.data[write_start..write_end].copy_from_slice(bytes);
         self.write_slot_meta(slot_id as SlotId, write_start, bytes.len());
-        self.set_next_free(write_start);
+        // Every value now lies above the slot directory.
+        self.set_next_free(self.next_free().max(dir_end).min(write_start));
 
         // update remaining_size
         remaining = remaining.saturating_sub(total_needed);
@@ -340,16 +461,53 @@ impl HeapPage for Page {
         Some(())
     }
 
-    fn update_value(&mut self, slot_id: SlotId, bytes: &[u8]) -> Option<()> {
+    fn update_value(&mut self, slot_id: SlotId, bytes: &[u8]) -> Option<UpdateOutcome> {
+        self.update_value_with(slot_id, bytes, FitPolicy::FirstFit)
+    }
+
+    fn update_value_with(
+        &mut self,
+        slot_id: SlotId,
+        bytes: &[u8],
+        fit: FitPolicy,
+    ) -> Option<UpdateOutcome> {
         let slot = slot_id as usize;
+        let old_len = self.get_value(slot_id)?.len();
+        if bytes.is_empty() {
+            return None;
+        }
+
+        // A value that does not grow is overwritten where it is.
+        if bytes.len() <= old_len {
+            let entry_off =
+                PAGE_FIXED_HEADER_LEN + HEAP_PAGE_FIXED_METADATA_SIZE + slot * SLOT_METADATA_SIZE;
+            let off = self.read_u16_at(entry_off) as usize;
+            self.data[off..off + bytes.len()].copy_from_slice(bytes);
+            self.write_slot_meta(slot_id, off, bytes.len());
+            self.set_remaining_size(self.remaining_size() + old_len - bytes.len());
+            return Some(UpdateOutcome::Updated);
+        }
+
+        // Check for space up front so a failed update leaves the old value in place.
+        let growth = bytes.len() - old_len;
+        if growth > self.remaining_size() {
+            return Some(UpdateOutcome::NeedsSpace {
+                required: growth - self.remaining_size(),
+            });
+        }
+        let lowest_avail = self.lowest_avail();
 
         self.delete_value(slot_id)?;
 
+        // Re-add into the same slot, then restore any lower free slot that
+        // add_value's forward scan would skip.
         self.set_lowest_avail(slot);
+        self.add_value_with(bytes, fit)?;
+        if lowest_avail < slot {
+            self.set_lowest_avail(lowest_avail);
+        }
 
-        self.add_value(bytes)?;
-
-        Some(())
+        Some(UpdateOutcome::UpdatedMoved)
     }
 
     #[allow(dead_code)]
@@ -368,6 +526,7 @@ impl HeapPage for Page {
             page: self,
             next_slot: 0,
             total_slots: self.slot_count(),
+            dictionary_slot: self.get_dictionary_slot(),
         }
     }
 
@@ -382,6 +541,8 @@ pub struct HeapPageIter<'a> {
     page: &'a Page,
     next_slot: usize,
     total_slots: usize,
+    /// The page dictionary is not a value of the page.
+    dictionary_slot: Option<SlotId>,
 }
 
 impl<'a> Iterator for HeapPageIter<'a> {
@@ -401,7 +562,7 @@ impl<'a> Iterator for HeapPageIter<'a> {
         let off = self.page.read_u16_at(entry_off) as usize;
         let len = self.page.read_u16_at(entry_off + OFFSET_SIZE) as usize;
 
-        if len == 0 {
+        if len == 0 || self.dictionary_slot == Some(self.next_slot as SlotId) {
             self.next_slot += 1;
             return self.next();
         }
@@ -427,6 +588,7 @@ impl<'a> IntoIterator for &'a Page {
             page: self,
             next_slot: 0,
             total_slots: self.slot_count(),
+            dictionary_slot: self.get_dictionary_slot(),
         }
     }
 }
@@ -0,0 +1,241 @@
+#[cfg(test)]
+mod tests {
+    //! Randomized harness for the heap page API.
+    //!
+    //! A random sequence of adds, deletes, updates and compactions is applied to
+    //! a page and to a shadow model (one optional value per slot). After every
+    //! step the page must agree with the model. Set `FAIRY_FUZZ_STEPS` for a
+    //! longer session and `FAIRY_SEED` to replay the seed a failure reports.
+
+    use crate::heap_page::*;
+    use crate::page::{Page, PAGE_FIXED_HEADER_LEN};
+    use common::ids::SlotId;
+    use common::testutil::get_fuzz_config;
+    use common::PAGE_SIZE;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    const BODY_SIZE: usize = PAGE_SIZE - PAGE_FIXED_HEADER_LEN - HEAP_PAGE_FIXED_METADATA_SIZE;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Add(Vec<u8>),
+        Delete(SlotId),
+        Update(SlotId, Vec<u8>),
+        Compact,
+    }
+
+    /// The expected contents of the page, indexed by slot.
+    #[derive(Default)]
+    struct Model {
+        slots: Vec<Option<Vec<u8>>>,
+    }
+
+    impl Model {
+        fn lowest_free(&self) -> usize {
+            self.slots
+                .iter()
+                .position(|s| s.is_none())
+                .unwrap_or(self.slots.len())
+        }
+
+        fn remaining(&self) -> usize {
+            let used: usize = self.slots.iter().flatten().map(|v| v.len()).sum();
+            BODY_SIZE - self.slots.len() * SLOT_METADATA_SIZE - used
+        }
+
+        fn get(&self, slot: SlotId) -> Option<&Vec<u8>> {
+            self.slots.get(slot as usize).and_then(|s| s.as_ref())
+        }
+    }
+
+    fn random_value(rng: &mut SmallRng, step: usize) -> Vec<u8> {
+        let len = match rng.random_range(0..10) {
+            0 => rng.random_range(BODY_SIZE / 4..BODY_SIZE / 2),
+            1..=3 => rng.random_range(100..400),
+            _ => rng.random_range(1..40),
+        };
+        // Tag values with the step so equal lengths still differ in content.
+        (0..len).map(|i| (step + i) as u8).collect()
+    }
+
+    fn random_op(rng: &mut SmallRng, model: &Model, step: usize) -> Op {
+        // Slots slightly past the end exercise the out-of-range paths.
+        let slot = rng.random_range(0..model.slots.len() + 2) as SlotId;
+        match rng.random_range(0..10) {
+            0..=3 => Op::Add(random_value(rng, step)),
+            4..=6 => Op::Delete(slot),
+            7..=8 => Op::Update(slot, random_value(rng, step)),
+            _ => Op::Compact,
+        }
+    }
+
+    /// Applies `op` to the page and the model, checking the page's answer.
+    /// Empty values are rejected, since a zero length marks a free slot.
+    fn apply(page: &mut Page, model: &mut Model, op: &Op) -> Result<(), String> {
+        match op {
+            Op::Add(bytes) => {
+                let slot = model.lowest_free();
+                let needs_slot = slot == model.slots.len();
+                let fits = !bytes.is_empty()
+                    && bytes.len() + needs_slot as usize * SLOT_METADATA_SIZE <= model.remaining();
+                match page.add_value(bytes) {
+                    Some(got) if fits && got as usize == slot => {
+                        if needs_slot {
+                            model.slots.push(Some(bytes.clone()));
+                        } else {
+                            model.slots[slot] = Some(bytes.clone());
+                        }
+                    }
+                    None if !fits => {}
+                    got => return Err(format!("add returned {:?}, expected slot {}", got, slot)),
+                }
+            }
+            Op::Delete(slot) => {
+                let expected = model.get(*slot).is_some();
+                if page.delete_value(*slot).is_some() != expected {
+                    return Err(format!("delete of slot {} disagreed with model", slot));
+                }
+                if expected {
+                    model.slots[*slot as usize] = None;
+                }
+            }
+            Op::Update(slot, bytes) => {
+                let expected = match model.get(*slot) {
+                    Some(_) if bytes.is_empty() => None,
+                    Some(old) if bytes.len() <= old.len() => Some(UpdateOutcome::Updated),
+                    Some(old) if bytes.len() - old.len() <= model.remaining() => {
+                        Some(UpdateOutcome::UpdatedMoved)
+                    }
+                    Some(old) => Some(UpdateOutcome::NeedsSpace {
+                        required: bytes.len() - old.len() - model.remaining(),
+                    }),
+                    None => None,
+                };
+                let got = page.update_value(*slot, bytes);
+                if got != expected {
+                    return Err(format!(
+                        "update of slot {} returned {:?}, expected {:?}",
+                        slot, got, expected
+                    ));
+                }
+                if matches!(
+                    expected,
+                    Some(UpdateOutcome::Updated | UpdateOutcome::UpdatedMoved)
+                ) {
+                    model.slots[*slot as usize] = Some(bytes.clone());
+                }
+            }
+            Op::Compact => page.compact_page(),
+        }
+        Ok(())
+    }
+
+    /// Checks that the page holds exactly the values of the model.
+    fn check(page: &Page, model: &Model) -> Result<(), String> {
+        for slot in 0..model.slots.len() + 2 {
+            let slot = slot as SlotId;
+            if page.get_value(slot) != model.get(slot).map(|v| v.as_slice()) {
+                return Err(format!("slot {} does not match the model", slot));
+            }
+        }
+        let listed: Vec<(Vec<u8>, SlotId)> = page.iter().map(|(v, s)| (v.to_vec(), s)).collect();
+        let expected: Vec<(Vec<u8>, SlotId)> = model
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.clone().map(|v| (v, i as SlotId)))
+            .collect();
+        if listed != expected {
+            return Err("iterator does not match the model".to_string());
+        }
+        if page.remaining_size() != model.remaining() {
+            return Err(format!(
+                "remaining size {} but model has {}",
+                page.remaining_size(),
+                model.remaining()
+            ));
+        }
+        if page.lowest_avail() != model.lowest_free() {
+            return Err(format!(
+                "lowest available slot {} but model has {}",
+                page.lowest_avail(),
+                model.lowest_free()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs `ops` against a fresh page, returning the first divergence.
+    fn run_ops(ops: &[Op]) -> Result<(), String> {
+        let mut page = Page::new(0);
+        page.init_heap_page();
+        let mut model = Model::default();
+        for (i, op) in ops.iter().enumerate() {
+            apply(&mut page, &mut model, op)
+                .and_then(|_| check(&page, &model))
+                .map_err(|e| format!("step {} ({:?}): {}", i, op, e))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fuzz_heap_page_against_model() {
+        let (seed, steps) = get_fuzz_config(2_000);
+        let mut rng = SmallRng::seed_from_u64(seed);
+        // Restart from an empty page now and then so early states get coverage too.
+        let mut model = Model::default();
+        let mut page = Page::new(0);
+        page.init_heap_page();
+        for step in 0..steps {
+            if step % 500 == 0 {
+                model = Model::default();
+                page.init_heap_page();
+            }
+            let op = random_op(&mut rng, &model, step);
+            if let Err(e) = apply(&mut page, &mut model, &op).and_then(|_| check(&page, &model)) {
+                panic!("seed {}: step {} ({:?}): {}", seed, step, op, e);
+            }
+        }
+    }
+
+    /// Inputs that broke the page before, replayed on every run.
+    #[test]
+    fn fuzz_heap_page_regressions() {
+        let big = |n: usize| vec![7u8; n];
+        let seeds: Vec<Vec<Op>> = vec![
+            // An empty value would be stored with length zero, which marks a free slot.
+            vec![Op::Add(vec![]), Op::Add(big(3))],
+            // An update that does not fit dropped the old value.
+            vec![
+                Op::Add(big(BODY_SIZE / 2)),
+                Op::Add(big(BODY_SIZE / 4)),
+                Op::Update(1, big(BODY_SIZE / 2)),
+            ],
+            // An update moved the lowest free slot past an earlier hole.
+            vec![
+                Op::Add(big(10)),
+                Op::Add(big(10)),
+                Op::Delete(0),
+                Op::Update(1, big(20)),
+                Op::Add(big(5)),
+            ],
+            // Updating a deleted slot must not resurrect it.
+            vec![Op::Add(big(10)), Op::Delete(0), Op::Update(0, big(10))],
+            // Filling the page exactly, including the last slot's metadata.
+            vec![
+                Op::Add(big(BODY_SIZE - 2 * SLOT_METADATA_SIZE - 1)),
+                Op::Add(big(1)),
+                Op::Add(big(1)),
+                Op::Delete(0),
+                Op::Compact,
+                Op::Add(big(BODY_SIZE - 2 * SLOT_METADATA_SIZE - 1)),
+            ],
+        ];
+        for ops in seeds {
+            if let Err(e) = run_ops(&ops) {
+                panic!("{:?}: {}", ops, e);
+            }
+        }
+    }
+}
@@ -7,6 +7,7 @@ mod tests {
 
     use crate::heap_page::*;
     use common::ids::SlotId;
+    use common::physical::config::FitPolicy;
     use common::testutil::init;
     use common::testutil::*;
     use common::PAGE_SIZE;
@@ -15,6 +16,24 @@ mod tests {
     /// Limits how on how many bytes we can use for page metadata / header
     pub const FIXED_HEADER_SIZE: usize = HEAP_PAGE_FIXED_METADATA_SIZE + PAGE_FIXED_HEADER_LEN;
     pub const HEADER_PER_VAL_SIZE: usize = SLOT_METADATA_SIZE;
+    const FIT_POLICIES: [FitPolicy; 2] = [FitPolicy::FirstFit, FitPolicy::BestFit];
+
+    /// Every byte of the page is either header, slot directory, a live value or
+    /// free, and the free regions add up to the free byte count.
+    fn assert_space_accounted(p: &Page) {
+        let live: usize = p.iter().map(|(v, _)| v.len()).sum();
+        let used = p.get_header_size() + p.slot_count() * SLOT_METADATA_SIZE + live;
+        assert_eq!(PAGE_SIZE - used, p.remaining_size());
+        let free: usize = p.free_regions().iter().map(|(_, len)| len).sum();
+        assert_eq!(p.remaining_size(), free);
+    }
+
+    /// Free bytes that cannot take a value as large as the largest free region.
+    fn wasted_bytes(p: &Page) -> usize {
+        let regions = p.free_regions();
+        let total: usize = regions.iter().map(|(_, len)| len).sum();
+        total - regions.iter().map(|(_, len)| *len).max().unwrap_or(0)
+    }
 
     /// This is a test for helping to debug the page by using records of ascending values
     #[test]
@@ -45,6 +64,19 @@ mod tests {
         assert_eq!(PAGE_SIZE - p.get_header_size(), p.get_free_space());
     }
 
+    #[test]
+    fn hs_page_max_tuple_size() {
+        init();
+        assert_eq!(
+            common::MAX_TUPLE_SIZE,
+            PAGE_SIZE - FIXED_HEADER_SIZE - HEADER_PER_VAL_SIZE
+        );
+        let mut p = Page::new(0);
+        p.init_heap_page();
+        assert!(p.add_value(&vec![1; common::MAX_TUPLE_SIZE + 1]).is_none());
+        assert_eq!(Some(0), p.add_value(&vec![1; common::MAX_TUPLE_SIZE]));
+    }
+
     #[test]
     fn hs_page_simple_insert() {
         init();
@@ -255,7 +287,10 @@ mod tests {
         // Check that we can update them
         for i in 0..vals.len() {
             let new_bytes = get_random_byte_vec(&mut rng, 100);
-            assert_eq!(Some(()), p.update_value(i as SlotId, &new_bytes));
+            assert_eq!(
+                Some(UpdateOutcome::Updated),
+                p.update_value(i as SlotId, &new_bytes)
+            );
             let check_bytes = p.get_value(i as SlotId).unwrap();
             assert_eq!(new_bytes, check_bytes);
         }
@@ -298,7 +333,10 @@ mod tests {
                 continue;
             }
             let new_bytes = get_random_byte_vec(&mut rng, 100);
-            assert_eq!(Some(()), p.update_value(i as SlotId, &new_bytes));
+            assert_eq!(
+                Some(UpdateOutcome::Updated),
+                p.update_value(i as SlotId, &new_bytes)
+            );
             let check_bytes = p.get_value(i as SlotId).unwrap();
             assert_eq!(new_bytes, check_bytes);
             vals[i] = new_bytes.clone();
@@ -319,12 +357,58 @@ mod tests {
 
         // Update the 4th value to be larger
         let new_bytes = get_random_byte_vec(&mut rng, 200);
-        assert_eq!(Some(()), p.update_value(4, &new_bytes));
+        assert_eq!(
+            Some(UpdateOutcome::UpdatedMoved),
+            p.update_value(4, &new_bytes)
+        );
         let check_bytes = p.get_value(4).unwrap();
         assert_eq!(new_bytes, check_bytes);
         vals[4] = new_bytes.clone();
     }
 
+    #[test]
+    fn hs_page_update_outcomes() {
+        init();
+        let mut p = Page::new(4);
+        p.init_heap_page();
+        let mut rng = get_rng();
+
+        // Fill the page with four values, leaving a little free space at the end
+        let vals = get_ascending_vec_of_byte_vec_02x(&mut rng, 4, 900, 900);
+        for (i, val) in vals.iter().enumerate() {
+            assert_eq!(Some(i as SlotId), p.add_value(val));
+        }
+        let free = p.remaining_size();
+        assert!(free < 900);
+
+        // Shrinking is done in place and frees the difference
+        let small = get_random_byte_vec(&mut rng, 100);
+        assert_eq!(Some(UpdateOutcome::Updated), p.update_value(1, &small));
+        assert_eq!(small, p.get_value(1).unwrap());
+        assert_eq!(free + 800, p.remaining_size());
+
+        // Growing past the contiguous free space relocates the value after compacting
+        let grown = get_random_byte_vec(&mut rng, 100 + free + 800);
+        assert_eq!(Some(UpdateOutcome::UpdatedMoved), p.update_value(1, &grown));
+        assert_eq!(grown, p.get_value(1).unwrap());
+        assert_eq!(0, p.remaining_size());
+        for i in [0, 2, 3] {
+            assert_eq!(vals[i], p.get_value(i as SlotId).unwrap());
+        }
+
+        // A value that does not fit reports the shortfall and leaves the old value
+        let too_big = get_random_byte_vec(&mut rng, 1000);
+        assert_eq!(
+            Some(UpdateOutcome::NeedsSpace { required: 100 }),
+            p.update_value(0, &too_big)
+        );
+        assert_eq!(vals[0], p.get_value(0).unwrap());
+
+        // Invalid slots and empty values are not updates
+        assert_eq!(None, p.update_value(4, &small));
+        assert_eq!(None, p.update_value(0, &[]));
+    }
+
     #[test]
     fn hs_page_delete_insert() {
         init();
@@ -525,19 +609,22 @@ mod tests {
         let size = 800;
         let mut rng = get_rng();
         let values = get_ascending_vec_of_byte_vec_02x(&mut rng, 6, size, size);
-        let mut p = Page::new(0);
-        p.init_heap_page();
-        assert_eq!(Some(0), p.add_value(&values[0]));
-        assert_eq!(Some(1), p.add_value(&values[1]));
-        assert_eq!(Some(2), p.add_value(&values[2]));
-        assert_eq!(Some(3), p.add_value(&values[3]));
-        assert_eq!(Some(4), p.add_value(&values[4]));
-        assert_eq!(values[0], p.get_value(0).unwrap());
-        assert_eq!(None, p.add_value(&values[0]));
-        assert_eq!(Some(()), p.delete_value(1));
-        assert_eq!(None, p.get_value(1));
-        assert_eq!(Some(1), p.add_value(&values[5]));
-        assert_eq!(values[5], p.get_value(1).unwrap());
+        for fit in FIT_POLICIES {
+            let mut p = Page::new(0);
+            p.init_heap_page();
+            assert_eq!(Some(0), p.add_value_with(&values[0], fit));
+            assert_eq!(Some(1), p.add_value_with(&values[1], fit));
+            assert_eq!(Some(2), p.add_value_with(&values[2], fit));
+            assert_eq!(Some(3), p.add_value_with(&values[3], fit));
+            assert_eq!(Some(4), p.add_value_with(&values[4], fit));
+            assert_eq!(values[0], p.get_value(0).unwrap());
+            assert_eq!(None, p.add_value_with(&values[0], fit));
+            assert_eq!(Some(()), p.delete_value(1));
+            assert_eq!(None, p.get_value(1));
+            assert_eq!(Some(1), p.add_value_with(&values[5], fit));
+            assert_eq!(values[5], p.get_value(1).unwrap());
+            assert_space_accounted(&p);
+        }
     }
 
     #[test]
@@ -547,24 +634,27 @@ mod tests {
         let mut rng = get_rng();
         let values = get_ascending_vec_of_byte_vec_02x(&mut rng, 8, size, size);
         let larger_val = get_random_byte_vec(&mut rng, size * 2 - 20);
-        let mut p = Page::new(0);
-        p.init_heap_page();
-        assert_eq!(Some(0), p.add_value(&values[0]));
-        assert_eq!(Some(1), p.add_value(&values[1]));
-        assert_eq!(Some(2), p.add_value(&values[2]));
-        assert_eq!(Some(3), p.add_value(&values[3]));
-        assert_eq!(Some(4), p.add_value(&values[4]));
-        assert_eq!(Some(5), p.add_value(&values[5]));
-        assert_eq!(Some(6), p.add_value(&values[6]));
-        assert_eq!(Some(7), p.add_value(&values[7]));
-        assert_eq!(values[5], p.get_value(5).unwrap());
-        assert_eq!(None, p.add_value(&values[0]));
-        assert_eq!(Some(()), p.delete_value(1));
-        assert_eq!(None, p.get_value(1));
-        assert_eq!(Some(()), p.delete_value(6));
-        assert_eq!(None, p.get_value(6));
-        assert_eq!(Some(1), p.add_value(&larger_val));
-        assert_eq!(larger_val, p.get_value(1).unwrap());
+        for fit in FIT_POLICIES {
+            let mut p = Page::new(0);
+            p.init_heap_page();
+            assert_eq!(Some(0), p.add_value_with(&values[0], fit));
+            assert_eq!(Some(1), p.add_value_with(&values[1], fit));
+            assert_eq!(Some(2), p.add_value_with(&values[2], fit));
+            assert_eq!(Some(3), p.add_value_with(&values[3], fit));
+            assert_eq!(Some(4), p.add_value_with(&values[4], fit));
+            assert_eq!(Some(5), p.add_value_with(&values[5], fit));
+            assert_eq!(Some(6), p.add_value_with(&values[6], fit));
+            assert_eq!(Some(7), p.add_value_with(&values[7], fit));
+            assert_eq!(values[5], p.get_value(5).unwrap());
+            assert_eq!(None, p.add_value_with(&values[0], fit));
+            assert_eq!(Some(()), p.delete_value(1));
+            assert_eq!(None, p.get_value(1));
+            assert_eq!(Some(()), p.delete_value(6));
+            assert_eq!(None, p.get_value(6));
+            assert_eq!(Some(1), p.add_value_with(&larger_val, fit));
+            assert_eq!(larger_val, p.get_value(1).unwrap());
+            assert_space_accounted(&p);
+        }
     }
 
     #[test]
@@ -580,19 +670,22 @@ mod tests {
             get_random_byte_vec(&mut rng, size),
             get_random_byte_vec(&mut rng, size / 4),
         ];
-        let mut p = Page::new(0);
-        p.init_heap_page();
-        assert_eq!(Some(0), p.add_value(&values[0]));
-        assert_eq!(Some(1), p.add_value(&values[1]));
-        assert_eq!(Some(2), p.add_value(&values[2]));
-        assert_eq!(Some(3), p.add_value(&values[3]));
-        assert_eq!(Some(4), p.add_value(&values[4]));
-        assert_eq!(values[0], p.get_value(0).unwrap());
-        assert_eq!(None, p.add_value(&values[0]));
-        assert_eq!(Some(()), p.delete_value(1));
-        assert_eq!(None, p.get_value(1));
-        assert_eq!(Some(1), p.add_value(&values[5]));
-        assert_eq!(values[5], p.get_value(1).unwrap());
+        for fit in FIT_POLICIES {
+            let mut p = Page::new(0);
+            p.init_heap_page();
+            assert_eq!(Some(0), p.add_value_with(&values[0], fit));
+            assert_eq!(Some(1), p.add_value_with(&values[1], fit));
+            assert_eq!(Some(2), p.add_value_with(&values[2], fit));
+            assert_eq!(Some(3), p.add_value_with(&values[3], fit));
+            assert_eq!(Some(4), p.add_value_with(&values[4], fit));
+            assert_eq!(values[0], p.get_value(0).unwrap());
+            assert_eq!(None, p.add_value_with(&values[0], fit));
+            assert_eq!(Some(()), p.delete_value(1));
+            assert_eq!(None, p.get_value(1));
+            assert_eq!(Some(1), p.add_value_with(&values[5], fit));
+            assert_eq!(values[5], p.get_value(1).unwrap());
+            assert_space_accounted(&p);
+        }
     }
 
     #[test]
@@ -643,6 +736,12 @@ mod tests {
     #[test]
     pub fn hs_page_stress_test() {
         init();
+        for fit in FIT_POLICIES {
+            stress_test(fit);
+        }
+    }
+
+    fn stress_test(fit: FitPolicy) {
         let mut p = Page::new(23);
         p.init_heap_page();
         let mut rng = get_rng();
@@ -656,7 +755,7 @@ mod tests {
             let bytes = original_vals
                 .pop_front()
                 .expect("ran out of data -- shouldn't happen");
-            let slot = p.add_value(&bytes);
+            let slot = p.add_value_with(&bytes, fit);
             match slot {
                 Some(slot_id) => {
                     stored_vals.push(bytes);
@@ -683,7 +782,7 @@ mod tests {
             trace!("Adding new value (left:{}). Need to make space for new record (len:{}).\n - Stored_slots {:?}", original_vals.len(), &bytes.len(), stored_slots);
             let mut added = false;
             while !added {
-                let try_slot = p.add_value(&bytes);
+                let try_slot = p.add_value_with(&bytes, fit);
                 match try_slot {
                     Some(new_slot) => {
                         stored_slots.push(new_slot);
@@ -691,6 +790,7 @@ mod tests {
                         let p_clone = p.clone();
                         check_vals = p_clone.iter().map(|(a, _)| a.to_vec()).collect();
                         assert!(compare_unordered_byte_vecs(&stored_vals, check_vals));
+                        assert_space_accounted(&p);
                         trace!("Added new value ({}) {:?}", new_slot, stored_slots);
                         added = true;
                     }
@@ -711,4 +811,48 @@ mod tests {
             }
         }
     }
+
+    /// Alternating small and large values, with the large ones deleted and
+    /// refilled by values slightly smaller. First-fit keeps appending below the
+    /// lowest value and leaves every hole behind; best-fit refills the holes and
+    /// only leaves slivers.
+    #[test]
+    pub fn hs_page_fragmentation() {
+        init();
+        let mut rng = get_rng();
+        let mut wasted = Vec::new();
+        for fit in FIT_POLICIES {
+            let mut p = Page::new(0);
+            p.init_heap_page();
+            let mut large_slots = Vec::new();
+            while p.remaining_size() > PAGE_SIZE / 2 {
+                let small = get_random_byte_vec(&mut rng, 40);
+                let large = get_random_byte_vec(&mut rng, 200);
+                p.add_value_with(&small, fit).unwrap();
+                large_slots.push(p.add_value_with(&large, fit).unwrap());
+            }
+            for slot in &large_slots {
+                p.delete_value(*slot).unwrap();
+            }
+            let mut refills = Vec::new();
+            for slot in &large_slots {
+                let refill = get_random_byte_vec(&mut rng, 190);
+                // slot ids are the lowest free one whatever the policy
+                assert_eq!(Some(*slot), p.add_value_with(&refill, fit));
+                refills.push((*slot, refill));
+            }
+            for (slot, refill) in &refills {
+                assert_eq!(refill, &p.get_value(*slot).unwrap());
+            }
+            assert_space_accounted(&p);
+            wasted.push(wasted_bytes(&p));
+        }
+        let (first_fit, best_fit) = (wasted[0], wasted[1]);
+        trace!(
+            "wasted bytes: first-fit {}, best-fit {}",
+            first_fit,
+            best_fit
+        );
+        assert!(best_fit < first_fit, "{} >= {}", best_fit, first_fit);
+    }
 }
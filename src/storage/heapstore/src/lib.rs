@@ -1,3 +1,7 @@
+// Frame guards borrow the pool they are latched in; signatures returning them
+// leave that lifetime elided.
+#![allow(mismatched_lifetime_syntaxes)]
+
 #[macro_use]
 #[allow(unused_imports)]
 extern crate log;
@@ -11,13 +15,16 @@ mod base_file_mock;
 mod base_file_tests;
 pub mod buffer_pool;
 pub mod container_file_catalog;
+pub mod durability;
 pub mod file_stats;
 mod heap_file;
 mod heap_file_tests;
 mod heap_page;
+mod heap_page_fuzz;
 mod heap_page_tests;
 mod page;
 mod page_tests;
+pub mod retry_policy;
 pub mod storage_manager;
 mod storage_manager_tests;
 pub mod testutil;
@@ -40,6 +40,10 @@ pub const PAGE_FIXED_HEADER_LEN: usize = 16;
 
 // PG MS Add any additional header fields or constants/metadata you neeed here.
 
+/// Offset of the slot id of the value holding the dictionary of the page. It is
+/// stored plus one, so that zero means the page has no dictionary.
+pub const DICTIONARY_SLOT_OFFSET: usize = CHECKSUM_OFFSET + CHECKSUM_SIZE;
+
 #[cfg(test)]
 const _: () = {
     /// Verify that the fixed header is the large enough to hold the page id, lsn, and checksum.
@@ -47,6 +51,7 @@ const _: () = {
     pub const LSN_SIZE: usize = mem::size_of::<Lsn>();
     pub const CHECKSUM_SIZE: usize = mem::size_of::<CheckSum>();
     assert!(PAGE_FIXED_HEADER_LEN >= (PAGE_ID_SIZE + LSN_SIZE + CHECKSUM_SIZE));
+    assert!(PAGE_FIXED_HEADER_LEN >= DICTIONARY_SLOT_OFFSET + SLOT_ID_SIZE);
 };
 
 /// Page struct. This must always occupy `PAGE_SIZE`` bytes at all times.
@@ -195,6 +200,19 @@ impl Page {
             .copy_from_slice(&checksum_bytes);
     }
 
+    /// Get the slot of the value holding the dictionary of the page, if it has one.
+    pub fn get_dictionary_slot(&self) -> Option<SlotId> {
+        let bytes = &self.data[DICTIONARY_SLOT_OFFSET..DICTIONARY_SLOT_OFFSET + SLOT_ID_SIZE];
+        SlotId::from_le_bytes(bytes.try_into().unwrap()).checked_sub(1)
+    }
+
+    /// Set the slot of the value holding the dictionary of the page.
+    pub fn set_dictionary_slot(&mut self, slot_id: Option<SlotId>) {
+        let stored = slot_id.map_or(0, |slot_id| slot_id + 1);
+        self.data[DICTIONARY_SLOT_OFFSET..DICTIONARY_SLOT_OFFSET + SLOT_ID_SIZE]
+            .copy_from_slice(&stored.to_le_bytes());
+    }
+
     /// Create a page from a byte array
     pub fn from_bytes(data: [u8; PAGE_SIZE]) -> Self {
         Page { data }
@@ -0,0 +1,180 @@
+use crate::buffer_pool::mem_pool_trait::MemPoolStatus;
+use crate::buffer_pool::mem_stats::MemoryStats;
+use common::metrics::storage_metrics;
+use common::physical::config::ServerConfig;
+use common::prelude::*;
+use common::util::backoff::Backoff;
+use std::time::{Duration, Instant};
+
+/// Delay before the first retry of a buffer pool request.
+const INITIAL_DELAY: Duration = Duration::from_micros(20);
+/// Upper bound for the delay between two retries.
+const MAX_DELAY: Duration = Duration::from_millis(5);
+
+/// How the storage manager retries buffer pool requests that failed for a
+/// transient reason.
+///
+/// A request whose frame latch could not be granted is retried for
+/// `latch_budget`, one that found no frame to evict for `evict_budget`, which is
+/// longer so that running scans get a chance to release their frames. Retries
+/// back off exponentially, from `initial_delay` up to `max_delay`, with random
+/// jitter so that waiting threads do not retry in lockstep. Other failures are
+/// not retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub latch_budget: Duration,
+    pub evict_budget: Duration,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            latch_budget: Duration::from_millis(1000),
+            evict_budget: Duration::from_millis(10000),
+            initial_delay: INITIAL_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        RetryPolicy {
+            latch_budget: Duration::from_millis(config.bp_latch_retry_ms),
+            evict_budget: Duration::from_millis(config.bp_evict_retry_ms),
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// How long a request failing with `status` is retried, or None if the
+    /// failure is not transient.
+    fn budget(&self, status: &MemPoolStatus) -> Option<Duration> {
+        match status {
+            MemPoolStatus::FrameReadLatchGrantFailed
+            | MemPoolStatus::FrameWriteLatchGrantFailed => Some(self.latch_budget),
+            MemPoolStatus::CannotEvictPage => Some(self.evict_budget),
+            _ => None,
+        }
+    }
+
+    /// Calls `request` until it succeeds, fails for a reason that is not
+    /// transient, or the budget for its failure runs out. Returns the last
+    /// failure in the latter two cases.
+    ///
+    /// The time spent waiting by requests that needed a retry is recorded in
+    /// the storage metrics, as is the time spent by requests that gave up.
+    pub fn retry<G>(
+        &self,
+        mut request: impl FnMut() -> Result<G, MemPoolStatus>,
+    ) -> Result<G, MemPoolStatus> {
+        let mut start: Option<Instant> = None;
+        let (res, _) = Backoff::sleeping(self.initial_delay, self.max_delay).retry(
+            |_| request(),
+            |status, _| {
+                let Some(budget) = self.budget(status) else {
+                    return false;
+                };
+                let started = *start.get_or_insert_with(Instant::now);
+                if started.elapsed() >= budget {
+                    storage_metrics()
+                        .bp_retry_exhausted
+                        .record(started.elapsed());
+                    return false;
+                }
+                true
+            },
+        );
+        if let (Ok(_), Some(start)) = (&res, start) {
+            storage_metrics().bp_retry.record(start.elapsed());
+        }
+        res
+    }
+}
+
+/// Converts a failed buffer pool request into the error reported to the user.
+/// Transient failures that outlasted their retry budget are reported as an
+/// exhausted buffer pool, with the current pool statistics attached.
+pub fn pool_error(status: MemPoolStatus, stats: impl FnOnce() -> MemoryStats) -> FairyError {
+    match status {
+        MemPoolStatus::FrameReadLatchGrantFailed
+        | MemPoolStatus::FrameWriteLatchGrantFailed
+        | MemPoolStatus::CannotEvictPage => FairyError::FairyError(format!(
+            "Buffer pool exhausted ({}), consider increasing the number of frames.\n{}",
+            status,
+            stats()
+        )),
+        MemPoolStatus::BaseFileError(e) => FairyError::IOError(e),
+        _ => FairyError::StorageError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(budget_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            latch_budget: Duration::from_millis(budget_ms),
+            evict_budget: Duration::from_millis(budget_ms * 2),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_retry_until_success() {
+        let mut attempts = 0;
+        let res = policy(1000).retry(|| {
+            attempts += 1;
+            match attempts {
+                1 => Err(MemPoolStatus::FrameReadLatchGrantFailed),
+                2 => Err(MemPoolStatus::CannotEvictPage),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(res, Ok(3));
+    }
+
+    #[test]
+    fn test_retry_budget_expires() {
+        let start = Instant::now();
+        let res: Result<(), _> =
+            policy(20).retry(|| Err(MemPoolStatus::FrameWriteLatchGrantFailed));
+        assert_eq!(res, Err(MemPoolStatus::FrameWriteLatchGrantFailed));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // Evictions get the longer budget
+        let start = Instant::now();
+        let res: Result<(), _> = policy(20).retry(|| Err(MemPoolStatus::CannotEvictPage));
+        assert_eq!(res, Err(MemPoolStatus::CannotEvictPage));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_permanent_failure_not_retried() {
+        let mut attempts = 0;
+        let res: Result<(), _> = policy(1000).retry(|| {
+            attempts += 1;
+            Err(MemPoolStatus::PageNotFound)
+        });
+        assert_eq!(res, Err(MemPoolStatus::PageNotFound));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_pool_error_mentions_frames() {
+        let err = pool_error(MemPoolStatus::CannotEvictPage, MemoryStats::new);
+        match err {
+            FairyError::FairyError(msg) => {
+                assert!(msg.contains("increasing the number of frames"));
+                assert!(msg.contains("Buffer pool stats"));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(
+            pool_error(MemPoolStatus::PageNotFound, MemoryStats::new),
+            FairyError::StorageError
+        );
+    }
+}
@@ -1,12 +1,17 @@
 use crate::buffer_pool::buffer_pool::{gen_random_pathname, BufferPool};
 use crate::buffer_pool::mem_pool_trait::MemPool;
+use crate::buffer_pool::mem_stats::MemoryStats;
 use crate::container_file_catalog::ContainerFileCatalog;
+use crate::durability::{FileSync, Fsync, GroupCommit};
 use crate::heap_file::{HeapFile, HeapFileIter};
-use common::physical::config::ServerConfig;
+use crate::retry_policy::RetryPolicy;
+use common::physical::config::{Durability, FitPolicy, ServerConfig};
+use common::physical::page_dictionary::PageFilter;
 use common::prelude::*;
-use common::traits::storage_trait::StorageTrait;
+use common::traits::storage_trait::{CompactionOutcome, StorageReport, StorageTrait};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 pub const STORAGE_DIR: &str = "heapstore";
 type HF = HeapFile<BufferPool>;
@@ -19,6 +24,9 @@ pub struct StorageManager {
     pub cfc: Arc<ContainerFileCatalog>,
     pub bp: Arc<BufferPool>,
     pub(crate) cid_heapfile_map: HFs,
+    retry: RetryPolicy,
+    fit: FitPolicy,
+    group_commit: GroupCommit,
 }
 
 /// The required functions in HeapStore's StorageManager that are specific for HeapFiles
@@ -33,6 +41,45 @@ impl StorageManager {
         }
     }
 
+    /// Get the runtime statistics of the buffer pool
+    pub fn mem_stats(&self) -> MemoryStats {
+        self.bp.stats()
+    }
+
+    /// Like `new`, but files are synced with `sync`.
+    pub fn new_with_sync(config: &'static ServerConfig, sync: Arc<dyn FileSync>) -> Self {
+        let dir = &config.db_path.join(STORAGE_DIR);
+        let cfc = Arc::new(
+            ContainerFileCatalog::new_with_durability(dir, false, config.durability, sync).unwrap(),
+        );
+        let bp = Arc::new(BufferPool::new(BP_FRAMES, cfc.clone()).unwrap());
+        let retry = RetryPolicy::from_config(config);
+        let fit = config.page_fit_policy;
+
+        // For each file in the cfc, create a heapfile object
+        let mut hf_map = HashMap::new();
+        for c_id in cfc.container_ids() {
+            //TODO milestone hs
+            // Load the heapfile and add it to hf_map
+            let hf = Arc::new(
+                HeapFile::load(c_id, bp.clone())
+                    .unwrap()
+                    .with_retry_policy(retry)
+                    .with_fit_policy(fit),
+            );
+            hf_map.insert(c_id, hf);
+        }
+
+        StorageManager {
+            bp,
+            cfc,
+            cid_heapfile_map: Arc::new(RwLock::new(hf_map)),
+            retry,
+            fit,
+            group_commit: GroupCommit::new(Duration::from_millis(config.durability_batch_delay_ms)),
+        }
+    }
+
     /// Get the number of pages for a container
     #[allow(dead_code)]
     pub(crate) fn get_num_pages(&self, container_id: ContainerId) -> PageId {
@@ -56,24 +103,7 @@ impl StorageTrait for StorageManager {
     /// For startup/shutdown: check the storage_dir for data persisted in shutdown() that you can
     /// use to populate this instance of the SM. Otherwise create a new one.
     fn new(config: &'static ServerConfig) -> Self {
-        let dir = &config.db_path.join(STORAGE_DIR);
-        let cfc = Arc::new(ContainerFileCatalog::new(dir, false).unwrap());
-        let bp = Arc::new(BufferPool::new(BP_FRAMES, cfc.clone()).unwrap());
-
-        // For each file in the cfc, create a heapfile object
-        let mut hf_map = HashMap::new();
-        for c_id in cfc.container_ids() {
-            //TODO milestone hs
-            // Load the heapfile and add it to hf_map
-            let hf = Arc::new(HeapFile::load(c_id, bp.clone()).unwrap());
-            hf_map.insert(c_id, hf);
-        }
-
-        StorageManager {
-            bp,
-            cfc,
-            cid_heapfile_map: Arc::new(RwLock::new(hf_map)),
-        }
+        Self::new_with_sync(config, Arc::new(Fsync))
     }
 
     /// Create a new storage manager for testing. There is no startup/shutdown logic here: it
@@ -90,6 +120,9 @@ impl StorageTrait for StorageManager {
             cfc,
             bp,
             cid_heapfile_map: Arc::new(RwLock::new(HashMap::new())),
+            retry: RetryPolicy::default(),
+            fit: FitPolicy::default(),
+            group_commit: GroupCommit::new(Duration::ZERO),
         }
     }
 
@@ -177,7 +210,12 @@ impl StorageTrait for StorageManager {
         if files.contains_key(&container_id) {
             return Err(FairyError::StorageError);
         }
-        let hf = Arc::new(HeapFile::new(container_id, self.bp.clone()).unwrap());
+        let hf = Arc::new(
+            HeapFile::new(container_id, self.bp.clone())
+                .unwrap()
+                .with_retry_policy(self.retry)
+                .with_fit_policy(self.fit),
+        );
         files.insert(container_id, hf);
         Ok(())
     }
@@ -187,11 +225,49 @@ impl StorageTrait for StorageManager {
         self.create_container(container_id, None, common::ids::StateType::BaseTable, None)
     }
 
+    fn container_exists(&self, container_id: ContainerId) -> bool {
+        self.cid_heapfile_map
+            .read()
+            .unwrap()
+            .contains_key(&container_id)
+    }
+
+    /// An id stays in use after its container is removed until the pages of the
+    /// container are out of the buffer pool and its file is deleted.
+    fn container_id_in_use(&self, container_id: ContainerId) -> bool {
+        self.container_exists(container_id) || self.cfc.contains(container_id)
+    }
+
+    /// Empties the pages of the container one at a time. Scans that hold a page
+    /// keep it until they move on.
+    fn truncate_container(&self, container_id: ContainerId) -> Result<usize, FairyError> {
+        self.get_heapfile(container_id)?.truncate()
+    }
+
     /// Remove the container and all stored values in the container.
-    /// If the container is persisted remove the underlying files
+    /// The container is marked as temporary in the buffer pool so that its pages
+    /// are never written back. Iterators that are still open keep the heapfile alive.
     fn remove_container(&self, container_id: ContainerId) -> Result<(), FairyError> {
-        panic!("Not implemented {container_id}. not needed for hs");
-        //Ok(())
+        let mut files = self.cid_heapfile_map.write().unwrap();
+        if files.remove(&container_id).is_none() {
+            return Err(FairyError::StorageError);
+        }
+        self.bp
+            .drop_container(container_id)
+            .map_err(|_| FairyError::StorageError)
+    }
+
+    fn release_removed_containers(&self) -> Result<(), FairyError> {
+        // Holding the map keeps the ids from being taken while they are released.
+        let files = self.cid_heapfile_map.read().unwrap();
+        for c_id in self.cfc.container_ids() {
+            if !files.contains_key(&c_id) {
+                self.bp
+                    .release_container(c_id)
+                    .map_err(|_| FairyError::StorageError)?;
+            }
+        }
+        Ok(())
     }
 
     /// Get an iterator that returns all valid records
@@ -205,6 +281,18 @@ impl StorageTrait for StorageManager {
         hf.iter()
     }
 
+    /// Get an iterator that skips the pages whose dictionary rules out every record
+    fn get_filtered_iterator(
+        &self,
+        container_id: ContainerId,
+        _tid: TransactionId,
+        _perm: Permissions,
+        filter: PageFilter,
+    ) -> Self::ValIterator {
+        let hf = self.get_heapfile(container_id).expect("unknown container");
+        hf.iter_filtered(filter)
+    }
+
     /// Get an iterator that returns all valid records starting from a particular value id
     fn get_iterator_from(
         &self,
@@ -254,8 +342,47 @@ impl StorageTrait for StorageManager {
         self.bp.reset().unwrap();
     }
 
+    fn storage_report(&self, c_id: ContainerId) -> Result<StorageReport, FairyError> {
+        self.get_heapfile(c_id)?.storage_report()
+    }
+
+    fn num_pages(&self, c_id: ContainerId) -> Option<PageId> {
+        self.get_heapfile(c_id).ok().map(|hf| hf.num_pages())
+    }
+
+    fn compact(
+        &self,
+        c_id: ContainerId,
+        start_page: PageId,
+        max_pages: usize,
+    ) -> Result<CompactionOutcome, FairyError> {
+        self.get_heapfile(c_id)?.compact(start_page, max_pages)
+    }
+
+    fn set_dictionary_columns(
+        &self,
+        c_id: ContainerId,
+        columns: Vec<usize>,
+    ) -> Result<(), FairyError> {
+        self.get_heapfile(c_id)?.set_dictionary_columns(columns);
+        Ok(())
+    }
+
     // Make sure all data is flushed to disk
-    fn shutdown(&self) {
-        self.bp.flush_all().unwrap();
+    /// Writes back dirty pages and syncs them, right away with the strict
+    /// durability level and together with concurrent commits with batch.
+    fn commit(&self, _tid: TransactionId) -> Result<(), FairyError> {
+        match self.cfc.durability() {
+            Durability::None => Ok(()),
+            Durability::Batch => self.group_commit.commit(|| self.bp.flush_all()),
+            Durability::Strict => self.bp.flush_all(),
+        }
+        .map_err(|e| FairyError::IOError(format!("Commit could not be synced: {:?}", e)))
+    }
+
+    fn shutdown(&self) -> Result<(), FairyError> {
+        self.bp
+            .flush_all()
+            .map_err(|e| FairyError::IOError(format!("Shutdown could not flush: {:?}", e)))
     }
 }
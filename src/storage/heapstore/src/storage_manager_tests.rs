@@ -12,6 +12,13 @@ mod tests {
 
     use common::util::vec_compare::compare_unordered;
 
+    use crate::durability::FileSync;
+    use common::physical::config::Durability;
+    use std::fs::File;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
     const RO: Permissions = Permissions::ReadOnly;
 
     fn get_test_sm<T: StorageTrait>() -> T {
@@ -123,6 +130,42 @@ mod tests {
         assert!(instance.update_value(vec![], val_id1, t).is_err());
     }
 
+    #[test]
+    fn sm_remove_container_releases_id() {
+        let instance = get_test_sm::<HeapStorageManager>();
+        let t = TransactionId::new();
+        let cid = 3 as ContainerId;
+        let mut rng = get_rng();
+        for round in 0..2000 {
+            let vals = get_random_vec_of_byte_vec(&mut rng, 20, 10, 200);
+            instance.create_table(cid).unwrap();
+            instance.insert_values(cid, vals.clone(), t);
+            let result: Vec<Vec<u8>> = instance.get_iterator(cid, t, RO).map(|(a, _)| a).collect();
+            // The pages of earlier rounds never show up again.
+            assert!(compare_unordered(&vals, &result), "round {}", round);
+            instance.remove_container(cid).unwrap();
+            assert!(instance.container_id_in_use(cid));
+            instance.release_removed_containers().unwrap();
+            assert!(!instance.container_id_in_use(cid));
+        }
+        assert!(instance.cfc.container_ids().is_empty());
+
+        // An open iterator keeps the id in use until it is dropped.
+        instance.create_table(cid).unwrap();
+        instance.insert_value(cid, vec![1, 2, 3], t);
+        let mut iter = instance.get_iterator(cid, t, RO);
+        assert!(iter.next().is_some());
+        instance.remove_container(cid).unwrap();
+        instance.release_removed_containers().unwrap();
+        assert!(!instance.container_exists(cid));
+        assert!(instance.container_id_in_use(cid));
+        drop(iter);
+        instance.release_removed_containers().unwrap();
+        assert!(!instance.container_id_in_use(cid));
+        instance.create_table(cid).unwrap();
+        assert_eq!(instance.get_iterator(cid, t, RO).count(), 0);
+    }
+
     #[test]
     fn sm_shutdown() {
         // create path if it doesn't exist
@@ -140,7 +183,7 @@ mod tests {
         let cid = 1;
         instance1.create_table(cid).unwrap();
         let _val_ids = instance1.insert_values(cid, expected.clone(), t);
-        instance1.shutdown();
+        instance1.shutdown().unwrap();
         drop(instance1);
 
         let instance2 = get_sm::<HeapStorageManager>(config);
@@ -166,7 +209,7 @@ mod tests {
         let cid = 1;
         instance1.create_table(cid).unwrap();
         let _val_ids = instance1.insert_values(cid, expected1.clone(), t);
-        instance1.shutdown();
+        instance1.shutdown().unwrap();
         drop(instance1);
 
         let instance2 = get_sm::<HeapStorageManager>(config);
@@ -206,7 +249,7 @@ mod tests {
         let cid = 1;
         instance1.create_table(cid).unwrap();
         let _val_ids = instance1.insert_values(cid, expected1.clone(), t);
-        instance1.shutdown();
+        instance1.shutdown().unwrap();
         drop(instance1);
 
         let instance2 = get_sm::<HeapStorageManager>(config);
@@ -359,4 +402,153 @@ mod tests {
         // let cv_smaller: Vec<&[u8]> = check_vals.iter().map(|f| &f[..5]).collect();
         assert!(compare_unordered_byte_vecs(&vals, check_vals));
     }
+
+    #[test]
+    fn sm_storage_report_flags_fragmentation() {
+        let sm = get_test_sm::<HeapStorageManager>();
+        let tid = TransactionId::new();
+        let vals: Vec<Vec<u8>> = (0..400).map(|i| vec![i as u8; 100]).collect();
+
+        // Bulk loaded table
+        sm.create_table(0).unwrap();
+        sm.insert_values(0, vals.clone(), tid);
+        let packed = sm.storage_report(0).unwrap();
+        assert_eq!(packed.num_records, 400);
+        assert_eq!(packed.avg_record_size, 100);
+        assert_eq!(packed.dead_slots, 0);
+        // Only the last page may be partially filled
+        assert!(packed.pages_under_75 <= 1);
+
+        // Same data with most of the records deleted again
+        sm.create_table(1).unwrap();
+        let val_ids = sm.insert_values(1, vals, tid);
+        for (i, id) in val_ids.iter().enumerate() {
+            if i % 4 != 0 {
+                sm.delete_value(*id, tid).unwrap();
+            }
+        }
+        let fragmented = sm.storage_report(1).unwrap();
+        assert_eq!(fragmented.num_pages, packed.num_pages);
+        assert_eq!(fragmented.num_records, 100);
+        assert_eq!(fragmented.dead_slots, 300);
+        assert_eq!(fragmented.pages_under_50, fragmented.num_pages);
+        assert_eq!(fragmented.wasted_bytes, packed.wasted_bytes + 300 * 100);
+
+        assert!(sm.storage_report(2).is_err());
+    }
+
+    #[test]
+    fn sm_compact_relocates_sparse_pages() {
+        let sm = get_test_sm::<HeapStorageManager>();
+        let tid = TransactionId::new();
+        let vals: Vec<Vec<u8>> = (0..400).map(|i| vec![i as u8; 100]).collect();
+        sm.create_table(0).unwrap();
+        let val_ids = sm.insert_values(0, vals.clone(), tid);
+        let mut kept = Vec::new();
+        for (i, id) in val_ids.iter().enumerate() {
+            if i % 8 == 0 {
+                kept.push(vals[i].clone());
+            } else {
+                sm.delete_value(*id, tid).unwrap();
+            }
+        }
+        let before = sm.storage_report(0).unwrap();
+        assert_eq!(before.pages_under_25, before.num_pages);
+        assert_eq!(before.empty_pages, 0);
+
+        // A round covers at most `max_pages` pages and says where the next starts.
+        let round = sm.compact(0, 1, 2).unwrap();
+        assert_eq!(round.pages_visited, 2);
+        assert_eq!(round.next_page, 3);
+        assert_eq!(round.pages_emptied, 0);
+
+        let round = sm.compact(0, 1, before.num_pages).unwrap();
+        assert_eq!(round.next_page, 1);
+        assert!(round.values_relocated > 0);
+        let after = sm.storage_report(0).unwrap();
+        assert_eq!(after.num_records, 50);
+        assert_eq!(after.empty_pages, round.pages_emptied);
+        assert!(after.empty_pages > 0);
+        assert!(after.dead_slots < before.dead_slots);
+        // Fewer pages hold values, and they are fuller.
+        assert!(after.pages_under_25 - after.empty_pages < before.pages_under_25);
+
+        let (check_vals, _): (Vec<Vec<u8>>, Vec<ValueId>) = sm.get_iterator(0, tid, RO).unzip();
+        assert!(compare_unordered_byte_vecs(&kept, check_vals));
+        assert!(sm.compact(1, 1, 1).is_err());
+    }
+
+    /// Counts syncs instead of syncing.
+    #[derive(Default)]
+    struct CountingSync {
+        syncs: AtomicUsize,
+    }
+
+    impl FileSync for CountingSync {
+        fn sync(&self, _file: &File) -> Result<(), std::io::Error> {
+            self.syncs.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn get_sm_with_durability(
+        durability: Durability,
+        batch_delay_ms: u64,
+    ) -> (Arc<HeapStorageManager>, Arc<CountingSync>) {
+        let config: &'static ServerConfig = Box::leak(Box::new(ServerConfig {
+            durability,
+            durability_batch_delay_ms: batch_delay_ms,
+            ..ServerConfig::temporary()
+        }));
+        let sync = Arc::new(CountingSync::default());
+        let sm = HeapStorageManager::new_with_sync(config, sync.clone());
+        (Arc::new(sm), sync)
+    }
+
+    #[test]
+    fn sm_durability_strict_syncs_every_commit() {
+        let (sm, sync) = get_sm_with_durability(Durability::Strict, 0);
+        sm.create_table(0).unwrap();
+        for i in 1..=3 {
+            let tid = TransactionId::new();
+            sm.insert_value(0, vec![i as u8; 50], tid);
+            sm.commit(tid).unwrap();
+            assert_eq!(sync.syncs.load(Ordering::SeqCst), i);
+        }
+
+        let (sm, sync) = get_sm_with_durability(Durability::None, 0);
+        sm.create_table(0).unwrap();
+        let tid = TransactionId::new();
+        sm.insert_value(0, vec![1; 50], tid);
+        sm.commit(tid).unwrap();
+        sm.shutdown().unwrap();
+        assert_eq!(sync.syncs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn sm_durability_batch_coalesces_commits() {
+        let (sm, sync) = get_sm_with_durability(Durability::Batch, 50);
+        sm.create_table(0).unwrap();
+        let threads = 8;
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let sm = sm.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    let tid = TransactionId::new();
+                    sm.insert_value(0, vec![i as u8; 50], tid);
+                    barrier.wait();
+                    sm.commit(tid).unwrap();
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let syncs = sync.syncs.load(Ordering::SeqCst);
+        assert!(syncs >= 1 && syncs < threads, "{} syncs", syncs);
+        let tid = TransactionId::new();
+        assert_eq!(sm.get_iterator(0, tid, RO).count(), threads);
+    }
 }
@@ -188,6 +188,21 @@ impl StorageTrait for StorageManager {
         self.create_container(container_id, None, StateType::BaseTable, None)
     }
 
+    fn container_exists(&self, container_id: ContainerId) -> bool {
+        self.containers.read().unwrap().contains_key(&container_id)
+    }
+
+    fn truncate_container(&self, container_id: ContainerId) -> Result<usize, FairyError> {
+        let containers = self.containers.read().unwrap();
+        let table_map = containers
+            .get(&container_id)
+            .ok_or_else(|| FairyError::FairyError(String::from("Container not found")))?;
+        let mut table_map = table_map.write().unwrap();
+        let deleted = table_map.len();
+        table_map.clear();
+        Ok(deleted)
+    }
+
     /// Remove the container and all stored values in the container.
     /// If the container is persisted remove the underlying files
     fn remove_container(&self, container_id: ContainerId) -> Result<(), FairyError> {
@@ -281,21 +296,23 @@ impl StorageTrait for StorageManager {
         // No cache here
     }
 
-    fn shutdown(&self) {
+    fn shutdown(&self) -> Result<(), FairyError> {
         info!("Shutting down and persisting containers");
         if let Some(storage_dir) = &self.storage_dir {
-            fs::create_dir_all(storage_dir).expect("Unable to create dir to store SM");
+            fs::create_dir_all(storage_dir)?;
             let containers = self.containers.read().unwrap();
             for (c_id, vals_lock) in containers.iter() {
                 let vals = vals_lock.read().unwrap();
                 let mut file_path = storage_dir.join(format!("{}", c_id));
                 file_path.set_extension("ms");
-                let file = std::fs::File::create(file_path).expect("Failed to create file");
-                serde_cbor::to_writer(file, &*vals).expect("Failed on persisting container");
+                let file = std::fs::File::create(file_path)?;
+                serde_cbor::to_writer(file, &*vals)
+                    .map_err(|e| FairyError::SerializationError(e.to_string()))?;
             }
         } else {
             info!("Test SM or no path, not persisting");
         }
+        Ok(())
     }
 
     fn get_name(&self) -> &'static str {
@@ -0,0 +1,92 @@
+//! Fixtures shared by the integration tests of the workspace.
+
+use cli_fairy::Client;
+use common::physical::config::{ClientConfig, ServerConfig};
+use common::FairyError;
+use server::Server;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+/// A server running on a background thread, on a port picked by the system and
+/// with its database in a directory of its own, so tests using it can run in
+/// parallel.
+///
+/// The server is shut down and its directory removed when the `TestServer` is
+/// dropped, also when the test panics. Clients must be dropped first, since the
+/// server waits for their connections to close. Clients declared after the
+/// server are, as locals are dropped in reverse order.
+pub struct TestServer {
+    client_config: ClientConfig,
+    shutdown_signal: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    dir: TempDir,
+}
+
+impl TestServer {
+    /// Starts a server with the default configuration.
+    pub fn start() -> Self {
+        Self::with_config(ServerConfig::default())
+    }
+
+    /// Starts a server with `config`, whose host, port and database directory
+    /// are replaced.
+    pub fn with_config(config: ServerConfig) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: "0".to_string(),
+            db_path: dir.path().to_path_buf(),
+            ..config
+        };
+        // The server keeps its configuration for as long as the process runs.
+        let config: &'static ServerConfig = Box::leak(Box::new(config));
+        let mut server = Server::new(config);
+        let listener = server.bind().unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let shutdown_signal = server.shutdown_signal();
+        let thread = thread::spawn(move || server.serve(listener));
+        TestServer {
+            client_config: ClientConfig {
+                host: config.host.clone(),
+                port: port.to_string(),
+                script: String::new(),
+                ..ClientConfig::default()
+            },
+            shutdown_signal,
+            thread: Some(thread),
+            dir,
+        }
+    }
+
+    /// Configuration of clients connecting to the server.
+    pub fn client_config(&self) -> ClientConfig {
+        self.client_config.clone()
+    }
+
+    /// Connects a new client to the server.
+    pub fn connect(&self) -> Result<Client, FairyError> {
+        Client::connect(self.client_config())
+    }
+
+    /// Directory of the databases of the server.
+    pub fn db_path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::Release);
+        // The server only sees the signal when it accepts a connection.
+        let addr = format!("{}:{}", self.client_config.host, self.client_config.port);
+        let _ = TcpStream::connect(addr);
+        if let Some(thread) = self.thread.take() {
+            // A server that panicked has nothing left to shut down.
+            let _ = thread.join();
+        }
+    }
+}